@@ -0,0 +1,199 @@
+// ABOUTME: `affected` report entries: files referencing a symbol, whether they are tests or use it in public API
+// ABOUTME: Filters (--tests-only, --public-only) and --group-by dir|language aggregation of the report
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::symbols::{extract_symbols, is_public_symbol, SupportedLanguage, Symbol, SymbolKind};
+use crate::testrun::mentions_word;
+
+/// A file that references the analyzed symbol
+#[derive(Debug, Clone, Serialize)]
+pub struct AffectedFile {
+    pub path: String,
+    pub language: String,
+    pub occurrences: usize,
+    pub is_definition: bool,
+    pub is_test: bool,
+    /// Public symbols of this file whose bodies use the analyzed symbol, so
+    /// a breaking change reaches this file's own callers (with --public-only)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub public_symbols: Vec<String>,
+}
+
+/// Public symbols (members qualified as `Class.method`) in `content` whose
+/// lines mention `symbol` as a word, not counting `symbol` itself
+pub fn public_users(content: &str, language: SupportedLanguage, symbol: &str) -> Vec<String> {
+    let lines: Vec<usize> = content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| mentions_word(line, symbol))
+        .map(|(i, _)| i + 1)
+        .collect();
+    if lines.is_empty() {
+        return Vec::new();
+    }
+    // Flattened, since some languages nest members and others list them
+    // after their class
+    let symbols = extract_symbols(content, language).unwrap_or_default();
+    let mut all = Vec::new();
+    flatten(&symbols, &mut all);
+    let matched: Vec<&Symbol> = all
+        .iter()
+        .copied()
+        .filter(|s| s.kind != SymbolKind::Import && s.name != symbol)
+        .filter(|s| {
+            lines
+                .iter()
+                .any(|l| (s.start_line..=s.end_line).contains(l))
+        })
+        .collect();
+    let within = |inner: &Symbol, outer: &Symbol| {
+        !std::ptr::eq(inner, outer)
+            && outer.start_line <= inner.start_line
+            && inner.end_line <= outer.end_line
+    };
+    matched
+        .iter()
+        .filter(|s| is_public_symbol(s, language))
+        // A class that uses it through a method is reported as the method
+        .filter(|s| !matched.iter().any(|inner| within(inner, s)))
+        .map(|s| {
+            let parents: Vec<&str> = all
+                .iter()
+                .filter(|outer| within(s, outer) && outer.kind != SymbolKind::Module)
+                .map(|outer| outer.name.as_str())
+                .collect();
+            if parents.is_empty() {
+                s.name.clone()
+            } else {
+                format!("{}.{}", parents.join("."), s.name)
+            }
+        })
+        .collect()
+}
+
+fn flatten<'a>(symbols: &'a [Symbol], out: &mut Vec<&'a Symbol>) {
+    for symbol in symbols {
+        out.push(symbol);
+        flatten(&symbol.children, out);
+    }
+}
+
+/// How `--group-by` aggregates the report
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    Dir,
+    Language,
+}
+
+impl GroupBy {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "dir" => Some(GroupBy::Dir),
+            "language" => Some(GroupBy::Language),
+            _ => None,
+        }
+    }
+
+    fn key(&self, file: &AffectedFile) -> String {
+        match self {
+            GroupBy::Dir => match file.path.rsplit_once('/') {
+                Some((dir, _)) => dir.to_string(),
+                None => ".".to_string(),
+            },
+            GroupBy::Language => file.language.clone(),
+        }
+    }
+}
+
+/// Affected files sharing a directory or language
+#[derive(Debug, Clone, Serialize)]
+pub struct FileGroup {
+    pub key: String,
+    pub files: usize,
+    pub occurrences: usize,
+    pub tests: usize,
+    pub paths: Vec<String>,
+}
+
+/// The files aggregated by `by`, most occurrences first
+pub fn group(files: &[AffectedFile], by: GroupBy) -> Vec<FileGroup> {
+    let mut groups: BTreeMap<String, FileGroup> = BTreeMap::new();
+    for file in files {
+        let key = by.key(file);
+        let group = groups.entry(key.clone()).or_insert_with(|| FileGroup {
+            key,
+            files: 0,
+            occurrences: 0,
+            tests: 0,
+            paths: Vec::new(),
+        });
+        group.files += 1;
+        group.occurrences += file.occurrences;
+        group.tests += usize::from(file.is_test);
+        group.paths.push(file.path.clone());
+    }
+    let mut groups: Vec<FileGroup> = groups.into_values().collect();
+    groups.sort_by_key(|g| std::cmp::Reverse(g.occurrences));
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, language: &str, occurrences: usize, is_test: bool) -> AffectedFile {
+        AffectedFile {
+            path: path.into(),
+            language: language.into(),
+            occurrences,
+            is_definition: false,
+            is_test,
+            public_symbols: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn finds_public_symbols_that_use_a_symbol() {
+        let source = "\
+def process(x):
+    return x
+
+def handle(req):
+    return process(req)
+
+def _helper():
+    return process(1)
+
+class Api:
+    def run(self):
+        return process(2)
+";
+        assert_eq!(
+            public_users(source, SupportedLanguage::Python, "process"),
+            vec!["handle", "Api.run"]
+        );
+        assert!(public_users(source, SupportedLanguage::Python, "missing").is_empty());
+    }
+
+    #[test]
+    fn groups_files_by_dir_and_language() {
+        let files = vec![
+            file("src/api/a.py", "Python", 2, false),
+            file("src/api/b.py", "Python", 1, false),
+            file("tests/test_a.py", "Python", 5, true),
+            file("main.rs", "Rust", 1, false),
+        ];
+        let by_dir = group(&files, GroupBy::Dir);
+        let keys: Vec<&str> = by_dir.iter().map(|g| g.key.as_str()).collect();
+        assert_eq!(keys, ["tests", "src/api", "."]);
+        assert_eq!(by_dir[1].files, 2);
+        assert_eq!(by_dir[0].tests, 1);
+
+        let by_language = group(&files, GroupBy::Language);
+        assert_eq!(by_language[0].key, "Python");
+        assert_eq!(by_language[0].occurrences, 8);
+        assert_eq!(GroupBy::from_name("language"), Some(GroupBy::Language));
+    }
+}
@@ -0,0 +1,81 @@
+// ABOUTME: Local-state directories under .agent/ (audit, approvals, caches, ...): created git-ignored
+// ABOUTME: and written as JSON files or JSON lines, so every store saves the same way
+
+use serde::Serialize;
+use std::io::Write;
+use std::path::Path;
+
+use crate::error::{Error, Result};
+
+/// Create `dir` with a `.gitignore` of `*` if it does not exist yet: local
+/// state stays out of snapshots even without `agentjj init`
+pub fn ensure_private(dir: &Path) -> std::io::Result<()> {
+    if !dir.exists() {
+        std::fs::create_dir_all(dir)?;
+        std::fs::write(dir.join(".gitignore"), "*\n")?;
+    }
+    Ok(())
+}
+
+/// Write `value` as pretty JSON to `path` in a private directory. Written
+/// then renamed, so a concurrent reader never sees half a record.
+pub fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    let dir = path.parent().expect("store path has a directory");
+    let _critical = crate::deadline::critical();
+    ensure_private(dir)?;
+    let content = serde_json::to_string_pretty(value).map_err(|e| Error::Io {
+        message: e.to_string(),
+    })?;
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = dir.join(format!("{}.{}.tmp", name, std::process::id()));
+    std::fs::write(&tmp, content)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Append `value` as one JSON line to `path` in a private directory
+pub fn append_json_line<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    let _critical = crate::deadline::critical();
+    ensure_private(path.parent().expect("log path has a directory"))?;
+    let line = serde_json::to_string(value).map_err(|e| Error::Io {
+        message: e.to_string(),
+    })?;
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{}", line)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stores_are_created_ignored_and_written_whole() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join(".agent/notes");
+
+        write_json(&dir.join("a.json"), &serde_json::json!({"id": "a"})).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.join(".gitignore")).unwrap(),
+            "*\n"
+        );
+        let saved: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(dir.join("a.json")).unwrap()).unwrap();
+        assert_eq!(saved["id"], "a");
+
+        let log = tmp.path().join(".agent/audit/default.jsonl");
+        append_json_line(&log, &1).unwrap();
+        append_json_line(&log, &2).unwrap();
+        assert_eq!(std::fs::read_to_string(&log).unwrap(), "1\n2\n");
+
+        // Only what was written: no temporary files left behind
+        let names: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(names.len(), 2);
+    }
+}
@@ -0,0 +1,100 @@
+// ABOUTME: .agentignore support: gitignore-syntax paths hidden from agent scans but still tracked
+// ABOUTME: Honored by files, orient, stats, bulk symbols, affected, dupes, and todos; --no-agentignore disables
+
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use jj_lib::gitignore::GitIgnoreFile;
+
+use crate::error::{Error, Result};
+
+/// Set by `--no-agentignore`: every scan sees the full tree
+static DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Ignore `.agentignore` for the rest of the process
+pub fn disable() {
+    DISABLED.store(true, Ordering::Relaxed);
+}
+
+/// Rules from the repository's `.agentignore`
+#[derive(Clone)]
+pub struct AgentIgnore {
+    rules: Option<Arc<GitIgnoreFile>>,
+}
+
+impl AgentIgnore {
+    pub const FILE: &'static str = ".agentignore";
+
+    /// Rules from `<root>/.agentignore`; none when the file is missing or
+    /// `--no-agentignore` was given
+    pub fn load(root: impl AsRef<Path>) -> Result<Self> {
+        let path = root.as_ref().join(Self::FILE);
+        if DISABLED.load(Ordering::Relaxed) || !path.is_file() {
+            return Ok(Self { rules: None });
+        }
+        let content = std::fs::read(&path)?;
+        Self::parse(&content)
+    }
+
+    /// Rules from `.agentignore` content
+    pub fn parse(content: &[u8]) -> Result<Self> {
+        let rules = GitIgnoreFile::empty()
+            .chain("", Path::new(Self::FILE), content)
+            .map_err(|e| Error::Config {
+                message: format!("{}: {}", Self::FILE, e),
+            })?;
+        Ok(Self { rules: Some(rules) })
+    }
+
+    /// Whether any rules apply
+    pub fn is_active(&self) -> bool {
+        self.rules.is_some()
+    }
+
+    /// Whether a slash-separated path relative to the root is hidden,
+    /// directly or through one of its parent directories
+    pub fn is_ignored(&self, rel_path: &str) -> bool {
+        self.rules.as_ref().is_some_and(|r| r.matches(rel_path))
+    }
+
+    /// `is_ignored` for a path under `root`; paths outside it never are
+    pub fn is_ignored_under(&self, root: &Path, path: &Path) -> bool {
+        if self.rules.is_none() {
+            return false;
+        }
+        path.strip_prefix(root).is_ok_and(|rel| {
+            let rel: Vec<_> = rel
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect();
+            self.is_ignored(&rel.join("/"))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uses_gitignore_syntax() {
+        let ignore = AgentIgnore::parse(
+            b"# generated and vendored code\nvendor/\n/fixtures\n*.pb.rs\n!keep.pb.rs\n",
+        )
+        .unwrap();
+
+        assert!(ignore.is_ignored("vendor/lib/a.rs"));
+        assert!(ignore.is_ignored("crates/x/vendor/b.rs"));
+        assert!(ignore.is_ignored("fixtures/big.json"));
+        assert!(!ignore.is_ignored("tests/fixtures/small.json"));
+        assert!(ignore.is_ignored("src/api.pb.rs"));
+        assert!(!ignore.is_ignored("src/keep.pb.rs"));
+        assert!(!ignore.is_ignored("src/main.rs"));
+
+        let root = Path::new("/repo");
+        assert!(ignore.is_ignored_under(root, Path::new("/repo/vendor/a.rs")));
+        assert!(!ignore.is_ignored_under(root, Path::new("/elsewhere/vendor/a.rs")));
+        assert!(!AgentIgnore { rules: None }.is_ignored("vendor/a.rs"));
+    }
+}
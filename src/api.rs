@@ -0,0 +1,147 @@
+// ABOUTME: Public API surface of a source file and the difference between two versions of it
+// ABOUTME: Flags removed symbols and changed signatures as breaking, for verifying change metadata
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::symbols::{extract_symbols, is_public_symbol, SupportedLanguage, Symbol, SymbolKind};
+
+/// How a public symbol changed between two versions of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiChangeKind {
+    Added,
+    Removed,
+    SignatureChanged,
+}
+
+/// One change to a file's public API
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ApiChange {
+    pub file: String,
+    /// Symbol name; members are qualified with their parent (`Class.method`)
+    pub symbol: String,
+    pub kind: SymbolKind,
+    pub change: ApiChangeKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+}
+
+impl ApiChange {
+    /// Whether existing callers may stop compiling or working
+    pub fn is_breaking(&self) -> bool {
+        self.change != ApiChangeKind::Added
+    }
+}
+
+/// Public symbols of a file keyed by qualified name, with their kind and signature
+pub fn public_surface(
+    source: &str,
+    language: SupportedLanguage,
+) -> BTreeMap<String, (SymbolKind, Option<String>)> {
+    let mut surface = BTreeMap::new();
+    let symbols = extract_symbols(source, language).unwrap_or_default();
+    collect_public(&symbols, None, language, &mut surface);
+    surface
+}
+
+fn collect_public(
+    symbols: &[Symbol],
+    parent: Option<&str>,
+    language: SupportedLanguage,
+    surface: &mut BTreeMap<String, (SymbolKind, Option<String>)>,
+) {
+    for symbol in symbols {
+        if symbol.kind == SymbolKind::Import || !is_public_symbol(symbol, language) {
+            continue;
+        }
+        let name = match parent {
+            Some(parent) => format!("{}.{}", parent, symbol.name),
+            None => symbol.name.clone(),
+        };
+        collect_public(&symbol.children, Some(&name), language, surface);
+        surface.insert(name, (symbol.kind, symbol.signature.clone()));
+    }
+}
+
+/// Public API changes between two versions of `file` (None = file absent)
+pub fn diff_api(
+    file: &str,
+    before: Option<&str>,
+    after: Option<&str>,
+    language: SupportedLanguage,
+) -> Vec<ApiChange> {
+    let before = before
+        .map(|s| public_surface(s, language))
+        .unwrap_or_default();
+    let after = after
+        .map(|s| public_surface(s, language))
+        .unwrap_or_default();
+
+    let mut changes = Vec::new();
+    for (name, (kind, signature)) in &before {
+        let change = match after.get(name) {
+            None => ApiChangeKind::Removed,
+            Some((_, new_signature)) if new_signature != signature => {
+                ApiChangeKind::SignatureChanged
+            }
+            Some(_) => continue,
+        };
+        changes.push(ApiChange {
+            file: file.to_string(),
+            symbol: name.clone(),
+            kind: *kind,
+            change,
+            before: signature.clone(),
+            after: after.get(name).and_then(|(_, s)| s.clone()),
+        });
+    }
+    for (name, (kind, signature)) in &after {
+        if !before.contains_key(name) {
+            changes.push(ApiChange {
+                file: file.to_string(),
+                symbol: name.clone(),
+                kind: *kind,
+                change: ApiChangeKind::Added,
+                before: None,
+                after: signature.clone(),
+            });
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_api_classifies_changes() {
+        let before = "pub fn keep() {}\npub fn gone() {}\npub fn sig(a: u32) {}\nfn private() {}\n";
+        let after = "pub fn keep() {}\npub fn sig(a: u64) {}\npub fn new_one() {}\n";
+
+        let changes = diff_api("lib.rs", Some(before), Some(after), SupportedLanguage::Rust);
+        let by_name: BTreeMap<&str, ApiChangeKind> = changes
+            .iter()
+            .map(|c| (c.symbol.as_str(), c.change))
+            .collect();
+
+        assert_eq!(by_name.get("gone"), Some(&ApiChangeKind::Removed));
+        assert_eq!(by_name.get("sig"), Some(&ApiChangeKind::SignatureChanged));
+        assert_eq!(by_name.get("new_one"), Some(&ApiChangeKind::Added));
+        assert!(!by_name.contains_key("keep"));
+        assert!(!by_name.contains_key("private"));
+        assert_eq!(changes.iter().filter(|c| c.is_breaking()).count(), 2);
+    }
+
+    #[test]
+    fn deleted_file_removes_its_whole_surface() {
+        let before = "def handler():\n    pass\n\ndef _helper():\n    pass\n";
+        let changes = diff_api("api.py", Some(before), None, SupportedLanguage::Python);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].symbol, "handler");
+        assert!(changes[0].is_breaking());
+    }
+}
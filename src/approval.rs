@@ -0,0 +1,398 @@
+// ABOUTME: Pending approvals for intents that touch `ask_change` paths (.agent/approvals/<id>.json)
+// ABOUTME: A human approves or denies each one (checked by `Decider`); approved intents are resumed by ID
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+use crate::intent::Intent;
+
+/// Where an approval is in its lifecycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApprovalStatus {
+    /// Waiting for a human
+    Pending,
+    /// Approved; can be resumed
+    Approved,
+    /// Rejected; will never be applied
+    Denied,
+    /// Approved and resumed
+    Applied,
+}
+
+impl ApprovalStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApprovalStatus::Pending => "pending",
+            ApprovalStatus::Approved => "approved",
+            ApprovalStatus::Denied => "denied",
+            ApprovalStatus::Applied => "applied",
+        }
+    }
+}
+
+/// An intent parked until a human decides on it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Approval {
+    pub id: String,
+    pub status: ApprovalStatus,
+    /// The intent exactly as it was submitted
+    pub intent: Intent,
+    /// The `ask_change` paths that triggered the approval
+    pub paths: Vec<String>,
+    /// Session that submitted the intent
+    pub requested_by: String,
+    pub requested_at: String,
+    /// Terminal the submitting process ran on, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requested_tty: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decided_by: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decided_at: Option<String>,
+    /// The process that decided
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decided_from: Option<Decider>,
+    /// Why it was denied (or any note left when approving)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// The change created when the approved intent was applied
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub change_id: Option<String>,
+}
+
+impl Approval {
+    /// A new pending approval, with an ID derived from its contents
+    pub fn new(
+        intent: Intent,
+        paths: Vec<String>,
+        requested_by: impl Into<String>,
+        requested_at: impl Into<String>,
+    ) -> Self {
+        let requested_by = requested_by.into();
+        let requested_at = requested_at.into();
+
+        let mut hasher = Sha256::new();
+        hasher.update(serde_json::to_vec(&intent).unwrap_or_default());
+        hasher.update(requested_by.as_bytes());
+        hasher.update(requested_at.as_bytes());
+        hasher.update(std::process::id().to_le_bytes());
+        let id = hex::encode(hasher.finalize())[..12].to_string();
+
+        Self {
+            id,
+            status: ApprovalStatus::Pending,
+            intent,
+            paths,
+            requested_by,
+            requested_at,
+            requested_tty: current_tty(),
+            decided_by: None,
+            decided_at: None,
+            decided_from: None,
+            reason: None,
+            change_id: None,
+        }
+    }
+
+    /// Record a human decision on a pending approval
+    pub fn decide(
+        &mut self,
+        approve: bool,
+        by: impl Into<String>,
+        at: impl Into<String>,
+        reason: Option<String>,
+    ) -> Result<()> {
+        self.expect_status(ApprovalStatus::Pending)?;
+        self.status = if approve {
+            ApprovalStatus::Approved
+        } else {
+            ApprovalStatus::Denied
+        };
+        self.decided_by = Some(by.into());
+        self.decided_at = Some(at.into());
+        self.reason = reason;
+        Ok(())
+    }
+
+    /// Fail unless the approval is in `expected` state
+    pub fn expect_status(&self, expected: ApprovalStatus) -> Result<()> {
+        if self.status != expected {
+            return Err(Error::PreconditionFailed {
+                reason: format!("approval {} is {}", self.id, self.status.as_str()),
+                expected: expected.as_str().to_string(),
+                actual: self.status.as_str().to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// The process deciding on an approval or confirmation, kept with the
+/// decision
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Decider {
+    /// `AGENTJJ_SESSION` of the deciding process ("default" when unset)
+    pub session: String,
+    pub pid: u32,
+    /// Terminal on its stdin; absent when stdin is not a terminal
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tty: Option<String>,
+}
+
+impl Decider {
+    pub fn current() -> Self {
+        Self {
+            session: crate::audit::current_session(),
+            pid: std::process::id(),
+            tty: current_tty(),
+        }
+    }
+
+    /// Refuse an approval that does not come from a human. Off a terminal,
+    /// `by` must be one of the manifest's `[review] approvers` and the
+    /// deciding session another than the requester's (the default session
+    /// included); on one, neither the session nor the terminal may be the
+    /// requester's.
+    pub fn check_approval(
+        &self,
+        id: &str,
+        by: Option<&str>,
+        approvers: &[String],
+        requested_by: &str,
+        requested_tty: Option<&str>,
+    ) -> Result<()> {
+        let refuse = |reason: &str| {
+            Err(Error::HumanDecisionRequired {
+                id: id.to_string(),
+                reason: reason.to_string(),
+            })
+        };
+        if self.tty.is_none() {
+            if !by.is_some_and(|by| approvers.iter().any(|a| a == by)) {
+                return refuse(
+                    "approving off a terminal needs --by naming one of the manifest's [review] approvers",
+                );
+            }
+            if self.session == requested_by || by == Some(requested_by) {
+                return refuse("the session that was blocked cannot approve itself");
+            }
+        }
+        if self.session == requested_by && self.session != "default" {
+            return refuse("the session that was blocked cannot approve itself");
+        }
+        if self.tty.is_some() && self.tty.as_deref() == requested_tty {
+            return refuse("approve from another terminal than the one that was blocked");
+        }
+        Ok(())
+    }
+}
+
+/// The terminal stdin is attached to (`/dev/pts/3`); None when stdin is
+/// not a terminal
+pub fn current_tty() -> Option<String> {
+    use std::io::IsTerminal;
+    if !std::io::stdin().is_terminal() {
+        return None;
+    }
+    std::fs::read_link("/proc/self/fd/0")
+        .ok()
+        .map(|path| path.display().to_string())
+        .or_else(|| {
+            let output = std::process::Command::new("tty")
+                .stdin(std::process::Stdio::inherit())
+                .output()
+                .ok()?;
+            let name = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            (output.status.success() && !name.is_empty()).then_some(name)
+        })
+        .or_else(|| Some("tty".to_string()))
+}
+
+/// The approvals directory of a repository
+pub struct ApprovalStore {
+    dir: PathBuf,
+}
+
+impl ApprovalStore {
+    pub const DEFAULT_DIR: &'static str = ".agent/approvals";
+
+    pub fn open(repo_root: impl AsRef<Path>) -> Self {
+        Self {
+            dir: repo_root.as_ref().join(Self::DEFAULT_DIR),
+        }
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+
+    /// Write (or overwrite) an approval
+    pub fn save(&self, approval: &Approval) -> Result<()> {
+        crate::agent_dir::write_json(&self.path(&approval.id), approval)
+    }
+
+    /// Load an approval by ID
+    pub fn load(&self, id: &str) -> Result<Approval> {
+        let valid = !id.is_empty() && id.chars().all(|c| c.is_ascii_hexdigit());
+        let content = valid
+            .then(|| std::fs::read_to_string(self.path(id)).ok())
+            .flatten()
+            .ok_or_else(|| Error::ApprovalNotFound {
+                approval_id: id.to_string(),
+            })?;
+        serde_json::from_str(&content).map_err(|e| Error::Io {
+            message: format!("corrupt approval {}: {}", id, e),
+        })
+    }
+
+    /// All approvals, oldest request first
+    pub fn list(&self) -> Result<Vec<Approval>> {
+        let mut approvals = Vec::new();
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return Ok(approvals);
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|x| x.to_str()) != Some("json") {
+                continue;
+            }
+            if let Some(id) = path.file_stem().and_then(|s| s.to_str()) {
+                approvals.push(self.load(id)?);
+            }
+        }
+        approvals.sort_by(|a, b| {
+            a.requested_at
+                .cmp(&b.requested_at)
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        Ok(approvals)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::change::ChangeType;
+    use crate::intent::ChangeSpec;
+
+    fn intent() -> Intent {
+        Intent::new(
+            "Bump timeout",
+            ChangeType::Config,
+            ChangeSpec::Patch {
+                content: "--- a/config/app.toml\n+++ b/config/app.toml\n".into(),
+            },
+        )
+    }
+
+    #[test]
+    fn approvals_round_trip_and_decide_once() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = ApprovalStore::open(tmp.path());
+
+        let mut approval = Approval::new(
+            intent(),
+            vec!["config/app.toml".into()],
+            "agent-1",
+            "2026-01-02T03:04:05Z",
+        );
+        assert_eq!(approval.id.len(), 12);
+        store.save(&approval).unwrap();
+
+        approval
+            .decide(true, "alice", "2026-01-02T04:00:00Z", None)
+            .unwrap();
+        store.save(&approval).unwrap();
+
+        let loaded = store.load(&approval.id).unwrap();
+        assert_eq!(loaded.status, ApprovalStatus::Approved);
+        assert_eq!(loaded.decided_by.as_deref(), Some("alice"));
+        assert_eq!(store.list().unwrap().len(), 1);
+
+        // A decision is final
+        let mut again = loaded;
+        assert!(again.decide(false, "bob", "later", None).is_err());
+    }
+
+    #[test]
+    fn approvals_need_a_human_other_than_the_requester() {
+        let on_tty = |session: &str| Decider {
+            session: session.into(),
+            pid: 1,
+            tty: Some("/dev/pts/1".into()),
+        };
+        let refused = |r: Result<()>| matches!(r, Err(Error::HumanDecisionRequired { .. }));
+
+        let headless = |session: &str| Decider {
+            tty: None,
+            ..on_tty(session)
+        };
+        let approvers = vec!["alice".to_string()];
+        // Off a terminal: a configured approver, from another session
+        assert!(refused(
+            headless("reviewer").check_approval("a1", None, &approvers, "agent-1", None)
+        ));
+        assert!(refused(headless("reviewer").check_approval(
+            "a1",
+            Some("mallory"),
+            &approvers,
+            "agent-1",
+            None
+        )));
+        assert!(refused(headless("reviewer").check_approval(
+            "a1",
+            Some("alice"),
+            &[],
+            "agent-1",
+            None
+        )));
+        // The default session approving what the default session requested
+        assert!(refused(headless("default").check_approval(
+            "a1",
+            Some("alice"),
+            &approvers,
+            "default",
+            None
+        )));
+        assert!(headless("reviewer")
+            .check_approval("a1", Some("alice"), &approvers, "default", None)
+            .is_ok());
+
+        // On a terminal: not the requesting session or terminal
+        assert!(refused(on_tty("agent-1").check_approval(
+            "a1",
+            Some("alice"),
+            &[],
+            "agent-1",
+            None
+        )));
+        assert!(refused(on_tty("default").check_approval(
+            "a1",
+            None,
+            &[],
+            "agent-1",
+            Some("/dev/pts/1")
+        )));
+        assert!(on_tty("default")
+            .check_approval("a1", None, &[], "agent-1", Some("/dev/pts/2"))
+            .is_ok());
+    }
+
+    #[test]
+    fn unknown_approval_is_not_found() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = ApprovalStore::open(tmp.path());
+        assert!(matches!(
+            store.load("abc123"),
+            Err(Error::ApprovalNotFound { .. })
+        ));
+        assert!(matches!(
+            store.load("../etc"),
+            Err(Error::ApprovalNotFound { .. })
+        ));
+        assert!(store.list().unwrap().is_empty());
+    }
+}
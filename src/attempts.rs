@@ -0,0 +1,283 @@
+// ABOUTME: Attempt history for intents applied with `apply` (.agent/intents/<id>.json)
+// ABOUTME: Each retry (`--attempt-of`) is recorded with its outcome so loops and the [intents] limits can be enforced
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+use crate::intent::IntentResult;
+use crate::manifest::IntentsConfig;
+use crate::repo::parse_utc_timestamp;
+
+/// Failed attempts in a row, ending the same way, that count as a loop
+pub const LOOP_THRESHOLD: usize = 3;
+
+/// One run of an intent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attempt {
+    /// 1 for the first attempt
+    pub number: usize,
+    pub session: String,
+    pub started_at: String,
+    pub duration_ms: u64,
+    /// The `apply` status (`success`, `conflict`, `invariant_failed`, ...), or
+    /// the error type when it failed before producing one
+    pub status: String,
+    /// What went wrong, in a line
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub change_id: Option<String>,
+}
+
+impl Attempt {
+    pub fn succeeded(&self) -> bool {
+        self.status == "success"
+    }
+
+    /// Status and reason of an `apply` result
+    pub fn outcome(result: &IntentResult) -> (String, Option<String>) {
+        let status = serde_json::to_value(result)
+            .ok()
+            .and_then(|v| v["status"].as_str().map(str::to_string))
+            .unwrap_or_else(|| "unknown".to_string());
+        let reason = match result {
+            IntentResult::Success { .. } => None,
+            IntentResult::PreconditionFailed { reason, .. } => Some(reason.clone()),
+            IntentResult::StaleEdit { drifted } => Some(format!(
+                "stale: {}",
+                drifted
+                    .iter()
+                    .map(|f| f.path.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+            IntentResult::Conflict { conflicts, .. } => Some(format!(
+                "conflicts in {}",
+                conflicts
+                    .iter()
+                    .map(|c| c.file.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+            IntentResult::InvariantFailed {
+                invariant,
+                exit_code,
+                ..
+            } => Some(format!("invariant {} exited {}", invariant, exit_code)),
+            IntentResult::PermissionDenied { action, path, .. } => {
+                Some(format!("{} denied on {}", action, path))
+            }
+            IntentResult::RequiresReview { paths, .. } => {
+                Some(format!("review required for {}", paths.join(", ")))
+            }
+            IntentResult::PendingApproval { approval_id, .. } => {
+                Some(format!("waiting for approval {}", approval_id))
+            }
+        };
+        (status, reason)
+    }
+}
+
+/// An intent and every attempt at it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IntentAttempts {
+    pub id: String,
+    /// The description of the first attempt
+    pub description: String,
+    pub attempts: Vec<Attempt>,
+}
+
+impl IntentAttempts {
+    /// A new intent with no attempts, with an ID derived from its first run
+    pub fn new(description: impl Into<String>, session: &str, started_at: &str) -> Self {
+        let description = description.into();
+        let mut hasher = Sha256::new();
+        hasher.update(description.as_bytes());
+        hasher.update(session.as_bytes());
+        hasher.update(started_at.as_bytes());
+        hasher.update(std::process::id().to_le_bytes());
+        Self {
+            id: hex::encode(hasher.finalize())[..12].to_string(),
+            description,
+            attempts: Vec::new(),
+        }
+    }
+
+    /// Whether any attempt applied the intent
+    pub fn succeeded(&self) -> bool {
+        self.attempts.iter().any(Attempt::succeeded)
+    }
+
+    /// Failed attempts at the end of the history that all ended the same way
+    /// (status and reason): a planner retrying without changing anything
+    pub fn repeated_failures(&self) -> usize {
+        let Some(last) = self.attempts.last().filter(|a| !a.succeeded()) else {
+            return 0;
+        };
+        self.attempts
+            .iter()
+            .rev()
+            .take_while(|a| a.status == last.status && a.reason == last.reason)
+            .count()
+    }
+
+    pub fn looping(&self) -> bool {
+        self.repeated_failures() >= LOOP_THRESHOLD
+    }
+
+    /// Fail with `TooManyAttempts` when another attempt at `now` would exceed
+    /// the manifest's limits
+    pub fn check_limits(&self, limits: &IntentsConfig, now: &str) -> Result<()> {
+        let elapsed_minutes = self
+            .attempts
+            .first()
+            .and_then(|first| {
+                let start = parse_utc_timestamp(&first.started_at)?;
+                let now = parse_utc_timestamp(now)?;
+                Some((now - start).max(0) as u64 / 60)
+            })
+            .unwrap_or(0);
+        let over_count = limits
+            .max_attempts
+            .is_some_and(|max| self.attempts.len() >= max);
+        let over_time = !self.attempts.is_empty()
+            && limits.max_minutes.is_some_and(|max| elapsed_minutes >= max);
+        if over_count || over_time {
+            return Err(Error::TooManyAttempts {
+                intent_id: self.id.clone(),
+                attempts: self.attempts.len(),
+                max_attempts: limits.max_attempts,
+                elapsed_minutes,
+                max_minutes: limits.max_minutes,
+                last_status: self.attempts.last().map(|a| a.status.clone()),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// The intents directory of a repository
+pub struct IntentStore {
+    dir: PathBuf,
+}
+
+impl IntentStore {
+    pub const DEFAULT_DIR: &'static str = ".agent/intents";
+
+    pub fn open(repo_root: impl AsRef<Path>) -> Self {
+        Self {
+            dir: repo_root.as_ref().join(Self::DEFAULT_DIR),
+        }
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+
+    /// Write (or overwrite) an intent's attempts
+    pub fn save(&self, intent: &IntentAttempts) -> Result<()> {
+        crate::agent_dir::write_json(&self.path(&intent.id), intent)
+    }
+
+    /// Load an intent by ID
+    pub fn load(&self, id: &str) -> Result<IntentAttempts> {
+        let valid = !id.is_empty() && id.chars().all(|c| c.is_ascii_hexdigit());
+        let content = valid
+            .then(|| std::fs::read_to_string(self.path(id)).ok())
+            .flatten()
+            .ok_or_else(|| Error::IntentNotFound {
+                intent_id: id.to_string(),
+            })?;
+        serde_json::from_str(&content).map_err(|e| Error::Io {
+            message: format!("corrupt intent {}: {}", id, e),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn attempt(number: usize, started_at: &str, status: &str, reason: Option<&str>) -> Attempt {
+        Attempt {
+            number,
+            session: "s1".into(),
+            started_at: started_at.into(),
+            duration_ms: 10,
+            status: status.into(),
+            reason: reason.map(str::to_string),
+            change_id: None,
+        }
+    }
+
+    #[test]
+    fn detects_loops_and_enforces_limits() {
+        let tmp = TempDir::new().unwrap();
+        let store = IntentStore::open(tmp.path());
+        let mut intent = IntentAttempts::new("fix login", "s1", "2026-01-01T10:00:00Z");
+        assert_eq!(intent.id.len(), 12);
+        let limits = IntentsConfig {
+            max_attempts: Some(4),
+            max_minutes: Some(30),
+        };
+        // No attempts yet: never over the time box
+        intent
+            .check_limits(&limits, "2027-01-01T00:00:00Z")
+            .unwrap();
+
+        intent.attempts.push(attempt(
+            1,
+            "2026-01-01T10:00:00Z",
+            "precondition_failed",
+            Some("main moved"),
+        ));
+        for n in 2..=3 {
+            intent.attempts.push(attempt(
+                n,
+                "2026-01-01T10:05:00Z",
+                "invariant_failed",
+                Some("invariant tests exited 1"),
+            ));
+        }
+        assert_eq!(intent.repeated_failures(), 2);
+        assert!(!intent.looping());
+        intent
+            .check_limits(&limits, "2026-01-01T10:29:59Z")
+            .unwrap();
+        let err = intent
+            .check_limits(&limits, "2026-01-01T10:30:00Z")
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::TooManyAttempts {
+                elapsed_minutes: 30,
+                ..
+            }
+        ));
+
+        intent.attempts.push(attempt(
+            4,
+            "2026-01-01T10:06:00Z",
+            "invariant_failed",
+            Some("invariant tests exited 1"),
+        ));
+        assert!(intent.looping());
+        let err = intent
+            .check_limits(&limits, "2026-01-01T10:07:00Z")
+            .unwrap_err();
+        assert!(err.to_string().contains("4 attempts (max_attempts = 4)"));
+        assert_eq!(err.exit_code(), crate::error::exit::POLICY);
+
+        store.save(&intent).unwrap();
+        let loaded = store.load(&intent.id).unwrap();
+        assert_eq!(loaded.attempts.len(), 4);
+        assert!(!loaded.succeeded());
+        assert!(matches!(
+            store.load("../etc"),
+            Err(Error::IntentNotFound { .. })
+        ));
+    }
+}
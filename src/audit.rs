@@ -0,0 +1,293 @@
+// ABOUTME: Per-session audit log of state-changing agentjj commands (.agent/audit/<session>.jsonl)
+// ABOUTME: Also converts a session's records into a replay script that can re-run them elsewhere
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::error::{Error, Result};
+
+/// Inputs a command attaches to its own audit record (e.g. a patch body),
+/// picked up by the CLI when it writes the record for this invocation.
+static PENDING_INPUTS: Mutex<Option<serde_json::Value>> = Mutex::new(None);
+
+/// Env var naming the session that records are grouped under
+pub const SESSION_ENV: &str = "AGENTJJ_SESSION";
+
+/// Whether a command acts on a remote rather than the local repository;
+/// such commands are never exported for replay
+fn acts_on_remote(record: &AuditRecord) -> bool {
+    record.command == "push" || record.args.iter().any(|a| a == "--push")
+}
+
+/// One state-changing command invocation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub session: String,
+    pub timestamp: String,
+    /// Command name (e.g. "commit", "change set")
+    pub command: String,
+    /// Arguments after the program name, without output flags
+    pub args: Vec<String>,
+    pub success: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Content needed to reproduce the command elsewhere
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inputs: Option<serde_json::Value>,
+}
+
+/// A replayable sequence of commands exported from one session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayScript {
+    pub version: u32,
+    pub session: String,
+    pub steps: Vec<ReplayStep>,
+    /// Successful commands left out of the script, with the reason
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<SkippedStep>,
+}
+
+impl ReplayScript {
+    /// Files the steps would write or delete whose paths are not plain
+    /// relative paths (`../x`, `/etc/x`), so they could land outside the
+    /// repository replaying them
+    pub fn unsafe_paths(&self) -> Vec<String> {
+        self.steps
+            .iter()
+            .filter_map(|step| step.inputs.as_ref()?["files"].as_object())
+            .flat_map(|files| files.keys())
+            .filter(|path| {
+                !Path::new(path)
+                    .components()
+                    .all(|c| matches!(c, std::path::Component::Normal(_)))
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayStep {
+    pub command: String,
+    pub args: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inputs: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkippedStep {
+    pub command: String,
+    pub reason: String,
+}
+
+/// Attach inputs to the audit record of the command currently running
+pub fn attach_inputs(inputs: serde_json::Value) {
+    if let Ok(mut pending) = PENDING_INPUTS.lock() {
+        *pending = Some(inputs);
+    }
+}
+
+/// Take inputs attached by the command currently running, if any
+pub fn take_inputs() -> Option<serde_json::Value> {
+    PENDING_INPUTS.lock().ok().and_then(|mut p| p.take())
+}
+
+/// Session id from `AGENTJJ_SESSION`, or "default"
+pub fn current_session() -> String {
+    std::env::var(SESSION_ENV)
+        .ok()
+        .filter(|s| !s.trim().is_empty())
+        .unwrap_or_else(|| "default".to_string())
+}
+
+/// Who is acting: the `AGENTJJ_SESSION` id, else $USER, else "unknown"
+pub fn current_actor() -> String {
+    std::env::var(SESSION_ENV)
+        .ok()
+        .filter(|s| !s.trim().is_empty())
+        .or_else(|| std::env::var("USER").ok())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// The audit log directory of a repository
+pub struct AuditLog {
+    dir: PathBuf,
+}
+
+impl AuditLog {
+    pub const DEFAULT_DIR: &'static str = ".agent/audit";
+
+    pub fn open(repo_root: impl AsRef<Path>) -> Self {
+        Self {
+            dir: repo_root.as_ref().join(Self::DEFAULT_DIR),
+        }
+    }
+
+    fn session_path(&self, session: &str) -> PathBuf {
+        let safe: String = session
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        self.dir.join(format!("{}.jsonl", safe))
+    }
+
+    /// Append a record to its session's log
+    pub fn append(&self, record: &AuditRecord) -> Result<()> {
+        crate::agent_dir::append_json_line(&self.session_path(&record.session), record)
+    }
+
+    /// All records of a session, oldest first
+    pub fn session(&self, session: &str) -> Result<Vec<AuditRecord>> {
+        let path = self.session_path(session);
+        let content = std::fs::read_to_string(&path).map_err(|_| Error::Io {
+            message: format!("no audit records for session '{}'", session),
+        })?;
+        content
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| {
+                serde_json::from_str(l).map_err(|e| Error::Io {
+                    message: format!("corrupt audit record in {}: {}", path.display(), e),
+                })
+            })
+            .collect()
+    }
+
+    /// Names of all recorded sessions, sorted
+    pub fn sessions(&self) -> Vec<String> {
+        let mut sessions: Vec<String> = std::fs::read_dir(&self.dir)
+            .map(|dir| {
+                dir.flatten()
+                    .filter_map(|e| {
+                        let path = e.path();
+                        (path.extension().and_then(|x| x.to_str()) == Some("jsonl"))
+                            .then(|| path.file_stem()?.to_str().map(str::to_string))
+                            .flatten()
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        sessions.sort();
+        sessions
+    }
+}
+
+/// Convert a session's records into a replay script. Failed commands are
+/// dropped (they changed nothing); remote-facing ones and automatic storage
+/// pruning are listed as skipped.
+pub fn export_replay(session: &str, records: &[AuditRecord]) -> ReplayScript {
+    let mut steps = Vec::new();
+    let mut skipped = Vec::new();
+
+    for record in records.iter().filter(|r| r.success) {
+        if record.command == crate::storage::AUTO_PRUNE_COMMAND {
+            skipped.push(SkippedStep {
+                command: record.command.clone(),
+                reason: "automatic storage pruning".to_string(),
+            });
+            continue;
+        }
+        if acts_on_remote(record) {
+            skipped.push(SkippedStep {
+                command: record.command.clone(),
+                reason: "acts on a remote".to_string(),
+            });
+            continue;
+        }
+        steps.push(ReplayStep {
+            command: record.command.clone(),
+            args: record.args.clone(),
+            inputs: record.inputs.clone(),
+        });
+    }
+
+    ReplayScript {
+        version: 1,
+        session: session.to_string(),
+        steps,
+        skipped,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn record(command: &str, success: bool) -> AuditRecord {
+        AuditRecord {
+            session: "s1".into(),
+            timestamp: "2026-01-01T00:00:00Z".into(),
+            command: command.into(),
+            args: vec![command.into()],
+            success,
+            error: None,
+            inputs: None,
+        }
+    }
+
+    #[test]
+    fn append_and_read_session() {
+        let tmp = TempDir::new().unwrap();
+        let log = AuditLog::open(tmp.path());
+
+        log.append(&record("commit", true)).unwrap();
+        log.append(&record("undo", false)).unwrap();
+
+        let records = log.session("s1").unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].command, "commit");
+        assert!(!records[1].success);
+        assert_eq!(log.sessions(), vec!["s1"]);
+    }
+
+    #[test]
+    fn missing_session_is_an_error() {
+        let tmp = TempDir::new().unwrap();
+        assert!(AuditLog::open(tmp.path()).session("nope").is_err());
+    }
+
+    #[test]
+    fn export_drops_failures_and_skips_push() {
+        let records = vec![
+            record("checkpoint create", true),
+            record("commit", false),
+            record("commit", true),
+            record("push", true),
+            AuditRecord {
+                args: vec!["tag".into(), "v1".into(), "--push".into()],
+                ..record("tag", true)
+            },
+            record(crate::storage::AUTO_PRUNE_COMMAND, true),
+        ];
+
+        let script = export_replay("s1", &records);
+
+        let commands: Vec<_> = script.steps.iter().map(|s| s.command.as_str()).collect();
+        assert_eq!(commands, vec!["checkpoint create", "commit"]);
+        assert_eq!(script.skipped.len(), 3);
+        assert_eq!(script.skipped[0].command, "push");
+        assert_eq!(script.skipped[1].command, "tag");
+        assert_eq!(script.skipped[2].reason, "automatic storage pruning");
+    }
+
+    #[test]
+    fn replay_paths_must_stay_inside_the_repo() {
+        let mut script = export_replay("s1", &[record("commit", true)]);
+        script.steps[0].inputs = Some(serde_json::json!({"files": {
+            "src/ok.rs": {"content": ""},
+            "../outside.txt": {"content": ""},
+            "/etc/passwd": {"deleted": true},
+        }}));
+        let mut bad = script.unsafe_paths();
+        bad.sort();
+        assert_eq!(bad, vec!["../outside.txt", "/etc/passwd"]);
+    }
+}
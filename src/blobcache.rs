@@ -0,0 +1,295 @@
+// ABOUTME: Read-through cache for file contents at a revision, keyed by (tree hash, path)
+// ABOUTME: LRU in memory, optionally on disk under .agent/cache/blobs/, with hit/miss counts for `stats --usage`
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::config::CacheConfig;
+use crate::error::Result;
+
+/// Cumulative lookups, kept in `.agent/cache/stats.json`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheStats {
+    pub memory_hits: u64,
+    pub disk_hits: u64,
+    pub misses: u64,
+}
+
+impl CacheStats {
+    pub fn lookups(&self) -> u64 {
+        self.memory_hits + self.disk_hits + self.misses
+    }
+
+    /// Hits / lookups, 0 before any lookup
+    pub fn hit_rate(&self) -> f64 {
+        match self.lookups() {
+            0 => 0.0,
+            n => (self.memory_hits + self.disk_hits) as f64 / n as f64,
+        }
+    }
+}
+
+struct Entry {
+    bytes: Vec<u8>,
+    /// Lookup tick of the last use, for LRU eviction
+    used: u64,
+}
+
+/// File contents by (tree, path). Contents at a tree never change, so
+/// entries are never invalidated, only evicted.
+pub struct BlobCache {
+    root: PathBuf,
+    memory: HashMap<String, Entry>,
+    memory_bytes: u64,
+    max_memory_bytes: u64,
+    disk: bool,
+    max_disk_bytes: u64,
+    tick: u64,
+    /// Lookups since the stats were last flushed
+    pending: CacheStats,
+}
+
+impl BlobCache {
+    pub const DEFAULT_DIR: &'static str = ".agent/cache";
+
+    pub fn open(repo_root: impl AsRef<Path>, config: &CacheConfig) -> Self {
+        Self {
+            root: repo_root.as_ref().to_path_buf(),
+            memory: HashMap::new(),
+            memory_bytes: 0,
+            max_memory_bytes: config.memory_bytes,
+            disk: config.disk,
+            max_disk_bytes: config.disk_max_bytes,
+            tick: 0,
+            pending: CacheStats::default(),
+        }
+    }
+
+    /// The key for `path` in the tree with hash `tree`
+    pub fn key(tree: &str, path: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(tree.as_bytes());
+        hasher.update([0]);
+        hasher.update(path.as_bytes());
+        hex::encode(hasher.finalize())
+    }
+
+    fn blobs_dir(&self) -> PathBuf {
+        self.root.join(Self::DEFAULT_DIR).join("blobs")
+    }
+
+    /// Cached content for `key`, from memory, then disk
+    pub fn get(&mut self, key: &str) -> Option<Vec<u8>> {
+        self.tick += 1;
+        if let Some(entry) = self.memory.get_mut(key) {
+            entry.used = self.tick;
+            self.pending.memory_hits += 1;
+            return Some(entry.bytes.clone());
+        }
+        if self.disk {
+            let path = self.blobs_dir().join(key);
+            if let Ok(bytes) = std::fs::read(&path) {
+                // Touch it so disk eviction drops the least recently used
+                let _ = std::fs::File::options()
+                    .append(true)
+                    .open(&path)
+                    .and_then(|f| f.set_modified(std::time::SystemTime::now()));
+                self.pending.disk_hits += 1;
+                self.remember(key, &bytes);
+                return Some(bytes);
+            }
+        }
+        self.pending.misses += 1;
+        None
+    }
+
+    /// Store content read on a miss. Disk failures only cost a later miss.
+    pub fn insert(&mut self, key: &str, bytes: &[u8]) {
+        self.remember(key, bytes);
+        if self.disk {
+            let _ = self.write_to_disk(key, bytes);
+        }
+    }
+
+    fn remember(&mut self, key: &str, bytes: &[u8]) {
+        let size = bytes.len() as u64;
+        if size > self.max_memory_bytes {
+            return;
+        }
+        while self.memory_bytes + size > self.max_memory_bytes {
+            let Some(oldest) = self
+                .memory
+                .iter()
+                .min_by_key(|(_, e)| e.used)
+                .map(|(k, _)| k.clone())
+            else {
+                break;
+            };
+            if let Some(entry) = self.memory.remove(&oldest) {
+                self.memory_bytes -= entry.bytes.len() as u64;
+            }
+        }
+        let entry = Entry {
+            bytes: bytes.to_vec(),
+            used: self.tick,
+        };
+        if let Some(old) = self.memory.insert(key.to_string(), entry) {
+            self.memory_bytes -= old.bytes.len() as u64;
+        }
+        self.memory_bytes += size;
+    }
+
+    fn write_to_disk(&self, key: &str, bytes: &[u8]) -> Result<()> {
+        let dir = self.blobs_dir();
+        if !dir.exists() {
+            crate::agent_dir::ensure_private(&self.root.join(Self::DEFAULT_DIR))?;
+            std::fs::create_dir_all(&dir)?;
+        }
+        // Write then rename, so a concurrent reader never sees half a blob
+        let tmp = dir.join(format!("{}.{}.tmp", key, std::process::id()));
+        std::fs::write(&tmp, bytes)?;
+        std::fs::rename(&tmp, dir.join(key))?;
+
+        let mut entries = disk_entries(&dir);
+        let mut total: u64 = entries.iter().map(|(_, size, _)| size).sum();
+        if total > self.max_disk_bytes {
+            // Oldest first, down to three quarters of the limit
+            entries.sort_by_key(|(_, _, modified)| *modified);
+            for (path, size, _) in entries {
+                if total <= self.max_disk_bytes / 4 * 3 {
+                    break;
+                }
+                if std::fs::remove_file(&path).is_ok() {
+                    total -= size;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Lookups made through this cache that are not yet in `stats.json`
+    pub fn pending_stats(&self) -> CacheStats {
+        self.pending
+    }
+
+    /// Add this process's lookups to `.agent/cache/stats.json`
+    pub fn flush_stats(&mut self) -> Result<()> {
+        if self.pending.lookups() == 0 {
+            return Ok(());
+        }
+        let mut stats = load_stats(&self.root);
+        stats.memory_hits += self.pending.memory_hits;
+        stats.disk_hits += self.pending.disk_hits;
+        stats.misses += self.pending.misses;
+        crate::agent_dir::write_json(
+            &self.root.join(Self::DEFAULT_DIR).join("stats.json"),
+            &stats,
+        )?;
+        self.pending = CacheStats::default();
+        Ok(())
+    }
+}
+
+impl Drop for BlobCache {
+    fn drop(&mut self) {
+        let _ = self.flush_stats();
+    }
+}
+
+/// Lookups recorded so far in a repository
+pub fn load_stats(repo_root: impl AsRef<Path>) -> CacheStats {
+    std::fs::read_to_string(
+        repo_root
+            .as_ref()
+            .join(BlobCache::DEFAULT_DIR)
+            .join("stats.json"),
+    )
+    .ok()
+    .and_then(|content| serde_json::from_str(&content).ok())
+    .unwrap_or_default()
+}
+
+/// Blobs on disk: (count, total bytes)
+pub fn disk_usage(repo_root: impl AsRef<Path>) -> (usize, u64) {
+    let entries = disk_entries(
+        &repo_root
+            .as_ref()
+            .join(BlobCache::DEFAULT_DIR)
+            .join("blobs"),
+    );
+    (entries.len(), entries.iter().map(|(_, size, _)| size).sum())
+}
+
+fn disk_entries(dir: &Path) -> Vec<(PathBuf, u64, std::time::SystemTime)> {
+    std::fs::read_dir(dir)
+        .map(|dir| {
+            dir.flatten()
+                .filter_map(|e| {
+                    let meta = e.metadata().ok()?;
+                    let modified = meta.modified().ok()?;
+                    Some((e.path(), meta.len(), modified))
+                })
+                .filter(|(path, _, _)| path.extension().is_none())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn config(memory_bytes: u64, disk: bool) -> CacheConfig {
+        CacheConfig {
+            memory_bytes,
+            disk,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn evicts_least_recently_used_and_shares_disk_across_processes() {
+        let tmp = TempDir::new().unwrap();
+        let (a, b, c) = (
+            BlobCache::key("t1", "a.rs"),
+            BlobCache::key("t1", "b.rs"),
+            BlobCache::key("t2", "a.rs"),
+        );
+        assert_ne!(a, c);
+
+        let mut cache = BlobCache::open(tmp.path(), &config(8, false));
+        assert_eq!(cache.get(&a), None);
+        cache.insert(&a, b"aaaa");
+        cache.insert(&b, b"bbbb");
+        assert_eq!(cache.get(&a).as_deref(), Some(&b"aaaa"[..]));
+        // b was used least recently, so it makes room for c
+        cache.insert(&c, b"cccc");
+        assert_eq!(cache.get(&b), None);
+        assert!(cache.get(&a).is_some());
+        assert_eq!(
+            cache.pending_stats(),
+            CacheStats {
+                memory_hits: 2,
+                disk_hits: 0,
+                misses: 2
+            }
+        );
+        drop(cache);
+        assert_eq!(load_stats(tmp.path()).lookups(), 4);
+
+        let mut first = BlobCache::open(tmp.path(), &config(1024, true));
+        first.insert(&a, b"aaaa");
+        drop(first);
+        let mut second = BlobCache::open(tmp.path(), &config(1024, true));
+        assert_eq!(second.get(&a).as_deref(), Some(&b"aaaa"[..]));
+        assert_eq!(second.get(&a).as_deref(), Some(&b"aaaa"[..]));
+        assert_eq!(second.pending_stats().disk_hits, 1);
+        assert_eq!(second.pending_stats().memory_hits, 1);
+        assert_eq!(disk_usage(tmp.path()), (1, 4));
+        assert!(tmp.path().join(".agent/cache/.gitignore").exists());
+    }
+}
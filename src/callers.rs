@@ -0,0 +1,733 @@
+// ABOUTME: `check-callers`: call sites of a function checked against its current parameter list
+// ABOUTME: Text-level parsing of parameters and call arguments, with arity and keyword rules per language
+
+use serde::Serialize;
+use std::collections::HashSet;
+
+use crate::symbols::{SupportedLanguage, Symbol, SymbolKind};
+
+/// How a parameter can be supplied
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParamKind {
+    /// `self`, `&self`, `cls`: the receiver of a method call supplies it
+    Receiver,
+    /// Python parameters before `/`
+    PositionalOnly,
+    Positional,
+    /// Python parameters after `*` or `*args`
+    KeywordOnly,
+    /// `*args`, `...rest`, C's `...`
+    Variadic,
+    /// Python `**kwargs`
+    VariadicKeywords,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Param {
+    pub name: String,
+    pub kind: ParamKind,
+    /// False when the parameter has a default or is marked optional
+    pub required: bool,
+}
+
+/// Whether a call site still fits the signature
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CallStatus {
+    Compatible,
+    Breaks,
+    /// Spread arguments (`*args`, `...xs`) hide how many it passes
+    Unverified,
+}
+
+/// One call site of the checked function
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CallCheck {
+    pub file: String,
+    pub line: usize,
+    /// The call as written, on one line and shortened
+    pub call: String,
+    pub status: CallStatus,
+    /// Why it will break
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub problem: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Arg {
+    Positional,
+    Keyword(String),
+    /// `*xs`, `**kw`, `...xs`
+    Spread,
+}
+
+/// Languages whose calls can be checked
+pub fn supported(lang: SupportedLanguage) -> bool {
+    use SupportedLanguage::*;
+    matches!(lang, Python | Rust | JavaScript | TypeScript | C | Cpp)
+}
+
+/// Whether code in `other` can call functions defined in `lang`: JavaScript
+/// and TypeScript call each other, as do C and C++
+pub fn same_family(lang: SupportedLanguage, other: SupportedLanguage) -> bool {
+    use SupportedLanguage::*;
+    let family = |l| match l {
+        TypeScript => JavaScript,
+        Cpp => C,
+        l => l,
+    };
+    family(lang) == family(other)
+}
+
+/// The function or method named `name` (or ending in `::name`) in a file's
+/// symbols, nested ones included
+pub fn find_definition<'a>(symbols: &'a [Symbol], name: &str) -> Option<&'a Symbol> {
+    let all = crate::triage::flatten(symbols);
+    let callable = |s: &&&Symbol| matches!(s.kind, SymbolKind::Function | SymbolKind::Method);
+    all.iter()
+        .filter(callable)
+        .find(|s| s.name == name)
+        .or_else(|| {
+            all.iter()
+                .filter(callable)
+                .find(|s| short_name(&s.name) == short_name(name))
+        })
+        .copied()
+}
+
+/// The name a call uses: `area` for `geo::Shape::area`
+pub fn short_name(name: &str) -> &str {
+    name.rsplit([':', '.']).next().unwrap_or(name)
+}
+
+pub(crate) fn is_ident(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+/// `source` with comments and the insides of string literals blanked out,
+/// byte for byte, so offsets still line up with the original
+pub(crate) fn mask(source: &str, lang: SupportedLanguage) -> String {
+    let hash_comments = lang == SupportedLanguage::Python;
+    let chars: Vec<char> = source.chars().collect();
+    let mut out = String::with_capacity(source.len());
+    let blank = |out: &mut String, c: char| {
+        if c == '\n' {
+            out.push('\n');
+        } else {
+            out.extend(std::iter::repeat_n(' ', c.len_utf8()));
+        }
+    };
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        if (hash_comments && c == '#') || (!hash_comments && c == '/' && next == Some('/')) {
+            while i < chars.len() && chars[i] != '\n' {
+                blank(&mut out, chars[i]);
+                i += 1;
+            }
+            continue;
+        }
+        if !hash_comments && c == '/' && next == Some('*') {
+            let end = (i + 2..chars.len().saturating_sub(1))
+                .find(|&j| chars[j] == '*' && chars[j + 1] == '/')
+                .map_or(chars.len(), |j| j + 2);
+            for &c in &chars[i..end] {
+                blank(&mut out, c);
+            }
+            i = end;
+            continue;
+        }
+        // A Rust `'` is a lifetime unless it closes as a char literal
+        let char_literal = next == Some('\\') || chars.get(i + 2) == Some(&'\'');
+        let quote = matches!(c, '"' | '`')
+            || (c == '\'' && (lang != SupportedLanguage::Rust || char_literal));
+        if !quote {
+            out.push(c);
+            i += 1;
+            continue;
+        }
+        let delimiter =
+            if lang == SupportedLanguage::Python && next == Some(c) && chars.get(i + 2) == Some(&c)
+            {
+                3
+            } else {
+                1
+            };
+        let closes = |j: usize| (j..j + delimiter).all(|k| chars.get(k) == Some(&c));
+        for _ in 0..delimiter {
+            out.push(c);
+        }
+        i += delimiter;
+        while i < chars.len() {
+            if chars[i] == '\\' {
+                for &c in &chars[i..chars.len().min(i + 2)] {
+                    blank(&mut out, c);
+                }
+                i += 2;
+            } else if closes(i) {
+                for _ in 0..delimiter {
+                    out.push(c);
+                }
+                i += delimiter;
+                break;
+            } else if chars[i] == '\n' && delimiter == 1 && c != '`' {
+                // Unterminated: the string ends with the line
+                break;
+            } else {
+                blank(&mut out, chars[i]);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+/// The text inside the brackets opening at byte `open`, and the byte just
+/// past the closing one
+fn bracketed(text: &str, open: usize) -> Option<(&str, usize)> {
+    let mut depth = 0usize;
+    for (i, c) in text[open..].char_indices() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => {
+                depth = depth.checked_sub(1)?;
+                if depth == 0 {
+                    return Some((&text[open + 1..open + i], open + i + 1));
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Split on commas outside brackets; `angles` counts `<...>` as brackets too,
+/// for generic types in parameter lists
+fn split_top_level(text: &str, angles: bool) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut depth, mut start) = (0usize, 0);
+    let mut previous = ' ';
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth = depth.saturating_sub(1),
+            '<' if angles => depth += 1,
+            // `->` and `=>` are arrows, not closing angles
+            '>' if angles && previous != '-' && previous != '=' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                parts.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+        previous = c;
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+/// `part` up to the first of `stops` outside brackets
+fn head<'a>(part: &'a str, stops: &[char]) -> &'a str {
+    let mut depth = 0usize;
+    for (i, c) in part.char_indices() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth = depth.saturating_sub(1),
+            c if depth == 0 && stops.contains(&c) => return &part[..i],
+            _ => {}
+        }
+    }
+    part
+}
+
+fn parse_params(inner: &str, lang: SupportedLanguage) -> Vec<Param> {
+    use SupportedLanguage::*;
+    let mut params: Vec<Param> = Vec::new();
+    let mut keyword_only = false;
+    let parts: Vec<&str> = split_top_level(inner, lang != Python)
+        .into_iter()
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .collect();
+    for part in &parts {
+        let param = |name: &str, kind, required| Param {
+            name: name.trim().to_string(),
+            kind,
+            required,
+        };
+        let has_default = head(part, &['=']).len() < part.len();
+        match lang {
+            Python => {
+                if *part == "/" {
+                    for p in params
+                        .iter_mut()
+                        .filter(|p| p.kind == ParamKind::Positional)
+                    {
+                        p.kind = ParamKind::PositionalOnly;
+                    }
+                } else if *part == "*" {
+                    keyword_only = true;
+                } else if let Some(rest) = part.strip_prefix("**") {
+                    params.push(param(
+                        head(rest, &[':']),
+                        ParamKind::VariadicKeywords,
+                        false,
+                    ));
+                } else if let Some(rest) = part.strip_prefix('*') {
+                    params.push(param(head(rest, &[':']), ParamKind::Variadic, false));
+                    keyword_only = true;
+                } else {
+                    let name = head(part, &[':', '=']).trim();
+                    let kind = if params.is_empty() && (name == "self" || name == "cls") {
+                        ParamKind::Receiver
+                    } else if keyword_only {
+                        ParamKind::KeywordOnly
+                    } else {
+                        ParamKind::Positional
+                    };
+                    params.push(param(name, kind, !has_default));
+                }
+            }
+            Rust => {
+                let pattern = head(part, &[':']).trim();
+                let bare = pattern.trim_start_matches('&').trim_start();
+                // `&'a self`, `&mut self`, `mut self`, `self: Box<Self>`
+                let bare = match bare.strip_prefix('\'') {
+                    Some(rest) => rest.trim_start_matches(is_ident).trim_start(),
+                    None => bare,
+                };
+                let bare = bare.strip_prefix("mut ").unwrap_or(bare).trim();
+                if bare == "self" && params.is_empty() {
+                    params.push(param("self", ParamKind::Receiver, true));
+                } else {
+                    let name = pattern.strip_prefix("mut ").unwrap_or(pattern);
+                    params.push(param(name, ParamKind::Positional, true));
+                }
+            }
+            JavaScript | TypeScript => {
+                if let Some(rest) = part.strip_prefix("...") {
+                    params.push(param(head(rest, &[':', '=']), ParamKind::Variadic, false));
+                    continue;
+                }
+                let name = head(part, &[':', '=']).trim();
+                // TypeScript's `this: Type` only declares the receiver's type
+                if name == "this" {
+                    continue;
+                }
+                let optional = name.ends_with('?');
+                params.push(param(
+                    name.trim_end_matches('?'),
+                    ParamKind::Positional,
+                    !has_default && !optional,
+                ));
+            }
+            _ => {
+                if *part == "..." {
+                    params.push(param("...", ParamKind::Variadic, false));
+                } else if !(*part == "void" && parts.len() == 1) {
+                    // The declarator's name, or its type when unnamed
+                    let declarator = head(part, &['=', '[']).trim_end();
+                    let start = declarator
+                        .rfind(|c: char| !is_ident(c))
+                        .map_or(0, |i| i + 1);
+                    let name = match &declarator[start..] {
+                        "" => declarator,
+                        name => name,
+                    };
+                    params.push(param(name, ParamKind::Positional, !has_default));
+                }
+            }
+        }
+    }
+    params
+}
+
+/// Byte offset where 1-based `line` starts
+fn line_offset(text: &str, line: usize) -> Option<usize> {
+    if line <= 1 {
+        return Some(0);
+    }
+    text.match_indices('\n').nth(line - 2).map(|(i, _)| i + 1)
+}
+
+/// The parameters of `symbol` as written in `source`, or `None` when its
+/// parameter list can't be found
+pub fn parameters(source: &str, symbol: &Symbol, lang: SupportedLanguage) -> Option<Vec<Param>> {
+    let masked = mask(source, lang);
+    let start = line_offset(&masked, symbol.start_line)?;
+    let name = short_name(&symbol.name);
+    let after_name = start + masked[start..].find(name)? + name.len();
+    let open = after_name + masked[after_name..].find('(')?;
+    let (inner, _) = bracketed(&masked, open)?;
+    Some(parse_params(inner, lang))
+}
+
+fn parse_args(inner: &str, lang: SupportedLanguage) -> Vec<Arg> {
+    split_top_level(inner, false)
+        .into_iter()
+        .map(str::trim)
+        .filter(|a| !a.is_empty())
+        .map(|arg| match lang {
+            SupportedLanguage::Python if arg.starts_with('*') => Arg::Spread,
+            SupportedLanguage::Python => {
+                let name = head(arg, &['=']);
+                let rest = &arg[name.len()..];
+                let name = name.trim();
+                // `x == y` and `x := y` are expressions, not keywords
+                let keyword = rest.starts_with('=')
+                    && !rest.starts_with("==")
+                    && !name.is_empty()
+                    && name.chars().all(is_ident)
+                    && !name.ends_with([':', '!', '<', '>']);
+                if keyword {
+                    Arg::Keyword(name.to_string())
+                } else {
+                    Arg::Positional
+                }
+            }
+            SupportedLanguage::JavaScript | SupportedLanguage::TypeScript
+                if arg.starts_with("...") =>
+            {
+                Arg::Spread
+            }
+            _ => Arg::Positional,
+        })
+        .collect()
+}
+
+/// Words that can come right before a call without making it a declaration
+const NOT_A_TYPE: &[&str] = &[
+    "return",
+    "else",
+    "case",
+    "throw",
+    "new",
+    "delete",
+    "co_return",
+    "co_await",
+    "co_yield",
+    "sizeof",
+    "await",
+    "yield",
+    "typeof",
+    "void",
+];
+
+/// Whether the match of the name at `at` declares it rather than calls it
+fn is_definition(masked: &str, at: usize, close: usize, lang: SupportedLanguage) -> bool {
+    use SupportedLanguage::*;
+    let line_start = masked[..at].rfind('\n').map_or(0, |i| i + 1);
+    let before = masked[line_start..at].trim_end();
+    let previous_word = before
+        .trim_end_matches(['*', '&', ' '])
+        .rsplit(|c: char| !is_ident(c))
+        .next()
+        .unwrap_or("");
+    match lang {
+        Python => previous_word == "def",
+        Rust => previous_word == "fn",
+        JavaScript | TypeScript => {
+            // `function f(`, or a class method `f(a) {` / `f(a): T {`
+            let after = masked[close..].trim_start();
+            previous_word == "function"
+                || after.starts_with('{')
+                || (after.starts_with(':')
+                    && before.split_whitespace().all(|w| {
+                        matches!(w, "async" | "static" | "public" | "private" | "protected")
+                    }))
+        }
+        // A type before the name: a prototype or definition
+        _ => {
+            !previous_word.is_empty()
+                && !before.ends_with(['.', '>', ':'])
+                && !NOT_A_TYPE.contains(&previous_word)
+        }
+    }
+}
+
+fn excerpt(text: &str) -> String {
+    let single: String = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if single.chars().count() > 120 {
+        format!("{}...", single.chars().take(117).collect::<String>())
+    } else {
+        single
+    }
+}
+
+/// Why a call with `args` no longer fits `params`
+fn problem(
+    params: &[Param],
+    args: &[Arg],
+    method_call: bool,
+    lang: SupportedLanguage,
+) -> Option<String> {
+    // A method call's receiver fills `self`
+    let params: Vec<&Param> = params
+        .iter()
+        .filter(|p| !(method_call && p.kind == ParamKind::Receiver))
+        .collect();
+    let positional: Vec<&&Param> = params
+        .iter()
+        .filter(|p| {
+            matches!(
+                p.kind,
+                ParamKind::Receiver | ParamKind::PositionalOnly | ParamKind::Positional
+            )
+        })
+        .collect();
+    let given = args.iter().filter(|a| **a == Arg::Positional).count();
+    let variadic = params.iter().any(|p| p.kind == ParamKind::Variadic);
+    // JavaScript ignores extra arguments
+    if given > positional.len() && !variadic && lang != SupportedLanguage::JavaScript {
+        return Some(format!(
+            "takes at most {} positional argument(s), {} given",
+            positional.len(),
+            given
+        ));
+    }
+
+    let mut filled: HashSet<&str> = positional
+        .iter()
+        .take(given)
+        .map(|p| p.name.as_str())
+        .collect();
+    for arg in args {
+        let Arg::Keyword(name) = arg else {
+            continue;
+        };
+        match params.iter().find(|p| &p.name == name) {
+            Some(p) if p.kind == ParamKind::PositionalOnly => {
+                return Some(format!("'{}' is positional-only", name));
+            }
+            Some(_) if filled.contains(name.as_str()) => {
+                return Some(format!("multiple values for '{}'", name));
+            }
+            Some(p) if !matches!(p.kind, ParamKind::Variadic | ParamKind::VariadicKeywords) => {
+                filled.insert(&p.name);
+            }
+            _ if params.iter().any(|p| p.kind == ParamKind::VariadicKeywords) => {}
+            _ => return Some(format!("unexpected keyword argument '{}'", name)),
+        }
+    }
+
+    let missing: Vec<&str> = params
+        .iter()
+        .filter(|p| p.required && !filled.contains(p.name.as_str()))
+        .map(|p| p.name.as_str())
+        .collect();
+    (!missing.is_empty()).then(|| format!("missing required argument(s): {}", missing.join(", ")))
+}
+
+/// Every call to `name` in `source`, checked against `params`. The
+/// definition itself, at `definition_line` when this is its file, is skipped.
+pub fn check_calls(
+    file: &str,
+    source: &str,
+    lang: SupportedLanguage,
+    name: &str,
+    params: &[Param],
+    definition_line: Option<usize>,
+) -> Vec<CallCheck> {
+    let masked = mask(source, lang);
+    let mut checks = Vec::new();
+    for (at, _) in masked.match_indices(name) {
+        let end = at + name.len();
+        if masked[..at].chars().next_back().is_some_and(is_ident)
+            || masked[end..].chars().next().is_some_and(is_ident)
+        {
+            continue;
+        }
+        // Generic arguments between the name and the call: `f::<T>(`, `f<T>(`
+        let mut open = end + (masked[end..].len() - masked[end..].trim_start().len());
+        let rest = &masked[open..];
+        let generics = rest.strip_prefix("::").unwrap_or(rest);
+        if generics.starts_with('<') {
+            let mut depth = 0usize;
+            let start = masked.len() - generics.len();
+            let Some(close) = generics.char_indices().find_map(|(i, c)| {
+                match c {
+                    '<' => depth += 1,
+                    '>' => depth = depth.saturating_sub(1),
+                    _ => {}
+                }
+                (c == '>' && depth == 0).then_some(i)
+            }) else {
+                continue;
+            };
+            open = start + close + 1;
+        }
+        if !masked[open..].starts_with('(') {
+            continue;
+        }
+        let Some((inner, close)) = bracketed(&masked, open) else {
+            continue;
+        };
+        let line = masked[..at].matches('\n').count() + 1;
+        if Some(line) == definition_line || is_definition(&masked, at, close, lang) {
+            continue;
+        }
+
+        let before = masked[..at].trim_end();
+        let method_call = before.ends_with('.') || before.ends_with("->");
+        let call_start = masked[..at]
+            .rfind(|c: char| !(is_ident(c) || c == '.' || c == ':' || c == '>' || c == '-'))
+            .map_or(0, |i| i + 1);
+        let args = parse_args(inner, lang);
+        let (status, problem) = if args.contains(&Arg::Spread) {
+            (CallStatus::Unverified, None)
+        } else {
+            match problem(params, &args, method_call, lang) {
+                Some(problem) => (CallStatus::Breaks, Some(problem)),
+                None => (CallStatus::Compatible, None),
+            }
+        };
+        checks.push(CallCheck {
+            file: file.to_string(),
+            line,
+            call: excerpt(&source[call_start..close]),
+            status,
+            problem,
+        });
+    }
+    checks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbols::extract_symbols;
+
+    fn params_of(source: &str, name: &str, lang: SupportedLanguage) -> Vec<Param> {
+        let symbols = extract_symbols(source, lang).unwrap();
+        let symbol = find_definition(&symbols, name).unwrap();
+        parameters(source, symbol, lang).unwrap()
+    }
+
+    fn problems(
+        source: &str,
+        name: &str,
+        params: &[Param],
+        lang: SupportedLanguage,
+    ) -> Vec<(usize, Option<String>)> {
+        check_calls("f", source, lang, name, params, None)
+            .into_iter()
+            .map(|c| (c.line, c.problem))
+            .collect()
+    }
+
+    #[test]
+    fn python_calls_checked_for_arity_and_keywords() {
+        let def = "class Handler:\n    def process(self, req, /, timeout, *, retries=3, **extra):\n        pass\n";
+        let params = params_of(def, "process", SupportedLanguage::Python);
+        let kinds: Vec<(&str, ParamKind, bool)> = params
+            .iter()
+            .map(|p| (p.name.as_str(), p.kind, p.required))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("self", ParamKind::Receiver, true),
+                ("req", ParamKind::PositionalOnly, true),
+                ("timeout", ParamKind::Positional, true),
+                ("retries", ParamKind::KeywordOnly, false),
+                ("extra", ParamKind::VariadicKeywords, false),
+            ]
+        );
+
+        let calls = "h.process(r, 5)\n\
+                     h.process(r, timeout=5, retries=1, verbose=True)\n\
+                     h.process(r)\n\
+                     h.process(r, 5, 1)\n\
+                     h.process(req=r, timeout=5)\n\
+                     h.process(r, 5, timeout=6)\n\
+                     h.process(*args)\n\
+                     # h.process() in a comment\n\
+                     log(\"h.process()\")\n";
+        assert_eq!(
+            problems(calls, "process", &params, SupportedLanguage::Python),
+            vec![
+                (1, None),
+                (2, None),
+                (3, Some("missing required argument(s): timeout".into())),
+                (
+                    4,
+                    Some("takes at most 2 positional argument(s), 3 given".into())
+                ),
+                (5, Some("'req' is positional-only".into())),
+                (6, Some("multiple values for 'timeout'".into())),
+                (7, None),
+            ]
+        );
+        let checks = check_calls(
+            "f",
+            calls,
+            SupportedLanguage::Python,
+            "process",
+            &params,
+            None,
+        );
+        assert_eq!(checks[6].status, CallStatus::Unverified);
+        assert_eq!(checks[2].call, "h.process(r)");
+    }
+
+    #[test]
+    fn rust_js_and_c_calls_checked_for_arity() {
+        let rust = "impl Client {\n    pub fn send<'a>(&'a mut self, body: &'a str, retries: HashMap<u8, u8>) -> bool {\n        true\n    }\n}\n";
+        let params = params_of(rust, "send", SupportedLanguage::Rust);
+        assert_eq!(params.len(), 3);
+        assert_eq!(params[0].kind, ParamKind::Receiver);
+        let calls = "fn main() {\n    client.send(\"a, b\", map);\n    client.send('x');\n    Client::send(&mut client, \"b\", map);\n}\n";
+        assert_eq!(
+            problems(calls, "send", &params, SupportedLanguage::Rust),
+            vec![
+                (2, None),
+                (3, Some("missing required argument(s): retries".into())),
+                (4, None),
+            ]
+        );
+
+        let ts = "export function fetchUser(id: string, opts?: Options, retries = 2) {\n}\n";
+        let symbol = Symbol {
+            name: "fetchUser".into(),
+            kind: SymbolKind::Function,
+            signature: None,
+            docstring: None,
+            start_line: 1,
+            end_line: 2,
+            children: Vec::new(),
+        };
+        let params = parameters(ts, &symbol, SupportedLanguage::TypeScript).unwrap();
+        let calls =
+            "fetchUser();\nfetchUser(id);\nfetchUser(id, {}, 3, true);\nfetchUser(...args);\n";
+        assert_eq!(
+            problems(calls, "fetchUser", &params, SupportedLanguage::TypeScript),
+            vec![
+                (1, Some("missing required argument(s): id".into())),
+                (2, None),
+                (
+                    3,
+                    Some("takes at most 3 positional argument(s), 4 given".into())
+                ),
+                (4, None),
+            ]
+        );
+        // JavaScript ignores the extra argument
+        assert_eq!(
+            problems(calls, "fetchUser", &params, SupportedLanguage::JavaScript)[2],
+            (3, None)
+        );
+
+        let c = "int log_msg(const char *fmt, int level, ...) {\n    return 0;\n}\n";
+        let params = params_of(c, "log_msg", SupportedLanguage::C);
+        let calls = "int log_msg(const char *fmt, int level, ...);\nvoid f(void) {\n    log_msg(\"x\", 1, 2, 3);\n    return log_msg(\"y\");\n}\n";
+        assert_eq!(
+            problems(calls, "log_msg", &params, SupportedLanguage::C),
+            vec![
+                (3, None),
+                (4, Some("missing required argument(s): level".into())),
+            ]
+        );
+    }
+}
@@ -0,0 +1,307 @@
+// ABOUTME: Capability report: which features work in this environment (languages, tools, forge, write mode)
+// ABOUTME: Every check is local and cheap (PATH lookups, config, one file walk) so agents can call it before planning
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::symbols::SupportedLanguage;
+
+/// Bumped when a field is removed or changes meaning; new fields do not bump it
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Everything an agent can rely on before it starts, as one stable document
+#[derive(Debug, Clone, Serialize)]
+pub struct Capabilities {
+    pub schema_version: u32,
+    /// agentjj version
+    pub version: String,
+    /// The repository here, if any
+    pub repository: Option<RepositoryInfo>,
+    pub languages: Languages,
+    /// External programs agentjj shells out to, by name
+    pub tools: BTreeMap<String, Tool>,
+    pub forge: Forge,
+    pub lsp: Lsp,
+    pub sandbox: Feature,
+    pub daemon: Daemon,
+    pub write: WriteMode,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RepositoryInfo {
+    pub root: String,
+    /// Whether jj is set up (false for a plain git repository not yet colocated)
+    pub jj: bool,
+    /// Whether a git repository is colocated at the root
+    pub git: bool,
+    /// Whether `.agent/manifest.toml` exists
+    pub manifest: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Languages {
+    /// Languages symbol queries work for
+    pub supported: Vec<&'static str>,
+    /// Supported languages with files in this repository, with file counts
+    pub indexed: BTreeMap<&'static str, usize>,
+    /// Whether codebase scans run at all (`index.enabled`)
+    pub index_enabled: bool,
+}
+
+impl Languages {
+    /// Tally `files` by symbol language
+    pub fn from_files(files: &[PathBuf], index_enabled: bool) -> Self {
+        let mut indexed = BTreeMap::new();
+        for file in files {
+            if let Some(language) = SupportedLanguage::from_path(file) {
+                *indexed.entry(language.name()).or_insert(0) += 1;
+            }
+        }
+        Self {
+            supported: SupportedLanguage::ALL.iter().map(|l| l.name()).collect(),
+            indexed,
+            index_enabled,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Tool {
+    pub available: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// What needs it
+    pub used_by: &'static str,
+}
+
+/// Programs agentjj runs, and the commands that need each
+pub const TOOLS: &[(&str, &str)] = &[
+    ("git", "push, tag --push, graph --format ascii"),
+    ("gh", "push --pr, triage --ci"),
+    ("jj", "nothing (agentjj links jj-lib); handy for humans"),
+];
+
+impl Tool {
+    pub fn find(name: &str, used_by: &'static str, path_var: Option<&str>) -> Self {
+        let path = path_var.and_then(|p| find_on_path(name, p));
+        Self {
+            available: path.is_some(),
+            path: path.map(|p| p.display().to_string()),
+            used_by,
+        }
+    }
+}
+
+/// `name` as an executable in one of the `PATH` directories, without running it
+pub fn find_on_path(name: &str, path_var: &str) -> Option<PathBuf> {
+    std::env::split_paths(path_var)
+        .map(|dir| dir.join(name))
+        .find(|candidate| is_executable(candidate))
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .is_ok_and(|m| m.is_file() && crate::repo::is_executable(&m))
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file() || path.with_extension("exe").is_file()
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Forge {
+    /// Whether a GitHub token is available to `gh`
+    pub github_token: bool,
+    /// Where it comes from: "secrets.env" (`GH_TOKEN` or `GITHUB_TOKEN` in
+    /// `.agent/secrets.env`), "GH_TOKEN",
+    /// "GITHUB_TOKEN", or "gh auth" (gh's own login)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token_source: Option<&'static str>,
+    /// Whether `push --pr` can open pull requests: gh plus a token
+    pub pull_requests: bool,
+}
+
+impl Forge {
+    /// Token presence only; the token itself is never read into the report.
+    /// `env` looks up environment variables.
+    pub fn detect(
+        secrets_token: bool,
+        gh_available: bool,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Self {
+        let set = |name: &str| env(name).is_some_and(|v| !v.is_empty());
+        let token_source = if secrets_token {
+            Some("secrets.env")
+        } else if set("GH_TOKEN") {
+            Some("GH_TOKEN")
+        } else if set("GITHUB_TOKEN") {
+            Some("GITHUB_TOKEN")
+        } else if gh_hosts_file(&env).is_some_and(|p| p.is_file()) {
+            Some("gh auth")
+        } else {
+            None
+        };
+        Self {
+            github_token: token_source.is_some(),
+            token_source,
+            pull_requests: gh_available && token_source.is_some(),
+        }
+    }
+}
+
+/// Where `gh auth login` keeps its credentials
+fn gh_hosts_file(env: &impl Fn(&str) -> Option<String>) -> Option<PathBuf> {
+    let dir = match env("GH_CONFIG_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => match env("XDG_CONFIG_HOME") {
+            Some(config) => PathBuf::from(config).join("gh"),
+            None => PathBuf::from(env("HOME")?).join(".config/gh"),
+        },
+    };
+    Some(dir.join("hosts.yml"))
+}
+
+/// Language servers: agentjj parses with tree-sitter and runs none
+#[derive(Debug, Clone, Serialize)]
+pub struct Lsp {
+    pub available: bool,
+    pub servers: Vec<String>,
+    pub reason: &'static str,
+}
+
+impl Default for Lsp {
+    fn default() -> Self {
+        Self {
+            available: false,
+            servers: Vec::new(),
+            reason: "agentjj extracts symbols with tree-sitter and runs no language servers",
+        }
+    }
+}
+
+/// A feature this build may not provide
+#[derive(Debug, Clone, Serialize)]
+pub struct Feature {
+    pub available: bool,
+    pub reason: &'static str,
+}
+
+impl Feature {
+    /// Commands, invariants, and hooks run directly in the working copy
+    pub fn sandbox() -> Self {
+        Self {
+            available: false,
+            reason: "invariants, tests, and hooks run unsandboxed in the working copy",
+        }
+    }
+}
+
+/// A background agentjj process: there is none, every command runs alone
+#[derive(Debug, Clone, Serialize)]
+pub struct Daemon {
+    pub running: bool,
+    pub reason: &'static str,
+}
+
+impl Default for Daemon {
+    fn default() -> Self {
+        Self {
+            running: false,
+            reason: "agentjj has no daemon; each invocation loads the repository itself",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WriteMode {
+    /// "read-write" or "read-only"
+    pub mode: &'static str,
+    /// What turned read-only mode on: "--read-only" or "manifest default_mode"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled_by: Option<String>,
+}
+
+impl WriteMode {
+    pub fn new(enabled_by: Option<&str>) -> Self {
+        Self {
+            mode: if enabled_by.is_some() {
+                "read-only"
+            } else {
+                "read-write"
+            },
+            enabled_by: enabled_by.map(str::to_string),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_presence_without_running_anything() {
+        let tmp = tempfile::tempdir().unwrap();
+        let bin = tmp.path().join("bin");
+        std::fs::create_dir_all(&bin).unwrap();
+        std::fs::write(bin.join("gh"), "#!/bin/sh\n").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(bin.join("gh"), std::fs::Permissions::from_mode(0o755))
+                .unwrap();
+            // Not executable: not available
+            std::fs::write(bin.join("git"), "").unwrap();
+            assert!(!Tool::find("git", "", bin.to_str()).available);
+        }
+        let gh = Tool::find("gh", "", bin.to_str());
+        assert!(gh.available);
+        assert!(!Tool::find("gh", "", None).available);
+
+        let home = tmp.path().to_str().unwrap().to_string();
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            let home = home.clone();
+            move |name: &str| {
+                if name == "HOME" {
+                    return Some(home.clone());
+                }
+                vars.iter()
+                    .find(|(k, _)| *k == name)
+                    .map(|(_, v)| v.to_string())
+            }
+        };
+        let forge = Forge::detect(false, true, env(&[("GH_TOKEN", ""), ("GITHUB_TOKEN", "x")]));
+        assert_eq!(forge.token_source, Some("GITHUB_TOKEN"));
+        assert!(forge.pull_requests);
+        assert_eq!(
+            Forge::detect(true, false, env(&[])).token_source,
+            Some("secrets.env")
+        );
+        assert!(!Forge::detect(true, false, env(&[])).pull_requests);
+        assert!(!Forge::detect(false, true, env(&[])).github_token);
+        std::fs::create_dir_all(tmp.path().join(".config/gh")).unwrap();
+        std::fs::write(tmp.path().join(".config/gh/hosts.yml"), "github.com:\n").unwrap();
+        assert_eq!(
+            Forge::detect(false, true, env(&[])).token_source,
+            Some("gh auth")
+        );
+
+        let files: Vec<PathBuf> = ["src/main.rs", "src/lib.rs", "build.py", "README"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        let languages = Languages::from_files(&files, true);
+        assert_eq!(languages.indexed["rust"], 2);
+        assert_eq!(languages.indexed["python"], 1);
+        assert_eq!(languages.indexed.len(), 2);
+        assert!(languages
+            .supported
+            .iter()
+            .all(|name| SupportedLanguage::from_name(name).is_some()));
+
+        assert_eq!(WriteMode::new(Some("--read-only")).mode, "read-only");
+        assert_eq!(WriteMode::new(None).mode, "read-write");
+    }
+}
@@ -2,7 +2,7 @@
 // ABOUTME: Semantic change records keyed by jj change ID (stable across rebases)
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::Path;
 
 use crate::error::{Error, Result};
@@ -27,9 +27,10 @@ pub enum ChangeType {
     Test,
 }
 
-/// Category of the change (more granular than type)
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
+/// Category of the change (more granular than type). A change can have
+/// several; repositories define their own in the manifest's `[categories]`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
 pub enum ChangeCategory {
     Feature,
     Fix,
@@ -38,6 +39,77 @@ pub enum ChangeCategory {
     Breaking,
     Deprecation,
     Chore,
+    /// A manifest-defined category (`compliance`, `i18n`)
+    Custom(String),
+}
+
+impl ChangeCategory {
+    /// The categories every repository has
+    pub const BUILTIN: [ChangeCategory; 7] = [
+        ChangeCategory::Feature,
+        ChangeCategory::Fix,
+        ChangeCategory::Perf,
+        ChangeCategory::Security,
+        ChangeCategory::Breaking,
+        ChangeCategory::Deprecation,
+        ChangeCategory::Chore,
+    ];
+
+    /// Lowercase name it is stored under
+    pub fn name(&self) -> &str {
+        match self {
+            ChangeCategory::Feature => "feature",
+            ChangeCategory::Fix => "fix",
+            ChangeCategory::Perf => "perf",
+            ChangeCategory::Security => "security",
+            ChangeCategory::Breaking => "breaking",
+            ChangeCategory::Deprecation => "deprecation",
+            ChangeCategory::Chore => "chore",
+            ChangeCategory::Custom(name) => name,
+        }
+    }
+}
+
+impl From<String> for ChangeCategory {
+    fn from(name: String) -> Self {
+        Self::BUILTIN
+            .into_iter()
+            .find(|c| c.name() == name)
+            .unwrap_or(ChangeCategory::Custom(name))
+    }
+}
+
+impl From<ChangeCategory> for String {
+    fn from(category: ChangeCategory) -> Self {
+        category.name().to_string()
+    }
+}
+
+impl std::fmt::Display for ChangeCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Categories as a list, also read from the single `category` string
+/// records made before a change could have several
+pub(crate) fn one_or_many<'de, D>(
+    deserializer: D,
+) -> std::result::Result<Vec<ChangeCategory>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(ChangeCategory),
+        Many(Vec<ChangeCategory>),
+    }
+    Ok(match Option::<OneOrMany>::deserialize(deserializer)? {
+        None => Vec::new(),
+        Some(OneOrMany::One(category)) => vec![category],
+        Some(OneOrMany::Many(categories)) => categories,
+    })
 }
 
 /// Typed metadata for a jj change
@@ -50,9 +122,14 @@ pub struct TypedChange {
     #[serde(rename = "type")]
     pub change_type: ChangeType,
 
-    /// Category for more granular classification
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub category: Option<ChangeCategory>,
+    /// Categories for more granular classification (`perf` and `breaking`)
+    #[serde(
+        default,
+        alias = "category",
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub categories: Vec<ChangeCategory>,
 
     /// Human-readable intent description
     pub intent: String,
@@ -80,6 +157,145 @@ pub struct TypedChange {
     /// Additional structured metadata
     #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub metadata: HashMap<String, String>,
+
+    /// Repository-defined fields, such as those a manifest change template
+    /// requires (`migration_id`, `cve`)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fields: BTreeMap<String, String>,
+
+    /// Changes that must be pushed or merged before this one, by change ID
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<String>,
+
+    /// Free-form working notes: decisions made, known limitations
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+
+    /// Corrections made to this metadata after it was first recorded
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<MetadataEdit>,
+
+    /// Diff size, recorded at commit time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<ChangeMetrics>,
+
+    /// When the metadata was first recorded (`YYYY-MM-DDTHH:MM:SSZ`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<String>,
+
+    /// Who recorded it: the agent session, else the user
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+}
+
+/// Lines added and removed in one file of a change
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FileStats {
+    pub path: String,
+    pub additions: usize,
+    pub deletions: usize,
+    /// Binary content, which has no line counts
+    pub binary: bool,
+}
+
+/// How big a change is, by file and in total
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ChangeMetrics {
+    pub additions: usize,
+    pub deletions: usize,
+    /// Additions plus deletions
+    pub churn: usize,
+    pub binary_files: usize,
+    pub files: Vec<FileStats>,
+}
+
+impl ChangeMetrics {
+    /// Line counts for each changed path, as `git diff --numstat` reports them
+    pub fn from_changes(changes: &[crate::unified::FileChange]) -> Self {
+        let mut metrics = Self::default();
+        for change in changes {
+            let counts = change.line_counts();
+            let (additions, deletions) = counts.unwrap_or_default();
+            metrics.additions += additions;
+            metrics.deletions += deletions;
+            metrics.binary_files += usize::from(counts.is_none());
+            metrics.files.push(FileStats {
+                path: change.path.clone(),
+                additions,
+                deletions,
+                binary: counts.is_none(),
+            });
+        }
+        metrics.churn = metrics.additions + metrics.deletions;
+        metrics
+    }
+
+    pub fn file(&self, path: &str) -> Option<&FileStats> {
+        self.files.iter().find(|f| f.path == path)
+    }
+}
+
+/// One after-the-fact correction of a change's metadata
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MetadataEdit {
+    /// Who made the edit (session or user)
+    pub by: String,
+    pub at: String,
+    pub fields: Vec<FieldEdit>,
+}
+
+/// A single field's old and new value. For `files`, an added path has only
+/// `after` and a removed one only `before`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FieldEdit {
+    pub field: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub before: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+}
+
+/// Requested corrections to a change's metadata; unset fields stay as they are
+#[derive(Debug, Clone, Default)]
+pub struct ChangeEdit {
+    pub intent: Option<String>,
+    pub change_type: Option<ChangeType>,
+    /// Replaces every category
+    pub categories: Option<Vec<ChangeCategory>>,
+    pub breaking: Option<bool>,
+    pub add_files: Vec<String>,
+    pub remove_files: Vec<String>,
+    pub add_depends_on: Vec<String>,
+    pub remove_depends_on: Vec<String>,
+}
+
+/// A dependency that does not hold for a push: `change_id` declares
+/// `depends_on`, which is neither pushed already nor part of the push
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct UnmetDependency {
+    pub change_id: String,
+    pub depends_on: String,
+    /// `not_pushed`, or `not_found` when the change no longer exists
+    pub reason: String,
+}
+
+/// Lowercase name a serde enum is stored under (e.g. "behavioral")
+pub(crate) fn enum_name(value: impl Serialize) -> String {
+    serde_json::to_value(value)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+/// Category names joined with ", "; `None` for no categories
+pub(crate) fn category_list(categories: &[ChangeCategory]) -> Option<String> {
+    (!categories.is_empty()).then(|| {
+        categories
+            .iter()
+            .map(ChangeCategory::name)
+            .collect::<Vec<_>>()
+            .join(", ")
+    })
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -108,7 +324,7 @@ pub enum InvariantStatus {
 }
 
 impl TypedChange {
-    /// Create a new typed change
+    /// Create a new typed change, stamped with the current time and actor
     pub fn new(
         change_id: impl Into<String>,
         change_type: ChangeType,
@@ -117,7 +333,7 @@ impl TypedChange {
         Self {
             change_id: change_id.into(),
             change_type,
-            category: None,
+            categories: Vec::new(),
             intent: intent.into(),
             files: Vec::new(),
             breaking: false,
@@ -125,27 +341,200 @@ impl TypedChange {
             dependencies_removed: Vec::new(),
             invariants: InvariantsResult::default(),
             metadata: HashMap::new(),
+            fields: BTreeMap::new(),
+            depends_on: Vec::new(),
+            notes: None,
+            history: Vec::new(),
+            metrics: None,
+            created_at: Some(crate::repo::utc_now()),
+            author: Some(crate::audit::current_actor()),
         }
     }
 
-    /// Set the category
+    /// Apply corrections, recording what actually changed in `history`.
+    /// Returns the changed fields; empty when the edit was a no-op.
+    pub fn apply_edit(
+        &mut self,
+        edit: &ChangeEdit,
+        by: impl Into<String>,
+        at: impl Into<String>,
+    ) -> Vec<FieldEdit> {
+        let mut fields = Vec::new();
+        let mut record = |field: &str, before: Option<String>, after: Option<String>| {
+            fields.push(FieldEdit {
+                field: field.to_string(),
+                before,
+                after,
+            });
+        };
+
+        if let Some(intent) = edit.intent.as_ref().filter(|i| **i != self.intent) {
+            record(
+                "intent",
+                Some(std::mem::replace(&mut self.intent, intent.clone())),
+                Some(intent.clone()),
+            );
+        }
+        if let Some(change_type) = edit.change_type.filter(|t| *t != self.change_type) {
+            record(
+                "type",
+                Some(enum_name(self.change_type)),
+                Some(enum_name(change_type)),
+            );
+            self.change_type = change_type;
+        }
+        if let Some(categories) = edit.categories.as_ref().filter(|c| **c != self.categories) {
+            record(
+                "categories",
+                category_list(&self.categories),
+                category_list(categories),
+            );
+            self.categories = categories.clone();
+        }
+        if let Some(breaking) = edit.breaking.filter(|b| *b != self.breaking) {
+            record(
+                "breaking",
+                Some(self.breaking.to_string()),
+                Some(breaking.to_string()),
+            );
+            self.breaking = breaking;
+        }
+        for file in &edit.remove_files {
+            if let Some(pos) = self.files.iter().position(|f| f == file) {
+                self.files.remove(pos);
+                record("files", Some(file.clone()), None);
+            }
+        }
+        for file in &edit.add_files {
+            if !self.files.contains(file) {
+                self.files.push(file.clone());
+                record("files", None, Some(file.clone()));
+            }
+        }
+        for dep in &edit.remove_depends_on {
+            if let Some(pos) = self.depends_on.iter().position(|d| d == dep) {
+                self.depends_on.remove(pos);
+                record("depends_on", Some(dep.clone()), None);
+            }
+        }
+        for dep in &edit.add_depends_on {
+            if !self.depends_on.contains(dep) {
+                self.depends_on.push(dep.clone());
+                record("depends_on", None, Some(dep.clone()));
+            }
+        }
+
+        if !fields.is_empty() {
+            self.history.push(MetadataEdit {
+                by: by.into(),
+                at: at.into(),
+                fields: fields.clone(),
+            });
+        }
+        fields
+    }
+
+    /// Add a category
     pub fn with_category(mut self, category: ChangeCategory) -> Self {
-        self.category = Some(category);
+        if !self.categories.contains(&category) {
+            self.categories.push(category);
+        }
         self
     }
 
+    pub fn has_category(&self, category: &ChangeCategory) -> bool {
+        self.categories.contains(category)
+    }
+
+    /// Flagged breaking or in the `breaking` category
+    pub fn is_breaking(&self) -> bool {
+        self.breaking || self.has_category(&ChangeCategory::Breaking)
+    }
+
     /// Set files changed
     pub fn with_files(mut self, files: Vec<String>) -> Self {
         self.files = files;
         self
     }
 
+    /// Set the changes this one depends on
+    pub fn with_depends_on(mut self, depends_on: Vec<String>) -> Self {
+        self.depends_on = depends_on;
+        self
+    }
+
+    /// Set repository-defined fields
+    pub fn with_fields(mut self, fields: BTreeMap<String, String>) -> Self {
+        self.fields = fields;
+        self
+    }
+
+    /// Set the diff size
+    pub fn with_metrics(mut self, metrics: ChangeMetrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
     /// Mark as breaking change
     pub fn breaking(mut self) -> Self {
         self.breaking = true;
         self
     }
 
+    /// Conventional-commit type: docs, test, refactor, and deps changes by
+    /// their type, others by category (fix, then perf, feat, chore; custom
+    /// categories have none), falling back to feat or chore
+    pub fn conventional_type(&self) -> &'static str {
+        use ChangeCategory::*;
+        let has = |categories: &[ChangeCategory]| categories.iter().any(|c| self.has_category(c));
+        match self.change_type {
+            ChangeType::Docs => "docs",
+            ChangeType::Test => "test",
+            ChangeType::Refactor => "refactor",
+            ChangeType::Deps => "build",
+            _ if has(&[Fix, Security]) => "fix",
+            _ if has(&[Perf]) => "perf",
+            _ if has(&[Feature, Breaking]) => "feat",
+            _ if has(&[Chore, Deprecation]) => "chore",
+            ChangeType::Behavioral | ChangeType::Schema => "feat",
+            ChangeType::Config => "chore",
+        }
+    }
+
+    /// A commit description generated from the metadata: a conventional
+    /// subject (`fix!: intent`), then fields and any breaking note as footers
+    pub fn description(&self) -> String {
+        let breaking = self.is_breaking();
+        let (summary, body) = self
+            .intent
+            .trim()
+            .split_once('\n')
+            .unwrap_or((self.intent.trim(), ""));
+        let mut lines = vec![format!(
+            "{}{}: {}",
+            self.conventional_type(),
+            if breaking { "!" } else { "" },
+            summary.trim()
+        )];
+        if !body.trim().is_empty() {
+            lines.push(String::new());
+            lines.push(body.trim().to_string());
+        }
+        let mut footers: Vec<String> = self
+            .fields
+            .iter()
+            .map(|(name, value)| format!("{}: {}", name, value))
+            .collect();
+        if breaking {
+            footers.push(format!("BREAKING CHANGE: {}", summary.trim()));
+        }
+        if !footers.is_empty() {
+            lines.push(String::new());
+            lines.extend(footers);
+        }
+        lines.join("\n") + "\n"
+    }
+
     /// Storage path for this change's metadata
     pub fn storage_path(&self) -> String {
         format!(".agent/changes/{}.toml", self.change_id)
@@ -194,6 +583,207 @@ impl TypedChange {
     }
 }
 
+/// A commit message in conventional-commit form: `type(scope)!: subject`,
+/// an optional body, and optional `token: value` footers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConventionalCommit {
+    /// The type, lowercased (`feat`, `fix`, ...)
+    pub kind: String,
+    pub scope: Option<String>,
+    /// A `!` after the type or a `BREAKING CHANGE:` footer
+    pub breaking: bool,
+    pub subject: String,
+    pub body: String,
+    /// Footers other than `BREAKING CHANGE`, in order
+    pub footers: Vec<(String, String)>,
+}
+
+impl ConventionalCommit {
+    /// Parse a message; `None` when its first line is not a conventional header
+    pub fn parse(message: &str) -> Option<Self> {
+        let message = message.trim();
+        let (header, rest) = message.split_once('\n').unwrap_or((message, ""));
+        let (prefix, subject) = header.split_once(':')?;
+        let subject = subject.trim();
+        let (prefix, bang) = match prefix.strip_suffix('!') {
+            Some(prefix) => (prefix, true),
+            None => (prefix, false),
+        };
+        let (kind, scope) = match prefix.split_once('(') {
+            Some((kind, scope)) => (kind, Some(scope.strip_suffix(')')?.trim())),
+            None => (prefix, None),
+        };
+        if kind.is_empty() || !kind.chars().all(|c| c.is_ascii_alphabetic()) || subject.is_empty() {
+            return None;
+        }
+
+        // Footers are the last paragraph, when every line of it is one
+        let rest = rest.trim();
+        let (body, last) = match rest.rsplit_once("\n\n") {
+            Some((body, last)) => (body.trim(), last),
+            None => ("", rest),
+        };
+        let footer = |line: &str| -> Option<(String, String)> {
+            let (token, value) = line.split_once(": ")?;
+            let token = token.trim();
+            let valid = token == "BREAKING CHANGE"
+                || (!token.is_empty()
+                    && token
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+            valid.then(|| (token.to_string(), value.trim().to_string()))
+        };
+        let parsed: Option<Vec<_>> = last.lines().map(footer).collect();
+        let (body, footers) = match parsed {
+            Some(footers) if !last.is_empty() => (body, footers),
+            _ => (rest, Vec::new()),
+        };
+        let breaking_footer = footers
+            .iter()
+            .any(|(token, _)| token == "BREAKING CHANGE" || token == "BREAKING-CHANGE");
+        Some(Self {
+            kind: kind.to_ascii_lowercase(),
+            scope: scope.filter(|s| !s.is_empty()).map(String::from),
+            breaking: bang || breaking_footer,
+            subject: subject.to_string(),
+            body: body.to_string(),
+            footers: footers
+                .into_iter()
+                .filter(|(token, _)| token != "BREAKING CHANGE" && token != "BREAKING-CHANGE")
+                .collect(),
+        })
+    }
+
+    /// The change type and category the commit type implies; `None` for
+    /// types with no clear meaning
+    pub fn classify(&self) -> Option<(ChangeType, Option<ChangeCategory>)> {
+        let deps = self.scope.as_deref() == Some("deps");
+        Some(match self.kind.as_str() {
+            "feat" | "feature" => (ChangeType::Behavioral, Some(ChangeCategory::Feature)),
+            "fix" | "bugfix" | "hotfix" => (ChangeType::Behavioral, Some(ChangeCategory::Fix)),
+            "perf" => (ChangeType::Behavioral, Some(ChangeCategory::Perf)),
+            "security" | "sec" => (ChangeType::Behavioral, Some(ChangeCategory::Security)),
+            "deprecate" => (ChangeType::Behavioral, Some(ChangeCategory::Deprecation)),
+            "revert" => (ChangeType::Behavioral, None),
+            "docs" | "doc" => (ChangeType::Docs, None),
+            "test" | "tests" => (ChangeType::Test, None),
+            "refactor" | "style" => (ChangeType::Refactor, None),
+            "deps" => (ChangeType::Deps, Some(ChangeCategory::Chore)),
+            "build" | "chore" if deps => (ChangeType::Deps, Some(ChangeCategory::Chore)),
+            "build" | "chore" | "ci" => (ChangeType::Config, Some(ChangeCategory::Chore)),
+            _ => return None,
+        })
+    }
+
+    /// Typed metadata for the commit, or `None` when its type is unknown.
+    /// The intent is the subject and body; lowercase footers become fields.
+    pub fn to_typed_change(&self, change_id: impl Into<String>) -> Option<TypedChange> {
+        let (change_type, category) = self.classify()?;
+        let intent = if self.body.is_empty() {
+            self.subject.clone()
+        } else {
+            format!("{}\n\n{}", self.subject, self.body)
+        };
+        let mut change = TypedChange::new(change_id, change_type, intent);
+        change.categories = category.into_iter().collect();
+        change.breaking = self.breaking;
+        change.fields = self
+            .footers
+            .iter()
+            .filter(|(token, _)| {
+                token
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+            })
+            .cloned()
+            .collect();
+        if let Some(scope) = &self.scope {
+            change.metadata.insert("scope".into(), scope.clone());
+        }
+        Some(change)
+    }
+}
+
+/// Order for `ChangeIndex::query`; each puts the largest or newest first
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeSort {
+    /// By `created_at`; undated changes last
+    #[default]
+    Created,
+    /// By number of recorded files
+    Files,
+    /// By lines added plus removed, from the recorded metrics
+    Churn,
+}
+
+/// Filters for `change list`; unset fields match everything
+#[derive(Debug, Clone, Default)]
+pub struct ChangeQuery {
+    pub change_type: Option<ChangeType>,
+    /// Categories a change must all have
+    pub categories: Vec<ChangeCategory>,
+    pub breaking: bool,
+    /// Created at or after this timestamp or date prefix (`2026-01-15`)
+    pub since: Option<String>,
+    /// Created at or before; a date prefix includes the whole day or month
+    pub until: Option<String>,
+    /// Case-insensitive substring of the author
+    pub author: Option<String>,
+    /// Glob that at least one recorded file must match
+    pub path: Option<String>,
+    pub sort: ChangeSort,
+    pub limit: Option<usize>,
+}
+
+impl ChangeQuery {
+    pub fn matches(&self, change: &TypedChange) -> bool {
+        if self.change_type.is_some_and(|t| t != change.change_type)
+            || !self.categories.iter().all(|c| change.has_category(c))
+            || (self.breaking && !change.is_breaking())
+        {
+            return false;
+        }
+        // Time bounds exclude changes with no known time
+        let created = change.created_at.as_deref();
+        if let Some(since) = &self.since {
+            if created.is_none_or(|c| c < since.as_str()) {
+                return false;
+            }
+        }
+        if let Some(until) = &self.until {
+            if created.is_none_or(|c| c.get(..until.len()).unwrap_or(c) > until.as_str()) {
+                return false;
+            }
+        }
+        if let Some(author) = &self.author {
+            let author = author.to_lowercase();
+            if !change
+                .author
+                .as_deref()
+                .is_some_and(|a| a.to_lowercase().contains(&author))
+            {
+                return false;
+            }
+        }
+        if let Some(path) = &self.path {
+            let Ok(pattern) = crate::globs::Globs::new([path]) else {
+                return false;
+            };
+            // A bare directory matches the files under it
+            let dir = format!("{}/", path.trim_end_matches('/'));
+            if !change
+                .files
+                .iter()
+                .any(|f| pattern.is_match(f) || f.starts_with(&dir))
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
+
 /// Index of all typed changes in a repo
 #[derive(Debug, Default)]
 pub struct ChangeIndex {
@@ -248,6 +838,41 @@ impl ChangeIndex {
     pub fn insert(&mut self, change: TypedChange) {
         self.changes.insert(change.change_id.clone(), change);
     }
+
+    /// Fill in `created_at` and `author` for changes recorded before they
+    /// were stored, from `lookup` (typically the commit's own time and author)
+    pub fn backfill(&mut self, lookup: impl Fn(&str) -> Option<(String, String)>) {
+        for change in self.changes.values_mut() {
+            if change.created_at.is_some() && change.author.is_some() {
+                continue;
+            }
+            if let Some((created_at, author)) = lookup(&change.change_id) {
+                change.created_at.get_or_insert(created_at);
+                change.author.get_or_insert(author);
+            }
+        }
+    }
+
+    /// Changes matching `query`, sorted and limited as it asks. Ties are
+    /// broken by change ID so the order is stable.
+    pub fn query(&self, query: &ChangeQuery) -> Vec<&TypedChange> {
+        let mut changes: Vec<&TypedChange> =
+            self.changes.values().filter(|c| query.matches(c)).collect();
+        let churn = |c: &TypedChange| c.metrics.as_ref().map_or(0, |m| m.churn);
+        changes.sort_by(|a, b| {
+            let order = match query.sort {
+                // None sorts before Some, so reversing puts undated last
+                ChangeSort::Created => b.created_at.cmp(&a.created_at),
+                ChangeSort::Files => b.files.len().cmp(&a.files.len()),
+                ChangeSort::Churn => churn(b).cmp(&churn(a)),
+            };
+            order.then_with(|| a.change_id.cmp(&b.change_id))
+        });
+        if let Some(limit) = query.limit {
+            changes.truncate(limit);
+        }
+        changes
+    }
 }
 
 #[cfg(test)]
@@ -265,20 +890,96 @@ mod tests {
 
         assert_eq!(change.change_id, "qpvuntsm");
         assert_eq!(change.change_type, ChangeType::Behavioral);
-        assert_eq!(change.category, Some(ChangeCategory::Feature));
+        assert_eq!(change.categories, vec![ChangeCategory::Feature]);
         assert_eq!(change.files.len(), 2);
     }
 
+    #[test]
+    fn several_and_custom_categories() {
+        let change = TypedChange::new("abc", ChangeType::Behavioral, "Cache lookups")
+            .with_category(ChangeCategory::Perf)
+            .with_category(ChangeCategory::Breaking)
+            .with_category(ChangeCategory::Custom("compliance".into()))
+            .with_category(ChangeCategory::Perf);
+        assert_eq!(change.categories.len(), 3);
+        assert!(change.is_breaking());
+        assert_eq!(change.conventional_type(), "perf");
+        assert!(change.description().starts_with("perf!: Cache lookups"));
+
+        let toml = change.to_toml().unwrap();
+        assert!(toml.contains("categories = ["));
+        assert_eq!(
+            TypedChange::parse(&toml).unwrap().categories,
+            change.categories
+        );
+
+        // Records from before a change could have several categories
+        let legacy = TypedChange::parse(
+            "change_id = \"old\"\ntype = \"behavioral\"\ncategory = \"fix\"\nintent = \"x\"\n",
+        )
+        .unwrap();
+        assert_eq!(legacy.categories, vec![ChangeCategory::Fix]);
+        assert_eq!(
+            ChangeCategory::from("i18n".to_string()),
+            ChangeCategory::Custom("i18n".into())
+        );
+    }
+
+    #[test]
+    fn metrics_count_lines_per_file() {
+        use crate::unified::{FileChange, Side};
+        let side = |content: &[u8]| {
+            Some(Side {
+                content: content.to_vec(),
+                mode: "100644",
+            })
+        };
+        let metrics = ChangeMetrics::from_changes(&[
+            FileChange {
+                path: "src/lib.rs".into(),
+                before: side(b"fn a() {}\nfn b() {}\n"),
+                after: side(b"fn a() {}\nfn c() {}\nfn d() {}\n"),
+            },
+            FileChange {
+                path: "logo.png".into(),
+                before: None,
+                after: side(b"\x89PNG\0\0"),
+            },
+            FileChange {
+                path: "old.txt".into(),
+                before: side(b"gone\n"),
+                after: None,
+            },
+        ]);
+
+        assert_eq!(
+            metrics.file("src/lib.rs"),
+            Some(&FileStats {
+                path: "src/lib.rs".into(),
+                additions: 2,
+                deletions: 1,
+                binary: false,
+            })
+        );
+        assert!(metrics.file("logo.png").unwrap().binary);
+        assert_eq!(metrics.file("old.txt").unwrap().deletions, 1);
+        assert_eq!((metrics.additions, metrics.deletions), (2, 2));
+        assert_eq!(metrics.churn, 4);
+        assert_eq!(metrics.binary_files, 1);
+    }
+
     #[test]
     fn roundtrip_toml() {
         let change = TypedChange::new("qpvuntsm", ChangeType::Refactor, "Clean up imports")
-            .with_files(vec!["src/api.py".into()]);
+            .with_files(vec!["src/api.py".into()])
+            .with_fields(BTreeMap::from([("ticket".into(), "ENG-42".into())]));
 
         let toml = change.to_toml().unwrap();
         let reparsed = TypedChange::parse(&toml).unwrap();
 
         assert_eq!(change.change_id, reparsed.change_id);
         assert_eq!(change.intent, reparsed.intent);
+        assert_eq!(reparsed.fields["ticket"], "ENG-42");
     }
 
     #[test]
@@ -301,6 +1002,189 @@ status = "passed"
         assert_eq!(change.invariants.status, InvariantStatus::Passed);
     }
 
+    #[test]
+    fn apply_edit_records_history() {
+        let mut change = TypedChange::new("abc123", ChangeType::Behavioral, "Add retry")
+            .with_files(vec!["src/a.rs".into(), "src/b.rs".into()]);
+
+        let edit = ChangeEdit {
+            intent: Some("Add retry with backoff".into()),
+            categories: Some(vec![ChangeCategory::Fix]),
+            add_files: vec!["src/c.rs".into(), "src/a.rs".into()],
+            remove_files: vec!["src/b.rs".into(), "src/missing.rs".into()],
+            add_depends_on: vec!["def456".into()],
+            ..Default::default()
+        };
+        let fields = change.apply_edit(&edit, "agent-1", "2026-01-02T03:04:05Z");
+
+        // Only real changes are recorded
+        assert_eq!(fields.len(), 5);
+        assert_eq!(change.depends_on, vec!["def456"]);
+        assert_eq!(change.intent, "Add retry with backoff");
+        assert_eq!(change.categories, vec![ChangeCategory::Fix]);
+        assert_eq!(change.files, vec!["src/a.rs", "src/c.rs"]);
+        assert_eq!(fields[1].field, "categories");
+        assert_eq!(fields[1].before, None);
+        assert_eq!(fields[1].after.as_deref(), Some("fix"));
+
+        // A no-op edit leaves history alone
+        assert!(change.apply_edit(&edit, "agent-1", "later").is_empty());
+        assert_eq!(change.history.len(), 1);
+
+        let reparsed = TypedChange::parse(&change.to_toml().unwrap()).unwrap();
+        assert_eq!(reparsed.history, change.history);
+        assert_eq!(reparsed.history[0].by, "agent-1");
+    }
+
+    #[test]
+    fn description_from_metadata() {
+        let change = TypedChange::new("abc", ChangeType::Behavioral, "Retry failed uploads")
+            .with_category(ChangeCategory::Fix);
+        assert_eq!(change.description(), "fix: Retry failed uploads\n");
+
+        let mut fields = BTreeMap::new();
+        fields.insert("migration_id".to_string(), "0042".to_string());
+        let change = TypedChange::new(
+            "def",
+            ChangeType::Schema,
+            "Drop legacy column\n\nUnused since 2.0.",
+        )
+        .with_fields(fields)
+        .breaking();
+        assert_eq!(
+            change.description(),
+            "feat!: Drop legacy column\n\nUnused since 2.0.\n\nmigration_id: 0042\nBREAKING CHANGE: Drop legacy column\n"
+        );
+
+        let docs = TypedChange::new("ghi", ChangeType::Docs, "Explain retries")
+            .with_category(ChangeCategory::Fix);
+        assert_eq!(docs.conventional_type(), "docs");
+    }
+
+    #[test]
+    fn query_filters_sorts_and_limits() {
+        let change = |id: &str, created: Option<&str>, author: &str, files: &[&str]| {
+            let mut c = TypedChange::new(id, ChangeType::Behavioral, id)
+                .with_files(files.iter().map(|f| f.to_string()).collect());
+            c.created_at = created.map(String::from);
+            c.author = Some(author.to_string());
+            c
+        };
+        let mut index = ChangeIndex::default();
+        index.insert(change(
+            "aaa",
+            Some("2026-01-10T09:00:00Z"),
+            "agent-1",
+            &["src/api/users.rs"],
+        ));
+        index.insert(
+            change(
+                "bbb",
+                Some("2026-02-01T12:00:00Z"),
+                "Agent-2",
+                &["src/api/auth.rs", "src/db.rs", "README.md"],
+            )
+            .with_category(ChangeCategory::Fix),
+        );
+        index.insert(change("ccc", None, "agent-1", &["docs/guide.md"]));
+
+        let ids = |query: &ChangeQuery| -> Vec<String> {
+            index
+                .query(query)
+                .iter()
+                .map(|c| c.change_id.clone())
+                .collect()
+        };
+        // Newest first, undated last
+        assert_eq!(ids(&ChangeQuery::default()), vec!["bbb", "aaa", "ccc"]);
+
+        let january = ChangeQuery {
+            since: Some("2026-01".into()),
+            until: Some("2026-01".into()),
+            ..Default::default()
+        };
+        assert_eq!(ids(&january), vec!["aaa"]);
+
+        let by_author = ChangeQuery {
+            author: Some("agent-2".into()),
+            ..Default::default()
+        };
+        assert_eq!(ids(&by_author), vec!["bbb"]);
+
+        let api = ChangeQuery {
+            path: Some("src/api".into()),
+            sort: ChangeSort::Files,
+            ..Default::default()
+        };
+        assert_eq!(ids(&api), vec!["bbb", "aaa"]);
+        let rust = ChangeQuery {
+            path: Some("src/*.rs".into()),
+            ..Default::default()
+        };
+        assert_eq!(ids(&rust), vec!["bbb"]);
+
+        let fixes = ChangeQuery {
+            categories: vec![ChangeCategory::Fix],
+            limit: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(ids(&fixes), vec!["bbb"]);
+
+        index.backfill(|id| (id == "ccc").then(|| ("2025-12-24T00:00:00Z".into(), "git".into())));
+        assert_eq!(
+            index.get("ccc").unwrap().created_at.as_deref(),
+            Some("2025-12-24T00:00:00Z")
+        );
+        assert_eq!(index.get("ccc").unwrap().author.as_deref(), Some("agent-1"));
+    }
+
+    #[test]
+    fn parses_conventional_commits() {
+        let commit = ConventionalCommit::parse(
+            "feat(api)!: Drop v1 endpoints\n\nClients moved to v2.\n\nmigration_id: 0042\nSigned-off-by: Dev <dev@example.com>\nBREAKING CHANGE: v1 is gone",
+        )
+        .unwrap();
+        assert_eq!(commit.kind, "feat");
+        assert_eq!(commit.scope.as_deref(), Some("api"));
+        assert!(commit.breaking);
+        assert_eq!(commit.body, "Clients moved to v2.");
+        assert_eq!(commit.footers.len(), 2);
+
+        let typed = commit.to_typed_change("abc").unwrap();
+        assert_eq!(typed.change_type, ChangeType::Behavioral);
+        assert_eq!(typed.categories, vec![ChangeCategory::Feature]);
+        assert!(typed.breaking);
+        assert_eq!(typed.intent, "Drop v1 endpoints\n\nClients moved to v2.");
+        assert_eq!(
+            typed.fields.get("migration_id").map(String::as_str),
+            Some("0042")
+        );
+        assert!(!typed.fields.contains_key("Signed-off-by"));
+        assert_eq!(typed.metadata["scope"], "api");
+
+        // Breaking via footer only; the body is kept when no footers follow
+        let commit =
+            ConventionalCommit::parse("fix: handle nulls\n\nBREAKING CHANGE: returns Option")
+                .unwrap();
+        assert!(commit.breaking);
+        let commit = ConventionalCommit::parse("docs: explain retries\n\nSee the guide.").unwrap();
+        assert_eq!(commit.body, "See the guide.");
+        assert!(commit.footers.is_empty());
+
+        let deps = ConventionalCommit::parse("chore(deps): bump serde").unwrap();
+        assert_eq!(
+            deps.classify(),
+            Some((ChangeType::Deps, Some(ChangeCategory::Chore)))
+        );
+
+        assert!(ConventionalCommit::parse("Update README").is_none());
+        assert!(ConventionalCommit::parse("Merge branch 'main': sync").is_none());
+        assert!(ConventionalCommit::parse("wip: stuff")
+            .unwrap()
+            .to_typed_change("x")
+            .is_none());
+    }
+
     #[test]
     fn storage_path() {
         let change = TypedChange::new("abc123", ChangeType::Docs, "Update readme");
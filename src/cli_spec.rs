@@ -0,0 +1,473 @@
+// ABOUTME: `cli-spec`: the whole command tree as machine-readable JSON, built from the clap definitions
+// ABOUTME: Typed flags and defaults, a JSON Schema per command for tool definitions, and output schema names
+
+use serde::Serialize;
+use std::any::TypeId;
+use std::collections::BTreeMap;
+
+use crate::skill::{schema_for, usage_token, value_name};
+
+/// Bumped when the shape of the spec changes
+pub const SPEC_VERSION: u32 = 1;
+
+/// Everything an agent framework needs to call agentjj
+#[derive(Debug, Clone, Serialize)]
+pub struct CliSpec {
+    pub spec_version: u32,
+    pub name: String,
+    pub version: String,
+    /// Flags every command accepts (`--json`, `--timeout`, ...)
+    pub global_args: Vec<ArgSpec>,
+    /// Process exit codes by name
+    pub exit_codes: BTreeMap<&'static str, i32>,
+    /// Every command and subcommand, depth first
+    pub commands: Vec<CommandSpec>,
+}
+
+/// A command by its full path (e.g. `env promote`)
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandSpec {
+    pub name: String,
+    /// `name` split into words, as they go on the command line
+    pub path: Vec<String>,
+    pub about: String,
+    pub usage: String,
+    pub args: Vec<ArgSpec>,
+    /// Groups of arg ids of which at least one must be given (e.g. `--intent`
+    /// or `--patch`)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub required_groups: Vec<Vec<String>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub subcommands: Vec<String>,
+    /// False for groups such as `env`, which need a subcommand
+    pub runnable: bool,
+    /// Whether the command, without optional flags, leaves the repository
+    /// unchanged (and so runs in read-only mode); absent for groups
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_only: Option<bool>,
+    /// `schema --type` name describing the `--json` output, when there is one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_schema: Option<&'static str>,
+    /// JSON Schema of the arguments, keyed by arg `id`, for function calling
+    pub parameters: serde_json::Value,
+}
+
+/// One flag, option, or positional argument
+#[derive(Debug, Clone, Serialize)]
+pub struct ArgSpec {
+    /// Key in `parameters`
+    pub id: String,
+    /// "flag" (no value), "option" (takes a value), or "positional"
+    pub kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub long: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub short: Option<char>,
+    /// 1-based position of a positional argument
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value_name: Option<String>,
+    /// "boolean", "count", "integer", "number", "path", or "string"
+    pub r#type: &'static str,
+    /// Takes several values, by repeating the flag or, for positionals, in a row
+    pub multiple: bool,
+    /// Separator for several values in one (e.g. `,`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delimiter: Option<char>,
+    pub required: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub values: Vec<String>,
+    /// Must come after `--` (it takes the rest of the command line)
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub last: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub conflicts_with: Vec<String>,
+    pub help: String,
+}
+
+/// Build the spec from the clap command tree. `read_only` is asked about
+/// each runnable command with a sample command line (required arguments
+/// filled in) and answers None when it cannot tell.
+pub fn build(root: &clap::Command, read_only: impl Fn(&[String]) -> Option<bool>) -> CliSpec {
+    let mut commands = Vec::new();
+    collect(root, root, &[], &read_only, &mut commands);
+    CliSpec {
+        spec_version: SPEC_VERSION,
+        name: root.get_name().to_string(),
+        version: root.get_version().unwrap_or_default().to_string(),
+        global_args: root
+            .get_arguments()
+            .filter(|a| !a.is_hide_set())
+            .map(|a| arg_spec(root, a))
+            .collect(),
+        exit_codes: crate::error::exit::ALL.into_iter().collect(),
+        commands,
+    }
+}
+
+fn collect(
+    root: &clap::Command,
+    cmd: &clap::Command,
+    prefix: &[String],
+    read_only: &impl Fn(&[String]) -> Option<bool>,
+    out: &mut Vec<CommandSpec>,
+) {
+    for sub in cmd.get_subcommands().filter(|s| !s.is_hide_set()) {
+        let mut path = prefix.to_vec();
+        path.push(sub.get_name().to_string());
+        let name = path.join(" ");
+        let clap_args: Vec<&clap::Arg> = sub
+            .get_arguments()
+            .filter(|a| !a.is_hide_set() && !a.is_global_set())
+            .collect();
+        let args: Vec<ArgSpec> = clap_args.iter().map(|a| arg_spec(sub, a)).collect();
+        let required_groups: Vec<Vec<String>> = sub
+            .get_groups()
+            .filter(|g| g.is_required_set())
+            .map(|g| g.get_args().map(|id| id.to_string()).collect())
+            .collect();
+        let mut usage = format!("agentjj {}", name);
+        for arg in &clap_args {
+            usage.push(' ');
+            usage.push_str(&usage_token(arg));
+        }
+        let runnable = !sub.has_subcommands() || !sub.is_subcommand_required_set();
+        if sub.has_subcommands() {
+            usage.push_str(if runnable { " [COMMAND]" } else { " <COMMAND>" });
+        }
+        out.push(CommandSpec {
+            read_only: runnable
+                .then(|| read_only(&sample(root, &path, &args, &required_groups)))
+                .flatten(),
+            output_schema: schema_for(&name),
+            parameters: parameters(&args),
+            path: path.clone(),
+            about: sub.get_about().map(|s| s.to_string()).unwrap_or_default(),
+            usage,
+            args,
+            required_groups,
+            subcommands: sub
+                .get_subcommands()
+                .filter(|s| !s.is_hide_set())
+                .map(|s| s.get_name().to_string())
+                .collect(),
+            runnable,
+            name,
+        });
+        collect(root, sub, &path, read_only, out);
+    }
+}
+
+/// The argument's type, from its action and value parser
+fn value_type(arg: &clap::Arg) -> &'static str {
+    match arg.get_action() {
+        clap::ArgAction::SetTrue | clap::ArgAction::SetFalse => return "boolean",
+        clap::ArgAction::Count => return "count",
+        _ => {}
+    }
+    let id = arg.get_value_parser().type_id();
+    let is = |t: TypeId| id == t;
+    if [
+        TypeId::of::<u8>(),
+        TypeId::of::<u16>(),
+        TypeId::of::<u32>(),
+        TypeId::of::<u64>(),
+        TypeId::of::<usize>(),
+        TypeId::of::<i8>(),
+        TypeId::of::<i16>(),
+        TypeId::of::<i32>(),
+        TypeId::of::<i64>(),
+        TypeId::of::<isize>(),
+    ]
+    .into_iter()
+    .any(is)
+    {
+        "integer"
+    } else if is(TypeId::of::<f32>()) || is(TypeId::of::<f64>()) {
+        "number"
+    } else if is(TypeId::of::<std::path::PathBuf>()) {
+        "path"
+    } else {
+        "string"
+    }
+}
+
+fn arg_spec(cmd: &clap::Command, arg: &clap::Arg) -> ArgSpec {
+    let takes_values = arg.get_action().takes_values();
+    let position = arg.is_positional().then(|| {
+        cmd.get_positionals()
+            .position(|p| p.get_id() == arg.get_id())
+            .unwrap_or(0)
+            + 1
+    });
+    let default = arg
+        .get_default_values()
+        .iter()
+        .map(|v| v.to_string_lossy().into_owned())
+        .collect::<Vec<_>>()
+        .join(",");
+    ArgSpec {
+        id: arg.get_id().to_string(),
+        kind: if arg.is_positional() {
+            "positional"
+        } else if takes_values {
+            "option"
+        } else {
+            "flag"
+        },
+        long: arg.get_long().map(str::to_string),
+        short: arg.get_short(),
+        position,
+        value_name: takes_values.then(|| value_name(arg)),
+        r#type: value_type(arg),
+        multiple: matches!(arg.get_action(), clap::ArgAction::Append)
+            || arg.get_num_args().is_some_and(|n| n.max_values() > 1),
+        delimiter: arg.get_value_delimiter(),
+        required: arg.is_required_set(),
+        default: (!default.is_empty()).then_some(default),
+        // Switches report true/false as possible values; only list real choices
+        values: if takes_values {
+            arg.get_possible_values()
+                .iter()
+                .map(|v| v.get_name().to_string())
+                .collect()
+        } else {
+            Vec::new()
+        },
+        last: arg.is_last_set(),
+        conflicts_with: cmd
+            .get_arg_conflicts_with(arg)
+            .iter()
+            .map(|a| a.get_id().to_string())
+            .collect(),
+        help: arg.get_help().map(|s| s.to_string()).unwrap_or_default(),
+    }
+}
+
+/// JSON Schema of a command's arguments
+fn parameters(args: &[ArgSpec]) -> serde_json::Value {
+    let mut properties = serde_json::Map::new();
+    for arg in args {
+        let mut value = serde_json::json!({
+            "type": match arg.r#type {
+                "count" => "integer",
+                "path" => "string",
+                other => other,
+            },
+        });
+        if !arg.values.is_empty() {
+            value["enum"] = serde_json::json!(arg.values);
+        }
+        if let Some(default) = &arg.default {
+            value["default"] = match arg.r#type {
+                "boolean" => serde_json::json!(default == "true"),
+                "integer" | "count" => default
+                    .parse::<i64>()
+                    .map_or_else(|_| serde_json::json!(default), |n| serde_json::json!(n)),
+                "number" => default
+                    .parse::<f64>()
+                    .map_or_else(|_| serde_json::json!(default), |n| serde_json::json!(n)),
+                _ => serde_json::json!(default),
+            };
+        }
+        if arg.multiple {
+            value = serde_json::json!({ "type": "array", "items": value });
+        }
+        value["description"] = serde_json::json!(arg.help);
+        properties.insert(arg.id.clone(), value);
+    }
+    let required: Vec<&str> = args
+        .iter()
+        .filter(|a| a.required)
+        .map(|a| a.id.as_str())
+        .collect();
+    serde_json::json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false,
+    })
+}
+
+/// A command line for `path` with every required argument filled in, and
+/// the first argument of each required group. Arguments that are required
+/// unless another is given are added as clap reports them missing.
+fn sample(
+    root: &clap::Command,
+    path: &[String],
+    args: &[ArgSpec],
+    groups: &[Vec<String>],
+) -> Vec<String> {
+    let mut needed: Vec<&ArgSpec> = args
+        .iter()
+        .filter(|a| a.required || groups.iter().any(|g| g.first() == Some(&a.id)))
+        .collect();
+    loop {
+        let argv = render(path, &needed);
+        let Err(err) = root.clone().try_get_matches_from(&argv) else {
+            return argv;
+        };
+        let missing = match err.get(clap::error::ContextKind::InvalidArg) {
+            Some(clap::error::ContextValue::Strings(tokens))
+                if err.kind() == clap::error::ErrorKind::MissingRequiredArgument =>
+            {
+                tokens.clone()
+            }
+            _ => return argv,
+        };
+        let before = needed.len();
+        for token in &missing {
+            let word = token.split_whitespace().next().unwrap_or_default();
+            let found = args.iter().find(|a| match (&a.long, &a.value_name) {
+                (Some(long), _) => word == format!("--{}", long),
+                (None, Some(name)) => word.trim_end_matches("...") == format!("<{}>", name),
+                (None, None) => false,
+            });
+            if let Some(arg) = found.filter(|a| !needed.iter().any(|n| n.id == a.id)) {
+                needed.push(arg);
+            }
+        }
+        if needed.len() == before {
+            return argv;
+        }
+    }
+}
+
+fn render(path: &[String], args: &[&ArgSpec]) -> Vec<String> {
+    let mut argv = vec!["agentjj".to_string()];
+    argv.extend(path.iter().cloned());
+    let value = |arg: &ArgSpec| match arg.values.first() {
+        Some(value) => value.clone(),
+        None if matches!(arg.r#type, "integer" | "count" | "number") => "1".to_string(),
+        None => "x".to_string(),
+    };
+    for arg in args.iter().filter(|a| a.kind != "positional") {
+        match (&arg.long, arg.short) {
+            (Some(long), _) => argv.push(format!("--{}", long)),
+            (None, Some(short)) => argv.push(format!("-{}", short)),
+            (None, None) => continue,
+        }
+        if arg.kind == "option" {
+            argv.push(value(arg));
+        }
+    }
+    let mut positionals: Vec<&&ArgSpec> = args.iter().filter(|a| a.kind == "positional").collect();
+    positionals.sort_by_key(|a| a.position);
+    for arg in positionals {
+        if arg.last {
+            argv.push("--".to_string());
+        }
+        argv.push(value(arg));
+    }
+    argv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{Arg, ArgAction, Command};
+
+    fn cli() -> Command {
+        Command::new("agentjj")
+            .version("1.2.3")
+            .arg(
+                Arg::new("json")
+                    .long("json")
+                    .global(true)
+                    .action(ArgAction::SetTrue)
+                    .help("Output as JSON"),
+            )
+            .subcommand(Command::new("status").about("Show current state"))
+            .subcommand(
+                Command::new("env")
+                    .about("Deployment environments")
+                    .subcommand_required(true)
+                    .subcommand(
+                        Command::new("promote")
+                            .about("Move an environment's bookmark")
+                            .arg(Arg::new("from").required(true).help("Source"))
+                            .arg(Arg::new("to").long("to").required(true))
+                            .arg(
+                                Arg::new("limit")
+                                    .long("limit")
+                                    .value_parser(clap::value_parser!(usize))
+                                    .default_value("10"),
+                            )
+                            .arg(
+                                Arg::new("path")
+                                    .short('p')
+                                    .long("path")
+                                    .action(ArgAction::Append)
+                                    .value_delimiter(','),
+                            )
+                            .arg(
+                                Arg::new("kind")
+                                    .long("kind")
+                                    .value_parser(["commits", "ops"]),
+                            )
+                            .arg(
+                                Arg::new("dry_run")
+                                    .long("dry-run")
+                                    .action(ArgAction::SetTrue)
+                                    .conflicts_with("kind"),
+                            ),
+                    ),
+            )
+    }
+
+    #[test]
+    fn spec_types_arguments_and_builds_parameter_schemas() {
+        let spec = build(&cli(), |argv| Some(argv[1] == "status"));
+        assert_eq!(spec.version, "1.2.3");
+        assert_eq!(spec.global_args[0].id, "json");
+        assert_eq!(spec.exit_codes["policy"], 7);
+        let names: Vec<&str> = spec.commands.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["status", "env", "env promote"]);
+        assert_eq!(spec.commands[0].read_only, Some(true));
+        assert_eq!(spec.commands[0].output_schema, Some("status"));
+        assert!(!spec.commands[1].runnable);
+        assert_eq!(spec.commands[1].read_only, None);
+
+        let promote = &spec.commands[2];
+        assert_eq!(promote.path, ["env", "promote"]);
+        assert_eq!(promote.read_only, Some(false));
+        let arg = |id: &str| promote.args.iter().find(|a| a.id == id).unwrap();
+        assert_eq!(arg("from").kind, "positional");
+        assert_eq!(arg("from").position, Some(1));
+        assert_eq!(arg("limit").r#type, "integer");
+        assert!(arg("path").multiple);
+        assert_eq!(arg("path").delimiter, Some(','));
+        assert_eq!(arg("dry_run").kind, "flag");
+        assert_eq!(arg("dry_run").r#type, "boolean");
+        assert_eq!(arg("dry_run").conflicts_with, ["kind"]);
+
+        assert_eq!(
+            promote.parameters["properties"]["limit"],
+            serde_json::json!({"type": "integer", "default": 10, "description": ""})
+        );
+        assert_eq!(
+            promote.parameters["properties"]["kind"]["enum"],
+            serde_json::json!(["commits", "ops"])
+        );
+        assert_eq!(
+            promote.parameters["properties"]["path"]["type"],
+            serde_json::json!("array")
+        );
+        assert_eq!(
+            promote.parameters["required"],
+            serde_json::json!(["from", "to"])
+        );
+        assert_eq!(
+            sample(
+                &cli(),
+                &promote.path,
+                &promote.args,
+                &promote.required_groups
+            ),
+            ["agentjj", "env", "promote", "--to", "x", "x"]
+        );
+    }
+}
@@ -0,0 +1,312 @@
+// ABOUTME: `compare <a> <b>`: two changes diffed directly, by file, symbol, metadata, and overlap
+// ABOUTME: Pure functions over tree changes and typed metadata, for arbitrating competing solutions
+
+use serde::Serialize;
+use std::path::Path;
+
+use crate::change::{category_list, enum_name, TypedChange};
+use crate::symbols::{extract_symbols, SupportedLanguage, Symbol};
+use crate::unified::FileChange;
+
+/// One side of the comparison
+#[derive(Debug, Clone, Serialize)]
+pub struct ChangeSide {
+    pub change_id: String,
+    pub commit_id: String,
+    /// Files the change touched relative to its own parent
+    pub files: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub typed: Option<TypedChange>,
+}
+
+/// How a path differs going from `a` to `b`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileStatus {
+    /// Only in `b`
+    Added,
+    /// Only in `a`
+    Removed,
+    Modified,
+}
+
+/// Symbols that differ in one file
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SymbolDiff {
+    pub only_in_a: Vec<String>,
+    pub only_in_b: Vec<String>,
+    /// In both, with different text
+    pub differing: Vec<String>,
+}
+
+impl SymbolDiff {
+    pub fn is_empty(&self) -> bool {
+        self.only_in_a.is_empty() && self.only_in_b.is_empty() && self.differing.is_empty()
+    }
+}
+
+/// A file whose content differs between the two changes
+#[derive(Debug, Clone, Serialize)]
+pub struct FileDiff {
+    pub path: String,
+    pub status: FileStatus,
+    /// Lines `b` has that `a` lacks; absent for binary files
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lines_added: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lines_removed: Option<usize>,
+    #[serde(skip_serializing_if = "SymbolDiff::is_empty")]
+    pub symbols: SymbolDiff,
+}
+
+/// A metadata field on both sides
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldComparison {
+    pub field: String,
+    pub a: Option<String>,
+    pub b: Option<String>,
+    pub same: bool,
+}
+
+/// How the files each change touched line up
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Overlap {
+    /// Touched by both and identical in the two results
+    pub agreeing: Vec<String>,
+    /// Touched by both with different results: the files to arbitrate
+    pub conflicting: Vec<String>,
+    pub only_a: Vec<String>,
+    pub only_b: Vec<String>,
+    /// Files touched by both over files touched by either, 0.0 to 1.0
+    pub ratio: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Comparison {
+    pub a: ChangeSide,
+    pub b: ChangeSide,
+    pub identical: bool,
+    pub files: Vec<FileDiff>,
+    /// Typed-change metadata; empty when neither change has any
+    pub metadata: Vec<FieldComparison>,
+    pub overlap: Overlap,
+}
+
+/// Compare two changes given the direct `a` to `b` tree changes
+pub fn compare(a: ChangeSide, b: ChangeSide, changes: &[FileChange]) -> Comparison {
+    let files: Vec<FileDiff> = changes.iter().map(file_diff).collect();
+    let metadata = metadata(a.typed.as_ref(), b.typed.as_ref());
+    let overlap = overlap(&a.files, &b.files, changes);
+    Comparison {
+        identical: files.is_empty(),
+        a,
+        b,
+        files,
+        metadata,
+        overlap,
+    }
+}
+
+pub(crate) fn file_diff(change: &FileChange) -> FileDiff {
+    let status = match (&change.before, &change.after) {
+        (None, _) => FileStatus::Added,
+        (_, None) => FileStatus::Removed,
+        _ => FileStatus::Modified,
+    };
+    let counts = change.line_counts();
+    FileDiff {
+        path: change.path.clone(),
+        status,
+        lines_added: counts.map(|(added, _)| added),
+        lines_removed: counts.map(|(_, removed)| removed),
+        symbols: symbol_diff(change),
+    }
+}
+
+/// Symbols by name and kind with their source text, for one side
+fn symbol_texts(source: &str, symbols: &[Symbol]) -> Vec<(String, String)> {
+    let lines: Vec<&str> = source.lines().collect();
+    crate::triage::flatten(symbols)
+        .into_iter()
+        .map(|s| {
+            let start = s.start_line.saturating_sub(1).min(lines.len());
+            let end = s.end_line.clamp(start, lines.len());
+            (
+                format!("{}:{}", enum_name(s.kind), s.name),
+                lines[start..end].join("\n"),
+            )
+        })
+        .collect()
+}
+
+fn symbol_diff(change: &FileChange) -> SymbolDiff {
+    let Some(lang) = SupportedLanguage::from_path(Path::new(&change.path)) else {
+        return SymbolDiff::default();
+    };
+    let side = |side: &Option<crate::unified::Side>| -> Vec<(String, String)> {
+        side.as_ref()
+            .and_then(|s| std::str::from_utf8(&s.content).ok())
+            .and_then(|source| {
+                let symbols = extract_symbols(source, lang).ok()?;
+                Some(symbol_texts(source, &symbols))
+            })
+            .unwrap_or_default()
+    };
+    let (a, b) = (side(&change.before), side(&change.after));
+    let name = |key: &String| {
+        key.split_once(':')
+            .map_or(key.clone(), |(_, n)| n.to_string())
+    };
+
+    let mut diff = SymbolDiff::default();
+    for (key, text) in &a {
+        match b.iter().find(|(k, _)| k == key) {
+            None => diff.only_in_a.push(name(key)),
+            Some((_, other)) if other != text => diff.differing.push(name(key)),
+            Some(_) => {}
+        }
+    }
+    for (key, _) in &b {
+        if !a.iter().any(|(k, _)| k == key) {
+            diff.only_in_b.push(name(key));
+        }
+    }
+    for list in [
+        &mut diff.only_in_a,
+        &mut diff.only_in_b,
+        &mut diff.differing,
+    ] {
+        list.sort();
+        list.dedup();
+    }
+    diff
+}
+
+fn metadata(a: Option<&TypedChange>, b: Option<&TypedChange>) -> Vec<FieldComparison> {
+    if a.is_none() && b.is_none() {
+        return Vec::new();
+    }
+    let field = |name: &str, get: &dyn Fn(&TypedChange) -> Option<String>| {
+        let (a, b) = (a.and_then(get), b.and_then(get));
+        FieldComparison {
+            field: name.to_string(),
+            same: a == b,
+            a,
+            b,
+        }
+    };
+    vec![
+        field("type", &|c| Some(enum_name(c.change_type))),
+        field("categories", &|c| category_list(&c.categories)),
+        field("breaking", &|c| Some(c.breaking.to_string())),
+        field("intent", &|c| Some(c.intent.clone())),
+    ]
+}
+
+fn overlap(a: &[String], b: &[String], changes: &[FileChange]) -> Overlap {
+    let mut overlap = Overlap::default();
+    for file in a {
+        if !b.contains(file) {
+            overlap.only_a.push(file.clone());
+        } else if changes.iter().any(|c| &c.path == file) {
+            overlap.conflicting.push(file.clone());
+        } else {
+            overlap.agreeing.push(file.clone());
+        }
+    }
+    overlap.only_b = b.iter().filter(|f| !a.contains(f)).cloned().collect();
+    for list in [
+        &mut overlap.agreeing,
+        &mut overlap.conflicting,
+        &mut overlap.only_a,
+        &mut overlap.only_b,
+    ] {
+        list.sort();
+        list.dedup();
+    }
+    let both = overlap.agreeing.len() + overlap.conflicting.len();
+    let either = both + overlap.only_a.len() + overlap.only_b.len();
+    if either > 0 {
+        overlap.ratio = both as f64 / either as f64;
+    }
+    overlap
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::change::{ChangeCategory, ChangeType};
+    use crate::unified::Side;
+
+    fn side(content: &str) -> Option<Side> {
+        Some(Side {
+            content: content.as_bytes().to_vec(),
+            mode: "100644",
+        })
+    }
+
+    fn change_side(id: &str, files: &[&str], typed: TypedChange) -> ChangeSide {
+        ChangeSide {
+            change_id: id.into(),
+            commit_id: format!("{}-commit", id),
+            files: files.iter().map(|f| f.to_string()).collect(),
+            typed: Some(typed),
+        }
+    }
+
+    #[test]
+    fn compares_files_symbols_metadata_and_overlap() {
+        let a = change_side(
+            "aaa",
+            &["src/retry.py", "README.md"],
+            TypedChange::new("aaa", ChangeType::Behavioral, "Add retry")
+                .with_category(ChangeCategory::Fix),
+        );
+        let b = change_side(
+            "bbb",
+            &["src/retry.py", "README.md", "tests/test_retry.py"],
+            TypedChange::new("bbb", ChangeType::Behavioral, "Add retry with backoff"),
+        );
+        let changes = vec![
+            FileChange {
+                path: "src/retry.py".into(),
+                before: side("def retry():\n    return 1\n\ndef old():\n    pass\n"),
+                after: side("def retry():\n    return 2\n\ndef backoff():\n    pass\n"),
+            },
+            FileChange {
+                path: "tests/test_retry.py".into(),
+                before: None,
+                after: side("def test_retry():\n    assert True\n"),
+            },
+        ];
+
+        let cmp = compare(a, b, &changes);
+        assert!(!cmp.identical);
+        assert_eq!(cmp.files[0].status, FileStatus::Modified);
+        assert_eq!(cmp.files[0].symbols.differing, vec!["retry"]);
+        assert_eq!(cmp.files[0].symbols.only_in_a, vec!["old"]);
+        assert_eq!(cmp.files[0].symbols.only_in_b, vec!["backoff"]);
+        assert_eq!(cmp.files[1].status, FileStatus::Added);
+        assert_eq!(cmp.files[1].lines_added, Some(2));
+
+        let category = cmp
+            .metadata
+            .iter()
+            .find(|f| f.field == "categories")
+            .unwrap();
+        assert_eq!(category.a.as_deref(), Some("fix"));
+        assert_eq!(category.b, None);
+        assert!(
+            cmp.metadata
+                .iter()
+                .find(|f| f.field == "type")
+                .unwrap()
+                .same
+        );
+
+        assert_eq!(cmp.overlap.conflicting, vec!["src/retry.py"]);
+        assert_eq!(cmp.overlap.agreeing, vec!["README.md"]);
+        assert_eq!(cmp.overlap.only_b, vec!["tests/test_retry.py"]);
+        assert!((cmp.overlap.ratio - 2.0 / 3.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,427 @@
+// ABOUTME: Layered configuration for agentjj behavior (.agent/config.toml, AGENTJJ_* env, --config)
+// ABOUTME: Centralizes defaults such as JSON output, change type, invariant/hook timeouts, and forge tokens
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::error::{Error, Result};
+
+/// Overrides passed on the command line with `--config key=value`.
+/// Set once by the CLI before any command runs; applied after env vars.
+static CLI_OVERRIDES: OnceLock<Vec<(String, String)>> = OnceLock::new();
+
+/// Every settable key, in `section.field` form. Env var names are derived
+/// from these (`output.json` -> `AGENTJJ_OUTPUT_JSON`).
+pub const KEYS: &[&str] = &[
+    "output.json",
+    "output.max_bytes",
+    "output.max_output_bytes",
+    "change.default_type",
+    "change.message_command",
+    "invariants.timeout_secs",
+    "hooks.timeout_secs",
+    "gc.keep_checkpoints",
+    "gc.checkpoint_max_age_days",
+    "gc.audit_max_age_days",
+    "index.enabled",
+    "index.exclude",
+    "glob.case_insensitive",
+    "cache.memory_bytes",
+    "cache.disk",
+    "cache.disk_max_bytes",
+    "cache.history",
+    "lfs.threshold_bytes",
+    "metrics.enabled",
+    "meta.members",
+    "auto.colocate",
+];
+
+/// Effective agentjj configuration after all layers are applied
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(default)]
+pub struct Config {
+    pub output: OutputConfig,
+    pub change: ChangeConfig,
+    pub invariants: InvariantsConfig,
+    pub hooks: HooksConfig,
+    pub gc: GcConfig,
+    pub index: IndexConfig,
+    pub glob: GlobConfig,
+    pub cache: CacheConfig,
+    pub lfs: LfsConfig,
+    pub metrics: MetricsConfig,
+    pub meta: MetaConfig,
+    pub auto: AutoConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(default)]
+pub struct OutputConfig {
+    /// Emit JSON for every command, as if --json were always passed
+    pub json: bool,
+    /// Truncate file content returned by read commands beyond this many bytes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_bytes: Option<usize>,
+    /// JSON results of diff, bulk, and read larger than this are written to
+    /// `.agent/out/` and replaced by a stub pointing at the file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_output_bytes: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ChangeConfig {
+    /// Change type used when --type is not given
+    pub default_type: String,
+    /// Command that writes `commit --auto-message` messages: gets the
+    /// structured diff as JSON on stdin, prints the message on stdout
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message_command: Option<String>,
+}
+
+impl Default for ChangeConfig {
+    fn default() -> Self {
+        Self {
+            default_type: "behavioral".to_string(),
+            message_command: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(default)]
+pub struct InvariantsConfig {
+    /// Kill an invariant command that runs longer than this
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(default)]
+pub struct HooksConfig {
+    /// Kill a manifest hook command that runs longer than this
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct GcConfig {
+    /// Newest checkpoints that `gc` never prunes, whatever their age
+    pub keep_checkpoints: usize,
+    /// Older checkpoints beyond the kept ones are pruned after this many days
+    pub checkpoint_max_age_days: u64,
+    /// Audit sessions untouched for this many days are pruned
+    pub audit_max_age_days: u64,
+}
+
+impl Default for GcConfig {
+    fn default() -> Self {
+        Self {
+            keep_checkpoints: 10,
+            checkpoint_max_age_days: 30,
+            audit_max_age_days: 90,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct IndexConfig {
+    /// Whether codebase scans (orient, files) are performed at all
+    pub enabled: bool,
+    /// Extra path fragments to skip when scanning the codebase
+    pub exclude: Vec<String>,
+}
+
+impl Default for IndexConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            exclude: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(default)]
+pub struct GlobConfig {
+    /// Match path and bookmark globs without regard to case
+    pub case_insensitive: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct CacheConfig {
+    /// Bytes of file contents read at a revision kept in memory per process
+    pub memory_bytes: u64,
+    /// Also keep them under `.agent/cache/blobs/`, shared across processes
+    pub disk: bool,
+    /// The least recently used blobs on disk are dropped beyond this
+    pub disk_max_bytes: u64,
+    /// Keep commit metadata read by history walks under `.agent/cache/history/`
+    pub history: bool,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            memory_bytes: 64 * 1024 * 1024,
+            disk: false,
+            disk_max_bytes: 256 * 1024 * 1024,
+            history: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct LfsConfig {
+    /// `commit` warns about new files at least this large that no LFS
+    /// pattern in .gitattributes covers
+    pub threshold_bytes: u64,
+}
+
+impl Default for LfsConfig {
+    fn default() -> Self {
+        Self {
+            threshold_bytes: 10 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(default)]
+pub struct MetricsConfig {
+    /// Record anonymous per-command counts and outcomes in `.agent/metrics/`
+    /// for `stats --usage` and `suggest`. Never leaves the machine.
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+#[serde(default)]
+pub struct MetaConfig {
+    /// Other repositories of the meta workspace (paths relative to this
+    /// repo's root), searched by `affected --cross-repo` for consumers
+    pub members: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct AutoConfig {
+    /// Let state-changing commands set up jj alongside a git repository
+    /// that has none (`AGENTJJ_AUTO_COLOCATE=false` turns it off)
+    pub colocate: bool,
+}
+
+impl Default for AutoConfig {
+    fn default() -> Self {
+        Self { colocate: true }
+    }
+}
+
+impl Config {
+    pub const DEFAULT_PATH: &'static str = ".agent/config.toml";
+
+    /// Record `--config key=value` overrides for the rest of the process.
+    /// Each entry must be `key=value` with a known key.
+    pub fn set_cli_overrides(raw: &[String]) -> Result<()> {
+        let mut overrides = Vec::with_capacity(raw.len());
+        for entry in raw {
+            let (key, value) = entry.split_once('=').ok_or_else(|| Error::Config {
+                message: format!("--config expects key=value, got '{}'", entry),
+            })?;
+            let key = key.trim();
+            // Validate eagerly so typos fail before any work is done
+            Config::default().set(key, value)?;
+            overrides.push((key.to_string(), value.to_string()));
+        }
+        let _ = CLI_OVERRIDES.set(overrides);
+        Ok(())
+    }
+
+    /// Load the effective config for a repo root: defaults, then
+    /// `.agent/config.toml`, then `AGENTJJ_*` env vars, then `--config` flags.
+    pub fn load(repo_root: impl AsRef<Path>) -> Result<Self> {
+        let path = repo_root.as_ref().join(Self::DEFAULT_PATH);
+        let mut config = match std::fs::read_to_string(&path) {
+            Ok(content) => Self::parse(&content)?,
+            Err(_) => Self::default(),
+        };
+        config.apply_env(|name| std::env::var(name).ok())?;
+        for (key, value) in CLI_OVERRIDES.get().into_iter().flatten() {
+            config.set(key, value)?;
+        }
+        Ok(config)
+    }
+
+    /// Load the config for the repository containing the current directory,
+    /// falling back to env and flags alone outside a repository.
+    pub fn discover() -> Result<Self> {
+        let cwd = std::env::current_dir()?;
+        let root = cwd
+            .ancestors()
+            .find(|dir| dir.join(".jj").exists() || dir.join(".git").exists())
+            .unwrap_or(&cwd);
+        Self::load(root)
+    }
+
+    /// Parse a config file
+    pub fn parse(content: &str) -> Result<Self> {
+        let error = |message: &str| Error::Config {
+            message: format!("{}: {}", Self::DEFAULT_PATH, message),
+        };
+        let value: toml::Table = toml::from_str(content).map_err(|e| error(e.message()))?;
+        // The file is committed with the repository, so a token there leaks
+        if value.contains_key("forge") {
+            return Err(error(&format!(
+                "[forge] github_token is not read from here, since this file is committed; \
+                 put GH_TOKEN in {} or the environment instead",
+                crate::secrets::FILE
+            )));
+        }
+        Self::deserialize(value).map_err(|e| error(e.message()))
+    }
+
+    /// Apply `AGENTJJ_<SECTION>_<FIELD>` variables using the given lookup
+    pub fn apply_env(&mut self, lookup: impl Fn(&str) -> Option<String>) -> Result<()> {
+        for key in KEYS {
+            if let Some(value) = lookup(&env_var_name(key)) {
+                self.set(key, &value)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Set a single `section.field` key from its string form. Values are
+    /// parsed as TOML (`true`, `30`, `["a", "b"]`) and fall back to a string.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        let (section, field) = key
+            .split_once('.')
+            .filter(|_| KEYS.contains(&key))
+            .ok_or_else(|| Error::Config {
+                message: format!("unknown config key '{}' (known: {})", key, KEYS.join(", ")),
+            })?;
+
+        let table = toml::Table::try_from(&*self).map_err(|e| Error::Config {
+            message: e.to_string(),
+        })?;
+        let with_value = |parsed: toml::Value| -> std::result::Result<Self, toml::de::Error> {
+            let mut table = table.clone();
+            let section_table = table
+                .entry(section)
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+            if let toml::Value::Table(section_table) = section_table {
+                section_table.insert(field.to_string(), parsed);
+            }
+            toml::Value::Table(table).try_into()
+        };
+
+        // Env-style booleans (1/0, yes/no) are retried as bools for bool keys
+        *self = with_value(parse_value(value))
+            .or_else(|e| parse_flag(value).map(with_value).unwrap_or(Err(e)))
+            .map_err(|e| Error::Config {
+                message: format!("invalid value '{}' for {}: {}", value, key, e.message()),
+            })?;
+        Ok(())
+    }
+}
+
+/// Env var name for a config key (`output.json` -> `AGENTJJ_OUTPUT_JSON`)
+pub fn env_var_name(key: &str) -> String {
+    format!("AGENTJJ_{}", key.replace('.', "_").to_uppercase())
+}
+
+fn parse_flag(raw: &str) -> Option<toml::Value> {
+    match raw.trim() {
+        "1" | "yes" | "on" => Some(toml::Value::Boolean(true)),
+        "0" | "no" | "off" => Some(toml::Value::Boolean(false)),
+        _ => None,
+    }
+}
+
+fn parse_value(raw: &str) -> toml::Value {
+    let raw = raw.trim();
+    toml::from_str::<toml::Table>(&format!("v = {}", raw))
+        .ok()
+        .and_then(|mut t| t.remove("v"))
+        .unwrap_or_else(|| toml::Value::String(raw.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_config_file_over_defaults() {
+        let config = Config::parse(
+            r#"
+[output]
+json = true
+
+[invariants]
+timeout_secs = 30
+"#,
+        )
+        .unwrap();
+
+        assert!(config.output.json);
+        assert_eq!(config.invariants.timeout_secs, Some(30));
+        assert_eq!(config.change.default_type, "behavioral");
+        assert!(config.index.enabled);
+    }
+
+    #[test]
+    fn env_overrides_file() {
+        let mut config = Config::parse("[change]\ndefault_type = \"docs\"\n").unwrap();
+        config
+            .apply_env(|name| match name {
+                "AGENTJJ_CHANGE_DEFAULT_TYPE" => Some("refactor".into()),
+                "AGENTJJ_OUTPUT_JSON" => Some("1".into()),
+                _ => None,
+            })
+            .unwrap();
+
+        assert_eq!(config.change.default_type, "refactor");
+        assert!(config.output.json);
+    }
+
+    #[test]
+    fn set_parses_typed_values() {
+        let mut config = Config::default();
+        config.set("output.max_bytes", "4096").unwrap();
+        config.set("output.max_output_bytes", "65536").unwrap();
+        config.set("invariants.timeout_secs", "1").unwrap();
+        config.set("index.enabled", "no").unwrap();
+        config.set("metrics.enabled", "yes").unwrap();
+        config
+            .set("index.exclude", r#"["vendor/", "dist/"]"#)
+            .unwrap();
+
+        assert_eq!(config.output.max_bytes, Some(4096));
+        assert_eq!(config.output.max_output_bytes, Some(65536));
+        assert_eq!(config.invariants.timeout_secs, Some(1));
+        assert!(!config.index.enabled);
+        assert!(config.metrics.enabled);
+        assert_eq!(config.index.exclude, vec!["vendor/", "dist/"]);
+        assert!(config.set("forge.github_token", "ghp_abc").is_err());
+        assert!(Config::parse("[forge]\ngithub_token = \"ghp_abc\"\n").is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_keys_and_bad_values() {
+        let mut config = Config::default();
+        assert!(config.set("output.colour", "true").is_err());
+        assert!(config.set("invariants.timeout_secs", "soon").is_err());
+        assert_eq!(config, Config::default());
+    }
+
+    #[test]
+    fn env_var_names_follow_keys() {
+        assert_eq!(
+            env_var_name("invariants.timeout_secs"),
+            "AGENTJJ_INVARIANTS_TIMEOUT_SECS"
+        );
+    }
+}
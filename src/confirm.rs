@@ -0,0 +1,334 @@
+// ABOUTME: Human confirmations for commands a policy or freeze blocked (.agent/confirmations/<id>.json)
+// ABOUTME: `confirm <id> --approve|--deny` decides one; the command waits for it or is rerun with --confirmation
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+pub use crate::approval::ApprovalStatus as ConfirmationStatus;
+use crate::error::{Error, Result};
+
+/// What kind of check a human can override
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Gate {
+    /// A commit the manifest's policies refuse: review size limits,
+    /// destructive schema changes, blocked prompt injections
+    Policy,
+    /// A promotion into a frozen environment
+    Freeze,
+}
+
+impl Gate {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Gate::Policy => "policy",
+            Gate::Freeze => "freeze",
+        }
+    }
+}
+
+/// A blocked command waiting for a human to let it through
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Confirmation {
+    pub id: String,
+    pub status: ConfirmationStatus,
+    pub gate: Gate,
+    /// Command path that was blocked (e.g. "env promote")
+    pub command: String,
+    /// Its arguments, without the confirmation flags
+    pub args: Vec<String>,
+    /// What blocked it
+    pub message: String,
+    /// The error the command would otherwise have failed with
+    pub blocked_by: serde_json::Value,
+    /// Session that ran the command
+    pub requested_by: String,
+    pub requested_at: String,
+    /// Terminal the blocked command ran on, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requested_tty: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decided_by: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decided_at: Option<String>,
+    /// The process that decided
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decided_from: Option<crate::approval::Decider>,
+    /// Why it was denied (or any note left when approving)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl Confirmation {
+    /// A new pending confirmation for `command` blocked by `blocked_by`
+    pub fn new(
+        gate: Gate,
+        command: impl Into<String>,
+        args: Vec<String>,
+        blocked_by: &Error,
+        requested_by: impl Into<String>,
+        requested_at: impl Into<String>,
+    ) -> Self {
+        let command = command.into();
+        let requested_by = requested_by.into();
+        let requested_at = requested_at.into();
+
+        let mut hasher = Sha256::new();
+        hasher.update(command.as_bytes());
+        hasher.update(serde_json::to_vec(&args).unwrap_or_default());
+        hasher.update(requested_by.as_bytes());
+        hasher.update(requested_at.as_bytes());
+        hasher.update(std::process::id().to_le_bytes());
+        let id = hex::encode(hasher.finalize())[..12].to_string();
+
+        Self {
+            id,
+            status: ConfirmationStatus::Pending,
+            gate,
+            command,
+            args,
+            message: blocked_by.to_string(),
+            blocked_by: serde_json::to_value(blocked_by).unwrap_or_default(),
+            requested_by,
+            requested_at,
+            requested_tty: crate::approval::current_tty(),
+            decided_by: None,
+            decided_at: None,
+            decided_from: None,
+            reason: None,
+        }
+    }
+
+    /// The command line that resumes the blocked command once approved
+    pub fn resume_command(&self) -> String {
+        let mut parts = vec!["agentjj".to_string()];
+        parts.extend(self.args.iter().map(|a| crate::custom::shell_quote(a)));
+        parts.push(format!("--confirmation {}", self.id));
+        parts.join(" ")
+    }
+
+    /// Record a human decision on a pending confirmation
+    pub fn decide(
+        &mut self,
+        approve: bool,
+        by: impl Into<String>,
+        at: impl Into<String>,
+        reason: Option<String>,
+    ) -> Result<()> {
+        self.expect_status(ConfirmationStatus::Pending)?;
+        self.status = if approve {
+            ConfirmationStatus::Approved
+        } else {
+            ConfirmationStatus::Denied
+        };
+        self.decided_by = Some(by.into());
+        self.decided_at = Some(at.into());
+        self.reason = reason;
+        Ok(())
+    }
+
+    /// Fail unless the confirmation is in `expected` state
+    pub fn expect_status(&self, expected: ConfirmationStatus) -> Result<()> {
+        if self.status != expected {
+            return Err(Error::PreconditionFailed {
+                reason: format!("confirmation {} is {}", self.id, self.status.as_str()),
+                expected: expected.as_str().to_string(),
+                actual: self.status.as_str().to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Check that this confirmation lets `command` through `gate`: it must
+    /// be approved, not yet used, and issued for the same command and gate
+    pub fn release(&self, gate: Gate, command: &str) -> Result<()> {
+        match self.status {
+            ConfirmationStatus::Pending => return Err(self.pending_error()),
+            ConfirmationStatus::Denied => {
+                return Err(Error::ConfirmationDenied {
+                    confirmation_id: self.id.clone(),
+                    decided_by: self.decided_by.clone().unwrap_or_default(),
+                    reason: self.reason.clone(),
+                })
+            }
+            _ => self.expect_status(ConfirmationStatus::Approved)?,
+        }
+        if self.gate != gate || self.command != command {
+            return Err(Error::PreconditionFailed {
+                reason: format!(
+                    "confirmation {} was issued for a {} check on `{}`",
+                    self.id,
+                    self.gate.as_str(),
+                    self.command
+                ),
+                expected: format!("{} on {}", self.gate.as_str(), self.command),
+                actual: format!("{} on {}", gate.as_str(), command),
+            });
+        }
+        Ok(())
+    }
+
+    /// The error a command fails with while this confirmation is pending
+    pub fn pending_error(&self) -> Error {
+        Error::ConfirmationPending {
+            confirmation_id: self.id.clone(),
+            gate: self.gate,
+            message: self.message.clone(),
+            resume: self.resume_command(),
+        }
+    }
+}
+
+/// The confirmations directory of a repository
+pub struct ConfirmationStore {
+    dir: PathBuf,
+}
+
+impl ConfirmationStore {
+    pub const DEFAULT_DIR: &'static str = ".agent/confirmations";
+
+    pub fn open(repo_root: impl AsRef<Path>) -> Self {
+        Self {
+            dir: repo_root.as_ref().join(Self::DEFAULT_DIR),
+        }
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+
+    /// Write (or overwrite) a confirmation
+    pub fn save(&self, confirmation: &Confirmation) -> Result<()> {
+        // Written then renamed: a waiting command polls this file
+        crate::agent_dir::write_json(&self.path(&confirmation.id), confirmation)
+    }
+
+    /// Whether a confirmation with this ID exists
+    pub fn contains(&self, id: &str) -> bool {
+        valid_id(id) && self.path(id).exists()
+    }
+
+    /// Load a confirmation by ID
+    pub fn load(&self, id: &str) -> Result<Confirmation> {
+        let content = valid_id(id)
+            .then(|| std::fs::read_to_string(self.path(id)).ok())
+            .flatten()
+            .ok_or_else(|| Error::ConfirmationNotFound {
+                confirmation_id: id.to_string(),
+            })?;
+        serde_json::from_str(&content).map_err(|e| Error::Io {
+            message: format!("corrupt confirmation {}: {}", id, e),
+        })
+    }
+
+    /// All confirmations, oldest request first
+    pub fn list(&self) -> Result<Vec<Confirmation>> {
+        let mut confirmations = Vec::new();
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return Ok(confirmations);
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|x| x.to_str()) != Some("json") {
+                continue;
+            }
+            if let Some(id) = path.file_stem().and_then(|s| s.to_str()) {
+                confirmations.push(self.load(id)?);
+            }
+        }
+        confirmations.sort_by(|a, b| {
+            a.requested_at
+                .cmp(&b.requested_at)
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        Ok(confirmations)
+    }
+
+    /// Poll until a human decides on `id` or `timeout` passes; returns the
+    /// confirmation as last seen, still pending on timeout
+    pub fn wait(&self, id: &str, timeout: Duration, poll: Duration) -> Result<Confirmation> {
+        let started = Instant::now();
+        loop {
+            let confirmation = self.load(id)?;
+            if confirmation.status != ConfirmationStatus::Pending || started.elapsed() >= timeout {
+                return Ok(confirmation);
+            }
+            crate::deadline::check("waiting for confirmation")?;
+            std::thread::sleep(poll.min(timeout.saturating_sub(started.elapsed())));
+        }
+    }
+}
+
+fn valid_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blocked() -> Error {
+        Error::PromotionBlocked {
+            environment: "prod".into(),
+            reason: "environment is frozen".into(),
+        }
+    }
+
+    #[test]
+    fn confirmations_release_only_their_own_approved_command() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = ConfirmationStore::open(tmp.path());
+        let args: Vec<String> = ["env", "promote", "staging", "--to", "prod"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let mut confirmation = Confirmation::new(
+            Gate::Freeze,
+            "env promote",
+            args,
+            &blocked(),
+            "agent-1",
+            "2026-01-02T03:04:05Z",
+        );
+        assert_eq!(confirmation.blocked_by["type"], "promotion_blocked");
+        assert_eq!(
+            confirmation.resume_command(),
+            format!(
+                "agentjj env promote staging --to prod --confirmation {}",
+                confirmation.id
+            )
+        );
+        store.save(&confirmation).unwrap();
+        assert!(matches!(
+            confirmation.release(Gate::Freeze, "env promote"),
+            Err(Error::ConfirmationPending { .. })
+        ));
+
+        // Someone else decides while the command waits
+        let mut decided = store.load(&confirmation.id).unwrap();
+        decided
+            .decide(true, "alice", "2026-01-02T04:00:00Z", None)
+            .unwrap();
+        store.save(&decided).unwrap();
+        confirmation = store
+            .wait(
+                &confirmation.id,
+                Duration::from_secs(5),
+                Duration::from_millis(10),
+            )
+            .unwrap();
+        assert_eq!(confirmation.status, ConfirmationStatus::Approved);
+        assert!(confirmation.release(Gate::Freeze, "env promote").is_ok());
+        assert!(confirmation.release(Gate::Policy, "commit").is_err());
+        assert!(confirmation.decide(false, "bob", "later", None).is_err());
+
+        assert!(store.contains(&confirmation.id));
+        assert!(matches!(
+            store.load("../etc"),
+            Err(Error::ConfirmationNotFound { .. })
+        ));
+        assert_eq!(store.list().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,151 @@
+// ABOUTME: `affected --cross-repo`: usages of a library's public symbols in the meta workspace's member repos
+// ABOUTME: Word-level matches outside comments and strings, in files of the symbol's language family
+
+use serde::Serialize;
+use std::path::Path;
+
+use crate::agentignore::AgentIgnore;
+use crate::callers::{is_ident, mask, same_family, short_name, supported};
+use crate::repo::{walk_files, WalkFilters};
+use crate::symbols::SupportedLanguage;
+
+/// Directories of build output and dependencies, never scanned for consumers
+const SKIP_DIRS: &[&str] = &[
+    "node_modules",
+    "target",
+    "dist",
+    "build",
+    "__pycache__",
+    "venv",
+    ".venv",
+    ".agent",
+];
+
+/// A symbol whose callers elsewhere may break
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Target {
+    /// Repo-relative file that defines it
+    pub file: String,
+    /// Name as the API diff reports it (`Class.method` for members)
+    pub symbol: String,
+    #[serde(skip)]
+    pub language: SupportedLanguage,
+}
+
+/// One use of a target in a member repo
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Usage {
+    /// File relative to the member repo's root
+    pub file: String,
+    pub line: usize,
+    pub symbol: String,
+    /// The line as written, trimmed and shortened
+    pub text: String,
+}
+
+/// Uses of `targets` in the repository at `root`, by file then line
+pub fn find_usages(root: &Path, targets: &[Target]) -> Vec<Usage> {
+    let filters = WalkFilters {
+        gitignore: true,
+        agentignore: AgentIgnore::load(root).ok(),
+        ..WalkFilters::default()
+    };
+    let mut usages = Vec::new();
+    for path in walk_files(root, filters) {
+        let Ok(rel) = path.strip_prefix(root) else {
+            continue;
+        };
+        if rel
+            .components()
+            .any(|c| SKIP_DIRS.iter().any(|d| c.as_os_str() == *d))
+        {
+            continue;
+        }
+        let Some(lang) = SupportedLanguage::from_path(&path) else {
+            continue;
+        };
+        let relevant: Vec<&Target> = targets
+            .iter()
+            .filter(|t| same_family(t.language, lang))
+            .collect();
+        if relevant.is_empty() {
+            continue;
+        }
+        let Ok(source) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        let file = rel
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        usages.extend(usages_in(&file, &source, lang, &relevant));
+    }
+    usages
+}
+
+/// Whole-word uses of each target's short name in `source`
+fn usages_in(file: &str, source: &str, lang: SupportedLanguage, targets: &[&Target]) -> Vec<Usage> {
+    let masked = if supported(lang) {
+        mask(source, lang)
+    } else {
+        source.to_string()
+    };
+    let lines: Vec<&str> = source.lines().collect();
+    let mut usages = Vec::new();
+    for (index, line) in masked.lines().enumerate() {
+        for target in targets {
+            let name = short_name(&target.symbol);
+            let found = line.match_indices(name).any(|(at, _)| {
+                let end = at + name.len();
+                !line[..at].chars().next_back().is_some_and(is_ident)
+                    && !line[end..].chars().next().is_some_and(is_ident)
+            });
+            if found {
+                let text: String = lines[index].trim().chars().take(120).collect();
+                usages.push(Usage {
+                    file: file.to_string(),
+                    line: index + 1,
+                    symbol: target.symbol.clone(),
+                    text,
+                });
+            }
+        }
+    }
+    usages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn finds_word_uses_outside_comments_in_the_same_language_family() {
+        let tmp = TempDir::new().unwrap();
+        let write = |path: &str, content: &str| {
+            let path = tmp.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        };
+        write(
+            "app/main.py",
+            "from lib import parse\n# parse is documented elsewhere\nx = parse(1)\nparser = 2\n",
+        );
+        write("web/index.ts", "parse()\n");
+        write("node_modules/lib/index.py", "parse()\n");
+        write(".agentignore", "generated/\n");
+        write("generated/api.py", "parse()\n");
+
+        let targets = [Target {
+            file: "lib/__init__.py".into(),
+            symbol: "Reader.parse".into(),
+            language: SupportedLanguage::Python,
+        }];
+        let usages = find_usages(tmp.path(), &targets);
+        let found: Vec<(&str, usize)> = usages.iter().map(|u| (u.file.as_str(), u.line)).collect();
+        assert_eq!(found, vec![("app/main.py", 1), ("app/main.py", 3)]);
+        assert_eq!(usages[1].text, "x = parse(1)");
+        assert_eq!(usages[1].symbol, "Reader.parse");
+    }
+}
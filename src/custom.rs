@@ -0,0 +1,298 @@
+// ABOUTME: Manifest-defined custom commands for `run`: argument binding, step templating, output checks
+// ABOUTME: Steps run through repo.rs; this module turns `--arg k=v` into concrete shell commands
+
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::error::{Error, Result};
+use crate::manifest::CustomCommand;
+
+/// One executed step
+#[derive(Debug, Clone, Serialize)]
+pub struct StepResult {
+    pub command: String,
+    pub success: bool,
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// The outcome of `run <name>`
+#[derive(Debug, Clone, Serialize)]
+pub struct RunResult {
+    pub name: String,
+    pub args: BTreeMap<String, String>,
+    pub success: bool,
+    pub steps: Vec<StepResult>,
+    /// The last step's stdout, parsed as JSON when it is JSON
+    pub output: serde_json::Value,
+    /// Ways the output breaks the declared schema
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// How a custom command is listed for discovery
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandInfo {
+    pub name: String,
+    pub description: String,
+    pub usage: String,
+    pub args: BTreeMap<String, crate::manifest::CustomArg>,
+    pub steps: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<serde_json::Value>,
+}
+
+/// Every custom command, by name
+pub fn list(commands: &HashMap<String, CustomCommand>) -> Vec<CommandInfo> {
+    let mut infos: Vec<CommandInfo> = commands
+        .iter()
+        .map(|(name, cmd)| CommandInfo {
+            name: name.clone(),
+            description: cmd.description.clone(),
+            usage: usage(name, cmd),
+            args: cmd
+                .args
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+            steps: cmd.steps.clone(),
+            output: cmd.output.clone(),
+        })
+        .collect();
+    infos.sort_by(|a, b| a.name.cmp(&b.name));
+    infos
+}
+
+/// `agentjj run <name> --arg k=<k> [--arg opt=<opt>]`
+pub fn usage(name: &str, cmd: &CustomCommand) -> String {
+    let mut names: Vec<&String> = cmd.args.keys().collect();
+    names.sort();
+    let mut usage = format!("agentjj run {}", name);
+    for arg in names {
+        let flag = format!("--arg {}=<{}>", arg, arg);
+        if cmd.args[arg].default.is_some() {
+            usage.push_str(&format!(" [{}]", flag));
+        } else {
+            usage.push(' ');
+            usage.push_str(&flag);
+        }
+    }
+    usage
+}
+
+/// Split `--arg key=value` entries
+pub fn parse_args(raw: &[String]) -> Result<Vec<(String, String)>> {
+    raw.iter()
+        .map(|entry| {
+            entry
+                .split_once('=')
+                .map(|(k, v)| (k.trim().to_string(), v.to_string()))
+                .ok_or_else(|| Error::Config {
+                    message: format!("--arg expects key=value, got '{}'", entry),
+                })
+        })
+        .collect()
+}
+
+/// Quote a value for `sh -c` so arguments can never inject commands; plainly
+/// safe words (paths, flags, IDs) are left bare so printed commands stay readable
+pub fn shell_quote(value: &str) -> String {
+    let plain = !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "/._-@:=+,".contains(c));
+    if plain {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', r"'\''"))
+    }
+}
+
+/// Bind arguments (given, then defaults) and substitute them, shell-quoted,
+/// into each step's `{{name}}` placeholders
+pub fn render(
+    name: &str,
+    cmd: &CustomCommand,
+    provided: &[(String, String)],
+) -> Result<(BTreeMap<String, String>, Vec<String>)> {
+    let mut args = BTreeMap::new();
+    for (key, value) in provided {
+        if !cmd.args.contains_key(key) {
+            return Err(Error::Config {
+                message: format!(
+                    "'{}' takes no argument '{}' (usage: {})",
+                    name,
+                    key,
+                    usage(name, cmd)
+                ),
+            });
+        }
+        args.insert(key.clone(), value.clone());
+    }
+    for (key, arg) in &cmd.args {
+        if !args.contains_key(key) {
+            let value = arg.default.clone().ok_or_else(|| Error::Config {
+                message: format!(
+                    "'{}' needs --arg {}=<value> (usage: {})",
+                    name,
+                    key,
+                    usage(name, cmd)
+                ),
+            })?;
+            args.insert(key.clone(), value);
+        }
+    }
+
+    let mut steps = Vec::with_capacity(cmd.steps.len());
+    for step in &cmd.steps {
+        let mut rendered = String::with_capacity(step.len());
+        let mut rest = step.as_str();
+        while let Some(start) = rest.find("{{") {
+            let Some(len) = rest[start..].find("}}") else {
+                break;
+            };
+            let key = rest[start + 2..start + len].trim();
+            let value = args.get(key).ok_or_else(|| Error::Config {
+                message: format!(
+                    "step '{}' of '{}' uses {{{{{}}}}}, which is not a declared arg",
+                    step, name, key
+                ),
+            })?;
+            rendered.push_str(&rest[..start]);
+            rendered.push_str(&shell_quote(value));
+            rest = &rest[start + len + 2..];
+        }
+        rendered.push_str(rest);
+        steps.push(rendered);
+    }
+    Ok((args, steps))
+}
+
+/// The last step's stdout as JSON, or as a string when it isn't JSON
+pub fn parse_output(stdout: &str) -> serde_json::Value {
+    serde_json::from_str(stdout.trim())
+        .unwrap_or_else(|_| serde_json::Value::String(stdout.trim_end().to_string()))
+}
+
+fn type_matches(expected: &str, value: &serde_json::Value) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+/// Check `output` against the declared schema's `type`, `required`, and
+/// the `type` of each listed property
+pub fn check_output(schema: &serde_json::Value, output: &serde_json::Value) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if let Some(expected) = schema["type"].as_str() {
+        if !type_matches(expected, output) {
+            warnings.push(format!("output is not a JSON {}", expected));
+            return warnings;
+        }
+    }
+    for key in schema["required"].as_array().into_iter().flatten() {
+        if let Some(key) = key.as_str() {
+            if output.get(key).is_none() {
+                warnings.push(format!("output is missing required field '{}'", key));
+            }
+        }
+    }
+    if let Some(properties) = schema["properties"].as_object() {
+        for (key, property) in properties {
+            if let (Some(value), Some(expected)) = (output.get(key), property["type"].as_str()) {
+                if !type_matches(expected, value) {
+                    warnings.push(format!(
+                        "output field '{}' should be of type {}",
+                        key, expected
+                    ));
+                }
+            }
+        }
+    }
+    warnings
+}
+
+/// Error for a name that isn't a custom command
+pub fn unknown_command(commands: &HashMap<String, CustomCommand>, name: &str) -> Error {
+    let mut known: Vec<&str> = commands.keys().map(String::as_str).collect();
+    known.sort();
+    Error::Config {
+        message: if known.is_empty() {
+            "no custom commands configured - add [commands.custom.<name>] to .agent/manifest.toml"
+                .into()
+        } else {
+            format!(
+                "'{}' is not a custom command (available: {})",
+                name,
+                known.join(", ")
+            )
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::Manifest;
+
+    const MANIFEST: &str = r#"
+[repo]
+name = "svc"
+
+[commands.custom.deploy-preview]
+steps = ["echo deploying {{pr}} to {{region}}", "agentjj env list"]
+args = { pr = { description = "PR number" }, region = { default = "eu" } }
+"#;
+
+    #[test]
+    fn binds_args_and_quotes_them_into_steps() {
+        let manifest = Manifest::parse(MANIFEST).unwrap();
+        let cmd = &manifest.commands.custom["deploy-preview"];
+        assert_eq!(
+            usage("deploy-preview", cmd),
+            "agentjj run deploy-preview --arg pr=<pr> [--arg region=<region>]"
+        );
+
+        let provided = parse_args(&["pr=12; rm -rf /".into()]).unwrap();
+        let (args, steps) = render("deploy-preview", cmd, &provided).unwrap();
+        assert_eq!(args["region"], "eu");
+        assert_eq!(steps[0], "echo deploying '12; rm -rf /' to eu");
+        assert_eq!(steps[1], "agentjj env list");
+
+        assert!(render("deploy-preview", cmd, &[]).is_err());
+        assert!(render("deploy-preview", cmd, &[("color".into(), "red".into())]).is_err());
+        assert!(parse_args(&["pr".into()]).is_err());
+    }
+
+    #[test]
+    fn checks_output_against_the_declared_schema() {
+        let schema = serde_json::json!({
+            "type": "object",
+            "required": ["files", "count"],
+            "properties": { "files": { "type": "array" } },
+        });
+        let good = parse_output("{\"files\": [], \"count\": 0}\n");
+        assert!(check_output(&schema, &good).is_empty());
+
+        let partial = serde_json::json!({ "files": "a.rs" });
+        assert_eq!(
+            check_output(&schema, &partial),
+            vec![
+                "output is missing required field 'count'",
+                "output field 'files' should be of type array"
+            ]
+        );
+        assert_eq!(
+            check_output(&schema, &parse_output("done\n")),
+            vec!["output is not a JSON object"]
+        );
+    }
+}
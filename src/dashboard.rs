@@ -0,0 +1,211 @@
+// ABOUTME: `agentjj ui` data: the change graph, working-copy status, typed metadata, and sessions in one snapshot
+// ABOUTME: Read from the repository on every refresh; tui.rs draws it and `agentjj --json ui` prints it once
+
+use serde::Serialize;
+use std::path::Path;
+
+use crate::audit::AuditLog;
+use crate::change::TypedChange;
+use crate::error::Result;
+use crate::repo::Repo;
+
+/// A session counts as active while its last command is this recent
+pub const ACTIVE_SECS: i64 = 15 * 60;
+
+/// Everything the dashboard shows
+#[derive(Debug, Clone, Serialize)]
+pub struct Dashboard {
+    /// The working-copy change
+    pub change_id: String,
+    pub operation_id: String,
+    /// Description of the operation `undo` would revert
+    pub last_operation: String,
+    /// Newest first, as `graph` walks them
+    pub changes: Vec<DashboardChange>,
+    /// Files in the working copy that are not clean (ignored ones left out),
+    /// by path
+    pub status: Vec<StatusEntry>,
+    /// Most recently active first
+    pub sessions: Vec<SessionInfo>,
+    pub refreshed_at: String,
+}
+
+/// A change in the graph, with its typed metadata when it has any
+#[derive(Debug, Clone, Serialize)]
+pub struct DashboardChange {
+    pub change_id: String,
+    pub commit_id: String,
+    pub description: String,
+    /// Short change IDs of the parents
+    pub parents: Vec<String>,
+    pub working_copy: bool,
+    pub immutable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+    /// Type, intent, categories, and invariant results
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub typed: Option<TypedChange>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusEntry {
+    pub path: String,
+    pub status: &'static str,
+}
+
+/// An audit session: an agent (or person) running state-changing commands
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionInfo {
+    pub session: String,
+    pub commands: usize,
+    pub failed: usize,
+    pub last_command: String,
+    pub last_active: String,
+    /// Last command within `ACTIVE_SECS`
+    pub active: bool,
+}
+
+impl Dashboard {
+    /// Read the newest `limit` changes and the rest of the dashboard. The
+    /// working copy is snapshotted first, as `status` does.
+    pub fn load(repo: &mut Repo, limit: usize) -> Result<Self> {
+        let status = repo.working_copy_status()?;
+        let mut status: Vec<StatusEntry> = status
+            .into_iter()
+            .filter(|(_, status)| *status != crate::repo::FileStatus::Ignored)
+            .map(|(path, status)| StatusEntry {
+                path,
+                status: status.as_str(),
+            })
+            .collect();
+        status.sort_by(|a, b| a.path.cmp(&b.path));
+
+        // The working copy and its ancestors first, then other heads' lines
+        let mut entries: Vec<_> = repo
+            .log_entries(limit, false)?
+            .into_iter()
+            .filter(|e| !e.full_commit_id.chars().all(|c| c == '0'))
+            .collect();
+        let mut ours = std::collections::HashSet::new();
+        let mut next: Vec<String> = entries
+            .iter()
+            .filter(|e| e.is_working_copy)
+            .map(|e| e.change_id.clone())
+            .collect();
+        while let Some(id) = next.pop() {
+            if ours.insert(id.clone()) {
+                if let Some(entry) = entries.iter().find(|e| e.change_id == id) {
+                    next.extend(entry.parent_change_ids.iter().cloned());
+                }
+            }
+        }
+        entries.sort_by_key(|e| !ours.contains(&e.change_id));
+
+        let mut changes = Vec::new();
+        for entry in entries {
+            let change_id = repo
+                .commit_change_id(&entry.full_commit_id)
+                .unwrap_or_else(|_| entry.change_id.clone());
+            changes.push(DashboardChange {
+                typed: repo.get_typed_change(&change_id).ok(),
+                change_id,
+                commit_id: entry.full_commit_id,
+                description: entry.description,
+                parents: entry.parent_change_ids,
+                working_copy: entry.is_working_copy,
+                immutable: entry.immutable,
+                author: entry.author,
+                timestamp: entry.timestamp,
+            });
+        }
+
+        let last_operation = repo
+            .operation_log(1)?
+            .into_iter()
+            .next()
+            .map(|op| op.description)
+            .unwrap_or_default();
+        let now = now_secs();
+        Ok(Self {
+            change_id: repo.current_change_id()?,
+            operation_id: repo.current_operation_id()?,
+            last_operation,
+            changes,
+            status,
+            sessions: sessions(repo.root(), now),
+            refreshed_at: crate::repo::utc_timestamp(now),
+        })
+    }
+}
+
+fn now_secs() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+/// Every audit session with its last command, most recently active first
+pub fn sessions(repo_root: &Path, now: i64) -> Vec<SessionInfo> {
+    let log = AuditLog::open(repo_root);
+    let mut sessions: Vec<SessionInfo> = log
+        .sessions()
+        .into_iter()
+        .filter_map(|name| {
+            let records = log.session(&name).ok()?;
+            let last = records.last()?;
+            let active = crate::repo::parse_utc_timestamp(&last.timestamp)
+                .is_some_and(|at| now - at <= ACTIVE_SECS);
+            Some(SessionInfo {
+                session: last.session.clone(),
+                commands: records.len(),
+                failed: records.iter().filter(|r| !r.success).count(),
+                last_command: last.command.clone(),
+                last_active: last.timestamp.clone(),
+                active,
+            })
+        })
+        .collect();
+    sessions.sort_by(|a, b| b.last_active.cmp(&a.last_active));
+    sessions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit::AuditRecord;
+    use tempfile::TempDir;
+
+    #[test]
+    fn sessions_are_listed_most_recent_first() {
+        let tmp = TempDir::new().unwrap();
+        let log = AuditLog::open(tmp.path());
+        let record = |session: &str, timestamp: &str, command: &str, success: bool| AuditRecord {
+            session: session.into(),
+            timestamp: timestamp.into(),
+            command: command.into(),
+            args: vec![command.into()],
+            success,
+            error: None,
+            inputs: None,
+        };
+        log.append(&record("agent-a", "2026-03-01T10:00:00Z", "commit", true))
+            .unwrap();
+        log.append(&record("agent-b", "2026-03-01T11:50:00Z", "apply", false))
+            .unwrap();
+        log.append(&record("agent-b", "2026-03-01T11:55:00Z", "commit", true))
+            .unwrap();
+
+        let now = crate::repo::parse_utc_timestamp("2026-03-01T12:00:00Z").unwrap();
+        let sessions = sessions(tmp.path(), now);
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].session, "agent-b");
+        assert_eq!(sessions[0].commands, 2);
+        assert_eq!(sessions[0].failed, 1);
+        assert_eq!(sessions[0].last_command, "commit");
+        assert!(sessions[0].active);
+        assert!(!sessions[1].active);
+    }
+}
@@ -0,0 +1,181 @@
+// ABOUTME: Process-wide deadline from the global --timeout flag
+// ABOUTME: Caps subprocess timeouts and lets long loops stop early; writes hold off the watchdog's exit
+
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::error::{Error, Result};
+
+/// The point by which the current command must be done
+#[derive(Debug, Clone, Copy)]
+pub struct Deadline {
+    at: Instant,
+    limit: Duration,
+}
+
+impl Deadline {
+    /// A deadline `limit` from now
+    pub fn after(limit: Duration) -> Self {
+        Self {
+            at: Instant::now() + limit,
+            limit,
+        }
+    }
+
+    /// Time left, zero once passed
+    pub fn remaining(&self) -> Duration {
+        self.at.saturating_duration_since(Instant::now())
+    }
+
+    pub fn expired(&self) -> bool {
+        self.remaining().is_zero()
+    }
+
+    /// The shorter of `timeout` and the time left
+    pub fn cap(&self, timeout: Option<Duration>) -> Duration {
+        let left = self.remaining();
+        timeout.map_or(left, |t| t.min(left))
+    }
+
+    /// The timeout error for work stopped during `phase`
+    pub fn error(&self, phase: impl Into<String>) -> Error {
+        Error::Timeout {
+            phase: phase.into(),
+            timeout_secs: self.limit.as_secs(),
+        }
+    }
+}
+
+static DEADLINE: OnceLock<Deadline> = OnceLock::new();
+
+/// Start the clock: the command must finish within `limit` from now
+pub fn set(limit: Duration) {
+    let _ = DEADLINE.set(Deadline::after(limit));
+}
+
+/// The deadline for this process, if --timeout was given
+pub fn current() -> Option<Deadline> {
+    DEADLINE.get().copied()
+}
+
+/// Whether the process deadline has passed
+pub fn expired() -> bool {
+    current().is_some_and(|d| d.expired())
+}
+
+/// `timeout` shortened to the time left before the process deadline
+pub fn cap(timeout: Option<Duration>) -> Option<Duration> {
+    match current() {
+        Some(deadline) => Some(deadline.cap(timeout)),
+        None => timeout,
+    }
+}
+
+/// Fail with `Error::Timeout` if the process deadline has passed
+pub fn check(phase: &str) -> Result<()> {
+    match current() {
+        Some(deadline) if deadline.expired() => Err(deadline.error(phase)),
+        _ => Ok(()),
+    }
+}
+
+/// Open critical sections: the watchdog waits for zero before exiting
+static CRITICAL: Mutex<usize> = Mutex::new(0);
+
+fn critical_count() -> std::sync::MutexGuard<'static, usize> {
+    CRITICAL.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// The thread that quiesced the process to exit it
+static QUIESCED_BY: OnceLock<std::thread::ThreadId> = OnceLock::new();
+
+/// A write (transaction commit, working-copy update, store file) the
+/// watchdog must not cut in half; ends when dropped. Sections may nest.
+#[must_use]
+pub struct Critical {
+    counted: bool,
+}
+
+/// Enter a critical section. Once the process is quiesced this blocks on
+/// other threads until it exits; the quiescing thread itself passes
+/// straight through, so its own exit path can still write.
+pub fn critical() -> Critical {
+    if QUIESCED_BY.get() == Some(&std::thread::current().id()) {
+        return Critical { counted: false };
+    }
+    *critical_count() += 1;
+    Critical { counted: true }
+}
+
+impl Drop for Critical {
+    fn drop(&mut self) {
+        if self.counted {
+            *critical_count() -= 1;
+        }
+    }
+}
+
+/// Wait until no critical section is open, and keep other threads from
+/// starting one while the returned guard lives: the watchdog holds it
+/// while it exits
+pub fn quiesce() -> impl Drop {
+    loop {
+        let open = critical_count();
+        if *open == 0 {
+            let _ = QUIESCED_BY.set(std::thread::current().id());
+            return open;
+        }
+        drop(open);
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cap_takes_the_shorter_timeout() {
+        let deadline = Deadline::after(Duration::from_secs(60));
+        assert!(deadline.cap(None) <= Duration::from_secs(60));
+        assert_eq!(
+            deadline.cap(Some(Duration::from_secs(5))),
+            Duration::from_secs(5)
+        );
+        assert!(!deadline.expired());
+    }
+
+    #[test]
+    fn critical_sections_nest() {
+        let outer = critical();
+        let inner = critical();
+        assert!(*critical_count() >= 2);
+        drop(inner);
+        drop(outer);
+    }
+
+    #[test]
+    fn the_quiescing_thread_can_still_write() {
+        // The watchdog records usage while it exits: no deadlock
+        std::thread::spawn(|| {
+            let quiet = quiesce();
+            let write = critical();
+            drop(write);
+            drop(quiet);
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[test]
+    fn expired_deadline_caps_to_zero() {
+        let deadline = Deadline::after(Duration::ZERO);
+        assert!(deadline.expired());
+        assert_eq!(deadline.cap(Some(Duration::from_secs(5))), Duration::ZERO);
+
+        let err = deadline.error("invariants");
+        assert!(
+            matches!(err, Error::Timeout { ref phase, timeout_secs: 0 } if phase == "invariants")
+        );
+    }
+}
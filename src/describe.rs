@@ -0,0 +1,308 @@
+// ABOUTME: commit --auto-message: a conventional-commit message synthesized from the structured diff
+// ABOUTME: Change type inferred from the kinds of files touched, subject from the symbols changed
+
+use serde::Serialize;
+use std::path::Path;
+
+use crate::change::{ChangeCategory, ChangeType, TypedChange};
+use crate::compare::{file_diff, FileDiff, FileStatus};
+use crate::unified::FileChange;
+
+/// Subjects longer than this fall back to counts
+const MAX_SUBJECT: usize = 72;
+
+/// Names listed in a subject before "and N more"
+const MAX_NAMES: usize = 3;
+
+/// A generated commit message and what it was built from
+#[derive(Debug, Clone, Serialize)]
+pub struct AutoMessage {
+    /// The full message: subject, then a body listing each file
+    pub message: String,
+    pub subject: String,
+    pub change_type: ChangeType,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub categories: Vec<ChangeCategory>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    /// `heuristic`, or `command` when `change.message_command` wrote it
+    pub source: &'static str,
+    pub files: Vec<FileDiff>,
+}
+
+/// What kind of file a path is, for inferring the change type
+fn file_kind(path: &str) -> ChangeType {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let ext = Path::new(name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    let under =
+        |dir: &str| path.starts_with(&format!("{}/", dir)) || path.contains(&format!("/{}/", dir));
+
+    const DEPS: &[&str] = &[
+        "Cargo.lock",
+        "package-lock.json",
+        "yarn.lock",
+        "pnpm-lock.yaml",
+        "poetry.lock",
+        "uv.lock",
+        "Pipfile.lock",
+        "requirements.txt",
+        "go.sum",
+        "Gemfile.lock",
+        "composer.lock",
+    ];
+    if DEPS.contains(&name) {
+        ChangeType::Deps
+    } else if under("tests")
+        || under("test")
+        || name.starts_with("test_")
+        || name.contains("_test.")
+        || name.contains(".test.")
+        || name.contains(".spec.")
+    {
+        ChangeType::Test
+    } else if matches!(ext.as_str(), "md" | "rst" | "adoc" | "txt") || under("docs") {
+        ChangeType::Docs
+    } else if ext == "sql" || under("migrations") {
+        ChangeType::Schema
+    } else if matches!(
+        ext.as_str(),
+        "toml" | "yaml" | "yml" | "json" | "ini" | "cfg" | "conf"
+    ) || under(".github")
+        || (name.starts_with('.') && ext.is_empty())
+    {
+        ChangeType::Config
+    } else {
+        ChangeType::Behavioral
+    }
+}
+
+/// The change type the files suggest: their common kind, else behavioral
+pub fn infer_change_type<'a>(paths: impl IntoIterator<Item = &'a str>) -> ChangeType {
+    let mut kinds = paths.into_iter().map(file_kind);
+    match kinds.next() {
+        Some(first) if kinds.all(|k| k == first) => first,
+        _ => ChangeType::Behavioral,
+    }
+}
+
+/// The directory every file is under, minus a leading `src`, as a scope
+fn scope(paths: &[&str]) -> Option<String> {
+    let dirs: Vec<Vec<&str>> = paths
+        .iter()
+        .map(|p| {
+            let mut parts: Vec<&str> = p.split('/').collect();
+            parts.pop();
+            if parts.first() == Some(&"src") {
+                parts.remove(0);
+            }
+            parts
+        })
+        .collect();
+    let first = dirs.first()?;
+    let common = (0..first.len())
+        .take_while(|&i| dirs.iter().all(|d| d.get(i) == first.get(i)))
+        .count();
+    (common > 0).then(|| first[common - 1].to_string())
+}
+
+/// `a, b, c and 2 more`
+fn names(list: &[String]) -> String {
+    if list.len() > MAX_NAMES {
+        format!(
+            "{} and {} more",
+            list[..MAX_NAMES].join(", "),
+            list.len() - MAX_NAMES
+        )
+    } else {
+        list.join(", ")
+    }
+}
+
+/// "add x, update y, remove z" from the lists that are not empty
+fn phrase(added: &[String], modified: &[String], removed: &[String]) -> String {
+    [("add", added), ("update", modified), ("remove", removed)]
+        .iter()
+        .filter(|(_, list)| !list.is_empty())
+        .map(|(verb, list)| format!("{} {}", verb, names(list)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn push_unique(list: &mut Vec<String>, items: &[String]) {
+    for item in items {
+        if !list.contains(item) {
+            list.push(item.clone());
+        }
+    }
+}
+
+/// A conventional-commit message for `changes`. An explicit `change_type`
+/// wins over the one inferred from the files.
+pub fn describe(
+    changes: &[FileChange],
+    change_type: Option<ChangeType>,
+    categories: &[ChangeCategory],
+    breaking: bool,
+) -> AutoMessage {
+    let files: Vec<FileDiff> = changes.iter().map(file_diff).collect();
+    let paths: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+    let change_type = change_type.unwrap_or_else(|| infer_change_type(paths.iter().copied()));
+    let scope = scope(&paths);
+
+    // Symbols when there are any, else whole files
+    let (mut added, mut modified, mut removed) = (Vec::new(), Vec::new(), Vec::new());
+    for file in &files {
+        push_unique(&mut added, &file.symbols.only_in_b);
+        push_unique(&mut modified, &file.symbols.differing);
+        push_unique(&mut removed, &file.symbols.only_in_a);
+    }
+    if added.is_empty() && modified.is_empty() && removed.is_empty() {
+        for file in &files {
+            let name = file
+                .path
+                .rsplit('/')
+                .next()
+                .unwrap_or(&file.path)
+                .to_string();
+            match file.status {
+                FileStatus::Added => added.push(name),
+                FileStatus::Modified => modified.push(name),
+                FileStatus::Removed => removed.push(name),
+            }
+        }
+    }
+
+    let mut typed = TypedChange::new("", change_type, "");
+    typed.categories = categories.to_vec();
+    typed.breaking = breaking;
+    let prefix = format!(
+        "{}{}{}: ",
+        typed.conventional_type(),
+        scope
+            .as_ref()
+            .map(|s| format!("({})", s))
+            .unwrap_or_default(),
+        if typed.is_breaking() { "!" } else { "" }
+    );
+    let mut subject = format!("{}{}", prefix, phrase(&added, &modified, &removed));
+    if subject.chars().count() > MAX_SUBJECT {
+        let counts = [
+            ("add", added.len()),
+            ("update", modified.len()),
+            ("remove", removed.len()),
+        ]
+        .iter()
+        .filter(|(_, n)| *n > 0)
+        .map(|(verb, n)| format!("{} {}", verb, n))
+        .collect::<Vec<_>>()
+        .join(", ");
+        subject = format!(
+            "{}{} across {} file{}",
+            prefix,
+            counts,
+            files.len(),
+            if files.len() == 1 { "" } else { "s" }
+        );
+    }
+    if files.is_empty() {
+        subject = format!("{}no changes", prefix);
+    }
+
+    let mut message = subject.clone();
+    if !files.is_empty() {
+        message.push('\n');
+        for file in &files {
+            let counts = match (file.lines_added, file.lines_removed) {
+                (Some(a), Some(r)) => format!(" (+{} -{})", a, r),
+                _ => " (binary)".to_string(),
+            };
+            let what = phrase(
+                &file.symbols.only_in_b,
+                &file.symbols.differing,
+                &file.symbols.only_in_a,
+            );
+            let status = match file.status {
+                FileStatus::Added => "new",
+                FileStatus::Modified => "changed",
+                FileStatus::Removed => "deleted",
+            };
+            let what = if what.is_empty() {
+                status.to_string()
+            } else {
+                what
+            };
+            message.push_str(&format!("\n- {}: {}{}", file.path, what, counts));
+        }
+    }
+    message.push('\n');
+
+    AutoMessage {
+        message,
+        subject,
+        change_type,
+        categories: typed.categories,
+        scope,
+        source: "heuristic",
+        files,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unified::Side;
+
+    fn side(content: &str) -> Option<Side> {
+        Some(Side {
+            content: content.as_bytes().to_vec(),
+            mode: "100644",
+        })
+    }
+
+    #[test]
+    fn message_names_changed_symbols_under_an_inferred_type() {
+        let changes = vec![FileChange {
+            path: "src/api/handlers.py".into(),
+            before: side("def keep():\n    return 1\n\ndef old():\n    pass\n"),
+            after: side("def keep():\n    return 2\n\ndef process(items):\n    return items\n"),
+        }];
+        let auto = describe(&changes, None, &[], false);
+        assert_eq!(auto.change_type, ChangeType::Behavioral);
+        assert_eq!(auto.scope.as_deref(), Some("api"));
+        assert_eq!(
+            auto.subject,
+            "feat(api): add process, update keep, remove old"
+        );
+        assert!(auto
+            .message
+            .contains("- src/api/handlers.py: add process, update keep, remove old (+3 -3)"));
+
+        let docs = vec![FileChange {
+            path: "README.md".into(),
+            before: side("# Usage\n"),
+            after: side("# Usage\n\nMore.\n"),
+        }];
+        let auto = describe(&docs, None, &[ChangeCategory::Fix], false);
+        assert_eq!(auto.subject, "docs: update Usage");
+
+        assert_eq!(
+            infer_change_type(["tests/cli.rs", "src/lib_test.go"]),
+            ChangeType::Test
+        );
+        assert_eq!(
+            infer_change_type(["Cargo.lock", "src/main.rs"]),
+            ChangeType::Behavioral
+        );
+        let fix = describe(
+            &docs,
+            Some(ChangeType::Behavioral),
+            &[ChangeCategory::Fix],
+            true,
+        );
+        assert!(fix.subject.starts_with("fix!: "));
+    }
+}
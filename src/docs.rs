@@ -0,0 +1,700 @@
+// ABOUTME: Normalizes raw docstrings/doc comments and parses their tags into structured fields
+// ABOUTME: Understands Sphinx/Google (Python), rustdoc sections, and JSDoc tags; renders markdown
+
+use serde::{Deserialize, Serialize};
+
+use crate::symbols::{SupportedLanguage, SymbolKind};
+
+/// Documentation of a symbol, split into the parts agents look for
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DocComment {
+    /// First paragraph, on one line
+    pub summary: String,
+    /// Remaining prose, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub params: Vec<DocItem>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub returns: Option<DocItem>,
+    /// Exceptions thrown, errors returned, or panics
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub raises: Vec<DocItem>,
+    /// Example code, without its markdown fences
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub examples: Vec<String>,
+}
+
+/// A documented parameter, return value, or error
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DocItem {
+    /// Parameter name, or the error type for `raises`; empty for `returns`
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub ty: Option<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+}
+
+impl DocItem {
+    fn append(&mut self, text: &str) {
+        if !self.description.is_empty() {
+            self.description.push(' ');
+        }
+        self.description.push_str(text);
+    }
+}
+
+/// Strip comment markers / quotes from a raw doc comment and dedent it
+pub fn normalize(raw: &str, language: SupportedLanguage) -> String {
+    let raw = raw.trim();
+    let lines: Vec<String> = if language == SupportedLanguage::Python {
+        let inner = strip_python_quotes(raw);
+        inner.lines().map(str::to_string).collect()
+    } else if matches!(language, SupportedLanguage::Ruby | SupportedLanguage::Shell) {
+        raw.lines()
+            .map(|l| {
+                let text = l.trim_start().trim_start_matches('#');
+                text.strip_prefix(' ').unwrap_or(text).to_string()
+            })
+            .collect()
+    } else {
+        strip_comment_markers(raw)
+    };
+
+    // PEP 257: the first line's indentation is not part of the block's
+    let mut lines = lines.into_iter();
+    let first = lines.next().map(|l| l.trim().to_string());
+    let rest: Vec<String> = lines.collect();
+    // Counted in characters: indentation may be non-ASCII whitespace
+    let indent = rest
+        .iter()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| l.chars().take_while(|c| c.is_whitespace()).count())
+        .min()
+        .unwrap_or(0);
+
+    let mut out: Vec<String> = first.into_iter().collect();
+    out.extend(rest.iter().map(|l| {
+        // At most `indent` leading whitespace characters, cut on a char boundary
+        let cut = l
+            .char_indices()
+            .take(indent)
+            .take_while(|(_, c)| c.is_whitespace())
+            .last()
+            .map_or(0, |(i, c)| i + c.len_utf8());
+        l[cut..].trim_end().to_string()
+    }));
+
+    while out.first().is_some_and(|l| l.is_empty()) {
+        out.remove(0);
+    }
+    while out.last().is_some_and(|l| l.is_empty()) {
+        out.pop();
+    }
+    out.join("\n")
+}
+
+fn strip_python_quotes(raw: &str) -> &str {
+    let raw = raw.trim_start_matches(['r', 'R', 'u', 'U']);
+    for quote in ["\"\"\"", "'''", "\"", "'"] {
+        if let Some(inner) = raw.strip_prefix(quote) {
+            return inner.strip_suffix(quote).unwrap_or(inner);
+        }
+    }
+    raw
+}
+
+/// Lines of `///`, `//!`, or `/** ... */` comments without their markers
+fn strip_comment_markers(raw: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    for line in raw.lines() {
+        let trimmed = line.trim_start();
+        let text = if let Some(rest) = trimmed
+            .strip_prefix("///")
+            .or_else(|| trimmed.strip_prefix("//!"))
+            .or_else(|| trimmed.strip_prefix("//"))
+        {
+            rest
+        } else {
+            let rest = trimmed
+                .strip_prefix("/**")
+                .or_else(|| trimmed.strip_prefix("/*!"))
+                .or_else(|| trimmed.strip_prefix("/*"))
+                .unwrap_or(trimmed);
+            let rest = rest.trim_end().strip_suffix("*/").unwrap_or(rest);
+            if rest.trim().is_empty() && trimmed.starts_with("/*") {
+                continue;
+            }
+            // Continuation lines of a block comment lead with " * "
+            if rest.trim_start().starts_with('*') && !trimmed.starts_with("/*") {
+                &rest.trim_start()[1..]
+            } else if trimmed == "*/" {
+                continue;
+            } else {
+                rest
+            }
+        };
+        lines.push(text.strip_prefix(' ').unwrap_or(text).to_string());
+    }
+    lines
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Section {
+    Description,
+    Params,
+    Returns,
+    Raises,
+    Examples,
+}
+
+/// Section a heading line starts (Google/NumPy `Args:`, rustdoc `# Arguments`)
+fn section_heading(line: &str) -> Option<Section> {
+    let heading = line
+        .trim()
+        .trim_start_matches('#')
+        .trim()
+        .trim_end_matches(':');
+    let section = match heading.to_lowercase().as_str() {
+        "args" | "arguments" | "parameters" | "params" => Section::Params,
+        "returns" | "return" | "yields" => Section::Returns,
+        "raises" | "errors" | "panics" | "throws" | "exceptions" => Section::Raises,
+        "example" | "examples" => Section::Examples,
+        _ => return None,
+    };
+    // Headings are alone on their line: `Args:` or `# Examples`
+    let is_heading = line.trim().starts_with('#') || line.trim().ends_with(':');
+    is_heading.then_some(section)
+}
+
+/// Parse a normalized doc comment into structured fields
+pub fn parse(text: &str) -> DocComment {
+    let mut doc = DocComment::default();
+    let mut prose: Vec<&str> = Vec::new();
+    let mut section = Section::Description;
+    let mut example: Option<Vec<String>> = None;
+    let mut in_fence = false;
+    // Which list the last item went to, so continuation lines extend it
+    let mut last: Option<Section> = None;
+    // Indentation of the items in the current section; deeper lines continue them
+    let mut item_indent: Option<usize> = None;
+
+    let flush_example = |example: &mut Option<Vec<String>>, doc: &mut DocComment| {
+        if let Some(lines) = example.take() {
+            let code = lines.join("\n").trim().to_string();
+            if !code.is_empty() {
+                doc.examples.push(code);
+            }
+        }
+    };
+
+    for line in text.lines() {
+        let trimmed = line.trim();
+
+        // Code fences (rustdoc/markdown examples) are copied verbatim
+        if trimmed.starts_with("```") {
+            if in_fence {
+                in_fence = false;
+                flush_example(&mut example, &mut doc);
+            } else {
+                in_fence = true;
+                example = Some(Vec::new());
+            }
+            continue;
+        }
+        if in_fence {
+            if let Some(lines) = example.as_mut() {
+                lines.push(line.to_string());
+            }
+            continue;
+        }
+
+        // Doctest prompts are examples wherever they appear
+        if trimmed.starts_with(">>>") && section != Section::Examples {
+            example
+                .get_or_insert_with(Vec::new)
+                .push(trimmed.to_string());
+            continue;
+        }
+
+        if let Some(tagged) = parse_tag(trimmed, &mut doc) {
+            flush_example(&mut example, &mut doc);
+            section = tagged;
+            last = Some(tagged);
+            if tagged == Section::Examples {
+                example = Some(Vec::new());
+            }
+            continue;
+        }
+
+        if let Some(heading) = section_heading(trimmed) {
+            flush_example(&mut example, &mut doc);
+            section = heading;
+            last = None;
+            item_indent = None;
+            if heading == Section::Examples {
+                example = Some(Vec::new());
+            }
+            continue;
+        }
+
+        match section {
+            Section::Description => {
+                if trimmed.is_empty() {
+                    flush_example(&mut example, &mut doc);
+                }
+                if example.is_some() {
+                    if let Some(lines) = example.as_mut() {
+                        lines.push(trimmed.to_string());
+                    }
+                } else if last.is_some() && !trimmed.is_empty() {
+                    // Continuation of the last :param:/@param
+                    append_to_last(&mut doc, last, trimmed);
+                } else {
+                    last = None;
+                    prose.push(line);
+                }
+            }
+            Section::Examples => {
+                if let Some(lines) = example.as_mut() {
+                    lines.push(line.to_string());
+                }
+            }
+            Section::Params | Section::Raises => {
+                if trimmed.is_empty() {
+                    continue;
+                }
+                let indent = line.len() - line.trim_start().len();
+                let continues = last == Some(section) && item_indent.is_some_and(|i| indent > i);
+                match parse_list_item(trimmed) {
+                    Some(item) if !continues => {
+                        item_indent = Some(indent);
+                        if section == Section::Params {
+                            doc.params.push(item);
+                        } else {
+                            doc.raises.push(item);
+                        }
+                        last = Some(section);
+                    }
+                    _ if last == Some(section) => append_to_last(&mut doc, last, trimmed),
+                    _ => {
+                        // Free text under `# Errors`/`# Panics`
+                        let item = DocItem {
+                            description: trimmed.to_string(),
+                            ..Default::default()
+                        };
+                        if section == Section::Params {
+                            doc.params.push(item);
+                        } else {
+                            doc.raises.push(item);
+                        }
+                        last = Some(section);
+                    }
+                }
+            }
+            Section::Returns => {
+                if !trimmed.is_empty() {
+                    let returns = doc.returns.get_or_insert_with(DocItem::default);
+                    match trimmed.split_once(':') {
+                        // Google style: `int: the count`
+                        Some((ty, desc)) if returns.description.is_empty() && is_type(ty) => {
+                            returns.ty = Some(ty.trim().to_string());
+                            returns.append(desc.trim());
+                        }
+                        _ => returns.append(trimmed),
+                    }
+                }
+            }
+        }
+    }
+    flush_example(&mut example, &mut doc);
+
+    // Summary is the first paragraph; the rest is the description
+    let prose = prose.join("\n");
+    let mut paragraphs = prose.split("\n\n").map(str::trim).filter(|p| !p.is_empty());
+    doc.summary = paragraphs
+        .next()
+        .map(|p| p.split_whitespace().collect::<Vec<_>>().join(" "))
+        .unwrap_or_default();
+    let rest: Vec<&str> = paragraphs.collect();
+    if !rest.is_empty() {
+        doc.description = Some(rest.join("\n\n"));
+    }
+    doc
+}
+
+fn append_to_last(doc: &mut DocComment, last: Option<Section>, text: &str) {
+    let item = match last {
+        Some(Section::Params) => doc.params.last_mut(),
+        Some(Section::Raises) => doc.raises.last_mut(),
+        Some(Section::Returns) => doc.returns.as_mut(),
+        _ => None,
+    };
+    if let Some(item) = item {
+        item.append(text);
+    }
+}
+
+/// Whether a `name: desc` prefix looks like a type rather than prose
+fn is_type(text: &str) -> bool {
+    let text = text.trim();
+    !text.is_empty() && !text.contains(' ') || text.contains('[') || text.contains('|')
+}
+
+/// A parameter-style list item: `name (type): desc`, `` * `name` - desc ``,
+/// `- name: desc`, or `name : type` (NumPy)
+fn parse_list_item(line: &str) -> Option<DocItem> {
+    let line = line
+        .strip_prefix("* ")
+        .or_else(|| line.strip_prefix("- "))
+        .unwrap_or(line);
+
+    let (head, description) = line
+        .split_once(" - ")
+        .or_else(|| line.split_once(": "))
+        .or_else(|| line.strip_suffix(':').map(|h| (h, "")))
+        .unwrap_or((line, ""));
+    let head = head.trim();
+
+    let (name, ty) = match head.split_once('(') {
+        Some((name, ty)) => (name.trim(), Some(ty.trim_end_matches(')').trim())),
+        None => match head.split_once(" : ") {
+            Some((name, ty)) => (name.trim(), Some(ty.trim())),
+            None => (head, None),
+        },
+    };
+    let name = name.trim_matches('`');
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '.' | '*' | ':' | '$'));
+    valid.then(|| DocItem {
+        name: name.to_string(),
+        ty: ty.filter(|t| !t.is_empty()).map(str::to_string),
+        description: description.trim().to_string(),
+    })
+}
+
+/// Handle one Sphinx (`:param x:`) or JSDoc (`@param`) tag line. Returns
+/// the section later lines continue, or None if the line is not a tag.
+fn parse_tag(line: &str, doc: &mut DocComment) -> Option<Section> {
+    if let Some(rest) = line.strip_prefix('@') {
+        let (tag, rest) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        let (ty, rest) = jsdoc_type(rest.trim());
+        return match tag {
+            "param" | "arg" | "argument" => {
+                let (name, desc) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                let name = name.trim_matches(['[', ']']);
+                let name = name.split('=').next().unwrap_or(name);
+                doc.params.push(DocItem {
+                    name: name.to_string(),
+                    ty,
+                    description: desc.trim().trim_start_matches("- ").to_string(),
+                });
+                Some(Section::Params)
+            }
+            "returns" | "return" => {
+                doc.returns = Some(DocItem {
+                    name: String::new(),
+                    ty,
+                    description: rest.to_string(),
+                });
+                Some(Section::Returns)
+            }
+            "throws" | "exception" => {
+                let (name, desc) = match &ty {
+                    Some(ty) => (ty.clone(), rest.to_string()),
+                    None => {
+                        let (name, desc) =
+                            rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                        (name.to_string(), desc.trim().to_string())
+                    }
+                };
+                doc.raises.push(DocItem {
+                    name,
+                    ty: None,
+                    description: desc,
+                });
+                Some(Section::Raises)
+            }
+            "example" => Some(Section::Examples),
+            _ => None,
+        };
+    }
+
+    let rest = line.strip_prefix(':')?;
+    let (field, desc) = rest.split_once(':')?;
+    let desc = desc.trim().to_string();
+    let mut words = field.split_whitespace();
+    let tag = words.next()?;
+    let args: Vec<&str> = words.collect();
+
+    match tag {
+        "param" | "parameter" | "arg" | "argument" | "key" | "keyword" => {
+            // `:param name:` or `:param type name:`
+            let name = args.last()?.to_string();
+            let ty = (args.len() > 1).then(|| args[..args.len() - 1].join(" "));
+            match doc.params.iter_mut().find(|p| p.name == name) {
+                Some(existing) => existing.description = desc,
+                None => doc.params.push(DocItem {
+                    name,
+                    ty,
+                    description: desc,
+                }),
+            }
+            Some(Section::Params)
+        }
+        "type" => {
+            let name = args.first()?.to_string();
+            match doc.params.iter_mut().find(|p| p.name == name) {
+                Some(existing) => existing.ty = Some(desc),
+                None => doc.params.push(DocItem {
+                    name,
+                    ty: Some(desc),
+                    description: String::new(),
+                }),
+            }
+            Some(Section::Params)
+        }
+        "returns" | "return" => {
+            doc.returns.get_or_insert_with(DocItem::default).description = desc;
+            Some(Section::Returns)
+        }
+        "rtype" => {
+            doc.returns.get_or_insert_with(DocItem::default).ty = Some(desc);
+            Some(Section::Returns)
+        }
+        "raises" | "raise" | "except" | "exception" => {
+            doc.raises.push(DocItem {
+                name: args.join(" "),
+                ty: None,
+                description: desc,
+            });
+            Some(Section::Raises)
+        }
+        _ => None,
+    }
+}
+
+/// Split a leading JSDoc `{Type}` off a tag's text
+fn jsdoc_type(text: &str) -> (Option<String>, &str) {
+    if let Some(inner) = text.strip_prefix('{') {
+        // Types may nest braces: {Object<string, {a: number}>}
+        let mut depth = 1;
+        for (i, c) in inner.char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return (Some(inner[..i].to_string()), inner[i + 1..].trim());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    (None, text)
+}
+
+fn fence_language(language: SupportedLanguage) -> &'static str {
+    match language {
+        SupportedLanguage::Python => "python",
+        SupportedLanguage::Rust => "rust",
+        SupportedLanguage::JavaScript => "javascript",
+        SupportedLanguage::TypeScript => "typescript",
+        SupportedLanguage::C => "c",
+        SupportedLanguage::Cpp => "cpp",
+        SupportedLanguage::Ruby => "ruby",
+        SupportedLanguage::Php => "php",
+        SupportedLanguage::Shell => "bash",
+        SupportedLanguage::Dockerfile => "dockerfile",
+        SupportedLanguage::Yaml => "yaml",
+        SupportedLanguage::Markdown => "markdown",
+        SupportedLanguage::Sql => "sql",
+        SupportedLanguage::Notebook => "python",
+    }
+}
+
+/// Render a symbol's documentation as markdown
+pub fn render_markdown(
+    name: &str,
+    kind: SymbolKind,
+    signature: Option<&str>,
+    doc: Option<&DocComment>,
+    language: SupportedLanguage,
+) -> String {
+    let fence = fence_language(language);
+    let kind = serde_json::to_value(kind)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_default();
+
+    let mut out = format!("### `{}` ({})\n", name, kind);
+    if let Some(signature) = signature {
+        out.push_str(&format!("\n```{}\n{}\n```\n", fence, signature));
+    }
+    let Some(doc) = doc else {
+        return out;
+    };
+
+    let item = |item: &DocItem| {
+        let mut line = String::from("- ");
+        if !item.name.is_empty() {
+            line.push_str(&format!("`{}`", item.name));
+        }
+        if let Some(ty) = &item.ty {
+            line.push_str(&format!(" (`{}`)", ty));
+        }
+        if !item.description.is_empty() {
+            if !item.name.is_empty() || item.ty.is_some() {
+                line.push_str(": ");
+            }
+            line.push_str(&item.description);
+        }
+        line.push('\n');
+        line
+    };
+
+    if !doc.summary.is_empty() {
+        out.push_str(&format!("\n{}\n", doc.summary));
+    }
+    if let Some(description) = &doc.description {
+        out.push_str(&format!("\n{}\n", description));
+    }
+    if !doc.params.is_empty() {
+        out.push_str("\n**Parameters**\n\n");
+        doc.params.iter().for_each(|p| out.push_str(&item(p)));
+    }
+    if let Some(returns) = &doc.returns {
+        out.push_str("\n**Returns**\n\n");
+        out.push_str(&item(returns));
+    }
+    if !doc.raises.is_empty() {
+        out.push_str("\n**Raises**\n\n");
+        doc.raises.iter().for_each(|r| out.push_str(&item(r)));
+    }
+    if !doc.examples.is_empty() {
+        out.push_str("\n**Examples**\n");
+        for example in &doc.examples {
+            out.push_str(&format!("\n```{}\n{}\n```\n", fence, example));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_strips_markers_and_dedents() {
+        let python = "\"\"\"Fetch a user.\n\n        Looks them up by id.\n        \"\"\"";
+        assert_eq!(
+            normalize(python, SupportedLanguage::Python),
+            "Fetch a user.\n\nLooks them up by id."
+        );
+
+        let rust = "/// Fetch a user.\n///\n/// Looks them up by id.";
+        assert_eq!(
+            normalize(rust, SupportedLanguage::Rust),
+            "Fetch a user.\n\nLooks them up by id."
+        );
+
+        let jsdoc = "/**\n * Fetch a user.\n *\n * @param {number} id - The id\n */";
+        assert_eq!(
+            normalize(jsdoc, SupportedLanguage::JavaScript),
+            "Fetch a user.\n\n@param {number} id - The id"
+        );
+    }
+
+    #[test]
+    fn normalize_dedents_non_ascii_whitespace() {
+        // U+3000 is one character but three bytes
+        let python = "\"\"\"Summary.\n x\n\u{3000}y\n\"\"\"";
+        assert_eq!(
+            normalize(python, SupportedLanguage::Python),
+            "Summary.\nx\ny"
+        );
+        let deeper = "\"\"\"Summary.\n\u{3000}\u{3000}x\n\u{3000}y\n\"\"\"";
+        assert_eq!(
+            normalize(deeper, SupportedLanguage::Python),
+            "Summary.\n\u{3000}x\ny"
+        );
+    }
+
+    #[test]
+    fn parse_sphinx_tags() {
+        let doc = parse(
+            "Fetch a user.\n\n:param int user_id: The id\n:param strict: Fail if missing\n:type strict: bool\n:returns: The user\n:rtype: User\n:raises KeyError: If missing",
+        );
+        assert_eq!(doc.summary, "Fetch a user.");
+        assert_eq!(doc.params.len(), 2);
+        assert_eq!(doc.params[0].name, "user_id");
+        assert_eq!(doc.params[0].ty.as_deref(), Some("int"));
+        assert_eq!(doc.params[1].ty.as_deref(), Some("bool"));
+        let returns = doc.returns.unwrap();
+        assert_eq!(returns.ty.as_deref(), Some("User"));
+        assert_eq!(returns.description, "The user");
+        assert_eq!(doc.raises[0].name, "KeyError");
+    }
+
+    #[test]
+    fn parse_google_sections_and_doctests() {
+        let doc = parse(
+            "Add numbers.\n\nArgs:\n    a (int): First\n    b (int): Second,\n        continued.\n\nReturns:\n    int: The sum\n\nExamples:\n    >>> add(1, 2)\n    3",
+        );
+        assert_eq!(doc.params.len(), 2);
+        assert_eq!(doc.params[1].description, "Second, continued.");
+        assert_eq!(doc.returns.as_ref().unwrap().ty.as_deref(), Some("int"));
+        assert_eq!(doc.examples, vec![">>> add(1, 2)\n    3"]);
+    }
+
+    #[test]
+    fn parse_rustdoc_sections() {
+        let doc = parse(
+            "Parse a config.\n\nMore detail here.\n\n# Arguments\n\n* `path` - Where to read from\n\n# Errors\n\nFails if the file is missing.\n\n# Examples\n\n```\nlet c = parse(\"a.toml\")?;\n```",
+        );
+        assert_eq!(doc.summary, "Parse a config.");
+        assert_eq!(doc.description.as_deref(), Some("More detail here."));
+        assert_eq!(doc.params[0].name, "path");
+        assert_eq!(doc.params[0].description, "Where to read from");
+        assert_eq!(doc.raises[0].description, "Fails if the file is missing.");
+        assert_eq!(doc.examples, vec!["let c = parse(\"a.toml\")?;"]);
+    }
+
+    #[test]
+    fn parse_jsdoc_tags() {
+        let doc = parse(
+            "Fetch a user.\n\n@param {number} id - The id\n@param {Object} [opts] Options\n@returns {Promise<User>} The user\n@throws {NotFound} When missing\n@example\nawait fetchUser(1);",
+        );
+        assert_eq!(doc.params[0].name, "id");
+        assert_eq!(doc.params[0].ty.as_deref(), Some("number"));
+        assert_eq!(doc.params[0].description, "The id");
+        assert_eq!(doc.params[1].name, "opts");
+        assert_eq!(
+            doc.returns.as_ref().unwrap().ty.as_deref(),
+            Some("Promise<User>")
+        );
+        assert_eq!(doc.raises[0].name, "NotFound");
+        assert_eq!(doc.examples, vec!["await fetchUser(1);"]);
+    }
+
+    #[test]
+    fn render_markdown_sections() {
+        let doc = parse("Add numbers.\n\n:param a: First\n:returns: The sum");
+        let md = render_markdown(
+            "add",
+            SymbolKind::Function,
+            Some("def add(a, b):"),
+            Some(&doc),
+            SupportedLanguage::Python,
+        );
+        assert!(md.starts_with("### `add` (function)\n"));
+        assert!(md.contains("```python\ndef add(a, b):\n```"));
+        assert!(md.contains("**Parameters**\n\n- `a`: First\n"));
+        assert!(md.contains("**Returns**\n\n- The sum\n"));
+    }
+}
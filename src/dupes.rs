@@ -0,0 +1,315 @@
+// ABOUTME: Near-duplicate code detection over symbol bodies via token-shingle hashing
+// ABOUTME: Backs `agentjj dupes`: clusters of similar functions and copies introduced by a change
+
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use crate::symbols::{Symbol, SymbolKind};
+
+/// Tokens per shingle
+const SHINGLE_SIZE: usize = 5;
+
+/// Split source into comparable tokens: identifiers and keywords as-is,
+/// numbers and string literals collapsed, punctuation one char at a time.
+/// Whitespace and comments are dropped so formatting does not matter.
+pub fn tokenize(source: &str) -> Vec<String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if (c == '/' && chars.get(i + 1) == Some(&'/')) || c == '#' {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+        } else if c == '/' && chars.get(i + 1) == Some(&'*') {
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                i += 1;
+            }
+            i += 2;
+        } else if c == '\'' && is_lifetime(&chars[i + 1..]) {
+            tokens.push(c.to_string());
+            i += 1;
+        } else if c == '"' || c == '\'' || c == '`' {
+            i += 1;
+            while i < chars.len() && chars[i] != c {
+                if chars[i] == '\\' {
+                    i += 1;
+                }
+                i += 1;
+            }
+            i += 1;
+            tokens.push("\"\"".to_string());
+        } else if c.is_ascii_digit() {
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '.') {
+                i += 1;
+            }
+            tokens.push("0".to_string());
+        } else if c.is_alphanumeric() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(chars[start..i].iter().collect());
+        } else {
+            tokens.push(c.to_string());
+            i += 1;
+        }
+    }
+    tokens
+}
+
+/// Whether a `'` followed by `rest` starts a Rust lifetime (`'a`, `'static`)
+/// rather than a quoted literal (`'a'`, `'text'`)
+fn is_lifetime(rest: &[char]) -> bool {
+    let ident = rest
+        .iter()
+        .take_while(|c| c.is_alphanumeric() || **c == '_')
+        .count();
+    ident > 0 && rest.get(ident) != Some(&'\'')
+}
+
+/// Hashes of every run of `SHINGLE_SIZE` consecutive tokens
+pub fn shingles(tokens: &[String]) -> HashSet<u64> {
+    tokens
+        .windows(SHINGLE_SIZE.min(tokens.len()).max(1))
+        .map(|window| {
+            let mut hasher = DefaultHasher::new();
+            window.hash(&mut hasher);
+            hasher.finish()
+        })
+        .collect()
+}
+
+/// Jaccard similarity of two shingle sets
+pub fn similarity(a: &HashSet<u64>, b: &HashSet<u64>) -> f64 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    let shared = a.intersection(b).count();
+    shared as f64 / (a.len() + b.len() - shared) as f64
+}
+
+/// A function or method body to compare
+#[derive(Debug, Clone, Serialize)]
+pub struct Fragment {
+    pub file: String,
+    pub name: String,
+    pub kind: SymbolKind,
+    pub start_line: usize,
+    pub end_line: usize,
+    #[serde(skip)]
+    pub shingles: HashSet<u64>,
+}
+
+impl Fragment {
+    /// Whether two fragments could reach `threshold`: Jaccard similarity is
+    /// at most the ratio of the smaller set to the larger
+    fn may_match(&self, other: &Fragment, threshold: f64) -> bool {
+        let (a, b) = (self.shingles.len(), other.shingles.len());
+        a.min(b) as f64 >= threshold * a.max(b) as f64
+    }
+
+    fn same_symbol(&self, other: &Fragment) -> bool {
+        self.file == other.file && self.name == other.name
+    }
+}
+
+/// Function and method fragments of a file with at least `min_lines` lines,
+/// including methods nested in classes and impls
+pub fn fragments(file: &str, source: &str, symbols: &[Symbol], min_lines: usize) -> Vec<Fragment> {
+    let lines: Vec<&str> = source.lines().collect();
+    let mut out = Vec::new();
+    let mut stack: Vec<&Symbol> = symbols.iter().collect();
+    while let Some(symbol) = stack.pop() {
+        stack.extend(&symbol.children);
+        if !matches!(symbol.kind, SymbolKind::Function | SymbolKind::Method)
+            || symbol.end_line + 1 < symbol.start_line + min_lines
+            || symbol.start_line == 0
+        {
+            continue;
+        }
+        let end = symbol.end_line.min(lines.len());
+        let body = lines
+            .get(symbol.start_line - 1..end)
+            .unwrap_or_default()
+            .join("\n");
+        out.push(Fragment {
+            file: file.to_string(),
+            name: symbol.name.clone(),
+            kind: symbol.kind,
+            start_line: symbol.start_line,
+            end_line: symbol.end_line,
+            shingles: shingles(&tokenize(&body)),
+        });
+    }
+    out.sort_by_key(|f| f.start_line);
+    out
+}
+
+/// A group of fragments that are all transitively similar
+#[derive(Debug, Clone, Serialize)]
+pub struct Cluster {
+    /// Lowest similarity among the pairs that joined the cluster
+    pub similarity: f64,
+    pub members: Vec<Fragment>,
+}
+
+fn find(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+/// Cluster fragments whose pairwise similarity is at least `threshold`,
+/// most similar clusters first
+pub fn clusters(fragments: &[Fragment], threshold: f64) -> Vec<Cluster> {
+    let mut parent: Vec<usize> = (0..fragments.len()).collect();
+    let mut weakest: BTreeMap<usize, f64> = BTreeMap::new();
+    let mut links = Vec::new();
+    for (i, a) in fragments.iter().enumerate() {
+        for (j, b) in fragments.iter().enumerate().skip(i + 1) {
+            if !a.may_match(b, threshold) {
+                continue;
+            }
+            let score = similarity(&a.shingles, &b.shingles);
+            if score >= threshold {
+                links.push((i, j, score));
+            }
+        }
+    }
+    for &(i, j, _) in &links {
+        let (ri, rj) = (find(&mut parent, i), find(&mut parent, j));
+        if ri != rj {
+            parent[rj] = ri;
+        }
+    }
+    for (i, _, score) in links {
+        let root = find(&mut parent, i);
+        let entry = weakest.entry(root).or_insert(score);
+        *entry = entry.min(score);
+    }
+
+    let mut out: Vec<Cluster> = weakest
+        .into_iter()
+        .map(|(root, similarity)| Cluster {
+            similarity: (similarity * 100.0).round() / 100.0,
+            members: (0..fragments.len())
+                .filter(|&k| find(&mut parent, k) == root)
+                .map(|k| fragments[k].clone())
+                .collect(),
+        })
+        .collect();
+    out.sort_by(|a, b| {
+        b.similarity
+            .total_cmp(&a.similarity)
+            .then_with(|| b.members.len().cmp(&a.members.len()))
+    });
+    out
+}
+
+/// An existing fragment that a new one resembles
+#[derive(Debug, Clone, Serialize)]
+pub struct Match {
+    pub similarity: f64,
+    #[serde(flatten)]
+    pub fragment: Fragment,
+}
+
+/// Fragments of `corpus` (other than `fragment` itself) at least
+/// `threshold` similar to `fragment`, most similar first
+pub fn matches(fragment: &Fragment, corpus: &[Fragment], threshold: f64) -> Vec<Match> {
+    let mut out: Vec<Match> = corpus
+        .iter()
+        .filter(|other| !other.same_symbol(fragment) && fragment.may_match(other, threshold))
+        .filter_map(|other| {
+            let score = similarity(&fragment.shingles, &other.shingles);
+            (score >= threshold).then(|| Match {
+                similarity: (score * 100.0).round() / 100.0,
+                fragment: other.clone(),
+            })
+        })
+        .collect();
+    out.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbols::{extract_symbols, SupportedLanguage};
+
+    const SOURCE: &str = r#"
+fn total_price(items: &[Item]) -> u64 {
+    let mut sum = 0;
+    for item in items {
+        sum += item.price * item.quantity;
+    }
+    sum
+}
+
+fn total_cost(items: &[Item]) -> u64 {
+    // same loop, different name
+    let mut sum = 0;
+    for item in items {
+        sum += item.price * item.quantity;
+    }
+    sum
+}
+
+fn greet(name: &str) -> String {
+    let greeting = format!("hello {}", name);
+    println!("{}", greeting);
+    log::info!("greeted");
+    greeting
+}
+"#;
+
+    fn rust_fragments() -> Vec<Fragment> {
+        let symbols = extract_symbols(SOURCE, SupportedLanguage::Rust).unwrap();
+        fragments("src/lib.rs", SOURCE, &symbols, 3)
+    }
+
+    #[test]
+    fn tokenize_ignores_formatting_and_literals() {
+        assert_eq!(
+            tokenize("let x = 42; // note\nlet   y=\"a b\";"),
+            tokenize("let x = 7;\nlet y = 'c';")
+        );
+        assert_eq!(
+            tokenize("fn f() -> &'static str"),
+            vec!["fn", "f", "(", ")", "-", ">", "&", "'", "static", "str"]
+        );
+    }
+
+    #[test]
+    fn clusters_similar_functions() {
+        let fragments = rust_fragments();
+        assert_eq!(fragments.len(), 3);
+
+        let clusters = clusters(&fragments, 0.8);
+        assert_eq!(clusters.len(), 1);
+        let names: Vec<&str> = clusters[0]
+            .members
+            .iter()
+            .map(|f| f.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["total_price", "total_cost"]);
+        assert!(clusters[0].similarity >= 0.8);
+    }
+
+    #[test]
+    fn matches_skip_the_fragment_itself() {
+        let fragments = rust_fragments();
+        let found = matches(&fragments[1], &fragments, 0.8);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].fragment.name, "total_price");
+        assert!(matches(&fragments[2], &fragments, 0.8).is_empty());
+    }
+}
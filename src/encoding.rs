@@ -0,0 +1,179 @@
+// ABOUTME: Text encoding and line-ending detection for files agents read and write
+// ABOUTME: Decodes UTF-8/UTF-16/latin-1 (with BOM) and re-encodes edits in the original format
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Encoding {
+    #[serde(rename = "utf-8")]
+    Utf8,
+    #[serde(rename = "utf-16le")]
+    Utf16Le,
+    #[serde(rename = "utf-16be")]
+    Utf16Be,
+    /// Any byte sequence that is not UTF-8 (ISO-8859-1)
+    #[serde(rename = "latin-1")]
+    Latin1,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+    /// Both LF and CRLF lines; left exactly as written
+    Mixed,
+    /// Single line, nothing to preserve
+    None,
+}
+
+/// How a file's text is stored on disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextFormat {
+    pub encoding: Encoding,
+    /// Whether the file starts with a byte order mark
+    pub bom: bool,
+    pub line_ending: LineEnding,
+}
+
+impl Default for TextFormat {
+    fn default() -> Self {
+        Self {
+            encoding: Encoding::Utf8,
+            bom: false,
+            line_ending: LineEnding::Lf,
+        }
+    }
+}
+
+/// The line ending a text uses
+pub fn line_ending(text: &str) -> LineEnding {
+    let crlf = text.matches("\r\n").count();
+    let lf = text.matches('\n').count() - crlf;
+    match (lf, crlf) {
+        (0, 0) => LineEnding::None,
+        (_, 0) => LineEnding::Lf,
+        (0, _) => LineEnding::Crlf,
+        _ => LineEnding::Mixed,
+    }
+}
+
+fn utf16(bytes: &[u8], from: fn([u8; 2]) -> u16) -> Option<String> {
+    let units: Vec<u16> = bytes
+        .chunks(2)
+        .map(|pair| from([pair[0], *pair.get(1).unwrap_or(&0)]))
+        .collect();
+    String::from_utf16(&units).ok()
+}
+
+/// Decode file bytes, detecting the encoding from a BOM or from whether they
+/// are valid UTF-8. Line endings are kept as they are. Returns None for
+/// binary content (NUL bytes outside UTF-16).
+pub fn decode(bytes: &[u8]) -> Option<(String, TextFormat)> {
+    let (text, encoding, bom) = if let Some(rest) = bytes.strip_prefix(b"\xEF\xBB\xBF") {
+        (String::from_utf8(rest.to_vec()).ok()?, Encoding::Utf8, true)
+    } else if let Some(rest) = bytes.strip_prefix(b"\xFF\xFE") {
+        (utf16(rest, u16::from_le_bytes)?, Encoding::Utf16Le, true)
+    } else if let Some(rest) = bytes.strip_prefix(b"\xFE\xFF") {
+        (utf16(rest, u16::from_be_bytes)?, Encoding::Utf16Be, true)
+    } else if bytes.contains(&0) {
+        return None;
+    } else {
+        match std::str::from_utf8(bytes) {
+            Ok(text) => (text.to_string(), Encoding::Utf8, false),
+            Err(_) => (
+                bytes.iter().map(|&b| b as char).collect(),
+                Encoding::Latin1,
+                false,
+            ),
+        }
+    };
+    let line_ending = line_ending(&text);
+    Some((
+        text,
+        TextFormat {
+            encoding,
+            bom,
+            line_ending,
+        },
+    ))
+}
+
+/// Encode text for a file stored as `format`. In CRLF files, bare LFs (as
+/// agents usually write) become CRLF. Characters latin-1 cannot represent
+/// become `?`.
+pub fn encode(text: &str, format: &TextFormat) -> Vec<u8> {
+    let text = if format.line_ending == LineEnding::Crlf {
+        text.replace("\r\n", "\n").replace('\n', "\r\n")
+    } else {
+        text.to_string()
+    };
+    match format.encoding {
+        Encoding::Utf8 => {
+            let mut out = Vec::new();
+            if format.bom {
+                out.extend_from_slice(b"\xEF\xBB\xBF");
+            }
+            out.extend_from_slice(text.as_bytes());
+            out
+        }
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            let le = format.encoding == Encoding::Utf16Le;
+            let mut out = Vec::new();
+            if format.bom {
+                out.extend_from_slice(if le { b"\xFF\xFE" } else { b"\xFE\xFF" });
+            }
+            for unit in text.encode_utf16() {
+                out.extend_from_slice(&if le {
+                    unit.to_le_bytes()
+                } else {
+                    unit.to_be_bytes()
+                });
+            }
+            out
+        }
+        Encoding::Latin1 => text
+            .chars()
+            .map(|c| u8::try_from(u32::from(c)).unwrap_or(b'?'))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_bom_utf16_and_latin1() {
+        for bytes in [
+            b"\xEF\xBB\xBFname = \"caf\xC3\xA9\"\r\n".to_vec(),
+            b"\xFF\xFEa\x00\r\x00\n\x00b\x00".to_vec(),
+            b"caf\xE9\n".to_vec(),
+        ] {
+            let (text, format) = decode(&bytes).unwrap();
+            assert!(text.contains('a'));
+            assert_eq!(encode(&text, &format), bytes);
+        }
+
+        let (text, format) = decode(b"caf\xE9\n").unwrap();
+        assert_eq!(text, "café\n");
+        assert_eq!(format.encoding, Encoding::Latin1);
+        assert!(decode(b"\x89PNG\x00\x00").is_none());
+    }
+
+    #[test]
+    fn crlf_files_keep_crlf_when_agents_write_lf() {
+        let (text, format) = decode(b"one\r\ntwo\r\n").unwrap();
+        assert_eq!(format.line_ending, LineEnding::Crlf);
+        assert_eq!(text, "one\r\ntwo\r\n");
+        assert_eq!(
+            encode("one\nTWO\r\nthree\n", &format),
+            b"one\r\nTWO\r\nthree\r\n"
+        );
+
+        // Mixed endings are written back untouched
+        let (_, mixed) = decode(b"a\r\nb\n").unwrap();
+        assert_eq!(mixed.line_ending, LineEnding::Mixed);
+        assert_eq!(encode("a\nb\r\n", &mixed), b"a\nb\r\n");
+    }
+}
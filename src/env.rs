@@ -0,0 +1,273 @@
+// ABOUTME: Bookmark-based deployment environments for `env list` and `env promote`
+// ABOUTME: Maps manifest [environments] to the bookmarks they track and checks promotion policy
+
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::error::{Error, Result};
+use crate::manifest::{Environment, Manifest};
+
+/// A local bookmark and the change it points at
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Bookmark {
+    pub name: String,
+    pub change_id: String,
+    pub commit_id: String,
+    /// First line of the commit description
+    pub description: String,
+}
+
+/// An environment and what it currently runs
+#[derive(Debug, Clone, Serialize)]
+pub struct EnvStatus {
+    pub name: String,
+    /// Bookmark name or `prefix/*` pattern
+    pub bookmark: String,
+    pub frozen: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub freeze_reason: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub from: Vec<String>,
+    /// Matching bookmarks; empty when nothing is deployed
+    pub deployed: Vec<Bookmark>,
+}
+
+/// A bookmark move into an environment
+#[derive(Debug, Clone, Serialize)]
+pub struct Promotion {
+    pub environment: String,
+    pub bookmark: String,
+    /// What was promoted: an environment, bookmark, or revision
+    pub from: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from_environment: Option<String>,
+    pub change_id: String,
+    pub commit_id: String,
+    /// Where the bookmark pointed before, if it existed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous: Option<Bookmark>,
+    pub dry_run: bool,
+}
+
+/// Every configured environment with its matching bookmarks, by name
+pub fn list(envs: &HashMap<String, Environment>, bookmarks: &[Bookmark]) -> Vec<EnvStatus> {
+    let mut statuses: Vec<EnvStatus> = envs
+        .iter()
+        .map(|(name, env)| EnvStatus {
+            name: name.clone(),
+            bookmark: env.bookmark().to_string(),
+            frozen: env.is_frozen(),
+            freeze_reason: env.freeze_reason().map(str::to_string),
+            from: env.promote_from().to_vec(),
+            deployed: bookmarks
+                .iter()
+                .filter(|b| env.matches(&b.name))
+                .cloned()
+                .collect(),
+        })
+        .collect();
+    statuses.sort_by(|a, b| a.name.cmp(&b.name));
+    statuses
+}
+
+/// The environment a bookmark belongs to
+fn environment_of<'a>(envs: &'a HashMap<String, Environment>, bookmark: &str) -> Option<&'a str> {
+    let mut names: Vec<&String> = envs.keys().collect();
+    names.sort();
+    names
+        .into_iter()
+        .find(|name| envs[*name].matches(bookmark))
+        .map(String::as_str)
+}
+
+/// Resolve `--to` (an environment, or a bookmark of a pattern environment
+/// like `preview/pr-12`) to the environment and the bookmark to move
+pub fn resolve_target(envs: &HashMap<String, Environment>, to: &str) -> Result<(String, String)> {
+    if let Some(env) = envs.get(to) {
+        if env.is_pattern() {
+            return Err(Error::Config {
+                message: format!(
+                    "environment '{}' tracks '{}' - promote to one of its bookmarks, e.g. --to {}",
+                    to,
+                    env.bookmark(),
+                    env.bookmark().replace('*', "<name>")
+                ),
+            });
+        }
+        return Ok((to.to_string(), env.bookmark().to_string()));
+    }
+    match environment_of(envs, to) {
+        Some(name) => Ok((name.to_string(), to.to_string())),
+        None => Err(unknown_environment(envs, to)),
+    }
+}
+
+/// Resolve the promotion source: an environment promotes the bookmark it
+/// tracks; a bookmark or any other revision is promoted as given. Returns
+/// the source environment, if any, and the revision to resolve.
+pub fn resolve_source(
+    envs: &HashMap<String, Environment>,
+    from: &str,
+) -> Result<(Option<String>, String)> {
+    if let Some(env) = envs.get(from) {
+        if env.is_pattern() {
+            return Err(Error::Config {
+                message: format!(
+                    "environment '{}' tracks several bookmarks ('{}') - promote one of them",
+                    from,
+                    env.bookmark()
+                ),
+            });
+        }
+        return Ok((Some(from.to_string()), env.bookmark().to_string()));
+    }
+    Ok((
+        environment_of(envs, from).map(str::to_string),
+        from.to_string(),
+    ))
+}
+
+/// Policy checks for moving `bookmark` into `environment`: the manifest's
+/// push permissions, the environment's freeze (unless a human confirmed
+/// overriding it), and its allowed sources
+pub fn check_promotion(
+    manifest: &Manifest,
+    environment: &str,
+    bookmark: &str,
+    from_environment: Option<&str>,
+    override_freeze: bool,
+) -> Result<()> {
+    if !manifest.permissions.can_push(bookmark) {
+        return Err(Error::PermissionDenied {
+            action: "promote".into(),
+            path: bookmark.into(),
+        });
+    }
+    let env = manifest
+        .environments
+        .get(environment)
+        .ok_or_else(|| unknown_environment(&manifest.environments, environment))?;
+    if env.is_frozen() && !override_freeze {
+        return Err(Error::PromotionBlocked {
+            environment: environment.into(),
+            reason: match env.freeze_reason() {
+                Some(reason) => format!("environment is frozen ({})", reason),
+                None => "environment is frozen".into(),
+            },
+        });
+    }
+    let allowed = env.promote_from();
+    if !allowed.is_empty() && !from_environment.is_some_and(|f| allowed.iter().any(|a| a == f)) {
+        return Err(Error::PromotionBlocked {
+            environment: environment.into(),
+            reason: format!(
+                "only promotable from {} (got {})",
+                allowed.join(", "),
+                from_environment.unwrap_or("a revision outside any environment")
+            ),
+        });
+    }
+    Ok(())
+}
+
+fn unknown_environment(envs: &HashMap<String, Environment>, name: &str) -> Error {
+    let mut known: Vec<&str> = envs.keys().map(String::as_str).collect();
+    known.sort();
+    Error::Config {
+        message: if known.is_empty() {
+            "no environments configured - add an [environments] table to .agent/manifest.toml"
+                .into()
+        } else {
+            format!(
+                "'{}' is not an environment or environment bookmark (environments: {})",
+                name,
+                known.join(", ")
+            )
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"
+[repo]
+name = "shop"
+
+[permissions]
+deny_push = ["legacy"]
+
+[environments]
+preview = "preview/*"
+staging = "staging"
+prod = { bookmark = "prod", from = ["staging"] }
+legacy = "legacy"
+frozen = { bookmark = "frozen", frozen = true, freeze_reason = "holiday" }
+"#;
+
+    fn bookmark(name: &str) -> Bookmark {
+        Bookmark {
+            name: name.into(),
+            change_id: "abc".into(),
+            commit_id: "def".into(),
+            description: String::new(),
+        }
+    }
+
+    #[test]
+    fn lists_environments_with_their_bookmarks() {
+        let manifest = Manifest::parse(MANIFEST).unwrap();
+        let bookmarks = [
+            bookmark("preview/a"),
+            bookmark("preview/b"),
+            bookmark("main"),
+        ];
+        let statuses = list(&manifest.environments, &bookmarks);
+        let names: Vec<&str> = statuses.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["frozen", "legacy", "preview", "prod", "staging"]
+        );
+        assert_eq!(statuses[2].deployed.len(), 2);
+        assert!(statuses[4].deployed.is_empty());
+    }
+
+    #[test]
+    fn resolves_targets_and_sources() {
+        let envs = Manifest::parse(MANIFEST).unwrap().environments;
+        assert_eq!(
+            resolve_target(&envs, "prod").unwrap(),
+            ("prod".into(), "prod".into())
+        );
+        assert_eq!(
+            resolve_target(&envs, "preview/pr-1").unwrap(),
+            ("preview".into(), "preview/pr-1".into())
+        );
+        assert!(resolve_target(&envs, "preview").is_err());
+        assert!(resolve_target(&envs, "nowhere").is_err());
+
+        assert_eq!(
+            resolve_source(&envs, "staging").unwrap(),
+            (Some("staging".into()), "staging".into())
+        );
+        assert_eq!(resolve_source(&envs, "@").unwrap(), (None, "@".into()));
+    }
+
+    #[test]
+    fn enforces_promotion_policy() {
+        let manifest = Manifest::parse(MANIFEST).unwrap();
+        assert!(check_promotion(&manifest, "prod", "prod", Some("staging"), false).is_ok());
+        assert!(matches!(
+            check_promotion(&manifest, "prod", "prod", None, false),
+            Err(Error::PromotionBlocked { .. })
+        ));
+        let frozen = check_promotion(&manifest, "frozen", "frozen", None, false).unwrap_err();
+        assert!(frozen.to_string().contains("frozen (holiday)"));
+        assert!(check_promotion(&manifest, "frozen", "frozen", None, true).is_ok());
+        assert!(matches!(
+            check_promotion(&manifest, "legacy", "legacy", None, false),
+            Err(Error::PermissionDenied { .. })
+        ));
+    }
+}
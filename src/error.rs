@@ -6,6 +6,40 @@ use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Process exit codes: the contract scripts and agents can branch on
+pub mod exit {
+    /// Success, including success with warnings unless `--strict`
+    pub const OK: i32 = 0;
+    /// Any other failure: bad input, repository or I/O errors, timeouts
+    pub const FAILURE: i32 = 1;
+    /// `--strict` only: the command succeeded but reported warnings
+    pub const WARNINGS: i32 = 2;
+    /// Something the command needs was not in place: a stale pre-image, a
+    /// missing manifest, nothing to validate
+    pub const PRECONDITION: i32 = 3;
+    /// The change conflicted with existing state
+    pub const CONFLICT: i32 = 4;
+    /// The manifest's permissions, or read-only mode, deny the action
+    pub const PERMISSION: i32 = 5;
+    /// An invariant command failed
+    pub const INVARIANT: i32 = 6;
+    /// A policy blocked it: hooks, review or approval requirements,
+    /// destructive schema changes, validation issues
+    pub const POLICY: i32 = 7;
+
+    /// Every code by name, for `cli-spec`
+    pub const ALL: [(&str, i32); 8] = [
+        ("ok", OK),
+        ("failure", FAILURE),
+        ("warnings", WARNINGS),
+        ("precondition", PRECONDITION),
+        ("conflict", CONFLICT),
+        ("permission", PERMISSION),
+        ("invariant", INVARIANT),
+        ("policy", POLICY),
+    ];
+}
+
 #[derive(Error, Debug, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum Error {
@@ -18,6 +52,9 @@ pub enum Error {
         line: Option<usize>,
     },
 
+    #[error("config error: {message}")]
+    Config { message: String },
+
     #[error("precondition failed: {reason}")]
     PreconditionFailed {
         reason: String,
@@ -32,26 +69,303 @@ pub enum Error {
         operation_id: String,
     },
 
-    #[error("invariant '{name}' failed (command: `{command}`, exit code: {exit_code})")]
+    #[error(
+        "invariant '{name}' failed (command: `{command}`, exit code: {exit_code}){}",
+        flake_note(.known_flakes)
+    )]
     InvariantFailed {
         name: String,
         command: String,
         exit_code: i32,
         stdout: String,
         stderr: String,
+        /// Failures that test history shows to be flaky: retry before reverting
+        /// (boxed slice keeps `Error` small)
+        #[serde(default, skip_serializing_if = "<[String]>::is_empty")]
+        known_flakes: Box<[String]>,
+    },
+
+    #[error(
+        "invariant environment variable(s) not set: {} (add them to {secrets_file} or the environment)",
+        variables.join(", ")
+    )]
+    MissingEnv {
+        variables: Vec<String>,
+        /// Invariants that reference them
+        invariants: Vec<String>,
+        secrets_file: String,
+    },
+
+    #[error(
+        "change template(s) {} require field(s): {} (pass --field name=value)",
+        templates.join(", "),
+        missing_fields(.missing, .any_of)
+    )]
+    MissingChangeFields {
+        /// Templates (change type or category names) that are not satisfied
+        templates: Vec<String>,
+        /// Required fields that are absent
+        missing: Vec<String>,
+        /// Groups with none of their fields present
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        any_of: Vec<Vec<String>>,
+    },
+
+    #[error("{event} hook rejected the operation: {reason} (command: `{command}`, exit code: {exit_code})")]
+    HookRejected {
+        event: String,
+        command: String,
+        exit_code: i32,
+        reason: String,
+        stderr: String,
     },
 
     #[error("permission denied: {action} on {path}")]
     PermissionDenied { action: String, path: String },
 
+    #[error("'{command}' is disabled in read-only mode (enabled by {enabled_by})")]
+    ReadOnlyMode { command: String, enabled_by: String },
+
     #[error("change {change_id} not found")]
     ChangeNotFound { change_id: String },
 
+    #[error("approval {approval_id} not found")]
+    ApprovalNotFound { approval_id: String },
+
+    #[error("confirmation {confirmation_id} not found")]
+    ConfirmationNotFound { confirmation_id: String },
+
+    #[error("intent {intent_id} not found")]
+    IntentNotFound { intent_id: String },
+
+    #[error("waiting for human confirmation {confirmation_id}: {message} (once `agentjj confirm {confirmation_id} --approve` runs, resume with `{resume}`)")]
+    ConfirmationPending {
+        confirmation_id: String,
+        gate: crate::confirm::Gate,
+        /// What blocked the command
+        message: String,
+        /// Reruns the command with the confirmation
+        resume: String,
+    },
+
+    #[error("confirmation {confirmation_id} was denied by {decided_by}{}", .reason.as_ref().map(|r| format!(": {}", r)).unwrap_or_default())]
+    ConfirmationDenied {
+        confirmation_id: String,
+        decided_by: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reason: Option<String>,
+    },
+
+    #[error("{id} must be decided by a human: {reason}")]
+    HumanDecisionRequired { id: String, reason: String },
+
+    #[error("note {note_id} not found")]
+    NoteNotFound { note_id: String },
+
     #[error("repository error: {message}")]
     Repository { message: String },
 
     #[error("io error: {message}")]
     Io { message: String },
+
+    #[error("timed out after {timeout_secs}s during {phase}")]
+    Timeout { phase: String, timeout_secs: u64 },
+
+    #[error("promotion to '{environment}' blocked: {reason}")]
+    PromotionBlocked { environment: String, reason: String },
+
+    #[error("{feature} needs git, but this jj repository has no colocated git repository")]
+    GitRequired { feature: String },
+
+    #[error("{} destructive schema operation(s) need --breaking or an allow_destructive_schema policy", operations.len())]
+    DestructiveSchema {
+        operations: Vec<crate::sql::DestructiveOp>,
+    },
+
+    #[error(
+        "immutable change(s) {}: already pushed or under branches.immutable (pass --allow-immutable to rewrite anyway)",
+        change_ids.join(", ")
+    )]
+    ImmutableCommit { change_ids: Vec<String> },
+
+    #[error(
+        "{} unmet dependenc(ies): {} (push or merge them first)",
+        dependencies.len(),
+        dependencies.iter().map(|d| format!("{} needs {}", d.change_id, d.depends_on)).collect::<Vec<_>>().join(", ")
+    )]
+    UnmetDependencies {
+        dependencies: Vec<crate::change::UnmetDependency>,
+    },
+
+    #[error(
+        "{remote}/{branch} moved: expected {}, found {}; fetch and review it, or pass --force-with-lease=<commit> to overwrite it",
+        expected.as_deref().unwrap_or("no branch"),
+        actual.as_deref().unwrap_or("no branch")
+    )]
+    RemoteMoved {
+        remote: String,
+        branch: String,
+        /// Where the lease expected the remote branch (absent: not to exist)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        expected: Option<String>,
+        /// Where it is (absent: deleted)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        actual: Option<String>,
+    },
+
+    #[error(
+        "{branch} is merged through a {queue} merge queue; push to another branch with --pr --enqueue instead"
+    )]
+    MergeQueueRequired {
+        branch: String,
+        /// "github" or "bors"
+        queue: String,
+    },
+
+    #[error("{} possible prompt injection(s) in changed files; the manifest's prompt_policy blocks them", findings.len())]
+    PromptInjection {
+        findings: Vec<crate::prompts::PromptFinding>,
+    },
+
+    #[error(
+        "change too large: {files} file(s), {lines} line(s){}; commit it in {} parts with `commit --paths` (see groups)",
+        change_limits(*.max_files, *.max_lines),
+        groups.len()
+    )]
+    ChangeTooLarge {
+        files: usize,
+        lines: usize,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_files: Option<usize>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_lines: Option<usize>,
+        /// Suggested commits, each within the limits where possible
+        groups: Vec<crate::partition::FileGroup>,
+    },
+
+    #[error(
+        "intent {intent_id} has used its {}; stop retrying and rethink it (`agentjj intent history {intent_id}`)",
+        attempt_limit(*.attempts, *.max_attempts, *.elapsed_minutes, *.max_minutes)
+    )]
+    TooManyAttempts {
+        intent_id: String,
+        attempts: usize,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_attempts: Option<usize>,
+        /// Minutes since the first attempt
+        elapsed_minutes: u64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_minutes: Option<u64>,
+        /// How the last attempt ended
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        last_status: Option<String>,
+    },
+
+    #[error("{error}; undid the partial apply: {cleanup}")]
+    ApplyFailed {
+        /// Why the apply stopped
+        error: Box<Error>,
+        /// What was put back in the working copy
+        cleanup: crate::preimage::Cleanup,
+    },
+}
+
+impl Error {
+    /// Exit code for a command that failed with this error
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::PreconditionFailed { .. }
+            | Error::ManifestNotFound { .. }
+            | Error::GitRequired { .. }
+            | Error::UnmetDependencies { .. }
+            | Error::RemoteMoved { .. }
+            | Error::MergeQueueRequired { .. }
+            | Error::MissingEnv { .. } => exit::PRECONDITION,
+            Error::Conflict { .. } => exit::CONFLICT,
+            Error::PermissionDenied { .. }
+            | Error::ReadOnlyMode { .. }
+            | Error::HumanDecisionRequired { .. } => exit::PERMISSION,
+            Error::InvariantFailed { .. } => exit::INVARIANT,
+            Error::HookRejected { .. }
+            | Error::DestructiveSchema { .. }
+            | Error::PromptInjection { .. }
+            | Error::ChangeTooLarge { .. }
+            | Error::TooManyAttempts { .. }
+            | Error::ConfirmationPending { .. }
+            | Error::ConfirmationDenied { .. }
+            | Error::ImmutableCommit { .. }
+            | Error::MissingChangeFields { .. }
+            | Error::PromotionBlocked { .. } => exit::POLICY,
+            Error::ApplyFailed { error, .. } => error.exit_code(),
+            _ => exit::FAILURE,
+        }
+    }
+
+    /// This error, with what undoing a partial apply cleaned up (unchanged
+    /// when the apply had written nothing)
+    pub fn rolled_back(self, cleanup: crate::preimage::Cleanup) -> Error {
+        if cleanup.is_empty() {
+            self
+        } else {
+            Error::ApplyFailed {
+                error: Box::new(self),
+                cleanup,
+            }
+        }
+    }
+}
+
+/// The review limits a change exceeded, for its error message
+fn change_limits(max_files: Option<usize>, max_lines: Option<usize>) -> String {
+    let limits: Vec<String> = [
+        max_files.map(|n| format!("max_change_files = {}", n)),
+        max_lines.map(|n| format!("max_change_loc = {}", n)),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    format!(" over {}", limits.join(", "))
+}
+
+/// Which `[intents]` limit an intent ran into, for its error message
+fn attempt_limit(
+    attempts: usize,
+    max_attempts: Option<usize>,
+    elapsed_minutes: u64,
+    max_minutes: Option<u64>,
+) -> String {
+    match (max_attempts, max_minutes) {
+        (Some(max), _) if attempts >= max => {
+            format!("{} attempts (max_attempts = {})", attempts, max)
+        }
+        (_, Some(max)) => format!(
+            "time box: {} minutes since the first attempt (max_minutes = {})",
+            elapsed_minutes, max
+        ),
+        _ => format!("{} attempts", attempts),
+    }
+}
+
+/// Suffix for an invariant failure whose failing tests are known flakes
+fn flake_note(known_flakes: &[String]) -> String {
+    if known_flakes.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "; known flaky: {} - retry before reverting",
+            known_flakes.join(", ")
+        )
+    }
+}
+
+/// "migration_id, cve or advisory" for missing template fields
+fn missing_fields(missing: &[String], any_of: &[Vec<String>]) -> String {
+    missing
+        .iter()
+        .cloned()
+        .chain(any_of.iter().map(|group| group.join(" or ")))
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -113,4 +427,37 @@ mod tests {
         assert!(json.contains("src/api.py"));
         assert!(json.contains("op123"));
     }
+
+    #[test]
+    fn errors_map_to_exit_codes() {
+        let denied = Error::PermissionDenied {
+            action: "change".into(),
+            path: "secrets/key".into(),
+        };
+        assert_eq!(denied.exit_code(), exit::PERMISSION);
+        let missing = Error::ManifestNotFound {
+            path: ".agent/manifest.toml".into(),
+        };
+        assert_eq!(missing.exit_code(), exit::PRECONDITION);
+        let git = Error::GitRequired {
+            feature: "push".into(),
+        };
+        assert_eq!(git.exit_code(), exit::PRECONDITION);
+        let env = Error::MissingEnv {
+            variables: vec!["DATABASE_URL".into()],
+            invariants: vec!["integration".into()],
+            secrets_file: ".agent/secrets.env".into(),
+        };
+        assert_eq!(env.exit_code(), exit::PRECONDITION);
+        let read_only = Error::ReadOnlyMode {
+            command: "commit".into(),
+            enabled_by: "--read-only".into(),
+        };
+        assert_eq!(read_only.exit_code(), exit::PERMISSION);
+        let timeout = Error::Timeout {
+            phase: "invariants".into(),
+            timeout_secs: 5,
+        };
+        assert_eq!(timeout.exit_code(), exit::FAILURE);
+    }
 }
@@ -0,0 +1,150 @@
+// ABOUTME: `validate --fix`: built-in whitespace fixers and manifest [fixers] commands over changed files
+// ABOUTME: Pure detection and rewriting here; repo.rs runs the fixers and records what each one changed
+
+use serde::Serialize;
+
+use crate::custom::shell_quote;
+
+/// Strips spaces and tabs at the ends of lines
+pub const TRAILING_WHITESPACE: &str = "trailing_whitespace";
+
+/// Ends a non-empty file with a newline
+pub const FINAL_NEWLINE: &str = "final_newline";
+
+/// Placeholder in a fixer command for the changed files it applies to
+pub const FILES_PLACEHOLDER: &str = "{files}";
+
+/// A file a fixer rewrote, and which fixers did
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileFix {
+    pub path: String,
+    pub fixers: Vec<String>,
+}
+
+/// A manifest fixer whose command failed; what it changed is kept
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FixerFailure {
+    pub fixer: String,
+    pub command: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    pub stderr: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct FixReport {
+    pub fixed: Vec<FileFix>,
+    pub failed: Vec<FixerFailure>,
+}
+
+impl FixReport {
+    /// Note that `fixer` changed `path`
+    pub fn record(&mut self, path: &str, fixer: &str) {
+        match self.fixed.iter_mut().find(|f| f.path == path) {
+            Some(fix) => fix.fixers.push(fixer.to_string()),
+            None => self.fixed.push(FileFix {
+                path: path.to_string(),
+                fixers: vec![fixer.to_string()],
+            }),
+        }
+    }
+}
+
+/// Trailing whitespace is meaningful in Markdown (hard breaks) and patches
+fn keeps_trailing_whitespace(path: &str) -> bool {
+    let ext = path
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase());
+    matches!(ext.as_deref(), Some("md" | "markdown" | "patch" | "diff"))
+}
+
+fn strip_trailing_whitespace(content: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(content.len());
+    for line in content.split_inclusive(|b| *b == b'\n') {
+        let ending = if line.ends_with(b"\r\n") {
+            &b"\r\n"[..]
+        } else if line.ends_with(b"\n") {
+            &b"\n"[..]
+        } else {
+            &[][..]
+        };
+        let body = &line[..line.len() - ending.len()];
+        let end = body
+            .iter()
+            .rposition(|b| *b != b' ' && *b != b'\t')
+            .map_or(0, |i| i + 1);
+        out.extend_from_slice(&body[..end]);
+        out.extend_from_slice(ending);
+    }
+    out
+}
+
+fn add_final_newline(content: &[u8]) -> Vec<u8> {
+    let mut out = content.to_vec();
+    if !out.is_empty() && !out.ends_with(b"\n") {
+        // Match the file's line endings
+        let crlf = content.windows(2).any(|w| w == b"\r\n");
+        out.extend_from_slice(if crlf { b"\r\n" } else { b"\n" });
+    }
+    out
+}
+
+/// Run the built-in fixers over `content`. Returns the fixed content and the
+/// fixers that changed it, or `None` when there is nothing to fix. Binary
+/// content is never touched.
+pub fn apply_builtins(path: &str, content: &[u8]) -> Option<(Vec<u8>, Vec<&'static str>)> {
+    if content.contains(&0) {
+        return None;
+    }
+    let mut fixed = content.to_vec();
+    let mut applied = Vec::new();
+    if !keeps_trailing_whitespace(path) {
+        let stripped = strip_trailing_whitespace(&fixed);
+        if stripped != fixed {
+            fixed = stripped;
+            applied.push(TRAILING_WHITESPACE);
+        }
+    }
+    let terminated = add_final_newline(&fixed);
+    if terminated != fixed {
+        fixed = terminated;
+        applied.push(FINAL_NEWLINE);
+    }
+    (!applied.is_empty()).then_some((fixed, applied))
+}
+
+/// A fixer's command with `{files}` replaced by the shell-quoted paths
+pub fn command_for(cmd: &str, files: &[String]) -> String {
+    let quoted: Vec<String> = files.iter().map(|f| shell_quote(f)).collect();
+    cmd.replace(FILES_PLACEHOLDER, &quoted.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtins_fix_whitespace_and_final_newline() {
+        let (fixed, applied) =
+            apply_builtins("src/app.py", b"def f():  \n\treturn 1\t\r\nx = 1").unwrap();
+        assert_eq!(fixed, b"def f():\n\treturn 1\r\nx = 1\r\n");
+        assert_eq!(applied, vec![TRAILING_WHITESPACE, FINAL_NEWLINE]);
+
+        // Markdown keeps its hard breaks but still gets a final newline
+        let (fixed, applied) = apply_builtins("README.md", b"line  \nend").unwrap();
+        assert_eq!(fixed, b"line  \nend\n");
+        assert_eq!(applied, vec![FINAL_NEWLINE]);
+
+        assert_eq!(apply_builtins("clean.rs", b"fn main() {}\n"), None);
+        assert_eq!(apply_builtins("empty.txt", b""), None);
+        assert_eq!(apply_builtins("logo.png", b"\x89PNG\0 \n"), None);
+
+        assert_eq!(
+            command_for(
+                "ruff check --fix {files}",
+                &["a.py".into(), "it's.py".into()]
+            ),
+            "ruff check --fix a.py 'it'\\''s.py'"
+        );
+    }
+}
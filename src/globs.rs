@@ -0,0 +1,218 @@
+// ABOUTME: The one glob syntax behind every path pattern: files, bulk symbols, change list, permissions, fixers
+// ABOUTME: globset-based, with {a,b} alternatives, !exclusions, and case-insensitivity under glob.case_insensitive
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::error::{Error, Result};
+
+/// Set from `glob.case_insensitive` before any command runs
+static CASE_INSENSITIVE: AtomicBool = AtomicBool::new(false);
+
+/// Match globs without regard to case for the rest of the process
+pub fn set_case_insensitive(on: bool) {
+    CASE_INSENSITIVE.store(on, Ordering::Relaxed);
+}
+
+/// Compiled patterns, matched against slash-separated repo-relative paths:
+///
+/// - `*`, `?`, and `[ab]` stay within one path segment; `**` spans any
+///   number of directories; `{a,b}` matches either alternative
+/// - a pattern without `/` matches the file name at any depth, as in
+///   `.gitignore`; a leading `/` anchors it to the root instead
+/// - `!pattern` excludes; a path matches when some other pattern matches
+///   (or there are only exclusions) and no exclusion does; no patterns
+///   at all match nothing
+/// - case-sensitive unless `glob.case_insensitive` is set
+#[derive(Debug, Clone)]
+pub struct Globs {
+    include: GlobSet,
+    exclude: GlobSet,
+    empty: bool,
+    /// Literal directory every included path is under, for walking less
+    base: PathBuf,
+}
+
+impl Globs {
+    /// Path patterns, with the rules above
+    pub fn new<S: AsRef<str>>(patterns: impl IntoIterator<Item = S>) -> Result<Self> {
+        Self::build(patterns, true)
+    }
+
+    /// Patterns for names such as bookmarks, matched whole: `*` also
+    /// spans `/`, so `preview/*` matches `preview/a/b`
+    pub fn names<S: AsRef<str>>(patterns: impl IntoIterator<Item = S>) -> Result<Self> {
+        Self::build(patterns, false)
+    }
+
+    fn build<S: AsRef<str>>(patterns: impl IntoIterator<Item = S>, paths: bool) -> Result<Self> {
+        let case_insensitive = CASE_INSENSITIVE.load(Ordering::Relaxed);
+        let (mut include, mut exclude) = (GlobSetBuilder::new(), GlobSetBuilder::new());
+        let mut bases: Vec<PathBuf> = Vec::new();
+        for pattern in patterns {
+            let pattern = pattern.as_ref().trim();
+            let (negated, pattern) = match pattern.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, pattern),
+            };
+            let pattern = if !paths {
+                pattern.to_string()
+            } else if let Some(anchored) = pattern.strip_prefix('/') {
+                anchored.to_string()
+            } else if pattern.contains('/') {
+                pattern.to_string()
+            } else {
+                format!("**/{}", pattern)
+            };
+            let glob = GlobBuilder::new(&pattern)
+                .literal_separator(paths)
+                .case_insensitive(case_insensitive)
+                .backslash_escape(true)
+                .build()
+                .map_err(|e| Error::Config {
+                    message: format!("invalid glob '{}': {}", pattern, e),
+                })?;
+            if negated {
+                exclude.add(glob);
+            } else {
+                include.add(glob);
+                bases.push(if case_insensitive {
+                    PathBuf::new()
+                } else {
+                    literal_dir(&pattern)
+                });
+            }
+        }
+        let build = |set: GlobSetBuilder| {
+            set.build().map_err(|e| Error::Config {
+                message: e.to_string(),
+            })
+        };
+        let (include, exclude) = (build(include)?, build(exclude)?);
+        Ok(Self {
+            empty: include.is_empty() && exclude.is_empty(),
+            include,
+            exclude,
+            base: common_dir(&bases),
+        })
+    }
+
+    pub fn is_match(&self, path: &str) -> bool {
+        let path = path.trim_start_matches("./");
+        !self.empty
+            && (self.include.is_empty() || self.include.is_match(path))
+            && !self.exclude.is_match(path)
+    }
+
+    /// The directory every match is under (empty for anywhere)
+    pub fn base(&self) -> &Path {
+        &self.base
+    }
+
+    /// Files under `root` that match, as repo-relative paths in sorted
+    /// order. `.git` and `.jj` are never entered.
+    pub fn walk(&self, root: &Path) -> Vec<String> {
+        walk(&root.join(&self.base))
+            .into_iter()
+            .filter_map(|path| {
+                let rel: Vec<_> = path
+                    .strip_prefix(root)
+                    .ok()?
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy().to_string())
+                    .collect();
+                Some(rel.join("/"))
+            })
+            .filter(|rel| self.is_match(rel))
+            .collect()
+    }
+}
+
+/// Whether `path` matches one path pattern; invalid patterns match nothing
+pub fn is_match(pattern: &str, path: &str) -> bool {
+    Globs::new([pattern]).is_ok_and(|g| g.is_match(path))
+}
+
+/// Whether `name` matches one name pattern (see `Globs::names`)
+pub fn name_matches(pattern: &str, name: &str) -> bool {
+    Globs::names([pattern]).is_ok_and(|g| g.is_match(name))
+}
+
+/// Every regular file under `dir` (following symlinks to files, not to
+/// directories), sorted, skipping `.git` and `.jj`
+pub fn walk(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut to_visit = vec![dir.to_path_buf()];
+    while let Some(dir) = to_visit.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                if entry.file_name() != ".git" && entry.file_name() != ".jj" {
+                    to_visit.push(path);
+                }
+            } else if path.is_file() {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+/// The leading directories of a pattern that contain no glob syntax
+fn literal_dir(pattern: &str) -> PathBuf {
+    let segments: Vec<&str> = pattern.split('/').collect();
+    segments[..segments.len() - 1]
+        .iter()
+        .take_while(|s| !s.contains(['*', '?', '[', '{', '\\']))
+        .collect()
+}
+
+/// The longest directory all of `dirs` are under
+fn common_dir(dirs: &[PathBuf]) -> PathBuf {
+    let Some(first) = dirs.first() else {
+        return PathBuf::new();
+    };
+    first
+        .components()
+        .enumerate()
+        .take_while(|(i, c)| dirs.iter().all(|d| d.components().nth(*i) == Some(*c)))
+        .map(|(_, c)| c)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn braces_exclusions_and_file_names_at_any_depth() {
+        let globs = Globs::new(["src/**/*.{rs,toml}", "!src/generated/**"]).unwrap();
+        assert!(globs.is_match("src/main.rs"));
+        assert!(globs.is_match("src/api/Cargo.toml"));
+        assert!(!globs.is_match("src/generated/schema.rs"));
+        assert!(!globs.is_match("tests/cli.rs"));
+        assert_eq!(globs.base, PathBuf::from("src"));
+
+        // No slash: the file name anywhere; a leading slash: the root only
+        assert!(is_match("*.md", "docs/guide/intro.md"));
+        assert!(is_match("/*.md", "README.md"));
+        assert!(!is_match("/*.md", "docs/intro.md"));
+        assert!(!is_match("src/*.rs", "src/api/mod.rs"));
+        assert!(is_match("!*.lock", "src/main.rs"));
+        assert!(!is_match("!*.lock", "Cargo.lock"));
+        assert!(!is_match("README.MD", "README.md"));
+
+        assert!(name_matches("preview/*", "preview/a/b"));
+        assert!(!name_matches("main", "feature/main"));
+        assert!(Globs::new(["src/{a,b"]).is_err());
+        assert!(!Globs::new(Vec::<String>::new()).unwrap().is_match("a.rs"));
+    }
+}
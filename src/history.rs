@@ -0,0 +1,224 @@
+// ABOUTME: Depth limit and persistent commit cache for history walks (graph, churn, change backfill, blame)
+// ABOUTME: Commits never change, so .agent/cache/history/commits.jsonl only grows; walks read it, not the store
+
+use jj_lib::backend::CommitId;
+use jj_lib::object_id::ObjectId;
+use jj_lib::store::Store;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::error::{Error, Result};
+
+/// Set from `--max-history` before any command runs; 0 when not given
+static MAX_HISTORY: AtomicUsize = AtomicUsize::new(0);
+
+/// Limit history walks to `depth` commits back for the rest of the process,
+/// over the manifest's `[history] max_depth`
+pub fn set_max_depth(depth: usize) {
+    MAX_HISTORY.store(depth, Ordering::Relaxed);
+}
+
+/// The `--max-history` depth, if one was given
+pub fn max_depth_override() -> Option<usize> {
+    match MAX_HISTORY.load(Ordering::Relaxed) {
+        0 => None,
+        depth => Some(depth),
+    }
+}
+
+/// What a history walk needs to know about one commit
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommitRecord {
+    /// Full commit ID
+    pub id: String,
+    /// Full change ID
+    pub change_id: String,
+    /// Parent commit IDs
+    pub parents: Vec<String>,
+    pub description: String,
+    pub author_name: String,
+    pub author_email: String,
+    /// Author time, milliseconds since the epoch
+    pub timestamp_ms: i64,
+    /// Author time zone, minutes east of UTC
+    pub tz_offset: i32,
+}
+
+impl CommitRecord {
+    pub fn from_commit(commit: &jj_lib::commit::Commit) -> Self {
+        let author = commit.author();
+        Self {
+            id: commit.id().hex(),
+            change_id: commit.change_id().hex(),
+            parents: commit.parent_ids().iter().map(|p| p.hex()).collect(),
+            description: commit.description().to_string(),
+            author_name: author.name.clone(),
+            author_email: author.email.clone(),
+            timestamp_ms: author.timestamp.timestamp.0,
+            tz_offset: author.timestamp.tz_offset,
+        }
+    }
+
+    /// Parent commit IDs, skipping any that are not valid hex
+    pub fn parent_ids(&self) -> Vec<CommitId> {
+        self.parents
+            .iter()
+            .filter_map(CommitId::try_from_hex)
+            .collect()
+    }
+}
+
+/// Commit records by commit ID, loaded from disk and appended to as walks
+/// reach commits not seen before
+pub struct HistoryCache {
+    path: Option<PathBuf>,
+    records: HashMap<String, CommitRecord>,
+    /// Read from the store this process, not yet written
+    new: Vec<CommitRecord>,
+}
+
+impl HistoryCache {
+    pub const DEFAULT_DIR: &'static str = ".agent/cache/history";
+
+    /// The repository's cache, or an in-memory one when `persist` is off
+    /// (`cache.history = false`). Unreadable lines are skipped.
+    pub fn open(repo_root: impl AsRef<Path>, persist: bool) -> Self {
+        let path = persist.then(|| {
+            repo_root
+                .as_ref()
+                .join(Self::DEFAULT_DIR)
+                .join("commits.jsonl")
+        });
+        let records = path
+            .as_ref()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .map(|content| {
+                content
+                    .lines()
+                    .filter_map(|line| serde_json::from_str::<CommitRecord>(line).ok())
+                    .map(|record| (record.id.clone(), record))
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self {
+            path,
+            records,
+            new: Vec::new(),
+        }
+    }
+
+    /// Commits recorded so far
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// The record for `id`, read from the store on a miss
+    pub fn get(&mut self, store: &Arc<Store>, id: &CommitId) -> Result<CommitRecord> {
+        let hex = id.hex();
+        if let Some(record) = self.records.get(&hex) {
+            return Ok(record.clone());
+        }
+        let commit = store.get_commit(id).map_err(|e| Error::Repository {
+            message: format!("failed to get commit: {}", e),
+        })?;
+        let record = CommitRecord::from_commit(&commit);
+        self.records.insert(hex, record.clone());
+        self.new.push(record.clone());
+        Ok(record)
+    }
+
+    /// Append the records read from the store since the last flush
+    pub fn flush(&mut self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if self.new.is_empty() {
+            return Ok(());
+        }
+        let dir = path.parent().unwrap_or(path);
+        if !dir.exists() {
+            crate::agent_dir::ensure_private(dir.parent().unwrap_or(dir))?;
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut lines = String::new();
+        for record in self.new.drain(..) {
+            lines.push_str(&serde_json::to_string(&record).map_err(|e| Error::Io {
+                message: e.to_string(),
+            })?);
+            lines.push('\n');
+        }
+        // One append per process: concurrent walks at worst record a commit twice
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?
+            .write_all(lines.as_bytes())?;
+        Ok(())
+    }
+}
+
+impl Drop for HistoryCache {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn record(id: &str, parents: &[&str]) -> CommitRecord {
+        CommitRecord {
+            id: id.into(),
+            change_id: format!("{}{}", id, id),
+            parents: parents.iter().map(|p| p.to_string()).collect(),
+            description: format!("commit {}\n", id),
+            author_name: "Test".into(),
+            author_email: "test@example.com".into(),
+            timestamp_ms: 1_700_000_000_000,
+            tz_offset: 60,
+        }
+    }
+
+    #[test]
+    fn records_persist_across_opens_and_only_grow() {
+        let tmp = TempDir::new().unwrap();
+        let mut cache = HistoryCache::open(tmp.path(), true);
+        assert!(cache.is_empty());
+        for r in [record("aa", &[]), record("bb", &["aa"])] {
+            cache.records.insert(r.id.clone(), r.clone());
+            cache.new.push(r);
+        }
+        drop(cache);
+
+        let mut cache = HistoryCache::open(tmp.path(), true);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.records["bb"].parent_ids().len(), 1);
+        cache.new.push(record("cc", &["bb"]));
+        cache.flush().unwrap();
+        // Nothing new: no rewrite
+        cache.flush().unwrap();
+        let lines =
+            std::fs::read_to_string(tmp.path().join(".agent/cache/history/commits.jsonl")).unwrap();
+        assert_eq!(lines.lines().count(), 3);
+        assert!(tmp.path().join(".agent/cache/.gitignore").exists());
+
+        // A torn last line is skipped, not fatal
+        std::fs::write(
+            tmp.path().join(".agent/cache/history/commits.jsonl"),
+            format!("{}{{\"id\": \"dd\"", lines),
+        )
+        .unwrap();
+        assert_eq!(HistoryCache::open(tmp.path(), true).len(), 3);
+        assert!(HistoryCache::open(tmp.path(), false).is_empty());
+    }
+}
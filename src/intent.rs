@@ -6,6 +6,7 @@ use std::collections::HashMap;
 
 use crate::change::{ChangeCategory, ChangeType, InvariantStatus};
 use crate::error::ConflictDetail;
+use crate::unified::{FileChange, Side};
 
 /// An intent to make changes to the repository
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,9 +18,14 @@ pub struct Intent {
     #[serde(rename = "type")]
     pub change_type: ChangeType,
 
-    /// Category for more granular classification
-    #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub category: Option<ChangeCategory>,
+    /// Categories for more granular classification
+    #[serde(
+        default,
+        alias = "category",
+        deserialize_with = "crate::change::one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub categories: Vec<ChangeCategory>,
 
     /// Preconditions that must be met
     #[serde(default)]
@@ -35,6 +41,11 @@ pub struct Intent {
     /// Whether this is a breaking change
     #[serde(default)]
     pub breaking: bool,
+
+    /// Build the change on this revision instead of the working copy,
+    /// leaving the working copy untouched
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub onto: Option<String>,
 }
 
 fn default_true() -> bool {
@@ -115,18 +126,220 @@ pub enum ChangeSpec {
 #[serde(tag = "op", rename_all = "lowercase")]
 pub enum FileOperation {
     /// Create a new file
-    Create { path: String, content: String },
+    Create {
+        path: String,
+        content: String,
+        /// Set (or clear) the executable bit
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        executable: Option<bool>,
+    },
 
     /// Replace file contents entirely
-    Replace { path: String, content: String },
+    Replace {
+        path: String,
+        content: String,
+        /// sha256 the whole file must have before the replace
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        expected_hash: Option<String>,
+        /// Set (or clear) the executable bit; the current mode is kept if unset
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        executable: Option<bool>,
+    },
+
+    /// Create a symbolic link at `path` pointing to `target`
+    #[serde(rename = "symlink")]
+    CreateSymlink { path: String, target: String },
+
+    /// Replace lines `start_line..=end_line` (1-based, numbered as in the
+    /// file before any edit in this change) with `content`
+    Edit {
+        path: String,
+        start_line: usize,
+        end_line: usize,
+        content: String,
+        /// sha256 those lines must have before the edit (the `hash` that
+        /// `read --symbol` / `--around-line` report)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        expected_hash: Option<String>,
+    },
 
     /// Delete a file
-    Delete { path: String },
+    Delete {
+        path: String,
+        /// sha256 the whole file must have before deletion
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        expected_hash: Option<String>,
+    },
 
     /// Rename/move a file
     Rename { from: String, to: String },
 }
 
+/// Inclusive, 1-based line range
+pub type LineSpan = (usize, usize);
+
+impl ChangeSpec {
+    /// Repo-relative paths these changes touch, as far as can be told
+    /// without applying them (a patch file on disk is not read)
+    pub fn paths(&self) -> Vec<String> {
+        match self {
+            ChangeSpec::Files { operations } => operations
+                .iter()
+                .flat_map(|op| match op {
+                    FileOperation::Rename { from, to } => vec![from.clone(), to.clone()],
+                    FileOperation::Create { path, .. }
+                    | FileOperation::CreateSymlink { path, .. }
+                    | FileOperation::Replace { path, .. }
+                    | FileOperation::Edit { path, .. }
+                    | FileOperation::Delete { path, .. } => vec![path.clone()],
+                })
+                .collect(),
+            ChangeSpec::Patch { content } => patch_paths(content),
+            ChangeSpec::PatchFile { .. } => Vec::new(),
+        }
+    }
+}
+
+/// Paths named in the `---`/`+++` headers of a unified diff (`-p1` style)
+pub fn patch_paths(patch: &str) -> Vec<String> {
+    let mut paths: Vec<String> = Vec::new();
+    for line in patch.lines() {
+        let Some(header) = line
+            .strip_prefix("--- ")
+            .or_else(|| line.strip_prefix("+++ "))
+        else {
+            continue;
+        };
+        let path = header.split('\t').next().unwrap_or("").trim();
+        if path == "/dev/null" || path.is_empty() {
+            continue;
+        }
+        let path = path
+            .strip_prefix("a/")
+            .or_else(|| path.strip_prefix("b/"))
+            .unwrap_or(path);
+        if !paths.iter().any(|p| p == path) {
+            paths.push(path.to_string());
+        }
+    }
+    paths
+}
+
+impl FileOperation {
+    /// The pre-image this operation asserts, if any: path, expected hash,
+    /// and the line range it covers (None for the whole file)
+    pub fn pre_image(&self) -> Option<(&str, &str, Option<LineSpan>)> {
+        match self {
+            FileOperation::Replace {
+                path,
+                expected_hash: Some(hash),
+                ..
+            }
+            | FileOperation::Delete {
+                path,
+                expected_hash: Some(hash),
+            } => Some((path, hash, None)),
+            FileOperation::Edit {
+                path,
+                start_line,
+                end_line,
+                expected_hash: Some(hash),
+                ..
+            } => Some((path, hash, Some((*start_line, *end_line)))),
+            _ => None,
+        }
+    }
+
+    /// Operations that turn `change.before` into `change.after` when
+    /// applied with `edit --ops`: line-range edits where the text and its
+    /// encoding allow, otherwise whole-file ones. Each carries the hash of
+    /// the pre-image it replaces. None for binary content.
+    pub fn from_change(change: &FileChange) -> Option<Vec<FileOperation>> {
+        let path = change.path.clone();
+        let hash = |side: &Side| crate::snippet::content_hash(&side.content);
+        let text = |side: &Side| crate::encoding::decode(&side.content);
+        let executable = |side: &Side| side.mode == "100755";
+
+        let mut ops = Vec::new();
+        let after = match (&change.before, &change.after) {
+            (Some(before), None) => {
+                return Some(vec![FileOperation::Delete {
+                    path,
+                    expected_hash: Some(hash(before)),
+                }]);
+            }
+            (None, None) => return Some(ops),
+            (before, Some(after)) if after.mode == "120000" => {
+                if let Some(before) = before {
+                    ops.push(FileOperation::Delete {
+                        path: path.clone(),
+                        expected_hash: Some(hash(before)),
+                    });
+                }
+                ops.push(FileOperation::CreateSymlink {
+                    path,
+                    target: String::from_utf8_lossy(&after.content).to_string(),
+                });
+                return Some(ops);
+            }
+            (_, Some(after)) => after,
+        };
+        let (after_text, after_format) = text(after)?;
+
+        let before = match &change.before {
+            Some(before) if before.mode != "120000" => before,
+            before => {
+                if let Some(before) = before {
+                    ops.push(FileOperation::Delete {
+                        path: path.clone(),
+                        expected_hash: Some(hash(before)),
+                    });
+                }
+                ops.push(FileOperation::Create {
+                    path,
+                    content: after_text,
+                    executable: executable(after).then_some(true),
+                });
+                return Some(ops);
+            }
+        };
+        let (before_text, before_format) = text(before)?;
+
+        // Edits keep the file's mode and format; anything else is replaced
+        if before.mode != after.mode || before_format != after_format || before_text.is_empty() {
+            ops.push(FileOperation::Replace {
+                path,
+                content: after_text,
+                expected_hash: Some(hash(before)),
+                executable: (before.mode != after.mode).then(|| executable(after)),
+            });
+            return Some(ops);
+        }
+        for edit in crate::unified::line_edits(&before_text, &after_text) {
+            ops.push(FileOperation::Edit {
+                path: path.clone(),
+                start_line: edit.start_line,
+                end_line: edit.end_line,
+                expected_hash: Some(crate::snippet::content_hash(&edit.old_content)),
+                content: edit.content,
+            });
+        }
+        Some(ops)
+    }
+}
+
+/// A file whose content no longer matches the pre-image an edit expected
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StaleFile {
+    pub path: String,
+    /// Line range the hash covered, when not the whole file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lines: Option<LineSpan>,
+    pub expected: String,
+    /// Current hash, or None when the file (or range) no longer exists
+    pub actual: Option<String>,
+}
+
 /// Result of applying an intent
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "status", rename_all = "snake_case")]
@@ -156,6 +369,12 @@ pub enum IntentResult {
         actual: String,
     },
 
+    /// Files changed since the edit's pre-image was read; nothing was applied
+    StaleEdit {
+        /// Every file (or range) that drifted
+        drifted: Vec<StaleFile>,
+    },
+
     /// Changes conflicted with existing state
     Conflict {
         /// The change ID that was created (with conflicts)
@@ -180,6 +399,9 @@ pub enum IntentResult {
         stdout: String,
         /// Stderr from the command
         stderr: String,
+        /// Failures that test history shows to be flaky: retry before reverting
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        known_flakes: Vec<String>,
         /// The change ID (changes were applied but not finalized)
         change_id: String,
         /// Command to rollback
@@ -205,6 +427,16 @@ pub enum IntentResult {
         /// Message for the human reviewer
         message: String,
     },
+
+    /// Touches `ask_change` paths; parked until a human approves it
+    PendingApproval {
+        /// ID to approve and then resume with `apply --resume`
+        approval_id: String,
+        /// Paths that need approval
+        paths: Vec<String>,
+        /// What to do next
+        message: String,
+    },
 }
 
 impl IntentResult {
@@ -213,6 +445,23 @@ impl IntentResult {
         matches!(self, IntentResult::Success { .. })
     }
 
+    /// Exit code for `apply`/`edit` when this is the result
+    pub fn exit_code(&self) -> i32 {
+        use crate::error::exit;
+        match self {
+            IntentResult::Success { .. } => exit::OK,
+            IntentResult::PreconditionFailed { .. } | IntentResult::StaleEdit { .. } => {
+                exit::PRECONDITION
+            }
+            IntentResult::Conflict { .. } => exit::CONFLICT,
+            IntentResult::InvariantFailed { .. } => exit::INVARIANT,
+            IntentResult::PermissionDenied { .. } => exit::PERMISSION,
+            IntentResult::RequiresReview { .. } | IntentResult::PendingApproval { .. } => {
+                exit::POLICY
+            }
+        }
+    }
+
     /// Get the change ID if available
     pub fn change_id(&self) -> Option<&str> {
         match self {
@@ -248,17 +497,20 @@ impl Intent {
         Self {
             description: description.into(),
             change_type,
-            category: None,
+            categories: Vec::new(),
             preconditions: Preconditions::default(),
             changes,
             run_invariants: true,
             breaking: false,
+            onto: None,
         }
     }
 
     /// Add a category
     pub fn with_category(mut self, category: ChangeCategory) -> Self {
-        self.category = Some(category);
+        if !self.categories.contains(&category) {
+            self.categories.push(category);
+        }
         self
     }
 
@@ -280,6 +532,12 @@ impl Intent {
         self
     }
 
+    /// Build the change on `rev` instead of the working copy
+    pub fn onto(mut self, rev: impl Into<String>) -> Self {
+        self.onto = Some(rev.into());
+        self
+    }
+
     /// Serialize to JSON (for CLI output)
     pub fn to_json(&self) -> serde_json::Result<String> {
         serde_json::to_string_pretty(self)
@@ -321,6 +579,7 @@ mod tests {
                 operations: vec![FileOperation::Create {
                     path: "config/new.toml".into(),
                     content: "[settings]\nkey = \"value\"".into(),
+                    executable: None,
                 }],
             },
         );
@@ -378,6 +637,51 @@ mod tests {
         assert!(json.contains("branch has advanced"));
     }
 
+    #[test]
+    fn file_operations_expose_pre_images() {
+        let ops: Vec<FileOperation> = serde_json::from_str(
+            r#"[
+                {"op": "replace", "path": "a.rs", "content": "x", "expected_hash": "aa"},
+                {"op": "edit", "path": "b.rs", "start_line": 3, "end_line": 5, "content": "y\n", "expected_hash": "bb"},
+                {"op": "delete", "path": "c.rs"}
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(ops[0].pre_image(), Some(("a.rs", "aa", None)));
+        assert_eq!(ops[1].pre_image(), Some(("b.rs", "bb", Some((3, 5)))));
+        assert_eq!(ops[2].pre_image(), None);
+
+        let stale = IntentResult::StaleEdit {
+            drifted: vec![StaleFile {
+                path: "a.rs".into(),
+                lines: None,
+                expected: "aa".into(),
+                actual: Some("cc".into()),
+            }],
+        };
+        let json = serde_json::to_string(&stale).unwrap();
+        assert!(json.contains("\"status\":\"stale_edit\""));
+    }
+
+    #[test]
+    fn change_spec_paths_from_patch_headers() {
+        let spec = ChangeSpec::Patch {
+            content: "--- a/src/old.rs\t2024-01-01\n+++ b/src/old.rs\n@@ -1 +1 @@\n-a\n+b\n\
+                      --- /dev/null\n+++ b/config/new.toml\n@@ -0,0 +1 @@\n+x\n"
+                .into(),
+        };
+        assert_eq!(spec.paths(), vec!["src/old.rs", "config/new.toml"]);
+
+        let spec = ChangeSpec::Files {
+            operations: vec![FileOperation::Rename {
+                from: "a.rs".into(),
+                to: "b.rs".into(),
+            }],
+        };
+        assert_eq!(spec.paths(), vec!["a.rs", "b.rs"]);
+    }
+
     #[test]
     fn preconditions_empty() {
         let empty = Preconditions::default();
@@ -0,0 +1,425 @@
+// ABOUTME: knowledge export/import: the .agent knowledge base as one tar.zst bundle
+// ABOUTME: Import merges file by file: new files added, JSONL logs unioned, differing files newest-wins
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::{Component, Path};
+
+use crate::error::{Error, Result};
+use crate::repo::utc_timestamp;
+
+/// What a bundle carries, as (section, path under `.agent/`); a directory
+/// takes every file beneath it. Secrets, spilled output, usage metrics, and
+/// stash records (which point at hidden commits of one clone) stay behind.
+pub const SECTIONS: &[(&str, &str)] = &[
+    ("manifest", "manifest.toml"),
+    ("config", "config.toml"),
+    ("changes", "changes"),
+    ("checkpoints", "checkpoints"),
+    ("sessions", "audit"),
+    ("approvals", "approvals"),
+    ("notes", "notes"),
+    ("qa", "qa"),
+    ("queries", "queries.toml"),
+    ("tests", "tests"),
+];
+
+/// The bundle's table of contents, stored first in the archive
+const INDEX: &str = "agentjj-knowledge.json";
+const VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleIndex {
+    pub version: u32,
+    pub exported_at: String,
+    pub files: Vec<BundleFile>,
+}
+
+impl BundleIndex {
+    /// Files per section
+    pub fn sections(&self) -> BTreeMap<String, usize> {
+        let mut counts = BTreeMap::new();
+        for file in &self.files {
+            *counts.entry(file.section.clone()).or_insert(0) += 1;
+        }
+        counts
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleFile {
+    /// Path under `.agent/`, `/`-separated
+    pub path: String,
+    pub section: String,
+    pub bytes: u64,
+    /// Last modified, seconds since the Unix epoch
+    pub modified: i64,
+}
+
+/// Which copy a differing file kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Local,
+    Bundle,
+}
+
+/// A file that differed on both sides and was not a log to union
+#[derive(Debug, Clone, Serialize)]
+pub struct Conflict {
+    pub path: String,
+    /// The newer copy; the local one on a tie
+    pub kept: Side,
+    pub local_modified: String,
+    pub bundle_modified: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    /// Files the repository did not have
+    pub added: Vec<String>,
+    /// JSONL logs that gained the bundle's missing lines
+    pub merged: Vec<String>,
+    pub conflicts: Vec<Conflict>,
+    /// Files identical on both sides
+    pub unchanged: usize,
+}
+
+/// The section a path under `.agent/` belongs to, if a bundle may carry it
+fn section_of(path: &str) -> Option<&'static str> {
+    let safe = Path::new(path)
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)));
+    if !safe {
+        return None;
+    }
+    SECTIONS.iter().find_map(|(section, root)| {
+        (path == *root || path.strip_prefix(root).is_some_and(|r| r.starts_with('/')))
+            .then_some(*section)
+    })
+}
+
+fn modified_secs(path: &Path) -> i64 {
+    std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Every file under `dir`, as paths relative to `base`
+fn walk(base: &Path, dir: &Path, out: &mut Vec<String>) -> Result<()> {
+    let mut entries: Vec<_> = std::fs::read_dir(dir)?.flatten().collect();
+    entries.sort_by_key(|e| e.file_name());
+    for entry in entries {
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            walk(base, &path, out)?;
+        } else if file_type.is_file() {
+            if let Ok(rel) = path.strip_prefix(base) {
+                out.push(rel.to_string_lossy().replace('\\', "/"));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The knowledge base files of a repository, in section order
+pub fn collect(repo_root: impl AsRef<Path>) -> Result<Vec<BundleFile>> {
+    let agent = repo_root.as_ref().join(".agent");
+    let mut files = Vec::new();
+    for (section, root) in SECTIONS {
+        let path = agent.join(root);
+        let mut paths = Vec::new();
+        if path.is_file() {
+            paths.push(root.to_string());
+        } else if path.is_dir() {
+            walk(&agent, &path, &mut paths)?;
+        }
+        for rel in paths {
+            let full = agent.join(&rel);
+            files.push(BundleFile {
+                bytes: std::fs::metadata(&full)?.len(),
+                modified: modified_secs(&full),
+                path: rel,
+                section: section.to_string(),
+            });
+        }
+    }
+    Ok(files)
+}
+
+/// Write the knowledge base of a repository to a zstd-compressed tar at `out`
+pub fn export(repo_root: impl AsRef<Path>, out: impl AsRef<Path>) -> Result<BundleIndex> {
+    let agent = repo_root.as_ref().join(".agent");
+    let index = BundleIndex {
+        version: VERSION,
+        exported_at: crate::repo::utc_now(),
+        files: collect(repo_root.as_ref())?,
+    };
+    let index_json = serde_json::to_vec_pretty(&index).map_err(|e| Error::Io {
+        message: e.to_string(),
+    })?;
+
+    let encoder = zstd::Encoder::new(std::fs::File::create(out.as_ref())?, 0)?;
+    let mut builder = tar::Builder::new(encoder);
+    let mut append = |path: &str, content: &[u8], modified: i64| -> Result<()> {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(modified.max(0) as u64);
+        builder.append_data(&mut header, path, content)?;
+        Ok(())
+    };
+    append(INDEX, &index_json, 0)?;
+    for file in &index.files {
+        let content = std::fs::read(agent.join(&file.path))?;
+        append(&format!(".agent/{}", file.path), &content, file.modified)?;
+    }
+    builder.into_inner()?.finish()?;
+    Ok(index)
+}
+
+/// The index and file contents (by path under `.agent/`) of a bundle
+pub fn read_bundle(bundle: impl AsRef<Path>) -> Result<(BundleIndex, BTreeMap<String, Vec<u8>>)> {
+    let decoder = zstd::Decoder::new(std::fs::File::open(bundle.as_ref())?)?;
+    let mut archive = tar::Archive::new(decoder);
+    let mut index = None;
+    let mut contents = BTreeMap::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_string_lossy().to_string();
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content)?;
+        if path == INDEX {
+            index = Some(
+                serde_json::from_slice::<BundleIndex>(&content).map_err(|e| Error::Io {
+                    message: format!("corrupt knowledge bundle index: {}", e),
+                })?,
+            );
+        } else if let Some(rel) = path.strip_prefix(".agent/") {
+            contents.insert(rel.to_string(), content);
+        }
+    }
+    let index = index.ok_or_else(|| Error::Io {
+        message: format!(
+            "{} is not an agentjj knowledge bundle (no {})",
+            bundle.as_ref().display(),
+            INDEX
+        ),
+    })?;
+    if index.version > VERSION {
+        return Err(Error::Io {
+            message: format!(
+                "knowledge bundle version {} is newer than this agentjj supports ({})",
+                index.version, VERSION
+            ),
+        });
+    }
+    Ok((index, contents))
+}
+
+/// Lines of `local` followed by the lines of `bundle` it lacks
+fn union_lines(local: &str, bundle: &str) -> String {
+    let seen: std::collections::HashSet<&str> = local.lines().collect();
+    let mut merged = local.to_string();
+    if !merged.is_empty() && !merged.ends_with('\n') {
+        merged.push('\n');
+    }
+    for line in bundle.lines().filter(|l| !seen.contains(l)) {
+        merged.push_str(line);
+        merged.push('\n');
+    }
+    merged
+}
+
+fn write_file(path: &Path, content: &[u8], modified: Option<i64>) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, content)?;
+    // Keep the bundle's time so newest-wins stays meaningful on a re-import
+    if let Some(secs) = modified {
+        let time = std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs.max(0) as u64);
+        std::fs::File::options()
+            .write(true)
+            .open(path)?
+            .set_modified(time)?;
+    }
+    Ok(())
+}
+
+/// Merge a bundle into a repository's `.agent/` directory. Nothing is
+/// written with `dry_run`.
+pub fn import(
+    repo_root: impl AsRef<Path>,
+    bundle: impl AsRef<Path>,
+    dry_run: bool,
+) -> Result<ImportReport> {
+    let agent = repo_root.as_ref().join(".agent");
+    let (index, contents) = read_bundle(bundle)?;
+    let mut report = ImportReport {
+        dry_run,
+        ..Default::default()
+    };
+    for file in &index.files {
+        if section_of(&file.path).is_none() {
+            return Err(Error::Io {
+                message: format!("knowledge bundle has an unexpected path: {}", file.path),
+            });
+        }
+        let content = contents.get(&file.path).ok_or_else(|| Error::Io {
+            message: format!("knowledge bundle is missing {}", file.path),
+        })?;
+        let local_path = agent.join(&file.path);
+        let Ok(local) = std::fs::read(&local_path) else {
+            if !dry_run {
+                write_file(&local_path, content, Some(file.modified))?;
+            }
+            report.added.push(file.path.clone());
+            continue;
+        };
+        if local == *content {
+            report.unchanged += 1;
+        } else if file.path.ends_with(".jsonl") {
+            // Audit sessions and test history only ever grow
+            let merged = union_lines(
+                &String::from_utf8_lossy(&local),
+                &String::from_utf8_lossy(content),
+            );
+            if merged.as_bytes() != local.as_slice() {
+                if !dry_run {
+                    write_file(&local_path, merged.as_bytes(), None)?;
+                }
+                report.merged.push(file.path.clone());
+            } else {
+                report.unchanged += 1;
+            }
+        } else {
+            let local_modified = modified_secs(&local_path);
+            let kept = if file.modified > local_modified {
+                Side::Bundle
+            } else {
+                Side::Local
+            };
+            if kept == Side::Bundle && !dry_run {
+                write_file(&local_path, content, Some(file.modified))?;
+            }
+            report.conflicts.push(Conflict {
+                path: file.path.clone(),
+                kept,
+                local_modified: utc_timestamp(local_modified),
+                bundle_modified: utc_timestamp(file.modified),
+            });
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(root: &Path, path: &str, content: &str, modified: i64) {
+        write_file(
+            &root.join(".agent").join(path),
+            content.as_bytes(),
+            Some(modified),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn import_adds_unions_logs_and_keeps_the_newer_copy() {
+        let source = TempDir::new().unwrap();
+        write(
+            source.path(),
+            "manifest.toml",
+            "[repo]\nname = \"a\"\n",
+            2000,
+        );
+        write(
+            source.path(),
+            "changes/abc.toml",
+            "intent = \"new\"\n",
+            2000,
+        );
+        write(
+            source.path(),
+            "changes/def.toml",
+            "intent = \"old\"\n",
+            1000,
+        );
+        write(
+            source.path(),
+            "audit/s1.jsonl",
+            "{\"a\":1}\n{\"a\":2}\n",
+            1000,
+        );
+        write(source.path(), "secrets.env", "TOKEN=x\n", 1000);
+        let bundle = source.path().join("kb.tar.zst");
+        let index = export(source.path(), &bundle).unwrap();
+        assert_eq!(index.files.len(), 4);
+        assert_eq!(index.sections()["changes"], 2);
+
+        let target = TempDir::new().unwrap();
+        write(
+            target.path(),
+            "manifest.toml",
+            "[repo]\nname = \"b\"\n",
+            1000,
+        );
+        write(
+            target.path(),
+            "changes/def.toml",
+            "intent = \"newer\"\n",
+            3000,
+        );
+        write(
+            target.path(),
+            "audit/s1.jsonl",
+            "{\"a\":1}\n{\"b\":1}\n",
+            1000,
+        );
+
+        let dry = import(target.path(), &bundle, true).unwrap();
+        assert_eq!(dry.added, vec!["changes/abc.toml"]);
+        assert!(!target.path().join(".agent/changes/abc.toml").exists());
+
+        let report = import(target.path(), &bundle, false).unwrap();
+        assert_eq!(report.merged, vec!["audit/s1.jsonl"]);
+        let kept: Vec<(&str, Side)> = report
+            .conflicts
+            .iter()
+            .map(|c| (c.path.as_str(), c.kept))
+            .collect();
+        assert_eq!(
+            kept,
+            vec![
+                ("manifest.toml", Side::Bundle),
+                ("changes/def.toml", Side::Local)
+            ]
+        );
+        let agent = target.path().join(".agent");
+        let read = |p: &str| std::fs::read_to_string(agent.join(p)).unwrap();
+        assert_eq!(read("manifest.toml"), "[repo]\nname = \"a\"\n");
+        assert_eq!(read("changes/def.toml"), "intent = \"newer\"\n");
+        assert_eq!(read("audit/s1.jsonl"), "{\"a\":1}\n{\"b\":1}\n{\"a\":2}\n");
+        assert!(!agent.join("secrets.env").exists());
+
+        // A second import finds nothing new; the newer local copy still wins
+        let again = import(target.path(), &bundle, false).unwrap();
+        assert!(again.added.is_empty() && again.merged.is_empty());
+        assert_eq!(again.unchanged, 3);
+        assert_eq!(again.conflicts.len(), 1);
+
+        assert_eq!(section_of("changes/x.toml"), Some("changes"));
+        assert_eq!(section_of("changes/../secrets.env"), None);
+        assert_eq!(section_of("secrets.env"), None);
+    }
+}
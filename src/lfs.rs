@@ -0,0 +1,107 @@
+// ABOUTME: Git LFS awareness: pointer file parsing and `filter=lfs` patterns from .gitattributes
+// ABOUTME: Lets read/files/commit tell LFS-tracked and oversized files apart from ordinary ones
+
+use serde::Serialize;
+use std::path::Path;
+
+const POINTER_VERSION: &str = "version https://git-lfs.github.com/spec/v1";
+
+/// Pointers are small text files; anything bigger is real content
+const MAX_POINTER_BYTES: usize = 1024;
+
+/// A Git LFS pointer: what the working copy holds instead of the content
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LfsPointer {
+    /// `sha256:<hex>` of the real content
+    pub oid: String,
+    /// Size of the real content in bytes
+    pub size: u64,
+}
+
+/// Parse an LFS pointer file, or None if the bytes are not one
+pub fn parse_pointer(bytes: &[u8]) -> Option<LfsPointer> {
+    if bytes.len() > MAX_POINTER_BYTES {
+        return None;
+    }
+    let text = std::str::from_utf8(bytes).ok()?;
+    let mut lines = text.lines();
+    if lines.next()? != POINTER_VERSION {
+        return None;
+    }
+    let (mut oid, mut size) = (None, None);
+    for line in lines {
+        match line.split_once(' ') {
+            Some(("oid", value)) => oid = Some(value.to_string()),
+            Some(("size", value)) => size = value.parse().ok(),
+            _ => {}
+        }
+    }
+    Some(LfsPointer {
+        oid: oid?,
+        size: size?,
+    })
+}
+
+/// Patterns marked `filter=lfs` in the repository's root .gitattributes
+pub fn patterns(root: &Path) -> Vec<String> {
+    let Ok(content) = std::fs::read_to_string(root.join(".gitattributes")) else {
+        return Vec::new();
+    };
+    content
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let pattern = fields.next()?;
+            fields
+                .any(|attr| attr == "filter=lfs")
+                .then(|| pattern.to_string())
+        })
+        .collect()
+}
+
+/// Whether a repo-relative path matches one of the LFS patterns. The
+/// shared glob rules follow gitattributes: a pattern without `/` matches
+/// the file name at any depth, otherwise the path from the root.
+pub fn is_tracked(path: &str, patterns: &[String]) -> bool {
+    patterns
+        .iter()
+        .any(|pattern| crate::globs::is_match(pattern, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_pointers_only() {
+        let pointer = b"version https://git-lfs.github.com/spec/v1\noid sha256:4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393\nsize 12345\n";
+        assert_eq!(
+            parse_pointer(pointer),
+            Some(LfsPointer {
+                oid: "sha256:4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393"
+                    .into(),
+                size: 12345,
+            })
+        );
+        assert_eq!(parse_pointer(b"version 1\nsize 3\n"), None);
+        assert_eq!(parse_pointer(b"hello"), None);
+    }
+
+    #[test]
+    fn matches_gitattributes_patterns() {
+        let tmp = tempfile::TempDir::new().unwrap();
+        std::fs::write(
+            tmp.path().join(".gitattributes"),
+            "# media\n*.psd filter=lfs diff=lfs merge=lfs -text\n/data/*.bin filter=lfs\n*.md text\n",
+        )
+        .unwrap();
+        let patterns = patterns(tmp.path());
+        assert_eq!(patterns, vec!["*.psd", "/data/*.bin"]);
+
+        assert!(is_tracked("art/cover.psd", &patterns));
+        assert!(is_tracked("data/model.bin", &patterns));
+        assert!(!is_tracked("nested/data/model.bin", &patterns));
+        assert!(!is_tracked("README.md", &patterns));
+    }
+}
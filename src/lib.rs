@@ -1,14 +1,84 @@
 // ABOUTME: Library root for agentjj - agent-oriented jj porcelain
-// ABOUTME: Exports manifest, typed changes, intent transactions, and repo operations
+// ABOUTME: Exports manifest, typed changes, intents, repo operations, config, audit log, snippets, and tooling
 
+pub mod affected;
+pub mod agent_dir;
+pub mod agentignore;
+pub mod api;
+pub mod approval;
+pub mod attempts;
+pub mod audit;
+pub mod blobcache;
+pub mod callers;
+pub mod capabilities;
 pub mod change;
+pub mod cli_spec;
+pub mod compare;
+pub mod config;
+pub mod confirm;
+pub mod consumers;
+pub mod custom;
+pub mod dashboard;
+pub mod deadline;
+pub mod describe;
+pub mod docs;
+pub mod dupes;
+pub mod encoding;
+pub mod env;
 pub mod error;
+pub mod fix;
+pub mod globs;
+pub mod history;
 pub mod intent;
+pub mod knowledge;
+pub mod lfs;
 pub mod manifest;
+pub mod manifest_audit;
+pub mod mergequeue;
+pub mod metrics;
+pub mod notebook;
+pub mod notes;
+pub mod overlay;
+pub mod ownership;
+pub mod partition;
+pub mod plan;
+pub mod preflight;
+pub mod preimage;
+pub mod presets;
+pub mod progress;
+pub mod prompts;
+pub mod qa;
+pub mod queries;
+pub mod reconcile;
+pub mod refsync;
 pub mod repo;
+pub mod rollback;
+pub mod scaffold;
+pub mod secrets;
+pub mod series;
+pub mod skill;
+pub mod snippet;
+pub mod spill;
+pub mod sql;
+pub mod stable;
+pub mod start;
+pub mod stash;
+pub mod stats;
+pub mod storage;
+pub mod structure;
+pub mod symbol_search;
 pub mod symbols;
+pub mod testrun;
+pub mod todos;
+pub mod tooling;
+pub mod trace;
+pub mod triage;
+#[cfg(feature = "tui")]
+pub mod tui;
+pub mod unified;
 
 pub use change::{ChangeCategory, ChangeType, TypedChange};
+pub use config::Config;
 pub use error::{Error, Result};
 pub use intent::{Intent, IntentResult};
 pub use manifest::Manifest;
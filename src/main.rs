@@ -2,12 +2,17 @@
 // ABOUTME: Provides commands for manifest, typed changes, intent transactions, and reads
 
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
 
 use agentjj::change::{ChangeCategory, ChangeType, TypedChange};
+use agentjj::confirm::Gate;
+use agentjj::error::exit;
 use agentjj::intent::{ChangeSpec, Intent, Preconditions};
-use agentjj::manifest::Manifest;
+use agentjj::manifest::{HookEvent, Manifest};
 use agentjj::repo::Repo;
+use agentjj::symbols::is_public_symbol;
 
 #[derive(Parser)]
 #[command(name = "agentjj")]
@@ -18,6 +23,81 @@ struct Cli {
     #[arg(long, global = true)]
     json: bool,
 
+    /// Emit progress records for long operations on stderr (format: jsonl)
+    #[arg(long, global = true, value_name = "FORMAT", value_parser = ["jsonl"])]
+    progress: Option<String>,
+
+    /// Give up after this many seconds, killing running invariants and hooks
+    #[arg(long, global = true, value_name = "SECS")]
+    timeout: Option<u64>,
+
+    /// Override a config value for this invocation (e.g. --config output.json=true)
+    #[arg(long = "config", global = true, value_name = "KEY=VALUE")]
+    config_overrides: Vec<String>,
+
+    /// Print deterministic JSON for golden-file tests: arrays sorted, IDs
+    /// as placeholders ("<op-id>"), timestamps pinned, durations zeroed
+    /// (implies --json)
+    #[arg(long, global = true)]
+    stable_output: bool,
+
+    /// Epoch seconds --stable-output sets every timestamp to
+    /// [default: $SOURCE_DATE_EPOCH, else 0]
+    #[arg(long, global = true, value_name = "SECS", requires = "stable_output")]
+    stable_epoch: Option<i64>,
+
+    /// Time workspace loads, snapshots, tree diffs, invariants, and
+    /// subprocesses; JSON output gains a "timings" section, other output a
+    /// summary on stderr
+    #[arg(long, global = true)]
+    trace: bool,
+
+    /// With --trace, also write a Chrome trace (chrome://tracing, Perfetto)
+    /// to .agent/trace/
+    #[arg(long, global = true, requires = "trace")]
+    trace_file: bool,
+
+    /// Spill JSON results larger than this to .agent/out/ and print a stub
+    /// [default: output.max_output_bytes from config, else unlimited]
+    #[arg(long, global = true, value_name = "BYTES")]
+    max_output_bytes: Option<usize>,
+
+    /// Exit 2 when a command succeeds with warnings (for CI gating)
+    #[arg(long, global = true)]
+    strict: bool,
+
+    /// Scan paths hidden by .agentignore too
+    #[arg(long, global = true)]
+    no_agentignore: bool,
+
+    /// Never set up jj in a git repository that has none (use `init --colocate`)
+    #[arg(long, global = true)]
+    no_auto_init: bool,
+
+    /// Refuse every command that changes the repository (for exploration-only agents)
+    #[arg(long, global = true)]
+    read_only: bool,
+
+    /// Walk at most this many commits back in graph, stats churn, change
+    /// backfill, and blame [default: the manifest's history.max_depth]
+    #[arg(long, global = true, value_name = "N")]
+    max_history: Option<usize>,
+
+    /// When a policy or an environment freeze blocks the command, record a
+    /// confirmation for a human (`confirm <id>`) and notify the
+    /// `confirmation` hooks instead of only failing
+    #[arg(long, global = true)]
+    request_confirmation: bool,
+
+    /// Like --request-confirmation, then wait up to this long for the
+    /// decision and carry on if approved
+    #[arg(long, global = true, value_name = "SECS")]
+    wait_confirmation: Option<u64>,
+
+    /// Resume a blocked command with a confirmation a human approved
+    #[arg(long, global = true, value_name = "ID")]
+    confirmation: Option<String>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -29,11 +109,32 @@ enum Commands {
         /// Repository name
         #[arg(short, long)]
         name: Option<String>,
+
+        /// Set up jj alongside the git repository even when auto-colocation
+        /// is off (--no-auto-init, auto.colocate = false)
+        #[arg(long)]
+        colocate: bool,
+
+        /// Commit the manifest and .agent/.gitignore as a typed config change
+        #[arg(long)]
+        commit: bool,
+
+        /// Also add an AGENTS.md pointing agents at `agentjj skill`
+        #[arg(long)]
+        with_skill: bool,
     },
 
     /// Show repository status (change ID, operation ID, files)
     Status,
 
+    /// Check that the workspace loads, the manifest parses, and git's
+    /// branches and HEAD agree with jj's bookmarks and @-
+    Doctor {
+        /// Only compare git refs with jj bookmarks
+        #[arg(long)]
+        sync_check: bool,
+    },
+
     /// Show or validate the manifest
     Manifest {
         #[command(subcommand)]
@@ -49,16 +150,18 @@ enum Commands {
     /// Apply an intent (atomic transaction)
     Apply {
         /// Intent description
-        #[arg(short, long)]
-        intent: String,
+        #[arg(short, long, required_unless_present = "resume")]
+        intent: Option<String>,
 
         /// Change type (behavioral, refactor, schema, docs, deps, config, test)
-        #[arg(short = 't', long, default_value = "behavioral")]
-        r#type: String,
+        /// [default: change.default_type from config, else behavioral]
+        #[arg(short = 't', long)]
+        r#type: Option<String>,
 
-        /// Category (feature, fix, perf, security, breaking, deprecation, chore)
-        #[arg(short, long)]
-        category: Option<String>,
+        /// Category (feature, fix, perf, security, breaking, deprecation, chore,
+        /// or one from the manifest's [categories]); repeatable
+        #[arg(short, long, value_delimiter = ',')]
+        category: Vec<String>,
 
         /// Patch file to apply
         #[arg(short, long)]
@@ -75,6 +178,56 @@ enum Commands {
         /// Mark as breaking change
         #[arg(long)]
         breaking: bool,
+
+        /// Resume an intent parked for approval, once a human approved it
+        #[arg(long, value_name = "APPROVAL_ID", conflicts_with_all = ["intent", "patch"])]
+        resume: Option<String>,
+
+        /// Build the change on this revision (change ID, bookmark, @-)
+        /// instead of the working copy, which is left untouched (still run
+        /// in a jj workspace: bare repositories are not supported)
+        #[arg(long, value_name = "REV", conflicts_with = "resume")]
+        onto: Option<String>,
+
+        /// Record this as a retry of an earlier intent (its `intent.id`),
+        /// subject to the manifest's [intents] limits
+        #[arg(long, value_name = "INTENT_ID", conflicts_with = "resume")]
+        attempt_of: Option<String>,
+    },
+
+    /// Apply file operations that carry expected pre-image hashes (compare-and-swap)
+    Edit {
+        /// Intent description
+        #[arg(short, long)]
+        intent: String,
+
+        /// JSON array of file operations, or "-" to read them from stdin
+        #[arg(long)]
+        ops: String,
+
+        /// Change type (behavioral, refactor, schema, docs, deps, config, test)
+        /// [default: change.default_type from config, else behavioral]
+        #[arg(short = 't', long)]
+        r#type: Option<String>,
+
+        /// Category (feature, fix, perf, security, breaking, deprecation, chore,
+        /// or one from the manifest's [categories]); repeatable
+        #[arg(short, long, value_delimiter = ',')]
+        category: Vec<String>,
+
+        /// Skip running invariants
+        #[arg(long)]
+        no_invariants: bool,
+
+        /// Mark as breaking change
+        #[arg(long)]
+        breaking: bool,
+
+        /// Build the change on this revision (change ID, bookmark, @-) in
+        /// memory, without touching the working copy (still run in a jj
+        /// workspace: bare repositories are not supported)
+        #[arg(long, value_name = "REV")]
+        onto: Option<String>,
     },
 
     /// Read file content at a specific change
@@ -85,22 +238,59 @@ enum Commands {
         /// Change ID or branch (default: @)
         #[arg(short, long)]
         at: Option<String>,
+
+        /// Only read the body of this symbol
+        #[arg(long, conflicts_with = "around_line")]
+        symbol: Option<String>,
+
+        /// Only read the lines around this 1-based line number
+        #[arg(long)]
+        around_line: Option<usize>,
+
+        /// Lines to include either side of --around-line
+        #[arg(long, default_value = "20", requires = "around_line")]
+        context: usize,
+
+        /// Read the file as stored: notebook JSON, or LFS pointer text
+        #[arg(long)]
+        raw: bool,
     },
 
     /// Query symbols in the codebase
     Symbol {
         /// Symbol path (e.g., src/api.py::process_request)
-        path: String,
+        #[arg(required_unless_present = "find")]
+        path: Option<String>,
 
         /// Show only signature
         #[arg(long)]
         signature: bool,
+
+        /// Search the whole repo for symbols whose names match, fuzzily
+        #[arg(long, value_name = "QUERY", conflicts_with = "path")]
+        find: Option<String>,
+
+        /// With --find: only symbols of this kind (function, method, class, ...)
+        #[arg(long, requires = "find")]
+        kind: Option<String>,
+
+        /// With --find: only files in this language (rust, python, ...)
+        #[arg(long, requires = "find")]
+        lang: Option<String>,
+
+        /// With --find: most matches to show
+        #[arg(long, default_value = "20", requires = "find")]
+        limit: usize,
     },
 
     /// Get minimal context needed to use a symbol
     Context {
         /// Symbol path (e.g., src/api.py::process_request)
         path: String,
+
+        /// Render the documentation (only "markdown" for now)
+        #[arg(long, value_parser = ["markdown"])]
+        render: Option<String>,
     },
 
     /// Push changes and optionally create a PR
@@ -109,7 +299,7 @@ enum Commands {
         #[arg(short, long)]
         branch: Option<String>,
 
-        /// Change ID to push (default: @- if @ is empty, else @)
+        /// Change to push (default: @-, the last committed change)
         #[arg(short, long)]
         change: Option<String>,
 
@@ -128,25 +318,66 @@ enum Commands {
         /// Target branch for PR (default: main)
         #[arg(long, default_value = "main")]
         target: String,
+
+        /// Add the PR to the target branch's merge queue (GitHub merge queue,
+        /// or a `bors r+` comment)
+        #[arg(long, requires = "pr")]
+        enqueue: bool,
+
+        /// Overwrite the remote branch, but only while it is still at
+        /// EXPECTED (default: where it was last fetched or pushed)
+        #[arg(long, value_name = "EXPECTED", num_args = 0..=1, require_equals = true, default_missing_value = "")]
+        force_with_lease: Option<String>,
+    },
+
+    /// Pull requests: review and merge-queue state
+    Pr {
+        #[command(subcommand)]
+        action: PrAction,
+    },
+
+    /// Reconcile jj with git after git commands moved branches or HEAD:
+    /// import git's branches, export jj's bookmarks, and rebase the working
+    /// copy onto a moved HEAD
+    Sync {
+        /// Only import and export branches; leave HEAD and the working copy alone
+        #[arg(long)]
+        refs_only: bool,
     },
 
     /// Commit current changes with a message (describe + new)
     Commit {
         /// Commit message
-        #[arg(short, long)]
-        message: String,
+        #[arg(
+            short,
+            long,
+            conflicts_with = "auto_message",
+            required_unless_present = "auto_message"
+        )]
+        message: Option<String>,
+
+        /// Generate a conventional-commit message from the files and symbols
+        /// changed (or with change.message_command when configured)
+        #[arg(long)]
+        auto_message: bool,
+
+        /// With --auto-message: show the generated message without committing
+        #[arg(long, requires = "auto_message")]
+        dry_run: bool,
 
         /// Don't create a new working copy after committing
         #[arg(long)]
         no_new: bool,
 
         /// Change type (behavioral, refactor, schema, docs, deps, config, test)
-        #[arg(short = 't', long = "type", default_value = "behavioral")]
-        change_type: String,
+        /// [default: change.default_type from config, else behavioral]
+        #[arg(short = 't', long = "type")]
+        change_type: Option<String>,
 
-        /// Category (feature, fix, perf, security, breaking, deprecation, chore)
-        #[arg(short, long)]
-        category: Option<String>,
+        /// Category (feature, fix, perf, security, breaking, deprecation, chore,
+        /// or one from the manifest's [categories]); repeatable
+        #[arg(short, long, value_delimiter = ',')]
+        category: Vec<String>,
 
         /// Skip running invariants
         #[arg(long)]
@@ -159,6 +390,46 @@ enum Commands {
         /// Only include changes to these paths in the commit
         #[arg(long, num_args = 1..)]
         paths: Option<Vec<String>>,
+
+        /// Set a typed-change field, e.g. one a manifest change template requires (repeatable)
+        #[arg(long = "field", value_name = "NAME=VALUE")]
+        fields: Vec<String>,
+
+        /// A change that must be pushed or merged before this one (repeatable)
+        #[arg(long = "depends-on", value_name = "CHANGE")]
+        depends_on: Vec<String>,
+    },
+
+    /// Rewrite the descriptions of existing changes, rebasing their descendants
+    Reword {
+        /// Changes to reword (change ID, bookmark, @, or @-). With --from-typed
+        /// and none given: every change in @'s history with typed metadata
+        changes: Vec<String>,
+
+        /// New description for a single change
+        #[arg(
+            short,
+            long,
+            conflicts_with = "from_typed",
+            required_unless_present = "from_typed"
+        )]
+        message: Option<String>,
+
+        /// Regenerate descriptions from typed-change metadata
+        #[arg(long)]
+        from_typed: bool,
+
+        /// With --from-typed: only changes after this revision (e.g. main)
+        #[arg(long, requires = "from_typed")]
+        since: Option<String>,
+
+        /// Show the new descriptions without rewriting anything
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Rewrite changes that are already pushed or configured immutable
+        #[arg(long)]
+        allow_immutable: bool,
     },
 
     /// Create or update a git tag
@@ -182,12 +453,62 @@ enum Commands {
     /// Complete repository orientation for agents - everything you need to start working
     Orient,
 
+    /// Report which features work in this environment, as a stable JSON
+    /// document (works without a repo)
+    Capabilities,
+
+    /// Begin a task in one call: session, brief orientation, preflight on the
+    /// paths it likely touches, a checkpoint, and a plan
+    Start {
+        /// The task in a sentence (e.g. "fix the login timeout in src/auth/")
+        #[arg(long)]
+        task: String,
+
+        /// Preflight these instead of the paths guessed from the task
+        #[arg(long, num_args = 1..)]
+        paths: Vec<String>,
+
+        /// Skip the checkpoint (the only way to run start in read-only mode)
+        #[arg(long)]
+        no_checkpoint: bool,
+
+        /// Number of recent changes preflight scans for churn
+        #[arg(long, default_value = "20")]
+        changes: usize,
+    },
+
     /// Checkpoint operations (create, list)
     Checkpoint {
         #[command(subcommand)]
         action: CheckpointAction,
     },
 
+    /// Deployment environments tracked by bookmarks ([environments] in the manifest)
+    Env {
+        #[command(subcommand)]
+        action: EnvAction,
+    },
+
+    /// Park uncommitted work as a hidden change and reapply it later
+    Stash {
+        #[command(subcommand)]
+        action: StashAction,
+    },
+
+    /// Plan (and with --execute, carry out) taking a change back out
+    Rollback {
+        /// Change to roll back (change ID, bookmark, @-)
+        change: String,
+
+        /// Strategy to execute [default: the plan's recommendation]
+        #[arg(long, value_parser = ["revert", "reset", "restore"])]
+        strategy: Option<String>,
+
+        /// Carry out the plan instead of only showing it
+        #[arg(long)]
+        execute: bool,
+    },
+
     /// Undo the last operation (restore to previous state)
     Undo {
         /// Number of operations to undo (default: 1)
@@ -201,6 +522,10 @@ enum Commands {
         /// Dry run - show what would be undone without doing it
         #[arg(long)]
         dry_run: bool,
+
+        /// Only restore these paths from the checkpoint, keeping other work
+        #[arg(long, num_args = 1.., requires = "to")]
+        paths: Option<Vec<String>>,
     },
 
     /// Bulk operations for efficiency
@@ -218,6 +543,31 @@ enum Commands {
         /// Include symbol counts per file
         #[arg(long)]
         symbols: bool,
+
+        /// Annotate each file with its VCS state (clean, modified, added,
+        /// deleted, conflicted, ignored, untracked)
+        #[arg(long)]
+        status: bool,
+
+        /// Only list files that differ from the parent change (implies --status)
+        #[arg(long)]
+        dirty: bool,
+    },
+
+    /// Write the tree at a revision into a directory outside the repo,
+    /// without touching the working copy
+    Export {
+        /// Revision to export (change ID, bookmark, @, or @-)
+        #[arg(long, default_value = "@")]
+        at: String,
+
+        /// Directory to write into (created if missing, must be empty)
+        #[arg(long)]
+        to: std::path::PathBuf,
+
+        /// Only export these files or directories
+        #[arg(long, num_args = 1..)]
+        paths: Option<Vec<String>>,
     },
 
     /// Show semantic diff of current changes
@@ -229,16 +579,90 @@ enum Commands {
         /// Include AI-generated explanation of changes
         #[arg(long)]
         explain: bool,
+
+        /// Diff the working copy against where it branched off --against
+        /// (their common ancestor) rather than the revision itself
+        #[arg(long, requires = "against")]
+        merge_base: bool,
+
+        /// unified, or edits: the file operations `edit --ops` accepts, to
+        /// replay the change elsewhere
+        #[arg(
+            long,
+            default_value = "unified",
+            value_parser = ["unified", "edits"],
+            conflicts_with = "explain"
+        )]
+        format: String,
+    },
+
+    /// Diff two changes directly: files, symbols, metadata, and overlap
+    Compare {
+        /// First change (change ID, bookmark, @, or @-)
+        a: String,
+
+        /// Second change
+        b: String,
+    },
+
+    /// Combine two competing changes into a new change with both as parents
+    Reconcile {
+        /// First change (change ID, bookmark, @, or @-)
+        a: String,
+
+        /// Second change
+        b: String,
+
+        /// Settle overlapping hunks [default: leave them as conflicts]
+        #[arg(long, value_parser = ["prefer-a", "prefer-b", "union"])]
+        strategy: Option<String>,
     },
 
     /// Analyze what would be affected by changing a symbol
     Affected {
-        /// Symbol to analyze (e.g., src/api.rs::process)
-        symbol: String,
+        /// Symbol to analyze (e.g., src/api.rs::process); with --cross-repo,
+        /// defaults to the public symbols the working copy breaks
+        #[arg(required_unless_present = "cross_repo")]
+        symbol: Option<String>,
 
         /// Depth of dependency analysis (default: 2)
         #[arg(short, long, default_value = "2")]
         depth: usize,
+
+        /// Search the meta workspace's member repos (meta.members) for
+        /// consumers instead of this repo
+        #[arg(long)]
+        cross_repo: bool,
+
+        /// Only test files that reference the symbol (what to run)
+        #[arg(long, conflicts_with_all = ["public_only", "cross_repo"])]
+        tests_only: bool,
+
+        /// Only files whose own public API uses the symbol (what may break)
+        #[arg(long, conflicts_with = "cross_repo")]
+        public_only: bool,
+
+        /// Aggregate the affected files by directory or language
+        #[arg(long, value_name = "BY", value_parser = ["dir", "language"], conflicts_with = "cross_repo")]
+        group_by: Option<String>,
+    },
+
+    /// Check every call site of a function against its current signature
+    CheckCallers {
+        /// Function or method whose signature changed (e.g., src/api.py::process)
+        symbol: String,
+    },
+
+    /// Before editing: check paths for upstream and branch changes, parked
+    /// approvals, churn, and review rules, and say whether to go ahead
+    Preflight {
+        /// Files or directories about to be edited
+        #[arg(long, num_args = 1.., required = true)]
+        paths: Vec<String>,
+
+        /// Number of recent changes to scan for churn
+        #[arg(long, default_value = "20")]
+        changes: usize,
     },
 
     /// Print JSON schemas for all output types (self-documenting)
@@ -248,14 +672,81 @@ enum Commands {
         r#type: Option<String>,
     },
 
+    /// Run the configured test commands and record the results for validate
+    Test {
+        #[command(subcommand)]
+        action: TestAction,
+    },
+
+    /// Parse a failing build/test log and rank the current change's files as culprits
+    Triage {
+        /// Log file to triage, or "-" to read it from stdin
+        #[arg(required_unless_present = "ci")]
+        log: Option<String>,
+
+        /// Fetch the log of the latest failed GitHub Actions run (needs `gh`)
+        #[arg(long, conflicts_with = "log")]
+        ci: bool,
+    },
+
+    /// Sessions, agents, and changes that modified a path, newest first
+    WhoTouched {
+        /// File, directory, or glob, relative to the repository root
+        path: String,
+
+        /// Only changes made at or after this date or day count (2026-01-15, 7d)
+        #[arg(long)]
+        since: Option<String>,
+    },
+
     /// Validate current changes are complete and ready
-    Validate,
+    Validate {
+        /// Run the fixers (whitespace, manifest [fixers]) on changed files first
+        #[arg(long, conflicts_with = "at")]
+        fix: bool,
+
+        /// Run the invariants against another revision (e.g. main), checked
+        /// out to a temporary directory, instead of validating the change
+        #[arg(long, value_name = "REV")]
+        at: Option<String>,
+
+        /// With --at: run them where the working copy branched off REV (their
+        /// common ancestor) instead of at REV itself
+        #[arg(long, requires = "at")]
+        merge_base: bool,
+    },
 
     /// Suggest next actions based on current state
-    Suggest,
+    Suggest {
+        /// Plan towards a goal instead (e.g. "add api endpoint", release, "fix bug")
+        #[arg(long = "for", value_name = "GOAL")]
+        goal: Option<String>,
+    },
+
+    /// Run a custom command from the manifest's [commands.custom], or list them
+    Run {
+        /// Custom command name (omit to list what the manifest defines)
+        name: Option<String>,
+
+        /// Argument for the command's {{placeholders}} (repeatable)
+        #[arg(long = "arg", value_name = "KEY=VALUE")]
+        args: Vec<String>,
+    },
 
     /// Output the full skill documentation (for agent self-discovery)
-    Skill,
+    Skill {
+        /// Focused guidance for one command instead (e.g. "env promote")
+        #[arg(long, value_name = "NAME")]
+        command: Option<String>,
+    },
+
+    /// Output the CLI as a JSON spec: commands, typed flags, defaults, and
+    /// output schemas (for generating tool definitions)
+    CliSpec {
+        /// Only this command and its subcommands (e.g. "env")
+        #[arg(long, value_name = "NAME")]
+        command: Option<String>,
+    },
 
     /// Show a concise getting-started guide (works without a repo)
     Quickstart,
@@ -273,6 +764,155 @@ enum Commands {
         /// Show all branches, not just current
         #[arg(long)]
         all: bool,
+
+        /// What to draw: the commit DAG, or the operation log with undo/restore edges
+        #[arg(long, default_value = "commits", value_parser = ["commits", "ops"])]
+        kind: String,
+    },
+
+    /// Live dashboard: change graph, working copy, typed metadata, and sessions
+    /// (with --json, the same data once)
+    Ui {
+        /// Number of changes to show
+        #[arg(long, default_value = "30")]
+        limit: usize,
+
+        /// Seconds between refreshes
+        #[arg(long, default_value = "2")]
+        interval: u64,
+    },
+
+    /// Repository analytics: size per language, churn, symbols, typed changes
+    Stats {
+        /// Number of recent changes to scan for churn hotspots
+        #[arg(long, default_value = "50")]
+        changes: usize,
+
+        /// Number of entries in each ranked list (largest files, hotspots)
+        #[arg(long, default_value = "10")]
+        top: usize,
+
+        /// Show local command usage and error rates (needs metrics.enabled) and
+        /// read-cache hits instead
+        #[arg(long)]
+        usage: bool,
+
+        /// Show disk usage of each .agent subsystem against its [storage] cap,
+        /// with retention recommendations, instead
+        #[arg(long, conflicts_with = "usage")]
+        storage: bool,
+    },
+
+    /// Inventory TODO/FIXME/HACK comments with the change that added them
+    Todos {
+        /// Only those introduced by the current change
+        #[arg(long)]
+        new_only: bool,
+    },
+
+    /// Find near-duplicate functions across the repo (consolidation targets)
+    Dupes {
+        /// Minimum similarity (0.0-1.0) for two functions to count as copies
+        #[arg(long, default_value = "0.8")]
+        threshold: f64,
+
+        /// Ignore functions shorter than this many lines
+        #[arg(long, default_value = "5")]
+        min_lines: usize,
+
+        /// Only report functions this change (default: @) adds or modifies
+        /// that copy existing code
+        #[arg(long, value_name = "REV", num_args = 0..=1, default_missing_value = "@")]
+        against_change: Option<String>,
+    },
+
+    /// Prune old checkpoints and audit sessions, and garbage-collect the jj/git store
+    Gc {
+        /// Also prune every checkpoint beyond gc.keep_checkpoints, metadata for
+        /// changes that no longer exist, and all unreachable objects regardless of age
+        #[arg(long)]
+        aggressive: bool,
+
+        /// Report what would be pruned without deleting anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Export or re-run a session's recorded commands
+    Replay {
+        #[command(subcommand)]
+        action: ReplayAction,
+    },
+
+    /// Carry a range of changes as git format-patch files, typed metadata included
+    Series {
+        #[command(subcommand)]
+        action: SeriesAction,
+    },
+
+    /// Move the .agent knowledge base between clones and CI machines
+    Knowledge {
+        #[command(subcommand)]
+        action: KnowledgeAction,
+    },
+
+    /// Attempt history of intents applied with `apply`
+    Intent {
+        #[command(subcommand)]
+        action: IntentAction,
+    },
+
+    /// Review intents parked by `ask_change` permissions
+    Approvals {
+        #[command(subcommand)]
+        action: ApprovalsAction,
+    },
+
+    /// Approve or deny a command waiting for a human: a confirmation from
+    /// --request-confirmation, or an approval (see `approvals`)
+    Confirm {
+        /// Confirmation or approval ID
+        id: String,
+
+        /// Let the command through
+        #[arg(long, required_unless_present = "deny", conflicts_with = "deny")]
+        approve: bool,
+
+        /// Refuse it for good
+        #[arg(long)]
+        deny: bool,
+
+        /// Who is deciding [default: $USER]; off a terminal, approving needs one of the manifest's [review] approvers
+        #[arg(long)]
+        by: Option<String>,
+
+        /// Note to keep with the decision
+        #[arg(long)]
+        reason: Option<String>,
+    },
+
+    /// Leave notes on files, lines, and symbols for later sessions
+    Note {
+        #[command(subcommand)]
+        action: NoteAction,
+    },
+
+    /// Record answers about the codebase and find them again while still true
+    Qa {
+        #[command(subcommand)]
+        action: QaAction,
+    },
+
+    /// Save read-only command lines under a name and run them again by name
+    Query {
+        #[command(subcommand)]
+        action: QueryAction,
+    },
+
+    /// Show effective configuration (.agent/config.toml, AGENTJJ_* env, --config)
+    Config {
+        /// Show a single key (e.g. invariants.timeout_secs)
+        key: Option<String>,
     },
 }
 
@@ -282,6 +922,10 @@ enum BulkAction {
     Read {
         /// File paths (space-separated)
         paths: Vec<String>,
+
+        /// Read them at this change or branch instead of from disk
+        #[arg(short, long)]
+        at: Option<String>,
     },
 
     /// Query symbols across multiple files
@@ -292,6 +936,11 @@ enum BulkAction {
         /// Only show public symbols
         #[arg(long)]
         public_only: bool,
+
+        /// Read files from this revision (@, @-, or a change ID) instead of
+        /// the working copy on disk
+        #[arg(long)]
+        at: Option<String>,
     },
 
     /// Get context for multiple symbols
@@ -309,12 +958,28 @@ enum ManifestAction {
     /// Validate the manifest
     Validate,
 
+    /// Check every manifest entry against the repository: missing commands
+    /// and scripts, patterns matching no file, moved paths, absent languages
+    Audit,
+
     /// Initialize a new manifest
     Init {
         /// Repository name
         #[arg(short, long)]
         name: String,
     },
+
+    /// Built-in invariant presets for `preset = [...]`
+    Presets {
+        #[command(subcommand)]
+        action: PresetAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum PresetAction {
+    /// List the presets and the invariants each adds
+    List,
 }
 
 #[derive(Subcommand)]
@@ -325,15 +990,43 @@ enum ChangeAction {
         change_id: String,
     },
 
-    /// List all typed changes
+    /// List typed changes, newest first
     List {
         /// Filter by type
         #[arg(short = 't', long)]
         r#type: Option<String>,
 
+        /// Filter by category; repeated, changes must have them all
+        #[arg(short = 'c', long, value_delimiter = ',')]
+        category: Vec<String>,
+
         /// Show only breaking changes
         #[arg(long)]
         breaking: bool,
+
+        /// Only changes created on or after a date (2026-01-15) or N days ago (7d)
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Only changes created on or before a date (2026-01-15, 2026-01) or N days ago
+        #[arg(long)]
+        until: Option<String>,
+
+        /// Only changes whose author (agent session or user) contains this
+        #[arg(long)]
+        author: Option<String>,
+
+        /// Only changes that touched a file matching this glob or under this directory
+        #[arg(long)]
+        path: Option<String>,
+
+        /// Order by creation time, number of files, or lines changed
+        #[arg(long, default_value = "created", value_parser = ["created", "files", "churn"])]
+        sort: String,
+
+        /// Show at most this many
+        #[arg(long)]
+        limit: Option<usize>,
     },
 
     /// Add or update typed change metadata
@@ -346,17 +1039,103 @@ enum ChangeAction {
         #[arg(short, long)]
         intent: String,
 
-        /// Change type
+        /// Change type [default: change.default_type from config]
         #[arg(short = 't', long)]
-        r#type: String,
+        r#type: Option<String>,
 
-        /// Category
-        #[arg(short = 'c', long)]
-        category: Option<String>,
+        /// Category; repeatable
+        #[arg(short = 'c', long, value_delimiter = ',')]
+        category: Vec<String>,
 
         /// Mark as breaking
         #[arg(long)]
         breaking: bool,
+
+        /// Set a field, e.g. one a manifest change template requires (repeatable)
+        #[arg(long = "field", value_name = "NAME=VALUE")]
+        fields: Vec<String>,
+
+        /// A change that must be pushed or merged before this one (repeatable)
+        #[arg(long = "depends-on", value_name = "CHANGE")]
+        depends_on: Vec<String>,
+    },
+
+    /// Correct recorded metadata, keeping a history of who changed what
+    Edit {
+        /// Change ID ("@" for the current change)
+        change_id: String,
+
+        /// New intent description
+        #[arg(short, long)]
+        intent: Option<String>,
+
+        /// New change type
+        #[arg(short = 't', long)]
+        r#type: Option<String>,
+
+        /// New categories, replacing the recorded ones; repeatable
+        #[arg(short = 'c', long, value_delimiter = ',')]
+        category: Vec<String>,
+
+        /// Set or clear the breaking flag
+        #[arg(long, value_name = "BOOL")]
+        breaking: Option<bool>,
+
+        /// Add a file to the recorded file list (repeatable)
+        #[arg(long = "add-file", value_name = "PATH")]
+        add_files: Vec<String>,
+
+        /// Remove a file from the recorded file list (repeatable)
+        #[arg(long = "remove-file", value_name = "PATH")]
+        remove_files: Vec<String>,
+
+        /// Add a change this one depends on (repeatable)
+        #[arg(long = "add-depends-on", value_name = "CHANGE")]
+        add_depends_on: Vec<String>,
+
+        /// Drop a declared dependency (repeatable)
+        #[arg(long = "remove-depends-on", value_name = "CHANGE")]
+        remove_depends_on: Vec<String>,
+
+        /// Who is editing [default: $AGENTJJ_SESSION, else $USER]
+        #[arg(long)]
+        by: Option<String>,
+    },
+
+    /// Check recorded metadata against what the change actually touched
+    Verify {
+        /// Change ID [default: every recorded change]
+        change_id: Option<String>,
+    },
+
+    /// Show or write the change's working notes: decisions made, known limitations
+    Notes {
+        /// Change ID
+        #[arg(default_value = "@")]
+        change_id: String,
+
+        /// Replace the notes with this text (empty clears them)
+        #[arg(long, conflicts_with = "edit")]
+        set: Option<String>,
+
+        /// Edit the notes in $VISUAL or $EDITOR
+        #[arg(long)]
+        edit: bool,
+    },
+
+    /// Record typed metadata for past commits from their conventional-commit messages
+    Backfill {
+        /// Only commits after this revision (change ID, bookmark, or @-) [default: all history]
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Replace metadata that was already recorded
+        #[arg(long)]
+        force: bool,
+
+        /// Show what would be recorded without saving it
+        #[arg(long)]
+        dry_run: bool,
     },
 }
 
@@ -374,730 +1153,1503 @@ enum CheckpointAction {
 
     /// List all checkpoints
     List,
+
+    /// Report what changed since a checkpoint (operations, changes, files, invariants)
+    Status {
+        /// Checkpoint name
+        name: String,
+
+        /// Skip running invariants
+        #[arg(long)]
+        no_invariants: bool,
+    },
 }
 
-fn main() {
-    let cli = Cli::parse();
-    let json_mode = cli.json;
+#[derive(Subcommand)]
+enum TestAction {
+    /// Run manifest invariants (or the detected test command) against the working copy
+    Run {
+        /// Only run tests impacted by the change's files
+        #[arg(long)]
+        affected_only: bool,
 
-    let result = run_command(cli);
+        /// Run only this invariant
+        #[arg(long)]
+        invariant: Option<String>,
 
-    if let Err(e) = result {
-        if json_mode {
-            println!(
-                "{}",
-                serde_json::json!({
-                    "error": true,
-                    "message": e.to_string()
-                })
-            );
-        } else {
-            eprintln!("Error: {}", e);
-        }
-        std::process::exit(1);
-    }
-}
+        /// Run against another revision, checked out to a temporary directory
+        #[arg(long, value_name = "REV", conflicts_with = "affected_only")]
+        at: Option<String>,
+    },
 
-fn run_command(cli: Cli) -> Result<()> {
-    match cli.command {
-        Commands::Init { name } => cmd_init(name, cli.json),
-        Commands::Status => cmd_status(cli.json),
-        Commands::Manifest { action } => cmd_manifest(action, cli.json),
-        Commands::Change { action } => cmd_change(action, cli.json),
-        Commands::Apply {
-            intent,
-            r#type,
-            category,
-            patch,
-            precondition,
-            no_invariants,
-            breaking,
-        } => cmd_apply(
-            intent,
-            r#type,
-            category,
-            patch,
-            precondition,
-            no_invariants,
-            breaking,
-            cli.json,
-        ),
-        Commands::Read { path, at } => cmd_read(path, at, cli.json),
-        Commands::Symbol { path, signature } => cmd_symbol(path, signature, cli.json),
-        Commands::Context { path } => cmd_context(path, cli.json),
-        Commands::Push {
-            branch,
-            change,
-            pr,
-            title,
-            body,
-            target,
-        } => cmd_push(branch, change, pr, title, body, target, cli.json),
-        Commands::Commit {
-            message,
-            no_new,
-            change_type,
-            category,
-            no_invariants,
-            breaking,
-            paths,
-        } => cmd_commit(
-            message,
-            no_new,
-            change_type,
-            category,
-            no_invariants,
-            breaking,
-            paths,
-            cli.json,
-        ),
-        Commands::Tag {
-            name,
-            message,
-            force,
-            push,
-        } => cmd_tag(name, message, force, push, cli.json),
-        Commands::Orient => cmd_orient(cli.json),
-        Commands::Checkpoint { action } => match action {
-            CheckpointAction::Create { name, description } => {
-                cmd_checkpoint(name, description, cli.json)
-            }
-            CheckpointAction::List => cmd_checkpoint_list(cli.json),
-        },
-        Commands::Undo { steps, to, dry_run } => cmd_undo(steps, to, dry_run, cli.json),
-        Commands::Bulk { action } => cmd_bulk(action, cli.json),
-        Commands::Files { pattern, symbols } => cmd_files(pattern, symbols, cli.json),
-        Commands::Diff { against, explain } => cmd_diff(against, explain, cli.json),
-        Commands::Affected { symbol, depth } => cmd_affected(symbol, depth, cli.json),
-        Commands::Schema { r#type } => cmd_schema(r#type, cli.json),
-        Commands::Validate => cmd_validate(cli.json),
-        Commands::Suggest => cmd_suggest(cli.json),
-        Commands::Skill => cmd_skill(cli.json),
-        Commands::Quickstart => cmd_quickstart(cli.json),
-        Commands::Graph { format, limit, all } => cmd_graph(format, limit, all, cli.json),
-    }
+    /// Tests that both passed and failed on unchanged code
+    Flaky,
+
+    /// Create a test file for a source file at its language's conventional
+    /// location, importing its public symbols with an empty test for each
+    Scaffold {
+        /// Source file to write tests for
+        file: String,
+
+        /// Overwrite the test file if it exists
+        #[arg(long)]
+        force: bool,
+
+        /// Print the test file without writing it
+        #[arg(long)]
+        dry_run: bool,
+    },
 }
 
-fn cmd_init(name: Option<String>, json: bool) -> Result<()> {
-    let repo = Repo::discover()?;
+#[derive(Subcommand)]
+enum StashAction {
+    /// Move working-copy changes into a hidden change and reset to the parent
+    Push {
+        /// Stash name [default: stash-<n>]
+        name: Option<String>,
 
-    if repo.has_manifest() {
-        if json {
-            println!(r#"{{"status": "exists", "path": ".agent/manifest.toml"}}"#);
-        } else {
-            println!("Manifest already exists at .agent/manifest.toml");
-        }
-        return Ok(());
-    }
+        /// Description for the stashed change
+        #[arg(short, long)]
+        message: Option<String>,
+    },
 
-    let repo_name = name.unwrap_or_else(|| {
-        repo.root()
-            .file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or("unnamed")
-            .to_string()
-    });
+    /// Reapply a stash to the working copy and drop it unless it conflicts
+    Pop {
+        /// Stash name [default: the newest stash of this session]
+        name: Option<String>,
+    },
 
-    let manifest = Manifest {
-        repo: agentjj::manifest::RepoInfo {
-            name: repo_name.clone(),
-            description: String::new(),
-            languages: Vec::new(),
-            vcs: "jj".to_string(),
-        },
-        ..Default::default()
-    };
+    /// Forget a stash (its hidden change stays in the operation log)
+    Drop {
+        /// Stash name
+        name: String,
+    },
 
-    let manifest_path = repo.root().join(Manifest::DEFAULT_PATH);
-    if let Some(parent) = manifest_path.parent() {
-        std::fs::create_dir_all(parent)?;
-    }
-    std::fs::write(&manifest_path, manifest.to_toml()?)?;
+    /// List stashes, newest first
+    List {
+        /// Only stashes from this session
+        #[arg(long)]
+        session: Option<String>,
+    },
+}
 
-    // Create .agent/.gitignore to exclude local state but track manifest
-    let agent_gitignore = repo.root().join(".agent/.gitignore");
-    let gitignore_content = "# Agent-local state (not shared)\n\
-                             checkpoints/\n\
-                             changes/\n";
-    std::fs::write(&agent_gitignore, gitignore_content)?;
+#[derive(Subcommand)]
+enum EnvAction {
+    /// Show the change each environment's bookmark(s) point at
+    List,
 
-    if json {
-        println!(
-            r#"{{"status": "created", "name": "{}", "path": ".agent/manifest.toml", "gitignore": ".agent/.gitignore"}}"#,
-            repo_name
-        );
-    } else {
-        println!("Initialized agentjj for '{}'", repo_name);
-        println!("Created .agent/manifest.toml");
-        println!("Created .agent/.gitignore (excludes local state)");
-    }
+    /// Move an environment's bookmark to what another environment runs
+    Promote {
+        /// Source: an environment, a bookmark, or a revision (@, change ID)
+        from: String,
 
-    Ok(())
+        /// Target environment, or a bookmark of a pattern environment (preview/<name>)
+        #[arg(long)]
+        to: String,
+
+        /// Check policy and show the move without making it
+        #[arg(long)]
+        dry_run: bool,
+    },
 }
 
-fn cmd_status(json: bool) -> Result<()> {
-    let mut repo = Repo::discover()?;
+#[derive(Subcommand)]
+enum PrAction {
+    /// Show a branch's PR: state, merge queue position, and expected outcome
+    Status {
+        /// Branch the PR is from (default: the bookmark on @- or @)
+        branch: Option<String>,
+    },
+}
 
-    let change_id = repo
-        .current_change_id()
-        .unwrap_or_else(|_| "unknown".into());
-    let operation_id = repo
-        .current_operation_id()
-        .unwrap_or_else(|_| "unknown".into());
-    let files = repo.changed_files(&change_id).unwrap_or_default();
-    let has_manifest = repo.has_manifest();
+#[derive(Subcommand)]
+enum SeriesAction {
+    /// Write one numbered .patch file per change in a range
+    Export {
+        /// Changes to export: `from..to` (to defaults to @), or one revision
+        #[arg(long)]
+        range: String,
 
-    // Try to load typed change for current change
-    let typed_change = repo.get_typed_change(&change_id).ok();
+        /// Directory for the .patch files
+        #[arg(long)]
+        out: String,
+    },
 
-    if json {
-        let status = serde_json::json!({
-            "change_id": change_id,
-            "operation_id": operation_id,
-            "files_changed": files,
-            "has_manifest": has_manifest,
-            "typed_change": typed_change,
-        });
-        println!("{}", serde_json::to_string_pretty(&status)?);
-    } else {
-        println!("Change:    {}", &change_id[..12.min(change_id.len())]);
-        println!(
-            "Operation: {}...",
-            &operation_id[..16.min(operation_id.len())]
-        );
-        println!("Manifest:  {}", if has_manifest { "yes" } else { "no" });
+    /// Recreate the changes of a series, without touching the working copy
+    Import {
+        /// Directory of .patch files, or one patch/mbox file
+        path: String,
 
-        if !files.is_empty() {
-            println!("\nChanged files:");
-            for f in &files {
-                println!("  {}", f);
-            }
-        }
+        /// Put the series on this revision (default: its base commit when
+        /// present here, else @-)
+        #[arg(long)]
+        onto: Option<String>,
+    },
+}
 
-        if let Some(tc) = typed_change {
-            println!("\nTyped change:");
-            println!("  type:   {:?}", tc.change_type);
-            println!("  intent: {}", tc.intent);
-            if tc.breaking {
-                println!("  ⚠️  BREAKING CHANGE");
-            }
-        }
-    }
+#[derive(Subcommand)]
+enum ReplayAction {
+    /// Convert a session's audit records into a replay script
+    Export {
+        /// Session id (default: $AGENTJJ_SESSION, else "default")
+        #[arg(long)]
+        session: Option<String>,
 
-    Ok(())
+        /// Write the script here instead of stdout
+        #[arg(long)]
+        out: Option<String>,
+    },
+
+    /// Re-run a replay script against the current repository
+    Run {
+        /// Replay script from `replay export`
+        script: String,
+
+        /// Show the steps without running them
+        #[arg(long)]
+        dry_run: bool,
+    },
 }
 
-fn cmd_manifest(action: ManifestAction, json: bool) -> Result<()> {
-    match action {
-        ManifestAction::Show => {
-            let mut repo = Repo::discover()?;
-            let manifest = repo.manifest()?;
-            if json {
-                println!("{}", serde_json::to_string_pretty(manifest)?);
-            } else {
-                println!("{}", manifest.to_toml()?);
-            }
-        }
-        ManifestAction::Validate => {
-            let mut repo = Repo::discover()?;
-            match repo.manifest() {
-                Ok(m) => {
-                    if json {
-                        println!(r#"{{"valid": true, "name": "{}"}}"#, m.repo.name);
-                    } else {
-                        println!("✓ Manifest is valid");
-                        println!("  name: {}", m.repo.name);
-                        println!("  invariants: {}", m.invariants.len());
-                    }
-                }
-                Err(e) => {
-                    if json {
-                        println!(r#"{{"valid": false, "error": "{}"}}"#, e);
-                    } else {
-                        println!("✗ Manifest is invalid: {}", e);
-                    }
-                    std::process::exit(1);
-                }
-            }
-        }
-        ManifestAction::Init { name } => {
-            return cmd_init(Some(name), json);
-        }
-    }
-    Ok(())
+#[derive(Subcommand)]
+enum KnowledgeAction {
+    /// Bundle the manifest, config, typed changes, checkpoints, sessions,
+    /// approvals, and test history into one tar.zst file
+    Export {
+        /// Bundle to write (e.g. kb.tar.zst)
+        #[arg(long)]
+        out: String,
+    },
+
+    /// Merge a bundle into this repository: new files are added, session
+    /// and test logs unioned, and differing files keep the newer copy
+    Import {
+        /// Bundle from `knowledge export`
+        bundle: String,
+
+        /// Report what would change without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
 }
 
-fn cmd_change(action: ChangeAction, json: bool) -> Result<()> {
-    let mut repo = Repo::discover()?;
+#[derive(Subcommand)]
+enum NoteAction {
+    /// Attach a note; `context`, `read`, and `orient` show it with the code
+    Add {
+        /// What the note is about: path::symbol, path:line, or path
+        #[arg(long)]
+        on: String,
 
-    match action {
-        ChangeAction::Show { change_id } => {
-            let change = repo.get_typed_change(&change_id)?;
-            if json {
-                println!("{}", serde_json::to_string_pretty(&change)?);
-            } else {
-                println!("{}", change.to_toml()?);
-            }
-        }
-        ChangeAction::List { r#type, breaking } => {
-            let index = agentjj::change::ChangeIndex::load_from_repo(repo.root())?;
+        /// The note
+        #[arg(long)]
+        text: String,
+    },
 
-            let changes: Vec<_> = if breaking {
-                index.breaking_changes()
-            } else if let Some(type_str) = r#type {
-                let change_type = parse_change_type(&type_str)?;
-                index.by_type(change_type)
-            } else {
-                index.all()
-            };
+    /// List notes
+    List {
+        /// Only notes on this file, or on this path::symbol or path:line
+        #[arg(long)]
+        on: Option<String>,
+    },
 
-            if json {
-                println!("{}", serde_json::to_string_pretty(&changes)?);
-            } else if changes.is_empty() {
-                println!("No typed changes found");
-            } else {
-                for change in changes {
-                    println!(
-                        "{} [{:?}] {}",
-                        change.change_id, change.change_type, change.intent
-                    );
-                }
-            }
-        }
-        ChangeAction::Set {
-            change_id,
-            intent,
-            r#type,
-            category,
-            breaking,
-        } => {
-            // Resolve @ to actual jj change ID
-            let cid = match change_id {
-                Some(id) if id != "@" => id,
-                _ => repo.current_change_id()?,
-            };
-            let change_type = parse_change_type(&r#type)?;
-            let category = category.map(|c| parse_category(&c)).transpose()?;
+    /// Delete a note
+    Remove {
+        /// Note ID
+        id: String,
+    },
+}
 
-            let mut change = TypedChange::new(cid.clone(), change_type, intent);
-            if let Some(cat) = category {
-                change = change.with_category(cat);
-            }
-            if breaking {
-                change = change.breaking();
-            }
+#[derive(Subcommand)]
+enum QaAction {
+    /// Record a validated answer with the repository state it holds for
+    Record {
+        /// The question
+        #[arg(long = "q")]
+        question: String,
+
+        /// The answer
+        #[arg(long = "a")]
+        answer: String,
+
+        /// Files the answer rests on [default: files named in the question
+        /// or answer; with none, any change makes it stale]
+        #[arg(long, num_args = 1..)]
+        files: Vec<String>,
+    },
 
-            repo.save_typed_change(&change)?;
+    /// Find recorded answers by keyword, still-valid ones first
+    Search {
+        /// Words to look for in questions and answers
+        query: String,
 
-            if json {
-                println!("{}", serde_json::to_string_pretty(&change)?);
-            } else {
-                println!("Saved typed change for {}", cid);
-            }
-        }
-    }
-    Ok(())
+        /// Maximum results
+        #[arg(long, default_value = "10")]
+        limit: usize,
+    },
 }
 
-#[allow(clippy::too_many_arguments)]
-fn cmd_apply(
-    intent_desc: String,
-    type_str: String,
-    category: Option<String>,
-    patch: Option<String>,
-    preconditions: Vec<String>,
-    no_invariants: bool,
-    breaking: bool,
-    json: bool,
-) -> Result<()> {
-    let mut repo = Repo::discover()?;
+#[derive(Subcommand)]
+enum QueryAction {
+    /// Save (or replace) a named query in .agent/queries.toml
+    Save {
+        /// Query name (letters, digits, '-' and '_')
+        name: String,
 
-    let change_type = parse_change_type(&type_str)?;
+        /// A read-only agentjj command line, e.g. 'change list --path src/api --since 7d'
+        command: String,
 
-    // Build change spec
-    let changes = if let Some(patch_file) = patch {
-        let content = std::fs::read_to_string(&patch_file)?;
-        ChangeSpec::Patch { content }
-    } else {
-        anyhow::bail!("--patch is required (for now)");
-    };
+        /// What the query is for
+        #[arg(long)]
+        description: Option<String>,
+    },
 
-    // Build preconditions
-    let mut preconds = Preconditions::default();
-    for p in preconditions {
-        if let Some((branch, change_id)) = p.split_once('@') {
-            preconds = preconds.with_branch_at(branch, change_id);
-        } else {
-            anyhow::bail!("Invalid precondition format: {}. Use branch@change_id", p);
-        }
-    }
+    /// Run a saved query
+    Run {
+        /// Query name
+        name: String,
 
-    // Build intent
-    let mut intent = Intent::new(intent_desc, change_type, changes).with_preconditions(preconds);
+        /// Extra arguments appended to the saved command line (after `--`)
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
 
-    if let Some(cat) = category {
-        intent = intent.with_category(parse_category(&cat)?);
-    }
-    if no_invariants {
-        intent = intent.skip_invariants();
-    }
-    if breaking {
-        intent = intent.breaking();
-    }
+    /// List saved queries
+    List,
+}
 
-    // Apply
-    let result = repo.apply(intent)?;
+#[derive(Subcommand)]
+enum IntentAction {
+    /// Every attempt at an intent and how it ended, to spot retry loops
+    History {
+        /// Intent ID (`intent.id` in `apply` output)
+        id: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ApprovalsAction {
+    /// List pending approvals
+    List {
+        /// Include approved, denied, and applied ones too
+        #[arg(long)]
+        all: bool,
+    },
 
-    let is_success = matches!(&result, agentjj::intent::IntentResult::Success { .. });
+    /// Approve a pending intent so it can be resumed with `apply --resume`
+    Approve {
+        /// Approval ID
+        id: String,
 
-    if json {
-        println!("{}", serde_json::to_string_pretty(&result)?);
-    } else {
-        match &result {
-            agentjj::intent::IntentResult::Success { change_id, .. } => {
-                println!("✓ Applied successfully");
-                println!("  change: {}", change_id);
-            }
-            agentjj::intent::IntentResult::Conflict { conflicts, .. } => {
-                println!("✗ Conflict in {} files", conflicts.len());
+        /// Who is approving [default: $USER]; off a terminal, one of the manifest's [review] approvers
+        #[arg(long)]
+        by: Option<String>,
+
+        /// Note to keep with the decision
+        #[arg(long)]
+        reason: Option<String>,
+    },
+
+    /// Reject a pending intent for good
+    Deny {
+        /// Approval ID
+        id: String,
+
+        /// Who is denying [default: $USER]
+        #[arg(long)]
+        by: Option<String>,
+
+        /// Why it was denied
+        #[arg(long)]
+        reason: Option<String>,
+    },
+}
+
+fn main() {
+    let matches = Cli::command().get_matches();
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    // The flag is shorthand for a config override, so it wins over the file
+    if let Some(bytes) = cli.max_output_bytes {
+        cli.config_overrides
+            .push(format!("output.max_output_bytes={}", bytes));
+    }
+    // Config may turn JSON on, so resolve it before anything is printed
+    let config = agentjj::Config::set_cli_overrides(&cli.config_overrides)
+        .and_then(|_| agentjj::Config::discover());
+    match config {
+        Ok(config) => {
+            cli.json |= config.output.json;
+            agentjj::globs::set_case_insensitive(config.glob.case_insensitive);
+            // Only state-changing commands may set up jj next to a plain git
+            // repository; queries never modify it
+            agentjj::repo::set_auto_colocate(
+                config.auto.colocate && !cli.no_auto_init && !query_command(&cli.command),
+            );
+            if config.metrics.enabled {
+                let _ = METRICS_COMMAND.set(command_path(&matches));
             }
-            agentjj::intent::IntentResult::PreconditionFailed {
-                reason,
-                expected,
-                actual,
-            } => {
-                println!("✗ Precondition failed: {}", reason);
-                println!("  expected: {}", expected);
-                println!("  actual: {}", actual);
-            }
-            agentjj::intent::IntentResult::InvariantFailed {
-                invariant,
-                stderr,
-                exit_code,
-                ..
-            } => {
-                println!("✗ Invariant '{}' failed (exit {})", invariant, exit_code);
-                if !stderr.is_empty() {
-                    println!("  stderr: {}", stderr);
-                }
-            }
-            agentjj::intent::IntentResult::PermissionDenied {
-                path, action, rule, ..
-            } => {
+        }
+        Err(e) => {
+            if cli.json {
                 println!(
-                    "✗ Permission denied: {} on '{}' (rule: {})",
-                    action, path, rule
+                    "{}",
+                    serde_json::json!({ "error": true, "message": e.to_string() })
                 );
+            } else {
+                eprintln!("Error: {}", e);
             }
-            agentjj::intent::IntentResult::RequiresReview { message, paths, .. } => {
-                println!("⚠ Requires human review: {}", message);
-                if !paths.is_empty() {
-                    println!("  paths: {}", paths.join(", "));
-                }
-            }
+            std::process::exit(exit::FAILURE);
         }
     }
+    STRICT.store(cli.strict, Ordering::Relaxed);
+    if cli.stable_output {
+        cli.json = true;
+        let epoch = cli.stable_epoch.unwrap_or_else(|| {
+            std::env::var("SOURCE_DATE_EPOCH")
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap_or(0)
+        });
+        let _ = STABLE_EPOCH.set(epoch);
+    }
+    if cli.request_confirmation || cli.wait_confirmation.is_some() || cli.confirmation.is_some() {
+        let _ = CONFIRMATION.set(ConfirmationFlags {
+            request: cli.request_confirmation || cli.wait_confirmation.is_some(),
+            wait: cli.wait_confirmation,
+            id: cli.confirmation.clone(),
+            command: command_path(&matches),
+            args: args_without_confirmation_flags(),
+            json: cli.json,
+        });
+    }
+    if cli.trace {
+        agentjj::trace::enable();
+        let _ = TRACE.set(TraceFlags {
+            command: command_path(&matches),
+            file: cli.trace_file,
+            json: cli.json,
+        });
+    }
+    if cli.no_agentignore {
+        agentjj::agentignore::disable();
+    }
+    if let Some(depth) = cli.max_history {
+        agentjj::history::set_max_depth(depth);
+    }
+    if cli.progress.is_some() {
+        agentjj::progress::enable();
+    }
+    if let Some(secs) = cli.timeout {
+        start_deadline(secs, cli.json);
+    }
+    let json_mode = cli.json;
+    let audited = audited_command(&cli.command);
+    let local = local_manifest();
+
+    // Read-only mode is enforced here, before any command code runs
+    let enabled_by = if cli.read_only {
+        Some("--read-only")
+    } else if manifest_read_only(local.as_ref()) {
+        Some("manifest default_mode")
+    } else {
+        None
+    };
+    if enabled_by.is_some() {
+        READ_ONLY.store(true, Ordering::Relaxed);
+        agentjj::repo::set_auto_colocate(false);
+    }
+    let result = match enabled_by {
+        Some(enabled_by) if !query_command(&cli.command) => Err(agentjj::Error::ReadOnlyMode {
+            command: command_path(&matches),
+            enabled_by: enabled_by.to_string(),
+        }
+        .into()),
+        _ => {
+            let _span = tracing::info_span!("command", name = %command_path(&matches)).entered();
+            run_command(cli)
+        }
+    };
 
-    if !is_success {
-        std::process::exit(1);
+    if let Some(command) = audited {
+        record_audit(command, &result);
+        if let Some((root, manifest)) = &local {
+            enforce_storage_caps(root, manifest);
+        }
     }
 
-    Ok(())
+    match result {
+        Ok(()) => {
+            finish_trace();
+            record_usage(exit::OK, None)
+        }
+        Err(e) => {
+            print_error(&e, json_mode, None);
+            exit_with(exit_code_for(&e), error_type(&e).as_deref());
+        }
+    }
 }
 
-fn cmd_read(path: String, at: Option<String>, json: bool) -> Result<()> {
-    let mut repo = Repo::discover()?;
-    let content = repo.read_file(&path, at.as_deref())?;
+/// Command path (e.g. "env promote") of this invocation, recorded in usage
+/// metrics when `metrics.enabled` is set
+static METRICS_COMMAND: OnceLock<String> = OnceLock::new();
 
-    if json {
-        println!(
-            "{}",
-            serde_json::to_string_pretty(&serde_json::json!({
-                "path": path,
-                "at": at,
-                "content": content
-            }))?
-        );
-    } else {
-        print!("{}", content);
+fn command_path(matches: &clap::ArgMatches) -> String {
+    let mut path = Vec::new();
+    let mut current = matches;
+    while let Some((name, sub)) = current.subcommand() {
+        path.push(name);
+        current = sub;
     }
+    path.join(" ")
+}
 
-    Ok(())
+/// The `type` of a library error (e.g. "stale_edit"), as in JSON `details`
+fn error_type(e: &anyhow::Error) -> Option<String> {
+    e.downcast_ref::<agentjj::Error>()
+        .and_then(|err| serde_json::to_value(err).ok())
+        .and_then(|v| v["type"].as_str().map(str::to_string))
 }
 
-fn cmd_symbol(path: String, signature_only: bool, json: bool) -> Result<()> {
-    // Parse path: can be "file.py" or "file.py::symbol_name"
-    let (file_path, symbol_name) = if let Some(idx) = path.find("::") {
-        (&path[..idx], Some(&path[idx + 2..]))
-    } else {
-        (path.as_str(), None)
+/// Exit with `code`, recording the outcome in usage metrics first
+fn exit_with(code: i32, error: Option<&str>) -> ! {
+    finish_trace();
+    record_usage(code, error.map(str::to_string));
+    std::process::exit(code)
+}
+
+/// Append this invocation to the usage metrics, if enabled (best-effort,
+/// and at most once even when a watchdog races the command)
+fn record_usage(exit_code: i32, error: Option<String>) {
+    use agentjj::metrics::{self, UsageEvent, UsageLog};
+    static RECORDED: AtomicBool = AtomicBool::new(false);
+
+    let Some(command) = METRICS_COMMAND.get() else {
+        return;
     };
+    if RECORDED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let Ok(repo) = Repo::discover() else {
+        return;
+    };
+    let event = UsageEvent {
+        timestamp: unix_now(),
+        command: command.clone(),
+        exit_code,
+        error: (exit_code != exit::OK && exit_code != exit::WARNINGS)
+            .then(|| error.unwrap_or_else(|| metrics::exit_category(exit_code).to_string())),
+    };
+    if let Err(e) = UsageLog::open(repo.root()).append(&event) {
+        eprintln!("warning: failed to record usage metrics: {}", e);
+    }
+}
 
-    let file_path_obj = std::path::Path::new(file_path);
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
 
-    // Detect language
-    let lang = agentjj::SupportedLanguage::from_path(file_path_obj)
-        .ok_or_else(|| anyhow::anyhow!("Unsupported file type: {}", file_path))?;
+/// Set from `--strict`: succeeding with warnings exits with `exit::WARNINGS`
+static STRICT: AtomicBool = AtomicBool::new(false);
 
-    // Read file content - use filesystem for absolute paths, jj for relative
-    let content = if file_path_obj.is_absolute() {
-        std::fs::read_to_string(file_path)?
-    } else {
-        let mut repo = Repo::discover()?;
-        repo.read_file(file_path, None)?
-    };
+/// Set by `--stable-output`: the epoch JSON timestamps are pinned to
+static STABLE_EPOCH: OnceLock<i64> = OnceLock::new();
 
-    if let Some(name) = symbol_name {
-        // Find specific symbol
-        let symbol = agentjj::symbols::find_symbol(&content, lang, name)?;
+/// Pretty JSON for stdout, with `--trace` timings and normalized under
+/// `--stable-output`
+fn json_string<T: serde::Serialize + ?Sized>(value: &T) -> serde_json::Result<String> {
+    if STABLE_EPOCH.get().is_none() && agentjj::trace::collector().is_none() {
+        return serde_json::to_string_pretty(value);
+    }
+    let mut value = serde_json::to_value(value)?;
+    if let Some(map) = value.as_object_mut() {
+        if let Some(timings) = trace_timings() {
+            map.insert("timings".to_string(), timings);
+        }
+    }
+    if let Some(&epoch) = STABLE_EPOCH.get() {
+        agentjj::stable::stabilize(&mut value, epoch);
+    }
+    serde_json::to_string_pretty(&value)
+}
 
-        match symbol {
-            Some(s) => {
-                if json {
-                    if signature_only {
-                        println!(
-                            "{}",
-                            serde_json::to_string_pretty(&serde_json::json!({
-                                "name": s.name,
-                                "signature": s.signature,
-                            }))?
+/// `--trace` and `--trace-file`, for reporting timings at exit
+struct TraceFlags {
+    command: String,
+    file: bool,
+    json: bool,
+}
+
+static TRACE: OnceLock<TraceFlags> = OnceLock::new();
+
+/// Set once a `timings` section has been printed with the output
+static TIMINGS_REPORTED: AtomicBool = AtomicBool::new(false);
+
+/// Where `--trace-file` writes this invocation's Chrome trace
+fn trace_file_path() -> Option<&'static std::path::PathBuf> {
+    static PATH: OnceLock<Option<std::path::PathBuf>> = OnceLock::new();
+    PATH.get_or_init(|| {
+        let flags = TRACE.get().filter(|flags| flags.file)?;
+        let started = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(0);
+        let name = match flags.command.as_str() {
+            "" => "agentjj".to_string(),
+            command => command.replace(' ', "-"),
+        };
+        let repo = Repo::discover().ok()?;
+        Some(
+            repo.root()
+                .join(".agent/trace")
+                .join(format!("{}-{}.json", started, name)),
+        )
+    })
+    .as_ref()
+}
+
+/// The `--trace` timings so far, with where the Chrome trace goes
+fn trace_timings() -> Option<serde_json::Value> {
+    let mut timings = agentjj::trace::collector()?.timings();
+    if let Some(path) = trace_file_path() {
+        timings["chrome_trace"] = path.display().to_string().into();
+    }
+    TIMINGS_REPORTED.store(true, Ordering::Relaxed);
+    Some(timings)
+}
+
+/// Report `--trace` timings no output carried (on stderr) and write the
+/// Chrome trace file (best-effort, once)
+fn finish_trace() {
+    static FINISHED: AtomicBool = AtomicBool::new(false);
+    let (Some(collector), Some(flags)) = (agentjj::trace::collector(), TRACE.get()) else {
+        return;
+    };
+    if FINISHED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    if !TIMINGS_REPORTED.load(Ordering::Relaxed) {
+        if let Some(timings) = trace_timings() {
+            if flags.json {
+                eprintln!("{}", serde_json::json!({ "timings": timings }));
+            } else {
+                eprintln!(
+                    "trace: {:.1}ms total",
+                    timings["total_ms"].as_f64().unwrap_or(0.0)
+                );
+                if let Some(by_name) = timings["by_name"].as_object() {
+                    for (name, total) in by_name {
+                        eprintln!(
+                            "  {:<16} {:>4}x {:>10.1}ms",
+                            name,
+                            total["count"],
+                            total["total_ms"].as_f64().unwrap_or(0.0)
                         );
-                    } else {
-                        println!("{}", serde_json::to_string_pretty(&s)?);
                     }
-                } else if signature_only {
-                    if let Some(sig) = &s.signature {
-                        println!("{}", sig);
-                    } else {
-                        println!("{}", s.name);
-                    }
-                } else {
-                    println!("{} ({:?})", s.name, s.kind);
-                    if let Some(sig) = &s.signature {
-                        println!("  {}", sig);
-                    }
-                    println!("  lines {}-{}", s.start_line, s.end_line);
                 }
-            }
-            None => {
-                if json {
-                    println!(r#"{{"error": "symbol not found", "name": "{}"}}"#, name);
-                } else {
-                    println!("Symbol '{}' not found in {}", name, file_path);
+                if let Some(path) = timings["chrome_trace"].as_str() {
+                    eprintln!("  chrome trace: {}", path);
                 }
-                std::process::exit(1);
             }
         }
-    } else {
-        // List all symbols in file
-        let symbols = agentjj::symbols::extract_symbols(&content, lang)?;
+    }
+    let Some(path) = trace_file_path() else {
+        return;
+    };
+    let process = format!("agentjj {}", flags.command);
+    let written = path
+        .parent()
+        .map_or(Ok(()), agentjj::agent_dir::ensure_private)
+        .and_then(|_| {
+            let trace = collector.chrome_trace(process.trim_end());
+            std::fs::write(path, trace.to_string())
+        });
+    if let Err(e) = written {
+        eprintln!("warning: failed to write trace file: {}", e);
+    }
+}
 
-        if json {
-            println!("{}", serde_json::to_string_pretty(&symbols)?);
-        } else {
-            for s in symbols {
-                let sig = s.signature.as_deref().unwrap_or(&s.name);
-                let truncated = if sig.len() > 60 {
-                    format!("{}...", &sig[..57])
-                } else {
-                    sig.to_string()
-                };
-                println!(
-                    "{:>4} {:10} {}",
-                    s.start_line,
-                    format!("{:?}", s.kind).to_lowercase(),
-                    truncated
-                );
-            }
+/// Set by `--read-only` or the manifest's `default_mode`: only query commands run
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// Exit code for a failed command: library errors map to their category,
+/// anything else is a plain failure
+fn exit_code_for(e: &anyhow::Error) -> i32 {
+    e.downcast_ref::<agentjj::Error>()
+        .map_or(exit::FAILURE, agentjj::Error::exit_code)
+}
+
+/// Under `--strict`, exit with `exit::WARNINGS` once a successful command
+/// has printed warnings
+fn exit_if_strict_warnings(warnings: &[String]) {
+    if STRICT.load(Ordering::Relaxed) && !warnings.is_empty() {
+        exit_with(exit::WARNINGS, None);
+    }
+}
+
+/// Print a failed command's error, with any results gathered before it
+fn print_error(e: &anyhow::Error, json: bool, partial: Option<serde_json::Value>) {
+    if json {
+        let mut output = serde_json::json!({
+            "error": true,
+            "message": e.to_string(),
+            "exit_code": exit_code_for(e),
+        });
+        // Library errors carry structured fields agents can act on
+        if let Some(details) = e
+            .downcast_ref::<agentjj::Error>()
+            .and_then(|err| serde_json::to_value(err).ok())
+        {
+            output["details"] = details;
+        }
+        if let Some(partial) = partial {
+            output["partial"] = partial;
         }
+        if let Some(timings) = trace_timings() {
+            output["timings"] = timings;
+        }
+        if let Some(&epoch) = STABLE_EPOCH.get() {
+            agentjj::stable::stabilize(&mut output, epoch);
+        }
+        println!("{}", output);
+    } else {
+        eprintln!("Error: {}", e);
     }
+}
 
-    Ok(())
+/// Stop a command that ran out of time during `phase`, reporting what it
+/// had done so far
+fn exit_timed_out(phase: &str, partial: serde_json::Value, json: bool) -> ! {
+    let err = agentjj::deadline::check(phase)
+        .err()
+        .unwrap_or_else(|| agentjj::Error::Timeout {
+            phase: phase.to_string(),
+            timeout_secs: 0,
+        });
+    let err = err.into();
+    print_error(&err, json, Some(partial));
+    exit_with(exit_code_for(&err), error_type(&err).as_deref());
 }
 
-fn parse_change_type(s: &str) -> Result<ChangeType> {
-    match s.to_lowercase().as_str() {
-        "behavioral" | "behavior" => Ok(ChangeType::Behavioral),
-        "refactor" => Ok(ChangeType::Refactor),
-        "schema" => Ok(ChangeType::Schema),
-        "docs" | "doc" => Ok(ChangeType::Docs),
-        "deps" | "dependency" | "dependencies" => Ok(ChangeType::Deps),
-        "config" | "configuration" => Ok(ChangeType::Config),
-        "test" | "tests" => Ok(ChangeType::Test),
-        _ => anyhow::bail!("Unknown change type: {}", s),
+/// Start the --timeout clock. Work checks the deadline cooperatively and
+/// subprocesses are killed when it passes; a watchdog exits the process if
+/// something still hasn't returned shortly after, but never in the middle
+/// of a transaction or store write (see `deadline::critical`).
+fn start_deadline(secs: u64, json: bool) {
+    let limit = std::time::Duration::from_secs(secs);
+    agentjj::deadline::set(limit);
+    std::thread::spawn(move || {
+        std::thread::sleep(limit + std::time::Duration::from_secs(2));
+        let _quiet = agentjj::deadline::quiesce();
+        exit_timed_out("command", serde_json::Value::Null, json);
+    });
+}
+
+fn run_command(cli: Cli) -> Result<()> {
+    match cli.command {
+        Commands::Init {
+            name,
+            colocate,
+            commit,
+            with_skill,
+        } => cmd_init(name, colocate, commit, with_skill, cli.json),
+        Commands::Status => cmd_status(cli.json),
+        Commands::Doctor { sync_check } => cmd_doctor(sync_check, cli.json),
+        Commands::Sync { refs_only } => cmd_sync(refs_only, cli.json),
+        Commands::Manifest { action } => cmd_manifest(action, cli.json),
+        Commands::Change { action } => cmd_change(action, cli.json),
+        Commands::Apply {
+            intent,
+            r#type,
+            category,
+            patch,
+            precondition,
+            no_invariants,
+            breaking,
+            resume,
+            onto,
+            attempt_of,
+        } => cmd_apply(
+            intent,
+            r#type,
+            category,
+            patch,
+            precondition,
+            no_invariants,
+            breaking,
+            resume,
+            onto,
+            attempt_of,
+            cli.json,
+        ),
+        Commands::Edit {
+            intent,
+            ops,
+            r#type,
+            category,
+            no_invariants,
+            breaking,
+            onto,
+        } => cmd_edit(
+            intent,
+            ops,
+            r#type,
+            category,
+            no_invariants,
+            breaking,
+            onto,
+            cli.json,
+        ),
+        Commands::Read {
+            path,
+            at,
+            symbol,
+            around_line,
+            context,
+            raw,
+        } => cmd_read(path, at, symbol, around_line, context, raw, cli.json),
+        Commands::Symbol {
+            path,
+            signature,
+            find,
+            kind,
+            lang,
+            limit,
+        } => match find {
+            Some(query) => {
+                cmd_symbol_find(&query, kind.as_deref(), lang.as_deref(), limit, cli.json)
+            }
+            None => cmd_symbol(path.unwrap_or_default(), signature, cli.json),
+        },
+        Commands::Context { path, render } => cmd_context(path, render.as_deref(), cli.json),
+        Commands::Push {
+            branch,
+            change,
+            pr,
+            title,
+            body,
+            target,
+            enqueue,
+            force_with_lease,
+        } => cmd_push(
+            branch,
+            change,
+            pr,
+            title,
+            body,
+            target,
+            enqueue,
+            force_with_lease,
+            cli.json,
+        ),
+        Commands::Pr { action } => match action {
+            PrAction::Status { branch } => cmd_pr_status(branch, cli.json),
+        },
+        Commands::Commit {
+            message,
+            auto_message,
+            dry_run,
+            no_new,
+            change_type,
+            category,
+            no_invariants,
+            breaking,
+            paths,
+            fields,
+            depends_on,
+        } => cmd_commit(
+            message,
+            auto_message,
+            dry_run,
+            no_new,
+            change_type,
+            category,
+            no_invariants,
+            breaking,
+            paths,
+            fields,
+            depends_on,
+            cli.json,
+        ),
+        Commands::Reword {
+            changes,
+            message,
+            from_typed,
+            since,
+            dry_run,
+            allow_immutable,
+        } => cmd_reword(
+            changes,
+            message,
+            from_typed,
+            since,
+            dry_run,
+            allow_immutable,
+            cli.json,
+        ),
+        Commands::Tag {
+            name,
+            message,
+            force,
+            push,
+        } => cmd_tag(name, message, force, push, cli.json),
+        Commands::Orient => cmd_orient(cli.json),
+        Commands::Capabilities => cmd_capabilities(cli.read_only, cli.json),
+        Commands::Start {
+            task,
+            paths,
+            no_checkpoint,
+            changes,
+        } => cmd_start(task, paths, no_checkpoint, changes, cli.json),
+        Commands::Checkpoint { action } => match action {
+            CheckpointAction::Create { name, description } => {
+                cmd_checkpoint(name, description, cli.json)
+            }
+            CheckpointAction::List => cmd_checkpoint_list(cli.json),
+            CheckpointAction::Status {
+                name,
+                no_invariants,
+            } => cmd_checkpoint_status(name, no_invariants, cli.json),
+        },
+        Commands::Env { action } => match action {
+            EnvAction::List => cmd_env_list(cli.json),
+            EnvAction::Promote { from, to, dry_run } => {
+                cmd_env_promote(from, to, dry_run, cli.json)
+            }
+        },
+        Commands::Stash { action } => match action {
+            StashAction::Push { name, message } => cmd_stash_push(name, message, cli.json),
+            StashAction::Pop { name } => cmd_stash_pop(name, cli.json),
+            StashAction::Drop { name } => cmd_stash_drop(name, cli.json),
+            StashAction::List { session } => cmd_stash_list(session, cli.json),
+        },
+        Commands::Rollback {
+            change,
+            strategy,
+            execute,
+        } => cmd_rollback(change, strategy, execute, cli.json),
+        Commands::Undo {
+            steps,
+            to,
+            dry_run,
+            paths,
+        } => cmd_undo(steps, to, dry_run, paths, cli.json),
+        Commands::Bulk { action } => cmd_bulk(action, cli.json),
+        Commands::Files {
+            pattern,
+            symbols,
+            status,
+            dirty,
+        } => cmd_files(pattern, symbols, status || dirty, dirty, cli.json),
+        Commands::Export { at, to, paths } => cmd_export(at, to, paths, cli.json),
+        Commands::Diff {
+            against,
+            explain,
+            merge_base,
+            format,
+        } => cmd_diff(against, explain, merge_base, &format, cli.json),
+        Commands::Compare { a, b } => cmd_compare(a, b, cli.json),
+        Commands::Reconcile { a, b, strategy } => cmd_reconcile(a, b, strategy, cli.json),
+        Commands::Affected {
+            symbol,
+            depth,
+            cross_repo: false,
+            tests_only,
+            public_only,
+            group_by,
+        } => cmd_affected(
+            symbol.unwrap_or_default(),
+            depth,
+            tests_only,
+            public_only,
+            group_by,
+            cli.json,
+        ),
+        Commands::Affected {
+            symbol,
+            cross_repo: true,
+            ..
+        } => cmd_affected_cross_repo(symbol, cli.json),
+        Commands::CheckCallers { symbol } => cmd_check_callers(symbol, cli.json),
+        Commands::Preflight { paths, changes } => cmd_preflight(paths, changes, cli.json),
+        Commands::Schema { r#type } => cmd_schema(r#type, cli.json),
+        Commands::Test {
+            action:
+                TestAction::Run {
+                    affected_only,
+                    invariant,
+                    at,
+                },
+        } => match at {
+            Some(rev) => cmd_test_run_at(&rev, invariant, cli.json),
+            None => cmd_test_run(affected_only, invariant, cli.json),
+        },
+        Commands::Test {
+            action: TestAction::Flaky,
+        } => cmd_test_flaky(cli.json),
+        Commands::Test {
+            action:
+                TestAction::Scaffold {
+                    file,
+                    force,
+                    dry_run,
+                },
+        } => cmd_test_scaffold(&file, force, dry_run, cli.json),
+        Commands::Triage { log, ci } => cmd_triage(log, ci, cli.json),
+        Commands::WhoTouched { path, since } => cmd_who_touched(path, since, cli.json),
+        Commands::Validate {
+            fix: _,
+            at: Some(rev),
+            merge_base,
+        } => cmd_validate_at(&rev, merge_base, cli.json),
+        Commands::Validate { fix, at: None, .. } => cmd_validate(fix, cli.json),
+        Commands::Suggest { goal: None } => cmd_suggest(cli.json),
+        Commands::Suggest { goal: Some(goal) } => cmd_suggest_for(goal, cli.json),
+        Commands::Run { name, args } => cmd_run(name, args, cli.json),
+        Commands::Skill { command } => cmd_skill(command, cli.json),
+        Commands::CliSpec { command } => cmd_cli_spec(command),
+        Commands::Quickstart => cmd_quickstart(cli.json),
+        Commands::Graph {
+            format,
+            limit,
+            all,
+            kind,
+        } => cmd_graph(format, limit, all, &kind, cli.json),
+        Commands::Ui { limit, interval } => cmd_ui(limit, interval, cli.json),
+        Commands::Stats { usage: true, .. } => cmd_stats_usage(cli.json),
+        Commands::Stats { storage: true, .. } => cmd_stats_storage(cli.json),
+        Commands::Stats { changes, top, .. } => cmd_stats(changes, top, cli.json),
+        Commands::Todos { new_only } => cmd_todos(new_only, cli.json),
+        Commands::Dupes {
+            threshold,
+            min_lines,
+            against_change,
+        } => cmd_dupes(threshold, min_lines, against_change, cli.json),
+        Commands::Gc {
+            aggressive,
+            dry_run,
+        } => cmd_gc(aggressive, dry_run, cli.json),
+        Commands::Replay { action } => match action {
+            ReplayAction::Export { session, out } => cmd_replay_export(session, out, cli.json),
+            ReplayAction::Run { script, dry_run } => cmd_replay_run(script, dry_run, cli.json),
+        },
+        Commands::Series { action } => match action {
+            SeriesAction::Export { range, out } => cmd_series_export(range, out, cli.json),
+            SeriesAction::Import { path, onto } => cmd_series_import(path, onto, cli.json),
+        },
+        Commands::Knowledge { action } => match action {
+            KnowledgeAction::Export { out } => cmd_knowledge_export(out, cli.json),
+            KnowledgeAction::Import { bundle, dry_run } => {
+                cmd_knowledge_import(bundle, dry_run, cli.json)
+            }
+        },
+        Commands::Intent { action } => cmd_intent(action, cli.json),
+        Commands::Approvals { action } => cmd_approvals(action, cli.json),
+        Commands::Confirm {
+            id,
+            approve,
+            deny: _,
+            by,
+            reason,
+        } => cmd_confirm(id, approve, by, reason, cli.json),
+        Commands::Note { action } => cmd_note(action, cli.json),
+        Commands::Qa { action } => match action {
+            QaAction::Record {
+                question,
+                answer,
+                files,
+            } => cmd_qa_record(question, answer, files, cli.json),
+            QaAction::Search { query, limit } => cmd_qa_search(query, limit, cli.json),
+        },
+        Commands::Query { action } => match action {
+            QueryAction::Save {
+                name,
+                command,
+                description,
+            } => cmd_query_save(name, command, description, cli.json),
+            QueryAction::Run { name, args } => cmd_query_run(name, args, cli.json),
+            QueryAction::List => cmd_query_list(cli.json),
+        },
+        Commands::Config { key } => cmd_config(key, cli.json),
     }
 }
 
-fn parse_category(s: &str) -> Result<ChangeCategory> {
-    match s.to_lowercase().as_str() {
-        "feature" | "feat" => Ok(ChangeCategory::Feature),
-        "fix" | "bugfix" => Ok(ChangeCategory::Fix),
-        "perf" | "performance" => Ok(ChangeCategory::Perf),
-        "security" | "sec" => Ok(ChangeCategory::Security),
-        "breaking" => Ok(ChangeCategory::Breaking),
-        "deprecation" | "deprecate" => Ok(ChangeCategory::Deprecation),
-        "chore" => Ok(ChangeCategory::Chore),
-        _ => anyhow::bail!("Unknown category: {}", s),
+/// Name of a state-changing command for the audit log, or None for reads
+fn audited_command(command: &Commands) -> Option<&'static str> {
+    match command {
+        Commands::Init { .. } => Some("init"),
+        Commands::Apply { .. } => Some("apply"),
+        Commands::Edit { .. } => Some("edit"),
+        Commands::Commit { .. } => Some("commit"),
+        Commands::Push { .. } => Some("push"),
+        Commands::Sync { .. } => Some("sync"),
+        Commands::Reword { dry_run: false, .. } => Some("reword"),
+        Commands::Tag { .. } => Some("tag"),
+        Commands::Change {
+            action: ChangeAction::Set { .. },
+        } => Some("change set"),
+        Commands::Change {
+            action: ChangeAction::Edit { .. },
+        } => Some("change edit"),
+        Commands::Change {
+            action: ChangeAction::Backfill { dry_run: false, .. },
+        } => Some("change backfill"),
+        Commands::Change {
+            action: ChangeAction::Notes { set, edit, .. },
+        } if set.is_some() || *edit => Some("change notes"),
+        Commands::Checkpoint {
+            action: CheckpointAction::Create { .. },
+        } => Some("checkpoint create"),
+        Commands::Start {
+            no_checkpoint: false,
+            ..
+        } => Some("start"),
+        Commands::Env {
+            action: EnvAction::Promote { dry_run: false, .. },
+        } => Some("env promote"),
+        Commands::Stash {
+            action: StashAction::Push { .. },
+        } => Some("stash push"),
+        Commands::Stash {
+            action: StashAction::Pop { .. },
+        } => Some("stash pop"),
+        Commands::Stash {
+            action: StashAction::Drop { .. },
+        } => Some("stash drop"),
+        Commands::Test {
+            action: TestAction::Scaffold { dry_run: false, .. },
+        } => Some("test scaffold"),
+        Commands::Rollback { execute: true, .. } => Some("rollback"),
+        Commands::Confirm { .. } => Some("confirm"),
+        Commands::Reconcile { .. } => Some("reconcile"),
+        Commands::Run { name: Some(_), .. } => Some("run"),
+        Commands::Undo { dry_run: false, .. } => Some("undo"),
+        Commands::Validate { fix: true, .. } => Some("validate --fix"),
+        Commands::Knowledge {
+            action: KnowledgeAction::Import { dry_run: false, .. },
+        } => Some("knowledge import"),
+        Commands::Series {
+            action: SeriesAction::Import { .. },
+        } => Some("series import"),
+        Commands::Note {
+            action: NoteAction::Add { .. },
+        } => Some("note add"),
+        Commands::Note {
+            action: NoteAction::Remove { .. },
+        } => Some("note remove"),
+        Commands::Qa {
+            action: QaAction::Record { .. },
+        } => Some("qa record"),
+        Commands::Query {
+            action: QueryAction::Save { .. },
+        } => Some("query save"),
+        _ => None,
     }
 }
 
-/// Check if a symbol is public based on language conventions
-fn is_public_symbol(symbol: &agentjj::symbols::Symbol, lang: agentjj::SupportedLanguage) -> bool {
-    match lang {
-        agentjj::SupportedLanguage::Rust => {
-            // Rust: check for "pub" keyword in signature
-            symbol
-                .signature
-                .as_ref()
-                .map(|sig: &String| sig.contains("pub"))
-                .unwrap_or(false)
+/// Commands that only read the repository. They never set up jj in a plain
+/// git repository, whatever `auto.colocate` says, and are all that read-only
+/// mode allows.
+fn query_command(command: &Commands) -> bool {
+    match command {
+        Commands::Status
+        | Commands::Doctor { .. }
+        | Commands::Orient
+        | Commands::Capabilities
+        | Commands::Start {
+            no_checkpoint: true,
+            ..
         }
-        agentjj::SupportedLanguage::Python => {
-            // Python: underscore prefix means private (convention)
-            !symbol.name.starts_with('_')
+        | Commands::Validate {
+            fix: false,
+            at: None,
+            ..
         }
-        agentjj::SupportedLanguage::JavaScript | agentjj::SupportedLanguage::TypeScript => {
-            // JS/TS: check for "export" keyword in signature
-            symbol
-                .signature
-                .as_ref()
-                .map(|sig: &String| sig.contains("export"))
-                .unwrap_or(true)
+        | Commands::Quickstart
+        | Commands::Config { .. }
+        | Commands::Triage { .. }
+        | Commands::WhoTouched { .. }
+        | Commands::Run { name: None, .. }
+        | Commands::Gc { dry_run: true, .. }
+        | Commands::Undo { dry_run: true, .. }
+        | Commands::Rollback { execute: false, .. }
+        | Commands::Reword { dry_run: true, .. }
+        | Commands::Read { .. }
+        | Commands::Symbol { .. }
+        | Commands::Context { .. }
+        | Commands::Bulk { .. }
+        | Commands::Files { .. }
+        | Commands::Export { .. }
+        | Commands::Diff { .. }
+        | Commands::Compare { .. }
+        | Commands::Affected { .. }
+        | Commands::CheckCallers { .. }
+        | Commands::Preflight { .. }
+        | Commands::Schema { .. }
+        | Commands::Suggest { .. }
+        | Commands::Skill { .. }
+        | Commands::CliSpec { .. }
+        | Commands::Graph { .. }
+        | Commands::Ui { .. }
+        | Commands::Stats { .. }
+        | Commands::Todos { .. }
+        | Commands::Dupes { .. } => true,
+        Commands::Manifest { action } => {
+            matches!(
+                action,
+                ManifestAction::Show
+                    | ManifestAction::Validate
+                    | ManifestAction::Audit
+                    | ManifestAction::Presets { .. }
+            )
         }
+        Commands::Change { action } => matches!(
+            action,
+            ChangeAction::Show { .. }
+                | ChangeAction::List { .. }
+                | ChangeAction::Verify { .. }
+                | ChangeAction::Backfill { dry_run: true, .. }
+                | ChangeAction::Notes {
+                    set: None,
+                    edit: false,
+                    ..
+                }
+        ),
+        Commands::Checkpoint { action } => matches!(
+            action,
+            CheckpointAction::List | CheckpointAction::Status { .. }
+        ),
+        Commands::Env { action } => matches!(
+            action,
+            EnvAction::List | EnvAction::Promote { dry_run: true, .. }
+        ),
+        Commands::Stash { action } => matches!(action, StashAction::List { .. }),
+        Commands::Intent { .. } => true,
+        Commands::Approvals { action } => matches!(action, ApprovalsAction::List { .. }),
+        Commands::Note { action } => matches!(action, NoteAction::List { .. }),
+        Commands::Qa { action } => matches!(action, QaAction::Search { .. }),
+        Commands::Query { action } => !matches!(action, QueryAction::Save { .. }),
+        Commands::Replay { action } => matches!(action, ReplayAction::Export { .. }),
+        Commands::Series { action } => matches!(action, SeriesAction::Export { .. }),
+        Commands::Pr { action } => matches!(action, PrAction::Status { .. }),
+        Commands::Knowledge { action } => matches!(
+            action,
+            KnowledgeAction::Export { .. } | KnowledgeAction::Import { dry_run: true, .. }
+        ),
+        Commands::Test { action } => matches!(
+            action,
+            TestAction::Flaky | TestAction::Scaffold { dry_run: true, .. }
+        ),
+        _ => false,
     }
 }
 
-fn cmd_context(path: String, json: bool) -> Result<()> {
-    // Parse path: "path/to/file.ext::symbol_name"
-    let (file_path, symbol_name) = if let Some(idx) = path.find("::") {
-        (&path[..idx], &path[idx + 2..])
-    } else {
-        anyhow::bail!("Symbol path must be path/to/file::symbol_name (e.g., src/main.rs::main)");
+/// The root of the repository here and its manifest, without loading the
+/// jj repository itself
+fn local_manifest() -> Option<(std::path::PathBuf, Manifest)> {
+    let cwd = std::env::current_dir().ok()?;
+    let root = cwd
+        .ancestors()
+        .find(|dir| dir.join(".jj").exists() || dir.join(".git").exists())?;
+    let manifest = Manifest::load_from_repo(root).ok()?;
+    Some((root.to_path_buf(), manifest))
+}
+
+/// Whether the manifest sets `[permissions] default_mode = "read-only"`
+fn manifest_read_only(local: Option<&(std::path::PathBuf, Manifest)>) -> bool {
+    local
+        .is_some_and(|(_, m)| m.permissions.default_mode == agentjj::manifest::AccessMode::ReadOnly)
+}
+
+/// Append this invocation to the session's audit log (best-effort: a
+/// failure to record never fails the command itself)
+fn record_audit(command: &str, result: &Result<()>) {
+    let Ok(repo) = Repo::discover() else {
+        return;
+    };
+    let record = agentjj::audit::AuditRecord {
+        session: agentjj::audit::current_session(),
+        timestamp: chrono_lite_now(),
+        command: command.to_string(),
+        args: std::env::args().skip(1).filter(|a| a != "--json").collect(),
+        success: result.is_ok(),
+        error: result.as_ref().err().map(|e| e.to_string()),
+        inputs: agentjj::audit::take_inputs(),
+    };
+    if let Err(e) = agentjj::audit::AuditLog::open(repo.root()).append(&record) {
+        eprintln!("warning: failed to write audit record: {}", e);
+    }
+}
+
+/// Prune `.agent` subsystems over their manifest [storage] caps, oldest
+/// files first, and log what went to the audit trail. Best-effort, like
+/// the audit record itself. Uses the manifest read before the command ran.
+fn enforce_storage_caps(root: &std::path::Path, manifest: &Manifest) {
+    if manifest.storage.is_empty() {
+        return;
+    }
+    let pruned = agentjj::storage::enforce(root, &manifest.storage);
+    if pruned.is_empty() {
+        return;
+    }
+    let record = agentjj::audit::AuditRecord {
+        session: agentjj::audit::current_session(),
+        timestamp: chrono_lite_now(),
+        command: agentjj::storage::AUTO_PRUNE_COMMAND.to_string(),
+        args: Vec::new(),
+        success: true,
+        error: None,
+        inputs: serde_json::to_value(&pruned).ok(),
     };
+    if let Err(e) = agentjj::audit::AuditLog::open(root).append(&record) {
+        eprintln!("warning: failed to write audit record: {}", e);
+    }
+}
 
-    let file_path_obj = std::path::Path::new(file_path);
+fn cmd_init(
+    name: Option<String>,
+    colocate: bool,
+    commit: bool,
+    with_skill: bool,
+    json: bool,
+) -> Result<()> {
+    if colocate {
+        // The explicit way to set up jj in a git repository
+        agentjj::repo::set_auto_colocate(true);
+    }
+    let mut repo = Repo::discover()?;
 
-    // Detect language
-    let lang = agentjj::SupportedLanguage::from_path(file_path_obj)
-        .ok_or_else(|| anyhow::anyhow!("Unsupported file type: {}", file_path))?;
+    let created = !repo.has_manifest();
+    let repo_name = if created {
+        let repo_name = name.unwrap_or_else(|| {
+            repo.root()
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or("unnamed")
+                .to_string()
+        });
 
-    // Read file content
-    let content = if file_path_obj.is_absolute() {
-        std::fs::read_to_string(file_path)?
+        let manifest = Manifest {
+            repo: agentjj::manifest::RepoInfo {
+                name: repo_name.clone(),
+                description: String::new(),
+                languages: Vec::new(),
+                vcs: "jj".to_string(),
+            },
+            ..Default::default()
+        };
+
+        let manifest_path = repo.root().join(Manifest::DEFAULT_PATH);
+        if let Some(parent) = manifest_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&manifest_path, manifest.to_toml()?)?;
+
+        // Create .agent/.gitignore to exclude local state but track manifest
+        let agent_gitignore = repo.root().join(".agent/.gitignore");
+        let gitignore_content = "# Agent-local state (not shared)\n\
+                                 checkpoints/\n\
+                                 changes/\n\
+                                 audit/\n\
+                                 stash/\n\
+                                 tests/\n\
+                                 secrets.env\n";
+        std::fs::write(&agent_gitignore, gitignore_content)?;
+        repo_name
     } else {
-        let mut repo = Repo::discover()?;
-        repo.read_file(file_path, None)?
+        repo.manifest()?.repo.name.clone()
     };
 
-    // Get minimal context
-    let context = agentjj::symbols::get_symbol_context(&content, lang, symbol_name)?;
-
-    match context {
-        Some(ctx) => {
-            if json {
-                println!("{}", serde_json::to_string_pretty(&ctx)?);
-            } else {
-                println!("# {}", ctx.name);
-                println!("kind: {:?}", ctx.kind);
-                if let Some(sig) = &ctx.signature {
-                    println!("\n```");
-                    println!("{}", sig);
-                    println!("```");
-                }
-                if let Some(doc) = &ctx.docstring {
-                    println!("\n{}", doc);
-                }
-                if !ctx.imports_needed.is_empty() {
-                    println!("\nimports needed:");
-                    for imp in &ctx.imports_needed {
-                        println!("  {}", imp);
-                    }
-                }
+    // AGENTS.md: created, or the pointer appended to an existing one
+    let skill_pointer = if with_skill {
+        let path = repo.root().join(agentjj::skill::POINTER_PATH);
+        let existing = std::fs::read_to_string(&path).unwrap_or_default();
+        match agentjj::skill::add_pointer(&existing, &repo_name) {
+            Some(content) => {
+                std::fs::write(&path, content)?;
+                Some(if existing.is_empty() {
+                    "created"
+                } else {
+                    "updated"
+                })
             }
+            None => Some("unchanged"),
         }
-        None => {
-            if json {
-                println!(
-                    r#"{{"error": "symbol not found", "name": "{}"}}"#,
-                    symbol_name
-                );
+    } else {
+        None
+    };
+
+    // Only the files init owns; anything else in the working copy stays put
+    let committed = if commit {
+        let mut paths = vec![
+            Manifest::DEFAULT_PATH.to_string(),
+            ".agent/.gitignore".to_string(),
+        ];
+        if with_skill {
+            paths.push(agentjj::skill::POINTER_PATH.to_string());
+        }
+        repo.snapshot()?;
+        let current = repo.current_change_id()?;
+        let changed = repo.changed_files(&current).unwrap_or_default();
+        paths.retain(|p| changed.contains(p));
+        if paths.is_empty() {
+            None
+        } else {
+            let message = if created {
+                "config: add agentjj manifest"
             } else {
-                println!("Symbol '{}' not found in {}", symbol_name, file_path);
+                "config: commit agentjj manifest"
+            };
+            let result = repo.commit_working_copy(agentjj::repo::CommitOptions {
+                message: message.to_string(),
+                no_new: false,
+                run_invariants: false,
+                change_type: ChangeType::Config,
+                categories: Vec::new(),
+                breaking: false,
+                paths: Some(paths),
+                fields: Default::default(),
+                depends_on: Vec::new(),
+            })?;
+            Some((message, result))
+        }
+    } else {
+        None
+    };
+
+    if json {
+        let mut output = serde_json::json!({
+            "status": if created { "created" } else { "exists" },
+            "name": repo_name,
+            "path": Manifest::DEFAULT_PATH,
+        });
+        if created {
+            output["gitignore"] = ".agent/.gitignore".into();
+        }
+        if let Some(status) = skill_pointer {
+            output["skill"] = serde_json::json!({
+                "path": agentjj::skill::POINTER_PATH,
+                "status": status,
+            });
+        }
+        if commit {
+            output["committed"] = match &committed {
+                Some((message, result)) => serde_json::json!({
+                    "change_id": result.change_id,
+                    "commit": result.commit_id,
+                    "message": message,
+                    "files_changed": result.files_changed,
+                }),
+                None => serde_json::Value::Bool(false),
+            };
+        }
+        println!("{}", json_string(&output)?);
+    } else {
+        if created {
+            println!("Initialized agentjj for '{}'", repo_name);
+            println!("Created .agent/manifest.toml");
+            println!("Created .agent/.gitignore (excludes local state)");
+        } else {
+            println!("Manifest already exists at .agent/manifest.toml");
+        }
+        match skill_pointer {
+            Some("unchanged") => println!(
+                "{} already points at agentjj skill",
+                agentjj::skill::POINTER_PATH
+            ),
+            Some(status) => println!(
+                "{} {} (points agents at agentjj skill)",
+                if status == "created" {
+                    "Created"
+                } else {
+                    "Updated"
+                },
+                agentjj::skill::POINTER_PATH
+            ),
+            None => {}
+        }
+        match &committed {
+            Some((message, result)) => {
+                println!("Committed: {}", message);
+                println!("  Change:  {}", result.change_id);
+                println!("  Commit:  {}", result.commit_id);
             }
-            std::process::exit(1);
+            None if commit => println!("Nothing to commit: agentjj files are already committed"),
+            None => {}
         }
     }
 
     Ok(())
 }
 
-#[allow(clippy::too_many_arguments)]
-fn cmd_commit(
-    message: String,
-    no_new: bool,
-    change_type_str: String,
-    category_str: Option<String>,
-    no_invariants: bool,
-    breaking: bool,
-    paths: Option<Vec<String>>,
-    json: bool,
-) -> Result<()> {
+fn cmd_status(json: bool) -> Result<()> {
     let mut repo = Repo::discover()?;
 
-    let change_type = parse_change_type(&change_type_str)?;
-    let category = match category_str {
-        Some(ref c) => Some(parse_category(c)?),
-        None => None,
-    };
-
-    let opts = agentjj::repo::CommitOptions {
-        message: message.clone(),
-        no_new,
-        run_invariants: !no_invariants,
-        change_type,
-        category,
-        breaking,
-        paths,
+    let change_id = repo
+        .current_change_id()
+        .unwrap_or_else(|_| "unknown".into());
+    let operation_id = repo
+        .current_operation_id()
+        .unwrap_or_else(|_| "unknown".into());
+    let files = repo.changed_files(&change_id).unwrap_or_default();
+    let has_manifest = repo.has_manifest();
+    // The working copy only becomes immutable when it was pushed as-is
+    let immutable = match (repo.resolve_revision("@"), repo.immutable_commits()) {
+        (Ok((_, commit_id)), Ok(set)) => set.contains(&commit_id),
+        _ => false,
     };
 
-    let result = repo.commit_working_copy(opts)?;
+    // Try to load typed change for current change
+    let typed_change = repo.get_typed_change(&change_id).ok();
+    // Colocated only: git commands may have moved refs under jj
+    let refs = repo.ref_sync_report().ok().flatten();
 
     if json {
-        let invariant_map: serde_json::Value = result
-            .invariants
-            .iter()
-            .map(|(k, v)| {
-                (
-                    k.clone(),
-                    serde_json::to_value(v).unwrap_or(serde_json::json!("unknown")),
-                )
-            })
-            .collect::<serde_json::Map<String, serde_json::Value>>()
-            .into();
-
-        let output = serde_json::json!({
-            "committed": true,
-            "change_id": result.change_id,
-            "commit": result.commit_id,
-            "message": message,
-            "files_changed": result.files_changed,
-            "invariants": invariant_map,
+        let mut status = serde_json::json!({
+            "change_id": change_id,
+            "operation_id": operation_id,
+            "files_changed": files,
+            "has_manifest": has_manifest,
+            "immutable": immutable,
+            "typed_change": typed_change,
         });
-        println!("{}", serde_json::to_string_pretty(&output)?);
+        if let Some(refs) = &refs {
+            status["refs"] = serde_json::to_value(refs)?;
+        }
+        println!("{}", json_string(&status)?);
     } else {
-        println!("Committed: {}", message);
-        println!("  Change:  {}", result.change_id);
-        println!("  Commit:  {}", result.commit_id);
-        if !result.files_changed.is_empty() {
-            println!("  Files:   {}", result.files_changed.len());
-            for f in &result.files_changed {
-                println!("    {}", f);
+        println!("Change:    {}", &change_id[..12.min(change_id.len())]);
+        println!(
+            "Operation: {}...",
+            &operation_id[..16.min(operation_id.len())]
+        );
+        println!("Manifest:  {}", if has_manifest { "yes" } else { "no" });
+        if immutable {
+            println!("Immutable: yes (already pushed; start a new change to keep working)");
+        }
+        if let Some(refs) = refs.filter(|r| !r.in_sync) {
+            println!(
+                "Refs:      {} out of sync with git (see `agentjj doctor --sync-check`)",
+                refs.divergences.len()
+            );
+        }
+
+        if !files.is_empty() {
+            println!("\nChanged files:");
+            for f in &files {
+                println!("  {}", f);
             }
         }
-        if !result.invariants.is_empty() {
-            println!("  Invariants:");
-            for (name, status) in &result.invariants {
-                println!("    {}: {:?}", name, status);
+
+        if let Some(tc) = typed_change {
+            println!("\nTyped change:");
+            println!("  type:   {:?}", tc.change_type);
+            println!("  intent: {}", tc.intent);
+            if tc.breaking {
+                println!("  ⚠️  BREAKING CHANGE");
             }
         }
     }
@@ -1105,1567 +2657,10040 @@ fn cmd_commit(
     Ok(())
 }
 
-fn cmd_tag(
-    name: String,
-    message: Option<String>,
-    force: bool,
-    push: bool,
-    json: bool,
-) -> Result<()> {
-    let repo = Repo::discover()?;
-
-    // Build tag command
-    let mut args = vec!["tag".to_string()];
-
-    if force {
-        args.push("-f".to_string());
-    }
-
-    if let Some(ref msg) = message {
-        args.push("-a".to_string());
-        args.push("-m".to_string());
-        args.push(msg.clone());
-    }
-
-    args.push(name.clone());
-
-    // Create the tag
-    let tag_output = std::process::Command::new("git")
-        .current_dir(repo.root())
-        .args(&args)
-        .output()
-        .map_err(|e| anyhow::anyhow!("Failed to run git tag: {}", e))?;
-
-    if !tag_output.status.success() {
-        let stderr = String::from_utf8_lossy(&tag_output.stderr);
-        anyhow::bail!("Failed to create tag: {}", stderr);
-    }
-
-    // Push tag if requested
-    if push {
-        let mut push_args = vec!["push".to_string(), "origin".to_string()];
-        if force {
-            push_args.push("--force".to_string());
-        }
-        push_args.push(name.clone());
-
-        let push_output = std::process::Command::new("git")
-            .current_dir(repo.root())
-            .args(&push_args)
-            .output()
-            .map_err(|e| anyhow::anyhow!("Failed to push tag: {}", e))?;
+/// One `doctor` check and what it found
+#[derive(serde::Serialize)]
+struct DoctorCheck {
+    name: &'static str,
+    ok: bool,
+    message: String,
+}
 
-        if !push_output.status.success() {
-            let stderr = String::from_utf8_lossy(&push_output.stderr);
-            anyhow::bail!("Failed to push tag: {}", stderr);
+fn cmd_doctor(sync_check: bool, json: bool) -> Result<()> {
+    let mut repo = Repo::discover()?;
+    let mut checks = Vec::new();
+    if !sync_check {
+        checks.push(match repo.current_change_id() {
+            Ok(change_id) => DoctorCheck {
+                name: "workspace",
+                ok: true,
+                message: format!("working copy at {}", &change_id[..12.min(change_id.len())]),
+            },
+            Err(e) => DoctorCheck {
+                name: "workspace",
+                ok: false,
+                message: e.to_string(),
+            },
+        });
+        if repo.has_manifest() {
+            checks.push(match repo.manifest() {
+                Ok(_) => DoctorCheck {
+                    name: "manifest",
+                    ok: true,
+                    message: "parses".to_string(),
+                },
+                Err(e) => DoctorCheck {
+                    name: "manifest",
+                    ok: false,
+                    message: e.to_string(),
+                },
+            });
         }
     }
+    let refs = repo.ref_sync_report()?;
+    checks.push(match &refs {
+        None => DoctorCheck {
+            name: "refs",
+            ok: true,
+            message: "no colocated git repository".to_string(),
+        },
+        Some(refs) if refs.in_sync => DoctorCheck {
+            name: "refs",
+            ok: true,
+            message: "git branches and HEAD match jj".to_string(),
+        },
+        Some(refs) => DoctorCheck {
+            name: "refs",
+            ok: false,
+            message: format!(
+                "{} ref(s) out of sync; run `{}`",
+                refs.divergences.len(),
+                refs.fix.as_deref().unwrap_or("agentjj sync")
+            ),
+        },
+    });
+    let healthy = checks.iter().all(|c| c.ok);
 
     if json {
-        let result = serde_json::json!({
-            "tag": name,
-            "pushed": push,
-            "forced": force,
-        });
-        println!("{}", serde_json::to_string_pretty(&result)?);
-    } else if push {
-        println!("✓ Tagged and pushed: {}", name);
+        let mut output = serde_json::json!({ "healthy": healthy, "checks": checks });
+        if let Some(refs) = &refs {
+            output["refs"] = serde_json::to_value(refs)?;
+        }
+        println!("{}", json_string(&output)?);
     } else {
-        println!("✓ Tagged: {}", name);
+        for check in &checks {
+            let mark = if check.ok { "✓" } else { "✗" };
+            println!("{} {:<10} {}", mark, check.name, check.message);
+        }
+        for d in refs.iter().flat_map(|r| &r.divergences) {
+            let short = |id: &Option<String>| match id {
+                Some(id) => id[..12.min(id.len())].to_string(),
+                None => "-".to_string(),
+            };
+            println!(
+                "  {:<20} {}: git {} / jj {}{}",
+                d.name,
+                d.kind.as_str(),
+                short(&d.git),
+                short(&d.jj),
+                d.moved_by
+                    .map(|by| format!(" (moved by {})", by))
+                    .unwrap_or_default()
+            );
+        }
+    }
+    if !healthy {
+        exit_with(exit::PRECONDITION, Some("unhealthy"));
     }
-
     Ok(())
 }
 
-fn cmd_push(
-    branch: Option<String>,
-    _change: Option<String>,
-    create_pr: bool,
-    title: Option<String>,
-    body: Option<String>,
-    target: String,
-    json: bool,
-) -> Result<()> {
-    let repo = Repo::discover()?;
-
-    // Use git directly for colocated repos (which is our primary mode)
-    let branch_name = branch.unwrap_or_else(|| "main".to_string());
-
-    // Get the commit to push (HEAD in git terms)
-    let rev_parse = std::process::Command::new("git")
-        .current_dir(repo.root())
-        .args(["rev-parse", "HEAD"])
-        .output()
-        .map_err(|e| anyhow::anyhow!("Failed to run git: {}", e))?;
-
-    if !rev_parse.status.success() {
-        anyhow::bail!("Not a git repository or no commits");
-    }
-
-    let _commit_sha = String::from_utf8_lossy(&rev_parse.stdout)
-        .trim()
-        .to_string();
-
-    // Push to remote using git
-    let push_output = std::process::Command::new("git")
-        .current_dir(repo.root())
-        .args(["push", "origin", &format!("HEAD:{}", branch_name)])
-        .output()?;
-
-    if !push_output.status.success() {
-        let stderr = String::from_utf8_lossy(&push_output.stderr);
-        anyhow::bail!("Push failed: {}", stderr);
-    }
-
-    let mut result = serde_json::json!({
-        "pushed": true,
-        "branch": branch_name,
-    });
-
-    if !json {
-        println!("✓ Pushed to {}", branch_name);
-    }
-
-    // Create PR if requested
-    if create_pr {
-        let pr_title = title.ok_or_else(|| anyhow::anyhow!("--title required for PR creation"))?;
-
-        let mut gh_args = vec![
-            "pr".to_string(),
-            "create".to_string(),
-            "--head".to_string(),
-            branch_name.clone(),
-            "--base".to_string(),
-            target.clone(),
-            "--title".to_string(),
-            pr_title.clone(),
-        ];
+fn cmd_sync(refs_only: bool, json: bool) -> Result<()> {
+    let mut repo = Repo::discover()?;
+    let outcome = repo.sync_refs(refs_only)?;
 
-        if let Some(b) = &body {
-            gh_args.push("--body".to_string());
-            gh_args.push(b.clone());
+    if json {
+        println!("{}", json_string(&outcome)?);
+    } else {
+        if outcome.fixed.is_empty() {
+            println!("Already in sync");
         }
+        for d in &outcome.fixed {
+            println!("✓ {} ({})", d.name, d.kind.as_str());
+        }
+        if let Some(onto) = &outcome.rebased_onto {
+            println!("Working copy rebased onto {}", &onto[..12.min(onto.len())]);
+        }
+        for failed in &outcome.failed {
+            println!("✗ could not export {}", failed);
+        }
+        for d in &outcome.remaining {
+            println!("! {} still {}", d.name, d.kind.as_str());
+        }
+    }
+    Ok(())
+}
 
-        let pr_output = std::process::Command::new("gh")
-            .current_dir(repo.root())
-            .args(&gh_args)
+fn cmd_manifest(action: ManifestAction, json: bool) -> Result<()> {
+    match action {
+        ManifestAction::Show => {
+            let mut repo = Repo::discover()?;
+            let manifest = repo.manifest()?;
+            if json {
+                println!("{}", json_string(manifest)?);
+            } else {
+                println!("{}", manifest.to_toml()?);
+            }
+        }
+        ManifestAction::Validate => {
+            let mut repo = Repo::discover()?;
+            match repo.manifest() {
+                Ok(m) => {
+                    if json {
+                        println!(r#"{{"valid": true, "name": "{}"}}"#, m.repo.name);
+                    } else {
+                        println!("✓ Manifest is valid");
+                        println!("  name: {}", m.repo.name);
+                        println!("  invariants: {}", m.invariants.len());
+                    }
+                }
+                Err(e) => {
+                    if json {
+                        println!(
+                            "{}",
+                            serde_json::json!({ "valid": false, "error": e.to_string() })
+                        );
+                    } else {
+                        println!("✗ Manifest is invalid: {}", e);
+                    }
+                    exit_with(exit::POLICY, Some("manifest_invalid"));
+                }
+            }
+        }
+        ManifestAction::Audit => {
+            use agentjj::manifest_audit::{audit, Severity};
+
+            let mut repo = Repo::discover()?;
+            let root = repo.root().to_path_buf();
+            let manifest = repo.manifest()?.clone();
+            // Gitignored files (build output, vendored deps) don't count as matches
+            let filters = agentjj::repo::WalkFilters {
+                gitignore: true,
+                ..Default::default()
+            };
+            let files: Vec<String> = agentjj::repo::walk_files(&root, filters)
+                .filter_map(|path| {
+                    let rel = path.strip_prefix(&root).ok()?;
+                    Some(rel.to_string_lossy().replace('\\', "/"))
+                })
+                .collect();
+            let path_var = std::env::var("PATH").ok();
+            let report = audit(&manifest, &root, &files, path_var.as_deref());
+
+            if json {
+                println!("{}", json_string(&report)?);
+            } else if report.findings.is_empty() {
+                println!("✓ Every manifest entry matches the repository");
+            } else {
+                for finding in &report.findings {
+                    let mark = match finding.severity {
+                        Severity::Error => "✗",
+                        Severity::Warn => "!",
+                        Severity::Info => "·",
+                    };
+                    println!("{} {}: {}", mark, finding.entry, finding.message);
+                    if let Some(patch) = &finding.patch {
+                        match (&patch.value, &patch.replacement) {
+                            (Some(old), Some(new)) => {
+                                println!("    {} {}: {} -> {}", patch.op, patch.key, old, new)
+                            }
+                            (Some(old), None) => {
+                                println!("    {} {}: {}", patch.op, patch.key, old)
+                            }
+                            (None, Some(new)) => {
+                                println!("    {} {}: {}", patch.op, patch.key, new)
+                            }
+                            (None, None) => {}
+                        }
+                    }
+                }
+            }
+
+            if report.counts.contains_key(&Severity::Error) {
+                exit_with(exit::POLICY, Some("manifest_drift"));
+            }
+            let warnings: Vec<String> = report
+                .findings
+                .iter()
+                .filter(|f| f.severity == Severity::Warn)
+                .map(|f| f.message.clone())
+                .collect();
+            exit_if_strict_warnings(&warnings);
+        }
+        ManifestAction::Init { name } => {
+            return cmd_init(Some(name), false, false, false, json);
+        }
+        ManifestAction::Presets {
+            action: PresetAction::List,
+        } => {
+            // Without a readable manifest nothing is enabled or overridden
+            let manifest = Repo::discover()
+                .ok()
+                .and_then(|mut repo| repo.manifest().ok().cloned());
+            let presets: Vec<serde_json::Value> = agentjj::presets::PRESETS
+                .iter()
+                .map(|preset| {
+                    let enabled = manifest
+                        .as_ref()
+                        .is_some_and(|m| m.preset.iter().any(|p| p == preset.name));
+                    let invariants: Vec<serde_json::Value> = preset
+                        .invariants
+                        .iter()
+                        .map(|(name, cmd)| {
+                            let overridden = enabled
+                                && manifest
+                                    .as_ref()
+                                    .and_then(|m| m.invariants.get(*name))
+                                    .is_some_and(|inv| inv.command() != *cmd);
+                            serde_json::json!({
+                                "name": name,
+                                "cmd": cmd,
+                                "overridden": overridden,
+                            })
+                        })
+                        .collect();
+                    serde_json::json!({
+                        "name": preset.name,
+                        "description": preset.description,
+                        "enabled": enabled,
+                        "invariants": invariants,
+                    })
+                })
+                .collect();
+            if json {
+                println!(
+                    "{}",
+                    json_string(&serde_json::json!({ "presets": presets }))?
+                );
+            } else {
+                for preset in &presets {
+                    println!(
+                        "{}{} - {}",
+                        preset["name"].as_str().unwrap_or(""),
+                        if preset["enabled"] == true {
+                            " (enabled)"
+                        } else {
+                            ""
+                        },
+                        preset["description"].as_str().unwrap_or("")
+                    );
+                    for invariant in preset["invariants"].as_array().into_iter().flatten() {
+                        println!(
+                            "  {:14} {}{}",
+                            invariant["name"].as_str().unwrap_or(""),
+                            invariant["cmd"].as_str().unwrap_or(""),
+                            if invariant["overridden"] == true {
+                                " (overridden)"
+                            } else {
+                                ""
+                            }
+                        );
+                    }
+                }
+                println!("\nEnable with `preset = [\"rust-basic\"]` at the top of the manifest");
+            }
+        }
+    }
+    Ok(())
+}
+
+fn cmd_change(action: ChangeAction, json: bool) -> Result<()> {
+    let mut repo = Repo::discover()?;
+
+    match action {
+        ChangeAction::Show { change_id } => {
+            let change = repo.get_typed_change(&change_id)?;
+            if json {
+                println!("{}", json_string(&change)?);
+            } else {
+                println!("{}", change.to_toml()?);
+            }
+        }
+        ChangeAction::List {
+            r#type,
+            category,
+            breaking,
+            since,
+            until,
+            author,
+            path,
+            sort,
+            limit,
+        } => {
+            let query = agentjj::change::ChangeQuery {
+                change_type: r#type.map(|t| parse_change_type(&t)).transpose()?,
+                categories: parse_categories(&mut repo, &category)?,
+                breaking,
+                since: since.map(|s| parse_change_time(&s)).transpose()?,
+                until: until.map(|s| parse_change_time(&s)).transpose()?,
+                author,
+                path,
+                sort: serde_json::from_value(serde_json::json!(sort))?,
+                limit,
+            };
+            let mut index = agentjj::change::ChangeIndex::load_from_repo(repo.root())?;
+            // Metadata from before changes were stamped: use the commit's time
+            if index.all().iter().any(|c| c.created_at.is_none()) {
+                let log = repo.log_entries(usize::MAX, true).unwrap_or_default();
+                index.backfill(|id| {
+                    let entry = log.iter().find(|e| id.starts_with(&e.change_id))?;
+                    Some((entry.timestamp.clone()?, entry.author.clone()?))
+                });
+            }
+            let changes = index.query(&query);
+
+            if json {
+                println!("{}", json_string(&changes)?);
+            } else if changes.is_empty() {
+                println!("No typed changes found");
+            } else {
+                for change in changes {
+                    println!(
+                        "{} [{:?}] {}",
+                        change.change_id, change.change_type, change.intent
+                    );
+                    if let Some(created) = &change.created_at {
+                        let churn = change
+                            .metrics
+                            .as_ref()
+                            .map(|m| format!(", +{} -{}", m.additions, m.deletions))
+                            .unwrap_or_default();
+                        println!(
+                            "    {} by {}, {} file(s){}",
+                            created,
+                            change.author.as_deref().unwrap_or("unknown"),
+                            change.files.len(),
+                            churn
+                        );
+                    }
+                    if !change.fields.is_empty() {
+                        let fields: Vec<String> = change
+                            .fields
+                            .iter()
+                            .map(|(name, value)| format!("{}={}", name, value))
+                            .collect();
+                        println!("    {}", fields.join(" "));
+                    }
+                }
+            }
+        }
+        ChangeAction::Set {
+            change_id,
+            intent,
+            r#type,
+            category,
+            breaking,
+            fields,
+            depends_on,
+        } => {
+            // Resolve @ to actual jj change ID
+            let cid = match change_id {
+                Some(id) if id != "@" => id,
+                _ => repo.current_change_id()?,
+            };
+            let change_type = resolve_change_type(&mut repo, r#type)?;
+            let depends_on = resolve_dependencies(&mut repo, &depends_on)?;
+            let categories = parse_categories(&mut repo, &category)?;
+            let fields = parse_fields(&fields)?;
+            if let Ok(manifest) = repo.manifest() {
+                manifest.check_change_fields(change_type, &categories, &fields)?;
+            }
+
+            let mut change = TypedChange::new(cid.clone(), change_type, intent)
+                .with_fields(fields)
+                .with_depends_on(depends_on);
+            // Replacing a record keeps when and by whom it was first made, and its notes
+            if let Ok(existing) = repo.get_typed_change(&cid) {
+                change.created_at = existing.created_at.or(change.created_at);
+                change.author = existing.author.or(change.author);
+                change.notes = existing.notes;
+            }
+            for category in categories {
+                change = change.with_category(category);
+            }
+            if breaking {
+                change = change.breaking();
+            }
+
+            repo.save_typed_change(&change)?;
+
+            if json {
+                println!("{}", json_string(&change)?);
+            } else {
+                println!("Saved typed change for {}", cid);
+            }
+        }
+        ChangeAction::Edit {
+            change_id,
+            intent,
+            r#type,
+            category,
+            breaking,
+            add_files,
+            remove_files,
+            add_depends_on,
+            remove_depends_on,
+            by,
+        } => {
+            let cid = if change_id == "@" {
+                repo.current_change_id()?
+            } else {
+                change_id
+            };
+            let edit = agentjj::change::ChangeEdit {
+                intent,
+                change_type: r#type.map(|t| parse_change_type(&t)).transpose()?,
+                categories: (!category.is_empty())
+                    .then(|| parse_categories(&mut repo, &category))
+                    .transpose()?,
+                breaking,
+                add_files,
+                remove_files,
+                add_depends_on: resolve_dependencies(&mut repo, &add_depends_on)?,
+                // Dropping one that no longer resolves must still work
+                remove_depends_on: remove_depends_on
+                    .iter()
+                    .map(|dep| {
+                        resolve_dependencies(&mut repo, std::slice::from_ref(dep))
+                            .map(|mut ids| ids.remove(0))
+                            .unwrap_or_else(|_| dep.clone())
+                    })
+                    .collect(),
+            };
+
+            let mut change = repo.get_typed_change(&cid)?;
+            let by = by.unwrap_or_else(agentjj::audit::current_actor);
+            let fields = change.apply_edit(&edit, by, chrono_lite_now());
+            if !fields.is_empty() {
+                repo.save_typed_change(&change)?;
+            }
+
+            if json {
+                println!(
+                    "{}",
+                    json_string(&serde_json::json!({
+                        "change_id": cid,
+                        "edited": fields,
+                        "change": change,
+                    }))?
+                );
+            } else if fields.is_empty() {
+                println!("No changes to {}", cid);
+            } else {
+                println!("Edited {}:", cid);
+                for f in &fields {
+                    println!(
+                        "  {}: {} -> {}",
+                        f.field,
+                        f.before.as_deref().unwrap_or("(none)"),
+                        f.after.as_deref().unwrap_or("(none)")
+                    );
+                }
+            }
+        }
+        ChangeAction::Verify { change_id } => {
+            let mut changes = match change_id {
+                Some(id) => {
+                    let id = if id == "@" {
+                        repo.current_change_id()?
+                    } else {
+                        id
+                    };
+                    vec![repo.get_typed_change(&id)?]
+                }
+                None => agentjj::change::ChangeIndex::load_from_repo(repo.root())?
+                    .all()
+                    .into_iter()
+                    .cloned()
+                    .collect(),
+            };
+            changes.sort_by(|a, b| a.change_id.cmp(&b.change_id));
+
+            let reports: Vec<serde_json::Value> = changes
+                .iter()
+                .map(|change| verify_change(&mut repo, change))
+                .collect::<Result<_>>()?;
+            let drifted = reports.iter().filter(|r| r["status"] != "ok").count();
+
+            if json {
+                println!(
+                    "{}",
+                    json_string(&serde_json::json!({
+                        "verified": reports.len(),
+                        "drifted": drifted,
+                        "changes": reports,
+                    }))?
+                );
+            } else if reports.is_empty() {
+                println!("No typed changes to verify");
+            } else {
+                for r in &reports {
+                    let mark = if r["status"] == "ok" { "✓" } else { "✗" };
+                    println!(
+                        "{} {} [{}]",
+                        mark,
+                        r["change_id"].as_str().unwrap_or(""),
+                        r["status"].as_str().unwrap_or("")
+                    );
+                    for (label, key) in [("not recorded", "unrecorded"), ("not in diff", "extra")] {
+                        for f in r["files"][key].as_array().into_iter().flatten() {
+                            println!("  {}: {}", label, f.as_str().unwrap_or(""));
+                        }
+                    }
+                    if r["breaking"]["drift"] == true {
+                        println!("  breaking API changes but not marked breaking");
+                    }
+                }
+                println!("{} of {} drifted", drifted, reports.len());
+            }
+
+            if drifted > 0 {
+                exit_with(exit::POLICY, Some("drift"));
+            }
+        }
+        ChangeAction::Backfill {
+            since,
+            force,
+            dry_run,
+        } => {
+            let since = since
+                .map(|rev| repo.resolve_revision(&rev).map(|(_, commit)| commit))
+                .transpose()?;
+            let history = repo.history_since(since.as_deref())?;
+
+            let mut imported = Vec::new();
+            let mut skipped = Vec::new();
+            let mut progress = agentjj::progress::Progress::start("backfill", Some(history.len()));
+            // Oldest first, the order the history was written in
+            for entry in history.iter().rev() {
+                progress.tick();
+                let commit = agentjj::change::ConventionalCommit::parse(&entry.description);
+                let typed = commit
+                    .as_ref()
+                    .and_then(|c| c.to_typed_change(entry.change_id.clone()));
+                let reason = if entry.description.trim().is_empty() {
+                    Some("no_description")
+                } else if !force && repo.get_typed_change(&entry.change_id).is_ok() {
+                    Some("recorded")
+                } else if commit.is_none() {
+                    Some("not_conventional")
+                } else if typed.is_none() {
+                    Some("unknown_type")
+                } else {
+                    None
+                };
+                let Some(mut typed) = typed.filter(|_| reason.is_none()) else {
+                    skipped.push(serde_json::json!({
+                        "change_id": entry.change_id,
+                        "reason": reason,
+                    }));
+                    continue;
+                };
+
+                let changes = repo.tree_changes(&entry.change_id)?;
+                typed.files = changes.iter().map(|c| c.path.clone()).collect();
+                typed.metrics = Some(agentjj::change::ChangeMetrics::from_changes(&changes));
+                typed.created_at = Some(entry.timestamp.clone());
+                typed.author = Some(entry.author.clone());
+                typed
+                    .metadata
+                    .insert("imported_from".into(), entry.commit_id.clone());
+                if !dry_run {
+                    repo.save_typed_change(&typed)?;
+                }
+                imported.push(typed);
+            }
+            progress.finish();
+
+            if json {
+                println!(
+                    "{}",
+                    json_string(&serde_json::json!({
+                        "dry_run": dry_run,
+                        "imported": imported,
+                        "skipped": skipped,
+                    }))?
+                );
+            } else {
+                let verb = if dry_run { "Would import" } else { "Imported" };
+                println!("{} {} typed change(s)", verb, imported.len());
+                for change in &imported {
+                    let mut kind = format!("{:?}", change.change_type);
+                    for category in &change.categories {
+                        kind.push_str(&format!("/{}", category));
+                    }
+                    println!(
+                        "  {} [{}{}] {}",
+                        &change.change_id[..12.min(change.change_id.len())],
+                        kind,
+                        if change.breaking { ", breaking" } else { "" },
+                        change.intent.lines().next().unwrap_or_default()
+                    );
+                }
+                if !skipped.is_empty() {
+                    println!(
+                        "Skipped {} commit(s): not conventional, unknown type, or already recorded",
+                        skipped.len()
+                    );
+                }
+            }
+        }
+        ChangeAction::Notes {
+            change_id,
+            set,
+            edit,
+        } => {
+            let cid = if change_id == "@" {
+                repo.current_change_id()?
+            } else {
+                change_id
+            };
+            let existing = repo.get_typed_change(&cid);
+            if set.is_none() && !edit {
+                let notes = existing.ok().and_then(|c| c.notes);
+                if json {
+                    println!(
+                        "{}",
+                        json_string(&serde_json::json!({
+                            "change_id": cid,
+                            "notes": notes,
+                        }))?
+                    );
+                } else {
+                    match notes {
+                        Some(notes) => println!("{}", notes),
+                        None => println!("No notes for {}", cid),
+                    }
+                }
+                return Ok(());
+            }
+
+            // Notes can come before anything else is recorded about the change
+            let created = existing.is_err();
+            let mut change = match existing {
+                Ok(change) => change,
+                Err(_) => {
+                    let change_type = resolve_change_type(&mut repo, None)?;
+                    TypedChange::new(cid.clone(), change_type, "")
+                }
+            };
+            let text = match set {
+                Some(text) => text,
+                None => edit_text(&cid, change.notes.as_deref().unwrap_or(""))?,
+            };
+            let text = text.trim();
+            change.notes = (!text.is_empty()).then(|| text.to_string());
+            repo.save_typed_change(&change)?;
+
+            if json {
+                println!(
+                    "{}",
+                    json_string(&serde_json::json!({
+                        "change_id": cid,
+                        "notes": change.notes,
+                        "created": created,
+                    }))?
+                );
+            } else if change.notes.is_some() {
+                println!("Saved notes for {}", cid);
+            } else {
+                println!("Cleared notes for {}", cid);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Let the user edit `initial` in $VISUAL or $EDITOR (else vi), returning
+/// the result
+fn edit_text(name: &str, initial: &str) -> Result<String> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let path = std::env::temp_dir().join(format!(
+        "agentjj-notes-{}-{}.md",
+        &name[..12.min(name.len())],
+        std::process::id()
+    ));
+    std::fs::write(&path, initial)?;
+    // Through the shell, so an editor given with arguments ("code --wait") works
+    let status = std::process::Command::new("sh")
+        .args(["-c", &format!("{} \"$1\"", editor), "sh"])
+        .arg(&path)
+        .status();
+    let text = std::fs::read_to_string(&path);
+    let _ = std::fs::remove_file(&path);
+    if !status?.success() {
+        anyhow::bail!("editor '{}' exited with an error; notes unchanged", editor);
+    }
+    Ok(text?)
+}
+
+/// Recompute a change's files, line stat, and public API changes from jj and
+/// compare them with its recorded metadata
+fn verify_change(repo: &mut Repo, change: &TypedChange) -> Result<serde_json::Value> {
+    let (parent, commit) = match repo.resolve_revision(&change.change_id) {
+        Ok(revs) => revs,
+        Err(_) => {
+            return Ok(serde_json::json!({
+                "change_id": change.change_id,
+                "status": "missing",
+                "message": "change is not visible in the repository",
+            }))
+        }
+    };
+
+    // Metadata lives under .agent/ and is never part of what a change records
+    let actual: Vec<String> = repo
+        .changed_files(&change.change_id)?
+        .into_iter()
+        .filter(|f| !f.starts_with(".agent/"))
+        .collect();
+    let recorded: Vec<&String> = change
+        .files
+        .iter()
+        .filter(|f| !f.starts_with(".agent/"))
+        .collect();
+    let unrecorded: Vec<&String> = actual.iter().filter(|f| !recorded.contains(f)).collect();
+    let extra: Vec<&String> = recorded
+        .iter()
+        .copied()
+        .filter(|f| !actual.contains(f))
+        .collect();
+
+    let (mut additions, mut deletions) = (0u64, 0u64);
+    if repo.has_git_backend()? {
+        // Line stat, as git sees it (colocated: jj commit IDs are git commit IDs)
+        let mut numstat = std::process::Command::new("git");
+        numstat.current_dir(repo.root());
+        match &parent {
+            Some(parent) => numstat.args(["diff", "--numstat", parent, &commit]),
+            None => numstat.args(["show", "--numstat", "--format=", &commit]),
+        };
+        for line in String::from_utf8_lossy(&numstat.output()?.stdout).lines() {
+            let cols: Vec<&str> = line.splitn(3, '\t').collect();
+            if cols.len() < 3 || !actual.iter().any(|f| f == cols[2]) {
+                continue;
+            }
+            // Binary files report "-" for both counts
+            additions += cols[0].parse::<u64>().unwrap_or(0);
+            deletions += cols[1].parse::<u64>().unwrap_or(0);
+        }
+    } else {
+        for file in repo.tree_changes(&change.change_id)? {
+            if let (true, Some((added, removed))) =
+                (actual.contains(&file.path), file.line_counts())
+            {
+                additions += added as u64;
+                deletions += removed as u64;
+            }
+        }
+    }
+
+    let mut api_changes = Vec::new();
+    for file in &actual {
+        if let Some(lang) = agentjj::SupportedLanguage::from_path(std::path::Path::new(file)) {
+            let before = parent
+                .as_deref()
+                .and_then(|p| repo.read_file(file, Some(p)).ok());
+            let after = repo.read_file(file, Some(&commit)).ok();
+            api_changes.extend(agentjj::api::diff_api(
+                file,
+                before.as_deref(),
+                after.as_deref(),
+                lang,
+            ));
+        }
+    }
+    let detected_breaking = api_changes.iter().any(|c| c.is_breaking());
+    let breaking_drift = detected_breaking && !change.breaking;
+
+    let files_drift = !unrecorded.is_empty() || !extra.is_empty();
+    let status = if files_drift || breaking_drift {
+        "drift"
+    } else {
+        "ok"
+    };
+
+    Ok(serde_json::json!({
+        "change_id": change.change_id,
+        "commit_id": commit,
+        "status": status,
+        "files": {
+            "recorded": recorded,
+            "actual": actual,
+            "unrecorded": unrecorded,
+            "extra": extra,
+        },
+        "stat": {
+            "files": actual.len(),
+            "additions": additions,
+            "deletions": deletions,
+        },
+        "breaking": {
+            "recorded": change.breaking,
+            "detected": detected_breaking,
+            "drift": breaking_drift,
+            "api_changes": api_changes,
+        },
+    }))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn cmd_apply(
+    intent_desc: Option<String>,
+    type_str: Option<String>,
+    category: Vec<String>,
+    patch: Option<String>,
+    preconditions: Vec<String>,
+    no_invariants: bool,
+    breaking: bool,
+    resume: Option<String>,
+    onto: Option<String>,
+    attempt_of: Option<String>,
+    json: bool,
+) -> Result<()> {
+    use agentjj::attempts::{Attempt, IntentAttempts, IntentStore};
+
+    let mut repo = Repo::discover()?;
+
+    let (result, attempts) = match (resume, intent_desc) {
+        // A parked intent runs exactly as it was submitted
+        (Some(approval_id), _) => {
+            let approval =
+                agentjj::approval::ApprovalStore::open(repo.root()).load(&approval_id)?;
+            repo.run_hooks(HookEvent::PreApply, serde_json::to_value(&approval.intent)?)?;
+            (repo.apply_approved(&approval_id)?, None)
+        }
+        (None, Some(intent_desc)) => {
+            // Every apply is an attempt; retries are checked against [intents]
+            let store = IntentStore::open(repo.root());
+            let session = agentjj::audit::current_session();
+            let started_at = chrono_lite_now();
+            let mut attempts = match &attempt_of {
+                Some(id) => store.load(id)?,
+                None => IntentAttempts::new(&intent_desc, &session, &started_at),
+            };
+            let limits = repo
+                .manifest()
+                .map(|m| m.intents.clone())
+                .unwrap_or_default();
+            attempts.check_limits(&limits, &started_at)?;
+
+            let timer = std::time::Instant::now();
+            let outcome = build_and_apply(
+                &mut repo,
+                intent_desc,
+                type_str,
+                category,
+                patch,
+                preconditions,
+                no_invariants,
+                breaking,
+                onto.clone(),
+            );
+            let (status, reason, change_id) = match &outcome {
+                Ok(result) => {
+                    let (status, reason) = Attempt::outcome(result);
+                    (status, reason, result.change_id().map(str::to_string))
+                }
+                Err(e) => {
+                    let status = e
+                        .downcast_ref::<agentjj::Error>()
+                        .and_then(|err| serde_json::to_value(err).ok())
+                        .and_then(|v| v["type"].as_str().map(str::to_string))
+                        .unwrap_or_else(|| "error".to_string());
+                    (status, Some(e.to_string()), None)
+                }
+            };
+            attempts.attempts.push(Attempt {
+                number: attempts.attempts.len() + 1,
+                session,
+                started_at,
+                duration_ms: timer.elapsed().as_millis() as u64,
+                status,
+                reason,
+                change_id,
+            });
+            store.save(&attempts)?;
+            (outcome?, Some(attempts))
+        }
+        (None, None) => anyhow::bail!("--intent is required"),
+    };
+
+    report_intent_result(&result, onto.as_deref(), attempts.as_ref(), json)
+}
+
+/// Print an apply/edit result, exiting with its code unless it succeeded
+fn report_intent_result(
+    result: &agentjj::intent::IntentResult,
+    onto: Option<&str>,
+    attempts: Option<&agentjj::attempts::IntentAttempts>,
+    json: bool,
+) -> Result<()> {
+    if json {
+        let mut output = serde_json::to_value(result)?;
+        if let Some(onto) = onto {
+            output["onto"] = serde_json::json!(onto);
+        }
+        if let Some(attempts) = attempts {
+            output["intent"] = serde_json::json!({
+                "id": attempts.id,
+                "attempt": attempts.attempts.len(),
+                "looping": attempts.looping(),
+            });
+        }
+        println!("{}", json_string(&output)?);
+    } else {
+        print_intent_result(result);
+        if let (Some(onto), Some(change_id)) = (onto, result.change_id()) {
+            println!("  onto: {} (working copy unchanged)", onto);
+            println!("  rebase with: jj rebase -r {} -d @", change_id);
+        }
+        if let Some(attempts) = attempts {
+            println!(
+                "  intent: {} (attempt {})",
+                attempts.id,
+                attempts.attempts.len()
+            );
+            if attempts.looping() {
+                println!(
+                    "  ⚠ the last {} attempts failed the same way; change the approach before retrying",
+                    attempts.repeated_failures()
+                );
+            }
+        }
+    }
+
+    if !result.is_success() {
+        let status = serde_json::to_value(result)?["status"].clone();
+        exit_with(result.exit_code(), status.as_str());
+    }
+
+    Ok(())
+}
+
+/// Build an intent from `apply` flags and apply it
+#[allow(clippy::too_many_arguments)]
+fn build_and_apply(
+    repo: &mut Repo,
+    intent_desc: String,
+    type_str: Option<String>,
+    category: Vec<String>,
+    patch: Option<String>,
+    preconditions: Vec<String>,
+    no_invariants: bool,
+    breaking: bool,
+    onto: Option<String>,
+) -> Result<agentjj::intent::IntentResult> {
+    let change_type = resolve_change_type(repo, type_str)?;
+
+    // Build change spec
+    let changes = if let Some(patch_file) = patch {
+        let content = std::fs::read_to_string(&patch_file)?;
+        agentjj::audit::attach_inputs(serde_json::json!({ "patch": content }));
+        ChangeSpec::Patch { content }
+    } else {
+        anyhow::bail!("--patch is required (for now)");
+    };
+
+    // Build preconditions
+    let mut preconds = Preconditions::default();
+    for p in preconditions {
+        if let Some((branch, change_id)) = p.split_once('@') {
+            preconds = preconds.with_branch_at(branch, change_id);
+        } else {
+            anyhow::bail!("Invalid precondition format: {}. Use branch@change_id", p);
+        }
+    }
+
+    // Build intent
+    let mut intent = Intent::new(intent_desc, change_type, changes).with_preconditions(preconds);
+
+    for category in parse_categories(repo, &category)? {
+        intent = intent.with_category(category);
+    }
+    if no_invariants {
+        intent = intent.skip_invariants();
+    }
+    if breaking {
+        intent = intent.breaking();
+    }
+    if let Some(rev) = onto {
+        intent = intent.onto(rev);
+    }
+
+    repo.run_hooks(HookEvent::PreApply, serde_json::to_value(&intent)?)?;
+
+    // Apply
+    Ok(repo.apply(intent)?)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn cmd_edit(
+    intent_desc: String,
+    ops: String,
+    type_str: Option<String>,
+    category: Vec<String>,
+    no_invariants: bool,
+    breaking: bool,
+    onto: Option<String>,
+    json: bool,
+) -> Result<()> {
+    let mut repo = Repo::discover()?;
+
+    let change_type = resolve_change_type(&mut repo, type_str)?;
+
+    let raw = if ops == "-" {
+        let mut buf = String::new();
+        std::io::Read::read_to_string(&mut std::io::stdin(), &mut buf)?;
+        buf
+    } else {
+        std::fs::read_to_string(&ops)?
+    };
+    let operations: Vec<agentjj::intent::FileOperation> =
+        serde_json::from_str(&raw).map_err(|e| {
+            anyhow::anyhow!(
+                "Invalid --ops (expected a JSON array of file operations): {}",
+                e
+            )
+        })?;
+    if operations.is_empty() {
+        anyhow::bail!("--ops contains no file operations");
+    }
+    agentjj::audit::attach_inputs(serde_json::json!({ "operations": operations }));
+
+    let mut intent = Intent::new(intent_desc, change_type, ChangeSpec::Files { operations });
+    for category in parse_categories(&mut repo, &category)? {
+        intent = intent.with_category(category);
+    }
+    if no_invariants {
+        intent = intent.skip_invariants();
+    }
+    if breaking {
+        intent = intent.breaking();
+    }
+    if let Some(rev) = &onto {
+        intent = intent.onto(rev);
+    }
+
+    repo.run_hooks(HookEvent::PreApply, serde_json::to_value(&intent)?)?;
+
+    let result = repo.apply(intent)?;
+    report_intent_result(&result, onto.as_deref(), None, json)
+}
+
+/// Human-readable summary of an intent result (apply, edit)
+fn print_intent_result(result: &agentjj::intent::IntentResult) {
+    match result {
+        agentjj::intent::IntentResult::Success { change_id, .. } => {
+            println!("✓ Applied successfully");
+            println!("  change: {}", change_id);
+        }
+        agentjj::intent::IntentResult::StaleEdit { drifted } => {
+            println!(
+                "✗ Stale edit: {} file(s) changed since they were read",
+                drifted.len()
+            );
+            for file in drifted {
+                match file.lines {
+                    Some((start, end)) => println!("  {} (lines {}-{})", file.path, start, end),
+                    None => println!("  {}", file.path),
+                }
+            }
+        }
+        agentjj::intent::IntentResult::Conflict { conflicts, .. } => {
+            println!("✗ Conflict in {} files", conflicts.len());
+        }
+        agentjj::intent::IntentResult::PreconditionFailed {
+            reason,
+            expected,
+            actual,
+        } => {
+            println!("✗ Precondition failed: {}", reason);
+            println!("  expected: {}", expected);
+            println!("  actual: {}", actual);
+        }
+        agentjj::intent::IntentResult::InvariantFailed {
+            invariant,
+            stderr,
+            exit_code,
+            known_flakes,
+            ..
+        } => {
+            println!("✗ Invariant '{}' failed (exit {})", invariant, exit_code);
+            if !stderr.is_empty() {
+                println!("  stderr: {}", stderr);
+            }
+            if !known_flakes.is_empty() {
+                println!(
+                    "  known flaky: {} - retry before reverting",
+                    known_flakes.join(", ")
+                );
+            }
+        }
+        agentjj::intent::IntentResult::PermissionDenied {
+            path, action, rule, ..
+        } => {
+            println!(
+                "✗ Permission denied: {} on '{}' (rule: {})",
+                action, path, rule
+            );
+        }
+        agentjj::intent::IntentResult::RequiresReview { message, paths, .. } => {
+            println!("⚠ Requires human review: {}", message);
+            if !paths.is_empty() {
+                println!("  paths: {}", paths.join(", "));
+            }
+        }
+        agentjj::intent::IntentResult::PendingApproval {
+            approval_id,
+            paths,
+            message,
+        } => {
+            println!("⏸ Parked for approval ({})", approval_id);
+            println!("  paths: {}", paths.join(", "));
+            println!("  {}", message);
+        }
+    }
+}
+
+fn cmd_read(
+    path: String,
+    at: Option<String>,
+    symbol: Option<String>,
+    around_line: Option<usize>,
+    context: usize,
+    raw: bool,
+    json: bool,
+) -> Result<()> {
+    let mut repo = Repo::discover()?;
+    // The file hash covers the bytes on disk, so it matches what `edit` checks
+    let bytes = repo.read_raw(&path, at.as_deref())?;
+    let file_hash = agentjj::snippet::content_hash(&bytes);
+
+    // The real content of an LFS file is not in the working copy
+    if let Some(pointer) = agentjj::lfs::parse_pointer(&bytes).filter(|_| !raw) {
+        if json {
+            println!(
+                "{}",
+                json_string(&serde_json::json!({
+                    "path": path,
+                    "at": at,
+                    "content": null,
+                    "lfs": pointer,
+                    "file_hash": file_hash,
+                }))?
+            );
+        } else {
+            println!(
+                "{} is a Git LFS pointer ({}, {}); run `git lfs pull` for the content",
+                path,
+                pointer.oid,
+                format_size(pointer.size)
+            );
+        }
+        return Ok(());
+    }
+    let (content, format) = agentjj::encoding::decode(&bytes)
+        .ok_or_else(|| anyhow::anyhow!("{} is a binary file", path))?;
+    let mut lang = agentjj::SupportedLanguage::from_path(std::path::Path::new(&path));
+
+    // Notebooks read as annotated cell source; lines and spans refer to it.
+    // Their raw JSON has no symbols.
+    let notebook = lang == Some(agentjj::SupportedLanguage::Notebook);
+    if notebook && raw {
+        lang = None;
+    }
+    let notebook = notebook && !raw;
+    let content = if notebook {
+        let notebook = agentjj::notebook::Notebook::parse(&content)?;
+        lang = notebook.code_language();
+        notebook.render()
+    } else {
+        content
+    };
+    let total_lines = content.lines().count();
+
+    // Narrow to a slice when a symbol or line was asked for
+    let snippet = if symbol.is_some() || around_line.is_some() {
+        let symbols = match lang {
+            Some(lang) => agentjj::symbols::extract_symbols(&content, lang)?,
+            None if symbol.is_some() => anyhow::bail!("Unsupported file type: {}", path),
+            None => Vec::new(),
+        };
+        let snippet = match (&symbol, around_line) {
+            (Some(name), _) => agentjj::snippet::symbol_snippet(&content, &symbols, name)
+                .ok_or_else(|| anyhow::anyhow!("Symbol not found: {}", name))?,
+            (None, Some(line)) => agentjj::snippet::around_line(&content, &symbols, line, context)
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Line {} is out of range ({} has {} lines)",
+                        line,
+                        path,
+                        total_lines
+                    )
+                })?,
+            (None, None) => unreachable!(),
+        };
+        Some(snippet)
+    } else {
+        None
+    };
+
+    let body = snippet
+        .as_ref()
+        .map(|s| s.content.clone())
+        .unwrap_or(content);
+    let (body, truncated) = truncate_output(body, repo.config()?.output.max_bytes);
+
+    if json {
+        let mut result = serde_json::json!({
+            "path": path,
+            "at": at,
+            "content": body,
+            "truncated": truncated,
+            "file_hash": file_hash,
+            "total_lines": total_lines,
+            "encoding": format,
+        });
+        if notebook {
+            result["format"] = serde_json::json!("notebook");
+        }
+        if let Some(snippet) = &snippet {
+            result["span"] = serde_json::json!({
+                "start_line": snippet.start_line,
+                "end_line": snippet.end_line,
+                "start_byte": snippet.start_byte,
+                "end_byte": snippet.end_byte,
+            });
+            result["symbol"] = serde_json::json!(snippet.symbol);
+            result["hash"] = serde_json::json!(snippet.hash);
+        }
+        // Notes on the working copy's code, for the slice or the whole file
+        if at.is_none() {
+            let notes = agentjj::notes::NoteStore::open(repo.root()).for_view(
+                &path,
+                snippet.as_ref().and_then(|s| s.symbol.as_deref()),
+                snippet.as_ref().map(|s| (s.start_line, s.end_line)),
+            )?;
+            if !notes.is_empty() {
+                result["notes"] = serde_json::json!(notes);
+            }
+        }
+        print_json_or_spill(&mut repo, "read", &result)?;
+    } else {
+        print!("{}", body);
+    }
+
+    Ok(())
+}
+
+fn cmd_symbol(path: String, signature_only: bool, json: bool) -> Result<()> {
+    // Parse path: can be "file.py" or "file.py::symbol_name"
+    let (file_path, symbol_name) = if let Some(idx) = path.find("::") {
+        (&path[..idx], Some(&path[idx + 2..]))
+    } else {
+        (path.as_str(), None)
+    };
+
+    let file_path_obj = std::path::Path::new(file_path);
+
+    // Detect language
+    let lang = agentjj::SupportedLanguage::from_path(file_path_obj)
+        .ok_or_else(|| anyhow::anyhow!("Unsupported file type: {}", file_path))?;
+
+    // Read file content - use filesystem for absolute paths, jj for relative
+    let content = if file_path_obj.is_absolute() {
+        std::fs::read_to_string(file_path)?
+    } else {
+        let mut repo = Repo::discover()?;
+        repo.read_file(file_path, None)?
+    };
+
+    if let Some(name) = symbol_name {
+        // Find specific symbol
+        let symbol = agentjj::symbols::find_symbol(&content, lang, name)?;
+
+        match symbol {
+            Some(s) => {
+                if json {
+                    if signature_only {
+                        println!(
+                            "{}",
+                            json_string(&serde_json::json!({
+                                "name": s.name,
+                                "signature": s.signature,
+                            }))?
+                        );
+                    } else {
+                        println!("{}", json_string(&s)?);
+                    }
+                } else if signature_only {
+                    if let Some(sig) = &s.signature {
+                        println!("{}", sig);
+                    } else {
+                        println!("{}", s.name);
+                    }
+                } else {
+                    println!("{} ({:?})", s.name, s.kind);
+                    if let Some(sig) = &s.signature {
+                        println!("  {}", sig);
+                    }
+                    println!("  lines {}-{}", s.start_line, s.end_line);
+                }
+            }
+            None => {
+                anyhow::bail!("Symbol '{}' not found in {}", name, file_path);
+            }
+        }
+    } else {
+        // List all symbols in file
+        let symbols = agentjj::symbols::extract_symbols(&content, lang)?;
+
+        if json {
+            println!("{}", json_string(&symbols)?);
+        } else {
+            for s in symbols {
+                let sig = s.signature.as_deref().unwrap_or(&s.name);
+                let truncated = if sig.len() > 60 {
+                    format!("{}...", &sig[..57])
+                } else {
+                    sig.to_string()
+                };
+                println!(
+                    "{:>9} {:10} {}",
+                    format!("{}-{}", s.start_line, s.end_line),
+                    format!("{:?}", s.kind).to_lowercase(),
+                    truncated
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Rank symbols across the codebase against a name query
+fn cmd_symbol_find(
+    query: &str,
+    kind: Option<&str>,
+    lang: Option<&str>,
+    limit: usize,
+    json: bool,
+) -> Result<()> {
+    use agentjj::symbol_search;
+
+    let kind: Option<agentjj::symbols::SymbolKind> = kind
+        .map(|k| {
+            serde_json::from_value(serde_json::json!(k.to_lowercase()))
+                .map_err(|_| anyhow::anyhow!("Unknown symbol kind: {}", k))
+        })
+        .transpose()?;
+    let lang = lang
+        .map(|l| {
+            agentjj::SupportedLanguage::from_name(l)
+                .ok_or_else(|| anyhow::anyhow!("Unsupported language: {}", l))
+        })
+        .transpose()?;
+
+    let mut repo = Repo::discover()?;
+    let root = repo.root().to_path_buf();
+    let codebase = codebase_files(&mut repo)?;
+    let mut matches = Vec::new();
+    let mut progress = agentjj::progress::Progress::start("scan", Some(codebase.len()));
+    for (scanned, path) in codebase.iter().enumerate() {
+        if agentjj::deadline::expired() {
+            symbol_search::rank(&mut matches, limit);
+            exit_timed_out(
+                "scan",
+                serde_json::json!({ "files_scanned": scanned, "matches": matches }),
+                json,
+            );
+        }
+        progress.tick();
+        let Some(file_lang) = agentjj::SupportedLanguage::from_path(path) else {
+            continue;
+        };
+        if lang.is_some_and(|l| l != file_lang) {
+            continue;
+        }
+        let Ok(content) = std::fs::read_to_string(path) else {
+            continue;
+        };
+        let Ok(symbols) = agentjj::symbols::extract_symbols(&content, file_lang) else {
+            continue;
+        };
+        let rel_path = path
+            .strip_prefix(&root)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .to_string();
+        matches.extend(symbol_search::search(&rel_path, &symbols, query, kind));
+    }
+    progress.finish();
+    symbol_search::rank(&mut matches, limit);
+
+    if json {
+        print_json_or_spill(
+            &mut repo,
+            "symbol",
+            &serde_json::json!({
+                "query": query,
+                "matches": matches,
+                "count": matches.len(),
+            }),
+        )?;
+    } else if matches.is_empty() {
+        println!("No symbols matching '{}'", query);
+    } else {
+        for m in &matches {
+            let name = match &m.parent {
+                Some(parent) => format!("{}::{}", parent, m.name),
+                None => m.name.clone(),
+            };
+            println!(
+                "{}:{} {} {}",
+                m.file,
+                m.line,
+                format!("{:?}", m.kind).to_lowercase(),
+                name
+            );
+            if let Some(sig) = &m.signature {
+                println!("    {}", sig);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn parse_change_type(s: &str) -> Result<ChangeType> {
+    match s.to_lowercase().as_str() {
+        "behavioral" | "behavior" => Ok(ChangeType::Behavioral),
+        "refactor" => Ok(ChangeType::Refactor),
+        "schema" => Ok(ChangeType::Schema),
+        "docs" | "doc" => Ok(ChangeType::Docs),
+        "deps" | "dependency" | "dependencies" => Ok(ChangeType::Deps),
+        "config" | "configuration" => Ok(ChangeType::Config),
+        "test" | "tests" => Ok(ChangeType::Test),
+        _ => anyhow::bail!("Unknown change type: {}", s),
+    }
+}
+
+/// Use the explicit --type if given, otherwise the configured default
+/// A message for `commit --auto-message`: from `change.message_command`
+/// when configured and it answers, else the heuristic one
+fn auto_commit_message(
+    repo: &mut Repo,
+    change_type: Option<ChangeType>,
+    categories: &[ChangeCategory],
+    breaking: bool,
+    paths: Option<&[String]>,
+) -> Result<agentjj::describe::AutoMessage> {
+    repo.snapshot()?;
+    let mut changes = repo.tree_changes("@")?;
+    // Typed-change records left behind by earlier commits aren't the work
+    changes.retain(|c| !c.path.starts_with(".agent/"));
+    if let Some(paths) = paths {
+        changes.retain(|c| paths.contains(&c.path));
+    }
+    let mut auto = agentjj::describe::describe(&changes, change_type, categories, breaking);
+    let payload = serde_json::json!({
+        "files": auto.files,
+        "change_type": auto.change_type,
+        "categories": auto.categories,
+        "breaking": breaking,
+        "heuristic": auto.message,
+    });
+    if let Some(message) = repo.message_from_command(&payload)? {
+        auto.subject = message.lines().next().unwrap_or("").to_string();
+        auto.message = message;
+        auto.source = "command";
+    }
+    Ok(auto)
+}
+
+fn resolve_change_type(repo: &mut Repo, explicit: Option<String>) -> Result<ChangeType> {
+    match explicit {
+        Some(t) => parse_change_type(&t),
+        None => parse_change_type(&repo.config()?.change.default_type),
+    }
+}
+
+/// Cut `content` to at most `max_bytes` (on a char boundary), reporting whether it was cut
+/// Print a JSON result, or spill it to `.agent/out/` and print a stub when
+/// it is larger than `output.max_output_bytes`
+fn print_json_or_spill(repo: &mut Repo, command: &str, value: &serde_json::Value) -> Result<()> {
+    let payload = json_string(value)?;
+    match repo.config()?.output.max_output_bytes {
+        Some(limit) if payload.len() > limit => {
+            let stub = agentjj::spill::spill(repo.root(), command, &payload, value, limit)?;
+            println!("{}", json_string(&stub)?);
+        }
+        _ => println!("{}", payload),
+    }
+    Ok(())
+}
+
+fn truncate_output(mut content: String, max_bytes: Option<usize>) -> (String, bool) {
+    match max_bytes {
+        Some(max) if content.len() > max => {
+            let mut end = max;
+            while !content.is_char_boundary(end) {
+                end -= 1;
+            }
+            content.truncate(end);
+            (content, true)
+        }
+        _ => (content, false),
+    }
+}
+
+fn parse_category(s: &str) -> Result<ChangeCategory> {
+    match s.to_lowercase().as_str() {
+        "feature" | "feat" => Ok(ChangeCategory::Feature),
+        "fix" | "bugfix" => Ok(ChangeCategory::Fix),
+        "perf" | "performance" => Ok(ChangeCategory::Perf),
+        "security" | "sec" => Ok(ChangeCategory::Security),
+        "breaking" => Ok(ChangeCategory::Breaking),
+        "deprecation" | "deprecate" => Ok(ChangeCategory::Deprecation),
+        "chore" => Ok(ChangeCategory::Chore),
+        _ => anyhow::bail!("Unknown category: {}", s),
+    }
+}
+
+/// `-c` values: built-in categories (or their aliases), then the ones the
+/// manifest's `[categories]` defines
+fn parse_categories(repo: &mut Repo, values: &[String]) -> Result<Vec<ChangeCategory>> {
+    if values.is_empty() {
+        return Ok(Vec::new());
+    }
+    let custom = repo
+        .manifest()
+        .map(|m| m.categories.clone())
+        .unwrap_or_default();
+    let mut categories = Vec::new();
+    for value in values {
+        let category = match parse_category(value) {
+            Ok(category) => category,
+            Err(_) if custom.contains_key(value) => ChangeCategory::Custom(value.clone()),
+            Err(_) => {
+                let known: Vec<&str> = ChangeCategory::BUILTIN
+                    .iter()
+                    .map(ChangeCategory::name)
+                    .chain(custom.keys().map(String::as_str))
+                    .collect();
+                anyhow::bail!(
+                    "Unknown category: {} (known: {}; add others to the manifest's [categories])",
+                    value,
+                    known.join(", ")
+                );
+            }
+        };
+        if !categories.contains(&category) {
+            categories.push(category);
+        }
+    }
+    Ok(categories)
+}
+
+/// Full change IDs for `--depends-on` revisions (change ID, bookmark, @, @-)
+fn resolve_dependencies(repo: &mut Repo, revs: &[String]) -> Result<Vec<String>> {
+    let mut ids: Vec<String> = Vec::new();
+    for rev in revs {
+        let (_, commit_id) =
+            repo.resolve_revision(rev)
+                .map_err(|_| agentjj::Error::ChangeNotFound {
+                    change_id: rev.clone(),
+                })?;
+        let id = repo.commit_change_id(&commit_id)?;
+        if !ids.contains(&id) {
+            ids.push(id);
+        }
+    }
+    Ok(ids)
+}
+
+/// Split `--field name=value` entries
+fn parse_fields(raw: &[String]) -> Result<std::collections::BTreeMap<String, String>> {
+    raw.iter()
+        .map(|entry| match entry.split_once('=') {
+            Some((name, value)) if !name.trim().is_empty() => {
+                Ok((name.trim().to_string(), value.to_string()))
+            }
+            _ => anyhow::bail!("--field expects name=value, got '{}'", entry),
+        })
+        .collect()
+}
+
+fn cmd_context(path: String, render: Option<&str>, json: bool) -> Result<()> {
+    // Parse path: "path/to/file.ext::symbol_name"
+    let (file_path, symbol_name) = if let Some(idx) = path.find("::") {
+        (&path[..idx], &path[idx + 2..])
+    } else {
+        anyhow::bail!("Symbol path must be path/to/file::symbol_name (e.g., src/main.rs::main)");
+    };
+
+    let file_path_obj = std::path::Path::new(file_path);
+
+    // Detect language
+    let lang = agentjj::SupportedLanguage::from_path(file_path_obj)
+        .ok_or_else(|| anyhow::anyhow!("Unsupported file type: {}", file_path))?;
+
+    // Read file content
+    let (content, root) = if file_path_obj.is_absolute() {
+        (std::fs::read_to_string(file_path)?, None)
+    } else {
+        let mut repo = Repo::discover()?;
+        (
+            repo.read_file(file_path, None)?,
+            Some(repo.root().to_path_buf()),
+        )
+    };
+
+    // Get minimal context
+    let context = agentjj::symbols::get_symbol_context(&content, lang, symbol_name)?;
+    let includes = resolve_includes(file_path_obj, &content, lang);
+
+    // Notes on the symbol, lines inside it, or the whole file
+    let notes = match root {
+        Some(root) => {
+            let lines = agentjj::symbols::find_symbol(&content, lang, symbol_name)?
+                .map(|s| (s.start_line, s.end_line));
+            agentjj::notes::NoteStore::open(root).for_view(file_path, Some(symbol_name), lines)?
+        }
+        None => Vec::new(),
+    };
+
+    match context {
+        Some(ctx) => {
+            let markdown = render.map(|_| match &ctx.body {
+                Some(body) => format!("{}\n", body),
+                None => agentjj::docs::render_markdown(
+                    &ctx.name,
+                    ctx.kind,
+                    ctx.signature.as_deref(),
+                    ctx.doc.as_ref(),
+                    lang,
+                ),
+            });
+            if json {
+                let mut value = serde_json::to_value(&ctx)?;
+                if let Some(markdown) = markdown {
+                    value["markdown"] = serde_json::Value::String(markdown);
+                }
+                if let Some(includes) = &includes {
+                    value["includes"] = serde_json::json!(includes);
+                }
+                if !notes.is_empty() {
+                    value["notes"] = serde_json::json!(notes);
+                }
+                println!("{}", json_string(&value)?);
+            } else if let Some(markdown) = markdown {
+                print!("{}", markdown);
+            } else if let Some(body) = &ctx.body {
+                println!("{}", body);
+                print_context_notes(&notes);
+            } else {
+                println!("# {}", ctx.name);
+                println!("kind: {:?}", ctx.kind);
+                if let Some(sig) = &ctx.signature {
+                    println!("\n```");
+                    println!("{}", sig);
+                    println!("```");
+                }
+                if let Some(doc) = &ctx.docstring {
+                    println!("\n{}", doc);
+                }
+                if !ctx.imports_needed.is_empty() {
+                    println!("\nimports needed:");
+                    for imp in &ctx.imports_needed {
+                        println!("  {}", imp);
+                    }
+                }
+                for include in includes.iter().flatten() {
+                    if let Some(resolved) = include["resolved"].as_str() {
+                        println!(
+                            "  {} -> {}",
+                            include["path"].as_str().unwrap_or(""),
+                            resolved
+                        );
+                    }
+                }
+                print_context_notes(&notes);
+            }
+        }
+        None => {
+            anyhow::bail!("Symbol '{}' not found in {}", symbol_name, file_path);
+        }
+    }
+
+    Ok(())
+}
+
+/// The notes section of `context` text output
+fn print_context_notes(notes: &[agentjj::notes::Note]) {
+    if !notes.is_empty() {
+        println!("\nnotes:");
+        for note in notes {
+            print_note(note);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn cmd_commit(
+    message: Option<String>,
+    auto_message: bool,
+    dry_run: bool,
+    no_new: bool,
+    change_type_str: Option<String>,
+    category: Vec<String>,
+    no_invariants: bool,
+    breaking: bool,
+    paths: Option<Vec<String>>,
+    fields: Vec<String>,
+    depends_on: Vec<String>,
+    json: bool,
+) -> Result<()> {
+    let mut repo = Repo::discover()?;
+
+    let categories = parse_categories(&mut repo, &category)?;
+    let (message, auto, change_type) = if auto_message {
+        let explicit = change_type_str
+            .as_deref()
+            .map(parse_change_type)
+            .transpose()?;
+        let auto =
+            auto_commit_message(&mut repo, explicit, &categories, breaking, paths.as_deref())?;
+        let change_type = auto.change_type;
+        (auto.message.clone(), Some(auto), change_type)
+    } else {
+        let change_type = resolve_change_type(&mut repo, change_type_str)?;
+        (message.unwrap_or_default(), None, change_type)
+    };
+    if dry_run {
+        if json {
+            println!(
+                "{}",
+                json_string(&serde_json::json!({
+                    "committed": false,
+                    "message": message,
+                    "auto_message": auto,
+                }))?
+            );
+        } else {
+            print!("{}", message);
+        }
+        return Ok(());
+    }
+
+    let depends_on = resolve_dependencies(&mut repo, &depends_on)?;
+    let fields = parse_fields(&fields)?;
+    if let Ok(manifest) = repo.manifest() {
+        manifest.check_change_fields(change_type, &categories, &fields)?;
+    }
+
+    let opts = agentjj::repo::CommitOptions {
+        message: message.clone(),
+        no_new,
+        run_invariants: !no_invariants,
+        change_type,
+        categories,
+        breaking,
+        paths,
+        fields,
+        depends_on,
+    };
+
+    // Oversized changes fail with a suggested split into smaller commits
+    if let Ok(review) = repo.manifest().map(|m| m.review.clone()) {
+        if review.max_change_loc.is_some() || review.max_change_files.is_some() {
+            repo.snapshot()?;
+            if let Err(blocked) = check_change_size(
+                &mut repo,
+                opts.paths.as_deref(),
+                review.max_change_loc,
+                review.max_change_files,
+            ) {
+                match blocked {
+                    agentjj::Error::ChangeTooLarge { .. } => {
+                        confirmation_gate(&mut repo, Gate::Policy, blocked)?
+                    }
+                    e => return Err(e.into()),
+                }
+            }
+        }
+    }
+
+    // Dropping tables or columns needs an explicit --breaking (or policy)
+    if !breaking {
+        repo.snapshot()?;
+        let current = repo.current_change_id()?;
+        let mut files = repo.changed_files(&current).unwrap_or_default();
+        if let Some(ref paths) = opts.paths {
+            files.retain(|f| paths.contains(f));
+        }
+        let operations = destructive_schema_ops(&mut repo, &files);
+        let operations = blocking_schema_ops(&mut repo, operations);
+        if !operations.is_empty() {
+            confirmation_gate(
+                &mut repo,
+                Gate::Policy,
+                agentjj::Error::DestructiveSchema { operations },
+            )?;
+        }
+    }
+
+    // Docs that try to steer agents: reported, or refused under a block policy
+    let (prompt_findings, block_prompts) = if repo.manifest().is_ok_and(|m| m.security.scan_prompts)
+    {
+        repo.snapshot()?;
+        let current = repo.current_change_id()?;
+        let mut files = repo.changed_files(&current).unwrap_or_default();
+        if let Some(ref paths) = opts.paths {
+            files.retain(|f| paths.contains(f));
+        }
+        changed_prompt_findings(&mut repo, &files)
+    } else {
+        (Vec::new(), false)
+    };
+    if block_prompts && !prompt_findings.is_empty() {
+        confirmation_gate(
+            &mut repo,
+            Gate::Policy,
+            agentjj::Error::PromptInjection {
+                findings: prompt_findings.clone(),
+            },
+        )?;
+    }
+
+    let current = repo.current_change_id()?;
+    let pending_files = repo.changed_files(&current).unwrap_or_default();
+    repo.run_hooks(
+        HookEvent::PreCommit,
+        serde_json::json!({
+            "message": opts.message,
+            "change_id": current,
+            "change_type": opts.change_type,
+            "categories": opts.categories,
+            "breaking": opts.breaking,
+            "paths": opts.paths,
+            "fields": opts.fields,
+            "files": pending_files,
+        }),
+    )?;
+
+    let result = repo.commit_working_copy(opts)?;
+    agentjj::audit::attach_inputs(serde_json::json!({
+        "files": committed_file_inputs(repo.root(), &result.files_changed),
+        "change_id": result.change_id,
+    }));
+
+    // Large files belong in LFS; warn when no .gitattributes pattern covers them
+    let threshold = repo.config()?.lfs.threshold_bytes;
+    let lfs_patterns = agentjj::lfs::patterns(repo.root());
+    let mut warnings: Vec<String> = result
+        .files_changed
+        .iter()
+        .filter_map(|file| {
+            let size = std::fs::metadata(repo.root().join(file)).ok()?.len();
+            (size >= threshold && !agentjj::lfs::is_tracked(file, &lfs_patterns)).then(|| {
+                format!(
+                    "{} is {} but not tracked by Git LFS (add a filter=lfs pattern to .gitattributes)",
+                    file,
+                    format_size(size)
+                )
+            })
+        })
+        .collect();
+    warnings.extend(prompt_findings.iter().map(prompt_warning));
+
+    let mut hook_payload = serde_json::json!({
+        "message": message,
+        "change_id": result.change_id,
+        "commit": result.commit_id,
+        "files_changed": result.files_changed,
+    });
+    let hooks = run_post_hooks(&mut repo, HookEvent::PostCommit, &mut hook_payload, json)?;
+
+    if json {
+        let invariant_map: serde_json::Value = result
+            .invariants
+            .iter()
+            .map(|(k, v)| {
+                (
+                    k.clone(),
+                    serde_json::to_value(v).unwrap_or(serde_json::json!("unknown")),
+                )
+            })
+            .collect::<serde_json::Map<String, serde_json::Value>>()
+            .into();
+
+        let mut output = serde_json::json!({
+            "committed": true,
+            "change_id": result.change_id,
+            "commit": result.commit_id,
+            "message": message,
+            "files_changed": result.files_changed,
+            "stats": result.stats,
+            "invariants": invariant_map,
+        });
+        if let Some(ref auto) = auto {
+            output["auto_message"] = serde_json::to_value(auto)?;
+        }
+        if !hooks.is_empty() {
+            output["hooks"] = serde_json::to_value(&hooks)?;
+        }
+        if !warnings.is_empty() {
+            output["warnings"] = serde_json::json!(warnings);
+        }
+        if !prompt_findings.is_empty() {
+            output["prompt_findings"] = serde_json::to_value(&prompt_findings)?;
+        }
+        println!("{}", json_string(&output)?);
+    } else {
+        println!("Committed: {}", message.lines().next().unwrap_or(""));
+        println!("  Change:  {}", result.change_id);
+        println!("  Commit:  {}", result.commit_id);
+        if !result.files_changed.is_empty() {
+            println!(
+                "  Files:   {} (+{} -{})",
+                result.files_changed.len(),
+                result.stats.additions,
+                result.stats.deletions
+            );
+            for f in &result.files_changed {
+                match result.stats.file(f) {
+                    Some(stat) if stat.binary => println!("    {} (binary)", f),
+                    Some(stat) => println!("    {} +{} -{}", f, stat.additions, stat.deletions),
+                    None => println!("    {}", f),
+                }
+            }
+        }
+        if !result.invariants.is_empty() {
+            println!("  Invariants:");
+            for (name, status) in &result.invariants {
+                println!("    {}: {:?}", name, status);
+            }
+        }
+        for warning in &warnings {
+            println!("  ⚠ {}", warning);
+        }
+    }
+    exit_if_strict_warnings(&warnings);
+
+    Ok(())
+}
+
+/// Rewrite change descriptions: one change to a given message, or a range
+/// regenerated from typed metadata so descriptions match it before a push
+fn cmd_reword(
+    changes: Vec<String>,
+    message: Option<String>,
+    from_typed: bool,
+    since: Option<String>,
+    dry_run: bool,
+    allow_immutable: bool,
+    json: bool,
+) -> Result<()> {
+    let mut repo = Repo::discover()?;
+    repo.snapshot()?;
+    let change_id = |repo: &mut Repo, rev: &str| -> Result<String> {
+        let (_, commit_id) = repo.resolve_revision(rev)?;
+        Ok(repo.commit_change_id(&commit_id)?)
+    };
+
+    let mut skipped = Vec::new();
+    let mut descriptions = Vec::new();
+    let mut typed_updates = Vec::new();
+    if let Some(message) = message {
+        let [rev] = changes.as_slice() else {
+            anyhow::bail!("reword -m takes exactly one change");
+        };
+        let id = change_id(&mut repo, rev)?;
+        // Keep the recorded intent in step with the new description
+        if let Ok(mut typed) = repo.get_typed_change(&id) {
+            let edit = agentjj::change::ChangeEdit {
+                intent: Some(message.trim().to_string()),
+                ..Default::default()
+            };
+            if !typed
+                .apply_edit(&edit, agentjj::audit::current_actor(), chrono_lite_now())
+                .is_empty()
+            {
+                typed_updates.push(typed);
+            }
+        }
+        descriptions.push((id, message));
+    } else if from_typed {
+        let ids = if changes.is_empty() {
+            let since = match since {
+                Some(rev) => Some(repo.resolve_revision(&rev)?.1),
+                None => None,
+            };
+            // Oldest first, as the stack would be read
+            let mut ids = repo.change_ids_since(since.as_deref())?;
+            ids.reverse();
+            ids
+        } else {
+            changes
+                .iter()
+                .map(|rev| change_id(&mut repo, rev))
+                .collect::<Result<_>>()?
+        };
+        // Untyped changes in a range are passed over; named ones are reported
+        let named = !changes.is_empty();
+        for id in ids {
+            match repo.get_typed_change(&id) {
+                Ok(typed) => descriptions.push((id, typed.description())),
+                Err(_) if named => skipped.push(id),
+                Err(_) => {}
+            }
+        }
+    }
+
+    if !allow_immutable {
+        let ids: Vec<String> = descriptions.iter().map(|(id, _)| id.clone()).collect();
+        repo.require_mutable(&ids)?;
+    }
+    let reworded = repo.reword(&descriptions, dry_run)?;
+    if !dry_run {
+        for typed in &typed_updates {
+            repo.save_typed_change(typed)?;
+        }
+    }
+    let unchanged: Vec<&String> = descriptions
+        .iter()
+        .map(|(id, _)| id)
+        .filter(|id| !reworded.iter().any(|r| &r.change_id == *id))
+        .collect();
+
+    if json {
+        println!(
+            "{}",
+            json_string(&serde_json::json!({
+                "dry_run": dry_run,
+                "reworded": reworded,
+                "unchanged": unchanged,
+                "skipped": skipped,
+            }))?
+        );
+        return Ok(());
+    }
+
+    let short = |id: &str| id[..12.min(id.len())].to_string();
+    let verb = if dry_run { "Would reword" } else { "Reworded" };
+    for change in &reworded {
+        println!(
+            "{} {}: {}",
+            verb,
+            short(&change.change_id),
+            change.description.lines().next().unwrap_or_default()
+        );
+        if let Some(previous) = change.previous.lines().next() {
+            println!("  was: {}", previous);
+        }
+    }
+    if reworded.is_empty() {
+        println!("Nothing to reword");
+    }
+    if !unchanged.is_empty() {
+        println!("  {} already up to date", unchanged.len());
+    }
+    if !skipped.is_empty() {
+        println!(
+            "  Skipped (no typed metadata): {}",
+            skipped
+                .iter()
+                .map(|id| short(id))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+    Ok(())
+}
+
+fn cmd_tag(
+    name: String,
+    message: Option<String>,
+    force: bool,
+    push: bool,
+    json: bool,
+) -> Result<()> {
+    let mut repo = Repo::discover()?;
+    repo.require_git("tag")?;
+
+    // Build tag command
+    let mut args = vec!["tag".to_string()];
+
+    if force {
+        args.push("-f".to_string());
+    }
+
+    if let Some(ref msg) = message {
+        args.push("-a".to_string());
+        args.push("-m".to_string());
+        args.push(msg.clone());
+    }
+
+    args.push(name.clone());
+
+    // Create the tag
+    let tag_output = std::process::Command::new("git")
+        .current_dir(repo.root())
+        .args(&args)
+        .output()
+        .map_err(|e| anyhow::anyhow!("Failed to run git tag: {}", e))?;
+
+    if !tag_output.status.success() {
+        let stderr = String::from_utf8_lossy(&tag_output.stderr);
+        anyhow::bail!("Failed to create tag: {}", stderr);
+    }
+
+    // Push tag if requested
+    if push {
+        let mut push_args = vec!["push".to_string(), "origin".to_string()];
+        if force {
+            push_args.push("--force".to_string());
+        }
+        push_args.push(name.clone());
+
+        let push_output = std::process::Command::new("git")
+            .current_dir(repo.root())
+            .args(&push_args)
+            .output()
+            .map_err(|e| anyhow::anyhow!("Failed to push tag: {}", e))?;
+
+        if !push_output.status.success() {
+            let stderr = String::from_utf8_lossy(&push_output.stderr);
+            anyhow::bail!("Failed to push tag: {}", stderr);
+        }
+    }
+
+    if json {
+        let result = serde_json::json!({
+            "tag": name,
+            "pushed": push,
+            "forced": force,
+        });
+        println!("{}", json_string(&result)?);
+    } else if push {
+        println!("✓ Tagged and pushed: {}", name);
+    } else {
+        println!("✓ Tagged: {}", name);
+    }
+
+    Ok(())
+}
+
+/// A `## Notes` section from the working notes of the changes pushing
+/// `head` would send, oldest first; `None` when none has notes
+fn pr_notes(repo: &mut Repo, head: &str) -> Result<Option<String>> {
+    let mut noted: Vec<(String, String)> = Vec::new();
+    for change_id in repo.unpushed_changes(head)?.into_iter().rev() {
+        let Ok(change) = repo.get_typed_change(&change_id) else {
+            continue;
+        };
+        if let Some(notes) = change.notes {
+            let title = match change.intent.lines().next() {
+                Some(line) if !line.trim().is_empty() => line.trim().to_string(),
+                _ => change_id[..12.min(change_id.len())].to_string(),
+            };
+            noted.push((title, notes));
+        }
+    }
+    Ok(match noted.as_slice() {
+        [] => None,
+        [(_, notes)] => Some(format!("## Notes\n\n{}", notes)),
+        all => Some(format!(
+            "## Notes\n\n{}",
+            all.iter()
+                .map(|(title, notes)| format!("### {}\n\n{}", title, notes))
+                .collect::<Vec<_>>()
+                .join("\n\n")
+        )),
+    })
+}
+
+/// Markers around the notes section `push --pr` writes into a PR body, so
+/// a later push replaces that section and leaves the rest alone
+const PR_NOTES_START: &str = "<!-- agentjj:notes -->";
+const PR_NOTES_END: &str = "<!-- /agentjj:notes -->";
+
+/// `body` with its notes section set to `notes`: replaced in place when it
+/// has one, appended otherwise
+fn with_pr_notes(body: &str, notes: &str) -> String {
+    let section = format!("{}\n{}\n{}", PR_NOTES_START, notes, PR_NOTES_END);
+    if let Some(start) = body.find(PR_NOTES_START) {
+        if let Some(end) = body[start..].find(PR_NOTES_END) {
+            let end = start + end + PR_NOTES_END.len();
+            return format!("{}{}{}", &body[..start], section, &body[end..]);
+        }
+    }
+    match body.trim_end() {
+        "" => section,
+        body => format!("{}\n\n{}", body, section),
+    }
+}
+
+/// Output of a git command in the repository, trimmed; None when it fails
+fn git_stdout(repo: &Repo, args: &[&str]) -> Option<String> {
+    let _span =
+        tracing::info_span!("subprocess", cmd = %format!("git {}", args.join(" "))).entered();
+    std::process::Command::new("git")
+        .current_dir(repo.root())
+        .args(args)
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+}
+
+/// Run `gh` with the configured forge token; its stdout, or an error with
+/// its stderr
+fn gh_stdout(repo: &mut Repo, args: &[&str]) -> Result<String> {
+    let token = agentjj::secrets::forge_token(repo.root());
+    let _span =
+        tracing::info_span!("subprocess", cmd = %format!("gh {}", args.join(" "))).entered();
+    let mut cmd = std::process::Command::new("gh");
+    cmd.current_dir(repo.root()).args(args);
+    if let Some(token) = &token {
+        cmd.env("GH_TOKEN", token);
+    }
+    let output = cmd
+        .output()
+        .map_err(|e| anyhow::anyhow!("could not run gh: {}", e))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "gh {} failed: {}",
+            args.iter().take(2).copied().collect::<Vec<_>>().join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// A GraphQL request through `gh api`, against the repository `gh` finds
+/// from the git remotes (`owner` and `name` are filled in)
+fn gh_graphql(repo: &mut Repo, query: &str, vars: &[(&str, &str)]) -> Result<serde_json::Value> {
+    let mut args = vec![
+        "api".to_string(),
+        "graphql".to_string(),
+        "-F".to_string(),
+        "owner={owner}".to_string(),
+        "-F".to_string(),
+        "name={repo}".to_string(),
+        "-f".to_string(),
+        format!("query={}", query),
+    ];
+    for (key, value) in vars {
+        args.extend(["-f".to_string(), format!("{}={}", key, value)]);
+    }
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    Ok(serde_json::from_str(&gh_stdout(repo, &args)?)?)
+}
+
+/// The branch origin's HEAD points at (else main, or master when only it
+/// exists)
+fn default_branch(repo: &Repo) -> String {
+    if let Some(head) = git_stdout(
+        repo,
+        &["symbolic-ref", "--short", "refs/remotes/origin/HEAD"],
+    ) {
+        return head.trim_start_matches("origin/").to_string();
+    }
+    let exists =
+        |name: &str| git_stdout(repo, &["rev-parse", "--verify", "--quiet", name]).is_some();
+    if !exists("refs/remotes/origin/main") && exists("refs/remotes/origin/master") {
+        "master".to_string()
+    } else {
+        "main".to_string()
+    }
+}
+
+/// The merge queue that lands changes on `branch`, if any: bors (with a
+/// bors.toml) for the default branch, else GitHub's, asked through `gh`.
+/// Without `gh` or a GitHub remote there is none.
+fn merge_queue(repo: &mut Repo, branch: &str) -> Option<agentjj::mergequeue::MergeQueue> {
+    use agentjj::mergequeue::{self, MergeQueue, QueueKind};
+
+    if mergequeue::bors_config(repo.root()).is_some() && branch == default_branch(repo) {
+        return Some(MergeQueue {
+            kind: QueueKind::Bors,
+            branch: branch.to_string(),
+            url: None,
+            length: None,
+        });
+    }
+    let response = gh_graphql(repo, mergequeue::QUEUE_QUERY, &[("branch", branch)]).ok()?;
+    mergequeue::parse_queue(&response, branch)
+}
+
+/// Put the branch's PR in `queue`: GitHub's enqueue mutation, or a
+/// `bors r+` comment. Returns the GitHub queue entry.
+fn enqueue_pr(
+    repo: &mut Repo,
+    branch: &str,
+    queue: &agentjj::mergequeue::MergeQueue,
+) -> Result<Option<agentjj::mergequeue::QueueEntry>> {
+    use agentjj::mergequeue::{self, QueueKind};
+
+    match queue.kind {
+        QueueKind::Bors => {
+            gh_stdout(
+                repo,
+                &["pr", "comment", branch, "--body", mergequeue::BORS_ENQUEUE],
+            )?;
+            Ok(None)
+        }
+        QueueKind::Github => {
+            let id = gh_stdout(repo, &["pr", "view", branch, "--json", "id", "--jq", ".id"])?;
+            let response = gh_graphql(repo, mergequeue::ENQUEUE_MUTATION, &[("id", id.trim())])?;
+            if let Some(message) = response["errors"][0]["message"].as_str() {
+                anyhow::bail!("{}", message);
+            }
+            Ok(mergequeue::parse_entry(
+                &response["data"]["enqueuePullRequest"]["mergeQueueEntry"],
+            ))
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn cmd_push(
+    branch: Option<String>,
+    change: Option<String>,
+    create_pr: bool,
+    title: Option<String>,
+    body: Option<String>,
+    target: String,
+    enqueue: bool,
+    force_with_lease: Option<String>,
+    json: bool,
+) -> Result<()> {
+    let mut repo = Repo::discover()?;
+    // Pushing and PRs go through a git remote; a native jj repo has none
+    repo.require_git(if create_pr { "push --pr" } else { "push" })?;
+
+    // Use git directly for colocated repos (which is our primary mode)
+    let branch_name = branch.unwrap_or_else(|| "main".to_string());
+    let remote = "origin";
+
+    // The change to push, resolved through jj: git's HEAD does not follow
+    // rewrites like reword or absorb
+    let change = change.unwrap_or_else(|| "@-".to_string());
+    let (_, commit_sha) = repo.resolve_revision(&change)?;
+
+    // Typed changes may name others that have to land first
+    let unmet = repo.unmet_dependencies(&commit_sha)?;
+    if !unmet.is_empty() {
+        return Err(agentjj::Error::UnmetDependencies {
+            dependencies: unmet,
+        }
+        .into());
+    }
+    // A branch a merge queue lands changes on is not pushed to directly
+    if !create_pr {
+        if let Some(queue) = merge_queue(&mut repo, &branch_name) {
+            return Err(agentjj::Error::MergeQueueRequired {
+                branch: branch_name,
+                queue: queue.kind.as_str().to_string(),
+            }
+            .into());
+        }
+    }
+
+    // Collected before pushing: afterwards the changes count as pushed
+    let pushing = repo.unpushed_changes(&commit_sha)?;
+    let notes = if create_pr {
+        pr_notes(&mut repo, &commit_sha)?
+    } else {
+        None
+    };
+
+    // Where the branch is on the remote now, and where we last saw it
+    let remote_ref = format!("refs/heads/{}", branch_name);
+    let actual = git_stdout(&repo, &["ls-remote", remote, &remote_ref])
+        .ok_or_else(|| anyhow::anyhow!("Failed to reach remote '{}'", remote))?
+        .split_whitespace()
+        .next()
+        .map(str::to_string);
+    let tracking = git_stdout(
+        &repo,
+        &[
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            &format!("refs/remotes/{}/{}", remote, branch_name),
+        ],
+    );
+    let up_to_date = actual.as_deref() == Some(commit_sha.as_str());
+    let fast_forward = actual.as_deref().is_none_or(|sha| {
+        git_stdout(&repo, &["merge-base", "--is-ancestor", sha, &commit_sha]).is_some()
+    });
+    // An earlier version of a change being pushed (after reword, absorb, or
+    // a rebase) is replaced without asking
+    let rewritten = !fast_forward
+        && actual
+            .as_deref()
+            .and_then(|sha| repo.commit_change_id(sha).ok())
+            .is_some_and(|change_id| pushing.contains(&change_id));
+    let forced = !up_to_date && !fast_forward && (force_with_lease.is_some() || rewritten);
+
+    if forced {
+        let expected = match force_with_lease.as_deref() {
+            Some(rev) if !rev.is_empty() => Some(
+                git_stdout(&repo, &["rev-parse", "--verify", "--quiet", rev]).ok_or_else(|| {
+                    anyhow::anyhow!("Unknown commit for --force-with-lease: {}", rev)
+                })?,
+            ),
+            _ => tracking.clone(),
+        };
+        if expected != actual {
+            return Err(agentjj::Error::RemoteMoved {
+                remote: remote.to_string(),
+                branch: branch_name,
+                expected,
+                actual,
+            }
+            .into());
+        }
+    }
+
+    if !up_to_date {
+        let mut args = vec!["push".to_string()];
+        if forced {
+            // Checked above; git checks again in case the remote moves meanwhile
+            args.push(format!(
+                "--force-with-lease={}:{}",
+                remote_ref,
+                actual.as_deref().unwrap_or("")
+            ));
+        }
+        args.push(remote.to_string());
+        args.push(format!("{}:{}", commit_sha, remote_ref));
+        let _span =
+            tracing::info_span!("subprocess", cmd = %format!("git {}", args.join(" "))).entered();
+        let push_output = std::process::Command::new("git")
+            .current_dir(repo.root())
+            .args(&args)
             .output()?;
 
-        if pr_output.status.success() {
-            let pr_url = String::from_utf8_lossy(&pr_output.stdout)
-                .trim()
-                .to_string();
-            result["pr_created"] = serde_json::json!(true);
-            result["pr_url"] = serde_json::json!(pr_url);
+        if !push_output.status.success() {
+            let stderr = String::from_utf8_lossy(&push_output.stderr);
+            anyhow::bail!("Push failed: {}", stderr);
+        }
+    }
+
+    // A local bookmark of the same name follows the pushed change
+    if let Some(bookmark) = repo
+        .local_bookmarks()?
+        .into_iter()
+        .find(|b| b.name == branch_name && b.commit_id != commit_sha)
+    {
+        repo.set_bookmarks(
+            &[(bookmark.name, commit_sha.clone())],
+            &format!("push: move bookmark {}", branch_name),
+        )?;
+    }
+
+    let mut result = serde_json::json!({
+        "pushed": true,
+        "branch": branch_name,
+        "commit": commit_sha,
+        "up_to_date": up_to_date,
+        "forced": forced,
+    });
+    if forced {
+        result["replaced"] = serde_json::json!(actual);
+    }
+
+    if !json {
+        if up_to_date {
+            println!("✓ {} is already at {}", branch_name, &commit_sha[..12]);
+        } else if let Some(replaced) = actual.as_deref().filter(|_| forced) {
+            println!(
+                "✓ Pushed to {} (replaced {})",
+                branch_name,
+                &replaced[..12.min(replaced.len())]
+            );
+        } else {
+            println!("✓ Pushed to {}", branch_name);
+        }
+    }
+
+    // Create PR if requested
+    if create_pr {
+        let token = agentjj::secrets::forge_token(repo.root());
+        let gh = |args: &[String]| {
+            let mut gh = std::process::Command::new("gh");
+            gh.current_dir(repo.root()).args(args);
+            if let Some(token) = &token {
+                gh.env("GH_TOKEN", token);
+            }
+            gh.output()
+        };
+
+        // A branch pushed again keeps its PR: update it rather than open another
+        let existing =
+            gh(&["pr", "view", &branch_name, "--json", "url", "--jq", ".url"].map(String::from))
+                .ok()
+                .filter(|o| o.status.success())
+                .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+                .filter(|url| !url.is_empty());
+
+        // The changes' working notes go in a marked section at the end of the
+        // PR body. An existing PR keeps its description unless --body
+        // replaces it; only the notes section is rewritten.
+        let body = match (body, notes) {
+            (Some(body), Some(notes)) => Some(with_pr_notes(&body, &notes)),
+            (Some(body), None) => Some(body),
+            (None, Some(notes)) => match &existing {
+                Some(_) => gh(&[
+                    "pr",
+                    "view",
+                    &branch_name,
+                    "--json",
+                    "body",
+                    "--jq",
+                    ".body",
+                ]
+                .map(String::from))
+                .ok()
+                .filter(|o| o.status.success())
+                .map(|o| {
+                    let current = String::from_utf8_lossy(&o.stdout);
+                    let current = current.strip_suffix('\n').unwrap_or(&current);
+                    (current.to_string(), with_pr_notes(current, &notes))
+                })
+                .and_then(|(current, body)| (body != current).then_some(body)),
+                None => Some(with_pr_notes("", &notes)),
+            },
+            (None, None) => None,
+        };
+
+        let mut gh_args: Vec<String> = match &existing {
+            Some(_) => {
+                let mut args = ["pr", "edit", &branch_name].map(String::from).to_vec();
+                if let Some(t) = title {
+                    args.extend(["--title".to_string(), t]);
+                }
+                args
+            }
+            None => {
+                let pr_title =
+                    title.ok_or_else(|| anyhow::anyhow!("--title required for PR creation"))?;
+                [
+                    "pr",
+                    "create",
+                    "--head",
+                    &branch_name,
+                    "--base",
+                    &target,
+                    "--title",
+                    &pr_title,
+                ]
+                .map(String::from)
+                .to_vec()
+            }
+        };
+        if let Some(b) = &body {
+            gh_args.extend(["--body".to_string(), b.clone()]);
+        }
+
+        let (done, verb, past) = match existing {
+            Some(_) => ("pr_updated", "update", "Updated"),
+            None => ("pr_created", "create", "Created"),
+        };
+        // An existing PR with nothing to edit: the push already updated it
+        let pr_output = match &existing {
+            Some(_) if gh_args.len() == 3 => None,
+            _ => Some(gh(&gh_args)?),
+        };
+        match pr_output {
+            Some(output) if !output.status.success() => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                result[done] = serde_json::json!(false);
+                result["pr_error"] = serde_json::json!(stderr.to_string());
+
+                if !json {
+                    println!("✗ Failed to {} PR: {}", verb, stderr);
+                }
+            }
+            output => {
+                let pr_url = match (existing, output) {
+                    (Some(url), _) => url,
+                    (None, output) => output
+                        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+                        .unwrap_or_default(),
+                };
+                result[done] = serde_json::json!(true);
+                result["pr_url"] = serde_json::json!(pr_url);
+
+                if !json {
+                    println!("✓ {} PR: {}", past, pr_url);
+                }
+            }
+        }
+
+        // With a merge queue on the target, the queue merges the PR
+        if result[done] == true {
+            let queue = merge_queue(&mut repo, &target);
+            if let Some(queue) = &queue {
+                result["merge_queue"] = serde_json::to_value(queue)?;
+            }
+            match (&queue, enqueue) {
+                (Some(queue), true) => match enqueue_pr(&mut repo, &branch_name, queue) {
+                    Ok(entry) => {
+                        result["enqueued"] = serde_json::json!(true);
+                        if let Some(entry) = &entry {
+                            result["queue_entry"] = serde_json::to_value(entry)?;
+                        }
+                        if !json {
+                            match entry {
+                                Some(entry) => println!(
+                                    "✓ Added to the {} merge queue at position {}",
+                                    target, entry.position
+                                ),
+                                None => println!(
+                                    "✓ Asked bors to merge it ({})",
+                                    agentjj::mergequeue::BORS_ENQUEUE
+                                ),
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        result["enqueued"] = serde_json::json!(false);
+                        result["enqueue_error"] = serde_json::json!(e.to_string());
+                        if !json {
+                            println!("✗ Failed to enqueue: {}", e);
+                        }
+                    }
+                },
+                (None, true) => {
+                    let message = format!("{} has no merge queue", target);
+                    result["enqueued"] = serde_json::json!(false);
+                    result["enqueue_error"] = serde_json::json!(message);
+                    if !json {
+                        println!("✗ Not enqueued: {}", message);
+                    }
+                }
+                (Some(queue), false) => {
+                    if !json {
+                        println!(
+                            "ℹ {} merges through a {} merge queue: add --enqueue to queue the PR",
+                            target,
+                            queue.kind.as_str()
+                        );
+                    }
+                }
+                (None, false) => {}
+            }
+        }
+    }
+
+    if json {
+        println!("{}", json_string(&result)?);
+    }
+
+    Ok(())
+}
+
+/// A branch's PR: its state, where it is in the merge queue, and what
+/// happens to it next
+fn cmd_pr_status(branch: Option<String>, json: bool) -> Result<()> {
+    use agentjj::mergequeue::{self, PrStatus, QueueKind};
+
+    let mut repo = Repo::discover()?;
+    repo.require_git("pr status")?;
+
+    let branch = match branch {
+        Some(branch) => branch,
+        None => {
+            let commits = ["@-", "@"]
+                .iter()
+                .filter_map(|rev| repo.resolve_revision(rev).ok().map(|(_, commit)| commit))
+                .collect::<Vec<_>>();
+            let bookmarks = repo.local_bookmarks()?;
+            commits
+                .iter()
+                .find_map(|commit| bookmarks.iter().find(|b| &b.commit_id == commit))
+                .map(|b| b.name.clone())
+                .ok_or_else(|| agentjj::Error::Config {
+                    message: "no bookmark on @- or @; name the branch: agentjj pr status <branch>"
+                        .into(),
+                })?
+        }
+    };
+
+    let response = gh_graphql(&mut repo, mergequeue::PR_QUERY, &[("branch", &branch)])?;
+    let base = response["data"]["repository"]["pullRequests"]["nodes"][0]["baseRefName"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| anyhow::anyhow!("No pull request for branch {}", branch))?;
+    let queue = merge_queue(&mut repo, &base);
+    let bors_queued = queue.as_ref().is_some_and(|q| q.kind == QueueKind::Bors)
+        && gh_stdout(
+            &mut repo,
+            &[
+                "pr",
+                "view",
+                &branch,
+                "--json",
+                "comments",
+                "--jq",
+                ".comments[].body",
+            ],
+        )
+        .is_ok_and(|comments| mergequeue::bors_queued([comments.as_str()]));
+    let status = PrStatus::from_response(&response, &branch, queue, bors_queued)
+        .ok_or_else(|| anyhow::anyhow!("No pull request for branch {}", branch))?;
+
+    if json {
+        println!("{}", json_string(&status)?);
+        return Ok(());
+    }
+
+    println!(
+        "PR #{} {} -> {} ({}{}): {}",
+        status.number,
+        status.branch,
+        status.base,
+        status.state,
+        if status.draft { ", draft" } else { "" },
+        status.url
+    );
+    if let Some(queue) = &status.merge_queue {
+        let place = match (&status.queue_entry, queue.length) {
+            (Some(entry), Some(length)) => {
+                format!("position {} of {}, {}", entry.position, length, entry.state)
+            }
+            (Some(entry), None) => format!("position {}, {}", entry.position, entry.state),
+            (None, _) if status.queued => "queued".to_string(),
+            (None, _) => "not queued".to_string(),
+        };
+        let eta = status
+            .queue_entry
+            .as_ref()
+            .and_then(|e| e.estimated_time_to_merge_secs)
+            .map(|secs| format!(", ~{} min to merge", secs.div_ceil(60)))
+            .unwrap_or_default();
+        println!("  Merge queue ({}): {}{}", queue.kind.as_str(), place, eta);
+    }
+    if let Some(merge_state) = &status.merge_state {
+        println!("  Merge state: {}", merge_state);
+    }
+    println!("  Expected: {}", status.expected.replace('_', " "));
+    if status.expected == "needs_enqueue" {
+        println!(
+            "  Queue it: agentjj push --branch {} --pr --enqueue --target {}",
+            status.branch, status.base
+        );
+    }
+    Ok(())
+}
+
+/// Complete repository orientation - everything an agent needs to start working
+/// Source files of the repository for codebase scans (orient, stats):
+/// everything under the root except VCS/tool state, build output, and
+/// `index.exclude` paths. Empty when `index.enabled` is off.
+fn codebase_files(repo: &mut Repo) -> Result<Vec<std::path::PathBuf>> {
+    let index_config = repo.config()?.index.clone();
+    if !index_config.enabled {
+        return Ok(Vec::new());
+    }
+
+    // Skipped even when not gitignored
+    let mut filters = agentjj::repo::WalkFilters::for_repo(repo)?;
+    filters.exclude.extend(
+        [
+            "target/",
+            "node_modules/",
+            ".agent/",
+            "__pycache__",
+            ".pyc",
+            "venv/",
+            ".venv/",
+        ]
+        .map(String::from),
+    );
+    Ok(agentjj::repo::walk_files(repo.root(), filters).collect())
+}
+
+fn cmd_orient(json: bool) -> Result<()> {
+    let mut repo = Repo::discover()?;
+
+    let change_id = repo
+        .current_change_id()
+        .unwrap_or_else(|_| "unknown".into());
+    let operation_id = repo
+        .current_operation_id()
+        .unwrap_or_else(|_| "unknown".into());
+    let files = repo.changed_files(&change_id).unwrap_or_default();
+    let has_manifest = repo.has_manifest();
+
+    // Get manifest info
+    let manifest_info = if has_manifest {
+        repo.manifest().ok().map(|m| {
+            serde_json::json!({
+                "name": m.repo.name,
+                "description": m.repo.description,
+                "languages": m.repo.languages,
+                "invariants_count": m.invariants.len(),
+                "custom_commands": agentjj::custom::list(&m.commands.custom)
+                    .into_iter()
+                    .map(|c| serde_json::json!({ "usage": c.usage, "description": c.description }))
+                    .collect::<Vec<_>>(),
+                "permissions": {
+                    "allow": m.permissions.allow_change,
+                    "deny": m.permissions.deny_change,
+                },
+            })
+        })
+    } else {
+        None
+    };
+
+    // Count files by extension
+    let mut file_counts: std::collections::HashMap<String, usize> =
+        std::collections::HashMap::new();
+    let codebase = codebase_files(&mut repo)?;
+    let total_files = codebase.len();
+    for entry in &codebase {
+        if let Some(ext) = entry.extension() {
+            *file_counts
+                .entry(ext.to_string_lossy().to_string())
+                .or_insert(0) += 1;
+        }
+    }
+
+    // Get recent changes via jj-lib (no jj CLI dependency)
+    let recent_changes: Vec<serde_json::Value> = repo
+        .log_entries(5, false)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|entry| {
+            serde_json::json!({
+                "change_id": entry.change_id,
+                "description": if entry.description.is_empty() {
+                    "(no description)".to_string()
+                } else {
+                    entry.description
+                },
+            })
+        })
+        .collect();
+
+    // Get typed changes
+    let typed_changes = agentjj::change::ChangeIndex::load_from_repo(repo.root())
+        .ok()
+        .map(|idx| idx.all().len())
+        .unwrap_or(0);
+
+    // Detect how to build, test, and run the project
+    let tooling = agentjj::tooling::detect(repo.root());
+
+    // Notes left by earlier sessions, with those on files being changed now
+    let notes = agentjj::notes::NoteStore::open(repo.root())
+        .list()
+        .unwrap_or_default();
+    let notes_on_changed: Vec<_> = notes.iter().filter(|n| files.contains(&n.file)).collect();
+
+    // Recorded answers: the newest still valid, and those the code has moved past
+    let qa = qa_entries(&mut repo).unwrap_or_default();
+    let (qa_valid, qa_stale): (Vec<_>, Vec<_>) =
+        qa.iter().partition(|(_, changed)| changed.is_empty());
+
+    let orientation = serde_json::json!({
+        "current_state": {
+            "change_id": change_id,
+            "operation_id": &operation_id[..32.min(operation_id.len())],
+            "uncommitted_files": files,
+        },
+        "repository": manifest_info,
+        "codebase": {
+            "total_files": total_files,
+            "agentignore": agentjj::agentignore::AgentIgnore::load(repo.root())?.is_active(),
+            "by_extension": file_counts,
+            "typed_changes": typed_changes,
+        },
+        "tooling": {
+            "build_systems": tooling.build_systems,
+            "test_command": tooling.test_command(),
+            "ci_providers": tooling.ci_providers,
+            "entry_points": tooling.entry_points,
+        },
+        "how_to": tooling.how_to,
+        "recent_changes": recent_changes,
+        "notes": {
+            "count": notes.len(),
+            "on_uncommitted_files": notes_on_changed,
+        },
+        "qa": {
+            "count": qa.len(),
+            "recent": qa_valid.iter().take(5).map(|(e, c)| qa_json(e, c)).collect::<Vec<_>>(),
+            "stale_count": qa_stale.len(),
+            "stale": qa_stale.iter().take(5).map(|(e, c)| qa_json(e, c)).collect::<Vec<_>>(),
+        },
+        "capabilities": {
+            "symbol_query": agentjj::SupportedLanguage::ALL.iter().map(|l| l.name()).collect::<Vec<_>>(),
+            "commands": [
+                "status", "read", "symbol", "context", "apply", "edit",
+                "change", "commit", "push", "orient", "checkpoint", "undo",
+                "bulk", "files", "diff", "affected", "validate", "suggest",
+                "graph", "tag", "schema", "skill", "quickstart", "config",
+                "replay", "gc", "stats", "dupes", "todos", "approvals", "run"
+            ],
+            "read_only": READ_ONLY.load(Ordering::Relaxed),
+        },
+        "quick_start": {
+            "read_file": "agentjj read <path>",
+            "query_symbol": "agentjj symbol <file>::<name>",
+            "get_context": "agentjj context <file>::<name>",
+            "commit_changes": "agentjj commit -m 'feat: description'",
+            "make_change": "agentjj apply --intent '...' --type behavioral --patch <file>",
+            "save_checkpoint": "agentjj checkpoint create <name>",
+        },
+    });
+
+    if json {
+        println!("{}", json_string(&orientation)?);
+    } else {
+        println!("=== Repository Orientation ===\n");
+        println!("Current change: {}", &change_id[..12.min(change_id.len())]);
+        if !files.is_empty() {
+            println!("Uncommitted: {} files", files.len());
+        }
+        println!();
+
+        if let Some(info) = &manifest_info {
+            println!("Project: {}", info["name"]);
+            if !info["description"].as_str().unwrap_or("").is_empty() {
+                println!("  {}", info["description"]);
+            }
+        }
+
+        println!("\nCodebase: {} files", total_files);
+        let mut sorted_counts: Vec<_> = file_counts.iter().collect();
+        sorted_counts.sort_by(|a, b| b.1.cmp(a.1));
+        for (ext, count) in sorted_counts.iter().take(5) {
+            println!("  .{}: {}", ext, count);
+        }
+
+        if !tooling.build_systems.is_empty() {
+            let names: Vec<_> = tooling
+                .build_systems
+                .iter()
+                .map(|b| b.name.as_str())
+                .collect();
+            println!("\nTooling: {}", names.join(", "));
+            if !tooling.ci_providers.is_empty() {
+                println!("  CI: {}", tooling.ci_providers.join(", "));
+            }
+            for entry in tooling.entry_points.iter().take(5) {
+                match &entry.path {
+                    Some(path) => println!("  {} {} ({})", entry.kind, entry.name, path),
+                    None => println!("  {} {}", entry.kind, entry.name),
+                }
+            }
+            let how_to = [
+                ("build", &tooling.how_to.build),
+                ("test", &tooling.how_to.test),
+                ("lint", &tooling.how_to.lint),
+                ("run", &tooling.how_to.run),
+            ];
+            for (label, command) in how_to {
+                if let Some(command) = command {
+                    println!("  {:<6} {}", format!("{}:", label), command);
+                }
+            }
+        }
+
+        if !recent_changes.is_empty() {
+            println!("\nRecent changes:");
+            for c in recent_changes.iter().take(3) {
+                let cid = c["change_id"].as_str().unwrap_or("");
+                let short_id = &cid[..8.min(cid.len())];
+                println!("  {} {}", short_id, c["description"]);
+            }
+        }
+
+        if !notes.is_empty() {
+            println!(
+                "\nNotes: {} (agentjj note list), {} on uncommitted files",
+                notes.len(),
+                notes_on_changed.len()
+            );
+            for note in &notes_on_changed {
+                print_note(note);
+            }
+        }
+
+        if !qa.is_empty() {
+            println!(
+                "\nRecorded answers: {} ({} stale; agentjj qa search <words>)",
+                qa.len(),
+                qa_stale.len()
+            );
+            for (entry, _) in qa_valid.iter().take(3) {
+                println!("  {} Q: {}", entry.id, entry.question);
+            }
+            for (entry, changed) in qa_stale.iter().take(3) {
+                println!(
+                    "  {} Q: {} [stale: {}]",
+                    entry.id,
+                    entry.question,
+                    changed.join(", ")
+                );
+            }
+        }
+
+        println!("\n=== Quick Start ===");
+        println!("  agentjj symbol <file>           # List symbols in file");
+        println!("  agentjj context <file>::<name>  # Get symbol context");
+        println!("  agentjj bulk read <files...>    # Read multiple files");
+        println!("  agentjj checkpoint create <name> # Save restore point");
+    }
+
+    Ok(())
+}
+
+/// Create a named checkpoint
+fn cmd_checkpoint(name: String, description: Option<String>, json: bool) -> Result<()> {
+    let mut repo = Repo::discover()?;
+    let checkpoint = create_checkpoint(&mut repo, &name, description)?;
+    let change_id = checkpoint["change_id"].as_str().unwrap_or_default();
+
+    if json {
+        println!(
+            "{}",
+            json_string(&serde_json::json!({
+                "created": true,
+                "checkpoint": checkpoint,
+                "restore_command": format!("agentjj undo --to {}", name),
+            }))?
+        );
+    } else {
+        println!("✓ Checkpoint '{}' created", name);
+        println!("  change: {}", &change_id[..12.min(change_id.len())]);
+        println!("  restore with: agentjj undo --to {}", name);
+    }
+
+    Ok(())
+}
+
+/// Snapshot the working copy and record it as checkpoint `name` in
+/// `.agent/checkpoints/`
+fn create_checkpoint(
+    repo: &mut Repo,
+    name: &str,
+    description: Option<String>,
+) -> Result<serde_json::Value> {
+    // Record uncommitted work in the operation log so the checkpoint
+    // captures what is actually on disk
+    let operation_id = repo.snapshot()?;
+    let change_id = repo.current_change_id()?;
+
+    // Store checkpoint as a file in .agent/checkpoints/
+    let checkpoints_dir = repo.root().join(".agent/checkpoints");
+    std::fs::create_dir_all(&checkpoints_dir)?;
+
+    let checkpoint = serde_json::json!({
+        "name": name,
+        "description": description,
+        "change_id": change_id,
+        "operation_id": operation_id,
+        "created_at": chrono_lite_now(),
+    });
+
+    let checkpoint_path = checkpoints_dir.join(format!("{}.json", name));
+    std::fs::write(&checkpoint_path, serde_json::to_string_pretty(&checkpoint)?)?;
+    Ok(checkpoint)
+}
+
+/// List all checkpoints sorted by created_at descending
+fn cmd_checkpoint_list(json: bool) -> Result<()> {
+    let repo = Repo::discover()?;
+    let checkpoints_dir = repo.root().join(".agent/checkpoints");
+
+    if !checkpoints_dir.exists() || !checkpoints_dir.is_dir() {
+        if json {
+            println!(
+                "{}",
+                json_string(&serde_json::json!({
+                    "checkpoints": []
+                }))?
+            );
+        } else {
+            println!("No checkpoints found.");
+        }
+        return Ok(());
+    }
+
+    let mut checkpoints: Vec<serde_json::Value> = Vec::new();
+
+    for entry in std::fs::read_dir(&checkpoints_dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) == Some("json") {
+            let content = std::fs::read_to_string(&path)?;
+            if let Ok(checkpoint) = serde_json::from_str::<serde_json::Value>(&content) {
+                checkpoints.push(checkpoint);
+            }
+        }
+    }
+
+    if checkpoints.is_empty() {
+        if json {
+            println!(
+                "{}",
+                json_string(&serde_json::json!({
+                    "checkpoints": []
+                }))?
+            );
+        } else {
+            println!("No checkpoints found.");
+        }
+        return Ok(());
+    }
+
+    // Sort by created_at descending
+    checkpoints.sort_by(|a, b| {
+        let a_time = a["created_at"].as_str().unwrap_or("");
+        let b_time = b["created_at"].as_str().unwrap_or("");
+        b_time.cmp(a_time)
+    });
+
+    if json {
+        println!(
+            "{}",
+            json_string(&serde_json::json!({
+                "checkpoints": checkpoints
+            }))?
+        );
+    } else {
+        println!("Checkpoints:");
+        for cp in &checkpoints {
+            let name = cp["name"].as_str().unwrap_or("(unknown)");
+            let created_at = cp["created_at"].as_str().unwrap_or("");
+            // Format timestamp for display: "2026-02-14T10:23:15Z" -> "2026-02-14 10:23:15"
+            let display_time = created_at
+                .replace('T', " ")
+                .trim_end_matches('Z')
+                .to_string();
+            let description = cp["description"]
+                .as_str()
+                .map(|d| format!("\"{}\"", d))
+                .unwrap_or_else(|| "(no description)".to_string());
+            println!("  {:<30} {}  {}", name, display_time, description);
+        }
+    }
+
+    Ok(())
+}
+
+/// Park working-copy changes in a hidden change
+fn cmd_stash_push(name: Option<String>, message: Option<String>, json: bool) -> Result<()> {
+    let mut repo = Repo::discover()?;
+    let store = agentjj::stash::StashStore::open(repo.root());
+    let name = name.unwrap_or_else(|| store.next_name());
+    if !agentjj::stash::valid_name(&name) {
+        anyhow::bail!(
+            "Invalid stash name '{}': use letters, digits, '.', '-' and '_'",
+            name
+        );
+    }
+    if store.contains(&name) {
+        anyhow::bail!("Stash '{}' already exists", name);
+    }
+
+    let entry = repo.stash_push(&name, message.as_deref())?;
+    store.save(&entry)?;
+
+    if json {
+        println!(
+            "{}",
+            json_string(&serde_json::json!({
+                "stashed": true,
+                "stash": entry,
+                "pop_command": format!("agentjj stash pop {}", name),
+            }))?
+        );
+    } else {
+        println!("✓ Stashed {} file(s) as '{}'", entry.files.len(), name);
+        println!(
+            "  change: {}",
+            &entry.change_id[..12.min(entry.change_id.len())]
+        );
+        println!("  reapply with: agentjj stash pop {}", name);
+    }
+
+    Ok(())
+}
+
+/// Reapply a stash, keeping it when the merge leaves conflicts
+fn cmd_stash_pop(name: Option<String>, json: bool) -> Result<()> {
+    let mut repo = Repo::discover()?;
+    let store = agentjj::stash::StashStore::open(repo.root());
+    let entry = match name {
+        Some(name) => store.get(&name)?,
+        None => {
+            let session = agentjj::audit::current_session();
+            store
+                .list()
+                .into_iter()
+                .find(|e| e.session == session)
+                .ok_or_else(|| anyhow::anyhow!("No stashes for session '{}'", session))?
+        }
+    };
+
+    let popped = repo.stash_pop(&entry)?;
+    let kept = !popped.conflicts.is_empty();
+    if !kept {
+        store.remove(&entry.name)?;
+    }
+
+    if json {
+        println!(
+            "{}",
+            json_string(&serde_json::json!({
+                "popped": true,
+                "name": popped.name,
+                "change_id": popped.change_id,
+                "files": popped.files,
+                "conflicts": popped.conflicts,
+                "kept": kept,
+            }))?
+        );
+    } else {
+        println!("✓ Applied stash '{}'", popped.name);
+        for path in &popped.conflicts {
+            println!("  ✗ conflict: {}", path);
+        }
+        if kept {
+            println!(
+                "  stash kept; resolve the conflicts, then: agentjj stash drop {}",
+                popped.name
+            );
+        }
+    }
+
+    if kept {
+        exit_with(exit::CONFLICT, None);
+    }
+    Ok(())
+}
+
+/// Forget a stash record
+fn cmd_stash_drop(name: String, json: bool) -> Result<()> {
+    let repo = Repo::discover()?;
+    let store = agentjj::stash::StashStore::open(repo.root());
+    let entry = store.get(&name)?;
+    store.remove(&name)?;
+
+    if json {
+        println!(
+            "{}",
+            json_string(&serde_json::json!({
+                "dropped": true,
+                "stash": entry,
+            }))?
+        );
+    } else {
+        println!("✓ Dropped stash '{}'", name);
+        println!(
+            "  commit: {}",
+            &entry.commit_id[..12.min(entry.commit_id.len())]
+        );
+    }
+
+    Ok(())
+}
+
+/// List stashes, newest first
+fn cmd_stash_list(session: Option<String>, json: bool) -> Result<()> {
+    let repo = Repo::discover()?;
+    let stashes: Vec<agentjj::stash::StashEntry> = agentjj::stash::StashStore::open(repo.root())
+        .list()
+        .into_iter()
+        .filter(|e| session.as_ref().is_none_or(|s| &e.session == s))
+        .collect();
+
+    if json {
+        println!(
+            "{}",
+            json_string(&serde_json::json!({ "stashes": stashes }))?
+        );
+    } else if stashes.is_empty() {
+        println!("No stashes found.");
+    } else {
+        println!("Stashes:");
+        for stash in &stashes {
+            let display_time = stash
+                .created_at
+                .replace('T', " ")
+                .trim_end_matches('Z')
+                .to_string();
+            println!(
+                "  {:<20} {}  [{}]  {} file(s){}",
+                stash.name,
+                display_time,
+                stash.session,
+                stash.files.len(),
+                stash
+                    .message
+                    .as_ref()
+                    .map(|m| format!("  \"{}\"", m))
+                    .unwrap_or_default()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn cmd_env_list(json: bool) -> Result<()> {
+    let mut repo = Repo::discover()?;
+    let bookmarks = repo.local_bookmarks()?;
+    let statuses = agentjj::env::list(&repo.manifest()?.environments, &bookmarks);
+
+    if json {
+        println!(
+            "{}",
+            json_string(&serde_json::json!({ "environments": statuses }))?
+        );
+    } else if statuses.is_empty() {
+        println!("No environments configured (add [environments] to .agent/manifest.toml)");
+    } else {
+        println!("Environments:");
+        for env in &statuses {
+            let frozen = match (&env.frozen, &env.freeze_reason) {
+                (true, Some(reason)) => format!("  [frozen: {}]", reason),
+                (true, None) => "  [frozen]".to_string(),
+                _ => String::new(),
+            };
+            println!("  {:<12} {}{}", env.name, env.bookmark, frozen);
+            if env.deployed.is_empty() {
+                println!("    (nothing deployed)");
+            }
+            for b in &env.deployed {
+                println!(
+                    "    {:<20} {}  {}",
+                    b.name,
+                    &b.change_id[..12.min(b.change_id.len())],
+                    b.description
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn cmd_env_promote(from: String, to: String, dry_run: bool, json: bool) -> Result<()> {
+    let mut repo = Repo::discover()?;
+    let manifest = repo.manifest()?.clone();
+
+    let (environment, bookmark) = agentjj::env::resolve_target(&manifest.environments, &to)?;
+    let (from_environment, rev) = agentjj::env::resolve_source(&manifest.environments, &from)?;
+    let check = |override_freeze| {
+        agentjj::env::check_promotion(
+            &manifest,
+            &environment,
+            &bookmark,
+            from_environment.as_deref(),
+            override_freeze,
+        )
+    };
+    match check(false) {
+        // A freeze is the one block a human can lift; the other checks still apply
+        Err(blocked @ agentjj::Error::PromotionBlocked { .. }) if check(true).is_ok() => {
+            confirmation_gate(&mut repo, Gate::Freeze, blocked)?
+        }
+        checked => checked?,
+    }
+
+    let (_, commit_id) = repo.resolve_revision(&rev)?;
+    let bookmarks = repo.local_bookmarks()?;
+    let previous = bookmarks.iter().find(|b| b.name == bookmark).cloned();
+    let change_id = repo.commit_change_id(&commit_id)?;
+    if !dry_run {
+        repo.set_bookmarks(
+            &[(bookmark.clone(), commit_id.clone())],
+            &format!("env promote {}", bookmark),
+        )?;
+    }
+
+    let promotion = agentjj::env::Promotion {
+        environment,
+        bookmark,
+        from,
+        from_environment,
+        change_id,
+        commit_id,
+        previous,
+        dry_run,
+    };
+
+    if json {
+        println!("{}", json_string(&promotion)?);
+    } else {
+        let verb = if dry_run { "Would move" } else { "✓ Moved" };
+        println!(
+            "{} {} ({}) to {} from {}",
+            verb,
+            promotion.bookmark,
+            promotion.environment,
+            &promotion.change_id[..12.min(promotion.change_id.len())],
+            promotion.from
+        );
+        if let Some(prev) = &promotion.previous {
+            println!(
+                "  previously {}",
+                &prev.change_id[..12.min(prev.change_id.len())]
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Load a checkpoint record from .agent/checkpoints/<name>.json
+fn load_checkpoint(repo: &Repo, name: &str) -> Result<serde_json::Value> {
+    let checkpoint_path = repo
+        .root()
+        .join(".agent/checkpoints")
+        .join(format!("{}.json", name));
+
+    if !checkpoint_path.exists() {
+        anyhow::bail!("Checkpoint '{}' not found", name);
+    }
+
+    Ok(serde_json::from_str(&std::fs::read_to_string(
+        &checkpoint_path,
+    )?)?)
+}
+
+/// Report what changed since a checkpoint so the caller can judge whether
+/// restoring it would throw away valuable work
+fn cmd_checkpoint_status(name: String, no_invariants: bool, json: bool) -> Result<()> {
+    let mut repo = Repo::discover()?;
+    let checkpoint = load_checkpoint(&repo, &name)?;
+    let checkpoint_op = checkpoint["operation_id"]
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("Invalid checkpoint: missing operation_id"))?;
+
+    let diff = repo.diff_since_operation(checkpoint_op)?;
+
+    let invariants = if no_invariants || !repo.has_manifest() {
+        None
+    } else {
+        Some(repo.invariant_report(agentjj::manifest::InvariantTrigger::PreCommit)?)
+    };
+
+    let has_new_work = !diff.changes_created.is_empty()
+        || !diff.changes_rewritten.is_empty()
+        || !diff.files_modified.is_empty();
+
+    if json {
+        println!(
+            "{}",
+            json_string(&serde_json::json!({
+                "checkpoint": checkpoint,
+                "operation_found": diff.found,
+                "operations_since": diff.operations_since.iter().map(|op| serde_json::json!({
+                    "id": op.id,
+                    "description": op.description,
+                })).collect::<Vec<_>>(),
+                "changes": {
+                    "created": diff.changes_created,
+                    "rewritten": diff.changes_rewritten,
+                    "abandoned": diff.changes_abandoned,
+                },
+                "files_modified": diff.files_modified.iter().map(|(path, kind)| serde_json::json!({
+                    "path": path,
+                    "status": kind.as_str(),
+                })).collect::<Vec<_>>(),
+                "invariants": invariants,
+                "restore_loses_work": has_new_work,
+                "restore_command": format!("agentjj undo --to {}", name),
+            }))?
+        );
+    } else {
+        println!("Since checkpoint '{}':", name);
+        if !diff.found {
+            println!("  ⚠ checkpoint operation is not an ancestor of the current operation");
+        }
+        println!("  {} operation(s)", diff.operations_since.len());
+        for op in diff.operations_since.iter().take(10) {
+            println!("    {} {}", &op.id[..12.min(op.id.len())], op.description);
+        }
+        println!(
+            "  changes: {} created, {} rewritten, {} abandoned",
+            diff.changes_created.len(),
+            diff.changes_rewritten.len(),
+            diff.changes_abandoned.len()
+        );
+        if !diff.files_modified.is_empty() {
+            println!("  files modified:");
+            for (path, kind) in &diff.files_modified {
+                println!("    {:<8} {}", kind.as_str(), path);
+            }
+        }
+        if let Some(results) = &invariants {
+            println!("  invariants:");
+            for (inv, status) in results {
+                println!("    {}: {:?}", inv, status);
+            }
+        }
+        if has_new_work {
+            println!("\n⚠ Restoring would discard the work listed above");
+        } else {
+            println!("\nNothing would be lost by restoring");
+        }
+    }
+
+    Ok(())
+}
+
+fn chrono_lite_now() -> String {
+    chrono_lite_days_ago(0)
+}
+
+/// A `change list` time bound: `Nd` for N days ago, else a date or
+/// timestamp prefix such as `2026-01` or `2026-01-15`
+fn parse_change_time(value: &str) -> Result<String> {
+    if let Some(days) = value.strip_suffix('d').and_then(|d| d.parse().ok()) {
+        return Ok(chrono_lite_days_ago(days));
+    }
+    let valid = value.len() >= 4
+        && value.bytes().take(4).all(|b| b.is_ascii_digit())
+        && value
+            .bytes()
+            .all(|b| b.is_ascii_digit() || matches!(b, b'-' | b'T' | b':' | b'Z'));
+    if !valid {
+        anyhow::bail!(
+            "invalid time '{}': use a date like 2026-01-15 or a day count like 7d",
+            value
+        );
+    }
+    Ok(value.to_string())
+}
+
+/// UTC timestamp `days` days before now, in the same format as `chrono_lite_now`
+fn chrono_lite_days_ago(days: u64) -> String {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    let duration = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = duration.as_secs().saturating_sub(days * 86400) as i64;
+    agentjj::repo::utc_timestamp(secs)
+}
+
+/// Undo operations or restore to checkpoint
+fn cmd_rollback(change: String, strategy: Option<String>, execute: bool, json: bool) -> Result<()> {
+    use agentjj::rollback::{self, Strategy};
+
+    let mut repo = Repo::discover()?;
+    repo.snapshot()?;
+    let facts = repo.rollback_facts(&change)?;
+    let plan = rollback::plan(&facts);
+    let short = |id: &str| id[..12.min(id.len())].to_string();
+
+    if !execute {
+        if json {
+            println!(
+                "{}",
+                json_string(&serde_json::json!({
+                    "executed": false,
+                    "plan": plan,
+                }))?
+            );
+        } else {
+            println!(
+                "Rollback plan for {} \"{}\"",
+                short(&plan.change_id),
+                plan.description
+            );
+            let radius = &plan.blast_radius;
+            println!(
+                "  Blast radius: {} file(s), {} bookmark(s), {} descendant change(s){}",
+                radius.files.len(),
+                radius.bookmarks.len(),
+                radius.descendants.len(),
+                if radius.working_copy_affected {
+                    ", working copy builds on it"
+                } else {
+                    ""
+                }
+            );
+            for b in &radius.bookmarks {
+                println!("    bookmark {} ({})", b.name, short(&b.change_id));
+            }
+            for d in &radius.descendants {
+                println!("    descendant {} {}", short(&d.change_id), d.description);
+            }
+            for s in &plan.strategies {
+                let tag = if Some(s.strategy) == plan.recommended {
+                    " (recommended)"
+                } else {
+                    ""
+                };
+                println!();
+                println!("  {:?}{}", s.strategy, tag);
+                if let Some(reason) = &s.blocked_by {
+                    println!("    not applicable: {}", reason);
+                }
+                for effect in &s.effects {
+                    println!("    - {}", effect);
+                }
+            }
+            println!();
+            println!("Rerun with --execute (and optionally --strategy) to carry it out");
+        }
+        return Ok(());
+    }
+
+    let strategy = match strategy {
+        Some(s) => {
+            Strategy::parse(&s).ok_or_else(|| anyhow::anyhow!("unknown strategy '{}'", s))?
+        }
+        None => plan
+            .recommended
+            .ok_or_else(|| agentjj::Error::PreconditionFailed {
+                reason: "no rollback strategy applies".into(),
+                expected: "an applicable strategy".into(),
+                actual: plan
+                    .strategies
+                    .iter()
+                    .filter_map(|s| s.blocked_by.clone())
+                    .collect::<Vec<_>>()
+                    .join("; "),
+            })?,
+    };
+    let chosen = plan.strategy(strategy);
+    if let Some(reason) = &chosen.blocked_by {
+        return Err(agentjj::Error::PreconditionFailed {
+            reason: format!("cannot roll back with {:?}", strategy).to_lowercase(),
+            expected: "an applicable strategy".into(),
+            actual: reason.clone(),
+        }
+        .into());
+    }
+
+    let original = repo.get_typed_change(&facts.change_id).ok();
+    let intent = format!(
+        "Roll back {}: {}",
+        short(&facts.change_id),
+        original
+            .as_ref()
+            .map(|c| c.intent.clone())
+            .unwrap_or_else(|| facts.description.clone())
+    );
+    match strategy {
+        Strategy::Revert => {
+            repo.revert_into_working_copy(&facts.commit_id, &intent)?;
+        }
+        Strategy::Reset => {
+            let parent = facts.parent_commit_id.clone().unwrap_or_default();
+            let targets: Vec<(String, String)> = facts
+                .bookmarks
+                .iter()
+                .map(|b| (b.name.clone(), parent.clone()))
+                .collect();
+            repo.set_bookmarks(&targets, &format!("rollback {} (reset)", facts.change_id))?;
+        }
+        Strategy::Restore => {
+            let (op, _) = facts.restore_point.as_ref().expect("applicable restore");
+            repo.restore_operation(op)?;
+            repo.check_out_working_copy()?;
+        }
+    }
+
+    // Link the rollback to the original through the current change's metadata
+    let strategy_name = serde_json::to_value(strategy)?
+        .as_str()
+        .unwrap_or_default()
+        .to_string();
+    // (reset and restore leave `@` on an existing change; its own metadata wins)
+    let rollback_change_id = repo.current_change_id()?;
+    let typed = if repo.get_typed_change(&rollback_change_id).is_ok() {
+        None
+    } else {
+        let mut typed = TypedChange::new(
+            rollback_change_id.clone(),
+            original
+                .as_ref()
+                .map(|c| c.change_type)
+                .unwrap_or(ChangeType::Behavioral),
+            intent,
+        )
+        .with_category(ChangeCategory::Fix)
+        .with_files(facts.files.clone());
+        typed
+            .metadata
+            .insert("rolls_back".into(), facts.change_id.clone());
+        typed
+            .metadata
+            .insert("rollback_strategy".into(), strategy_name.clone());
+        repo.save_typed_change(&typed)?;
+        Some(typed)
+    };
+
+    if json {
+        println!(
+            "{}",
+            json_string(&serde_json::json!({
+                "executed": true,
+                "strategy": strategy_name,
+                "change_id": rollback_change_id,
+                "typed_change": typed,
+                "plan": plan,
+            }))?
+        );
+    } else {
+        println!(
+            "✓ Rolled back {} ({})",
+            short(&facts.change_id),
+            strategy_name
+        );
+        if typed.is_some() {
+            println!("  Recorded as fix change {}", short(&rollback_change_id));
+        }
+        if strategy == Strategy::Revert {
+            println!("  Review the working copy and `agentjj commit` to keep the rollback");
+        }
+    }
+
+    Ok(())
+}
+
+fn cmd_undo(
+    steps: usize,
+    to: Option<String>,
+    dry_run: bool,
+    paths: Option<Vec<String>>,
+    json: bool,
+) -> Result<()> {
+    let mut repo = Repo::discover()?;
+
+    // If --to is specified, restore to named checkpoint
+    if let Some(checkpoint_name) = to {
+        let checkpoint_data = load_checkpoint(&repo, &checkpoint_name)?;
+        let target_op = checkpoint_data["operation_id"]
+            .as_str()
+            .ok_or_else(|| anyhow::anyhow!("Invalid checkpoint: missing operation_id"))?;
+
+        if let Some(paths) = paths {
+            return cmd_undo_paths(
+                &mut repo,
+                &checkpoint_name,
+                target_op,
+                &paths,
+                dry_run,
+                json,
+            );
+        }
+
+        if dry_run {
+            if json {
+                println!(
+                    "{}",
+                    json_string(&serde_json::json!({
+                        "dry_run": true,
+                        "checkpoint": checkpoint_name,
+                        "would_restore_to": target_op,
+                        "checkpoint_data": checkpoint_data,
+                    }))?
+                );
+            } else {
+                println!("Would restore to checkpoint '{}'", checkpoint_name);
+                println!(
+                    "Would restore to operation: {}...",
+                    &target_op[..16.min(target_op.len())]
+                );
+            }
+            return Ok(());
+        }
+
+        // Restore to checkpoint operation using Repo method
+        repo.restore_operation(target_op)?;
+
+        let mut result = serde_json::json!({
+            "restored": true,
+            "checkpoint": checkpoint_name,
+            "restored_to": target_op,
+        });
+        run_post_hooks(&mut repo, HookEvent::PostUndo, &mut result, json)?;
+
+        if json {
+            println!("{}", json_string(&result)?);
+        } else {
+            println!("✓ Restored to checkpoint '{}'", checkpoint_name);
+        }
+
+        return Ok(());
+    }
+
+    // Otherwise, undo by steps
+    // Use Repo.operation_log() to find operations to undo
+    let operations = repo.operation_log(steps + 1)?;
+
+    if operations.len() <= steps {
+        anyhow::bail!("Not enough operations to undo {} steps", steps);
+    }
+
+    let target_op = &operations[steps].id;
+
+    if dry_run {
+        if json {
+            println!(
+                "{}",
+                json_string(&serde_json::json!({
+                    "dry_run": true,
+                    "would_restore_to": target_op,
+                    "operations_to_undo": steps,
+                }))?
+            );
+        } else {
+            println!("Would undo {} operation(s)", steps);
+            println!(
+                "Would restore to operation: {}...",
+                &target_op[..16.min(target_op.len())]
+            );
+        }
+        return Ok(());
+    }
+
+    // Actually undo using Repo method
+    repo.undo_to_operation(target_op)?;
+
+    let mut result = serde_json::json!({
+        "undone": true,
+        "steps": steps,
+        "restored_to": target_op,
+    });
+    run_post_hooks(&mut repo, HookEvent::PostUndo, &mut result, json)?;
+
+    if json {
+        println!("{}", json_string(&result)?);
+    } else {
+        println!("✓ Undid {} operation(s)", steps);
+    }
+
+    Ok(())
+}
+
+/// Run post-operation hooks with the command's result as payload. Outcomes
+/// are attached to `result` under `hooks`; failures only warn.
+fn run_post_hooks(
+    repo: &mut Repo,
+    event: HookEvent,
+    result: &mut serde_json::Value,
+    json: bool,
+) -> Result<Vec<agentjj::repo::HookResult>> {
+    let hooks = repo.run_hooks(event, result.clone())?;
+    if !json {
+        for hook in hooks.iter().filter(|h| !h.success) {
+            eprintln!(
+                "warning: {} hook `{}` failed: {}",
+                event.as_str(),
+                hook.command,
+                hook.stderr.trim()
+            );
+        }
+    }
+    if !hooks.is_empty() {
+        result["hooks"] = serde_json::to_value(&hooks)?;
+    }
+    Ok(hooks)
+}
+
+/// Restore only the selected paths from a checkpoint, keeping all other work
+fn cmd_undo_paths(
+    repo: &mut Repo,
+    checkpoint_name: &str,
+    target_op: &str,
+    paths: &[String],
+    dry_run: bool,
+    json: bool,
+) -> Result<()> {
+    if dry_run {
+        // Files modified since the checkpoint under the selected paths are
+        // exactly the ones a restore would touch
+        let diff = repo.diff_since_operation(target_op)?;
+        let would_restore: Vec<_> = diff
+            .files_modified
+            .iter()
+            .filter(|(file, _)| paths.iter().any(|p| path_is_under(file, p)))
+            .collect();
+
+        if json {
+            println!(
+                "{}",
+                json_string(&serde_json::json!({
+                    "dry_run": true,
+                    "checkpoint": checkpoint_name,
+                    "paths": paths,
+                    "would_restore": would_restore.iter().map(|(file, _)| file).collect::<Vec<_>>(),
+                }))?
+            );
+        } else {
+            println!(
+                "Would restore {} file(s) from checkpoint '{}'",
+                would_restore.len(),
+                checkpoint_name
+            );
+            for (file, _) in &would_restore {
+                println!("  {}", file);
+            }
+        }
+        return Ok(());
+    }
+
+    let restored = repo.restore_paths_from_operation(target_op, paths)?;
+
+    let mut result = serde_json::json!({
+        "restored": true,
+        "checkpoint": checkpoint_name,
+        "paths": paths,
+        "files": restored.iter().map(|(file, kind)| serde_json::json!({
+            "path": file,
+            "action": kind.as_str(),
+        })).collect::<Vec<_>>(),
+    });
+    run_post_hooks(repo, HookEvent::PostUndo, &mut result, json)?;
+
+    if json {
+        println!("{}", json_string(&result)?);
+    } else if restored.is_empty() {
+        println!(
+            "Nothing to restore: selected paths already match checkpoint '{}'",
+            checkpoint_name
+        );
+    } else {
+        println!(
+            "✓ Restored {} file(s) from checkpoint '{}'",
+            restored.len(),
+            checkpoint_name
+        );
+        for (file, kind) in &restored {
+            println!("  {:<8} {}", kind.as_str(), file);
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether `file` is `prefix` itself or lives underneath it
+fn path_is_under(file: &str, prefix: &str) -> bool {
+    let prefix = prefix.trim_end_matches('/');
+    file == prefix || file.starts_with(&format!("{}/", prefix))
+}
+
+/// A file's `#include`s (C/C++), `require`s (Ruby), or `use`s (PHP), each
+/// resolved to a repo file where possible (None for other languages)
+fn resolve_includes(
+    file: &std::path::Path,
+    content: &str,
+    lang: agentjj::SupportedLanguage,
+) -> Option<Vec<serde_json::Value>> {
+    use agentjj::SupportedLanguage as Lang;
+    if !matches!(lang, Lang::C | Lang::Cpp | Lang::Ruby | Lang::Php) {
+        return None;
+    }
+    let repo = Repo::discover().ok();
+    let root = match &repo {
+        Some(repo) => repo.root().to_path_buf(),
+        None => file.parent().map(|p| p.to_path_buf()).unwrap_or_default(),
+    };
+    let abs_file = if file.is_absolute() {
+        file.to_path_buf()
+    } else {
+        root.join(file)
+    };
+
+    let symbols = agentjj::symbols::extract_symbols(content, lang).unwrap_or_default();
+    let includes = symbols
+        .iter()
+        .filter(|s| s.kind == agentjj::SymbolKind::Import)
+        .map(|include| {
+            let resolved = agentjj::symbols::resolve_include(include, lang, &abs_file, &root)
+                .map(|p| p.strip_prefix(&root).unwrap_or(&p).display().to_string());
+            serde_json::json!({
+                "path": include.name,
+                "system": matches!(lang, Lang::C | Lang::Cpp)
+                    && include.signature.as_deref().is_some_and(|s| s.contains('<')),
+                "resolved": resolved,
+            })
+        })
+        .collect();
+    Some(includes)
+}
+
+/// Names declared in C/C++ headers: the matched headers themselves plus the
+/// quoted headers the matched sources include
+fn header_declarations(
+    root: &std::path::Path,
+    files: &[(
+        std::path::PathBuf,
+        agentjj::SupportedLanguage,
+        Vec<agentjj::Symbol>,
+    )],
+) -> std::collections::HashSet<String> {
+    let mut headers: Vec<(std::path::PathBuf, agentjj::SupportedLanguage)> = Vec::new();
+    let mut names = std::collections::HashSet::new();
+    for (path, lang, symbols) in files {
+        if agentjj::SupportedLanguage::is_header(path) {
+            names.extend(symbols.iter().map(|s| s.name.clone()));
+            continue;
+        }
+        for include in symbols
+            .iter()
+            .filter(|s| s.kind == agentjj::SymbolKind::Import)
+        {
+            if let Some(header) = agentjj::symbols::resolve_include(include, *lang, path, root) {
+                if !files.iter().any(|(p, _, _)| *p == header) {
+                    headers.push((header, *lang));
+                }
+            }
+        }
+    }
+    for (header, lang) in headers {
+        let symbols = std::fs::read_to_string(&header)
+            .ok()
+            .and_then(|content| agentjj::symbols::extract_symbols(&content, lang).ok())
+            .unwrap_or_default();
+        names.extend(symbols.into_iter().map(|s| s.name));
+    }
+    names
+}
+
+/// Bulk operations
+fn cmd_bulk(action: BulkAction, json: bool) -> Result<()> {
+    let mut repo = Repo::discover()?;
+
+    match action {
+        BulkAction::Read { paths, at } => {
+            let max_bytes = repo.config()?.output.max_bytes;
+            let mut results = Vec::new();
+            let mut errors = Vec::new();
+
+            let mut progress = agentjj::progress::Progress::start("read", Some(paths.len()));
+            for path in &paths {
+                if agentjj::deadline::expired() {
+                    exit_timed_out(
+                        "read",
+                        serde_json::json!({ "files": results, "errors": errors }),
+                        json,
+                    );
+                }
+                match repo.read_file(path, at.as_deref()) {
+                    Ok(content) => {
+                        let lines = content.lines().count();
+                        let (content, truncated) = truncate_output(content, max_bytes);
+                        results.push(serde_json::json!({
+                            "path": path,
+                            "content": content,
+                            "lines": lines,
+                            "truncated": truncated,
+                        }));
+                    }
+                    Err(e) => {
+                        errors.push(serde_json::json!({
+                            "path": path,
+                            "error": e.to_string(),
+                        }));
+                    }
+                }
+                progress.tick();
+            }
+            progress.finish();
+
+            if json {
+                let output = serde_json::json!({
+                    "at": at,
+                    "files": results,
+                    "errors": errors,
+                    "summary": {
+                        "read": results.len(),
+                        "failed": errors.len(),
+                    }
+                });
+                print_json_or_spill(&mut repo, "bulk read", &output)?;
+            } else {
+                for r in &results {
+                    println!("=== {} ({} lines) ===", r["path"], r["lines"]);
+                    println!("{}", r["content"].as_str().unwrap_or(""));
+                    println!();
+                }
+                for e in &errors {
+                    eprintln!("Error reading {}: {}", e["path"], e["error"]);
+                }
+            }
+        }
+
+        BulkAction::Symbols {
+            pattern,
+            public_only,
+            at,
+        } => {
+            let mut all_symbols = Vec::new();
+            let agentignore = agentjj::agentignore::AgentIgnore::load(repo.root())?;
+
+            // Files matching the glob, with their content when it comes from
+            // a revision rather than the disk
+            let entries: Vec<(std::path::PathBuf, Option<String>)> = match &at {
+                Some(rev) => {
+                    let matcher = agentjj::globs::Globs::new([&pattern])?;
+                    repo.files_at(rev, |path| {
+                        matcher.is_match(path) && !agentignore.is_ignored(path)
+                    })?
+                    .into_iter()
+                    .map(|(path, bytes)| {
+                        let content = agentjj::encoding::decode(&bytes).map(|(text, _)| text);
+                        (repo.root().join(path), content)
+                    })
+                    .collect()
+                }
+                None => {
+                    let filters = agentjj::repo::WalkFilters::for_repo(&mut repo)?
+                        .including(agentjj::globs::Globs::new([&pattern])?);
+                    agentjj::repo::walk_files(repo.root(), filters)
+                        .map(|path| (path, None))
+                        .collect()
+                }
+            };
+            let symbol_json = |rel_path: &std::path::Path, s: agentjj::Symbol| {
+                serde_json::json!({
+                    "file": rel_path.display().to_string(),
+                    "name": s.name,
+                    "kind": s.kind,
+                    "line": s.start_line,
+                    "signature": s.signature,
+                })
+            };
+            // C/C++ visibility depends on headers, so those files wait until
+            // every header has been seen
+            let mut native = Vec::new();
+            let mut progress = agentjj::progress::Progress::start("symbols", Some(entries.len()));
+            for (entry, stored) in entries {
+                if agentjj::deadline::expired() {
+                    exit_timed_out(
+                        "symbols",
+                        serde_json::json!({ "pattern": pattern, "symbols": all_symbols }),
+                        json,
+                    );
+                }
+                let lang = agentjj::SupportedLanguage::from_path(&entry);
+                let content = match &at {
+                    Some(_) => stored,
+                    None => std::fs::read_to_string(&entry).ok(),
+                };
+                if let (Some(lang), Some(content)) = (lang, content) {
+                    if let Ok(symbols) = agentjj::symbols::extract_symbols(&content, lang) {
+                        if public_only
+                            && matches!(
+                                lang,
+                                agentjj::SupportedLanguage::C | agentjj::SupportedLanguage::Cpp
+                            )
+                        {
+                            native.push((entry, lang, symbols));
+                            progress.tick();
+                            continue;
+                        }
+                        let rel_path = entry.strip_prefix(repo.root()).unwrap_or(&entry);
+                        for s in symbols {
+                            if !public_only || is_public_symbol(&s, lang) {
+                                all_symbols.push(symbol_json(rel_path, s));
+                            }
+                        }
+                    }
+                }
+                progress.tick();
+            }
+            progress.finish();
+
+            if !native.is_empty() {
+                let header_names = header_declarations(repo.root(), &native);
+                for (entry, lang, symbols) in native {
+                    let rel_path = entry.strip_prefix(repo.root()).unwrap_or(&entry);
+                    for s in symbols {
+                        if s.kind != agentjj::SymbolKind::Import
+                            && agentjj::symbols::is_public_symbol_in(
+                                &s,
+                                lang,
+                                &entry,
+                                &header_names,
+                            )
+                        {
+                            all_symbols.push(symbol_json(rel_path, s));
+                        }
+                    }
+                }
+            }
+
+            if json {
+                let output = serde_json::json!({
+                    "pattern": pattern,
+                    "at": at,
+                    "symbols": all_symbols,
+                    "count": all_symbols.len(),
+                });
+                print_json_or_spill(&mut repo, "bulk symbols", &output)?;
+            } else {
+                println!(
+                    "Found {} symbols matching '{}'{}:",
+                    all_symbols.len(),
+                    pattern,
+                    at.as_ref()
+                        .map(|rev| format!(" at {}", rev))
+                        .unwrap_or_default()
+                );
+                for s in &all_symbols {
+                    println!(
+                        "  {}::{} ({:?}, line {})",
+                        s["file"], s["name"], s["kind"], s["line"]
+                    );
+                }
+            }
+        }
+
+        BulkAction::Context { symbols } => {
+            let mut results = Vec::new();
+            let mut errors = Vec::new();
+
+            let mut progress = agentjj::progress::Progress::start("context", Some(symbols.len()));
+            for sym_path in &symbols {
+                if agentjj::deadline::expired() {
+                    exit_timed_out(
+                        "context",
+                        serde_json::json!({ "contexts": results, "errors": errors }),
+                        json,
+                    );
+                }
+                if let Some(idx) = sym_path.find("::") {
+                    let (file_path, symbol_name) = (&sym_path[..idx], &sym_path[idx + 2..]);
+                    let file_path_obj = std::path::Path::new(file_path);
+
+                    if let Some(lang) = agentjj::SupportedLanguage::from_path(file_path_obj) {
+                        let content_result: Result<String> = if file_path_obj.is_absolute() {
+                            std::fs::read_to_string(file_path).map_err(|e| anyhow::anyhow!("{}", e))
+                        } else {
+                            repo.read_file(file_path, None)
+                                .map_err(|e| anyhow::anyhow!("{}", e))
+                        };
+
+                        match content_result {
+                            Ok(content) => {
+                                match agentjj::symbols::get_symbol_context(
+                                    &content,
+                                    lang,
+                                    symbol_name,
+                                ) {
+                                    Ok(Some(ctx)) => {
+                                        results.push(serde_json::json!({
+                                            "path": sym_path,
+                                            "context": ctx,
+                                        }));
+                                    }
+                                    Ok(None) => {
+                                        errors.push(serde_json::json!({
+                                            "path": sym_path,
+                                            "error": "symbol not found",
+                                        }));
+                                    }
+                                    Err(e) => {
+                                        errors.push(serde_json::json!({
+                                            "path": sym_path,
+                                            "error": e.to_string(),
+                                        }));
+                                    }
+                                }
+                            }
+                            Err(e) => {
+                                errors.push(serde_json::json!({
+                                    "path": sym_path,
+                                    "error": e.to_string(),
+                                }));
+                            }
+                        }
+                    } else {
+                        errors.push(serde_json::json!({
+                            "path": sym_path,
+                            "error": "unsupported file type",
+                        }));
+                    }
+                } else {
+                    errors.push(serde_json::json!({
+                        "path": sym_path,
+                        "error": "invalid format, expected file::symbol",
+                    }));
+                }
+                progress.tick();
+            }
+            progress.finish();
+
+            if json {
+                let output = serde_json::json!({
+                    "contexts": results,
+                    "errors": errors,
+                });
+                print_json_or_spill(&mut repo, "bulk context", &output)?;
+            } else {
+                for r in &results {
+                    println!("=== {} ===", r["path"]);
+                    let ctx = &r["context"];
+                    println!("  {} ({:?})", ctx["name"], ctx["kind"]);
+                    if let Some(sig) = ctx["signature"].as_str() {
+                        println!("  {}", sig);
+                    }
+                    println!();
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Materialize the tree at `at` into `to` from the store
+fn cmd_export(
+    at: String,
+    to: std::path::PathBuf,
+    paths: Option<Vec<String>>,
+    json: bool,
+) -> Result<()> {
+    let mut repo = Repo::discover()?;
+    let dest = std::env::current_dir()?.join(&to);
+
+    // Resolve through the deepest directory that exists, since `to` may not
+    let existing = dest.ancestors().find(|a| a.exists()).unwrap_or(&dest);
+    let resolved = existing
+        .canonicalize()?
+        .join(dest.strip_prefix(existing).unwrap_or(&dest));
+    let root = repo.root().canonicalize()?;
+    if resolved.starts_with(&root) {
+        return Err(agentjj::Error::PreconditionFailed {
+            reason: "export directory is inside the repository".into(),
+            expected: format!("a directory outside {}", root.display()),
+            actual: resolved.display().to_string(),
+        }
+        .into());
+    }
+    if std::fs::read_dir(&dest).is_ok_and(|mut entries| entries.next().is_some()) {
+        return Err(agentjj::Error::PreconditionFailed {
+            reason: "export directory is not empty".into(),
+            expected: "an empty or missing directory".into(),
+            actual: dest.display().to_string(),
+        }
+        .into());
+    }
+    std::fs::create_dir_all(&dest)?;
+
+    let (_, commit_id) = repo.resolve_revision(&at)?;
+    let written = repo.export_at(
+        &at,
+        |path| match &paths {
+            Some(paths) => paths.iter().any(|p| path_is_under(path, p)),
+            None => true,
+        },
+        &dest,
+    )?;
+    let bytes: u64 = written.iter().map(|(_, size)| size).sum();
+
+    if json {
+        println!(
+            "{}",
+            json_string(&serde_json::json!({
+                "at": at,
+                "commit_id": commit_id,
+                "to": dest.display().to_string(),
+                "files": written.len(),
+                "bytes": bytes,
+                "paths": written.iter().map(|(path, _)| path).collect::<Vec<_>>(),
+            }))?
+        );
+    } else {
+        println!(
+            "Exported {} file(s) ({} bytes) at {} to {}",
+            written.len(),
+            bytes,
+            at,
+            dest.display()
+        );
+    }
+    Ok(())
+}
+
+/// List files with optional symbol counts
+fn cmd_files(
+    pattern: Option<String>,
+    with_symbols: bool,
+    with_status: bool,
+    dirty_only: bool,
+    json: bool,
+) -> Result<()> {
+    let mut repo = Repo::discover()?;
+    let lfs_patterns = agentjj::lfs::patterns(repo.root());
+    let statuses = if with_status {
+        Some(repo.working_copy_status()?)
+    } else {
+        None
+    };
+    let status_of = |path: &str| {
+        statuses
+            .as_ref()
+            .and_then(|s| s.get(path).copied())
+            .unwrap_or(agentjj::repo::FileStatus::Clean)
+    };
+
+    let glob_pattern = pattern.unwrap_or_else(|| "**/*".to_string());
+    let matcher = agentjj::globs::Globs::new([&glob_pattern])?;
+    let mut filters = agentjj::repo::WalkFilters::for_repo(&mut repo)?.including(matcher);
+    // `--status` reports ignored files too
+    filters.gitignore = !with_status;
+
+    let mut files = Vec::new();
+
+    for entry in agentjj::repo::walk_files(repo.root(), filters.clone()) {
+        let rel_path = entry.strip_prefix(repo.root()).unwrap_or(&entry);
+        let status = status_of(&rel_path.to_string_lossy());
+        if dirty_only && !status.is_dirty() {
+            continue;
+        }
+        let ext = entry.extension().map(|e| e.to_string_lossy().to_string());
+        let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+
+        let mut file_info = serde_json::json!({
+            "path": rel_path.display().to_string(),
+            "extension": ext,
+            "size": size,
+        });
+        if with_status {
+            file_info["status"] = serde_json::json!(status.as_str());
+        }
+
+        // Tracked by an LFS pattern, or a pointer whose content isn't pulled
+        let pointer = if size <= 1024 {
+            std::fs::read(&entry)
+                .ok()
+                .and_then(|bytes| agentjj::lfs::parse_pointer(&bytes))
+        } else {
+            None
+        };
+        let is_lfs = pointer.is_some()
+            || agentjj::lfs::is_tracked(&rel_path.to_string_lossy(), &lfs_patterns);
+        if is_lfs {
+            file_info["lfs"] = serde_json::json!(true);
+        }
+        if let Some(pointer) = &pointer {
+            file_info["lfs_pointer"] = serde_json::json!(pointer);
+        }
+
+        if with_symbols && pointer.is_none() {
+            if let Some(lang) = agentjj::SupportedLanguage::from_path(&entry) {
+                if let Ok(content) = std::fs::read_to_string(&entry) {
+                    if let Ok(symbols) = agentjj::symbols::extract_symbols(&content, lang) {
+                        file_info["symbol_count"] = serde_json::json!(symbols.len());
+                        file_info["symbols"] =
+                            serde_json::json!(symbols.iter().map(|s| &s.name).collect::<Vec<_>>());
+                    }
+                }
+            }
+        }
+
+        files.push(file_info);
+    }
+
+    // Deleted files are no longer on disk for the glob to find
+    if let Some(statuses) = &statuses {
+        let mut deleted: Vec<&String> = statuses
+            .iter()
+            .filter(|(path, status)| {
+                **status == agentjj::repo::FileStatus::Deleted && !filters.skips_file(path)
+            })
+            .map(|(path, _)| path)
+            .collect();
+        deleted.sort();
+        for path in deleted {
+            files.push(serde_json::json!({
+                "path": path,
+                "extension": std::path::Path::new(path).extension().map(|e| e.to_string_lossy()),
+                "size": 0,
+                "status": "deleted",
+            }));
+        }
+    }
+
+    if json {
+        println!(
+            "{}",
+            json_string(&serde_json::json!({
+                "pattern": glob_pattern,
+                "files": files,
+                "count": files.len(),
+            }))?
+        );
+    } else {
+        println!("Files matching '{}':", glob_pattern);
+        for f in &files {
+            let mut size_str = format_size(f["size"].as_u64().unwrap_or(0));
+            if f["lfs"] == true {
+                size_str.push_str(", lfs");
+            }
+            if let Some(status) = f["status"].as_str() {
+                println!("  {:<10} {} ({})", status, f["path"], size_str);
+                continue;
+            }
+            if with_symbols {
+                if let Some(count) = f["symbol_count"].as_u64() {
+                    println!("  {} ({}, {} symbols)", f["path"], size_str, count);
+                } else {
+                    println!("  {} ({})", f["path"], size_str);
+                }
+            } else {
+                println!("  {} ({})", f["path"], size_str);
+            }
+        }
+        println!("\nTotal: {} files", files.len());
+    }
+
+    Ok(())
+}
+
+fn format_size(bytes: u64) -> String {
+    if bytes < 1024 {
+        format!("{} B", bytes)
+    } else if bytes < 1024 * 1024 {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    } else {
+        format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+    }
+}
+
+/// Show semantic diff
+/// Notebook paths in a diff with their content before and after
+type NotebookSides = Vec<(String, Option<String>, Option<String>)>;
+
+/// `git diff` output for `target` (notebooks excluded) and the notebooks it
+/// touches, from the colocated git repository
+fn git_diff_text(repo: &mut Repo, target: &str) -> Result<(String, NotebookSides)> {
+    // Use git for diff rendering since jj CLI is not required to be installed.
+    let (before_rev, after_rev) = if target == "@" {
+        // Working copy changes: compare git HEAD to working tree
+        (Some("HEAD".to_string()), None)
+    } else {
+        // Resolve the jj revision to git-compatible commit IDs.
+        // In colocated mode, jj commit IDs are git commit IDs.
+        let (parent_hex, commit_hex) = repo.resolve_revision(target)?;
+        // jj's root commit has no git counterpart
+        let parent_hex = parent_hex.filter(|p| p.bytes().any(|b| b != b'0'));
+        (parent_hex, Some(commit_hex))
+    };
+    git_diff_revs(repo, before_rev, after_rev)
+}
+
+/// `git diff` from `before_rev` to `after_rev` (the working tree when
+/// `None`), or the whole of `after_rev` when there is no `before_rev`
+fn git_diff_revs(
+    repo: &mut Repo,
+    before_rev: Option<String>,
+    after_rev: Option<String>,
+) -> Result<(String, NotebookSides)> {
+    // Notebooks are compared cell by cell rather than as raw JSON
+    let without_notebooks = ["--", ".", ":(exclude)*.ipynb"];
+    let only_notebooks = ["--", "*.ipynb"];
+
+    let git_diff = |extra: &[&str], paths: &[&str]| {
+        let mut args: Vec<&str> = match (&before_rev, &after_rev) {
+            (Some(before), Some(after)) => vec!["diff", before, after],
+            (Some(before), None) => vec!["diff", before],
+            // Root commit: show entire commit as additions
+            (None, after) => vec!["show", "--format=", after.as_deref().unwrap_or("HEAD")],
+        };
+        args.extend(extra);
+        args.extend(paths);
+        std::process::Command::new("git")
+            .current_dir(repo.root())
+            .args(args)
+            .output()
+    };
+    let diff_output = git_diff(&[], &without_notebooks)?;
+    let notebook_files: Vec<String> = git_diff(&["--name-only"], &only_notebooks)
+        .map(|o| {
+            String::from_utf8_lossy(&o.stdout)
+                .lines()
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default();
+
+    if !diff_output.status.success() {
+        let stderr = String::from_utf8_lossy(&diff_output.stderr);
+        anyhow::bail!("Diff failed: {}", stderr);
+    }
+
+    let mut notebooks = Vec::new();
+    for file in notebook_files {
+        let show = |rev: &str| {
+            std::process::Command::new("git")
+                .current_dir(repo.root())
+                .args(["show", &format!("{}:{}", rev, file)])
+                .output()
+                .ok()
+                .filter(|o| o.status.success())
+                .map(|o| String::from_utf8_lossy(&o.stdout).to_string())
+        };
+        let before = before_rev.as_deref().and_then(show);
+        let after = match &after_rev {
+            Some(rev) => show(rev),
+            None => std::fs::read_to_string(repo.root().join(&file)).ok(),
+        };
+        notebooks.push((file, before, after));
+    }
+
+    Ok((
+        String::from_utf8_lossy(&diff_output.stdout).to_string(),
+        notebooks,
+    ))
+}
+
+/// The same as `git_diff_text`, rendered from jj's trees for repositories
+/// without git
+fn native_diff_text(repo: &mut Repo, target: &str) -> Result<(String, NotebookSides)> {
+    Ok(native_diff_sides(repo.tree_changes(target)?))
+}
+
+/// Render file changes as a unified diff, with notebooks set apart
+fn native_diff_sides(changes: Vec<agentjj::unified::FileChange>) -> (String, NotebookSides) {
+    let (notebooks, files): (Vec<_>, Vec<_>) = changes
+        .into_iter()
+        .partition(|c| c.path.ends_with(".ipynb"));
+    let text = |side: &Option<agentjj::unified::Side>| {
+        side.as_ref()
+            .map(|s| String::from_utf8_lossy(&s.content).to_string())
+    };
+    (
+        agentjj::unified::render(&files),
+        notebooks
+            .iter()
+            .map(|c| (c.path.clone(), text(&c.before), text(&c.after)))
+            .collect(),
+    )
+}
+
+/// The diff as `edit --ops` operations. Binary files cannot be carried
+/// and are listed as skipped.
+fn print_diff_edits(
+    repo: &mut Repo,
+    target: &str,
+    base: Option<(String, String)>,
+    changes: Vec<agentjj::unified::FileChange>,
+    json: bool,
+) -> Result<()> {
+    let mut operations = Vec::new();
+    let mut skipped = Vec::new();
+    for change in &changes {
+        match agentjj::intent::FileOperation::from_change(change) {
+            Some(ops) => operations.extend(ops),
+            None => skipped.push(serde_json::json!({
+                "path": change.path,
+                "reason": "binary",
+            })),
+        }
+    }
+
+    if json {
+        let mut output = serde_json::json!({
+            "against": target,
+            "format": "edits",
+            "operations": operations,
+            "skipped": skipped,
+        });
+        if let Some((change_id, commit_id)) = &base {
+            output["merge_base"] = serde_json::json!({
+                "change_id": change_id,
+                "commit_id": commit_id,
+            });
+        }
+        print_json_or_spill(repo, "diff", &output)?;
+    } else {
+        // Bare, so it can go straight to `edit --ops -`
+        println!("{}", json_string(&operations)?);
+        for skip in &skipped {
+            eprintln!(
+                "warning: skipped binary file {}",
+                skip["path"].as_str().unwrap_or("")
+            );
+        }
+    }
+    Ok(())
+}
+
+fn cmd_diff(
+    against: Option<String>,
+    explain: bool,
+    merge_base: bool,
+    format: &str,
+    json: bool,
+) -> Result<()> {
+    let mut repo = Repo::discover()?;
+    let target = against.unwrap_or_else(|| "@-".to_string());
+
+    // With --merge-base: the working copy against where it left `target`
+    let base = if merge_base {
+        repo.snapshot()?;
+        let commit = repo.merge_base(&target, "@")?;
+        Some((repo.commit_change_id(&commit)?, commit))
+    } else {
+        None
+    };
+
+    if format == "edits" {
+        let changes = match &base {
+            Some((_, commit)) => repo.tree_changes_between(commit, "@")?,
+            None => repo.tree_changes(&target)?,
+        };
+        return print_diff_edits(&mut repo, &target, base, changes, json);
+    }
+
+    let (raw_diff, notebook_sides) = match (&base, repo.has_git_backend()?) {
+        (Some((_, commit)), true) => {
+            // The working-copy commit, so files git doesn't track yet are included
+            let (_, wc) = repo.resolve_revision("@")?;
+            git_diff_revs(&mut repo, Some(commit.clone()), Some(wc))?
+        }
+        (Some((_, commit)), false) => {
+            let changes = repo.tree_changes_between(commit, "@")?;
+            native_diff_sides(changes)
+        }
+        (None, true) => git_diff_text(&mut repo, &target)?,
+        (None, false) => native_diff_text(&mut repo, &target)?,
+    };
+
+    // Parse diff into structured format
+    let mut files_changed = Vec::new();
+    let mut current_file: Option<String> = None;
+    let mut additions = 0;
+    let mut deletions = 0;
+    // Executable bit flips, and new executables or symlinks (git modes)
+    let mut mode_changes = Vec::new();
+    let mut old_mode: Option<&str> = None;
+
+    for line in raw_diff.lines() {
+        if let Some(header) = line.strip_prefix("diff --git ") {
+            // Mode-only changes have no ---/+++ lines
+            if let Some((_, path)) = header.split_once(" b/") {
+                current_file = Some(path.to_string());
+            }
+            old_mode = None;
+        } else if let Some(mode) = line.strip_prefix("old mode ") {
+            old_mode = Some(mode);
+        } else if let Some(mode) = line
+            .strip_prefix("new mode ")
+            .or_else(|| line.strip_prefix("new file mode "))
+        {
+            if old_mode.is_some() || mode != "100644" {
+                mode_changes.push(serde_json::json!({
+                    "path": current_file,
+                    "old_mode": old_mode.take(),
+                    "new_mode": mode,
+                }));
+            }
+        } else if line.starts_with("--- ") || line.starts_with("+++ ") {
+            let path = line[4..].trim_start_matches("a/").trim_start_matches("b/");
+            if !path.is_empty() && path != "/dev/null" {
+                current_file = Some(path.to_string());
+            }
+        } else if line.starts_with('+') && !line.starts_with("+++") {
+            additions += 1;
+        } else if line.starts_with('-') && !line.starts_with("---") {
+            deletions += 1;
+        }
+
+        if let Some(ref file) = current_file {
+            if !files_changed.contains(file) {
+                files_changed.push(file.clone());
+            }
+        }
+    }
+
+    let mut notebooks = Vec::new();
+    for (file, before, after) in notebook_sides {
+        let parse =
+            |json: Option<String>| json.and_then(|j| agentjj::notebook::Notebook::parse(&j).ok());
+        let cells = agentjj::notebook::diff(parse(before).as_ref(), parse(after).as_ref());
+        additions += cells.iter().map(|c| c.additions()).sum::<usize>();
+        deletions += cells.iter().map(|c| c.deletions()).sum::<usize>();
+        notebooks.push(serde_json::json!({ "path": file, "cells": cells }));
+        files_changed.push(file);
+    }
+
+    let semantic_summary = if explain && !files_changed.is_empty() {
+        // Generate a semantic summary based on file types and changes
+        let mut summary_parts = Vec::new();
+
+        for file in &files_changed {
+            let ext = std::path::Path::new(file)
+                .extension()
+                .and_then(|e| e.to_str())
+                .unwrap_or("");
+
+            let file_type = match ext {
+                "rs" => "Rust code",
+                "py" => "Python code",
+                "ts" | "tsx" => "TypeScript code",
+                "js" | "jsx" => "JavaScript code",
+                "c" | "h" => "C code",
+                "cc" | "cpp" | "cxx" | "hh" | "hpp" | "hxx" => "C++ code",
+                "rb" => "Ruby code",
+                "php" => "PHP code",
+                "toml" => "TOML configuration",
+                "json" => "JSON data",
+                "md" => "documentation",
+                "ipynb" => "Jupyter notebook",
+                "yaml" | "yml" => "YAML configuration",
+                _ => "file",
+            };
+
+            summary_parts.push(format!("{} ({})", file, file_type));
+        }
+
+        Some(format!(
+            "Changes affect {} file(s): {}. Net change: +{} -{} lines.",
+            files_changed.len(),
+            summary_parts.join(", "),
+            additions,
+            deletions
+        ))
+    } else {
+        None
+    };
+    // The working notes recorded for the change being explained
+    let notes = if explain {
+        let change_id = if target == "@" || merge_base {
+            repo.current_change_id().ok()
+        } else {
+            repo.resolve_revision(&target)
+                .and_then(|(_, commit)| repo.commit_change_id(&commit))
+                .ok()
+        };
+        change_id
+            .and_then(|id| repo.get_typed_change(&id).ok())
+            .and_then(|c| c.notes)
+    } else {
+        None
+    };
+
+    if json {
+        let mut output = serde_json::json!({
+            "against": target,
+            "files_changed": files_changed,
+            "stats": {
+                "additions": additions,
+                "deletions": deletions,
+                "net": additions as i64 - deletions as i64,
+            },
+            "explanation": semantic_summary,
+            "notes": notes,
+            "raw_diff": raw_diff,
+            "mode_changes": mode_changes,
+            "notebooks": notebooks,
+        });
+        if let Some((change_id, commit_id)) = &base {
+            output["merge_base"] = serde_json::json!({
+                "change_id": change_id,
+                "commit_id": commit_id,
+            });
+        }
+        print_json_or_spill(&mut repo, "diff", &output)?;
+    } else {
+        match &base {
+            Some((change_id, _)) => println!(
+                "Diff against {} (merge base {}):",
+                target,
+                &change_id[..12.min(change_id.len())]
+            ),
+            None => println!("Diff against {}:", target),
+        }
+        println!("  {} file(s) changed", files_changed.len());
+        println!("  +{} -{} lines", additions, deletions);
+
+        if let Some(summary) = &semantic_summary {
+            println!("\nSummary: {}", summary);
+        }
+        if let Some(notes) = &notes {
+            println!("\nNotes:\n{}", notes);
+        }
+
+        for change in &mode_changes {
+            println!(
+                "  mode {}: {} -> {}",
+                change["path"].as_str().unwrap_or(""),
+                change["old_mode"].as_str().unwrap_or("(new)"),
+                change["new_mode"].as_str().unwrap_or("")
+            );
+        }
+
+        println!("\n{}", raw_diff);
+
+        for notebook in &notebooks {
+            println!("notebook {}", notebook["path"].as_str().unwrap_or(""));
+            for cell in notebook["cells"].as_array().into_iter().flatten() {
+                println!(
+                    "  cell {} [{}] {}",
+                    cell["cell"],
+                    cell["cell_type"].as_str().unwrap_or(""),
+                    cell["change"].as_str().unwrap_or("")
+                );
+                for line in cell["diff"].as_array().into_iter().flatten() {
+                    println!("    {}", line.as_str().unwrap_or(""));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// One side of `compare`: the change, what it touched, and its metadata
+fn compare_side(repo: &mut Repo, rev: &str) -> Result<agentjj::compare::ChangeSide> {
+    let (_, commit_id) = repo.resolve_revision(rev)?;
+    let change_id = repo.commit_change_id(&commit_id)?;
+    Ok(agentjj::compare::ChangeSide {
+        files: repo.commit_changed_files(&commit_id)?,
+        typed: repo.get_typed_change(&change_id).ok(),
+        change_id,
+        commit_id,
+    })
+}
+
+/// Diff two changes against each other rather than their parents, so an
+/// arbiter can pick between competing solutions or merge them
+fn cmd_compare(a: String, b: String, json: bool) -> Result<()> {
+    let mut repo = Repo::discover()?;
+    if a == "@" || b == "@" {
+        repo.snapshot()?;
+    }
+    let changes = repo.tree_changes_between(&a, &b)?;
+    let (side_a, side_b) = (compare_side(&mut repo, &a)?, compare_side(&mut repo, &b)?);
+    let cmp = agentjj::compare::compare(side_a, side_b, &changes);
+
+    if json {
+        println!("{}", json_string(&cmp)?);
+        return Ok(());
+    }
+
+    println!("a: {} ({})", cmp.a.change_id, a);
+    println!("b: {} ({})", cmp.b.change_id, b);
+    let differing: Vec<_> = cmp.metadata.iter().filter(|f| !f.same).collect();
+    if !differing.is_empty() {
+        println!("\nMetadata:");
+        for field in differing {
+            println!(
+                "  {}: {} | {}",
+                field.field,
+                field.a.as_deref().unwrap_or("-"),
+                field.b.as_deref().unwrap_or("-")
+            );
+        }
+    }
+
+    if cmp.identical {
+        println!("\nThe two changes produce identical trees");
+    } else {
+        println!("\nFiles ({} differ, a -> b):", cmp.files.len());
+        for file in &cmp.files {
+            let status = match file.status {
+                agentjj::compare::FileStatus::Added => 'A',
+                agentjj::compare::FileStatus::Removed => 'D',
+                agentjj::compare::FileStatus::Modified => 'M',
+            };
+            let lines = match (file.lines_added, file.lines_removed) {
+                (Some(added), Some(removed)) => format!(" +{} -{}", added, removed),
+                _ => " (binary)".to_string(),
+            };
+            println!("  {} {}{}", status, file.path, lines);
+            let symbols = &file.symbols;
+            for (label, names) in [
+                ("only in a", &symbols.only_in_a),
+                ("only in b", &symbols.only_in_b),
+                ("differ", &symbols.differing),
+            ] {
+                if !names.is_empty() {
+                    println!("      {}: {}", label, names.join(", "));
+                }
+            }
+        }
+    }
+
+    let overlap = &cmp.overlap;
+    println!("\nOverlap: {:.0}% of touched files", overlap.ratio * 100.0);
+    for (label, files) in [
+        ("both, conflicting", &overlap.conflicting),
+        ("both, agreeing", &overlap.agreeing),
+        ("only a", &overlap.only_a),
+        ("only b", &overlap.only_b),
+    ] {
+        if !files.is_empty() {
+            println!("  {}: {}", label, files.join(", "));
+        }
+    }
+    Ok(())
+}
+
+/// Merge two competing changes into a new `@` that links both in its
+/// typed-change metadata. Overlaps left unresolved exit with CONFLICT.
+fn cmd_reconcile(a: String, b: String, strategy: Option<String>, json: bool) -> Result<()> {
+    use agentjj::reconcile::Strategy;
+
+    let strategy = strategy
+        .map(|s| Strategy::parse(&s).ok_or_else(|| anyhow::anyhow!("unknown strategy '{}'", s)))
+        .transpose()?;
+    let mut repo = Repo::discover()?;
+    if a == "@" || b == "@" {
+        repo.snapshot()?;
+    }
+    let (side_a, side_b) = (compare_side(&mut repo, &a)?, compare_side(&mut repo, &b)?);
+    let short = |id: &str| id[..12.min(id.len())].to_string();
+    let intent = format!(
+        "Reconcile {} and {}",
+        short(&side_a.change_id),
+        short(&side_b.change_id)
+    );
+    let result = repo.reconcile(&side_a.change_id, &side_b.change_id, &intent, strategy)?;
+
+    // The sides' shared metadata carries over; where they differ, `a` wins
+    let (typed_a, typed_b) = (side_a.typed.as_ref(), side_b.typed.as_ref());
+    let change_type = match typed_a.or(typed_b) {
+        Some(t) => t.change_type,
+        None => resolve_change_type(&mut repo, None)?,
+    };
+    let mut files: Vec<String> = side_a.files.iter().chain(&side_b.files).cloned().collect();
+    files.sort();
+    files.dedup();
+    let mut typed =
+        TypedChange::new(result.change_id.clone(), change_type, intent).with_files(files);
+    if let Some(t) = typed_a.or(typed_b) {
+        typed.categories = t.categories.clone();
+    }
+    if typed_a.is_some_and(|t| t.breaking) || typed_b.is_some_and(|t| t.breaking) {
+        typed = typed.breaking();
+    }
+    typed
+        .metadata
+        .insert("reconciles_a".into(), side_a.change_id.clone());
+    typed
+        .metadata
+        .insert("reconciles_b".into(), side_b.change_id.clone());
+    if let Some(strategy) = strategy {
+        typed.metadata.insert(
+            "reconcile_strategy".into(),
+            serde_json::to_value(strategy)?
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+        );
+    }
+    repo.save_typed_change(&typed)?;
+
+    let unresolved = result.unresolved();
+    if json {
+        println!(
+            "{}",
+            json_string(&serde_json::json!({
+                "change_id": result.change_id,
+                "commit_id": result.commit_id,
+                "sources": { "a": side_a.change_id, "b": side_b.change_id },
+                "strategy": strategy,
+                "conflicts": result.conflicts,
+                "unresolved": unresolved,
+                "typed_change": typed,
+            }))?
+        );
+    } else {
+        println!(
+            "✓ Reconciled {} and {} as {}",
+            short(&side_a.change_id),
+            short(&side_b.change_id),
+            short(&result.change_id)
+        );
+        for conflict in &result.conflicts {
+            let hunks = match conflict.hunks.len() {
+                0 => String::new(),
+                n => format!(", {} hunk(s)", n),
+            };
+            match conflict.resolved_with {
+                Some(s) => println!(
+                    "  ✓ {} (resolved with {}{})",
+                    conflict.path,
+                    serde_json::to_value(s)?.as_str().unwrap_or_default(),
+                    hunks
+                ),
+                None => println!("  ✗ conflict: {}{}", conflict.path, hunks),
+            }
+        }
+        if !unresolved.is_empty() {
+            println!("  Resolve the conflicts in the working copy, then `agentjj commit`");
+        }
+    }
+
+    if !unresolved.is_empty() {
+        exit_with(exit::CONFLICT, None);
+    }
+    Ok(())
+}
+
+/// Re-parse every call site of a function whose signature changed and list
+/// the ones its new parameters break
+fn cmd_check_callers(symbol_path: String, json: bool) -> Result<()> {
+    use agentjj::callers::{self, CallStatus};
+
+    let Some((file_path, symbol_name)) = symbol_path.split_once("::") else {
+        anyhow::bail!("Symbol path must be file::symbol_name");
+    };
+    let lang = agentjj::SupportedLanguage::from_path(std::path::Path::new(file_path))
+        .filter(|lang| callers::supported(*lang))
+        .ok_or_else(|| anyhow::anyhow!("check-callers does not support {}", file_path))?;
+
+    let mut repo = Repo::discover()?;
+    let content = repo.read_file(file_path, None)?;
+    let symbols = agentjj::symbols::extract_symbols(&content, lang)?;
+    let symbol = callers::find_definition(&symbols, symbol_name)
+        .ok_or_else(|| anyhow::anyhow!("Function '{}' not found in {}", symbol_name, file_path))?;
+    let params = callers::parameters(&content, symbol, lang)
+        .ok_or_else(|| anyhow::anyhow!("Could not read the parameters of '{}'", symbol_name))?;
+
+    // What the signature was before this change, when it differs
+    let previous_signature = repo.read_file(file_path, Some("@-")).ok().and_then(|old| {
+        let old_symbols = agentjj::symbols::extract_symbols(&old, lang).ok()?;
+        let old_symbol = callers::find_definition(&old_symbols, symbol_name)?;
+        (callers::parameters(&old, old_symbol, lang)? != params)
+            .then(|| old_symbol.signature.clone())
+            .flatten()
+    });
+
+    let name = callers::short_name(&symbol.name);
+    let root = repo.root().to_path_buf();
+    let codebase = codebase_files(&mut repo)?;
+    let mut checks = Vec::new();
+    let mut progress = agentjj::progress::Progress::start("scan", Some(codebase.len()));
+    for (scanned, path) in codebase.iter().enumerate() {
+        if agentjj::deadline::expired() {
+            exit_timed_out(
+                "scan",
+                serde_json::json!({ "files_scanned": scanned, "call_sites": checks }),
+                json,
+            );
+        }
+        progress.tick();
+        let Some(file_lang) = agentjj::SupportedLanguage::from_path(path) else {
+            continue;
+        };
+        if !callers::same_family(lang, file_lang) {
+            continue;
+        }
+        let Ok(source) = std::fs::read_to_string(path) else {
+            continue;
+        };
+        if !source.contains(name) {
+            continue;
+        }
+        let rel_path = path
+            .strip_prefix(&root)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .to_string();
+        let definition_line = (rel_path == file_path).then_some(symbol.start_line);
+        checks.extend(callers::check_calls(
+            &rel_path,
+            &source,
+            file_lang,
+            name,
+            &params,
+            definition_line,
+        ));
+    }
+    progress.finish();
+
+    let with_status = |status| {
+        checks
+            .iter()
+            .filter(|c| c.status == status)
+            .collect::<Vec<_>>()
+    };
+    let (breaking, unverified) = (
+        with_status(CallStatus::Breaks),
+        with_status(CallStatus::Unverified),
+    );
+    let compatible = checks.len() - breaking.len() - unverified.len();
+
+    if json {
+        let mut out = serde_json::json!({
+            "symbol": symbol_path,
+            "signature": symbol.signature,
+            "params": params,
+            "call_sites": checks.len(),
+            "compatible": compatible,
+            "breaking": breaking,
+            "unverified": unverified,
+        });
+        if let Some(previous) = &previous_signature {
+            out["previous_signature"] = serde_json::json!(previous);
+        }
+        print_json_or_spill(&mut repo, "check-callers", &out)?;
+    } else {
+        println!(
+            "{}",
+            symbol.signature.as_deref().unwrap_or(&symbol.name).trim()
+        );
+        if let Some(previous) = &previous_signature {
+            println!("  was: {}", previous.trim());
+        }
+        println!(
+            "{} call site(s): {} compatible, {} breaking, {} unverified",
+            checks.len(),
+            compatible,
+            breaking.len(),
+            unverified.len()
+        );
+        for check in &breaking {
+            println!(
+                "  ✗ {}:{} {}: {}",
+                check.file,
+                check.line,
+                check.call,
+                check.problem.as_deref().unwrap_or("")
+            );
+        }
+        for check in &unverified {
+            println!(
+                "  ? {}:{} {}: spread arguments, not checked",
+                check.file, check.line, check.call
+            );
+        }
+    }
+
+    if !breaking.is_empty() {
+        exit_with(exit::FAILURE, None);
+    }
+    Ok(())
+}
+
+/// Go/no-go for editing `paths`: changes to them the working copy lacks on
+/// the trunk or other branches, parked approvals, churn, and manifest rules
+fn cmd_preflight(paths: Vec<String>, changes: usize, json: bool) -> Result<()> {
+    let mut repo = Repo::discover()?;
+    let report = preflight_report(&mut repo, &paths, changes)?;
+
+    if json {
+        println!("{}", json_string(&report)?);
+    } else {
+        println!("{}", if report.go { "✓ go" } else { "✗ no-go" });
+        for reason in &report.reasons {
+            let mark = if reason.severity == "block" {
+                "✗"
+            } else {
+                "!"
+            };
+            println!("  {} {}", mark, reason.message);
+        }
+    }
+
+    if !report.go {
+        exit_with(exit::POLICY, Some("preflight_blocked"));
+    }
+    Ok(())
+}
+
+/// Go/no-go for editing `paths`, with churn counted over the last `changes`
+/// committed changes
+fn preflight_report(
+    repo: &mut Repo,
+    paths: &[String],
+    changes: usize,
+) -> Result<agentjj::preflight::Report> {
+    use agentjj::preflight::{touches, Check, Report, HOT_CHURN};
+    use std::collections::BTreeMap;
+
+    let paths: Vec<String> = paths
+        .iter()
+        .map(|p| p.trim_start_matches("./").to_string())
+        .collect();
+    let short = |id: &str| id[..12.min(id.len())].to_string();
+    let mut report = Report::new(paths.clone());
+
+    // Branches, the trunk first; a commit two of them share is checked once
+    let trunk = repo
+        .manifest()
+        .map(|m| m.branches.trunk.clone())
+        .unwrap_or_else(|_| "main".to_string());
+    let mut branches: Vec<(Check, String, String)> = repo
+        .remote_branches()?
+        .into_iter()
+        .chain(
+            repo.local_bookmarks()?
+                .into_iter()
+                .map(|b| (b.name, b.commit_id)),
+        )
+        .map(|(name, commit_id)| {
+            let is_trunk = name == trunk || name.ends_with(&format!("/{}", trunk));
+            let check = if is_trunk {
+                Check::Upstream
+            } else {
+                Check::Branch
+            };
+            (check, name, commit_id)
+        })
+        .collect();
+    branches.sort();
+    let mut seen = std::collections::HashSet::new();
+    for (check, name, commit_id) in &branches {
+        if !seen.insert(commit_id.clone()) {
+            continue;
+        }
+        let missing = repo.commits_missing(commit_id)?;
+        let mut by_path: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+        for entry in &missing {
+            let files = repo.commit_changed_files(&entry.commit_id)?;
+            for path in &paths {
+                if files.iter().any(|f| touches(path, f)) {
+                    by_path
+                        .entry(path)
+                        .or_default()
+                        .push(short(&entry.change_id));
+                }
+            }
+        }
+        for (path, ids) in by_path {
+            let message = match check {
+                Check::Upstream => format!(
+                    "{} has {} change(s) to {} not in the working copy; rebase before editing",
+                    name,
+                    ids.len(),
+                    path
+                ),
+                _ => format!("{} has {} unmerged change(s) to {}", name, ids.len(), path),
+            };
+            report.add(*check, path, message, ids);
+        }
+    }
+
+    // Intents parked for a human, which will land on the same files
+    for approval in agentjj::approval::ApprovalStore::open(repo.root()).list()? {
+        use agentjj::approval::ApprovalStatus;
+        if !matches!(
+            approval.status,
+            ApprovalStatus::Pending | ApprovalStatus::Approved
+        ) {
+            continue;
+        }
+        let touched = approval.intent.changes.paths();
+        for path in &paths {
+            if touched.iter().any(|f| touches(path, f)) {
+                report.add(
+                    Check::Approval,
+                    path,
+                    format!(
+                        "approval {} ({}, requested by {}) edits {}",
+                        approval.id,
+                        approval.status.as_str(),
+                        approval.requested_by,
+                        path
+                    ),
+                    vec![approval.id.clone()],
+                );
+            }
+        }
+    }
+
+    // Churn over the last `changes` committed changes (plus the working copy)
+    let log = repo
+        .log_entries(changes.saturating_add(1), false)
+        .unwrap_or_default();
+    let mut churn: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    for entry in log.iter().filter(|e| !e.is_working_copy).take(changes) {
+        let files = repo
+            .commit_changed_files(&entry.full_commit_id)
+            .unwrap_or_default();
+        for path in &paths {
+            if files.iter().any(|f| touches(path, f)) {
+                churn.entry(path).or_default().push(entry.change_id.clone());
+            }
+        }
+    }
+    for (path, ids) in churn {
+        if ids.len() >= HOT_CHURN {
+            report.add(
+                Check::Churn,
+                path,
+                format!(
+                    "{} changed in {} of the last {} changes; expect concurrent edits",
+                    path,
+                    ids.len(),
+                    changes
+                ),
+                ids,
+            );
+        }
+    }
+
+    if let Ok(manifest) = repo.manifest() {
+        for path in &paths {
+            if !manifest.permissions.can_change(path) {
+                report.add(
+                    Check::Permission,
+                    path,
+                    format!("the manifest does not allow changing {}", path),
+                    vec![],
+                );
+            } else if manifest.permissions.needs_approval(path) {
+                report.add(
+                    Check::Review,
+                    path,
+                    format!("changes to {} wait for human approval (ask_change)", path),
+                    vec![],
+                );
+            }
+            if manifest.requires_human_review(path) {
+                report.add(
+                    Check::Review,
+                    path,
+                    format!("{} requires human review before merge", path),
+                    vec![],
+                );
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Analyze what would be affected by changing a symbol
+fn cmd_affected(
+    symbol_path: String,
+    depth: usize,
+    tests_only: bool,
+    public_only: bool,
+    group_by: Option<String>,
+    json: bool,
+) -> Result<()> {
+    use agentjj::affected::{group, public_users, AffectedFile, GroupBy};
+
+    let mut repo = Repo::discover()?;
+
+    // Parse the symbol path
+    let (file_path, symbol_name) = if let Some(idx) = symbol_path.find("::") {
+        (&symbol_path[..idx], &symbol_path[idx + 2..])
+    } else {
+        anyhow::bail!("Symbol path must be file::symbol_name");
+    };
+
+    // Find all files that might reference this symbol
+    let mut affected_files = Vec::new();
+    let mut excluded = 0;
+    let filters = agentjj::repo::WalkFilters::for_repo(&mut repo)?;
+
+    for entry in agentjj::repo::walk_files(repo.root(), filters) {
+        if let Some(lang) = agentjj::SupportedLanguage::from_path(&entry) {
+            if let Ok(content) = std::fs::read_to_string(&entry) {
+                // Simple text search for the symbol name
+                if content.contains(symbol_name) {
+                    let rel_path = entry.strip_prefix(repo.root()).unwrap_or(&entry);
+                    let path = rel_path.display().to_string();
+
+                    // Try to find actual usages (not just the definition)
+                    let is_definition = rel_path.to_string_lossy() == file_path;
+                    if is_definition && depth == 0 {
+                        continue;
+                    }
+
+                    let mut file = AffectedFile {
+                        is_test: agentjj::testrun::is_test_file(&path, &content),
+                        path,
+                        language: format!("{:?}", lang),
+                        // Count occurrences
+                        occurrences: content.matches(symbol_name).count(),
+                        is_definition,
+                        public_symbols: Vec::new(),
+                    };
+                    if public_only && !file.is_test {
+                        file.public_symbols = public_users(&content, lang, symbol_name);
+                    }
+                    let keep = if tests_only {
+                        file.is_test
+                    } else if public_only {
+                        !file.public_symbols.is_empty()
+                    } else {
+                        true
+                    };
+                    if keep {
+                        affected_files.push(file);
+                    } else {
+                        excluded += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    // Sort by occurrences (most affected first)
+    affected_files.sort_by_key(|f| std::cmp::Reverse(f.occurrences));
+
+    // Risk is judged on every reference, whatever the filters show
+    let total = affected_files.len() + excluded;
+    let mut analysis = serde_json::json!({
+        "symbol": symbol_path,
+        "depth": depth,
+        "affected_files": affected_files,
+        "total_files": affected_files.len(),
+        "risk_assessment": if total > 10 {
+            "high"
+        } else if total > 3 {
+            "medium"
+        } else {
+            "low"
+        },
+        "recommendation": if total > 10 {
+            "Consider creating a deprecation path or using feature flags"
+        } else if total > 3 {
+            "Run tests after change, review affected files"
+        } else {
+            "Safe to modify with standard review"
+        },
+    });
+    if tests_only || public_only {
+        analysis["filter"] = serde_json::json!(if tests_only { "tests" } else { "public" });
+        analysis["filtered_out"] = serde_json::json!(excluded);
+    }
+    let groups = group_by
+        .as_deref()
+        .and_then(GroupBy::from_name)
+        .map(|by| group(&affected_files, by));
+    if let Some(groups) = &groups {
+        analysis["group_by"] = serde_json::json!(group_by);
+        analysis["groups"] = serde_json::to_value(groups)?;
+    }
+
+    if json {
+        println!("{}", json_string(&analysis)?);
+    } else {
+        println!("Impact analysis for '{}':", symbol_path);
+        println!("  Risk: {}", analysis["risk_assessment"]);
+        match (tests_only, public_only) {
+            (true, _) => println!(
+                "  {} test file(s) affected ({} other file(s) left out)",
+                affected_files.len(),
+                excluded
+            ),
+            (_, true) => println!(
+                "  {} file(s) use it in their public API ({} other file(s) left out)",
+                affected_files.len(),
+                excluded
+            ),
+            _ => println!("  {} file(s) affected", affected_files.len()),
+        }
+        println!();
+
+        if let Some(groups) = &groups {
+            for g in groups.iter().take(10) {
+                println!(
+                    "  {} ({} file(s), {} refs{})",
+                    g.key,
+                    g.files,
+                    g.occurrences,
+                    if g.tests > 0 {
+                        format!(", {} test file(s)", g.tests)
+                    } else {
+                        String::new()
+                    }
+                );
+            }
+            if groups.len() > 10 {
+                println!("  ... and {} more", groups.len() - 10);
+            }
+        } else {
+            for f in affected_files.iter().take(10) {
+                let marker = if f.is_definition { "(def)" } else { "" };
+                println!("  {} ({} refs) {}", f.path, f.occurrences, marker);
+                if !f.public_symbols.is_empty() {
+                    println!("    via {}", f.public_symbols.join(", "));
+                }
+            }
+
+            if affected_files.len() > 10 {
+                println!("  ... and {} more", affected_files.len() - 10);
+            }
+        }
+
+        println!("\n{}", analysis["recommendation"]);
+    }
+
+    Ok(())
+}
+
+/// Consumers in the meta workspace's member repos of `symbol_path`, or of
+/// every public symbol the working copy removes or changes the signature of
+fn cmd_affected_cross_repo(symbol_path: Option<String>, json: bool) -> Result<()> {
+    use agentjj::consumers::{find_usages, Target};
+
+    let mut repo = Repo::discover()?;
+    let members = repo.config()?.meta.members.clone();
+    if members.is_empty() {
+        return Err(agentjj::Error::Config {
+            message: "no meta workspace members - list the other repos under meta.members in .agent/config.toml".into(),
+        }
+        .into());
+    }
+
+    let mut api_changes = Vec::new();
+    let targets: Vec<Target> = match &symbol_path {
+        Some(path) => {
+            let (file, name) = path
+                .split_once("::")
+                .ok_or_else(|| anyhow::anyhow!("Symbol path must be file::symbol_name"))?;
+            let language = agentjj::SupportedLanguage::from_path(std::path::Path::new(file))
+                .ok_or_else(|| anyhow::anyhow!("Unsupported file type: {}", file))?;
+            vec![Target {
+                file: file.to_string(),
+                symbol: name.to_string(),
+                language,
+            }]
+        }
+        None => {
+            let text = |side: &Option<agentjj::unified::Side>| {
+                side.as_ref()
+                    .and_then(|s| agentjj::encoding::decode(&s.content))
+                    .map(|(text, _)| text)
+            };
+            let mut targets = Vec::new();
+            for change in repo.tree_changes("@")? {
+                let path = std::path::Path::new(&change.path);
+                let Some(language) = agentjj::SupportedLanguage::from_path(path) else {
+                    continue;
+                };
+                let (before, after) = (text(&change.before), text(&change.after));
+                for api in agentjj::api::diff_api(
+                    &change.path,
+                    before.as_deref(),
+                    after.as_deref(),
+                    language,
+                ) {
+                    if api.is_breaking() {
+                        targets.push(Target {
+                            file: api.file.clone(),
+                            symbol: api.symbol.clone(),
+                            language,
+                        });
+                        api_changes.push(api);
+                    }
+                }
+            }
+            targets
+        }
+    };
+
+    let mut reports = Vec::new();
+    let mut total = 0;
+    for member in &members {
+        let path = repo.root().join(member);
+        let found = path.is_dir();
+        let consumers = if found && !targets.is_empty() {
+            find_usages(&path, &targets)
+        } else {
+            Vec::new()
+        };
+        total += consumers.len();
+        reports.push(serde_json::json!({
+            "member": member,
+            "path": path.display().to_string(),
+            "found": found,
+            "consumers": consumers,
+        }));
+    }
+
+    let analysis = serde_json::json!({
+        "cross_repo": true,
+        "symbol": symbol_path,
+        "targets": targets,
+        "api_changes": api_changes,
+        "members": reports,
+        "total_consumers": total,
+        "risk_assessment": if total > 10 {
+            "high"
+        } else if total > 0 {
+            "medium"
+        } else {
+            "low"
+        },
+    });
+
+    if json {
+        println!("{}", json_string(&analysis)?);
+        return Ok(());
+    }
+    match &symbol_path {
+        Some(path) => println!("Cross-repo consumers of '{}':", path),
+        None if targets.is_empty() => {
+            println!("The working copy changes no public symbols; nothing to look for");
+            return Ok(());
+        }
+        None => println!(
+            "Cross-repo consumers of {} changed public symbol(s):",
+            targets.len()
+        ),
+    }
+    for report in &reports {
+        let member = report["member"].as_str().unwrap_or("");
+        if report["found"] != true {
+            println!("  {}: not found", member);
+            continue;
+        }
+        let consumers = report["consumers"].as_array().cloned().unwrap_or_default();
+        println!("  {}: {} use(s)", member, consumers.len());
+        for c in &consumers {
+            println!(
+                "    {}:{} {} | {}",
+                c["file"].as_str().unwrap_or(""),
+                c["line"],
+                c["symbol"].as_str().unwrap_or(""),
+                c["text"].as_str().unwrap_or("")
+            );
+        }
+    }
+    println!(
+        "\nRisk: {}",
+        analysis["risk_assessment"].as_str().unwrap_or("")
+    );
+    Ok(())
+}
+
+/// Print JSON schemas for output types
+/// JSON schemas for command output, shared by `schema` and `skill`
+fn output_schemas() -> serde_json::Value {
+    serde_json::json!({
+        "status": {
+            "type": "object",
+            "properties": {
+                "change_id": { "type": "string", "description": "Current jj change ID" },
+                "operation_id": { "type": "string", "description": "Current jj operation ID" },
+                "files_changed": { "type": "array", "items": { "type": "string" } },
+                "has_manifest": { "type": "boolean" },
+                "typed_change": { "type": "object", "nullable": true },
+            }
+        },
+        "symbol": {
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" },
+                "kind": { "type": "string", "enum": ["function", "method", "class", "struct", "enum", "interface", "constant", "variable", "module", "import", "typedef", "macro", "stage", "instruction", "key", "job", "step", "section", "code_block", "table", "column", "index", "view", "alter"] },
+                "signature": { "type": "string", "nullable": true },
+                "docstring": { "type": "string", "nullable": true },
+                "start_line": { "type": "integer" },
+                "end_line": { "type": "integer" },
+            }
+        },
+        "context": {
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" },
+                "kind": { "type": "string" },
+                "signature": { "type": "string", "nullable": true },
+                "docstring": { "type": "string", "nullable": true },
+                "doc": {
+                    "type": "object",
+                    "nullable": true,
+                    "properties": {
+                        "summary": { "type": "string" },
+                        "description": { "type": "string" },
+                        "params": { "type": "array", "items": { "type": "object" } },
+                        "returns": { "type": "object" },
+                        "raises": { "type": "array", "items": { "type": "object" } },
+                        "examples": { "type": "array", "items": { "type": "string" } },
+                    }
+                },
+                "markdown": { "type": "string", "description": "with --render markdown" },
+                "body": { "type": "string", "description": "full text of a Markdown section" },
+                "imports_needed": { "type": "array", "items": { "type": "string" } },
+            }
+        },
+        "apply_result": {
+            "oneOf": [
+                {
+                    "type": "object",
+                    "properties": {
+                        "status": { "const": "success" },
+                        "change_id": { "type": "string" },
+                        "files_changed": { "type": "array" },
+                    }
+                },
+                {
+                    "type": "object",
+                    "properties": {
+                        "status": { "const": "precondition_failed" },
+                        "reason": { "type": "string" },
+                        "expected": { "type": "string" },
+                        "actual": { "type": "string" },
+                    }
+                },
+                {
+                    "type": "object",
+                    "properties": {
+                        "status": { "const": "stale_edit" },
+                        "drifted": { "type": "array", "items": { "type": "object", "properties": {
+                            "path": { "type": "string" },
+                            "lines": { "type": "array", "items": { "type": "integer" }, "description": "[start, end] when the hash covered a range" },
+                            "expected": { "type": "string" },
+                            "actual": { "type": "string", "nullable": true },
+                        }}},
+                    }
+                },
+                {
+                    "type": "object",
+                    "properties": {
+                        "status": { "const": "conflict" },
+                        "conflicts": { "type": "array" },
+                    }
+                },
+                {
+                    "type": "object",
+                    "properties": {
+                        "status": { "const": "pending_approval" },
+                        "approval_id": { "type": "string", "description": "Approve with `approvals approve`, then `apply --resume`" },
+                        "paths": { "type": "array", "items": { "type": "string" } },
+                        "message": { "type": "string" },
+                    }
+                }
+            ]
+        },
+        "approval": {
+            "type": "object",
+            "properties": {
+                "id": { "type": "string" },
+                "status": { "type": "string", "enum": ["pending", "approved", "denied", "applied"] },
+                "intent": { "type": "object", "description": "The intent as submitted" },
+                "paths": { "type": "array", "items": { "type": "string" } },
+                "requested_by": { "type": "string", "description": "Session that submitted it" },
+                "requested_at": { "type": "string" },
+                "decided_by": { "type": "string", "nullable": true },
+                "decided_at": { "type": "string", "nullable": true },
+                "reason": { "type": "string", "nullable": true },
+                "change_id": { "type": "string", "nullable": true, "description": "Set once applied" },
+            }
+        },
+        "error": {
+            "type": "object",
+            "properties": {
+                "error": { "const": true },
+                "message": { "type": "string" },
+            }
+        },
+        "spilled": {
+            "type": "object",
+            "description": "Printed by diff, bulk, and read instead of a result larger than --max-output-bytes",
+            "properties": {
+                "spilled": { "const": true },
+                "path": { "type": "string", "description": "Full result, e.g. .agent/out/diff-<hash>.json" },
+                "bytes": { "type": "integer" },
+                "max_output_bytes": { "type": "integer" },
+                "summary": { "type": "object", "description": "Top-level fields: arrays as {items}, long strings as {bytes}" },
+            }
+        },
+        "orient": {
+            "type": "object",
+            "description": "Complete repository orientation for agents",
+            "properties": {
+                "current_state": { "type": "object" },
+                "repository": { "type": "object", "nullable": true },
+                "codebase": { "type": "object" },
+                "tooling": {
+                    "type": "object",
+                    "properties": {
+                        "build_systems": { "type": "array", "items": { "type": "object", "properties": {
+                            "name": { "type": "string" },
+                            "manifest": { "type": "string" },
+                            "build": { "type": "string" },
+                            "test": { "type": "string" },
+                            "lint": { "type": "string" },
+                        }}},
+                        "test_command": { "type": "string", "nullable": true },
+                        "ci_providers": { "type": "array", "items": { "type": "string" } },
+                        "entry_points": { "type": "array", "items": { "type": "object", "properties": {
+                            "kind": { "type": "string", "enum": ["bin", "main", "script"] },
+                            "name": { "type": "string" },
+                            "path": { "type": "string" },
+                        }}},
+                    }
+                },
+                "how_to": {
+                    "type": "object",
+                    "description": "Concrete build/test/lint/run commands for the primary build system",
+                },
+                "recent_changes": { "type": "array" },
+                "capabilities": { "type": "object" },
+                "quick_start": { "type": "object" },
+            }
+        },
+        "capabilities": {
+            "type": "object",
+            "description": "Which features work in this environment; fields are only added until schema_version changes",
+            "properties": {
+                "schema_version": { "type": "integer" },
+                "version": { "type": "string" },
+                "repository": { "type": "object", "nullable": true, "properties": {
+                    "root": { "type": "string" },
+                    "jj": { "type": "boolean", "description": "False for a git repository not yet colocated" },
+                    "git": { "type": "boolean" },
+                    "manifest": { "type": "boolean" },
+                }},
+                "languages": { "type": "object", "properties": {
+                    "supported": { "type": "array", "items": { "type": "string" } },
+                    "indexed": { "type": "object", "description": "Language -> files in this repository" },
+                    "index_enabled": { "type": "boolean" },
+                }},
+                "tools": { "type": "object", "description": "git, gh, jj -> {available, path, used_by}" },
+                "forge": { "type": "object", "properties": {
+                    "github_token": { "type": "boolean" },
+                    "token_source": { "type": "string", "enum": ["secrets.env", "GH_TOKEN", "GITHUB_TOKEN", "gh auth"] },
+                    "pull_requests": { "type": "boolean", "description": "gh and a token: push --pr works" },
+                }},
+                "lsp": { "type": "object", "properties": {
+                    "available": { "type": "boolean" },
+                    "servers": { "type": "array", "items": { "type": "string" } },
+                    "reason": { "type": "string" },
+                }},
+                "sandbox": { "type": "object", "properties": {
+                    "available": { "type": "boolean" },
+                    "reason": { "type": "string" },
+                }},
+                "daemon": { "type": "object", "properties": {
+                    "running": { "type": "boolean" },
+                    "reason": { "type": "string" },
+                }},
+                "write": { "type": "object", "properties": {
+                    "mode": { "type": "string", "enum": ["read-write", "read-only"] },
+                    "enabled_by": { "type": "string", "enum": ["--read-only", "manifest default_mode"] },
+                }},
+            }
+        },
+        "stats": {
+            "type": "object",
+            "description": "Repository analytics for dashboards and planning",
+            "properties": {
+                "totals": { "type": "object", "properties": {
+                    "files": { "type": "integer" },
+                    "lines": { "type": "integer" },
+                    "bytes": { "type": "integer" },
+                    "symbols": { "type": "integer" },
+                }},
+                "languages": { "type": "object", "description": "Language -> {files, lines, code_lines, blank_lines, bytes}" },
+                "largest_files": { "type": "array", "items": { "type": "object", "properties": {
+                    "path": { "type": "string" },
+                    "bytes": { "type": "integer" },
+                    "lines": { "type": "integer", "nullable": true },
+                    "language": { "type": "string", "nullable": true },
+                }}},
+                "churn": { "type": "object", "properties": {
+                    "changes_scanned": { "type": "integer" },
+                    "hotspots": { "type": "array", "items": { "type": "object", "properties": {
+                        "path": { "type": "string" },
+                        "changes": { "type": "integer" },
+                    }}},
+                }},
+                "symbols_by_module": { "type": "array", "items": { "type": "object", "properties": {
+                    "module": { "type": "string" },
+                    "files": { "type": "integer" },
+                    "symbols": { "type": "integer" },
+                }}},
+                "typed_changes": { "type": "object", "properties": {
+                    "total": { "type": "integer" },
+                    "by_type": { "type": "object" },
+                    "by_category": { "type": "object" },
+                    "by_month": { "type": "object", "description": "YYYY-MM -> type -> count" },
+                    "undated": { "type": "integer" },
+                }},
+            }
+        },
+        "todos": {
+            "type": "object",
+            "description": "TODO/FIXME/HACK comments with the change that last touched their line",
+            "properties": {
+                "todos": { "type": "array", "items": { "type": "object", "properties": {
+                    "file": { "type": "string" },
+                    "line": { "type": "integer" },
+                    "kind": { "type": "string", "enum": ["TODO", "FIXME", "HACK"] },
+                    "text": { "type": "string" },
+                    "change_id": { "type": "string", "nullable": true },
+                    "commit_id": { "type": "string", "nullable": true },
+                    "age_days": { "type": "integer", "nullable": true },
+                    "new": { "type": "boolean", "description": "Introduced by the current change" },
+                }}},
+                "count": { "type": "integer" },
+                "new": { "type": "integer" },
+                "by_kind": { "type": "object" },
+                "max_new": { "type": "integer", "nullable": true, "description": "Manifest [review] max_new_todos" },
+            }
+        },
+        "dupes": {
+            "type": "object",
+            "description": "Clusters of near-duplicate functions (token-shingle Jaccard similarity)",
+            "properties": {
+                "threshold": { "type": "number" },
+                "min_lines": { "type": "integer" },
+                "functions_scanned": { "type": "integer" },
+                "clusters": { "type": "array", "items": { "type": "object", "properties": {
+                    "similarity": { "type": "number", "description": "Lowest similarity among linked pairs" },
+                    "members": { "type": "array", "items": { "type": "object", "properties": {
+                        "file": { "type": "string" },
+                        "name": { "type": "string" },
+                        "kind": { "type": "string" },
+                        "start_line": { "type": "integer" },
+                        "end_line": { "type": "integer" },
+                    }}},
+                }}},
+                "introduced": {
+                    "type": "array",
+                    "description": "With --against-change: new or modified functions and the existing ones they copy (each match adds similarity)",
+                },
+                "count": { "type": "integer" },
+            }
+        },
+    })
+}
+
+fn cmd_schema(type_filter: Option<String>, json: bool) -> Result<()> {
+    let schemas = output_schemas();
+
+    if let Some(type_name) = type_filter {
+        if let Some(schema) = schemas.get(&type_name) {
+            if json {
+                println!("{}", json_string(schema)?);
+            } else {
+                println!("Schema for '{}':", type_name);
+                println!("{}", json_string(schema)?);
+            }
+        } else {
+            anyhow::bail!(
+                "Unknown type: {}. Available: status, symbol, context, apply_result, error, orient",
+                type_name
+            );
+        }
+    } else if json {
+        println!("{}", json_string(&schemas)?);
+    } else {
+        println!("Available schemas:");
+        for key in schemas.as_object().unwrap().keys() {
+            println!("  {}", key);
+        }
+        println!("\nUse --type <name> to see a specific schema");
+    }
+
+    Ok(())
+}
+
+/// Validate current changes are complete
+/// Run test commands, optionally narrowed to the change's impacted tests,
+/// and store the results against the current change
+fn cmd_test_run(affected_only: bool, invariant: Option<String>, json: bool) -> Result<()> {
+    let mut repo = Repo::discover()?;
+    repo.snapshot()?;
+    let change_id = repo.current_change_id()?;
+    let commit_id = repo.current_commit_id()?;
+    let tree_id = repo.current_tree_id()?;
+
+    let commands = test_commands(&mut repo, invariant.as_deref())?;
+
+    let selected = if affected_only {
+        let changed = repo.changed_files(&change_id)?;
+        let mut test_files = Vec::new();
+        for path in codebase_files(&mut repo)? {
+            let rel = path
+                .strip_prefix(repo.root())
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .to_string();
+            let content = std::fs::read_to_string(&path).unwrap_or_default();
+            if agentjj::testrun::is_test_file(&rel, &content) {
+                test_files.push((rel, content));
+            }
+        }
+        Some(agentjj::testrun::affected_tests(&changed, &test_files))
+    } else {
+        None
+    };
+
+    let mut results = Vec::new();
+    let mut progress = agentjj::progress::Progress::start("tests", Some(commands.len()));
+    for (name, command) in &commands {
+        let command = match &selected {
+            // Nothing impacted: no command needs to run
+            Some(tests) if tests.is_empty() => break,
+            Some(tests) => {
+                agentjj::testrun::narrow_command(command, tests).unwrap_or_else(|| command.clone())
+            }
+            None => command.clone(),
+        };
+        results.push(repo.run_test_command(name, &command, &tree_id, &change_id)?);
+        progress.tick();
+    }
+    progress.finish();
+
+    let run = agentjj::testrun::TestRun {
+        change_id: change_id.clone(),
+        commit_id,
+        ran_at: chrono_lite_now(),
+        affected_only,
+        selected,
+        success: results.iter().all(|r| r.success),
+        commands: results,
+    };
+    agentjj::testrun::TestStore::open(repo.root()).save(&run)?;
+
+    if json {
+        println!("{}", json_string(&run)?);
+    } else {
+        if let Some(selected) = &run.selected {
+            println!("{} impacted test file(s)", selected.len());
+        }
+        print_test_results(&run);
+        if run.commands.is_empty() {
+            println!("No impacted tests to run");
+        }
+    }
+
+    if !run.success {
+        exit_with(exit::INVARIANT, None);
+    }
+    Ok(())
+}
+
+/// Manifest invariants are the configured test commands, sorted by name
+/// (just `only`, when given); without any, the detected test command
+fn test_commands(repo: &mut Repo, only: Option<&str>) -> Result<Vec<(String, String)>> {
+    let mut commands: Vec<(String, String)> = repo
+        .manifest()
+        .map(|m| {
+            m.invariants
+                .iter()
+                .map(|(name, inv)| (name.clone(), inv.command().to_string()))
+                .collect()
+        })
+        .unwrap_or_default();
+    commands.sort();
+    if let Some(name) = only {
+        commands.retain(|(n, _)| n == name);
+        if commands.is_empty() {
+            anyhow::bail!("Invariant '{}' not found in the manifest", name);
+        }
+    }
+    if commands.is_empty() {
+        if let Some(test) = agentjj::tooling::detect(repo.root()).test_command() {
+            commands.push(("test".to_string(), test.to_string()));
+        }
+    }
+    if commands.is_empty() {
+        anyhow::bail!("No test commands configured - add an invariant to .agent/manifest.toml");
+    }
+    Ok(commands)
+}
+
+/// Run the test commands against another revision; nothing is stored
+fn cmd_test_run_at(rev: &str, invariant: Option<String>, json: bool) -> Result<()> {
+    let mut repo = Repo::discover()?;
+    let commands = test_commands(&mut repo, invariant.as_deref())?;
+    let run = repo.run_tests_at(rev, &commands)?;
+
+    if json {
+        let mut out = serde_json::to_value(&run)?;
+        out["revision"] = serde_json::json!(rev);
+        println!("{}", json_string(&out)?);
+    } else {
+        println!(
+            "At {} ({}):",
+            rev,
+            &run.change_id[..12.min(run.change_id.len())]
+        );
+        print_test_results(&run);
+    }
+
+    if !run.success {
+        exit_with(exit::INVARIANT, None);
+    }
+    Ok(())
+}
+
+/// One line per test command, then its failing tests and output tail
+fn print_test_results(run: &agentjj::testrun::TestRun) {
+    for result in &run.commands {
+        let mark = if result.success { "✓" } else { "✗" };
+        let counts = match &result.summary.format {
+            Some(_) => format!(
+                " ({} passed, {} failed, {} ignored)",
+                result.summary.passed, result.summary.failed, result.summary.ignored
+            ),
+            None => String::new(),
+        };
+        println!(
+            "{} {}: {}{} in {}ms",
+            mark, result.name, result.command, counts, result.duration_ms
+        );
+        for test in &result.summary.failing {
+            let flaky = if result.known_flakes.contains(test) {
+                " (known flake)"
+            } else {
+                ""
+            };
+            println!("    ✗ {}{}", test, flaky);
+        }
+        if result.known_flakes.contains(&result.name) {
+            println!("    ⚠ known flaky command - retry before reverting");
+        }
+        for line in &result.output_tail {
+            println!("    | {}", line);
+        }
+    }
+}
+
+/// Report tests with inconsistent outcomes on the same tree, from the
+/// history of `test run` and invariant runs
+fn cmd_test_flaky(json: bool) -> Result<()> {
+    let repo = Repo::discover()?;
+    let history = agentjj::testrun::TestStore::open(repo.root()).history();
+    let flakes = agentjj::testrun::flaky(&history);
+
+    if json {
+        println!(
+            "{}",
+            json_string(&serde_json::json!({
+                "flaky": flakes,
+                "runs": history.len(),
+            }))?
+        );
+    } else if flakes.is_empty() {
+        println!("No flaky tests in {} recorded run(s)", history.len());
+    } else {
+        println!("Flaky tests ({} recorded run(s)):", history.len());
+        for flake in &flakes {
+            println!(
+                "  {:<40} {} passed, {} failed on {} tree(s)  [{}]",
+                flake.test, flake.passes, flake.failures, flake.trees, flake.invariant
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// The Rust crate `file` (repository-relative) belongs to: the nearest
+/// Cargo.toml above it with a package
+fn rust_crate_of(root: &std::path::Path, file: &str) -> Option<agentjj::scaffold::RustCrate> {
+    std::path::Path::new(file)
+        .ancestors()
+        .skip(1)
+        .find_map(|dir| {
+            let manifest = std::fs::read_to_string(root.join(dir).join("Cargo.toml")).ok()?;
+            let manifest: toml::Value = toml::from_str(&manifest).ok()?;
+            let name = manifest
+                .get("lib")
+                .and_then(|lib| lib.get("name"))
+                .or_else(|| manifest.get("package")?.get("name"))?
+                .as_str()?;
+            Some(agentjj::scaffold::RustCrate {
+                dir: dir.to_string_lossy().to_string(),
+                name: name.replace('-', "_"),
+            })
+        })
+}
+
+fn cmd_test_scaffold(file: &str, force: bool, dry_run: bool, json: bool) -> Result<()> {
+    use agentjj::scaffold;
+    use agentjj::symbols::{extract_symbols, SupportedLanguage};
+
+    let mut repo = Repo::discover()?;
+    let source = std::path::Path::new(file)
+        .strip_prefix(repo.root())
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|_| file.trim_start_matches("./").to_string());
+    let lang = SupportedLanguage::from_path(std::path::Path::new(&source))
+        .ok_or_else(|| anyhow::anyhow!("Unsupported file type: {}", source))?;
+    let templates = repo
+        .manifest()
+        .map(|m| m.test_templates.clone())
+        .unwrap_or_default();
+    let template = scaffold::template_for(lang, &templates).ok_or_else(|| {
+        anyhow::anyhow!(
+            "No test template for {}: add [test_templates.{}] with path, header, and stub to the manifest",
+            lang.name(),
+            lang.name()
+        )
+    })?;
+
+    let code = std::fs::read_to_string(repo.root().join(&source))?;
+    let symbols = scaffold::testable_symbols(&extract_symbols(&code, lang)?, lang, &code);
+    let rust_crate = rust_crate_of(repo.root(), &source);
+    let scaffold = scaffold::render(lang, &template, &source, &symbols, rust_crate.as_ref());
+
+    let target = repo.root().join(&scaffold.test_file);
+    if target.exists() && !force && !dry_run {
+        return Err(agentjj::Error::PreconditionFailed {
+            reason: format!(
+                "{} already exists (pass --force to overwrite it)",
+                scaffold.test_file
+            ),
+            expected: "no test file".to_string(),
+            actual: scaffold.test_file.clone(),
+        }
+        .into());
+    }
+    if !dry_run {
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&target, &scaffold.content)?;
+    }
+
+    if json {
+        let mut result = serde_json::to_value(&scaffold)?;
+        result["created"] = serde_json::json!(!dry_run);
+        println!("{}", json_string(&result)?);
+    } else if dry_run {
+        println!("Would create {}:\n", scaffold.test_file);
+        print!("{}", scaffold.content);
+    } else {
+        println!(
+            "Created {} with {} test stub(s) for {}",
+            scaffold.test_file,
+            scaffold.symbols.len(),
+            scaffold.source
+        );
+    }
+
+    Ok(())
+}
+
+fn cmd_triage(log: Option<String>, ci: bool, json: bool) -> Result<()> {
+    use agentjj::triage;
+
+    let mut repo = Repo::discover()?;
+    let (text, source) = match log {
+        Some(path) if path == "-" => {
+            let mut buf = String::new();
+            std::io::Read::read_to_string(&mut std::io::stdin(), &mut buf)?;
+            (buf, "stdin".to_string())
+        }
+        Some(path) => (std::fs::read_to_string(&path)?, path),
+        None if ci => ci_failed_log(&mut repo)?,
+        None => anyhow::bail!("a log file or --ci is required"),
+    };
+
+    let mut failures = triage::parse_log(&text);
+    // Runners print absolute paths; culprits are matched repo-relative
+    let root = format!("{}/", repo.root().display());
+    for failure in &mut failures {
+        if let Some(file) = failure.file.as_mut().filter(|f| f.starts_with(&root)) {
+            *file = file[root.len()..].to_string();
+        }
+    }
+
+    // Symbols the current change adds or modifies, compared with its parent
+    repo.snapshot()?;
+    let (parent, commit) = repo.resolve_revision("@")?;
+    let changed = repo.commit_changed_files(&commit)?;
+    let mut touched = Vec::new();
+    for file in &changed {
+        let Some(lang) = agentjj::SupportedLanguage::from_path(std::path::Path::new(file)) else {
+            continue;
+        };
+        let Ok(after) = repo.read_file(file, Some(&commit)) else {
+            continue;
+        };
+        let Ok(after_symbols) = agentjj::symbols::extract_symbols(&after, lang) else {
+            continue;
+        };
+        let before = parent
+            .as_deref()
+            .and_then(|p| repo.read_file(file, Some(p)).ok())
+            .and_then(|content| {
+                let symbols = agentjj::symbols::extract_symbols(&content, lang).ok()?;
+                Some((content, symbols))
+            });
+        touched.extend(triage::touched_symbols(
+            file,
+            before.as_ref().map(|(c, s)| (c.as_str(), s.as_slice())),
+            (&after, &after_symbols),
+        ));
+    }
+    let culprits = triage::culprits(&failures, &changed, &touched);
+
+    if json {
+        println!(
+            "{}",
+            json_string(&serde_json::json!({
+                "source": source,
+                "failures": failures,
+                "changed_files": changed,
+                "touched_symbols": touched,
+                "culprits": culprits,
+            }))?
+        );
+    } else if failures.is_empty() {
+        println!("No failures recognized in {}", source);
+    } else {
+        println!("{} failure(s) in {}:", failures.len(), source);
+        for f in &failures {
+            let location = match (&f.file, f.line) {
+                (Some(file), Some(line)) => format!("{}:{}", file, line),
+                (Some(file), None) => file.clone(),
+                _ => "?".to_string(),
+            };
+            let name = f
+                .symbol
+                .as_deref()
+                .map(|s| format!(" {}", s))
+                .unwrap_or_default();
+            println!("  [{}]{} at {}: {}", f.source, name, location, f.message);
+        }
+        println!();
+        if culprits.is_empty() {
+            println!("No likely culprits among {} changed file(s)", changed.len());
+        } else {
+            println!("Likely culprits:");
+            for c in &culprits {
+                let note = if c.changed {
+                    ""
+                } else {
+                    " (not in this change)"
+                };
+                println!("  {:>3}  {}{}", c.score, c.file, note);
+                for reason in &c.reasons {
+                    println!("         - {}", reason);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Sessions, agents, and changes that modified a path, from commit history,
+/// typed-change metadata, and the audit log
+fn cmd_who_touched(path: String, since: Option<String>, json: bool) -> Result<()> {
+    use agentjj::ownership::{self, CommitFiles};
+
+    let mut repo = Repo::discover()?;
+    let since = since.map(|s| parse_change_time(&s)).transpose()?;
+
+    // The working-copy commit is work in progress: nobody owns it yet
+    let working_copy = repo.current_commit_id()?;
+    let mut commits = Vec::new();
+    for entry in repo.history_since(None)? {
+        if entry.commit_id == working_copy
+            || since
+                .as_deref()
+                .is_some_and(|s| entry.timestamp.as_str() < s)
+        {
+            continue;
+        }
+        let files = repo.commit_changed_files(&entry.commit_id)?;
+        commits.push(CommitFiles {
+            change_id: entry.change_id,
+            commit_id: entry.commit_id,
+            description: entry.description,
+            timestamp: entry.timestamp,
+            author: entry.author,
+            files,
+        });
+    }
+    let index = agentjj::change::ChangeIndex::load_from_repo(repo.root())?;
+    let log = agentjj::audit::AuditLog::open(repo.root());
+    let audit: Vec<agentjj::audit::AuditRecord> = log
+        .sessions()
+        .iter()
+        .filter_map(|session| log.session(session).ok())
+        .flatten()
+        .collect();
+    let report = ownership::who_touched(&path, since.as_deref(), &commits, &index.all(), &audit)?;
+
+    if json {
+        println!("{}", json_string(&report)?);
+        return Ok(());
+    }
+    let short = |id: &Option<String>| {
+        id.as_deref()
+            .map(|id| id[..12.min(id.len())].to_string())
+            .unwrap_or_else(|| "-".to_string())
+    };
+    let Some(last) = &report.last else {
+        println!("No recorded changes touch {}", report.path);
+        return Ok(());
+    };
+    println!(
+        "{}: last touched in {} by {} ({})",
+        report.path,
+        short(&last.change_id),
+        last.session.as_deref().unwrap_or("no session"),
+        last.agent.as_deref().unwrap_or("unknown agent"),
+    );
+    if !report.sessions.is_empty() {
+        println!("\nSessions:");
+        for session in &report.sessions {
+            println!(
+                "  {}  {} change(s), last {}{}",
+                session.session,
+                session.changes,
+                session.last_touched.as_deref().unwrap_or("unknown"),
+                if session.agents.is_empty() {
+                    String::new()
+                } else {
+                    format!(", agents: {}", session.agents.join(", "))
+                }
+            );
+        }
+    }
+    println!("\nChanges:");
+    for touch in &report.changes {
+        println!(
+            "  {}  {}  {}  {}",
+            touch.timestamp.as_deref().unwrap_or("-"),
+            short(&touch.change_id),
+            touch.session.as_deref().unwrap_or("-"),
+            touch.description.as_deref().unwrap_or("")
+        );
+    }
+    if report.unattributed > 0 {
+        println!(
+            "\n{} change(s) were made without agentjj and have no session",
+            report.unattributed
+        );
+    }
+    Ok(())
+}
+
+/// The failed-step log of the most recent failed GitHub Actions run
+fn ci_failed_log(repo: &mut Repo) -> Result<(String, String)> {
+    let runs = gh_stdout(
+        repo,
+        &[
+            "run",
+            "list",
+            "--status",
+            "failure",
+            "--limit",
+            "1",
+            "--json",
+            "databaseId",
+        ],
+    )?;
+    let id = serde_json::from_str::<serde_json::Value>(&runs)?
+        .get(0)
+        .and_then(|run| run["databaseId"].as_u64())
+        .ok_or_else(|| anyhow::anyhow!("no failed CI runs found"))?
+        .to_string();
+    let log = gh_stdout(repo, &["run", "view", &id, "--log-failed"])?;
+    Ok((log, format!("CI run {}", id)))
+}
+
+/// Run the invariants against another revision, checked out on its own,
+/// to tell whether it was already broken before the current change
+fn cmd_validate_at(rev: &str, merge_base: bool, json: bool) -> Result<()> {
+    let mut repo = Repo::discover()?;
+    let commands = test_commands(&mut repo, None)?;
+    let base = if merge_base {
+        let commit = repo.merge_base(rev, "@")?;
+        Some((repo.commit_change_id(&commit)?, commit))
+    } else {
+        None
+    };
+    let run = repo.run_tests_at(
+        base.as_ref()
+            .map(|(change_id, _)| change_id.as_str())
+            .unwrap_or(rev),
+        &commands,
+    )?;
+    // What was checked out: REV, or where @ branched off it
+    let checked = match &base {
+        Some((change_id, _)) => format!(
+            "merge base of {} ({})",
+            rev,
+            &change_id[..12.min(change_id.len())]
+        ),
+        None => rev.to_string(),
+    };
+    let issues: Vec<String> = run
+        .commands
+        .iter()
+        .filter(|r| !r.success)
+        .map(|r| {
+            if r.summary.failing.is_empty() {
+                format!("{} failed at {}", r.name, checked)
+            } else {
+                format!(
+                    "{} failed at {}: {}",
+                    r.name,
+                    checked,
+                    r.summary.failing.join(", ")
+                )
+            }
+        })
+        .collect();
+
+    if json {
+        let mut output = serde_json::json!({
+            "valid": run.success,
+            "revision": rev,
+            "change_id": run.change_id,
+            "commit_id": run.commit_id,
+            "issues": issues,
+            "tests": run,
+        });
+        if let Some((change_id, commit_id)) = &base {
+            output["merge_base"] = serde_json::json!({
+                "change_id": change_id,
+                "commit_id": commit_id,
+            });
+        }
+        println!("{}", json_string(&output)?);
+    } else {
+        if run.success {
+            println!("✓ {} passes {} invariant(s)", checked, run.commands.len());
+        } else {
+            println!("✗ {} fails {} invariant(s)", checked, issues.len());
+        }
+        print_test_results(&run);
+    }
+
+    if !run.success {
+        exit_with(exit::POLICY, Some("validation_failed"));
+    }
+    Ok(())
+}
+
+fn cmd_validate(fix: bool, json: bool) -> Result<()> {
+    let mut repo = Repo::discover()?;
+    // Validate what is on disk, not the last snapshot
+    repo.snapshot()?;
+
+    let change_id = repo.current_change_id()?;
+    let mut files = repo.changed_files(&change_id)?;
+
+    // Fix first, record the fixes in the change, then validate the result
+    let fixes = if fix {
+        let report = repo.run_fixers(&files)?;
+        repo.snapshot()?;
+        files = repo.changed_files(&change_id)?;
+        Some(report)
+    } else {
+        None
+    };
+
+    let mut issues = Vec::new();
+    let mut warnings = Vec::new();
+
+    // Problems the built-in fixers would settle
+    let mut fixable = Vec::new();
+    for file in &files {
+        let Ok(content) = std::fs::read(repo.root().join(file)) else {
+            continue;
+        };
+        if let Some((_, fixers)) = agentjj::fix::apply_builtins(file, &content) {
+            warnings.push(format!(
+                "{}: {} (fix with 'agentjj validate --fix')",
+                file,
+                fixers.join(", ").replace('_', " ")
+            ));
+            fixable.push(agentjj::fix::FileFix {
+                path: file.clone(),
+                fixers: fixers.into_iter().map(str::to_string).collect(),
+            });
+        }
+    }
+
+    // Check if there are any changes
+    if files.is_empty() {
+        issues.push("No changes to validate".to_string());
+    }
+
+    // Check for typed change metadata
+    let typed_change = repo.get_typed_change(&change_id).ok();
+    if typed_change.is_none() {
+        warnings.push("No typed change metadata - consider using 'agentjj change set'".to_string());
+    }
+    let breaking_change = typed_change.as_ref().is_some_and(|c| c.is_breaking());
+    if breaking_change && typed_change.as_ref().is_some_and(|c| c.notes.is_none()) {
+        warnings.push(
+            "Breaking change has no notes - record what breaks and how to migrate with 'agentjj change notes --set'"
+                .to_string(),
+        );
+    }
+
+    // Check manifest exists
+    if !repo.has_manifest() {
+        warnings.push("No manifest found - consider using 'agentjj init'".to_string());
+    }
+
+    // Check for common issues in changed files
+    let test_templates = repo
+        .manifest()
+        .map(|m| m.test_templates.clone())
+        .unwrap_or_default();
+    for file in &files {
+        let path = std::path::Path::new(file);
+
+        // Check for test files if code was changed
+        if path
+            .extension()
+            .map(|e| e == "rs" || e == "py" || e == "ts" || e == "js")
+            .unwrap_or(false)
+        {
+            let is_test = file.contains("test")
+                || file.contains("spec")
+                || file.contains("_test.")
+                || file.contains(".test.");
+            if !is_test {
+                // For Rust files, tests are often inline (mod tests) - skip warning
+                // For other languages, check common test locations
+                let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+                if ext != "rs" {
+                    let file_stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+
+                    // Check common test locations
+                    let test_patterns = [
+                        format!("tests/{}.{}", file_stem, ext),
+                        format!("test/{}.{}", file_stem, ext),
+                        format!("tests/test_{}.{}", file_stem, ext),
+                        format!("{}_test.{}", file_stem, ext),
+                        format!("{}.test.{}", file_stem, ext),
+                        format!("{}.spec.{}", file_stem, ext),
+                    ];
+
+                    // Where `test scaffold` would put them
+                    let scaffolded = agentjj::SupportedLanguage::from_path(path)
+                        .and_then(|lang| agentjj::scaffold::template_for(lang, &test_templates))
+                        .map(|template| agentjj::scaffold::test_path(&template, file, None));
+
+                    let has_test = test_patterns
+                        .iter()
+                        .chain(scaffolded.iter())
+                        .any(|p| repo.root().join(p).exists());
+                    if !has_test {
+                        warnings.push(format!(
+                            "Consider adding tests for {} - start one with 'agentjj test scaffold {}'",
+                            file, file
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    // Structural changes to infra files: shell functions, Dockerfile stages,
+    // YAML keys, workflow jobs and steps
+    let mut infra_changes = Vec::new();
+    let parent = repo
+        .resolve_revision("@")
+        .ok()
+        .and_then(|(parent, _)| parent);
+    for file in &files {
+        let Some(lang) = agentjj::SupportedLanguage::from_path(std::path::Path::new(file)) else {
+            continue;
+        };
+        if !matches!(
+            lang,
+            agentjj::SupportedLanguage::Shell
+                | agentjj::SupportedLanguage::Dockerfile
+                | agentjj::SupportedLanguage::Yaml
+        ) {
+            continue;
+        }
+        let before = parent
+            .as_deref()
+            .and_then(|p| repo.read_file(file, Some(p)).ok());
+        let after = std::fs::read_to_string(repo.root().join(file)).ok();
+        let changes = agentjj::api::diff_api(file, before.as_deref(), after.as_deref(), lang);
+        // Instructions repeat (many RUNs), so only their stages are compared
+        for change in changes
+            .into_iter()
+            .filter(|c| c.kind != agentjj::SymbolKind::Instruction)
+        {
+            if change.change == agentjj::api::ApiChangeKind::Removed {
+                let kind = serde_json::to_value(change.kind)?;
+                warnings.push(format!(
+                    "{}: {} '{}' removed",
+                    file,
+                    kind.as_str().unwrap_or("symbol"),
+                    change.symbol
+                ));
+            }
+            infra_changes.push(change);
+        }
+    }
+
+    // Destructive SQL schema operations need --breaking or a policy override
+    let schema_changes = destructive_schema_ops(&mut repo, &files);
+    let breaking = typed_change.as_ref().is_some_and(|c| c.breaking);
+    for op in blocking_schema_ops(&mut repo, schema_changes.clone()) {
+        let message = format!(
+            "{}:{}: destructive schema operation {} on '{}'",
+            op.file,
+            op.line,
+            serde_json::to_value(op.operation)?
+                .as_str()
+                .unwrap_or("drop"),
+            op.object
+        );
+        if breaking {
+            warnings.push(message);
+        } else {
+            issues.push(format!(
+                "{} - mark the change breaking or allow it in the manifest",
+                message
+            ));
+        }
+    }
+
+    // Prompt-injection patterns in changed docs, under the manifest's policy
+    let (prompt_findings, block_prompts) = changed_prompt_findings(&mut repo, &files);
+    for finding in &prompt_findings {
+        if block_prompts {
+            issues.push(prompt_warning(finding));
+        } else {
+            warnings.push(prompt_warning(finding));
+        }
+    }
+
+    // New TODO/FIXME/HACK comments, capped by the manifest's review policy
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    let mut new_todos = 0;
+    for file in &files {
+        new_todos += file_todos(&mut repo, file, now)
+            .iter()
+            .filter(|t| t["new"] == true)
+            .count();
+    }
+    let max_new_todos = repo.manifest().ok().and_then(|m| m.review.max_new_todos);
+    if let Some(max) = max_new_todos.filter(|max| new_todos > *max) {
+        issues.push(format!(
+            "{} new TODO/FIXME/HACK comment(s), the manifest allows {} per change",
+            new_todos, max
+        ));
+    }
+
+    // Results of the last `test run` on this change
+    let test_run = agentjj::testrun::TestStore::open(repo.root()).get(&change_id);
+    let tests_fresh = match &test_run {
+        Some(run) if run.commit_id == repo.current_commit_id()? => {
+            if !run.success {
+                issues.push(format!("Tests failed: {}", run.failures().join(", ")));
+            }
+            true
+        }
+        Some(_) => {
+            warnings.push(
+                "Test results are stale (files changed since) - rerun 'agentjj test run'"
+                    .to_string(),
+            );
+            false
+        }
+        None => false,
+    };
+
+    // Check invariants from manifest
+    if let Ok(manifest) = repo.manifest() {
+        if !manifest.invariants.is_empty() && test_run.is_none() {
+            warnings.push(format!(
+                "{} invariant(s) defined - run 'agentjj test run' to verify",
+                manifest.invariants.len()
+            ));
+        }
+    }
+
+    let is_valid = issues.is_empty();
+
+    if json {
+        println!(
+            "{}",
+            json_string(&serde_json::json!({
+                "valid": is_valid,
+                "change_id": change_id,
+                "files_changed": files,
+                "typed_change": typed_change,
+                "issues": issues,
+                "warnings": warnings,
+                "infra_changes": infra_changes,
+                "schema_changes": schema_changes,
+                "todos": { "new": new_todos, "max_new": max_new_todos },
+                "prompt_findings": prompt_findings,
+                "fixable": fixable,
+                "fixes": fixes,
+                "tests": test_run.map(|run| serde_json::json!({
+                    "success": run.success,
+                    "fresh": tests_fresh,
+                    "ran_at": run.ran_at,
+                    "failing": run.failures(),
+                })),
+            }))?
+        );
+    } else {
+        if is_valid {
+            println!("✓ Changes are valid");
+        } else {
+            println!("✗ Validation failed");
+        }
+
+        println!("  {} file(s) changed", files.len());
+
+        if let Some(fixes) = &fixes {
+            if fixes.fixed.is_empty() {
+                println!("  Nothing to fix");
+            }
+            for fix in &fixes.fixed {
+                println!("  ✓ fixed {} ({})", fix.path, fix.fixers.join(", "));
+            }
+            for failure in &fixes.failed {
+                println!(
+                    "  ✗ fixer {} failed: {}",
+                    failure.fixer,
+                    failure.stderr.lines().next().unwrap_or("")
+                );
+            }
+        }
+
+        if !issues.is_empty() {
+            println!("\nIssues:");
+            for issue in &issues {
+                println!("  ✗ {}", issue);
+            }
+        }
+
+        if !warnings.is_empty() {
+            println!("\nWarnings:");
+            for warning in &warnings {
+                println!("  ⚠ {}", warning);
+            }
+        }
+
+        if is_valid && warnings.is_empty() {
+            println!("\nReady to push!");
+        }
+    }
+
+    if !is_valid {
+        exit_with(
+            if files.is_empty() {
+                exit::PRECONDITION
+            } else {
+                exit::POLICY
+            },
+            Some("validation_failed"),
+        );
+    }
+    exit_if_strict_warnings(&warnings);
+
+    Ok(())
+}
+
+/// Fail with suggested commit groups when the working copy's change (or
+/// the `paths` part of it) is over the manifest's review limits
+fn check_change_size(
+    repo: &mut Repo,
+    paths: Option<&[String]>,
+    max_lines: Option<usize>,
+    max_files: Option<usize>,
+) -> agentjj::Result<()> {
+    let files: Vec<agentjj::partition::ChangedFile> = repo
+        .tree_changes("@")?
+        .into_iter()
+        .filter(|c| !c.path.starts_with(".agent/"))
+        .filter(|c| paths.is_none_or(|paths| paths.contains(&c.path)))
+        .map(|c| agentjj::partition::ChangedFile {
+            lines: c
+                .line_counts()
+                .map_or(0, |(added, removed)| added + removed),
+            content: c
+                .after
+                .as_ref()
+                .and_then(|side| String::from_utf8(side.content.clone()).ok()),
+            path: c.path,
+        })
+        .collect();
+    let lines = files.iter().map(|f| f.lines).sum();
+    let over =
+        max_lines.is_some_and(|max| lines > max) || max_files.is_some_and(|max| files.len() > max);
+    if !over {
+        return Ok(());
+    }
+    Err(agentjj::Error::ChangeTooLarge {
+        files: files.len(),
+        lines,
+        max_files,
+        max_lines,
+        groups: agentjj::partition::partition(&files, max_lines, max_files),
+    })
+}
+
+/// Destructive operations in the changed SQL files, comparing the parent of
+/// the working copy with what is on disk
+fn destructive_schema_ops(repo: &mut Repo, files: &[String]) -> Vec<agentjj::sql::DestructiveOp> {
+    let parent = repo
+        .resolve_revision("@")
+        .ok()
+        .and_then(|(parent, _)| parent);
+    let mut ops = Vec::new();
+    for file in files {
+        if agentjj::SupportedLanguage::from_path(std::path::Path::new(file))
+            != Some(agentjj::SupportedLanguage::Sql)
+        {
+            continue;
+        }
+        let before = parent
+            .as_deref()
+            .and_then(|p| repo.read_file(file, Some(p)).ok());
+        let after = std::fs::read_to_string(repo.root().join(file)).ok();
+        ops.extend(agentjj::sql::destructive_ops(
+            file,
+            before.as_deref(),
+            after.as_deref(),
+        ));
+    }
+    ops
+}
+
+/// Prompt-injection findings in the changed docs, comparing the parent of the
+/// working copy with what is on disk, and whether the manifest blocks them.
+/// Empty unless the manifest sets `[security] scan_prompts`.
+fn changed_prompt_findings(
+    repo: &mut Repo,
+    files: &[String],
+) -> (Vec<agentjj::prompts::PromptFinding>, bool) {
+    let security = match repo.manifest() {
+        Ok(manifest) if manifest.security.scan_prompts => manifest.security.clone(),
+        _ => return (Vec::new(), false),
+    };
+    let parent = repo
+        .resolve_revision("@")
+        .ok()
+        .and_then(|(parent, _)| parent);
+    let mut findings = Vec::new();
+    for file in files.iter().filter(|f| agentjj::prompts::is_scanned(f)) {
+        let before = parent
+            .as_deref()
+            .and_then(|p| repo.read_file(file, Some(p)).ok());
+        let after = std::fs::read_to_string(repo.root().join(file)).ok();
+        findings.extend(agentjj::prompts::scan(
+            file,
+            before.as_deref(),
+            after.as_deref(),
+        ));
+    }
+    let block = security.prompt_policy == agentjj::manifest::PromptPolicy::Block;
+    (findings, block)
+}
+
+fn prompt_warning(finding: &agentjj::prompts::PromptFinding) -> String {
+    format!(
+        "{}:{}: possible prompt injection ({}): {}",
+        finding.file,
+        finding.line,
+        serde_json::to_value(finding.pattern)
+            .ok()
+            .and_then(|v| v.as_str().map(str::to_string))
+            .unwrap_or_default(),
+        finding.excerpt
+    )
+}
 
-            if !json {
-                println!("✓ Created PR: {}", pr_url);
-            }
-        } else {
-            let stderr = String::from_utf8_lossy(&pr_output.stderr);
-            result["pr_created"] = serde_json::json!(false);
-            result["pr_error"] = serde_json::json!(stderr.to_string());
+/// The operations the manifest's `allow_destructive_schema` does not cover
+fn blocking_schema_ops(
+    repo: &mut Repo,
+    ops: Vec<agentjj::sql::DestructiveOp>,
+) -> Vec<agentjj::sql::DestructiveOp> {
+    let Ok(manifest) = repo.manifest() else {
+        return ops;
+    };
+    ops.into_iter()
+        .filter(|op| !manifest.permissions.allows_destructive_schema(&op.file))
+        .collect()
+}
+/// Output the repository DAG in various formats
+fn cmd_graph(format: String, limit: usize, all: bool, kind: &str, json: bool) -> Result<()> {
+    let mut repo = Repo::discover()?;
 
-            if !json {
-                println!("✗ Failed to create PR: {}", stderr);
-            }
-        }
+    if kind == "ops" {
+        return cmd_graph_ops(&mut repo, &format.to_lowercase(), limit, json);
+    }
+    match format.to_lowercase().as_str() {
+        "ascii" => cmd_graph_ascii(&mut repo, limit, all, json),
+        "mermaid" => cmd_graph_mermaid(&mut repo, limit, all, json),
+        "dot" => cmd_graph_dot(&mut repo, limit, all, json),
+        _ => anyhow::bail!(
+            "Unknown format: {}. Use 'ascii', 'mermaid', or 'dot'",
+            format
+        ),
     }
+}
 
+/// The dashboard: drawn live in a terminal, or printed once with --json
+fn cmd_ui(limit: usize, interval: u64, json: bool) -> Result<()> {
+    let mut repo = Repo::discover()?;
     if json {
-        println!("{}", serde_json::to_string_pretty(&result)?);
+        let dashboard = agentjj::dashboard::Dashboard::load(&mut repo, limit)?;
+        println!("{}", json_string(&dashboard)?);
+        return Ok(());
     }
+    run_dashboard(repo, limit, interval)
+}
 
+#[cfg(feature = "tui")]
+fn run_dashboard(repo: Repo, limit: usize, interval: u64) -> Result<()> {
+    use std::io::IsTerminal;
+    if !std::io::stdout().is_terminal() {
+        return Err(agentjj::Error::Config {
+            message: "agentjj ui needs a terminal; use `agentjj --json ui` for the data".into(),
+        }
+        .into());
+    }
+    agentjj::tui::run(
+        repo.root().to_path_buf(),
+        limit,
+        std::time::Duration::from_secs(interval.max(1)),
+        READ_ONLY.load(Ordering::Relaxed),
+    )?;
     Ok(())
 }
 
-/// Complete repository orientation - everything an agent needs to start working
-fn cmd_orient(json: bool) -> Result<()> {
-    let mut repo = Repo::discover()?;
+#[cfg(not(feature = "tui"))]
+fn run_dashboard(_repo: Repo, _limit: usize, _interval: u64) -> Result<()> {
+    Err(agentjj::Error::Config {
+        message:
+            "this agentjj was built without the `tui` feature; use `agentjj --json ui` for the data"
+                .into(),
+    }
+    .into())
+}
 
-    let change_id = repo
-        .current_change_id()
-        .unwrap_or_else(|_| "unknown".into());
-    let operation_id = repo
-        .current_operation_id()
-        .unwrap_or_else(|_| "unknown".into());
-    let files = repo.changed_files(&change_id).unwrap_or_default();
-    let has_manifest = repo.has_manifest();
+/// Graph node representation for structured output
+#[derive(Clone)]
+struct GraphNode {
+    id: String,
+    description: String,
+    parents: Vec<String>,
+    timestamp: Option<String>,
+    author: Option<String>,
+    full_commit_id: String,
+    immutable: bool,
+    /// Typed-change `depends_on`, shortened like `id`
+    depends_on: Vec<String>,
+}
 
-    // Get manifest info
-    let manifest_info = if has_manifest {
-        repo.manifest().ok().map(|m| {
-            serde_json::json!({
-                "name": m.repo.name,
-                "description": m.repo.description,
-                "languages": m.repo.languages,
-                "invariants_count": m.invariants.len(),
-                "permissions": {
-                    "allow": m.permissions.allow_change,
-                    "deny": m.permissions.deny_change,
-                },
+/// Get structured graph nodes using Repo.log_entries()
+fn get_graph_nodes(repo: &mut Repo, limit: usize, all: bool) -> Result<Vec<GraphNode>> {
+    let entries = repo.log_entries(limit, all)?;
+    let index = agentjj::change::ChangeIndex::load_from_repo(repo.root()).ok();
+    let depends_on = |id: &str| -> Vec<String> {
+        index
+            .iter()
+            .flat_map(|index| index.all())
+            .find(|c| c.change_id.starts_with(id))
+            .map(|c| {
+                c.depends_on
+                    .iter()
+                    .map(|dep| dep[..id.len().min(dep.len())].to_string())
+                    .collect()
             })
-        })
-    } else {
-        None
+            .unwrap_or_default()
     };
 
-    // Count files by extension
-    let mut file_counts: std::collections::HashMap<String, usize> =
-        std::collections::HashMap::new();
-    let mut total_files = 0;
-
-    // Patterns to exclude from file counting
-    let exclude_patterns = [
-        ".jj",
-        ".git",
-        "target/",
-        "node_modules/",
-        ".agent/",
-        "__pycache__",
-        ".pyc",
-        "venv/",
-        ".venv/",
-    ];
-
-    if let Ok(entries) = glob::glob(&format!("{}/**/*", repo.root().display())) {
-        for entry in entries.flatten() {
-            let path_str = entry.to_string_lossy();
-            let should_exclude = exclude_patterns.iter().any(|p| path_str.contains(p));
-
-            if entry.is_file() && !should_exclude {
-                total_files += 1;
-                if let Some(ext) = entry.extension() {
-                    *file_counts
-                        .entry(ext.to_string_lossy().to_string())
-                        .or_insert(0) += 1;
-                }
-            }
-        }
-    }
-
-    // Get recent changes via jj-lib (no jj CLI dependency)
-    let recent_changes: Vec<serde_json::Value> = repo
-        .log_entries(5, false)
-        .unwrap_or_default()
+    let nodes = entries
         .into_iter()
-        .map(|entry| {
-            serde_json::json!({
-                "change_id": entry.change_id,
-                "description": if entry.description.is_empty() {
-                    "(no description)".to_string()
-                } else {
-                    entry.description
-                },
-            })
+        .map(|entry| GraphNode {
+            depends_on: depends_on(&entry.change_id),
+            id: entry.change_id,
+            description: entry.description,
+            parents: entry.parent_change_ids,
+            timestamp: entry.timestamp,
+            author: entry.author,
+            full_commit_id: entry.full_commit_id,
+            immutable: entry.immutable,
         })
         .collect();
 
-    // Get typed changes
-    let typed_changes = agentjj::change::ChangeIndex::load_from_repo(repo.root())
-        .ok()
-        .map(|idx| idx.all().len())
-        .unwrap_or(0);
+    Ok(nodes)
+}
 
-    let orientation = serde_json::json!({
-        "current_state": {
-            "change_id": change_id,
-            "operation_id": &operation_id[..32.min(operation_id.len())],
-            "uncommitted_files": files,
-        },
-        "repository": manifest_info,
-        "codebase": {
-            "total_files": total_files,
-            "by_extension": file_counts,
-            "typed_changes": typed_changes,
-        },
-        "recent_changes": recent_changes,
-        "capabilities": {
-            "symbol_query": ["python", "rust", "javascript", "typescript"],
-            "commands": [
-                "status", "read", "symbol", "context", "apply",
-                "change", "commit", "push", "orient", "checkpoint", "undo",
-                "bulk", "files", "diff", "affected", "validate", "suggest",
-                "graph", "tag", "schema", "skill", "quickstart"
-            ],
-        },
-        "quick_start": {
-            "read_file": "agentjj read <path>",
-            "query_symbol": "agentjj symbol <file>::<name>",
-            "get_context": "agentjj context <file>::<name>",
-            "commit_changes": "agentjj commit -m 'feat: description'",
-            "make_change": "agentjj apply --intent '...' --type behavioral --patch <file>",
-            "save_checkpoint": "agentjj checkpoint create <name>",
-        },
-    });
+/// Dependency edges whose ends are both drawn, as (dependent, dependency)
+fn graph_dependency_edges(nodes: &[GraphNode]) -> Vec<(&str, &str)> {
+    nodes
+        .iter()
+        .flat_map(|n| {
+            n.depends_on
+                .iter()
+                .map(move |dep| (n.id.as_str(), dep.as_str()))
+        })
+        .filter(|(_, dep)| nodes.iter().any(|n| n.id == *dep))
+        .collect()
+}
+
+/// ASCII format: structured log output with optional timestamps
+fn cmd_graph_ascii(repo: &mut Repo, limit: usize, all: bool, json: bool) -> Result<()> {
+    let nodes = get_graph_nodes(repo, limit, all)?;
 
     if json {
-        println!("{}", serde_json::to_string_pretty(&orientation)?);
+        // Also get the raw ASCII diagram for backwards compatibility
+        let ascii_output = repo.log_ascii(limit, all).unwrap_or_default();
+        println!(
+            "{}",
+            json_string(&serde_json::json!({
+                "format": "ascii",
+                "diagram": ascii_output,
+                "nodes": nodes.iter().map(|n| serde_json::json!({
+                    "id": n.id,
+                    "description": n.description,
+                    "parents": n.parents,
+                    "timestamp": n.timestamp,
+                    "author": n.author,
+                    "full_commit_id": n.full_commit_id,
+                    "immutable": n.immutable,
+                    "depends_on": n.depends_on,
+                })).collect::<Vec<_>>(),
+            }))?
+        );
     } else {
-        println!("=== Repository Orientation ===\n");
-        println!("Current change: {}", &change_id[..12.min(change_id.len())]);
-        if !files.is_empty() {
-            println!("Uncommitted: {} files", files.len());
+        // Render ASCII graph with timestamps inline
+        for node in &nodes {
+            let ts_part = node
+                .timestamp
+                .as_deref()
+                .map(|ts| format!(" [{}]", ts))
+                .unwrap_or_default();
+            let desc = if node.description.is_empty() {
+                "(empty)".to_string()
+            } else {
+                node.description.clone()
+            };
+            // Pushed history is drawn as jj draws it
+            let marker = if node.immutable { '◆' } else { '*' };
+            let deps = if node.depends_on.is_empty() {
+                String::new()
+            } else {
+                format!(" (depends on {})", node.depends_on.join(", "))
+            };
+            println!("{} {}{} {}{}", marker, node.id, ts_part, desc, deps);
         }
-        println!();
+    }
 
-        if let Some(info) = &manifest_info {
-            println!("Project: {}", info["name"]);
-            if !info["description"].as_str().unwrap_or("").is_empty() {
-                println!("  {}", info["description"]);
-            }
-        }
+    Ok(())
+}
 
-        println!("\nCodebase: {} files", total_files);
-        let mut sorted_counts: Vec<_> = file_counts.iter().collect();
-        sorted_counts.sort_by(|a, b| b.1.cmp(a.1));
-        for (ext, count) in sorted_counts.iter().take(5) {
-            println!("  .{}: {}", ext, count);
-        }
+/// Mermaid format: generate flowchart from jj log
+fn cmd_graph_mermaid(repo: &mut Repo, limit: usize, all: bool, json: bool) -> Result<()> {
+    let nodes = get_graph_nodes(repo, limit, all)?;
 
-        if !recent_changes.is_empty() {
-            println!("\nRecent changes:");
-            for c in recent_changes.iter().take(3) {
-                let cid = c["change_id"].as_str().unwrap_or("");
-                let short_id = &cid[..8.min(cid.len())];
-                println!("  {} {}", short_id, c["description"]);
-            }
+    // Build Mermaid flowchart
+    let mut diagram = String::from("flowchart TD\n");
+
+    for node in &nodes {
+        // Escape quotes in description and truncate
+        let desc = node.description.replace('"', "'").replace('\n', " ");
+        let truncated_desc = if desc.chars().count() > 40 {
+            format!("{}...", desc.chars().take(37).collect::<String>())
+        } else {
+            desc.clone()
+        };
+
+        // Include timestamp in the node label when available
+        let ts_suffix = node
+            .timestamp
+            .as_deref()
+            .map(|ts| format!("<br/>{}", ts))
+            .unwrap_or_default();
+
+        // Node definition with short ID
+        diagram.push_str(&format!(
+            "  {}[\"{}{}\"]\n",
+            node.id, truncated_desc, ts_suffix
+        ));
+
+        // Edges to parents
+        for parent_id in &node.parents {
+            diagram.push_str(&format!("  {} --> {}\n", node.id, parent_id));
         }
+    }
 
-        println!("\n=== Quick Start ===");
-        println!("  agentjj symbol <file>           # List symbols in file");
-        println!("  agentjj context <file>::<name>  # Get symbol context");
-        println!("  agentjj bulk read <files...>    # Read multiple files");
-        println!("  agentjj checkpoint create <name> # Save restore point");
+    // Dependencies are dotted, unlike parent edges
+    for (id, dep) in graph_dependency_edges(&nodes) {
+        diagram.push_str(&format!("  {} -.->|depends on| {}\n", id, dep));
+    }
+
+    let immutable: Vec<&str> = nodes
+        .iter()
+        .filter(|n| n.immutable)
+        .map(|n| n.id.as_str())
+        .collect();
+    if !immutable.is_empty() {
+        diagram.push_str("  classDef immutable fill:#eee,stroke:#999\n");
+        diagram.push_str(&format!("  class {} immutable\n", immutable.join(",")));
+    }
+
+    if json {
+        println!(
+            "{}",
+            json_string(&serde_json::json!({
+                "format": "mermaid",
+                "diagram": diagram,
+                "nodes": nodes.iter().map(|n| serde_json::json!({
+                    "id": n.id,
+                    "description": n.description,
+                    "parents": n.parents,
+                    "timestamp": n.timestamp,
+                    "author": n.author,
+                    "full_commit_id": n.full_commit_id,
+                    "immutable": n.immutable,
+                    "depends_on": n.depends_on,
+                })).collect::<Vec<_>>(),
+            }))?
+        );
+    } else {
+        print!("{}", diagram);
     }
 
     Ok(())
 }
 
-/// Create a named checkpoint
-fn cmd_checkpoint(name: String, description: Option<String>, json: bool) -> Result<()> {
-    let mut repo = Repo::discover()?;
+/// DOT format: generate Graphviz output from jj log
+fn cmd_graph_dot(repo: &mut Repo, limit: usize, all: bool, json: bool) -> Result<()> {
+    let nodes = get_graph_nodes(repo, limit, all)?;
 
-    let change_id = repo.current_change_id()?;
-    let operation_id = repo.current_operation_id()?;
+    // Build DOT graph
+    let mut diagram = String::from("digraph G {\n");
+    diagram.push_str("  rankdir=BT;\n");
+    diagram.push_str("  node [shape=box, style=rounded];\n\n");
 
-    // Store checkpoint as a file in .agent/checkpoints/
-    let checkpoints_dir = repo.root().join(".agent/checkpoints");
-    std::fs::create_dir_all(&checkpoints_dir)?;
+    for node in &nodes {
+        // Escape quotes in description and truncate
+        let desc = node.description.replace('"', "\\\"").replace('\n', "\\n");
+        let truncated_desc = if desc.chars().count() > 40 {
+            format!("{}...", desc.chars().take(37).collect::<String>())
+        } else {
+            desc.clone()
+        };
 
-    let checkpoint = serde_json::json!({
-        "name": name,
-        "description": description,
-        "change_id": change_id,
-        "operation_id": operation_id,
-        "created_at": chrono_lite_now(),
-    });
+        // Include timestamp in the label when available
+        let ts_line = node
+            .timestamp
+            .as_deref()
+            .map(|ts| format!("\\n{}", ts))
+            .unwrap_or_default();
 
-    let checkpoint_path = checkpoints_dir.join(format!("{}.json", name));
-    std::fs::write(&checkpoint_path, serde_json::to_string_pretty(&checkpoint)?)?;
+        // Node definition; pushed history is shaded
+        let fill = if node.immutable {
+            ", style=\"rounded,filled\", fillcolor=lightgray"
+        } else {
+            ""
+        };
+        diagram.push_str(&format!(
+            "  \"{}\" [label=\"{}\\n{}{}\"{}];\n",
+            node.id, node.id, truncated_desc, ts_line, fill
+        ));
+
+        // Edges to parents
+        for parent_id in &node.parents {
+            diagram.push_str(&format!("  \"{}\" -> \"{}\";\n", node.id, parent_id));
+        }
+    }
+
+    // Dependencies are dashed, unlike parent edges
+    for (id, dep) in graph_dependency_edges(&nodes) {
+        diagram.push_str(&format!(
+            "  \"{}\" -> \"{}\" [style=dashed, color=blue, label=\"depends on\"];\n",
+            id, dep
+        ));
+    }
+
+    diagram.push_str("}\n");
 
     if json {
         println!(
             "{}",
-            serde_json::to_string_pretty(&serde_json::json!({
-                "created": true,
-                "checkpoint": checkpoint,
-                "restore_command": format!("agentjj undo --to {}", name),
+            json_string(&serde_json::json!({
+                "format": "dot",
+                "diagram": diagram,
+                "nodes": nodes.iter().map(|n| serde_json::json!({
+                    "id": n.id,
+                    "description": n.description,
+                    "parents": n.parents,
+                    "timestamp": n.timestamp,
+                    "author": n.author,
+                    "full_commit_id": n.full_commit_id,
+                    "immutable": n.immutable,
+                    "depends_on": n.depends_on,
+                })).collect::<Vec<_>>(),
             }))?
         );
     } else {
-        println!("✓ Checkpoint '{}' created", name);
-        println!("  change: {}", &change_id[..12.min(change_id.len())]);
-        println!("  restore with: agentjj undo --to {}", name);
+        print!("{}", diagram);
     }
 
     Ok(())
 }
 
-/// List all checkpoints sorted by created_at descending
-fn cmd_checkpoint_list(json: bool) -> Result<()> {
-    let repo = Repo::discover()?;
-    let checkpoints_dir = repo.root().join(".agent/checkpoints");
+/// Operation log as a graph: each operation points at its parent, and undo
+/// and restore operations get a highlighted edge to the state they brought back
+fn cmd_graph_ops(repo: &mut Repo, format: &str, limit: usize, json: bool) -> Result<()> {
+    let nodes = repo.operation_graph(limit)?;
 
-    if !checkpoints_dir.exists() || !checkpoints_dir.is_dir() {
-        if json {
-            println!(
-                "{}",
-                serde_json::to_string_pretty(&serde_json::json!({
-                    "checkpoints": []
-                }))?
-            );
+    // Checkpoints mark the operations worth restoring to
+    let mut checkpoints: std::collections::HashMap<String, Vec<String>> =
+        std::collections::HashMap::new();
+    if let Ok(dir) = std::fs::read_dir(repo.root().join(".agent/checkpoints")) {
+        for path in dir.flatten().map(|e| e.path()) {
+            let Ok(content) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            let Ok(checkpoint) = serde_json::from_str::<serde_json::Value>(&content) else {
+                continue;
+            };
+            if let (Some(name), Some(op)) = (
+                checkpoint["name"].as_str(),
+                checkpoint["operation_id"].as_str(),
+            ) {
+                checkpoints
+                    .entry(op.to_string())
+                    .or_default()
+                    .push(name.to_string());
+            }
+        }
+    }
+    for names in checkpoints.values_mut() {
+        names.sort();
+    }
+    let checkpoints_of = |node: &agentjj::repo::OperationNode| {
+        checkpoints.get(&node.full_id).cloned().unwrap_or_default()
+    };
+    // (fill, stroke) for highlighted nodes and edges
+    let palette = |kind: agentjj::repo::OperationEdgeKind| match kind {
+        agentjj::repo::OperationEdgeKind::Undo => ("#fde68a", "#d97706"),
+        agentjj::repo::OperationEdgeKind::Restore => ("#bfdbfe", "#2563eb"),
+    };
+    let truncate = |desc: String| {
+        if desc.chars().count() > 40 {
+            format!("{}...", desc.chars().take(37).collect::<String>())
         } else {
-            println!("No checkpoints found.");
+            desc
         }
-        return Ok(());
+    };
+
+    let diagram = match format {
+        "ascii" => {
+            let mut diagram = String::new();
+            for node in &nodes {
+                let marker = if node.is_head { "@" } else { "*" };
+                diagram.push_str(&format!(
+                    "{} {} [{}] {}\n",
+                    marker, node.id, node.timestamp, node.description
+                ));
+                if let Some((kind, target)) = &node.restores {
+                    diagram.push_str(&format!("  ↺ {} → {}\n", kind.as_str(), target));
+                }
+                let names = checkpoints_of(node);
+                if !names.is_empty() {
+                    diagram.push_str(&format!("  ⚑ checkpoint: {}\n", names.join(", ")));
+                }
+            }
+            diagram
+        }
+        "mermaid" => {
+            let mut diagram = String::from("flowchart TD\n");
+            let mut highlighted = Vec::new();
+            let mut edges = 0;
+            for node in &nodes {
+                let desc = truncate(node.description.replace('"', "'").replace('\n', " "));
+                let names = checkpoints_of(node);
+                let checkpoint = if names.is_empty() {
+                    String::new()
+                } else {
+                    format!("<br/>⚑ {}", names.join(", "))
+                };
+                diagram.push_str(&format!(
+                    "  op_{}[\"{}<br/>{}{}\"]\n",
+                    node.id, desc, node.timestamp, checkpoint
+                ));
+                for parent in &node.parents {
+                    diagram.push_str(&format!("  op_{} --> op_{}\n", node.id, parent));
+                    edges += 1;
+                }
+                if let Some((kind, target)) = &node.restores {
+                    diagram.push_str(&format!(
+                        "  op_{} -.->|{}| op_{}\n",
+                        node.id,
+                        kind.as_str(),
+                        target
+                    ));
+                    highlighted.push((edges, *kind));
+                    edges += 1;
+                    diagram.push_str(&format!("  class op_{} {}\n", node.id, kind.as_str()));
+                }
+            }
+            for kind in [
+                agentjj::repo::OperationEdgeKind::Undo,
+                agentjj::repo::OperationEdgeKind::Restore,
+            ] {
+                let (fill, stroke) = palette(kind);
+                diagram.push_str(&format!(
+                    "  classDef {} fill:{},stroke:{}\n",
+                    kind.as_str(),
+                    fill,
+                    stroke
+                ));
+            }
+            for (edge, kind) in highlighted {
+                diagram.push_str(&format!(
+                    "  linkStyle {} stroke:{},stroke-width:2px\n",
+                    edge,
+                    palette(kind).1
+                ));
+            }
+            diagram
+        }
+        "dot" => {
+            let mut diagram = String::from("digraph Ops {\n");
+            diagram.push_str("  rankdir=BT;\n");
+            diagram.push_str("  node [shape=box, style=rounded];\n\n");
+            for node in &nodes {
+                let desc = truncate(node.description.replace('"', "\\\"").replace('\n', "\\n"));
+                let names = checkpoints_of(node);
+                let checkpoint = if names.is_empty() {
+                    String::new()
+                } else {
+                    format!("\\ncheckpoint: {}", names.join(", "))
+                };
+                let style = node
+                    .restores
+                    .as_ref()
+                    .map(|(kind, _)| {
+                        format!(
+                            ", style=\"rounded,filled\", fillcolor=\"{}\"",
+                            palette(*kind).0
+                        )
+                    })
+                    .unwrap_or_default();
+                diagram.push_str(&format!(
+                    "  \"{}\" [label=\"{}\\n{}\\n{}{}\"{}];\n",
+                    node.id, node.id, desc, node.timestamp, checkpoint, style
+                ));
+                for parent in &node.parents {
+                    diagram.push_str(&format!("  \"{}\" -> \"{}\";\n", node.id, parent));
+                }
+                if let Some((kind, target)) = &node.restores {
+                    diagram.push_str(&format!(
+                        "  \"{}\" -> \"{}\" [style=dashed, color=\"{}\", penwidth=2, label=\"{}\"];\n",
+                        node.id,
+                        target,
+                        palette(*kind).1,
+                        kind.as_str()
+                    ));
+                }
+            }
+            diagram.push_str("}\n");
+            diagram
+        }
+        _ => anyhow::bail!(
+            "Unknown format: {}. Use 'ascii', 'mermaid', or 'dot'",
+            format
+        ),
+    };
+
+    if json {
+        println!(
+            "{}",
+            json_string(&serde_json::json!({
+                "kind": "ops",
+                "format": format,
+                "diagram": diagram,
+                "nodes": nodes.iter().map(|n| serde_json::json!({
+                    "id": n.id,
+                    "full_id": n.full_id,
+                    "description": n.description,
+                    "parents": n.parents,
+                    "timestamp": n.timestamp,
+                    "is_head": n.is_head,
+                    "restores": n.restores.as_ref().map(|(kind, target)| serde_json::json!({
+                        "kind": kind.as_str(),
+                        "operation": target,
+                    })),
+                    "checkpoints": checkpoints_of(n),
+                })).collect::<Vec<_>>(),
+            }))?
+        );
+    } else {
+        print!("{}", diagram);
     }
 
-    let mut checkpoints: Vec<serde_json::Value> = Vec::new();
+    Ok(())
+}
 
-    for entry in std::fs::read_dir(&checkpoints_dir)? {
-        let entry = entry?;
-        let path = entry.path();
-        if path.extension().and_then(|e| e.to_str()) == Some("json") {
-            let content = std::fs::read_to_string(&path)?;
-            if let Ok(checkpoint) = serde_json::from_str::<serde_json::Value>(&content) {
-                checkpoints.push(checkpoint);
-            }
+/// Output the CLI specification; always JSON, it is meant for programs
+fn cmd_cli_spec(command: Option<String>) -> Result<()> {
+    let mut spec = agentjj::cli_spec::build(&Cli::command(), |argv| {
+        Cli::try_parse_from(argv)
+            .ok()
+            .map(|cli| query_command(&cli.command))
+    });
+    if let Some(name) = command {
+        let prefix = format!("{} ", name);
+        spec.commands
+            .retain(|c| c.name == name || c.name.starts_with(&prefix));
+        if spec.commands.is_empty() {
+            anyhow::bail!(
+                "Unknown command: {}. Run `agentjj cli-spec` for the full list",
+                name
+            );
         }
     }
+    println!("{}", json_string(&spec)?);
+    Ok(())
+}
 
-    if checkpoints.is_empty() {
+/// Output the full skill documentation, embedded at compile time
+fn cmd_skill(command: Option<String>, json: bool) -> Result<()> {
+    use agentjj::skill;
+
+    let doc = include_str!("../docs/skill.md");
+    let registry = skill::registry(&Cli::command());
+    let schemas = output_schemas();
+
+    if let Some(name) = command {
+        let Some(cmd) = skill::find(&registry, &name) else {
+            let known: Vec<&str> = registry
+                .iter()
+                .filter(|c| !c.name.contains(' '))
+                .map(|c| c.name.as_str())
+                .collect();
+            anyhow::bail!("Unknown command: {}. Available: {}", name, known.join(", "));
+        };
+        let guide = skill::guide(doc, cmd, &schemas);
         if json {
-            println!(
-                "{}",
-                serde_json::to_string_pretty(&serde_json::json!({
-                    "checkpoints": []
-                }))?
-            );
+            let mut out = serde_json::to_value(&guide)?;
+            out["format"] = "markdown".into();
+            out["content"] = skill::render_guide(&guide).into();
+            println!("{}", json_string(&out)?);
         } else {
-            println!("No checkpoints found.");
+            print!("{}", skill::render_guide(&guide));
         }
         return Ok(());
     }
 
-    // Sort by created_at descending
-    checkpoints.sort_by(|a, b| {
-        let a_time = a["created_at"].as_str().unwrap_or("");
-        let b_time = b["created_at"].as_str().unwrap_or("");
-        b_time.cmp(a_time)
-    });
+    // Outside a repository (or without a manifest) the doc is just generic
+    let manifest = Repo::discover()
+        .ok()
+        .and_then(|mut repo| repo.manifest().ok().cloned());
+    let schema_names: Vec<String> = schemas
+        .as_object()
+        .map(|o| o.keys().cloned().collect())
+        .unwrap_or_default();
+    let content = skill::render(doc, &registry, &schema_names, manifest.as_ref());
 
     if json {
         println!(
             "{}",
-            serde_json::to_string_pretty(&serde_json::json!({
-                "checkpoints": checkpoints
+            json_string(&serde_json::json!({
+                "format": "markdown",
+                "content": content,
+                "description": "Full agentjj skill documentation for agent self-discovery",
+                "commands": registry,
             }))?
         );
     } else {
-        println!("Checkpoints:");
-        for cp in &checkpoints {
-            let name = cp["name"].as_str().unwrap_or("(unknown)");
-            let created_at = cp["created_at"].as_str().unwrap_or("");
-            // Format timestamp for display: "2026-02-14T10:23:15Z" -> "2026-02-14 10:23:15"
-            let display_time = created_at
-                .replace('T', " ")
-                .trim_end_matches('Z')
-                .to_string();
-            let description = cp["description"]
-                .as_str()
-                .map(|d| format!("\"{}\"", d))
-                .unwrap_or_else(|| "(no description)".to_string());
-            println!("  {:<30} {}  {}", name, display_time, description);
-        }
+        print!("{}", content);
     }
 
     Ok(())
 }
 
-fn chrono_lite_now() -> String {
-    use std::time::{SystemTime, UNIX_EPOCH};
-    let duration = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default();
-    let secs = duration.as_secs() as i64;
-    let days = secs.div_euclid(86400);
-    let time_of_day = secs.rem_euclid(86400);
-    let (year, month, day) = agentjj::repo::days_to_ymd(days);
-    let hours = time_of_day / 3600;
-    let minutes = (time_of_day % 3600) / 60;
-    let seconds = time_of_day % 60;
-    format!(
-        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
-        year, month, day, hours, minutes, seconds
-    )
+/// Total size in bytes of a file or directory tree
+fn disk_usage(path: &std::path::Path) -> u64 {
+    let Ok(meta) = std::fs::symlink_metadata(path) else {
+        return 0;
+    };
+    if !meta.is_dir() {
+        return meta.len();
+    }
+    std::fs::read_dir(path)
+        .map(|dir| dir.flatten().map(|e| disk_usage(&e.path())).sum())
+        .unwrap_or(0)
 }
 
-/// Undo operations or restore to checkpoint
-fn cmd_undo(steps: usize, to: Option<String>, dry_run: bool, json: bool) -> Result<()> {
+/// Disk usage of each .agent subsystem against its [storage] cap
+fn cmd_stats_storage(json: bool) -> Result<()> {
     let mut repo = Repo::discover()?;
+    let caps = if repo.has_manifest() {
+        repo.manifest()?.storage.clone()
+    } else {
+        Default::default()
+    };
+    let report = agentjj::storage::report(repo.root(), &caps);
 
-    // If --to is specified, restore to named checkpoint
-    if let Some(checkpoint_name) = to {
-        let checkpoint_path = repo
-            .root()
-            .join(".agent/checkpoints")
-            .join(format!("{}.json", checkpoint_name));
+    if json {
+        println!("{}", json_string(&report)?);
+        return Ok(());
+    }
 
-        if !checkpoint_path.exists() {
-            anyhow::bail!("Checkpoint '{}' not found", checkpoint_name);
+    println!(
+        "=== .agent Storage ({}) ===",
+        format_size(report.total_bytes)
+    );
+    for usage in &report.subsystems {
+        let cap = match usage.cap_bytes {
+            Some(cap) => format!(" / cap {}", format_size(cap)),
+            None => String::new(),
+        };
+        println!(
+            "  {:<12} {:>10}{}  {} file(s){}",
+            usage.name,
+            format_size(usage.bytes),
+            cap,
+            usage.files,
+            if usage.over_cap { "  OVER CAP" } else { "" }
+        );
+    }
+    println!("  {:<12} {:>10}", "other", format_size(report.other_bytes));
+
+    let recommendations: Vec<_> = report
+        .subsystems
+        .iter()
+        .filter_map(|u| Some((u.name, u.recommendation.as_ref()?)))
+        .collect();
+    if !recommendations.is_empty() {
+        println!("\n=== Recommendations ===");
+        for (name, recommendation) in recommendations {
+            println!("  {}: {}", name, recommendation);
         }
+    }
+    Ok(())
+}
 
-        let checkpoint_data: serde_json::Value =
-            serde_json::from_str(&std::fs::read_to_string(&checkpoint_path)?)?;
-        let target_op = checkpoint_data["operation_id"]
-            .as_str()
-            .ok_or_else(|| anyhow::anyhow!("Invalid checkpoint: missing operation_id"))?;
+/// Per-command runs and error rates from the local usage metrics
+fn cmd_stats_usage(json: bool) -> Result<()> {
+    use agentjj::metrics::{self, UsageLog};
 
-        if dry_run {
-            if json {
-                println!(
-                    "{}",
-                    serde_json::json!({
-                        "dry_run": true,
-                        "checkpoint": checkpoint_name,
-                        "would_restore_to": target_op,
-                        "checkpoint_data": checkpoint_data,
-                    })
-                );
-            } else {
-                println!("Would restore to checkpoint '{}'", checkpoint_name);
-                println!(
-                    "Would restore to operation: {}...",
-                    &target_op[..16.min(target_op.len())]
-                );
-            }
+    let mut repo = Repo::discover()?;
+    let enabled = repo.config()?.metrics.enabled;
+    let events = UsageLog::open(repo.root()).events();
+    let usage = metrics::summarize(&events);
+    let hints = metrics::hints(&events, unix_now());
+    let cache_config = repo.config()?.cache.clone();
+    let cache_stats = agentjj::blobcache::load_stats(repo.root());
+    let (cache_entries, cache_bytes) = agentjj::blobcache::disk_usage(repo.root());
+
+    if json {
+        println!(
+            "{}",
+            json_string(&serde_json::json!({
+                "enabled": enabled,
+                "events": events.len(),
+                "commands": usage,
+                "hints": hints,
+                "read_cache": {
+                    "memory_hits": cache_stats.memory_hits,
+                    "disk_hits": cache_stats.disk_hits,
+                    "misses": cache_stats.misses,
+                    "hit_rate": cache_stats.hit_rate(),
+                    "disk": {
+                        "enabled": cache_config.disk,
+                        "entries": cache_entries,
+                        "bytes": cache_bytes,
+                    },
+                },
+            }))?
+        );
+        return Ok(());
+    }
+
+    if cache_stats.lookups() > 0 {
+        println!(
+            "=== Read Cache ===\n  {} hits ({} memory, {} disk), {} misses ({:.1}% hit rate)",
+            cache_stats.memory_hits + cache_stats.disk_hits,
+            cache_stats.memory_hits,
+            cache_stats.disk_hits,
+            cache_stats.misses,
+            cache_stats.hit_rate() * 100.0
+        );
+        if cache_config.disk {
+            println!(
+                "  {} blob(s) on disk, {}",
+                cache_entries,
+                format_size(cache_bytes)
+            );
+        }
+        println!();
+    }
+    if !enabled {
+        println!(
+            "Usage metrics are off - enable with `metrics.enabled = true` in .agent/config.toml"
+        );
+        if events.is_empty() {
             return Ok(());
         }
+        println!();
+    }
+    if usage.is_empty() {
+        println!("No usage recorded yet");
+        return Ok(());
+    }
+    println!("=== Usage ({} runs) ===", events.len());
+    for u in &usage {
+        println!(
+            "  {:<20} {:>5} runs  {:>5.1}% failed",
+            u.command,
+            u.runs,
+            u.error_rate * 100.0
+        );
+        for (error, count) in &u.errors {
+            println!("    {} x{}", error, count);
+        }
+    }
+    if !hints.is_empty() {
+        println!("\n=== Recent Failures Suggest ===");
+        for hint in &hints {
+            println!("  {}\n    $ {}", hint.reason, hint.command);
+        }
+    }
+    Ok(())
+}
 
-        // Restore to checkpoint operation using Repo method
-        repo.restore_operation(target_op)?;
+/// Repository analytics: size per language, largest files, churn hotspots,
+/// symbols per module, and typed-change distribution
+fn cmd_stats(changes: usize, top: usize, json: bool) -> Result<()> {
+    use agentjj::stats::{self, LanguageStats};
+    use std::collections::BTreeMap;
 
-        if json {
-            println!(
-                "{}",
-                serde_json::json!({
-                    "restored": true,
-                    "checkpoint": checkpoint_name,
-                    "restored_to": target_op,
-                })
+    let mut repo = Repo::discover()?;
+    let root = repo.root().to_path_buf();
+
+    // Size: LOC per language, largest files, symbols per module
+    let mut languages: BTreeMap<String, LanguageStats> = BTreeMap::new();
+    let mut sizes: Vec<(String, u64, Option<usize>, Option<&'static str>)> = Vec::new();
+    let mut modules: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+    let mut total_symbols = 0;
+    let mut total_lines = 0;
+    let mut total_bytes = 0;
+
+    let codebase = codebase_files(&mut repo)?;
+    let mut progress = agentjj::progress::Progress::start("scan", Some(codebase.len()));
+    for path in &codebase {
+        if agentjj::deadline::expired() {
+            exit_timed_out(
+                "scan",
+                serde_json::json!({ "files_scanned": sizes.len(), "languages": languages }),
+                json,
             );
-        } else {
-            println!("✓ Restored to checkpoint '{}'", checkpoint_name);
         }
+        let rel_path = path
+            .strip_prefix(&root)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .to_string();
+        let bytes = path.metadata().map(|m| m.len()).unwrap_or(0);
+        let language = stats::language_for(path);
+        // Binary files still count towards size, just not lines
+        let content = std::fs::read_to_string(path).ok();
+        let lines = content.as_deref().map(stats::count_lines);
+
+        total_bytes += bytes;
+        if let Some(lines) = lines {
+            total_lines += lines.total;
+            if let Some(language) = language {
+                languages
+                    .entry(language.to_string())
+                    .or_default()
+                    .add(lines, bytes);
+            }
+        }
+        sizes.push((rel_path.clone(), bytes, lines.map(|l| l.total), language));
 
-        return Ok(());
+        if let (Some(lang), Some(content)) = (agentjj::SupportedLanguage::from_path(path), &content)
+        {
+            if let Ok(symbols) = agentjj::symbols::extract_symbols(content, lang) {
+                let module = modules.entry(stats::module_of(&rel_path)).or_default();
+                module.0 += 1;
+                module.1 += symbols.len();
+                total_symbols += symbols.len();
+            }
+        }
+        progress.tick();
     }
+    progress.finish();
 
-    // Otherwise, undo by steps
-    // Use Repo.operation_log() to find operations to undo
-    let operations = repo.operation_log(steps + 1)?;
-
-    if operations.len() <= steps {
-        anyhow::bail!("Not enough operations to undo {} steps", steps);
-    }
+    sizes.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    let largest_files: Vec<serde_json::Value> = sizes
+        .iter()
+        .take(top)
+        .map(|(path, bytes, lines, language)| {
+            serde_json::json!({
+                "path": path,
+                "bytes": bytes,
+                "lines": lines,
+                "language": language,
+            })
+        })
+        .collect();
 
-    let target_op = &operations[steps].id;
+    let mut symbols_by_module: Vec<serde_json::Value> = modules
+        .iter()
+        .map(|(module, (files, symbols))| {
+            serde_json::json!({ "module": module, "files": files, "symbols": symbols })
+        })
+        .collect();
+    symbols_by_module.sort_by(|a, b| b["symbols"].as_u64().cmp(&a["symbols"].as_u64()));
 
-    if dry_run {
-        if json {
-            println!(
-                "{}",
+    // Churn: how often each path changed in the last N committed changes
+    let log = repo
+        .log_entries(changes.saturating_add(1), false)
+        .unwrap_or_default();
+    let recent: Vec<_> = log
+        .iter()
+        .filter(|e| !e.is_working_copy)
+        .take(changes)
+        .collect();
+    let mut churn: BTreeMap<String, usize> = BTreeMap::new();
+    let mut progress = agentjj::progress::Progress::start("churn", Some(recent.len()));
+    for (scanned, entry) in recent.iter().enumerate() {
+        if agentjj::deadline::expired() {
+            exit_timed_out(
+                "churn",
                 serde_json::json!({
-                    "dry_run": true,
-                    "would_restore_to": target_op,
-                    "operations_to_undo": steps,
-                })
-            );
-        } else {
-            println!("Would undo {} operation(s)", steps);
-            println!(
-                "Would restore to operation: {}...",
-                &target_op[..16.min(target_op.len())]
+                    "languages": languages,
+                    "largest_files": largest_files,
+                    "churn": {
+                        "changes_scanned": scanned,
+                        "hotspots": stats::top_counts(&churn, top),
+                    },
+                }),
+                json,
             );
         }
-        return Ok(());
+        for file in repo
+            .commit_changed_files(&entry.full_commit_id)
+            .unwrap_or_default()
+        {
+            *churn.entry(file).or_insert(0) += 1;
+        }
+        progress.tick();
     }
+    progress.finish();
+    let hotspots: Vec<serde_json::Value> = stats::top_counts(&churn, top)
+        .into_iter()
+        .map(|(path, count)| serde_json::json!({ "path": path, "changes": count }))
+        .collect();
 
-    // Actually undo using Repo method
-    repo.restore_operation(target_op)?;
+    // Typed changes by type, category, and the month their commit was authored
+    let index = agentjj::change::ChangeIndex::load_from_repo(&root).unwrap_or_default();
+    let typed = index.all();
+    let label = |value: serde_json::Value| value.as_str().unwrap_or("unknown").to_string();
+    let mut by_type: BTreeMap<String, usize> = BTreeMap::new();
+    let mut by_category: BTreeMap<String, usize> = BTreeMap::new();
+    let mut by_month: BTreeMap<String, BTreeMap<String, usize>> = BTreeMap::new();
+    let mut undated = 0;
+    for change in &typed {
+        let change_type = label(serde_json::to_value(change.change_type)?);
+        *by_type.entry(change_type.clone()).or_insert(0) += 1;
+        // A change counts once under each of its categories
+        for category in &change.categories {
+            *by_category.entry(category.to_string()).or_insert(0) += 1;
+        }
+        if change.categories.is_empty() {
+            *by_category.entry("none".to_string()).or_insert(0) += 1;
+        }
+
+        // Log entries carry short change ids; typed changes carry full ones
+        let month = log
+            .iter()
+            .find(|e| change.change_id.starts_with(&e.change_id))
+            .and_then(|e| e.timestamp.as_deref())
+            .and_then(stats::month_of);
+        match month {
+            Some(month) => {
+                *by_month
+                    .entry(month)
+                    .or_default()
+                    .entry(change_type)
+                    .or_insert(0) += 1
+            }
+            None => undated += 1,
+        }
+    }
+
+    let result = serde_json::json!({
+        "totals": {
+            "files": codebase.len(),
+            "lines": total_lines,
+            "bytes": total_bytes,
+            "symbols": total_symbols,
+        },
+        "languages": languages,
+        "largest_files": largest_files,
+        "churn": {
+            "changes_scanned": recent.len(),
+            "hotspots": hotspots,
+        },
+        "symbols_by_module": symbols_by_module,
+        "typed_changes": {
+            "total": typed.len(),
+            "by_type": by_type,
+            "by_category": by_category,
+            "by_month": by_month,
+            "undated": undated,
+        },
+    });
 
     if json {
+        println!("{}", json_string(&result)?);
+        return Ok(());
+    }
+
+    println!(
+        "Codebase: {} files, {} lines, {}",
+        codebase.len(),
+        total_lines,
+        format_size(total_bytes)
+    );
+    let mut by_lines: Vec<_> = languages.iter().collect();
+    by_lines.sort_by_key(|(_, lang_stats)| std::cmp::Reverse(lang_stats.lines));
+    for (language, lang_stats) in by_lines {
         println!(
-            "{}",
-            serde_json::json!({
-                "undone": true,
-                "steps": steps,
-                "restored_to": target_op,
-            })
+            "  {:<12} {:>5} files {:>8} lines",
+            language, lang_stats.files, lang_stats.lines
         );
-    } else {
-        println!("✓ Undid {} operation(s)", steps);
     }
 
-    Ok(())
-}
+    println!("\nLargest files:");
+    for (path, bytes, _, _) in sizes.iter().take(top) {
+        println!("  {:>10}  {}", format_size(*bytes), path);
+    }
 
-/// Bulk operations
-fn cmd_bulk(action: BulkAction, json: bool) -> Result<()> {
-    let mut repo = Repo::discover()?;
+    println!("\nChurn hotspots (last {} changes):", recent.len());
+    for hotspot in &hotspots {
+        println!(
+            "  {:>4}  {}",
+            hotspot["changes"],
+            hotspot["path"].as_str().unwrap_or("")
+        );
+    }
 
-    match action {
-        BulkAction::Read { paths } => {
-            let mut results = Vec::new();
-            let mut errors = Vec::new();
+    println!("\nSymbols: {} total", total_symbols);
+    for module in symbols_by_module.iter().take(top) {
+        println!(
+            "  {:>5}  {}",
+            module["symbols"],
+            module["module"].as_str().unwrap_or("")
+        );
+    }
 
-            for path in &paths {
-                match repo.read_file(path, None) {
-                    Ok(content) => {
-                        results.push(serde_json::json!({
-                            "path": path,
-                            "content": content,
-                            "lines": content.lines().count(),
-                        }));
-                    }
-                    Err(e) => {
-                        errors.push(serde_json::json!({
-                            "path": path,
-                            "error": e.to_string(),
-                        }));
-                    }
-                }
-            }
+    println!("\nTyped changes: {}", typed.len());
+    for (change_type, count) in &by_type {
+        println!("  {:<12} {}", change_type, count);
+    }
 
-            if json {
-                println!(
-                    "{}",
-                    serde_json::to_string_pretty(&serde_json::json!({
-                        "files": results,
-                        "errors": errors,
-                        "summary": {
-                            "read": results.len(),
-                            "failed": errors.len(),
-                        }
-                    }))?
-                );
-            } else {
-                for r in &results {
-                    println!("=== {} ({} lines) ===", r["path"], r["lines"]);
-                    println!("{}", r["content"].as_str().unwrap_or(""));
-                    println!();
-                }
-                for e in &errors {
-                    eprintln!("Error reading {}: {}", e["path"], e["error"]);
-                }
-            }
-        }
+    Ok(())
+}
 
-        BulkAction::Symbols {
-            pattern,
-            public_only,
-        } => {
-            let mut all_symbols = Vec::new();
+/// TODO/FIXME/HACK comments in a working-copy file, each with the change
+/// that last touched its line and how many days ago that was
+fn file_todos(repo: &mut Repo, file: &str, now: i64) -> Vec<serde_json::Value> {
+    let Ok(content) = std::fs::read_to_string(repo.root().join(file)) else {
+        return Vec::new();
+    };
+    let found = agentjj::todos::scan(&content);
+    if found.is_empty() {
+        return Vec::new();
+    }
+    let blame = repo.blame(file).unwrap_or_default();
+    found
+        .into_iter()
+        .map(|todo| {
+            let origin = blame.get(todo.line - 1);
+            serde_json::json!({
+                "file": file,
+                "line": todo.line,
+                "kind": todo.kind,
+                "text": todo.text,
+                "change_id": origin.map(|b| &b.change_id),
+                "commit_id": origin.map(|b| &b.commit_id),
+                "age_days": origin.map(|b| (now - b.timestamp).max(0) / 86400),
+                "new": origin.is_some_and(|b| b.is_working_copy),
+            })
+        })
+        .collect()
+}
 
-            // Use glob to find matching files
-            let glob_pattern = format!("{}/{}", repo.root().display(), pattern);
-            if let Ok(entries) = glob::glob(&glob_pattern) {
-                for entry in entries.flatten() {
-                    if entry.is_file() {
-                        if let Some(lang) = agentjj::SupportedLanguage::from_path(&entry) {
-                            if let Ok(content) = std::fs::read_to_string(&entry) {
-                                if let Ok(symbols) =
-                                    agentjj::symbols::extract_symbols(&content, lang)
-                                {
-                                    let rel_path =
-                                        entry.strip_prefix(repo.root()).unwrap_or(&entry);
-                                    for s in symbols {
-                                        if !public_only || is_public_symbol(&s, lang) {
-                                            all_symbols.push(serde_json::json!({
-                                                "file": rel_path.display().to_string(),
-                                                "name": s.name,
-                                                "kind": s.kind,
-                                                "line": s.start_line,
-                                                "signature": s.signature,
-                                            }));
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+/// Inventory TODO/FIXME/HACK comments across the codebase, or only the ones
+/// the current change introduces
+fn cmd_todos(new_only: bool, json: bool) -> Result<()> {
+    let mut repo = Repo::discover()?;
+    // Blame the files as they are on disk
+    repo.snapshot()?;
+    let root = repo.root().to_path_buf();
+    let max_new = repo.manifest().ok().and_then(|m| m.review.max_new_todos);
+
+    let files: Vec<String> = if new_only {
+        let change_id = repo.current_change_id()?;
+        repo.changed_files(&change_id)?
+    } else {
+        codebase_files(&mut repo)?
+            .iter()
+            .map(|path| {
+                path.strip_prefix(&root)
+                    .unwrap_or(path)
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect()
+    };
 
-            if json {
-                println!(
-                    "{}",
-                    serde_json::to_string_pretty(&serde_json::json!({
-                        "pattern": pattern,
-                        "symbols": all_symbols,
-                        "count": all_symbols.len(),
-                    }))?
-                );
-            } else {
-                println!(
-                    "Found {} symbols matching '{}':",
-                    all_symbols.len(),
-                    pattern
-                );
-                for s in &all_symbols {
-                    println!(
-                        "  {}::{} ({:?}, line {})",
-                        s["file"], s["name"], s["kind"], s["line"]
-                    );
-                }
-            }
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0);
+    let mut todos = Vec::new();
+    let mut progress = agentjj::progress::Progress::start("todos", Some(files.len()));
+    for file in &files {
+        if agentjj::deadline::expired() {
+            exit_timed_out("todos", serde_json::json!({ "todos": todos }), json);
         }
+        todos.extend(
+            file_todos(&mut repo, file, now)
+                .into_iter()
+                .filter(|t| !new_only || t["new"] == true),
+        );
+        progress.tick();
+    }
+    progress.finish();
+
+    let new_count = todos.iter().filter(|t| t["new"] == true).count();
+    let mut by_kind: std::collections::BTreeMap<String, usize> = Default::default();
+    for todo in &todos {
+        *by_kind
+            .entry(todo["kind"].as_str().unwrap_or("TODO").to_string())
+            .or_insert(0) += 1;
+    }
 
-        BulkAction::Context { symbols } => {
-            let mut results = Vec::new();
-            let mut errors = Vec::new();
-
-            for sym_path in &symbols {
-                if let Some(idx) = sym_path.find("::") {
-                    let (file_path, symbol_name) = (&sym_path[..idx], &sym_path[idx + 2..]);
-                    let file_path_obj = std::path::Path::new(file_path);
-
-                    if let Some(lang) = agentjj::SupportedLanguage::from_path(file_path_obj) {
-                        let content_result: Result<String> = if file_path_obj.is_absolute() {
-                            std::fs::read_to_string(file_path).map_err(|e| anyhow::anyhow!("{}", e))
-                        } else {
-                            repo.read_file(file_path, None)
-                                .map_err(|e| anyhow::anyhow!("{}", e))
-                        };
-
-                        match content_result {
-                            Ok(content) => {
-                                match agentjj::symbols::get_symbol_context(
-                                    &content,
-                                    lang,
-                                    symbol_name,
-                                ) {
-                                    Ok(Some(ctx)) => {
-                                        results.push(serde_json::json!({
-                                            "path": sym_path,
-                                            "context": ctx,
-                                        }));
-                                    }
-                                    Ok(None) => {
-                                        errors.push(serde_json::json!({
-                                            "path": sym_path,
-                                            "error": "symbol not found",
-                                        }));
-                                    }
-                                    Err(e) => {
-                                        errors.push(serde_json::json!({
-                                            "path": sym_path,
-                                            "error": e.to_string(),
-                                        }));
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                errors.push(serde_json::json!({
-                                    "path": sym_path,
-                                    "error": e.to_string(),
-                                }));
-                            }
-                        }
-                    } else {
-                        errors.push(serde_json::json!({
-                            "path": sym_path,
-                            "error": "unsupported file type",
-                        }));
-                    }
-                } else {
-                    errors.push(serde_json::json!({
-                        "path": sym_path,
-                        "error": "invalid format, expected file::symbol",
-                    }));
+    if json {
+        println!(
+            "{}",
+            json_string(&serde_json::json!({
+                "todos": todos,
+                "count": todos.len(),
+                "new": new_count,
+                "by_kind": by_kind,
+                "max_new": max_new,
+            }))?
+        );
+    } else if todos.is_empty() {
+        println!(
+            "No TODO/FIXME/HACK comments{}",
+            if new_only { " in this change" } else { "" }
+        );
+    } else {
+        println!(
+            "{} TODO/FIXME/HACK comment(s), {} new in this change:",
+            todos.len(),
+            new_count
+        );
+        for todo in &todos {
+            let age = match (todo["new"].as_bool(), todo["age_days"].as_i64()) {
+                (Some(true), _) => "new".to_string(),
+                (_, Some(days)) => {
+                    format!("{}d, {}", days, todo["change_id"].as_str().unwrap_or(""))
+                }
+                _ => "untracked".to_string(),
+            };
+            println!(
+                "  {}:{} {} {} ({})",
+                todo["file"].as_str().unwrap_or(""),
+                todo["line"],
+                todo["kind"].as_str().unwrap_or(""),
+                todo["text"].as_str().unwrap_or(""),
+                age
+            );
+        }
+        if let Some(max) = max_new.filter(|max| new_count > *max) {
+            println!(
+                "  ⚠ {} new exceeds the manifest limit of {}",
+                new_count, max
+            );
+        }
+    }
+    Ok(())
+}
+/// Find near-duplicate functions across the codebase, or the ones a change
+/// introduces that copy existing code
+fn cmd_dupes(
+    threshold: f64,
+    min_lines: usize,
+    against_change: Option<String>,
+    json: bool,
+) -> Result<()> {
+    use agentjj::dupes;
+
+    let mut repo = Repo::discover()?;
+    let root = repo.root().to_path_buf();
+    let file_fragments = |rel_path: &str, content: &str| -> Vec<dupes::Fragment> {
+        agentjj::SupportedLanguage::from_path(std::path::Path::new(rel_path))
+            .and_then(|lang| agentjj::symbols::extract_symbols(content, lang).ok())
+            .map(|symbols| dupes::fragments(rel_path, content, &symbols, min_lines))
+            .unwrap_or_default()
+    };
+
+    let codebase = codebase_files(&mut repo)?;
+    let mut corpus = Vec::new();
+    let mut progress = agentjj::progress::Progress::start("scan", Some(codebase.len()));
+    for (scanned, path) in codebase.iter().enumerate() {
+        if agentjj::deadline::expired() {
+            exit_timed_out(
+                "scan",
+                serde_json::json!({ "files_scanned": scanned, "fragments": corpus.len() }),
+                json,
+            );
+        }
+        let rel_path = path
+            .strip_prefix(&root)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .to_string();
+        if let Ok(content) = std::fs::read_to_string(path) {
+            corpus.extend(file_fragments(&rel_path, &content));
+        }
+        progress.tick();
+    }
+    progress.finish();
+
+    let Some(rev) = against_change else {
+        let clusters = dupes::clusters(&corpus, threshold);
+        if json {
+            println!(
+                "{}",
+                json_string(&serde_json::json!({
+                    "threshold": threshold,
+                    "min_lines": min_lines,
+                    "functions_scanned": corpus.len(),
+                    "clusters": clusters,
+                    "count": clusters.len(),
+                }))?
+            );
+        } else if clusters.is_empty() {
+            println!(
+                "No near-duplicate functions ({} scanned, threshold {})",
+                corpus.len(),
+                threshold
+            );
+        } else {
+            println!("{} cluster(s) of near-duplicate functions:", clusters.len());
+            for cluster in &clusters {
+                println!();
+                println!("  similarity {:.2}", cluster.similarity);
+                for f in &cluster.members {
+                    println!(
+                        "    {}::{} (lines {}-{})",
+                        f.file, f.name, f.start_line, f.end_line
+                    );
                 }
             }
+        }
+        return Ok(());
+    };
 
-            if json {
+    // Functions the change adds or modifies, compared with its parent
+    if rev == "@" {
+        repo.snapshot()?;
+    }
+    let (parent, commit) = repo.resolve_revision(&rev)?;
+    let mut introduced = Vec::new();
+    for file in repo.commit_changed_files(&commit)? {
+        let Ok(after) = repo.read_file(&file, Some(&commit)) else {
+            continue;
+        };
+        let before: Vec<dupes::Fragment> = parent
+            .as_deref()
+            .and_then(|p| repo.read_file(&file, Some(p)).ok())
+            .map(|content| file_fragments(&file, &content))
+            .unwrap_or_default();
+        for fragment in file_fragments(&file, &after) {
+            let unchanged = before
+                .iter()
+                .any(|old| old.name == fragment.name && old.shingles == fragment.shingles);
+            if unchanged {
+                continue;
+            }
+            let matches = dupes::matches(&fragment, &corpus, threshold);
+            if !matches.is_empty() {
+                introduced.push(serde_json::json!({
+                    "file": fragment.file,
+                    "name": fragment.name,
+                    "kind": fragment.kind,
+                    "start_line": fragment.start_line,
+                    "end_line": fragment.end_line,
+                    "matches": matches,
+                }));
+            }
+        }
+    }
+
+    if json {
+        println!(
+            "{}",
+            json_string(&serde_json::json!({
+                "change": rev,
+                "commit_id": commit,
+                "threshold": threshold,
+                "min_lines": min_lines,
+                "introduced": introduced,
+                "count": introduced.len(),
+            }))?
+        );
+    } else if introduced.is_empty() {
+        println!("Change {} introduces no copies of existing code", rev);
+    } else {
+        println!(
+            "Change {} introduces {} function(s) that copy existing code:",
+            rev,
+            introduced.len()
+        );
+        for f in &introduced {
+            println!();
+            println!(
+                "  {}::{} (lines {}-{})",
+                f["file"].as_str().unwrap_or(""),
+                f["name"].as_str().unwrap_or(""),
+                f["start_line"],
+                f["end_line"]
+            );
+            for m in f["matches"].as_array().into_iter().flatten() {
                 println!(
-                    "{}",
-                    serde_json::to_string_pretty(&serde_json::json!({
-                        "contexts": results,
-                        "errors": errors,
-                    }))?
+                    "    ~ {}::{} ({:.2})",
+                    m["file"].as_str().unwrap_or(""),
+                    m["name"].as_str().unwrap_or(""),
+                    m["similarity"].as_f64().unwrap_or(0.0)
                 );
-            } else {
-                for r in &results {
-                    println!("=== {} ===", r["path"]);
-                    let ctx = &r["context"];
-                    println!("  {} ({:?})", ctx["name"], ctx["kind"]);
-                    if let Some(sig) = ctx["signature"].as_str() {
-                        println!("  {}", sig);
-                    }
-                    println!();
-                }
             }
         }
     }
-
     Ok(())
 }
+/// Prune agentjj's local state per the gc retention policy, then run jj's gc
+fn cmd_gc(aggressive: bool, dry_run: bool, json: bool) -> Result<()> {
+    let mut repo = Repo::discover()?;
+    let policy = repo.config()?.gc.clone();
+    let root = repo.root().to_path_buf();
 
-/// List files with optional symbol counts
-fn cmd_files(pattern: Option<String>, with_symbols: bool, json: bool) -> Result<()> {
-    let repo = Repo::discover()?;
-
-    let glob_pattern = pattern.unwrap_or_else(|| "**/*".to_string());
-    let full_pattern = format!("{}/{}", repo.root().display(), glob_pattern);
+    let remove = |path: &std::path::Path| -> Result<u64> {
+        let bytes = disk_usage(path);
+        if !dry_run {
+            std::fs::remove_file(path)?;
+        }
+        Ok(bytes)
+    };
 
-    let mut files = Vec::new();
+    // Checkpoints: newest N are kept unless their operation is gone
+    let mut checkpoints: Vec<(std::path::PathBuf, serde_json::Value)> =
+        std::fs::read_dir(root.join(".agent/checkpoints"))
+            .map(|dir| {
+                dir.flatten()
+                    .map(|e| e.path())
+                    .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("json"))
+                    .filter_map(|p| {
+                        let value =
+                            serde_json::from_str(&std::fs::read_to_string(&p).ok()?).ok()?;
+                        Some((p, value))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+    checkpoints.sort_by(|a, b| {
+        let a_time = a.1["created_at"].as_str().unwrap_or("");
+        let b_time = b.1["created_at"].as_str().unwrap_or("");
+        b_time.cmp(a_time)
+    });
 
-    if let Ok(entries) = glob::glob(&full_pattern) {
-        for entry in entries.flatten() {
-            if entry.is_file()
-                && !entry.to_string_lossy().contains(".jj")
-                && !entry.to_string_lossy().contains(".git")
-            {
-                let rel_path = entry.strip_prefix(repo.root()).unwrap_or(&entry);
-                let ext = entry.extension().map(|e| e.to_string_lossy().to_string());
-                let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
-
-                let mut file_info = serde_json::json!({
-                    "path": rel_path.display().to_string(),
-                    "extension": ext,
-                    "size": size,
-                });
+    let checkpoint_cutoff = chrono_lite_days_ago(policy.checkpoint_max_age_days);
+    let mut pruned_checkpoints = Vec::new();
+    for (index, (path, checkpoint)) in checkpoints.iter().enumerate() {
+        let op_id = checkpoint["operation_id"].as_str().unwrap_or("");
+        let created_at = checkpoint["created_at"].as_str().unwrap_or("");
+        let reason = if !repo.operation_exists(op_id) {
+            Some("operation no longer exists".to_string())
+        } else if index < policy.keep_checkpoints {
+            None
+        } else if aggressive {
+            Some(format!("beyond newest {}", policy.keep_checkpoints))
+        } else if created_at < checkpoint_cutoff.as_str() {
+            Some(format!(
+                "older than {} days",
+                policy.checkpoint_max_age_days
+            ))
+        } else {
+            None
+        };
+        if let Some(reason) = reason {
+            pruned_checkpoints.push(serde_json::json!({
+                "name": checkpoint["name"],
+                "created_at": created_at,
+                "reason": reason,
+                "bytes": remove(path)?,
+            }));
+        }
+    }
 
-                if with_symbols {
-                    if let Some(lang) = agentjj::SupportedLanguage::from_path(&entry) {
-                        if let Ok(content) = std::fs::read_to_string(&entry) {
-                            if let Ok(symbols) = agentjj::symbols::extract_symbols(&content, lang) {
-                                file_info["symbol_count"] = serde_json::json!(symbols.len());
-                                file_info["symbols"] = serde_json::json!(symbols
-                                    .iter()
-                                    .map(|s| &s.name)
-                                    .collect::<Vec<_>>());
-                            }
-                        }
-                    }
-                }
+    // Audit sessions nobody has written to recently
+    let audit_dir = root.join(agentjj::audit::AuditLog::DEFAULT_DIR);
+    let audit_max_age =
+        std::time::Duration::from_secs(policy.audit_max_age_days.saturating_mul(86400));
+    let mut pruned_sessions = Vec::new();
+    for session in agentjj::audit::AuditLog::open(&root).sessions() {
+        let path = audit_dir.join(format!("{}.jsonl", session));
+        let age = std::fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|t| t.elapsed().ok())
+            .unwrap_or_default();
+        if age > audit_max_age {
+            pruned_sessions.push(serde_json::json!({
+                "session": session,
+                "bytes": remove(&path)?,
+            }));
+        }
+    }
 
-                files.push(file_info);
+    // Typed-change metadata for changes that were abandoned (aggressive only)
+    let mut pruned_changes = Vec::new();
+    if aggressive {
+        let index = agentjj::change::ChangeIndex::load_from_repo(&root).unwrap_or_default();
+        for change in index.all() {
+            if !repo.change_is_visible(&change.change_id)? {
+                pruned_changes.push(serde_json::json!({
+                    "change_id": change.change_id,
+                    "intent": change.intent,
+                    "bytes": remove(&root.join(change.storage_path()))?,
+                }));
             }
         }
     }
 
+    // jj op log and object store
+    let store_paths = [root.join(".jj"), root.join(".git")];
+    let store_before: u64 = store_paths.iter().map(|p| disk_usage(p)).sum();
+    let keep_newer_days = if aggressive { 0 } else { 14 };
+    if !dry_run {
+        let progress = agentjj::progress::Progress::start("store gc", None);
+        repo.gc(
+            std::time::SystemTime::now() - std::time::Duration::from_secs(keep_newer_days * 86400)
+        )?;
+        progress.finish();
+    }
+    let store_after: u64 = store_paths.iter().map(|p| disk_usage(p)).sum();
+    let store_reclaimed = store_before.saturating_sub(store_after);
+
+    let bytes_of = |items: &[serde_json::Value]| -> u64 {
+        items.iter().filter_map(|i| i["bytes"].as_u64()).sum()
+    };
+    let reclaimed = bytes_of(&pruned_checkpoints)
+        + bytes_of(&pruned_sessions)
+        + bytes_of(&pruned_changes)
+        + store_reclaimed;
+
     if json {
         println!(
             "{}",
-            serde_json::to_string_pretty(&serde_json::json!({
-                "pattern": glob_pattern,
-                "files": files,
-                "count": files.len(),
+            json_string(&serde_json::json!({
+                "dry_run": dry_run,
+                "aggressive": aggressive,
+                "checkpoints": {
+                    "pruned": pruned_checkpoints,
+                    "kept": checkpoints.len() - pruned_checkpoints.len(),
+                },
+                "audit_sessions": { "pruned": pruned_sessions },
+                "typed_changes": { "pruned": pruned_changes },
+                "store": {
+                    "gc_ran": !dry_run,
+                    "keep_newer_days": keep_newer_days,
+                    "bytes_before": store_before,
+                    "bytes_after": store_after,
+                    "reclaimed_bytes": store_reclaimed,
+                },
+                "reclaimed_bytes": reclaimed,
             }))?
         );
     } else {
-        println!("Files matching '{}':", glob_pattern);
-        for f in &files {
-            let size_str = format_size(f["size"].as_u64().unwrap_or(0));
-            if with_symbols {
-                if let Some(count) = f["symbol_count"].as_u64() {
-                    println!("  {} ({}, {} symbols)", f["path"], size_str, count);
-                } else {
-                    println!("  {} ({})", f["path"], size_str);
-                }
-            } else {
-                println!("  {} ({})", f["path"], size_str);
-            }
+        let verb = if dry_run { "Would prune" } else { "Pruned" };
+        println!("{} {} checkpoint(s)", verb, pruned_checkpoints.len());
+        for c in &pruned_checkpoints {
+            println!(
+                "  {} ({})",
+                c["name"].as_str().unwrap_or(""),
+                c["reason"].as_str().unwrap_or("")
+            );
         }
-        println!("\nTotal: {} files", files.len());
+        println!("{} {} audit session(s)", verb, pruned_sessions.len());
+        if aggressive {
+            println!("{} {} typed change(s)", verb, pruned_changes.len());
+        }
+        if dry_run {
+            println!("Store gc skipped (dry run)");
+        } else {
+            println!("Store gc reclaimed {} bytes", store_reclaimed);
+        }
+        println!("Total reclaimed: {} bytes", reclaimed);
     }
 
     Ok(())
 }
 
-fn format_size(bytes: u64) -> String {
-    if bytes < 1024 {
-        format!("{} B", bytes)
-    } else if bytes < 1024 * 1024 {
-        format!("{:.1} KB", bytes as f64 / 1024.0)
+/// File contents of a commit, so a replay can recreate the same change.
+/// Deleted files are marked as such; binary files cannot be replayed.
+fn committed_file_inputs(root: &std::path::Path, files: &[String]) -> serde_json::Value {
+    files
+        .iter()
+        .map(|file| {
+            let entry = match std::fs::read(root.join(file)) {
+                Ok(bytes) => match String::from_utf8(bytes) {
+                    Ok(content) => serde_json::json!({ "content": content }),
+                    Err(_) => serde_json::json!({ "binary": true }),
+                },
+                Err(_) => serde_json::json!({ "deleted": true }),
+            };
+            (file.clone(), entry)
+        })
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
+/// Export a session's audit records as a replay script
+fn cmd_replay_export(session: Option<String>, out: Option<String>, json: bool) -> Result<()> {
+    let repo = Repo::discover()?;
+    let session = session.unwrap_or_else(agentjj::audit::current_session);
+    let records = agentjj::audit::AuditLog::open(repo.root()).session(&session)?;
+    let script = agentjj::audit::export_replay(&session, &records);
+    let rendered = serde_json::to_string_pretty(&script)?;
+
+    let Some(out) = out else {
+        // The script itself is the output
+        println!("{}", rendered);
+        return Ok(());
+    };
+
+    std::fs::write(&out, format!("{}\n", rendered))?;
+
+    if json {
+        println!(
+            "{}",
+            json_string(&serde_json::json!({
+                "exported": true,
+                "session": session,
+                "out": out,
+                "steps": script.steps.len(),
+                "skipped": script.skipped,
+            }))?
+        );
     } else {
-        format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+        println!(
+            "✓ Exported {} step(s) from session '{}' to {}",
+            script.steps.len(),
+            session,
+            out
+        );
+        for skipped in &script.skipped {
+            println!("  skipped {}: {}", skipped.command, skipped.reason);
+        }
     }
+
+    Ok(())
 }
 
-/// Show semantic diff
-fn cmd_diff(against: Option<String>, explain: bool, json: bool) -> Result<()> {
-    let mut repo = Repo::discover()?;
-    let target = against.unwrap_or_else(|| "@-".to_string());
+/// Bundle the .agent knowledge base into a tar.zst file
+fn cmd_knowledge_export(out: String, json: bool) -> Result<()> {
+    let repo = Repo::discover()?;
+    let index = agentjj::knowledge::export(repo.root(), &out)?;
+    let bytes: u64 = index.files.iter().map(|f| f.bytes).sum();
 
-    // agentjj is colocated with git; use git for diff rendering since jj CLI
-    // is not required to be installed.
-    let diff_output = if target == "@" {
-        // Working copy changes: compare git HEAD to working tree
-        std::process::Command::new("git")
-            .current_dir(repo.root())
-            .args(["diff", "HEAD"])
-            .output()?
+    if json {
+        println!(
+            "{}",
+            json_string(&serde_json::json!({
+                "exported": true,
+                "out": out,
+                "files": index.files.len(),
+                "bytes": bytes,
+                "sections": index.sections(),
+            }))?
+        );
     } else {
-        // Resolve the jj revision to git-compatible commit IDs.
-        // In colocated mode, jj commit IDs are git commit IDs.
-        let (parent_hex, commit_hex) = repo.resolve_revision(&target)?;
+        println!(
+            "✓ Exported {} file(s) ({} bytes) to {}",
+            index.files.len(),
+            bytes,
+            out
+        );
+        for (section, count) in index.sections() {
+            println!("  {:12} {}", section, count);
+        }
+    }
+    Ok(())
+}
 
-        match parent_hex {
-            Some(parent) => std::process::Command::new("git")
-                .current_dir(repo.root())
-                .args(["diff", &parent, &commit_hex])
-                .output()?,
-            None => {
-                // Root commit: show entire commit as additions
-                std::process::Command::new("git")
-                    .current_dir(repo.root())
-                    .args(["show", "--format=", &commit_hex])
-                    .output()?
-            }
+/// Write a range of changes as numbered format-patch files
+fn cmd_series_export(range: String, out: String, json: bool) -> Result<()> {
+    let mut repo = Repo::discover()?;
+    let series = repo.export_series(&range)?;
+    std::fs::create_dir_all(&out)?;
+
+    let total = series.patches.len();
+    let mut patches = Vec::new();
+    for (index, patch) in series.patches.iter().enumerate() {
+        let number = index + 1;
+        let file = agentjj::series::file_name(number, patch.subject());
+        let base = series.base.as_deref().filter(|_| number == 1);
+        let message = agentjj::series::render(patch, number, total, base);
+        std::fs::write(std::path::Path::new(&out).join(&file), message)?;
+        patches.push(agentjj::series::ExportedPatch {
+            file,
+            change_id: patch
+                .change
+                .as_ref()
+                .map(|c| c.change_id.clone())
+                .unwrap_or_default(),
+            commit_id: patch.commit_id.clone(),
+            subject: patch.subject().to_string(),
+            metadata: patch.change.is_some(),
+        });
+    }
+
+    if json {
+        println!(
+            "{}",
+            json_string(&serde_json::json!({
+                "exported": total,
+                "out": out,
+                "base": series.base,
+                "patches": patches,
+                "empty": series.empty,
+            }))?
+        );
+        return Ok(());
+    }
+    println!("✓ Exported {} patch(es) to {}", total, out);
+    for patch in &patches {
+        let typed = if patch.metadata { "" } else { " (no metadata)" };
+        println!("  {}{}", patch.file, typed);
+    }
+    if !series.empty.is_empty() {
+        println!("  skipped {} empty change(s)", series.empty.len());
+    }
+    Ok(())
+}
+
+/// Recreate the changes of an exported series
+fn cmd_series_import(path: String, onto: Option<String>, json: bool) -> Result<()> {
+    let source = std::path::Path::new(&path);
+    let text = if source.is_dir() {
+        let mut files: Vec<_> = std::fs::read_dir(source)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| p.extension().is_some_and(|ext| ext == "patch"))
+            .collect();
+        files.sort();
+        let mut text = String::new();
+        for file in files {
+            text.push_str(&std::fs::read_to_string(file)?);
         }
+        text
+    } else {
+        std::fs::read_to_string(source)?
     };
+    let (patches, base) = agentjj::series::parse(&text)?;
+    if patches.is_empty() {
+        return Err(agentjj::Error::Config {
+            message: format!("no patches in {}", path),
+        }
+        .into());
+    }
 
-    if !diff_output.status.success() {
-        let stderr = String::from_utf8_lossy(&diff_output.stderr);
-        anyhow::bail!("Diff failed: {}", stderr);
+    let mut repo = Repo::discover()?;
+    let import = repo.import_series(&patches, base.as_deref(), onto.as_deref())?;
+
+    if json {
+        println!(
+            "{}",
+            json_string(&serde_json::json!({
+                "imported": import.changes.len(),
+                "onto": import.onto,
+                "changes": import.changes,
+            }))?
+        );
+        return Ok(());
+    }
+    println!(
+        "✓ Imported {} change(s) onto {}",
+        import.changes.len(),
+        &import.onto[..12.min(import.onto.len())]
+    );
+    for change in &import.changes {
+        println!("  {} {}", &change.change_id[..12], change.subject);
+        if let Some(original) = &change.original_change_id {
+            println!(
+                "    (was {}, which is taken here)",
+                &original[..12.min(original.len())]
+            );
+        }
+    }
+    if let Some(last) = import.changes.last() {
+        println!(
+            "\nThe working copy is unchanged; see `agentjj change show {}`",
+            &last.change_id[..12]
+        );
     }
+    Ok(())
+}
 
-    let raw_diff = String::from_utf8_lossy(&diff_output.stdout).to_string();
+/// Merge a knowledge bundle into this repository's .agent directory
+fn cmd_knowledge_import(bundle: String, dry_run: bool, json: bool) -> Result<()> {
+    let repo = Repo::discover()?;
+    let report = agentjj::knowledge::import(repo.root(), &bundle, dry_run)?;
 
-    // Parse diff into structured format
-    let mut files_changed = Vec::new();
-    let mut current_file: Option<String> = None;
-    let mut additions = 0;
-    let mut deletions = 0;
+    if json {
+        println!("{}", json_string(&report)?);
+        return Ok(());
+    }
+    let verb = if dry_run { "Would import" } else { "Imported" };
+    println!(
+        "✓ {} {}: {} added, {} merged, {} conflict(s), {} unchanged",
+        verb,
+        bundle,
+        report.added.len(),
+        report.merged.len(),
+        report.conflicts.len(),
+        report.unchanged
+    );
+    for path in &report.added {
+        println!("  + {}", path);
+    }
+    for path in &report.merged {
+        println!("  ~ {} (merged)", path);
+    }
+    for conflict in &report.conflicts {
+        let kept = match conflict.kept {
+            agentjj::knowledge::Side::Bundle => "bundle",
+            agentjj::knowledge::Side::Local => "local",
+        };
+        println!(
+            "  ! {}: kept {} copy (local {}, bundle {})",
+            conflict.path, kept, conflict.local_modified, conflict.bundle_modified
+        );
+    }
+    Ok(())
+}
 
-    for line in raw_diff.lines() {
-        if line.starts_with("--- ") || line.starts_with("+++ ") {
-            let path = line[4..].trim_start_matches("a/").trim_start_matches("b/");
-            if !path.is_empty() && path != "/dev/null" {
-                current_file = Some(path.to_string());
+/// Re-run a replay script step by step, stopping at the first failure
+fn cmd_replay_run(script_path: String, dry_run: bool, json: bool) -> Result<()> {
+    let content = std::fs::read_to_string(&script_path)?;
+    let script: agentjj::audit::ReplayScript = serde_json::from_str(&content)
+        .map_err(|e| anyhow::anyhow!("Invalid replay script {}: {}", script_path, e))?;
+    if script.version != 1 {
+        anyhow::bail!("Unsupported replay script version: {}", script.version);
+    }
+    let unsafe_paths = script.unsafe_paths();
+    if !unsafe_paths.is_empty() {
+        anyhow::bail!(
+            "Replay script {} writes outside the repository: {}",
+            script_path,
+            unsafe_paths.join(", ")
+        );
+    }
+
+    if dry_run {
+        if json {
+            println!(
+                "{}",
+                json_string(&serde_json::json!({
+                    "dry_run": true,
+                    "session": script.session,
+                    "steps": script.steps.iter().map(|s| &s.args).collect::<Vec<_>>(),
+                }))?
+            );
+        } else {
+            println!("Would replay {} step(s):", script.steps.len());
+            for step in &script.steps {
+                println!("  agentjj {}", step.args.join(" "));
+            }
+        }
+        return Ok(());
+    }
+
+    let repo = Repo::discover()?;
+    let exe = std::env::current_exe()?;
+    let mut results = Vec::new();
+    let mut completed = true;
+
+    let mut progress = agentjj::progress::Progress::start("replay", Some(script.steps.len()));
+    for (index, step) in script.steps.iter().enumerate() {
+        if agentjj::deadline::expired() {
+            exit_timed_out(
+                "replay",
+                serde_json::json!({
+                    "session": script.session,
+                    "replayed": results.len(),
+                    "total": script.steps.len(),
+                    "steps": results,
+                }),
+                json,
+            );
+        }
+        let mut args = step.args.clone();
+        let inputs = step.inputs.clone().unwrap_or_default();
+
+        // Recreate the files the original commit captured
+        if let Some(files) = inputs["files"].as_object() {
+            for (file, entry) in files {
+                let dest = repo.root().join(file);
+                if let Some(content) = entry["content"].as_str() {
+                    if let Some(parent) = dest.parent() {
+                        std::fs::create_dir_all(parent)?;
+                    }
+                    std::fs::write(&dest, content)?;
+                } else if entry["deleted"] == true && dest.exists() {
+                    std::fs::remove_file(&dest)?;
+                }
             }
-        } else if line.starts_with('+') && !line.starts_with("+++") {
-            additions += 1;
-        } else if line.starts_with('-') && !line.starts_with("---") {
-            deletions += 1;
         }
 
-        if let Some(ref file) = current_file {
-            if !files_changed.contains(file) {
-                files_changed.push(file.clone());
-            }
+        // Copies of recorded inputs, removed once the step has run
+        let mut temp_files = Vec::new();
+
+        // Point --patch at a copy of the recorded patch body
+        if let Some(patch) = inputs["patch"].as_str() {
+            let patch_path = std::env::temp_dir().join(format!(
+                "agentjj-replay-{}-{}.patch",
+                std::process::id(),
+                index
+            ));
+            std::fs::write(&patch_path, patch)?;
+            replace_arg_value(
+                &mut args,
+                &["--patch", "-p"],
+                &patch_path.display().to_string(),
+            );
+            temp_files.push(patch_path);
         }
-    }
 
-    let semantic_summary = if explain && !files_changed.is_empty() {
-        // Generate a semantic summary based on file types and changes
-        let mut summary_parts = Vec::new();
+        // Attempt histories stay in the clone that recorded them
+        remove_arg(&mut args, "--attempt-of");
 
-        for file in &files_changed {
-            let ext = std::path::Path::new(file)
-                .extension()
-                .and_then(|e| e.to_str())
-                .unwrap_or("");
+        // Same for the file operations of an edit
+        if let Some(operations) = inputs.get("operations") {
+            let ops_path = std::env::temp_dir().join(format!(
+                "agentjj-replay-{}-{}.json",
+                std::process::id(),
+                index
+            ));
+            std::fs::write(&ops_path, serde_json::to_string(operations)?)?;
+            replace_arg_value(&mut args, &["--ops"], &ops_path.display().to_string());
+            temp_files.push(ops_path);
+        }
 
-            let file_type = match ext {
-                "rs" => "Rust code",
-                "py" => "Python code",
-                "ts" | "tsx" => "TypeScript code",
-                "js" | "jsx" => "JavaScript code",
-                "toml" => "TOML configuration",
-                "json" => "JSON data",
-                "md" => "documentation",
-                "yaml" | "yml" => "YAML configuration",
-                _ => "file",
-            };
+        let mut command = std::process::Command::new(&exe);
+        command.arg("--json");
+        // Each step gets whatever is left of this run's --timeout
+        if let Some(deadline) = agentjj::deadline::current() {
+            let left = deadline.remaining().as_secs_f64().ceil().max(1.0);
+            command.args(["--timeout", &(left as u64).to_string()]);
+        }
+        let output = command
+            .args(&args)
+            .current_dir(repo.root())
+            .env(
+                agentjj::audit::SESSION_ENV,
+                format!("replay-{}", script.session),
+            )
+            .output();
+        for path in &temp_files {
+            let _ = std::fs::remove_file(path);
+        }
+        let output = output?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let parsed: serde_json::Value = serde_json::from_str(stdout.trim())
+            .unwrap_or_else(|_| serde_json::json!(stdout.trim()));
+        let success = output.status.success();
+        results.push(serde_json::json!({
+            "step": index + 1,
+            "command": step.command,
+            "args": args,
+            "success": success,
+            "output": parsed,
+        }));
 
-            summary_parts.push(format!("{} ({})", file, file_type));
+        if !success {
+            completed = false;
+            break;
         }
+        progress.tick();
+    }
+    progress.finish();
 
-        Some(format!(
-            "Changes affect {} file(s): {}. Net change: +{} -{} lines.",
-            files_changed.len(),
-            summary_parts.join(", "),
-            additions,
-            deletions
-        ))
-    } else {
-        None
-    };
+    let replayed = results.iter().filter(|r| r["success"] == true).count();
 
     if json {
         println!(
             "{}",
-            serde_json::to_string_pretty(&serde_json::json!({
-                "against": target,
-                "files_changed": files_changed,
-                "stats": {
-                    "additions": additions,
-                    "deletions": deletions,
-                    "net": additions as i64 - deletions as i64,
-                },
-                "explanation": semantic_summary,
-                "raw_diff": raw_diff,
+            json_string(&serde_json::json!({
+                "session": script.session,
+                "completed": completed,
+                "replayed": replayed,
+                "total": script.steps.len(),
+                "steps": results,
             }))?
         );
     } else {
-        println!("Diff against {}:", target);
-        println!("  {} file(s) changed", files_changed.len());
-        println!("  +{} -{} lines", additions, deletions);
-
-        if let Some(summary) = &semantic_summary {
-            println!("\nSummary: {}", summary);
+        for r in &results {
+            let mark = if r["success"] == true { "✓" } else { "✗" };
+            println!(
+                "{} {} {}",
+                mark,
+                r["step"],
+                r["command"].as_str().unwrap_or("")
+            );
+            if r["success"] != true {
+                println!("  {}", r["output"]);
+            }
         }
+        println!(
+            "\nReplayed {}/{} step(s) from session '{}'",
+            replayed,
+            script.steps.len(),
+            script.session
+        );
+    }
 
-        println!("\n{}", raw_diff);
+    if !completed {
+        exit_with(exit::FAILURE, None);
     }
 
     Ok(())
 }
 
-/// Analyze what would be affected by changing a symbol
-fn cmd_affected(symbol_path: String, depth: usize, json: bool) -> Result<()> {
-    let repo = Repo::discover()?;
+/// How this invocation lets a human override a blocking check
+struct ConfirmationFlags {
+    /// Record a confirmation instead of only failing
+    request: bool,
+    /// Seconds to wait for the decision
+    wait: Option<u64>,
+    /// An approved confirmation to resume with
+    id: Option<String>,
+    /// Command path, which a confirmation is only valid for
+    command: String,
+    /// Arguments to resume the command with
+    args: Vec<String>,
+    json: bool,
+}
 
-    // Parse the symbol path
-    let (file_path, symbol_name) = if let Some(idx) = symbol_path.find("::") {
-        (&symbol_path[..idx], &symbol_path[idx + 2..])
-    } else {
-        anyhow::bail!("Symbol path must be file::symbol_name");
-    };
+static CONFIRMATION: OnceLock<ConfirmationFlags> = OnceLock::new();
 
-    // Find all files that might reference this symbol
-    let mut affected_files = Vec::new();
-    let pattern = format!("{}/**/*", repo.root().display());
-
-    if let Ok(entries) = glob::glob(&pattern) {
-        for entry in entries.flatten() {
-            if entry.is_file() {
-                if let Some(lang) = agentjj::SupportedLanguage::from_path(&entry) {
-                    if let Ok(content) = std::fs::read_to_string(&entry) {
-                        // Simple text search for the symbol name
-                        if content.contains(symbol_name) {
-                            let rel_path = entry.strip_prefix(repo.root()).unwrap_or(&entry);
-
-                            // Count occurrences
-                            let occurrences = content.matches(symbol_name).count();
-
-                            // Try to find actual usages (not just the definition)
-                            let is_definition = rel_path.to_string_lossy() == file_path;
-
-                            if !is_definition || depth > 0 {
-                                affected_files.push(serde_json::json!({
-                                    "path": rel_path.display().to_string(),
-                                    "language": format!("{:?}", lang),
-                                    "occurrences": occurrences,
-                                    "is_definition": is_definition,
-                                }));
-                            }
-                        }
-                    }
-                }
-            }
+/// Set once a confirmation let this invocation through; it covers the
+/// invocation's remaining checks too
+static CONFIRMED: AtomicBool = AtomicBool::new(false);
+
+/// This invocation's arguments without the confirmation flags, so a
+/// confirmation resumes exactly the command it was issued for
+fn args_without_confirmation_flags() -> Vec<String> {
+    let mut args = Vec::new();
+    let mut skip_value = false;
+    for arg in std::env::args().skip(1) {
+        if std::mem::take(&mut skip_value) {
+            continue;
+        }
+        match arg.as_str() {
+            "--request-confirmation" => {}
+            "--wait-confirmation" | "--confirmation" => skip_value = true,
+            a if a.starts_with("--wait-confirmation=") || a.starts_with("--confirmation=") => {}
+            _ => args.push(arg),
         }
     }
+    args
+}
 
-    // Sort by occurrences (most affected first)
-    affected_files.sort_by(|a, b| {
-        b["occurrences"]
-            .as_u64()
-            .unwrap_or(0)
-            .cmp(&a["occurrences"].as_u64().unwrap_or(0))
-    });
-
-    let analysis = serde_json::json!({
-        "symbol": symbol_path,
-        "depth": depth,
-        "affected_files": affected_files,
-        "total_files": affected_files.len(),
-        "risk_assessment": if affected_files.len() > 10 {
-            "high"
-        } else if affected_files.len() > 3 {
-            "medium"
-        } else {
-            "low"
-        },
-        "recommendation": if affected_files.len() > 10 {
-            "Consider creating a deprecation path or using feature flags"
-        } else if affected_files.len() > 3 {
-            "Run tests after change, review affected files"
-        } else {
-            "Safe to modify with standard review"
-        },
-    });
+/// A check a human can override blocked the command: let it through with
+/// an approved --confirmation, or record a pending confirmation (and wait
+/// for it under --wait-confirmation); otherwise fail with `blocked`
+fn confirmation_gate(repo: &mut Repo, gate: Gate, blocked: agentjj::Error) -> Result<()> {
+    use agentjj::confirm::{Confirmation, ConfirmationStatus, ConfirmationStore};
 
-    if json {
-        println!("{}", serde_json::to_string_pretty(&analysis)?);
+    if CONFIRMED.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    let Some(flags) = CONFIRMATION.get() else {
+        return Err(blocked.into());
+    };
+    let store = ConfirmationStore::open(repo.root());
+    let mut confirmation = if let Some(id) = &flags.id {
+        store.load(id)?
+    } else if flags.request {
+        let confirmation = Confirmation::new(
+            gate,
+            &flags.command,
+            flags.args.clone(),
+            &blocked,
+            agentjj::audit::current_session(),
+            chrono_lite_now(),
+        );
+        store.save(&confirmation)?;
+        let mut payload = serde_json::to_value(&confirmation)?;
+        payload["kind"] = serde_json::json!("confirmation");
+        payload["resume"] = serde_json::json!(confirmation.resume_command());
+        run_post_hooks(repo, HookEvent::Confirmation, &mut payload, flags.json)?;
+        match flags.wait {
+            Some(secs) => store.wait(
+                &confirmation.id,
+                std::time::Duration::from_secs(secs),
+                std::time::Duration::from_millis(500),
+            )?,
+            None => return Err(confirmation.pending_error().into()),
+        }
     } else {
-        println!("Impact analysis for '{}':", symbol_path);
-        println!("  Risk: {}", analysis["risk_assessment"]);
-        println!("  {} file(s) affected", affected_files.len());
-        println!();
+        return Err(blocked.into());
+    };
+
+    confirmation.release(gate, &flags.command)?;
+    confirmation.status = ConfirmationStatus::Applied;
+    store.save(&confirmation)?;
+    CONFIRMED.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Decide on a confirmation, or on an approval parked by `ask_change`
+fn cmd_confirm(
+    id: String,
+    approve: bool,
+    by: Option<String>,
+    reason: Option<String>,
+    json: bool,
+) -> Result<()> {
+    use agentjj::confirm::ConfirmationStore;
 
-        for f in affected_files.iter().take(10) {
-            let marker = if f["is_definition"].as_bool().unwrap_or(false) {
-                "(def)"
+    let repo = Repo::discover()?;
+    let store = ConfirmationStore::open(repo.root());
+    if !store.contains(&id) {
+        if agentjj::approval::ApprovalStore::open(repo.root())
+            .load(&id)
+            .is_ok()
+        {
+            let action = if approve {
+                ApprovalsAction::Approve { id, by, reason }
             } else {
-                ""
+                ApprovalsAction::Deny { id, by, reason }
             };
-            println!("  {} ({} refs) {}", f["path"], f["occurrences"], marker);
+            return cmd_approvals(action, json);
         }
-
-        if affected_files.len() > 10 {
-            println!("  ... and {} more", affected_files.len() - 10);
+        return Err(agentjj::Error::ConfirmationNotFound {
+            confirmation_id: id,
         }
+        .into());
+    }
 
-        println!("\n{}", analysis["recommendation"]);
+    let mut confirmation = store.load(&id)?;
+    let (by, decider) = human_decider(
+        repo.root(),
+        &id,
+        approve,
+        by,
+        &confirmation.requested_by,
+        confirmation.requested_tty.as_deref(),
+    )?;
+    confirmation.decide(approve, by, chrono_lite_now(), reason)?;
+    confirmation.decided_from = Some(decider);
+    store.save(&confirmation)?;
+
+    if json {
+        println!("{}", json_string(&confirmation)?);
+    } else if approve {
+        println!("✓ Approved {}: {}", confirmation.id, confirmation.message);
+        println!(
+            "  a waiting command carries on; otherwise resume with: {}",
+            confirmation.resume_command()
+        );
+    } else {
+        println!("✗ Denied {}", confirmation.id);
     }
 
     Ok(())
 }
 
-/// Print JSON schemas for output types
-fn cmd_schema(type_filter: Option<String>, json: bool) -> Result<()> {
-    let schemas = serde_json::json!({
-        "status": {
-            "type": "object",
-            "properties": {
-                "change_id": { "type": "string", "description": "Current jj change ID" },
-                "operation_id": { "type": "string", "description": "Current jj operation ID" },
-                "files_changed": { "type": "array", "items": { "type": "string" } },
-                "has_manifest": { "type": "boolean" },
-                "typed_change": { "type": "object", "nullable": true },
-            }
-        },
-        "symbol": {
-            "type": "object",
-            "properties": {
-                "name": { "type": "string" },
-                "kind": { "type": "string", "enum": ["function", "method", "class", "struct", "enum", "interface", "constant", "variable", "module", "import"] },
-                "signature": { "type": "string", "nullable": true },
-                "docstring": { "type": "string", "nullable": true },
-                "start_line": { "type": "integer" },
-                "end_line": { "type": "integer" },
-            }
-        },
-        "context": {
-            "type": "object",
-            "properties": {
-                "name": { "type": "string" },
-                "kind": { "type": "string" },
-                "signature": { "type": "string", "nullable": true },
-                "docstring": { "type": "string", "nullable": true },
-                "imports_needed": { "type": "array", "items": { "type": "string" } },
-            }
-        },
-        "apply_result": {
-            "oneOf": [
-                {
-                    "type": "object",
-                    "properties": {
-                        "status": { "const": "success" },
-                        "change_id": { "type": "string" },
-                        "files_changed": { "type": "array" },
-                    }
-                },
-                {
-                    "type": "object",
-                    "properties": {
-                        "status": { "const": "precondition_failed" },
-                        "reason": { "type": "string" },
-                        "expected": { "type": "string" },
-                        "actual": { "type": "string" },
-                    }
-                },
-                {
-                    "type": "object",
-                    "properties": {
-                        "status": { "const": "conflict" },
-                        "conflicts": { "type": "array" },
+/// Attempt history of an intent: every run, how it ended, and whether the
+/// planner is going round in circles
+fn cmd_intent(action: IntentAction, json: bool) -> Result<()> {
+    let IntentAction::History { id } = action;
+    let mut repo = Repo::discover()?;
+    let attempts = agentjj::attempts::IntentStore::open(repo.root()).load(&id)?;
+    let limits = repo
+        .manifest()
+        .map(|m| m.intents.clone())
+        .unwrap_or_default();
+    let remaining = limits
+        .max_attempts
+        .map(|max| max.saturating_sub(attempts.attempts.len()));
+
+    if json {
+        let mut output = serde_json::to_value(&attempts)?;
+        output["succeeded"] = attempts.succeeded().into();
+        output["repeated_failures"] = attempts.repeated_failures().into();
+        output["looping"] = attempts.looping().into();
+        output["limits"] = serde_json::json!({
+            "max_attempts": limits.max_attempts,
+            "max_minutes": limits.max_minutes,
+            "remaining_attempts": remaining,
+        });
+        println!("{}", json_string(&output)?);
+        return Ok(());
+    }
+
+    println!("Intent {}: {}", attempts.id, attempts.description);
+    for attempt in &attempts.attempts {
+        println!(
+            "  #{} {} {} ({} ms, session {}){}",
+            attempt.number,
+            attempt.started_at,
+            attempt.status,
+            attempt.duration_ms,
+            attempt.session,
+            attempt
+                .reason
+                .as_deref()
+                .map(|r| format!(": {}", r))
+                .unwrap_or_default()
+        );
+    }
+    if attempts.succeeded() {
+        println!("Applied.");
+    } else if attempts.looping() {
+        println!(
+            "⚠ The last {} attempts failed the same way; change the approach before retrying",
+            attempts.repeated_failures()
+        );
+    }
+    if let Some(remaining) = remaining {
+        println!("{} attempt(s) left (max_attempts)", remaining);
+    }
+    Ok(())
+}
+
+/// Who decides on approval or confirmation `id`, and the deciding process.
+/// Approvals must come from a human (see `Decider::check_approval`);
+/// anyone may deny.
+fn human_decider(
+    root: &std::path::Path,
+    id: &str,
+    approve: bool,
+    by: Option<String>,
+    requested_by: &str,
+    requested_tty: Option<&str>,
+) -> Result<(String, agentjj::approval::Decider)> {
+    let decider = agentjj::approval::Decider::current();
+    if approve {
+        let approvers = Manifest::load_from_repo(root)
+            .map(|m| m.review.approvers)
+            .unwrap_or_default();
+        decider.check_approval(id, by.as_deref(), &approvers, requested_by, requested_tty)?;
+    }
+    let by = by
+        .or_else(|| std::env::var("USER").ok())
+        .unwrap_or_else(|| "unknown".to_string());
+    Ok((by, decider))
+}
+
+/// List, approve, or deny intents parked by `ask_change` permissions
+fn cmd_approvals(action: ApprovalsAction, json: bool) -> Result<()> {
+    use agentjj::approval::{ApprovalStatus, ApprovalStore};
+
+    let repo = Repo::discover()?;
+    let store = ApprovalStore::open(repo.root());
+
+    let (id, by, reason, approve) = match action {
+        ApprovalsAction::List { all } => {
+            let approvals: Vec<_> = store
+                .list()?
+                .into_iter()
+                .filter(|a| all || a.status == ApprovalStatus::Pending)
+                .collect();
+
+            if json {
+                println!(
+                    "{}",
+                    json_string(&serde_json::json!({
+                        "approvals": approvals,
+                        "count": approvals.len(),
+                    }))?
+                );
+            } else if approvals.is_empty() {
+                println!("No {}approvals", if all { "" } else { "pending " });
+            } else {
+                for a in &approvals {
+                    println!(
+                        "{} [{}] {} ({})",
+                        a.id,
+                        a.status.as_str(),
+                        a.intent.description,
+                        a.paths.join(", ")
+                    );
+                    println!("  requested by {} at {}", a.requested_by, a.requested_at);
+                    if let (Some(by), Some(at)) = (&a.decided_by, &a.decided_at) {
+                        println!("  decided by {} at {}", by, at);
                     }
                 }
-            ]
-        },
-        "error": {
-            "type": "object",
-            "properties": {
-                "error": { "const": true },
-                "message": { "type": "string" },
             }
-        },
-        "orient": {
-            "type": "object",
-            "description": "Complete repository orientation for agents",
-            "properties": {
-                "current_state": { "type": "object" },
-                "repository": { "type": "object", "nullable": true },
-                "codebase": { "type": "object" },
-                "recent_changes": { "type": "array" },
-                "capabilities": { "type": "object" },
-                "quick_start": { "type": "object" },
+            return Ok(());
+        }
+        ApprovalsAction::Approve { id, by, reason } => (id, by, reason, true),
+        ApprovalsAction::Deny { id, by, reason } => (id, by, reason, false),
+    };
+
+    let mut approval = store.load(&id)?;
+    let (by, decider) = human_decider(
+        repo.root(),
+        &id,
+        approve,
+        by,
+        &approval.requested_by,
+        approval.requested_tty.as_deref(),
+    )?;
+    approval.decide(approve, by, chrono_lite_now(), reason)?;
+    approval.decided_from = Some(decider);
+    store.save(&approval)?;
+
+    if json {
+        println!("{}", json_string(&approval)?);
+    } else if approve {
+        println!("✓ Approved {}", approval.id);
+        println!("  resume with: agentjj apply --resume {}", approval.id);
+    } else {
+        println!("✗ Denied {}", approval.id);
+    }
+
+    Ok(())
+}
+/// Add, list, or remove notes on files, lines, and symbols
+fn cmd_note(action: NoteAction, json: bool) -> Result<()> {
+    use agentjj::notes::{Note, NoteStore, Target};
+
+    let mut repo = Repo::discover()?;
+    let store = NoteStore::open(repo.root());
+
+    match action {
+        NoteAction::Add { on, text } => {
+            let target = Target::parse(&on)?;
+            // Notes on code that does not exist would never be shown
+            let content = repo.read_file(&target.file, None)?;
+            if let Some(name) = &target.symbol {
+                let lang =
+                    agentjj::SupportedLanguage::from_path(std::path::Path::new(&target.file))
+                        .ok_or_else(|| anyhow::anyhow!("Unsupported file type: {}", target.file))?;
+                if agentjj::symbols::find_symbol(&content, lang, name)?.is_none() {
+                    anyhow::bail!("Symbol '{}' not found in {}", name, target.file);
+                }
+            }
+            if let Some(line) = target.line {
+                let total = content.lines().count();
+                if line > total {
+                    anyhow::bail!(
+                        "Line {} is out of range ({} has {} lines)",
+                        line,
+                        target.file,
+                        total
+                    );
+                }
             }
-        },
-    });
 
-    if let Some(type_name) = type_filter {
-        if let Some(schema) = schemas.get(&type_name) {
+            let note = Note::new(
+                target,
+                text,
+                agentjj::audit::current_actor(),
+                chrono_lite_now(),
+            );
+            store.save(&note)?;
             if json {
-                println!("{}", serde_json::to_string_pretty(schema)?);
+                println!("{}", json_string(&note)?);
             } else {
-                println!("Schema for '{}':", type_name);
-                println!("{}", serde_json::to_string_pretty(schema)?);
+                println!("✓ Note {} on {}", note.id, note.target());
             }
-        } else {
-            anyhow::bail!(
-                "Unknown type: {}. Available: status, symbol, context, apply_result, error, orient",
-                type_name
-            );
         }
-    } else if json {
-        println!("{}", serde_json::to_string_pretty(&schemas)?);
-    } else {
-        println!("Available schemas:");
-        for key in schemas.as_object().unwrap().keys() {
-            println!("  {}", key);
+        NoteAction::List { on } => {
+            let notes = match on {
+                Some(on) => {
+                    let target = Target::parse(&on)?;
+                    store.for_view(
+                        &target.file,
+                        target.symbol.as_deref(),
+                        target.line.map(|line| (line, line)),
+                    )?
+                }
+                None => store.list()?,
+            };
+            if json {
+                println!(
+                    "{}",
+                    json_string(&serde_json::json!({
+                        "notes": notes,
+                        "count": notes.len(),
+                    }))?
+                );
+            } else if notes.is_empty() {
+                println!("No notes");
+            } else {
+                for note in &notes {
+                    print_note(note);
+                }
+            }
+        }
+        NoteAction::Remove { id } => {
+            let note = store.remove(&id)?;
+            if json {
+                println!(
+                    "{}",
+                    json_string(&serde_json::json!({
+                        "removed": true,
+                        "note": note,
+                    }))?
+                );
+            } else {
+                println!("✓ Removed note {} on {}", note.id, note.target());
+            }
         }
-        println!("\nUse --type <name> to see a specific schema");
     }
 
     Ok(())
 }
 
-/// Validate current changes are complete
-fn cmd_validate(json: bool) -> Result<()> {
-    let mut repo = Repo::discover()?;
-
-    let change_id = repo.current_change_id()?;
-    let files = repo.changed_files(&change_id)?;
+/// One note as text: target, author, and date, then the note indented
+fn print_note(note: &agentjj::notes::Note) {
+    println!(
+        "{} {} ({}, {})",
+        note.id,
+        note.target(),
+        note.author,
+        note.created_at
+    );
+    for line in note.text.lines() {
+        println!("  {}", line);
+    }
+}
 
-    let mut issues = Vec::new();
-    let mut warnings = Vec::new();
+/// Record an answer with the tree and file hashes it was true at
+fn cmd_qa_record(question: String, answer: String, files: Vec<String>, json: bool) -> Result<()> {
+    use agentjj::qa::{Entry, FileHash, QaStore};
 
-    // Check if there are any changes
-    if files.is_empty() {
-        issues.push("No changes to validate".to_string());
-    }
+    let mut repo = Repo::discover()?;
+    let store = QaStore::open(repo.root());
 
-    // Check for typed change metadata
-    let typed_change = repo.get_typed_change(&change_id).ok();
-    if typed_change.is_none() {
-        warnings.push("No typed change metadata - consider using 'agentjj change set'".to_string());
+    let files = if files.is_empty() {
+        let root = repo.root().to_path_buf();
+        agentjj::qa::mentioned_files(&format!("{}\n{}", question, answer), |path| {
+            root.join(path).is_file()
+        })
+    } else {
+        files
+    };
+    let files = files
+        .into_iter()
+        .map(|path| {
+            let hash = agentjj::snippet::content_hash(repo.read_raw(&path, None)?);
+            Ok(FileHash { path, hash })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let entry = Entry::new(
+        question,
+        answer,
+        files,
+        repo.current_tree_id()?,
+        repo.current_change_id()?,
+        agentjj::audit::current_actor(),
+        chrono_lite_now(),
+    );
+    // A question answered again replaces its old answer
+    let replaced: Vec<String> = store
+        .list()?
+        .into_iter()
+        .filter(|e| e.same_question(&entry.question))
+        .map(|e| e.id)
+        .collect();
+    for id in &replaced {
+        store.remove(id)?;
     }
+    store.save(&entry)?;
 
-    // Check manifest exists
-    if !repo.has_manifest() {
-        warnings.push("No manifest found - consider using 'agentjj init'".to_string());
+    if json {
+        let mut value = serde_json::to_value(&entry)?;
+        value["replaced"] = serde_json::json!(replaced);
+        println!("{}", json_string(&value)?);
+    } else {
+        println!("✓ Recorded {}", entry.id);
+        if entry.files.is_empty() {
+            println!("  valid until the tree changes (no files named; pass --files to narrow)");
+        } else {
+            let paths: Vec<_> = entry.files.iter().map(|f| f.path.as_str()).collect();
+            println!("  valid until these change: {}", paths.join(", "));
+        }
+        for id in &replaced {
+            println!("  replaces {}", id);
+        }
     }
 
-    // Check for common issues in changed files
-    for file in &files {
-        let path = std::path::Path::new(file);
-
-        // Check for test files if code was changed
-        if path
-            .extension()
-            .map(|e| e == "rs" || e == "py" || e == "ts" || e == "js")
-            .unwrap_or(false)
-        {
-            let is_test = file.contains("test")
-                || file.contains("spec")
-                || file.contains("_test.")
-                || file.contains(".test.");
-            if !is_test {
-                // For Rust files, tests are often inline (mod tests) - skip warning
-                // For other languages, check common test locations
-                let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
-                if ext != "rs" {
-                    let file_stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
-
-                    // Check common test locations
-                    let test_patterns = [
-                        format!("tests/{}.{}", file_stem, ext),
-                        format!("test/{}.{}", file_stem, ext),
-                        format!("tests/test_{}.{}", file_stem, ext),
-                        format!("{}_test.{}", file_stem, ext),
-                        format!("{}.test.{}", file_stem, ext),
-                        format!("{}.spec.{}", file_stem, ext),
-                    ];
+    Ok(())
+}
 
-                    let has_test = test_patterns.iter().any(|p| repo.root().join(p).exists());
-                    if !has_test {
-                        warnings.push(format!("Consider adding tests for {}", file));
-                    }
-                }
-            }
-        }
+/// Recorded answers with the files that changed since each, newest first
+fn qa_entries(repo: &mut Repo) -> Result<Vec<(agentjj::qa::Entry, Vec<String>)>> {
+    let entries = agentjj::qa::QaStore::open(repo.root()).list()?;
+    if entries.is_empty() {
+        return Ok(Vec::new());
     }
+    let tree = repo.current_tree_id()?;
+    let mut hashes: std::collections::HashMap<String, Option<String>> =
+        std::collections::HashMap::new();
+    Ok(entries
+        .into_iter()
+        .map(|entry| {
+            let changed = entry.changed_files(&tree, |path| {
+                hashes
+                    .entry(path.to_string())
+                    .or_insert_with(|| {
+                        repo.read_raw(path, None)
+                            .ok()
+                            .map(agentjj::snippet::content_hash)
+                    })
+                    .clone()
+            });
+            (entry, changed)
+        })
+        .collect())
+}
 
-    // Check invariants from manifest
-    if let Ok(manifest) = repo.manifest() {
-        if !manifest.invariants.is_empty() {
-            warnings.push(format!(
-                "{} invariant(s) defined - run tests manually to verify",
-                manifest.invariants.len()
-            ));
-        }
+/// An entry as JSON, with `stale` and the files behind it
+fn qa_json(entry: &agentjj::qa::Entry, changed: &[String]) -> serde_json::Value {
+    let mut value = serde_json::to_value(entry).unwrap_or_default();
+    value["stale"] = serde_json::json!(!changed.is_empty());
+    if !changed.is_empty() {
+        value["changed_files"] = serde_json::json!(changed);
     }
+    value
+}
 
-    let is_valid = issues.is_empty();
+/// Search recorded answers, ranking still-valid ones above stale ones
+fn cmd_qa_search(query: String, limit: usize, json: bool) -> Result<()> {
+    let mut repo = Repo::discover()?;
+    let mut matches: Vec<_> = qa_entries(&mut repo)?
+        .into_iter()
+        .map(|(entry, changed)| (agentjj::qa::score(&entry, &query), entry, changed))
+        .filter(|(score, _, _)| *score > 0)
+        .collect();
+    // Stable: equal ranks stay newest first
+    matches.sort_by_key(|(score, _, changed)| (!changed.is_empty(), std::cmp::Reverse(*score)));
+    matches.truncate(limit);
 
     if json {
+        let results: Vec<_> = matches
+            .iter()
+            .map(|(score, entry, changed)| {
+                let mut value = qa_json(entry, changed);
+                value["score"] = serde_json::json!(score);
+                value
+            })
+            .collect();
         println!(
             "{}",
-            serde_json::to_string_pretty(&serde_json::json!({
-                "valid": is_valid,
-                "change_id": change_id,
-                "files_changed": files,
-                "typed_change": typed_change,
-                "issues": issues,
-                "warnings": warnings,
+            json_string(&serde_json::json!({
+                "query": query,
+                "results": results,
+                "count": results.len(),
             }))?
         );
+    } else if matches.is_empty() {
+        println!("No recorded answers match '{}'", query);
     } else {
-        if is_valid {
-            println!("✓ Changes are valid");
-        } else {
-            println!("✗ Validation failed");
-        }
-
-        println!("  {} file(s) changed", files.len());
-
-        if !issues.is_empty() {
-            println!("\nIssues:");
-            for issue in &issues {
-                println!("  ✗ {}", issue);
-            }
-        }
-
-        if !warnings.is_empty() {
-            println!("\nWarnings:");
-            for warning in &warnings {
-                println!("  ⚠ {}", warning);
+        for (_, entry, changed) in &matches {
+            let stale = if changed.is_empty() {
+                String::new()
+            } else {
+                format!(" [stale: {}]", changed.join(", "))
+            };
+            println!("{} Q: {}{}", entry.id, entry.question, stale);
+            for line in entry.answer.lines() {
+                println!("  {}", line);
             }
+            println!("  ({}, {})", entry.author, entry.recorded_at);
         }
-
-        if is_valid && warnings.is_empty() {
-            println!("\nReady to push!");
-        }
-    }
-
-    if !is_valid {
-        std::process::exit(1);
     }
 
     Ok(())
 }
 
-/// Output the repository DAG in various formats
-fn cmd_graph(format: String, limit: usize, all: bool, json: bool) -> Result<()> {
-    let mut repo = Repo::discover()?;
-
-    match format.to_lowercase().as_str() {
-        "ascii" => cmd_graph_ascii(&mut repo, limit, all, json),
-        "mermaid" => cmd_graph_mermaid(&mut repo, limit, all, json),
-        "dot" => cmd_graph_dot(&mut repo, limit, all, json),
-        _ => anyhow::bail!(
-            "Unknown format: {}. Use 'ascii', 'mermaid', or 'dot'",
-            format
-        ),
+/// Parse a saved query's arguments as an agentjj command, which must be a
+/// read-only one other than `query` itself
+fn query_cli(name: &str, args: Vec<String>) -> Result<Cli> {
+    let line = agentjj::queries::join(&args);
+    let cli =
+        Cli::try_parse_from(std::iter::once("agentjj".to_string()).chain(args)).map_err(|e| {
+            let rendered = e.to_string();
+            let reason = rendered.lines().next().unwrap_or_default();
+            agentjj::Error::Config {
+                message: format!(
+                    "query '{}' is not a valid command (`{}`): {}",
+                    name,
+                    line,
+                    reason.trim_start_matches("error: ")
+                ),
+            }
+        })?;
+    if matches!(cli.command, Commands::Query { .. }) || !query_command(&cli.command) {
+        return Err(agentjj::Error::Config {
+            message: format!(
+                "query '{}' must be a read-only command other than `query`, not `{}`",
+                name, line
+            ),
+        }
+        .into());
     }
+    Ok(cli)
 }
 
-/// Graph node representation for structured output
-#[derive(Clone)]
-struct GraphNode {
-    id: String,
-    description: String,
-    parents: Vec<String>,
-    timestamp: Option<String>,
-    author: Option<String>,
-    full_commit_id: String,
-}
+fn cmd_query_save(
+    name: String,
+    command: String,
+    description: Option<String>,
+    json: bool,
+) -> Result<()> {
+    use agentjj::queries::{self, NamedQuery, Queries};
 
-/// Get structured graph nodes using Repo.log_entries()
-fn get_graph_nodes(repo: &mut Repo, limit: usize, all: bool) -> Result<Vec<GraphNode>> {
-    let entries = repo.log_entries(limit, all)?;
+    let repo = Repo::discover()?;
+    query_cli(&name, queries::split(&command)?)?;
+    let mut saved = Queries::load(repo.root())?;
+    let replaced = saved.insert(
+        &name,
+        NamedQuery {
+            command: command.clone(),
+            description,
+        },
+    )?;
+    saved.save(repo.root())?;
 
-    let nodes = entries
-        .into_iter()
-        .map(|entry| GraphNode {
-            id: entry.change_id,
-            description: entry.description,
-            parents: entry.parent_change_ids,
-            timestamp: entry.timestamp,
-            author: entry.author,
-            full_commit_id: entry.full_commit_id,
-        })
-        .collect();
+    if json {
+        println!(
+            "{}",
+            json_string(&serde_json::json!({
+                "saved": true,
+                "name": name,
+                "command": command,
+                "replaced": replaced.map(|q| q.command),
+                "path": Queries::DEFAULT_PATH,
+            }))?
+        );
+    } else {
+        let verb = if replaced.is_some() {
+            "Replaced"
+        } else {
+            "Saved"
+        };
+        println!("✓ {} query '{}': agentjj {}", verb, name, command);
+        println!("  Run it with: agentjj query run {}", name);
+    }
+    Ok(())
+}
 
-    Ok(nodes)
+fn cmd_query_run(name: String, extra: Vec<String>, json: bool) -> Result<()> {
+    let repo = Repo::discover()?;
+    let saved = agentjj::queries::Queries::load(repo.root())?;
+    let mut args = saved.get(&name)?.args()?;
+    args.extend(extra);
+    drop(repo);
+
+    let mut cli = query_cli(&name, args.clone())?;
+    cli.json |= json;
+    if !cli.json {
+        println!("$ agentjj {}\n", agentjj::queries::join(&args));
+    }
+    run_command(cli)
 }
 
-/// ASCII format: structured log output with optional timestamps
-fn cmd_graph_ascii(repo: &mut Repo, limit: usize, all: bool, json: bool) -> Result<()> {
-    let nodes = get_graph_nodes(repo, limit, all)?;
+fn cmd_query_list(json: bool) -> Result<()> {
+    let repo = Repo::discover()?;
+    let queries = agentjj::queries::Queries::load(repo.root())?.list();
 
     if json {
-        // Also get the raw ASCII diagram for backwards compatibility
-        let ascii_output = repo.log_ascii(limit, all).unwrap_or_default();
         println!(
             "{}",
-            serde_json::to_string_pretty(&serde_json::json!({
-                "format": "ascii",
-                "diagram": ascii_output,
-                "nodes": nodes.iter().map(|n| serde_json::json!({
-                    "id": n.id,
-                    "description": n.description,
-                    "parents": n.parents,
-                    "timestamp": n.timestamp,
-                    "author": n.author,
-                    "full_commit_id": n.full_commit_id,
-                })).collect::<Vec<_>>(),
-            }))?
+            json_string(&serde_json::json!({ "queries": queries }))?
         );
+    } else if queries.is_empty() {
+        println!("No saved queries - add one with `agentjj query save <name> '<command>'`");
     } else {
-        // Render ASCII graph with timestamps inline
-        for node in &nodes {
-            let ts_part = node
-                .timestamp
-                .as_deref()
-                .map(|ts| format!(" [{}]", ts))
-                .unwrap_or_default();
-            let desc = if node.description.is_empty() {
-                "(empty)".to_string()
-            } else {
-                node.description.clone()
-            };
-            println!("* {}{} {}", node.id, ts_part, desc);
+        for info in &queries {
+            println!("{:<24} agentjj {}", info.name, info.query.command);
+            if let Some(description) = &info.query.description {
+                println!("{:<24} {}", "", description);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Replace the value of the first matching flag (`--flag value` or `--flag=value`)
+fn replace_arg_value(args: &mut [String], flags: &[&str], value: &str) {
+    for i in 0..args.len() {
+        if flags.contains(&args[i].as_str()) && i + 1 < args.len() {
+            args[i + 1] = value.to_string();
+            return;
+        }
+        if let Some((flag, _)) = args[i].split_once('=') {
+            if flags.contains(&flag) {
+                args[i] = format!("{}={}", flag, value);
+                return;
+            }
         }
     }
+}
 
-    Ok(())
+/// Drop a flag and its value (`--flag value` or `--flag=value`)
+fn remove_arg(args: &mut Vec<String>, flag: &str) {
+    if let Some(i) = args.iter().position(|a| a == flag) {
+        args.drain(i..(i + 2).min(args.len()));
+    } else {
+        args.retain(|a| !a.starts_with(&format!("{}=", flag)));
+    }
 }
 
-/// Mermaid format: generate flowchart from jj log
-fn cmd_graph_mermaid(repo: &mut Repo, limit: usize, all: bool, json: bool) -> Result<()> {
-    let nodes = get_graph_nodes(repo, limit, all)?;
+/// Show the effective configuration after all layers are applied
+fn cmd_config(key: Option<String>, json: bool) -> Result<()> {
+    let config = agentjj::Config::discover()?;
+    let values = serde_json::to_value(&config)?;
 
-    // Build Mermaid flowchart
-    let mut diagram = String::from("flowchart TD\n");
+    let env: Vec<String> = agentjj::config::KEYS
+        .iter()
+        .map(|k| agentjj::config::env_var_name(k))
+        .filter(|name| std::env::var_os(name).is_some())
+        .collect();
 
-    for node in &nodes {
-        // Escape quotes in description and truncate
-        let desc = node.description.replace('"', "'").replace('\n', " ");
-        let truncated_desc = if desc.chars().count() > 40 {
-            format!("{}...", desc.chars().take(37).collect::<String>())
+    if let Some(key) = key {
+        let value = key
+            .split_once('.')
+            .filter(|_| agentjj::config::KEYS.contains(&key.as_str()))
+            .map(|(section, field)| values[section][field].clone())
+            .ok_or_else(|| anyhow::anyhow!("Unknown config key: {}", key))?;
+        if json {
+            println!(
+                "{}",
+                json_string(&serde_json::json!({
+                    "key": key,
+                    "value": value,
+                }))?
+            );
         } else {
-            desc.clone()
-        };
-
-        // Include timestamp in the node label when available
-        let ts_suffix = node
-            .timestamp
-            .as_deref()
-            .map(|ts| format!("<br/>{}", ts))
-            .unwrap_or_default();
-
-        // Node definition with short ID
-        diagram.push_str(&format!(
-            "  {}[\"{}{}\"]\n",
-            node.id, truncated_desc, ts_suffix
-        ));
-
-        // Edges to parents
-        for parent_id in &node.parents {
-            diagram.push_str(&format!("  {} --> {}\n", node.id, parent_id));
+            println!("{}", value);
         }
+        return Ok(());
     }
 
     if json {
         println!(
             "{}",
-            serde_json::to_string_pretty(&serde_json::json!({
-                "format": "mermaid",
-                "diagram": diagram,
-                "nodes": nodes.iter().map(|n| serde_json::json!({
-                    "id": n.id,
-                    "description": n.description,
-                    "parents": n.parents,
-                    "timestamp": n.timestamp,
-                    "author": n.author,
-                    "full_commit_id": n.full_commit_id,
-                })).collect::<Vec<_>>(),
+            json_string(&serde_json::json!({
+                "config": values,
+                "file": agentjj::Config::DEFAULT_PATH,
+                "env_overrides": env,
             }))?
         );
     } else {
-        print!("{}", diagram);
+        for key in agentjj::config::KEYS {
+            if let Some((section, field)) = key.split_once('.') {
+                println!("{} = {}", key, values[section][field]);
+            }
+        }
+        if !env.is_empty() {
+            println!("\nFrom environment: {}", env.join(", "));
+        }
     }
 
     Ok(())
 }
 
-/// DOT format: generate Graphviz output from jj log
-fn cmd_graph_dot(repo: &mut Repo, limit: usize, all: bool, json: bool) -> Result<()> {
-    let nodes = get_graph_nodes(repo, limit, all)?;
+/// One-call task bootstrap: composes orient, preflight, checkpoint create,
+/// and suggest --for. A preflight no-go is reported, not fatal: the result
+/// says `ready: false` and lists the blocks under `warnings`.
+fn cmd_start(
+    task: String,
+    paths: Vec<String>,
+    no_checkpoint: bool,
+    changes: usize,
+    json: bool,
+) -> Result<()> {
+    use agentjj::plan::Goal;
+    use agentjj::start::{likely_paths, task_id, LikelyPath, MAX_LIKELY_PATHS};
 
-    // Build DOT graph
-    let mut diagram = String::from("digraph G {\n");
-    diagram.push_str("  rankdir=BT;\n");
-    diagram.push_str("  node [shape=box, style=rounded];\n\n");
+    let mut repo = Repo::discover()?;
+    let root = repo.root().to_path_buf();
+    let started_at = chrono_lite_now();
+    let id = task_id(&task, &started_at);
 
-    for node in &nodes {
-        // Escape quotes in description and truncate
-        let desc = node.description.replace('"', "\\\"").replace('\n', "\\n");
-        let truncated_desc = if desc.chars().count() > 40 {
-            format!("{}...", desc.chars().take(37).collect::<String>())
-        } else {
-            desc.clone()
-        };
+    // Session: keep the caller's, else open one named after the task. This
+    // invocation's audit record already goes to it.
+    let existing = std::env::var(agentjj::audit::SESSION_ENV)
+        .ok()
+        .filter(|s| !s.trim().is_empty());
+    let new_session = existing.is_none();
+    let session = existing.unwrap_or_else(|| id.clone());
+    if new_session {
+        std::env::set_var(agentjj::audit::SESSION_ENV, &session);
+    }
 
-        // Include timestamp in the label when available
-        let ts_line = node
-            .timestamp
-            .as_deref()
-            .map(|ts| format!("\\n{}", ts))
-            .unwrap_or_default();
+    // Brief orientation
+    let change_id = repo.current_change_id()?;
+    let files = repo.changed_files(&change_id)?;
+    let tooling = agentjj::tooling::detect(&root);
+    let manifest = repo.manifest().ok().cloned();
+    let recent_changes: Vec<serde_json::Value> = repo
+        .log_entries(4, false)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|entry| !entry.is_working_copy)
+        .take(3)
+        .map(|entry| {
+            serde_json::json!({
+                "change_id": entry.change_id,
+                "description": entry.description.lines().next().unwrap_or("(no description)"),
+            })
+        })
+        .collect();
+    let orient = serde_json::json!({
+        "change_id": &change_id[..12.min(change_id.len())],
+        "uncommitted_files": files,
+        "repository": manifest.as_ref().map(|m| serde_json::json!({
+            "name": m.repo.name,
+            "description": m.repo.description,
+            "languages": m.repo.languages,
+        })),
+        "test_command": tooling.test_command(),
+        "how_to": tooling.how_to,
+        "recent_changes": recent_changes,
+    });
 
-        // Node definition
-        diagram.push_str(&format!(
-            "  \"{}\" [label=\"{}\\n{}{}\"];\n",
-            node.id, node.id, truncated_desc, ts_line
-        ));
+    // Preflight the paths the task names or most likely touches
+    let likely: Vec<LikelyPath> = if paths.is_empty() {
+        let codebase: Vec<String> = codebase_files(&mut repo)?
+            .iter()
+            .map(|p| {
+                p.strip_prefix(&root)
+                    .unwrap_or(p)
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect();
+        likely_paths(&task, &codebase, MAX_LIKELY_PATHS)
+    } else {
+        paths
+            .iter()
+            .map(|path| LikelyPath {
+                path: path.trim_start_matches("./").to_string(),
+                reason: "given".to_string(),
+                matched: Vec::new(),
+            })
+            .collect()
+    };
+    let preflight_paths: Vec<String> = likely.iter().map(|p| p.path.clone()).collect();
+    let preflight = if preflight_paths.is_empty() {
+        None
+    } else {
+        Some(preflight_report(&mut repo, &preflight_paths, changes)?)
+    };
+    let warnings: Vec<String> = preflight
+        .iter()
+        .flat_map(|report| &report.reasons)
+        .filter(|reason| reason.severity == "block")
+        .map(|reason| reason.message.clone())
+        .collect();
 
-        // Edges to parents
-        for parent_id in &node.parents {
-            diagram.push_str(&format!("  \"{}\" -> \"{}\";\n", node.id, parent_id));
+    let checkpoint = if no_checkpoint {
+        None
+    } else {
+        let name = format!("start-{}", id);
+        let mut checkpoint =
+            create_checkpoint(&mut repo, &name, Some(format!("Before: {}", task)))?;
+        checkpoint["restore_command"] = serde_json::json!(format!("agentjj undo --to {}", name));
+        Some(checkpoint)
+    };
+
+    // The plan, minus the checkpoint step start just took
+    let (goal, guessed) = match Goal::parse(&task) {
+        Some(goal) => (goal, false),
+        None => (Goal::Feature, true),
+    };
+    let (_, mut steps) = goal_plan(&mut repo, goal, &task, files.len())?;
+    if checkpoint.is_some() {
+        steps.retain(|step| step.action != "checkpoint");
+        for (i, step) in steps.iter_mut().enumerate() {
+            step.step = i + 1;
         }
     }
 
-    diagram.push_str("}\n");
+    let ready = preflight.as_ref().is_none_or(|report| report.go);
 
     if json {
+        let result = serde_json::json!({
+            "task": task,
+            "session": {
+                "id": session,
+                "new": new_session,
+                "env": format!("{}={}", agentjj::audit::SESSION_ENV, session),
+            },
+            "orient": orient,
+            "paths": likely,
+            "preflight": preflight,
+            "checkpoint": checkpoint,
+            "plan": {
+                "matched": goal,
+                "guessed": guessed,
+                "steps": steps,
+            },
+            "ready": ready,
+            "warnings": warnings,
+        });
+        println!("{}", json_string(&result)?);
+    } else {
+        println!("=== Start: {} ===\n", task);
+        if new_session {
+            println!(
+                "Session: {} (export {}={})",
+                session,
+                agentjj::audit::SESSION_ENV,
+                session
+            );
+        } else {
+            println!("Session: {}", session);
+        }
         println!(
-            "{}",
-            serde_json::to_string_pretty(&serde_json::json!({
-                "format": "dot",
-                "diagram": diagram,
-                "nodes": nodes.iter().map(|n| serde_json::json!({
-                    "id": n.id,
-                    "description": n.description,
-                    "parents": n.parents,
-                    "timestamp": n.timestamp,
-                    "author": n.author,
-                    "full_commit_id": n.full_commit_id,
-                })).collect::<Vec<_>>(),
-            }))?
+            "Change: {} ({} uncommitted file(s))",
+            &change_id[..12.min(change_id.len())],
+            files.len()
         );
-    } else {
-        print!("{}", diagram);
+        if let Some(test) = tooling.test_command() {
+            println!("Test: {}", test);
+        }
+        if let Some(checkpoint) = &checkpoint {
+            println!(
+                "Checkpoint: {} (restore with: {})",
+                checkpoint["name"].as_str().unwrap_or_default(),
+                checkpoint["restore_command"].as_str().unwrap_or_default()
+            );
+        }
+        if likely.is_empty() {
+            println!("\nNo likely paths found; pass --paths to preflight specific ones");
+        } else {
+            println!("\n{}", if ready { "✓ go" } else { "✗ no-go" });
+            for path in &likely {
+                println!("  {} ({})", path.path, path.reason);
+            }
+            for reason in preflight.iter().flat_map(|report| &report.reasons) {
+                let mark = if reason.severity == "block" {
+                    "✗"
+                } else {
+                    "!"
+                };
+                println!("  {} {}", mark, reason.message);
+            }
+        }
+        println!();
+        for step in &steps {
+            println!("{}. {}", step.step, step.reason);
+            println!("   $ {}", step.command);
+        }
     }
 
+    exit_if_strict_warnings(&warnings);
     Ok(())
 }
 
-/// Output the full skill documentation, embedded at compile time
-fn cmd_skill(json: bool) -> Result<()> {
-    let skill_text = include_str!("../docs/skill.md");
+/// Report which features work here; never changes the repository, so a git
+/// repository without jj is described rather than colocated
+fn cmd_capabilities(read_only_flag: bool, json: bool) -> Result<()> {
+    use agentjj::capabilities::{
+        Capabilities, Daemon, Feature, Forge, Languages, Lsp, RepositoryInfo, Tool, WriteMode,
+        SCHEMA_VERSION, TOOLS,
+    };
 
-    if json {
-        println!(
-            "{}",
-            serde_json::to_string_pretty(&serde_json::json!({
-                "format": "markdown",
-                "content": skill_text,
-                "description": "Full agentjj skill documentation for agent self-discovery",
-            }))?
-        );
+    let cwd = std::env::current_dir()?;
+    let root = cwd
+        .ancestors()
+        .find(|dir| dir.join(".jj").exists() || dir.join(".git").exists())
+        .map(std::path::Path::to_path_buf);
+    let config = agentjj::config::Config::discover()?;
+
+    let files = match &root {
+        Some(root) if root.join(".jj").exists() => {
+            agentjj::repo::set_auto_colocate(false);
+            Repo::discover()
+                .map(|mut repo| codebase_files(&mut repo).unwrap_or_default())
+                .unwrap_or_default()
+        }
+        _ => Vec::new(),
+    };
+
+    let path_var = std::env::var("PATH").ok();
+    let tools: std::collections::BTreeMap<String, Tool> = TOOLS
+        .iter()
+        .map(|(name, used_by)| {
+            (
+                name.to_string(),
+                Tool::find(name, used_by, path_var.as_deref()),
+            )
+        })
+        .collect();
+    let forge = Forge::detect(
+        root.as_ref()
+            .is_some_and(|root| agentjj::secrets::forge_token(root).is_some()),
+        tools["gh"].available,
+        |name| std::env::var(name).ok(),
+    );
+
+    let enabled_by = if read_only_flag {
+        Some("--read-only")
+    } else if manifest_read_only(local_manifest().as_ref()) {
+        Some("manifest default_mode")
     } else {
-        print!("{}", skill_text);
+        None
+    };
+
+    let capabilities = Capabilities {
+        schema_version: SCHEMA_VERSION,
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        repository: root.as_ref().map(|root| RepositoryInfo {
+            root: root.display().to_string(),
+            jj: root.join(".jj").exists(),
+            git: root.join(".git").exists(),
+            manifest: root.join(Manifest::DEFAULT_PATH).exists(),
+        }),
+        languages: Languages::from_files(&files, config.index.enabled),
+        tools,
+        forge,
+        lsp: Lsp::default(),
+        sandbox: Feature::sandbox(),
+        daemon: Daemon::default(),
+        write: WriteMode::new(enabled_by),
+    };
+
+    if json {
+        println!("{}", json_string(&capabilities)?);
+        return Ok(());
     }
 
+    println!("agentjj {}", capabilities.version);
+    match &capabilities.repository {
+        Some(repo) if repo.jj => println!("Repository: {}", repo.root),
+        Some(repo) => println!(
+            "Repository: {} (git only: run `agentjj init --colocate`)",
+            repo.root
+        ),
+        None => println!("Repository: none"),
+    }
+    let mut indexed: Vec<_> = capabilities.languages.indexed.iter().collect();
+    indexed.sort_by(|a, b| b.1.cmp(a.1));
+    if indexed.is_empty() {
+        println!("Languages: none indexed");
+    } else {
+        let names: Vec<_> = indexed
+            .iter()
+            .map(|(name, files)| format!("{} ({})", name, files))
+            .collect();
+        println!("Languages: {}", names.join(", "));
+    }
+    for (name, tool) in &capabilities.tools {
+        let mark = if tool.available { "✓" } else { "✗" };
+        println!("  {} {:<4} {}", mark, name, tool.used_by);
+    }
+    match capabilities.forge.token_source {
+        Some(source) => println!("GitHub token: yes ({})", source),
+        None => println!("GitHub token: no"),
+    }
+    println!(
+        "Pull requests: {}",
+        if capabilities.forge.pull_requests {
+            "yes"
+        } else {
+            "no"
+        }
+    );
+    println!("LSP: no ({})", capabilities.lsp.reason);
+    println!("Sandbox: no ({})", capabilities.sandbox.reason);
+    println!("Daemon: no ({})", capabilities.daemon.reason);
+    match &capabilities.write.enabled_by {
+        Some(by) => println!("Write mode: {} ({})", capabilities.write.mode, by),
+        None => println!("Write mode: {}", capabilities.write.mode),
+    }
     Ok(())
 }
 
@@ -2729,7 +12754,7 @@ fn cmd_quickstart(json: bool) -> Result<()> {
 
         println!(
             "{}",
-            serde_json::to_string_pretty(&serde_json::json!({
+            json_string(&serde_json::json!({
                 "title": "agentjj Quick Start",
                 "description": "6 steps to productive version control with agentjj",
                 "steps": json_steps,
@@ -2762,6 +12787,20 @@ fn cmd_suggest(json: bool) -> Result<()> {
 
     let mut suggestions = Vec::new();
 
+    // What recently failed comes first: it's what the agent is stuck on
+    if repo.config()?.metrics.enabled {
+        let events = agentjj::metrics::UsageLog::open(repo.root()).events();
+        for hint in agentjj::metrics::hints(&events, unix_now()) {
+            suggestions.push(serde_json::json!({
+                "action": hint.action,
+                "command": hint.command,
+                "reason": hint.reason,
+                "priority": "high",
+                "source": "usage_metrics",
+            }));
+        }
+    }
+
     // Based on current state, suggest actions
     if !has_manifest {
         suggestions.push(serde_json::json!({
@@ -2815,7 +12854,7 @@ fn cmd_suggest(json: bool) -> Result<()> {
     if json {
         println!(
             "{}",
-            serde_json::to_string_pretty(&serde_json::json!({
+            json_string(&serde_json::json!({
                 "current_state": {
                     "change_id": &change_id[..12.min(change_id.len())],
                     "files_changed": files.len(),
@@ -2843,6 +12882,232 @@ fn cmd_suggest(json: bool) -> Result<()> {
     Ok(())
 }
 
+/// Run a manifest-defined custom command, or list them when no name is given
+fn cmd_run(name: Option<String>, raw_args: Vec<String>, json: bool) -> Result<()> {
+    use agentjj::custom::{self, RunResult};
+
+    let mut repo = Repo::discover()?;
+    let commands = repo.manifest()?.commands.custom.clone();
+
+    let Some(name) = name else {
+        let infos = custom::list(&commands);
+        if json {
+            println!(
+                "{}",
+                json_string(&serde_json::json!({ "commands": infos }))?
+            );
+        } else if infos.is_empty() {
+            println!("No custom commands - add [commands.custom.<name>] to .agent/manifest.toml");
+        } else {
+            for info in &infos {
+                println!("{}", info.usage);
+                if !info.description.is_empty() {
+                    println!("    {}", info.description);
+                }
+            }
+        }
+        return Ok(());
+    };
+
+    let cmd = commands
+        .get(&name)
+        .ok_or_else(|| custom::unknown_command(&commands, &name))?;
+    let (args, steps) = custom::render(&name, cmd, &custom::parse_args(&raw_args)?)?;
+    let results = repo.run_custom_steps(&steps)?;
+
+    let success = results.len() == steps.len() && results.iter().all(|r| r.success);
+    let output = results
+        .last()
+        .map(|r| custom::parse_output(&r.stdout))
+        .unwrap_or(serde_json::Value::Null);
+    let warnings = match (&cmd.output, success) {
+        (Some(schema), true) => custom::check_output(schema, &output),
+        _ => Vec::new(),
+    };
+    let failed_code = results
+        .last()
+        .filter(|r| !r.success)
+        .map(|r| match r.exit_code {
+            // A composed agentjj call keeps its meaning
+            Some(code) if (exit::FAILURE..=exit::POLICY).contains(&code) => code,
+            _ => exit::FAILURE,
+        });
+    let result = RunResult {
+        name,
+        args,
+        success,
+        steps: results,
+        output,
+        warnings,
+    };
+
+    if json {
+        println!("{}", json_string(&result)?);
+    } else {
+        for step in &result.steps {
+            println!(
+                "{} $ {}",
+                if step.success { "✓" } else { "✗" },
+                step.command
+            );
+            print!("{}", step.stdout);
+            if !step.success {
+                eprint!("{}", step.stderr);
+            }
+        }
+        for warning in &result.warnings {
+            println!("warning: {}", warning);
+        }
+    }
+
+    if let Some(code) = failed_code {
+        exit_with(code, Some("custom_command_failed"));
+    }
+    exit_if_strict_warnings(&result.warnings);
+    Ok(())
+}
+
+/// Ordered command plan for a goal such as "add api endpoint" or "release"
+fn cmd_suggest_for(goal_text: String, json: bool) -> Result<()> {
+    use agentjj::plan::Goal;
+
+    let Some(goal) = Goal::parse(&goal_text) else {
+        anyhow::bail!(
+            "Unrecognized goal '{}' - mention one of: {}",
+            goal_text,
+            Goal::examples().join(", ")
+        );
+    };
+
+    let mut repo = Repo::discover()?;
+    let change_id = repo.current_change_id()?;
+    let files = repo.changed_files(&change_id)?;
+    let (ctx, steps) = goal_plan(&mut repo, goal, &goal_text, files.len())?;
+
+    if json {
+        let mut result = serde_json::json!({
+            "goal": goal_text,
+            "matched": goal,
+            "current_state": {
+                "change_id": &change_id[..12.min(change_id.len())],
+                "files_changed": files.len(),
+                "has_manifest": ctx.has_manifest,
+            },
+            "plan": steps,
+        });
+        if goal == Goal::Release {
+            result["release"] = serde_json::json!({
+                "last_tag": ctx.last_tag,
+                "unreleased_changes": ctx.unreleased,
+                "breaking_changes": ctx.breaking,
+                "bump": ctx.bump,
+                "next_version": ctx.next_version,
+            });
+        }
+        println!("{}", json_string(&result)?);
+    } else {
+        println!(
+            "=== Plan: {} ({}) ===\n",
+            goal_text,
+            serde_json::to_value(goal)?.as_str().unwrap_or("")
+        );
+        for step in &steps {
+            println!("{}. {}", step.step, step.reason);
+            println!("   $ {}", step.command);
+            println!();
+        }
+    }
+
+    Ok(())
+}
+
+/// The `suggest --for` plan for `goal`, with the repository state it was
+/// built from
+fn goal_plan(
+    repo: &mut Repo,
+    goal: agentjj::plan::Goal,
+    goal_text: &str,
+    files_changed: usize,
+) -> Result<(agentjj::plan::PlanContext, Vec<agentjj::plan::Step>)> {
+    use agentjj::plan::{self, Goal, PlanContext};
+
+    let root = repo.root().to_path_buf();
+    let manifest = repo.manifest().ok();
+    let tooling = agentjj::tooling::detect(&root);
+    let build_manifest = tooling.build_systems.first().map(|b| b.manifest.clone());
+
+    let mut ctx = PlanContext {
+        has_manifest: manifest.is_some(),
+        files_changed,
+        // Interface schemas and entry-point modules (`src/api.py:app`) that exist
+        interfaces: manifest
+            .map(|m| {
+                let mut paths: Vec<String> = m
+                    .interfaces
+                    .values()
+                    .chain(m.entry_points.values())
+                    .filter_map(|value| value.split(':').next())
+                    .filter(|path| root.join(path).is_file())
+                    .map(str::to_string)
+                    .collect();
+                paths.sort();
+                paths.dedup();
+                paths
+            })
+            .unwrap_or_default(),
+        build_manifest: build_manifest.clone(),
+        test_command: tooling.test_command().map(str::to_string),
+        ..Default::default()
+    };
+
+    if goal == Goal::Release {
+        // Typed changes since the last tag reachable from the working copy
+        let git = |args: &[&str]| -> Option<String> {
+            let output = std::process::Command::new("git")
+                .current_dir(&root)
+                .args(args)
+                .output()
+                .ok()?;
+            output
+                .status
+                .success()
+                .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        };
+        let (_, wc_commit) = repo.resolve_revision("@")?;
+        // Tags live in git; without it every change counts as unreleased
+        if repo.has_git_backend()? {
+            ctx.last_tag = git(&["describe", "--tags", "--abbrev=0", &wc_commit]);
+        }
+        let tag_commit = ctx
+            .last_tag
+            .as_deref()
+            .and_then(|tag| git(&["rev-list", "-n", "1", tag]));
+        let since = repo.change_ids_since(tag_commit.as_deref())?;
+        let index = agentjj::change::ChangeIndex::load_from_repo(&root).unwrap_or_default();
+        let unreleased: Vec<&agentjj::TypedChange> = index
+            .all()
+            .into_iter()
+            .filter(|c| since.iter().any(|id| id.starts_with(&c.change_id)))
+            .collect();
+        let bump = plan::bump_for(&unreleased);
+        ctx.unreleased = unreleased.len();
+        ctx.breaking = unreleased.iter().filter(|c| c.breaking).count();
+        ctx.bump = Some(bump);
+        ctx.version_line = build_manifest.as_deref().and_then(|path| {
+            std::fs::read_to_string(root.join(path))
+                .ok()
+                .and_then(|content| plan::find_version_line(path, &content))
+        });
+        ctx.next_version = match (&ctx.last_tag, &ctx.version_line) {
+            (Some(tag), _) => plan::next_version(tag, bump),
+            (None, Some(line)) => plan::next_version(&format!("v{}", line.version), bump),
+            (None, None) => None,
+        };
+    }
+
+    let steps = plan::plan(goal, goal_text, &ctx);
+    Ok((ctx, steps))
+}
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -3106,4 +13371,18 @@ mod tests {
         let symbol = make_symbol("myFunc", None);
         assert!(is_public_symbol(&symbol, SupportedLanguage::TypeScript));
     }
+    #[test]
+    fn test_pr_notes_section_is_replaced_in_place() {
+        let first = with_pr_notes("Fixes the retry loop.\n", "## Notes\n\nv1");
+        assert!(first.starts_with("Fixes the retry loop.\n\n<!-- agentjj:notes -->"));
+        let edited = first.replace("Fixes", "Human edit: fixes") + "\n\nTrailing text";
+        let second = with_pr_notes(&edited, "## Notes\n\nv2");
+        assert!(second.starts_with("Human edit: fixes the retry loop."));
+        assert!(second.contains("v2") && !second.contains("v1"));
+        assert!(second.ends_with("Trailing text"));
+        assert_eq!(
+            with_pr_notes("", "n"),
+            "<!-- agentjj:notes -->\nn\n<!-- /agentjj:notes -->"
+        );
+    }
 }
@@ -2,10 +2,12 @@
 // ABOUTME: Defines repo capabilities, interfaces, invariants, and permissions
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::Path;
 
+use crate::change::{enum_name, ChangeCategory, ChangeType};
 use crate::error::{Error, Result};
+use crate::globs::Globs;
 
 /// The root manifest structure, typically at `.agent/manifest.toml`
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -21,6 +23,11 @@ pub struct Manifest {
     #[serde(default)]
     pub invariants: HashMap<String, Invariant>,
 
+    /// Built-in invariant presets (`rust-basic`, ...) whose invariants are
+    /// added unless `[invariants]` defines one of the same name
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub preset: Vec<String>,
+
     #[serde(default)]
     pub permissions: Permissions,
 
@@ -29,6 +36,49 @@ pub struct Manifest {
 
     #[serde(default)]
     pub review: ReviewConfig,
+
+    #[serde(default)]
+    pub hooks: Hooks,
+
+    #[serde(default)]
+    pub environments: HashMap<String, Environment>,
+
+    #[serde(default)]
+    pub commands: CommandsConfig,
+
+    /// Change categories beyond the built-in ones, with what each means
+    /// (`compliance = "Needs sign-off from legal"`)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub categories: BTreeMap<String, String>,
+
+    /// Fields a typed change must carry, by change type or category name
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub change_templates: HashMap<String, ChangeTemplate>,
+
+    /// Test files `test scaffold` creates, by language (`python`, `rust`, ...)
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub test_templates: HashMap<String, TestTemplate>,
+
+    /// Autofix commands `validate --fix` runs over changed files, by name
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub fixers: HashMap<String, Fixer>,
+
+    #[serde(default)]
+    pub security: SecurityConfig,
+
+    #[serde(default)]
+    pub history: HistoryConfig,
+
+    #[serde(default)]
+    pub intents: IntentsConfig,
+
+    /// Disk caps on `.agent` subsystems (`cache = "500MB"`), pruned
+    /// oldest-first when exceeded
+    #[serde(
+        default,
+        skip_serializing_if = "crate::storage::StorageConfig::is_empty"
+    )]
+    pub storage: crate::storage::StorageConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -55,11 +105,15 @@ pub enum Invariant {
     /// Simple form: just a command string
     Simple(String),
 
-    /// Full form: command with triggers
+    /// Full form: command with triggers and environment
     Full {
         cmd: String,
         #[serde(default)]
         on: Vec<InvariantTrigger>,
+        /// Variables to set, with `${VAR}` interpolated from
+        /// `.agent/secrets.env`, the process environment, or the OS keychain
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        env: BTreeMap<String, String>,
     },
 }
 
@@ -78,12 +132,77 @@ impl Invariant {
         }
     }
 
+    pub fn env(&self) -> Option<&BTreeMap<String, String>> {
+        match self {
+            Invariant::Simple(_) => None,
+            Invariant::Full { env, .. } => Some(env),
+        }
+    }
+
     pub fn should_run_on(&self, trigger: InvariantTrigger) -> bool {
         let triggers = self.triggers();
         triggers.is_empty() || triggers.contains(&trigger)
     }
 }
 
+/// A deployment environment, tracked by the bookmark(s) pointing at what it runs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Environment {
+    /// Simple form: a bookmark name or `prefix/*` pattern
+    Simple(String),
+
+    /// Full form: bookmark with promotion policy
+    Full {
+        bookmark: String,
+        /// Refuse promotions into this environment
+        #[serde(default)]
+        frozen: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        freeze_reason: Option<String>,
+        /// Environments that may be promoted into this one (empty: any)
+        #[serde(default)]
+        from: Vec<String>,
+    },
+}
+
+impl Environment {
+    pub fn bookmark(&self) -> &str {
+        match self {
+            Environment::Simple(bookmark) => bookmark,
+            Environment::Full { bookmark, .. } => bookmark,
+        }
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        matches!(self, Environment::Full { frozen: true, .. })
+    }
+
+    pub fn freeze_reason(&self) -> Option<&str> {
+        match self {
+            Environment::Full { freeze_reason, .. } => freeze_reason.as_deref(),
+            Environment::Simple(_) => None,
+        }
+    }
+
+    pub fn promote_from(&self) -> &[String] {
+        match self {
+            Environment::Simple(_) => &[],
+            Environment::Full { from, .. } => from,
+        }
+    }
+
+    /// Whether the bookmark pattern names several bookmarks (`preview/*`)
+    pub fn is_pattern(&self) -> bool {
+        self.bookmark().contains('*')
+    }
+
+    /// Check if a bookmark belongs to this environment
+    pub fn matches(&self, bookmark: &str) -> bool {
+        crate::globs::name_matches(self.bookmark(), bookmark)
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub enum InvariantTrigger {
@@ -93,6 +212,96 @@ pub enum InvariantTrigger {
     Always,
 }
 
+/// Points around agentjj commands where hooks run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HookEvent {
+    PreCommit,
+    PostCommit,
+    PreApply,
+    PostUndo,
+    /// A command is waiting for a human (a confirmation or an approval)
+    Confirmation,
+}
+
+impl HookEvent {
+    pub const ALL: [HookEvent; 5] = [
+        HookEvent::PreCommit,
+        HookEvent::PostCommit,
+        HookEvent::PreApply,
+        HookEvent::PostUndo,
+        HookEvent::Confirmation,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HookEvent::PreCommit => "pre-commit",
+            HookEvent::PostCommit => "post-commit",
+            HookEvent::PreApply => "pre-apply",
+            HookEvent::PostUndo => "post-undo",
+            HookEvent::Confirmation => "confirmation",
+        }
+    }
+
+    /// Pre-hooks can veto the operation; post-hooks only report
+    pub fn can_veto(&self) -> bool {
+        matches!(self, HookEvent::PreCommit | HookEvent::PreApply)
+    }
+}
+
+/// One command or a list of commands for a hook event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum HookCommands {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl HookCommands {
+    pub fn commands(&self) -> Vec<&str> {
+        match self {
+            HookCommands::One(cmd) => vec![cmd.as_str()],
+            HookCommands::Many(cmds) => cmds.iter().map(String::as_str).collect(),
+        }
+    }
+}
+
+/// Commands run around agentjj operations. Each receives the operation's
+/// JSON payload on stdin; a non-zero exit from a pre-hook blocks it.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct Hooks {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_commit: Option<HookCommands>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_commit: Option<HookCommands>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_apply: Option<HookCommands>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub post_undo: Option<HookCommands>,
+
+    /// Notification (e.g. a webhook via curl) that a human needs to confirm
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confirmation: Option<HookCommands>,
+}
+
+impl Hooks {
+    /// Commands configured for an event, in declaration order
+    pub fn for_event(&self, event: HookEvent) -> Vec<&str> {
+        let commands = match event {
+            HookEvent::PreCommit => &self.pre_commit,
+            HookEvent::PostCommit => &self.post_commit,
+            HookEvent::PreApply => &self.pre_apply,
+            HookEvent::PostUndo => &self.post_undo,
+            HookEvent::Confirmation => &self.confirmation,
+        };
+        commands.as_ref().map(|c| c.commands()).unwrap_or_default()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Permissions {
     #[serde(default)]
@@ -101,61 +310,112 @@ pub struct Permissions {
     #[serde(default)]
     pub deny_change: Vec<String>,
 
+    /// Paths an agent may change only once a human approves
+    #[serde(default)]
+    pub ask_change: Vec<String>,
+
     #[serde(default)]
     pub allow_push: Vec<String>,
 
     #[serde(default)]
     pub deny_push: Vec<String>,
+
+    /// SQL files where DROP/TRUNCATE may be committed without `--breaking`
+    #[serde(default)]
+    pub allow_destructive_schema: Vec<String>,
+
+    /// `read-only` disables every state-changing command, as `--read-only` does
+    #[serde(default)]
+    pub default_mode: AccessMode,
+}
+
+/// Whether agents may change the repository at all
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum AccessMode {
+    #[default]
+    ReadWrite,
+    ReadOnly,
 }
 
 impl Permissions {
     /// Check if a path is allowed for changes (local modifications)
     pub fn can_change(&self, path: &str) -> bool {
         // Deny takes precedence
-        if self.matches_any(path, &self.deny_change) {
+        if restricts(Globs::new(&self.deny_change), path) {
             return false;
         }
         // If allow list is empty, allow everything not denied
         if self.allow_change.is_empty() {
             return true;
         }
-        self.matches_any(path, &self.allow_change)
+        grants(Globs::new(&self.allow_change), path) || self.needs_ask(path)
+    }
+
+    /// Check if changing a path must wait for human approval
+    pub fn needs_approval(&self, path: &str) -> bool {
+        self.can_change(path) && self.needs_ask(path)
+    }
+
+    fn needs_ask(&self, path: &str) -> bool {
+        restricts(Globs::new(&self.ask_change), path)
+    }
+
+    /// Check if destructive schema operations in a path are pre-approved
+    pub fn allows_destructive_schema(&self, path: &str) -> bool {
+        grants(Globs::new(&self.allow_destructive_schema), path)
     }
 
     /// Check if a branch is allowed for push
     pub fn can_push(&self, branch: &str) -> bool {
-        if self.matches_any(branch, &self.deny_push) {
+        if restricts(Globs::names(&self.deny_push), branch) {
             return false;
         }
         if self.allow_push.is_empty() {
             return true;
         }
-        self.matches_any(branch, &self.allow_push)
-    }
-
-    fn matches_any(&self, path: &str, patterns: &[String]) -> bool {
-        patterns.iter().any(|p| Self::glob_match(p, path))
+        grants(Globs::names(&self.allow_push), branch)
     }
 
-    fn glob_match(pattern: &str, path: &str) -> bool {
-        // Simple glob matching: ** matches anything, * matches single segment
-        if pattern == "**" {
-            return true;
+    /// Compile every pattern list, failing on the first invalid pattern
+    fn check_globs(&self) -> Result<()> {
+        for (field, patterns) in [
+            ("allow_change", &self.allow_change),
+            ("deny_change", &self.deny_change),
+            ("ask_change", &self.ask_change),
+            ("allow_destructive_schema", &self.allow_destructive_schema),
+        ] {
+            check_patterns(&format!("permissions.{}", field), Globs::new(patterns))?;
         }
-        if pattern.contains("**") {
-            let prefix = pattern.trim_end_matches("/**").trim_end_matches("**");
-            return path.starts_with(prefix);
+        for (field, patterns) in [
+            ("allow_push", &self.allow_push),
+            ("deny_push", &self.deny_push),
+        ] {
+            check_patterns(&format!("permissions.{}", field), Globs::names(patterns))?;
         }
-        if pattern.contains('*') {
-            let parts: Vec<&str> = pattern.split('*').collect();
-            if parts.len() == 2 {
-                return path.starts_with(parts[0]) && path.ends_with(parts[1]);
-            }
-        }
-        pattern == path
+        Ok(())
     }
 }
 
+/// Whether a restricting list (deny, ask, review) covers `path`. A list
+/// that does not compile covers everything: it fails closed.
+fn restricts(globs: Result<Globs>, path: &str) -> bool {
+    globs.map_or(true, |g| g.is_match(path))
+}
+
+/// Whether a granting list (allow) covers `path`; one that does not
+/// compile grants nothing
+fn grants(globs: Result<Globs>, path: &str) -> bool {
+    globs.is_ok_and(|g| g.is_match(path))
+}
+
+/// The manifest error for an invalid pattern in `field`
+fn check_patterns(field: &str, globs: Result<Globs>) -> Result<()> {
+    globs.map(|_| ()).map_err(|e| Error::Config {
+        message: format!("{}: {}", field, e),
+    })
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BranchConfig {
     #[serde(default = "default_trunk")]
@@ -163,6 +423,11 @@ pub struct BranchConfig {
 
     #[serde(default)]
     pub protected: Vec<String>,
+
+    /// Revisions (bookmarks, change IDs) whose history must not be
+    /// rewritten, in addition to everything already pushed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub immutable: Vec<String>,
 }
 
 fn default_trunk() -> String {
@@ -174,6 +439,7 @@ impl Default for BranchConfig {
         Self {
             trunk: default_trunk(),
             protected: Vec::new(),
+            immutable: Vec::new(),
         }
     }
 }
@@ -183,6 +449,142 @@ pub struct ReviewConfig {
     /// Paths that require human review before merge
     #[serde(default)]
     pub require_human: Vec<String>,
+
+    /// Most TODO/FIXME/HACK comments one change may add (validate fails above it)
+    #[serde(default)]
+    pub max_new_todos: Option<usize>,
+
+    /// Most lines (added plus removed) one commit may change
+    #[serde(default)]
+    pub max_change_loc: Option<usize>,
+
+    /// Most files one commit may touch
+    #[serde(default)]
+    pub max_change_files: Option<usize>,
+
+    /// Who may approve approvals and confirmations with `--by` off a
+    /// terminal (from a script or webhook rather than a person's shell)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub approvers: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct HistoryConfig {
+    /// Walk at most this many commits back in graph, churn, backfill, and
+    /// blame (`--max-history` overrides it)
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+}
+
+/// Retry limits for `apply --attempt-of`
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct IntentsConfig {
+    /// Attempts one intent may have, the first included
+    #[serde(default)]
+    pub max_attempts: Option<usize>,
+    /// Minutes after the first attempt during which retries are allowed
+    #[serde(default)]
+    pub max_minutes: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct SecurityConfig {
+    /// Scan changed markdown, text, and HTML for prompt-injection patterns
+    #[serde(default)]
+    pub scan_prompts: bool,
+
+    /// `block` makes `commit` refuse changes with findings; `warn` only reports them
+    #[serde(default)]
+    pub prompt_policy: PromptPolicy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PromptPolicy {
+    #[default]
+    Warn,
+    Block,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct CommandsConfig {
+    /// Repo-specific workflows exposed as `agentjj run <name>`
+    #[serde(default)]
+    pub custom: HashMap<String, CustomCommand>,
+}
+
+/// Fields required of typed changes of one type (`schema`) or category
+/// (`security`), set with `--field name=value`
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ChangeTemplate {
+    /// Fields that must all be present
+    #[serde(default)]
+    pub required: Vec<String>,
+
+    /// Groups of fields of which at least one must be present
+    #[serde(default)]
+    pub any_of: Vec<Vec<String>>,
+}
+
+/// Layout of a new test file for one language; unset fields keep the
+/// built-in template's
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct TestTemplate {
+    /// Where it goes: `{dir}`, `{stem}`, `{ext}`, `{name}`, `{package}`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+
+    /// Top of the file: `{module}`, `{symbols}`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header: Option<String>,
+
+    /// Appended once per public symbol: `{symbol}`, `{snake}`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stub: Option<String>,
+}
+
+/// A command that rewrites files in place (a formatter, `--fix` linter)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fixer {
+    /// Shell command; `{files}` expands to the matching changed files
+    pub cmd: String,
+
+    /// Globs of the files it handles; empty handles every changed file
+    #[serde(default)]
+    pub files: Vec<String>,
+}
+
+impl Fixer {
+    pub fn applies_to(&self, path: &str) -> bool {
+        self.files.is_empty() || Globs::new(&self.files).is_ok_and(|g| g.is_match(path))
+    }
+}
+
+/// A named workflow: shell steps (which may call `agentjj`) with
+/// `{{arg}}` placeholders and a declared JSON output
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomCommand {
+    #[serde(default)]
+    pub description: String,
+
+    pub steps: Vec<String>,
+
+    #[serde(default)]
+    pub args: HashMap<String, CustomArg>,
+
+    /// JSON schema of what the last step prints
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct CustomArg {
+    #[serde(default)]
+    pub description: String,
+
+    /// Used when `--arg` doesn't set it; without one the arg is required
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
 }
 
 impl Manifest {
@@ -205,7 +607,43 @@ impl Manifest {
 
     /// Parse manifest from TOML string
     pub fn parse(content: &str) -> Result<Self> {
-        toml::from_str(content).map_err(Into::into)
+        let mut manifest: Self = toml::from_str(content)?;
+        manifest.expand_presets()?;
+        manifest.check_globs()?;
+        Ok(manifest)
+    }
+
+    /// Refuse a manifest with a pattern that does not compile, rather than
+    /// let it silently match nothing
+    fn check_globs(&self) -> Result<()> {
+        self.permissions.check_globs()?;
+        check_patterns(
+            "review.require_human",
+            Globs::new(&self.review.require_human),
+        )?;
+        for (name, fixer) in &self.fixers {
+            check_patterns(&format!("fixers.{}.files", name), Globs::new(&fixer.files))?;
+        }
+        Ok(())
+    }
+
+    /// Add the invariants of each preset, keeping those defined by name
+    fn expand_presets(&mut self) -> Result<()> {
+        for name in &self.preset {
+            let preset = crate::presets::find(name).ok_or_else(|| Error::ManifestParse {
+                message: format!(
+                    "unknown invariant preset '{}' (see `agentjj manifest presets list`)",
+                    name
+                ),
+                line: None,
+            })?;
+            for (invariant, cmd) in preset.invariants {
+                self.invariants
+                    .entry(invariant.to_string())
+                    .or_insert_with(|| Invariant::Simple(cmd.to_string()));
+            }
+        }
+        Ok(())
     }
 
     /// Serialize manifest to TOML string
@@ -218,10 +656,47 @@ impl Manifest {
 
     /// Check if a path requires human review
     pub fn requires_human_review(&self, path: &str) -> bool {
-        self.review
-            .require_human
-            .iter()
-            .any(|p| Permissions::glob_match(p, path))
+        restricts(Globs::new(&self.review.require_human), path)
+    }
+
+    /// Check `fields` against the change templates for a change's type and
+    /// categories, failing with everything that is missing
+    pub fn check_change_fields(
+        &self,
+        change_type: ChangeType,
+        categories: &[ChangeCategory],
+        fields: &BTreeMap<String, String>,
+    ) -> Result<()> {
+        let present = |field: &String| fields.get(field).is_some_and(|v| !v.is_empty());
+        let names = std::iter::once(enum_name(change_type))
+            .chain(categories.iter().map(|c| c.name().to_string()));
+        let (mut templates, mut missing, mut any_of) = (Vec::new(), Vec::new(), Vec::new());
+        for name in names {
+            let Some(template) = self.change_templates.get(&name) else {
+                continue;
+            };
+            let before = missing.len() + any_of.len();
+            missing.extend(template.required.iter().filter(|f| !present(f)).cloned());
+            any_of.extend(
+                template
+                    .any_of
+                    .iter()
+                    .filter(|group| !group.iter().any(present))
+                    .cloned(),
+            );
+            if missing.len() + any_of.len() > before {
+                templates.push(name);
+            }
+        }
+        if templates.is_empty() {
+            return Ok(());
+        }
+        missing.dedup();
+        Err(Error::MissingChangeFields {
+            templates,
+            missing,
+            any_of,
+        })
     }
 
     /// Get all invariants that should run for a given trigger
@@ -261,8 +736,10 @@ no_secrets = "! grep -r 'API_KEY=' src/"
 [permissions]
 allow_change = ["src/**", "tests/**"]
 deny_change = [".agent/*", "migrations/*"]
+ask_change = ["config/*"]
 allow_push = ["feat/*", "fix/*"]
 deny_push = ["main", "release/*"]
+allow_destructive_schema = ["db/scratch/*"]
 
 [branches]
 trunk = "main"
@@ -270,6 +747,21 @@ protected = ["main", "release/*"]
 
 [review]
 require_human = ["src/billing/*", "migrations/*"]
+
+[hooks]
+pre-commit = ["./scripts/check-policy.sh", "./scripts/check-size.sh"]
+post-undo = "notify-send undone"
+
+[environments]
+preview = "preview/*"
+staging = "staging"
+prod = { bookmark = "prod", from = ["staging"], frozen = true, freeze_reason = "release freeze" }
+
+[commands.custom.regen-protos]
+description = "Regenerate protobuf bindings"
+steps = ["buf generate --template {{template}}", "agentjj files --dirty"]
+args = { template = { description = "buf template", default = "buf.gen.yaml" } }
+output = { type = "object", required = ["files"] }
 "#;
 
     #[test]
@@ -295,6 +787,98 @@ require_human = ["src/billing/*", "migrations/*"]
         assert!(!manifest.permissions.can_change("migrations/001.sql"));
     }
 
+    #[test]
+    fn default_mode_is_read_write() {
+        let manifest = Manifest::parse(SAMPLE_MANIFEST).unwrap();
+        assert_eq!(manifest.permissions.default_mode, AccessMode::ReadWrite);
+
+        let manifest = Manifest::parse(
+            "[repo]\nname = \"x\"\n\n[permissions]\ndefault_mode = \"read-only\"\n",
+        )
+        .unwrap();
+        assert_eq!(manifest.permissions.default_mode, AccessMode::ReadOnly);
+        assert!(
+            Manifest::parse("[repo]\nname = \"x\"\n[permissions]\ndefault_mode = \"yolo\"\n")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn change_templates_require_fields() {
+        let manifest = Manifest::parse(
+            r#"
+[repo]
+name = "x"
+
+[change_templates.schema]
+required = ["migration_id"]
+
+[change_templates.security]
+any_of = [["cve", "advisory"]]
+"#,
+        )
+        .unwrap();
+        let fields = |pairs: &[(&str, &str)]| -> BTreeMap<String, String> {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+
+        assert!(manifest
+            .check_change_fields(ChangeType::Docs, &[], &fields(&[]))
+            .is_ok());
+        let err = manifest
+            .check_change_fields(
+                ChangeType::Schema,
+                &[ChangeCategory::Security],
+                &fields(&[("migration_id", "")]),
+            )
+            .unwrap_err();
+        match err {
+            Error::MissingChangeFields {
+                templates,
+                missing,
+                any_of,
+            } => {
+                assert_eq!(templates, vec!["schema", "security"]);
+                assert_eq!(missing, vec!["migration_id"]);
+                assert_eq!(any_of, vec![vec!["cve", "advisory"]]);
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        assert!(manifest
+            .check_change_fields(
+                ChangeType::Schema,
+                &[ChangeCategory::Security],
+                &fields(&[("migration_id", "0042"), ("advisory", "GHSA-xxxx")]),
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn permissions_ask() {
+        let manifest = Manifest::parse(SAMPLE_MANIFEST).unwrap();
+
+        // Ask paths are changeable, but only with approval
+        assert!(manifest.permissions.can_change("config/prod.toml"));
+        assert!(manifest.permissions.needs_approval("config/prod.toml"));
+        assert!(!manifest.permissions.needs_approval("src/api.py"));
+        assert!(!manifest.permissions.needs_approval("migrations/001.sql"));
+    }
+
+    #[test]
+    fn permissions_destructive_schema() {
+        let manifest = Manifest::parse(SAMPLE_MANIFEST).unwrap();
+
+        assert!(manifest
+            .permissions
+            .allows_destructive_schema("db/scratch/reset.sql"));
+        assert!(!manifest
+            .permissions
+            .allows_destructive_schema("migrations/001.sql"));
+    }
+
     #[test]
     fn branch_permissions() {
         let manifest = Manifest::parse(SAMPLE_MANIFEST).unwrap();
@@ -319,6 +903,51 @@ require_human = ["src/billing/*", "migrations/*"]
         assert!(names.contains(&"no_secrets"));
     }
 
+    #[test]
+    fn hooks_accept_one_or_many_commands() {
+        let manifest = Manifest::parse(SAMPLE_MANIFEST).unwrap();
+
+        assert_eq!(
+            manifest.hooks.for_event(HookEvent::PreCommit),
+            vec!["./scripts/check-policy.sh", "./scripts/check-size.sh"]
+        );
+        assert_eq!(
+            manifest.hooks.for_event(HookEvent::PostUndo),
+            vec!["notify-send undone"]
+        );
+        assert!(manifest.hooks.for_event(HookEvent::PreApply).is_empty());
+    }
+
+    #[test]
+    fn environments_map_bookmarks() {
+        let manifest = Manifest::parse(SAMPLE_MANIFEST).unwrap();
+
+        let preview = &manifest.environments["preview"];
+        assert!(preview.is_pattern());
+        assert!(preview.matches("preview/pr-12"));
+        assert!(!preview.matches("staging"));
+        assert!(!manifest.environments["staging"].is_frozen());
+
+        let prod = &manifest.environments["prod"];
+        assert_eq!(prod.bookmark(), "prod");
+        assert_eq!(prod.promote_from(), ["staging"]);
+        assert!(prod.is_frozen());
+        assert_eq!(prod.freeze_reason(), Some("release freeze"));
+    }
+
+    #[test]
+    fn custom_commands_parse() {
+        let manifest = Manifest::parse(SAMPLE_MANIFEST).unwrap();
+
+        let regen = &manifest.commands.custom["regen-protos"];
+        assert_eq!(regen.steps.len(), 2);
+        assert_eq!(
+            regen.args["template"].default.as_deref(),
+            Some("buf.gen.yaml")
+        );
+        assert_eq!(regen.output.as_ref().unwrap()["required"][0], "files");
+    }
+
     #[test]
     fn human_review_required() {
         let manifest = Manifest::parse(SAMPLE_MANIFEST).unwrap();
@@ -341,6 +970,67 @@ require_human = ["src/billing/*", "migrations/*"]
         );
     }
 
+    #[test]
+    fn presets_expand_into_invariants_with_overrides() {
+        let manifest = Manifest::parse(
+            "preset = [\"rust-basic\"]\n\n[repo]\nname = \"x\"\n\n[invariants]\nrust_tests = \"cargo nextest run\"\n",
+        )
+        .unwrap();
+        assert_eq!(manifest.invariants.len(), 4);
+        assert_eq!(
+            manifest.invariants["rust_build"].command(),
+            "cargo build --workspace"
+        );
+        assert_eq!(
+            manifest.invariants["rust_tests"].command(),
+            "cargo nextest run"
+        );
+
+        let reparsed = Manifest::parse(&manifest.to_toml().unwrap()).unwrap();
+        assert_eq!(reparsed.preset, vec!["rust-basic"]);
+        assert_eq!(
+            reparsed.invariants["rust_tests"].command(),
+            "cargo nextest run"
+        );
+
+        let err = Manifest::parse("preset = [\"go-basic\"]\n[repo]\nname = \"x\"\n").unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("unknown invariant preset 'go-basic'"));
+    }
+
+    #[test]
+    fn invalid_patterns_are_refused_on_load() {
+        let err = Manifest::parse(
+            "[repo]\nname = \"x\"\n\n[permissions]\ndeny_change = [\"src/**\", \"secrets/{a\"]\n",
+        )
+        .unwrap_err();
+        assert!(matches!(err, Error::Config { .. }));
+        assert!(err.to_string().contains("permissions.deny_change"));
+
+        let err = Manifest::parse("[repo]\nname = \"x\"\n\n[review]\nrequire_human = [\"[a\"]\n")
+            .unwrap_err();
+        assert!(err.to_string().contains("review.require_human"));
+    }
+
+    #[test]
+    fn invalid_restricting_patterns_fail_closed() {
+        // Built directly, past the load-time check
+        let permissions = Permissions {
+            allow_change: vec!["src/{a".into()],
+            deny_change: vec!["secrets/{a".into()],
+            deny_push: vec!["release/{a".into()],
+            ..Default::default()
+        };
+        assert!(!permissions.can_change("src/lib.rs"));
+        assert!(!permissions.can_change("README.md"));
+        assert!(!permissions.can_push("feature"));
+
+        let mut manifest = Manifest::parse("[repo]\nname = \"x\"\n").unwrap();
+        manifest.review.require_human = vec!["billing/{a".into()];
+        assert!(manifest.requires_human_review("src/lib.rs"));
+    }
+
     #[test]
     fn minimal_manifest() {
         let minimal = r#"
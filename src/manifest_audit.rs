@@ -0,0 +1,729 @@
+// ABOUTME: `manifest audit`: every manifest entry checked against the repository as it is now
+// ABOUTME: Missing commands and scripts, globs matching nothing, moved paths, absent languages, with patches
+
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+use crate::globs::Globs;
+use crate::manifest::{HookEvent, Manifest};
+use crate::symbols::SupportedLanguage;
+
+/// How much a finding matters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The entry cannot work: a command or file it runs is gone
+    Error,
+    /// The entry does nothing: a pattern or path matches no file
+    Warn,
+    /// Worth updating, harmless as is
+    Info,
+}
+
+/// What is wrong with an entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FindingKind {
+    /// The program a command runs is neither in the repository nor on `PATH`
+    MissingCommand,
+    /// A script or file a command passes as an argument does not exist
+    MissingScript,
+    /// A path pattern matches no file
+    NoMatch,
+    /// A path or pattern matches nothing, but the same name exists elsewhere
+    Moved,
+    /// An interface or entry point names a file that does not exist
+    MissingPath,
+    /// `repo.languages` lists a language with no files
+    MissingLanguage,
+    /// Files of a supported language exist but `repo.languages` omits it
+    UnlistedLanguage,
+}
+
+/// An edit to the manifest that resolves a finding
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Patch {
+    /// "remove", "replace", or "add"
+    pub op: &'static str,
+    /// Dotted manifest key: a list (`permissions.deny_change`) or a table
+    /// entry (`invariants.lint`)
+    pub key: String,
+    /// The list item or value as it is now (absent for "add")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    /// What to put instead ("replace") or append ("add")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replacement: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    pub kind: FindingKind,
+    pub severity: Severity,
+    /// Dotted manifest key of the entry (`invariants.lint`, `review.require_human`)
+    pub entry: String,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub patch: Option<Patch>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    /// No errors or warnings (info findings allowed)
+    pub clean: bool,
+    /// Findings per severity
+    pub counts: BTreeMap<Severity, usize>,
+    pub findings: Vec<Finding>,
+    /// Every suggested patch, in finding order
+    pub patches: Vec<Patch>,
+}
+
+/// Shell builtins and keywords a command may start with
+const BUILTINS: &[&str] = &[
+    "cd", "echo", "test", "[", "true", "false", "exit", "set", "export", "source", ".", "if",
+    "then", "else", "fi", "for", "do", "done", "while", "case", "esac", "printf", "read", "eval",
+    "exec", "command", "type", "unset", "shift", "trap", "wait", ":",
+];
+
+/// Programs that run the command after them
+const WRAPPERS: &[&str] = &["env", "exec", "time", "nice", "nohup", "command"];
+
+/// Interface schema files named without a directory
+const SCHEMA_EXTENSIONS: &[&str] = &["json", "proto", "graphql", "gql", "toml", "thrift", "avsc"];
+
+/// Directories whose files say nothing about the languages of the project
+const VENDORED: &[&str] = &[
+    "target",
+    "node_modules",
+    "vendor",
+    "venv",
+    ".venv",
+    "__pycache__",
+    "dist",
+    "build",
+    ".agent",
+];
+
+/// Check `manifest` against the repository at `root`, whose files (repo
+/// relative, slash-separated) are `files`. Programs are looked up in
+/// `path_var` (the `PATH` value).
+pub fn audit(manifest: &Manifest, root: &Path, files: &[String], path_var: Option<&str>) -> Report {
+    let repo = RepoFiles::new(files);
+    let mut findings = Vec::new();
+
+    let mut commands: Vec<(String, &str)> = manifest
+        .invariants
+        .iter()
+        .map(|(name, inv)| (format!("invariants.{}", name), inv.command()))
+        .collect();
+    commands.extend(
+        manifest
+            .fixers
+            .iter()
+            .map(|(name, fixer)| (format!("fixers.{}.cmd", name), fixer.cmd.as_str())),
+    );
+    for event in HookEvent::ALL {
+        for cmd in manifest.hooks.for_event(event) {
+            commands.push((format!("hooks.{}", event.as_str()), cmd));
+        }
+    }
+    commands.sort();
+    for (entry, cmd) in commands {
+        check_command(&entry, cmd, root, &repo, path_var, &mut findings);
+    }
+
+    let p = &manifest.permissions;
+    let mut globs: Vec<(&str, &[String])> = vec![
+        ("permissions.allow_change", &p.allow_change),
+        ("permissions.deny_change", &p.deny_change),
+        ("permissions.ask_change", &p.ask_change),
+        (
+            "permissions.allow_destructive_schema",
+            &p.allow_destructive_schema,
+        ),
+        ("review.require_human", &manifest.review.require_human),
+    ];
+    let fixer_keys: BTreeMap<String, &[String]> = manifest
+        .fixers
+        .iter()
+        .map(|(name, fixer)| (format!("fixers.{}.files", name), fixer.files.as_slice()))
+        .collect();
+    globs.extend(fixer_keys.iter().map(|(k, v)| (k.as_str(), *v)));
+    for (key, patterns) in globs {
+        for pattern in patterns {
+            check_pattern(key, pattern, &repo, &mut findings);
+        }
+    }
+
+    for (table, values) in [
+        ("interfaces", &manifest.interfaces),
+        ("entry_points", &manifest.entry_points),
+    ] {
+        let sorted: BTreeMap<_, _> = values.iter().collect();
+        for (name, value) in sorted {
+            check_location(&format!("{}.{}", table, name), value, &repo, &mut findings);
+        }
+    }
+
+    check_languages(&manifest.repo.languages, files, &mut findings);
+
+    findings.sort_by_key(|f| f.severity);
+    let mut counts = BTreeMap::new();
+    for finding in &findings {
+        *counts.entry(finding.severity).or_insert(0) += 1;
+    }
+    Report {
+        clean: findings.iter().all(|f| f.severity == Severity::Info),
+        counts,
+        patches: findings.iter().filter_map(|f| f.patch.clone()).collect(),
+        findings,
+    }
+}
+
+/// Files and the directories they are in, for existence checks
+struct RepoFiles<'a> {
+    files: &'a [String],
+    set: BTreeSet<&'a str>,
+    dirs: BTreeSet<String>,
+}
+
+impl<'a> RepoFiles<'a> {
+    fn new(files: &'a [String]) -> Self {
+        let mut dirs = BTreeSet::new();
+        for file in files {
+            let mut dir = file.as_str();
+            while let Some((parent, _)) = dir.rsplit_once('/') {
+                if !dirs.insert(parent.to_string()) {
+                    break;
+                }
+                dir = parent;
+            }
+        }
+        Self {
+            files,
+            set: files.iter().map(String::as_str).collect(),
+            dirs,
+        }
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        let path = path.trim_start_matches("./").trim_end_matches('/');
+        path.is_empty() || self.set.contains(path) || self.dirs.contains(path)
+    }
+
+    /// The one file elsewhere with the same name as missing `path`, if
+    /// exactly one has it
+    fn moved_file(&self, path: &str) -> Option<&'a str> {
+        let name = path.rsplit('/').next()?;
+        let mut candidates = self
+            .files
+            .iter()
+            .filter(|f| f.rsplit('/').next() == Some(name));
+        let found = candidates.next()?;
+        candidates.next().is_none().then_some(found.as_str())
+    }
+
+    /// The one directory elsewhere named like missing directory `dir`
+    fn moved_dir(&self, dir: &str) -> Option<&str> {
+        let name = dir.rsplit('/').next()?;
+        let mut candidates = self
+            .dirs
+            .iter()
+            .filter(|d| d.rsplit('/').next() == Some(name) && d.as_str() != dir);
+        let found = candidates.next()?;
+        candidates.next().is_none().then_some(found.as_str())
+    }
+}
+
+/// Split a command into simple commands at `&&`, `||`, `;`, and `|`
+fn simple_commands(cmd: &str) -> Vec<Vec<String>> {
+    let mut commands = vec![Vec::new()];
+    for token in cmd.split_whitespace() {
+        let token = token.trim_matches(|c| c == '"' || c == '\'');
+        match token {
+            "&&" | "||" | ";" | "|" => commands.push(Vec::new()),
+            _ => {
+                let (word, ends) = match token.strip_suffix(';') {
+                    Some(word) => (word, true),
+                    None => (token, false),
+                };
+                if !word.is_empty() {
+                    commands.last_mut().unwrap().push(word.to_string());
+                }
+                if ends {
+                    commands.push(Vec::new());
+                }
+            }
+        }
+    }
+    commands.retain(|c| !c.is_empty());
+    commands
+}
+
+/// Whether an argument names a file in the repository that must exist:
+/// relative, with a directory and an extension, and no shell syntax
+fn is_file_argument(arg: &str) -> bool {
+    let last = arg.rsplit('/').next().unwrap_or(arg);
+    arg.contains('/')
+        && !arg.starts_with('/')
+        && !arg.starts_with('-')
+        && !arg.starts_with('~')
+        && !arg.contains("://")
+        && !arg.contains(['=', '$', '{', '*', '?', '[', '<', '>', '`'])
+        && last.contains('.')
+        && !last.ends_with('.')
+}
+
+fn check_command(
+    entry: &str,
+    cmd: &str,
+    root: &Path,
+    repo: &RepoFiles,
+    path_var: Option<&str>,
+    findings: &mut Vec<Finding>,
+) {
+    // `cd dir && ./run.sh` runs ./run.sh in dir
+    let mut cwd = String::new();
+    for words in simple_commands(cmd) {
+        let mut words = words
+            .iter()
+            .skip_while(|w| w.contains('=') && !w.starts_with('-'))
+            .skip_while(|w| WRAPPERS.contains(&w.as_str()))
+            .skip_while(|w| w.contains('=') && !w.starts_with('-'));
+        let Some(program) = words.next() else {
+            continue;
+        };
+        if program == "cd" {
+            if let Some(dir) = words.next() {
+                cwd = join(&cwd, dir);
+            }
+            continue;
+        }
+        let in_repo = |path: &str| join(&cwd, path);
+
+        if program.contains('$') || program.contains('{') || BUILTINS.contains(&program.as_str()) {
+            // Nothing to check
+        } else if program.contains('/') {
+            let path = in_repo(program);
+            if !program.starts_with('/') && !repo.exists(&path) && !root.join(&path).exists() {
+                findings.push(missing_file(entry, cmd, program, &path, repo, true));
+            }
+        } else if path_var
+            .and_then(|p| crate::capabilities::find_on_path(program, p))
+            .is_none()
+            && !repo.exists(&in_repo(program))
+        {
+            findings.push(Finding {
+                kind: FindingKind::MissingCommand,
+                severity: Severity::Error,
+                entry: entry.to_string(),
+                message: format!("`{}` runs `{}`, which is not on PATH", cmd, program),
+                patch: None,
+            });
+        }
+
+        for arg in words.filter(|w| is_file_argument(w)) {
+            let path = in_repo(arg);
+            if !repo.exists(&path) && !root.join(&path).exists() {
+                findings.push(missing_file(entry, cmd, arg, &path, repo, false));
+            }
+        }
+    }
+}
+
+/// `dir/path`, with `./` and `..` resolved as far as they go
+fn join(dir: &str, path: &str) -> String {
+    let mut parts: Vec<&str> = dir.split('/').filter(|p| !p.is_empty()).collect();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+fn missing_file(
+    entry: &str,
+    cmd: &str,
+    written: &str,
+    path: &str,
+    repo: &RepoFiles,
+    is_program: bool,
+) -> Finding {
+    let what = if is_program { "runs" } else { "uses" };
+    // Only offer the new path when `written` is relative to the root, not
+    // to a directory the command changed into
+    let rooted = path == written.trim_start_matches("./");
+    match repo.moved_file(path) {
+        Some(found) => Finding {
+            kind: FindingKind::Moved,
+            severity: Severity::Error,
+            entry: entry.to_string(),
+            message: format!(
+                "`{}` {} {}, which is gone; {} looks like it moved there",
+                cmd, what, written, found
+            ),
+            patch: rooted.then(|| {
+                let found = if written.starts_with("./") {
+                    format!("./{}", found)
+                } else {
+                    found.to_string()
+                };
+                Patch {
+                    op: "replace",
+                    key: entry.to_string(),
+                    value: Some(cmd.to_string()),
+                    replacement: Some(cmd.replace(written, &found)),
+                }
+            }),
+        },
+        None => Finding {
+            kind: FindingKind::MissingScript,
+            severity: Severity::Error,
+            entry: entry.to_string(),
+            message: format!("`{}` {} {}, which does not exist", cmd, what, written),
+            patch: entry.starts_with("invariants.").then(|| Patch {
+                op: "remove",
+                key: entry.to_string(),
+                value: Some(cmd.to_string()),
+                replacement: None,
+            }),
+        },
+    }
+}
+
+fn check_pattern(key: &str, pattern: &str, repo: &RepoFiles, findings: &mut Vec<Finding>) {
+    if pattern.starts_with('!') {
+        return;
+    }
+    let Ok(globs) = Globs::new([pattern]) else {
+        return;
+    };
+    if repo.files.iter().any(|f| globs.is_match(f)) {
+        return;
+    }
+    // A literal directory the pattern is under, that moved as a whole
+    let literal: Vec<&str> = pattern
+        .trim_start_matches('/')
+        .split('/')
+        .take_while(|s| !s.contains(['*', '?', '[', '{', '\\']))
+        .collect();
+    let is_literal = literal.len() == pattern.trim_start_matches('/').split('/').count();
+    let moved = if is_literal {
+        repo.moved_file(pattern.trim_start_matches('/'))
+            .map(|found| found.to_string())
+            .or_else(|| {
+                repo.moved_dir(pattern.trim_matches('/'))
+                    .map(|d| format!("{}/", d))
+            })
+    } else {
+        (1..=literal.len()).rev().find_map(|n| {
+            let dir = literal[..n].join("/");
+            if repo.exists(&dir) {
+                return None;
+            }
+            let found = repo.moved_dir(&dir)?;
+            let moved = format!("{}{}", found, &pattern.trim_start_matches('/')[dir.len()..]);
+            let globs = Globs::new([&moved]).ok()?;
+            repo.files
+                .iter()
+                .any(|f| globs.is_match(f))
+                .then_some(moved)
+        })
+    };
+    findings.push(match moved {
+        Some(moved) => Finding {
+            kind: FindingKind::Moved,
+            severity: Severity::Warn,
+            entry: key.to_string(),
+            message: format!(
+                "{} matches no file; the files look like they moved to {}",
+                pattern, moved
+            ),
+            patch: Some(Patch {
+                op: "replace",
+                key: key.to_string(),
+                value: Some(pattern.to_string()),
+                replacement: Some(moved),
+            }),
+        },
+        None => Finding {
+            kind: FindingKind::NoMatch,
+            severity: Severity::Warn,
+            entry: key.to_string(),
+            message: format!("{} matches no file", pattern),
+            patch: Some(Patch {
+                op: "remove",
+                key: key.to_string(),
+                value: Some(pattern.to_string()),
+                replacement: None,
+            }),
+        },
+    });
+}
+
+/// `path` or `path:symbol` from `[interfaces]` / `[entry_points]`
+fn check_location(entry: &str, value: &str, repo: &RepoFiles, findings: &mut Vec<Finding>) {
+    let (path, symbol) = match value.split_once(':') {
+        Some((path, symbol)) => (path, Some(symbol)),
+        None => (value, None),
+    };
+    // Module paths (`app.main:run`) and commands are not files
+    let ext = path.rsplit_once('.').map(|(_, ext)| ext).unwrap_or("");
+    let file_like = path.contains('/')
+        || SupportedLanguage::from_path(Path::new(path)).is_some()
+        || SCHEMA_EXTENSIONS.contains(&ext);
+    if !file_like || path.contains(' ') || repo.exists(path) {
+        return;
+    }
+    let with_symbol = |p: &str| match symbol {
+        Some(symbol) => format!("{}:{}", p, symbol),
+        None => p.to_string(),
+    };
+    let moved = repo.moved_file(path);
+    findings.push(Finding {
+        kind: if moved.is_some() {
+            FindingKind::Moved
+        } else {
+            FindingKind::MissingPath
+        },
+        severity: Severity::Warn,
+        entry: entry.to_string(),
+        message: match moved {
+            Some(found) => format!(
+                "{} does not exist; it looks like it moved to {}",
+                path, found
+            ),
+            None => format!("{} does not exist", path),
+        },
+        patch: Some(Patch {
+            op: if moved.is_some() { "replace" } else { "remove" },
+            key: entry.to_string(),
+            value: Some(value.to_string()),
+            replacement: moved.map(with_symbol),
+        }),
+    });
+}
+
+fn check_languages(listed: &[String], files: &[String], findings: &mut Vec<Finding>) {
+    let mut present: BTreeMap<&'static str, usize> = BTreeMap::new();
+    let mut extensions: BTreeSet<String> = BTreeSet::new();
+    for file in files {
+        if file.split('/').any(|part| VENDORED.contains(&part)) {
+            continue;
+        }
+        if let Some(language) = SupportedLanguage::from_path(Path::new(file)) {
+            *present.entry(language.name()).or_insert(0) += 1;
+        }
+        if let Some((_, ext)) = file.rsplit_once('.') {
+            extensions.insert(ext.to_lowercase());
+        }
+    }
+
+    let mut covered = BTreeSet::new();
+    for name in listed {
+        let found = match SupportedLanguage::from_name(name) {
+            Some(language) => {
+                covered.insert(language.name());
+                present.contains_key(language.name())
+            }
+            // Languages without symbol support: look for their extension
+            None => extensions.contains(&name.to_lowercase()),
+        };
+        if !found {
+            findings.push(Finding {
+                kind: FindingKind::MissingLanguage,
+                severity: Severity::Warn,
+                entry: "repo.languages".to_string(),
+                message: format!("{} is listed but no {} files exist", name, name),
+                patch: Some(Patch {
+                    op: "remove",
+                    key: "repo.languages".to_string(),
+                    value: Some(name.clone()),
+                    replacement: None,
+                }),
+            });
+        }
+    }
+    // Only when languages are listed at all; an empty list is a choice
+    if listed.is_empty() {
+        return;
+    }
+    for (language, count) in present {
+        // Docs and config alone do not make a project language
+        if covered.contains(language) || matches!(language, "markdown" | "yaml" | "dockerfile") {
+            continue;
+        }
+        findings.push(Finding {
+            kind: FindingKind::UnlistedLanguage,
+            severity: Severity::Info,
+            entry: "repo.languages".to_string(),
+            message: format!(
+                "{} {} file(s) exist but {} is not listed",
+                count, language, language
+            ),
+            patch: Some(Patch {
+                op: "add",
+                key: "repo.languages".to_string(),
+                value: None,
+                replacement: Some(language.to_string()),
+            }),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_drift_and_suggests_patches() {
+        let tmp = tempfile::tempdir().unwrap();
+        let files: Vec<String> = [
+            "Cargo.toml",
+            "src/main.rs",
+            "tools/ci/check.sh",
+            "services/billing/invoice.rs",
+            "api/openapi.yaml",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        let manifest = Manifest::parse(
+            r#"
+[repo]
+name = "x"
+languages = ["rust", "python", "go"]
+
+[interfaces]
+http = "openapi.yaml"
+rpc = "proto/service.proto"
+
+[invariants]
+build = "cargo build"
+check = "bash scripts/check.sh --strict"
+gone = "definitely-not-a-real-tool-xyz --all"
+nested = "cd src && ../tools/ci/check.sh"
+
+[permissions]
+deny_change = ["billing/**", "secrets/**", "*.rs"]
+
+[review]
+require_human = ["src/main.rs", "docs/**"]
+"#,
+        )
+        .unwrap();
+        let bin = tmp.path().join("bin");
+        std::fs::create_dir_all(&bin).unwrap();
+        for tool in ["cargo", "bash"] {
+            std::fs::write(bin.join(tool), "").unwrap();
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                std::fs::set_permissions(bin.join(tool), std::fs::Permissions::from_mode(0o755))
+                    .unwrap();
+            }
+        }
+
+        let report = audit(&manifest, tmp.path(), &files, bin.to_str());
+        let by_entry = |entry: &str| -> Vec<&Finding> {
+            report
+                .findings
+                .iter()
+                .filter(|f| f.entry == entry)
+                .collect()
+        };
+        assert!(!report.clean);
+        assert!(by_entry("invariants.build").is_empty());
+        assert!(by_entry("invariants.nested").is_empty());
+
+        let check = by_entry("invariants.check");
+        assert_eq!(check[0].kind, FindingKind::Moved);
+        assert_eq!(
+            check[0].patch.as_ref().unwrap().replacement.as_deref(),
+            Some("bash tools/ci/check.sh --strict")
+        );
+        assert_eq!(
+            by_entry("invariants.gone")[0].kind,
+            FindingKind::MissingCommand
+        );
+
+        let deny = by_entry("permissions.deny_change");
+        assert_eq!(deny.len(), 2);
+        assert_eq!(deny[0].kind, FindingKind::Moved);
+        assert_eq!(
+            deny[0].patch.as_ref().unwrap().replacement.as_deref(),
+            Some("services/billing/**")
+        );
+        assert_eq!(deny[1].kind, FindingKind::NoMatch);
+        assert_eq!(deny[1].patch.as_ref().unwrap().op, "remove");
+        let review = by_entry("review.require_human");
+        assert_eq!(review.len(), 1);
+        assert_eq!(
+            review[0].patch.as_ref().unwrap().value.as_deref(),
+            Some("docs/**")
+        );
+
+        let interfaces: Vec<_> = report
+            .findings
+            .iter()
+            .filter(|f| f.entry.starts_with("interfaces."))
+            .collect();
+        assert_eq!(interfaces.len(), 2);
+        assert_eq!(
+            by_entry("interfaces.http")[0]
+                .patch
+                .as_ref()
+                .unwrap()
+                .replacement
+                .as_deref(),
+            Some("api/openapi.yaml")
+        );
+        assert_eq!(by_entry("interfaces.rpc")[0].kind, FindingKind::MissingPath);
+
+        let languages: Vec<_> = by_entry("repo.languages")
+            .iter()
+            .map(|f| (f.kind, f.patch.as_ref().unwrap()))
+            .map(|(kind, p)| (kind, p.value.clone().or(p.replacement.clone()).unwrap()))
+            .collect();
+        assert_eq!(
+            languages,
+            vec![
+                (FindingKind::MissingLanguage, "python".to_string()),
+                (FindingKind::MissingLanguage, "go".to_string()),
+                (FindingKind::UnlistedLanguage, "shell".to_string()),
+            ]
+        );
+        // Sorted by severity, every patch collected
+        assert_eq!(report.findings[0].severity, Severity::Error);
+        assert_eq!(report.counts[&Severity::Error], 2);
+        assert_eq!(
+            report.patches.len(),
+            report.findings.iter().filter(|f| f.patch.is_some()).count()
+        );
+    }
+
+    #[test]
+    fn splits_commands_and_spots_file_arguments() {
+        assert_eq!(
+            simple_commands("A=1 make test && ./run.sh; echo done | tee 'out.log'"),
+            vec![
+                vec!["A=1", "make", "test"],
+                vec!["./run.sh"],
+                vec!["echo", "done"],
+                vec!["tee", "out.log"]
+            ]
+        );
+        assert!(is_file_argument("scripts/check.py"));
+        assert!(!is_file_argument("--manifest-path=sub/Cargo.toml"));
+        assert!(!is_file_argument("https://example.com/x.sh"));
+        assert!(!is_file_argument("src/"));
+        assert!(!is_file_argument("{files}"));
+        assert_eq!(join("src", "../tools/x.sh"), "tools/x.sh");
+    }
+}
@@ -0,0 +1,297 @@
+// ABOUTME: Merge queues (GitHub merge queue, bors): detection, forge queries, and PR queue status
+// ABOUTME: `push --pr --enqueue` adds a PR to the queue, `pr status` reports its position and merge outlook
+
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// Which queue merges into a branch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueueKind {
+    /// GitHub's merge queue, enabled by a branch ruleset
+    Github,
+    /// bors (bors-ng, homu), driven by `bors r+` comments
+    Bors,
+}
+
+impl QueueKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QueueKind::Github => "github",
+            QueueKind::Bors => "bors",
+        }
+    }
+}
+
+/// A merge queue guarding a branch
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MergeQueue {
+    pub kind: QueueKind,
+    pub branch: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// PRs in the queue now (GitHub only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub length: Option<u64>,
+}
+
+/// The bors configuration file, when the repository has one
+pub fn bors_config(repo_root: &Path) -> Option<PathBuf> {
+    ["bors.toml", ".github/bors.toml"]
+        .into_iter()
+        .map(|path| repo_root.join(path))
+        .find(|path| path.is_file())
+}
+
+/// The comment that puts a PR in the bors queue
+pub const BORS_ENQUEUE: &str = "bors r+";
+
+/// `gh api graphql` query for a branch's merge queue; variables `owner`,
+/// `name`, `branch`
+pub const QUEUE_QUERY: &str = "query($owner: String!, $name: String!, $branch: String!) {
+  repository(owner: $owner, name: $name) {
+    mergeQueue(branch: $branch) { url entries { totalCount } }
+  }
+}";
+
+/// `gh api graphql` query for the newest PR from a branch; variables
+/// `owner`, `name`, `branch`
+pub const PR_QUERY: &str = "query($owner: String!, $name: String!, $branch: String!) {
+  repository(owner: $owner, name: $name) {
+    pullRequests(headRefName: $branch, first: 1, orderBy: {field: CREATED_AT, direction: DESC}) {
+      nodes {
+        number url state isDraft baseRefName mergeStateStatus
+        mergeQueueEntry { position state estimatedTimeToMerge enqueuedAt }
+      }
+    }
+  }
+}";
+
+/// `gh api graphql` mutation adding a PR to its base branch's queue;
+/// variable `id` (the PR's node ID)
+pub const ENQUEUE_MUTATION: &str = "mutation($id: ID!) {
+  enqueuePullRequest(input: {pullRequestId: $id}) {
+    mergeQueueEntry { position state estimatedTimeToMerge enqueuedAt }
+  }
+}";
+
+/// The queue in a `QUEUE_QUERY` response (None: the branch has no queue)
+pub fn parse_queue(response: &serde_json::Value, branch: &str) -> Option<MergeQueue> {
+    let queue = &response["data"]["repository"]["mergeQueue"];
+    if !queue.is_object() {
+        return None;
+    }
+    Some(MergeQueue {
+        kind: QueueKind::Github,
+        branch: branch.to_string(),
+        url: queue["url"].as_str().map(str::to_string),
+        length: queue["entries"]["totalCount"].as_u64(),
+    })
+}
+
+/// A pull request and where it stands in its base branch's queue
+#[derive(Debug, Clone, Serialize)]
+pub struct PrStatus {
+    pub number: u64,
+    pub url: String,
+    /// "open", "merged", or "closed"
+    pub state: String,
+    pub draft: bool,
+    pub branch: String,
+    pub base: String,
+    /// GitHub's merge state: "clean", "blocked", "behind", "dirty", ...
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub merge_state: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub merge_queue: Option<MergeQueue>,
+    /// Whether the PR is in the queue
+    pub queued: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub queue_entry: Option<QueueEntry>,
+    /// What happens next: "merged", "closed", "will_merge", "awaiting_checks",
+    /// "queued", "will_be_removed", "queue_blocked", "needs_enqueue",
+    /// "ready", "behind", "conflicts", "blocked", or "unknown"
+    pub expected: &'static str,
+}
+
+/// A PR's place in a GitHub merge queue
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct QueueEntry {
+    /// 1 is the head of the queue
+    pub position: u64,
+    /// "queued", "awaiting_checks", "mergeable", "unmergeable", or "locked"
+    pub state: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub estimated_time_to_merge_secs: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enqueued_at: Option<String>,
+}
+
+/// A `mergeQueueEntry` object (None when null)
+pub fn parse_entry(entry: &serde_json::Value) -> Option<QueueEntry> {
+    Some(QueueEntry {
+        position: entry["position"].as_u64()?,
+        state: entry["state"].as_str()?.to_ascii_lowercase(),
+        estimated_time_to_merge_secs: entry["estimatedTimeToMerge"].as_u64(),
+        enqueued_at: entry["enqueuedAt"].as_str().map(str::to_string),
+    })
+}
+
+impl PrStatus {
+    /// The PR in a `PR_QUERY` response (None: the branch has no PR).
+    /// `queue` is its base branch's queue; `bors_queued` whether the PR's
+    /// comments leave it approved for bors.
+    pub fn from_response(
+        response: &serde_json::Value,
+        branch: &str,
+        queue: Option<MergeQueue>,
+        bors_queued: bool,
+    ) -> Option<Self> {
+        let pr = &response["data"]["repository"]["pullRequests"]["nodes"][0];
+        let number = pr["number"].as_u64()?;
+        let state = pr["state"].as_str().unwrap_or("OPEN").to_ascii_lowercase();
+        let queue_entry = parse_entry(&pr["mergeQueueEntry"]);
+        let queued = match queue.as_ref().map(|q| q.kind) {
+            Some(QueueKind::Bors) => bors_queued && state == "open",
+            _ => queue_entry.is_some(),
+        };
+        let mut status = Self {
+            number,
+            url: pr["url"].as_str().unwrap_or_default().to_string(),
+            state,
+            draft: pr["isDraft"].as_bool().unwrap_or(false),
+            branch: branch.to_string(),
+            base: pr["baseRefName"].as_str().unwrap_or_default().to_string(),
+            merge_state: pr["mergeStateStatus"].as_str().map(str::to_ascii_lowercase),
+            merge_queue: queue,
+            queued,
+            queue_entry,
+            expected: "unknown",
+        };
+        status.expected = status.outlook();
+        Some(status)
+    }
+
+    fn outlook(&self) -> &'static str {
+        match self.state.as_str() {
+            "merged" => return "merged",
+            "closed" => return "closed",
+            _ => {}
+        }
+        if let Some(entry) = &self.queue_entry {
+            return match entry.state.as_str() {
+                "mergeable" => "will_merge",
+                "awaiting_checks" => "awaiting_checks",
+                "unmergeable" => "will_be_removed",
+                "locked" => "queue_blocked",
+                _ => "queued",
+            };
+        }
+        if self.queued {
+            return "queued";
+        }
+        if self.merge_queue.is_some() {
+            return "needs_enqueue";
+        }
+        match self.merge_state.as_deref() {
+            Some("clean" | "has_hooks" | "unstable") => "ready",
+            Some("behind") => "behind",
+            Some("dirty") => "conflicts",
+            Some("blocked" | "draft") => "blocked",
+            _ => "unknown",
+        }
+    }
+}
+
+/// Whether the last bors command among a PR's comments approves it
+/// (`bors r+`, `bors merge`) rather than withdrawing it (`bors r-`,
+/// `bors merge-`, `bors cancel`)
+pub fn bors_queued<'a>(comments: impl IntoIterator<Item = &'a str>) -> bool {
+    let mut queued = false;
+    for line in comments.into_iter().flat_map(str::lines) {
+        let mut words = line.split_whitespace();
+        if !words
+            .next()
+            .is_some_and(|w| w.eq_ignore_ascii_case("bors") || w.eq_ignore_ascii_case("@bors"))
+        {
+            continue;
+        }
+        match words.next() {
+            Some("r+" | "merge") => queued = true,
+            Some(command) if command.starts_with("r=") => queued = true,
+            Some("r-" | "merge-" | "cancel") => queued = false,
+            _ => {}
+        }
+    }
+    queued
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn pr(state: &str, entry: serde_json::Value, merge_state: &str) -> serde_json::Value {
+        json!({"data": {"repository": {"pullRequests": {"nodes": [{
+            "number": 42, "url": "https://github.com/o/r/pull/42",
+            "state": state, "isDraft": false, "baseRefName": "main",
+            "mergeStateStatus": merge_state, "mergeQueueEntry": entry,
+        }]}}}})
+    }
+
+    #[test]
+    fn reports_queue_position_and_outlook() {
+        let queue = parse_queue(
+            &json!({"data": {"repository": {"mergeQueue": {
+                "url": "https://github.com/o/r/queue/main", "entries": {"totalCount": 3}
+            }}}}),
+            "main",
+        );
+        assert_eq!(queue.as_ref().unwrap().length, Some(3));
+        assert!(parse_queue(
+            &json!({"data": {"repository": {"mergeQueue": null}}}),
+            "main"
+        )
+        .is_none());
+
+        let entry = json!({"position": 2, "state": "AWAITING_CHECKS", "estimatedTimeToMerge": 600});
+        let status =
+            PrStatus::from_response(&pr("OPEN", entry, "BLOCKED"), "fix", queue.clone(), false)
+                .unwrap();
+        assert!(status.queued);
+        assert_eq!(status.queue_entry.as_ref().unwrap().position, 2);
+        assert_eq!(status.expected, "awaiting_checks");
+
+        let waiting =
+            PrStatus::from_response(&pr("OPEN", json!(null), "CLEAN"), "fix", queue, false)
+                .unwrap();
+        assert_eq!(waiting.expected, "needs_enqueue");
+        let plain =
+            PrStatus::from_response(&pr("OPEN", json!(null), "DIRTY"), "fix", None, false).unwrap();
+        assert_eq!(plain.expected, "conflicts");
+        let merged =
+            PrStatus::from_response(&pr("MERGED", json!(null), "CLEAN"), "fix", None, false)
+                .unwrap();
+        assert_eq!(merged.expected, "merged");
+        assert!(PrStatus::from_response(&json!({}), "fix", None, false).is_none());
+    }
+
+    #[test]
+    fn bors_queue_follows_the_last_command() {
+        assert!(bors_queued(["LGTM", "bors r+"]));
+        assert!(bors_queued(["@bors r=alice"]));
+        assert!(!bors_queued(["bors r+", "oops\nbors r-"]));
+        assert!(!bors_queued(["bors try"]));
+
+        let bors = MergeQueue {
+            kind: QueueKind::Bors,
+            branch: "main".into(),
+            url: None,
+            length: None,
+        };
+        let status =
+            PrStatus::from_response(&pr("OPEN", json!(null), "CLEAN"), "fix", Some(bors), true)
+                .unwrap();
+        assert_eq!(status.expected, "queued");
+    }
+}
@@ -0,0 +1,286 @@
+// ABOUTME: Opt-in local usage metrics (.agent/metrics/usage.jsonl): per-command runs and outcomes
+// ABOUTME: Summarized by `stats --usage` and turned into hints that bias `suggest` after failures
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::error::{exit, Result};
+
+/// Events kept once the log is compacted
+const MAX_EVENTS: usize = 5000;
+
+/// Failures older than this don't influence `suggest`
+pub const RECENT_SECS: u64 = 24 * 60 * 60;
+
+/// One command invocation. Deliberately anonymous: no arguments, paths,
+/// messages, or session ids are recorded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageEvent {
+    /// Unix seconds
+    pub timestamp: u64,
+    /// Command path (e.g. "commit", "env promote")
+    pub command: String,
+    pub exit_code: i32,
+    /// Error type for failures (e.g. "stale_edit"), or the exit category
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl UsageEvent {
+    pub fn succeeded(&self) -> bool {
+        self.exit_code == exit::OK || self.exit_code == exit::WARNINGS
+    }
+}
+
+/// Failure label for an exit code when no error type is known
+pub fn exit_category(code: i32) -> &'static str {
+    match code {
+        exit::OK => "ok",
+        exit::WARNINGS => "warnings",
+        exit::PRECONDITION => "precondition_failed",
+        exit::CONFLICT => "conflict",
+        exit::PERMISSION => "permission_denied",
+        exit::INVARIANT => "invariant_failed",
+        exit::POLICY => "policy",
+        _ => "failure",
+    }
+}
+
+/// Runs and failures of one command
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandUsage {
+    pub command: String,
+    pub runs: usize,
+    pub failures: usize,
+    /// failures / runs
+    pub error_rate: f64,
+    /// Unix seconds of the latest run
+    pub last_used: u64,
+    /// Failure counts by error type
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub errors: BTreeMap<String, usize>,
+}
+
+/// A next step suggested by recent failures
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Hint {
+    pub action: String,
+    pub command: String,
+    pub reason: String,
+}
+
+/// The usage log of a repository
+pub struct UsageLog {
+    path: PathBuf,
+}
+
+impl UsageLog {
+    pub const DEFAULT_DIR: &'static str = ".agent/metrics";
+
+    pub fn open(repo_root: impl AsRef<Path>) -> Self {
+        Self {
+            path: repo_root
+                .as_ref()
+                .join(Self::DEFAULT_DIR)
+                .join("usage.jsonl"),
+        }
+    }
+
+    /// Append an event, compacting the log to the newest events once it
+    /// grows well past the limit
+    pub fn append(&self, event: &UsageEvent) -> Result<()> {
+        crate::agent_dir::append_json_line(&self.path, event)?;
+
+        // ~100 bytes per event; only count lines when it could be over
+        let size = std::fs::metadata(&self.path).map(|m| m.len()).unwrap_or(0);
+        if size > (MAX_EVENTS * 2 * 64) as u64 {
+            let events = self.events();
+            if events.len() > MAX_EVENTS * 2 {
+                let keep = &events[events.len() - MAX_EVENTS..];
+                let mut content = String::new();
+                for event in keep {
+                    content.push_str(&serde_json::to_string(event).unwrap_or_default());
+                    content.push('\n');
+                }
+                std::fs::write(&self.path, content)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// All recorded events, oldest first; unreadable lines are skipped
+    pub fn events(&self) -> Vec<UsageEvent> {
+        std::fs::read_to_string(&self.path)
+            .map(|content| {
+                content
+                    .lines()
+                    .filter_map(|l| serde_json::from_str(l).ok())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Per-command usage, most used first
+pub fn summarize(events: &[UsageEvent]) -> Vec<CommandUsage> {
+    let mut by_command: BTreeMap<&str, CommandUsage> = BTreeMap::new();
+    for event in events {
+        let usage = by_command
+            .entry(&event.command)
+            .or_insert_with(|| CommandUsage {
+                command: event.command.clone(),
+                runs: 0,
+                failures: 0,
+                error_rate: 0.0,
+                last_used: 0,
+                errors: BTreeMap::new(),
+            });
+        usage.runs += 1;
+        usage.last_used = usage.last_used.max(event.timestamp);
+        if !event.succeeded() {
+            usage.failures += 1;
+            let label = event
+                .error
+                .clone()
+                .unwrap_or_else(|| exit_category(event.exit_code).to_string());
+            *usage.errors.entry(label).or_default() += 1;
+        }
+    }
+    let mut usage: Vec<CommandUsage> = by_command
+        .into_values()
+        .map(|mut u| {
+            u.error_rate = u.failures as f64 / u.runs as f64;
+            u
+        })
+        .collect();
+    usage.sort_by(|a, b| b.runs.cmp(&a.runs).then(a.command.cmp(&b.command)));
+    usage
+}
+
+/// Next steps suggested by failures within `RECENT_SECS` of `now`
+pub fn hints(events: &[UsageEvent], now: u64) -> Vec<Hint> {
+    let recent: Vec<&UsageEvent> = events
+        .iter()
+        .filter(|e| !e.succeeded() && now.saturating_sub(e.timestamp) <= RECENT_SECS)
+        .collect();
+    let count = |pred: &dyn Fn(&UsageEvent) -> bool| recent.iter().filter(|e| pred(e)).count();
+    let is = |e: &UsageEvent, label: &str| {
+        e.error.as_deref() == Some(label) || exit_category(e.exit_code) == label
+    };
+
+    let mut hints = Vec::new();
+    let invariants = count(&|e| is(e, "invariant_failed"));
+    if invariants >= 2 {
+        hints.push(Hint {
+            action: "test_affected".into(),
+            command: "agentjj test run --affected-only".into(),
+            reason: format!(
+                "Invariants failed {} times recently - iterate on the impacted tests only",
+                invariants
+            ),
+        });
+    }
+    let test_runs = count(&|e| e.command == "test run");
+    if test_runs >= 2 {
+        hints.push(Hint {
+            action: "flaky".into(),
+            command: "agentjj test flaky".into(),
+            reason: format!(
+                "`test run` failed {} times recently - check whether these are known flakes",
+                test_runs
+            ),
+        });
+    }
+    let stale = count(&|e| e.error.as_deref() == Some("stale_edit"));
+    if stale >= 1 {
+        hints.push(Hint {
+            action: "reread".into(),
+            command: "agentjj read <path> --symbol <name>".into(),
+            reason: format!(
+                "{} edit(s) were rejected as stale recently - re-read files before editing them",
+                stale
+            ),
+        });
+    }
+    let conflicts = count(&|e| is(e, "conflict"));
+    if conflicts >= 1 {
+        hints.push(Hint {
+            action: "review_conflicts".into(),
+            command: "agentjj diff".into(),
+            reason: format!(
+                "{} command(s) hit conflicts recently - review the working copy before continuing",
+                conflicts
+            ),
+        });
+    }
+    let blocked =
+        count(&|e| is(e, "permission_denied") || e.error.as_deref() == Some("hook_failed"));
+    if blocked >= 2 {
+        hints.push(Hint {
+            action: "manifest".into(),
+            command: "agentjj manifest show".into(),
+            reason: format!(
+                "Permissions or hooks blocked {} commands recently - check what the manifest allows",
+                blocked
+            ),
+        });
+    }
+    hints
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn event(timestamp: u64, command: &str, exit_code: i32, error: Option<&str>) -> UsageEvent {
+        UsageEvent {
+            timestamp,
+            command: command.into(),
+            exit_code,
+            error: error.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn appends_and_summarizes() {
+        let tmp = TempDir::new().unwrap();
+        let log = UsageLog::open(tmp.path());
+        log.append(&event(10, "status", 0, None)).unwrap();
+        log.append(&event(20, "commit", 6, Some("invariant_failed")))
+            .unwrap();
+        log.append(&event(30, "commit", 0, None)).unwrap();
+        log.append(&event(40, "status", 2, None)).unwrap();
+
+        let usage = summarize(&log.events());
+        assert_eq!(usage[0].command, "commit");
+        assert_eq!(usage[0].failures, 1);
+        assert_eq!(usage[0].error_rate, 0.5);
+        assert_eq!(usage[0].errors["invariant_failed"], 1);
+        assert_eq!(usage[1].command, "status");
+        assert_eq!(usage[1].failures, 0);
+        assert_eq!(usage[1].last_used, 40);
+        assert!(tmp.path().join(".agent/metrics/.gitignore").exists());
+    }
+
+    #[test]
+    fn recent_failures_become_hints() {
+        let now = 100_000;
+        let events = vec![
+            event(
+                now - 60,
+                "commit",
+                exit::INVARIANT,
+                Some("invariant_failed"),
+            ),
+            event(now - 30, "test run", exit::INVARIANT, None),
+            event(now - 10, "edit", exit::PRECONDITION, Some("stale_edit")),
+            // Too old to count
+            event(now - RECENT_SECS - 1, "test run", exit::INVARIANT, None),
+        ];
+        let actions: Vec<String> = hints(&events, now).into_iter().map(|h| h.action).collect();
+        assert_eq!(actions, vec!["test_affected", "reread"]);
+        assert!(hints(&events[..1], now).is_empty());
+    }
+}
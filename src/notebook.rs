@@ -0,0 +1,335 @@
+// ABOUTME: Jupyter notebook (.ipynb) parsing, rendering as annotated source, and cell diffs
+// ABOUTME: Lets read/symbol/diff work on cell contents instead of the raw nbformat JSON
+
+use serde::Serialize;
+
+use crate::error::{Error, Result};
+use crate::symbols::SupportedLanguage;
+
+/// One notebook cell
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cell {
+    /// `code`, `markdown`, or `raw`
+    pub cell_type: String,
+    pub source: String,
+    /// nbformat 4.5+ cell id
+    pub id: Option<String>,
+    pub execution_count: Option<u64>,
+}
+
+/// A parsed notebook: its kernel language and cells
+#[derive(Debug, Clone)]
+pub struct Notebook {
+    /// Kernel language (e.g. `python`), if the metadata names one
+    pub language: Option<String>,
+    pub cells: Vec<Cell>,
+}
+
+/// nbformat stores text either as a string or as a list of lines
+fn text(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Array(lines) => lines.iter().filter_map(|l| l.as_str()).collect(),
+        _ => String::new(),
+    }
+}
+
+impl Notebook {
+    /// Parse nbformat 4 JSON
+    pub fn parse(json: &str) -> Result<Self> {
+        let value: serde_json::Value =
+            serde_json::from_str(json).map_err(|e| Error::Repository {
+                message: format!("invalid notebook JSON: {}", e),
+            })?;
+        let cells = value["cells"]
+            .as_array()
+            .ok_or_else(|| Error::Repository {
+                message: "notebook has no cells array".into(),
+            })?
+            .iter()
+            .map(|cell| Cell {
+                cell_type: cell["cell_type"].as_str().unwrap_or("code").to_string(),
+                source: text(&cell["source"]),
+                id: cell["id"].as_str().map(str::to_string),
+                execution_count: cell["execution_count"].as_u64(),
+            })
+            .collect();
+        let metadata = &value["metadata"];
+        let language = metadata["kernelspec"]["language"]
+            .as_str()
+            .or_else(|| metadata["language_info"]["name"].as_str())
+            .map(str::to_lowercase);
+        Ok(Self { language, cells })
+    }
+
+    /// Language of the code cells (Python unless the kernel says otherwise)
+    pub fn code_language(&self) -> Option<SupportedLanguage> {
+        match self.language.as_deref() {
+            None | Some("python") | Some("python3") => Some(SupportedLanguage::Python),
+            Some("c++") | Some("c++17") | Some("c++20") => Some(SupportedLanguage::Cpp),
+            Some("bash") | Some("sh") => Some(SupportedLanguage::Shell),
+            Some(other) => SupportedLanguage::from_extension(match other {
+                "rust" => "rs",
+                "javascript" => "js",
+                "typescript" => "ts",
+                "ruby" => "rb",
+                other => other,
+            }),
+        }
+    }
+
+    /// Line comment marker for the kernel language
+    fn comment(&self) -> &'static str {
+        match self.code_language() {
+            Some(
+                SupportedLanguage::Rust
+                | SupportedLanguage::JavaScript
+                | SupportedLanguage::TypeScript
+                | SupportedLanguage::C
+                | SupportedLanguage::Cpp
+                | SupportedLanguage::Php,
+            ) => "//",
+            Some(SupportedLanguage::Sql) => "--",
+            _ => "#",
+        }
+    }
+
+    /// Render as annotated source in the "percent" format: each cell starts
+    /// with a `# %% [type] cell N` marker, code cells are kept as-is, and
+    /// markdown/raw cells are commented out. For Python notebooks the result
+    /// is valid Python, so symbols can be extracted from it.
+    pub fn render(&self) -> String {
+        let comment = self.comment();
+        let mut out = String::new();
+        for (i, cell) in self.cells.iter().enumerate() {
+            if i > 0 {
+                out.push('\n');
+            }
+            out.push_str(&format!(
+                "{} %% [{}] cell {}",
+                comment,
+                cell.cell_type,
+                i + 1
+            ));
+            if let Some(count) = cell.execution_count {
+                out.push_str(&format!(" (In [{}])", count));
+            }
+            out.push('\n');
+            for line in cell.source.lines() {
+                if cell.cell_type == "code" {
+                    out.push_str(line);
+                } else if line.is_empty() {
+                    out.push_str(comment);
+                } else {
+                    out.push_str(&format!("{} {}", comment, line));
+                }
+                out.push('\n');
+            }
+        }
+        out
+    }
+}
+
+/// Render a notebook's JSON as annotated source
+pub fn render(json: &str) -> Result<String> {
+    Ok(Notebook::parse(json)?.render())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CellChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// A cell that differs between two versions of a notebook
+#[derive(Debug, Clone, Serialize)]
+pub struct CellChange {
+    /// 1-based cell number (in the new version, or the old one if removed)
+    pub cell: usize,
+    pub cell_type: String,
+    pub change: CellChangeKind,
+    /// Source lines prefixed with ` `, `-`, or `+`
+    pub diff: Vec<String>,
+}
+
+impl CellChange {
+    pub fn additions(&self) -> usize {
+        self.diff.iter().filter(|l| l.starts_with('+')).count()
+    }
+
+    pub fn deletions(&self) -> usize {
+        self.diff.iter().filter(|l| l.starts_with('-')).count()
+    }
+}
+
+/// Longest common subsequence of two sequences, as matched index pairs
+fn lcs<T>(a: &[T], b: &[T], eq: impl Fn(&T, &T) -> bool) -> Vec<(usize, usize)> {
+    let mut table = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            table[i][j] = if eq(&a[i], &b[j]) {
+                table[i + 1][j + 1] + 1
+            } else {
+                table[i + 1][j].max(table[i][j + 1])
+            };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut pairs = Vec::new();
+    while i < a.len() && j < b.len() {
+        if eq(&a[i], &b[j]) {
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+        } else if table[i + 1][j] >= table[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs
+}
+
+/// Line diff of two texts, each line prefixed with ` `, `-`, or `+`
+fn line_diff(before: &str, after: &str) -> Vec<String> {
+    let a: Vec<&str> = before.lines().collect();
+    let b: Vec<&str> = after.lines().collect();
+    let mut out = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    for (mi, mj) in lcs(&a, &b, |x, y| x == y)
+        .into_iter()
+        .chain([(a.len(), b.len())])
+    {
+        out.extend(a[i..mi].iter().map(|l| format!("-{}", l)));
+        out.extend(b[j..mj].iter().map(|l| format!("+{}", l)));
+        if mi < a.len() {
+            out.push(format!(" {}", a[mi]));
+        }
+        i = mi + 1;
+        j = mj + 1;
+    }
+    out
+}
+
+/// Cell-level changes between two versions of a notebook. Cells are matched
+/// by id when both versions have them, otherwise by identical content; an
+/// unmatched removed cell and added cell at the same position count as a
+/// modification.
+pub fn diff(before: Option<&Notebook>, after: Option<&Notebook>) -> Vec<CellChange> {
+    let empty = Vec::new();
+    let old = before.map(|n| &n.cells).unwrap_or(&empty);
+    let new = after.map(|n| &n.cells).unwrap_or(&empty);
+    let by_id = old.iter().chain(new).all(|c| c.id.is_some());
+    let same = |a: &Cell, b: &Cell| {
+        if by_id {
+            a.id == b.id
+        } else {
+            a.cell_type == b.cell_type && a.source == b.source
+        }
+    };
+
+    let mut changes = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    for (mi, mj) in lcs(old, new, same)
+        .into_iter()
+        .chain([(old.len(), new.len())])
+    {
+        let removed = &old[i..mi];
+        let added = &new[j..mj];
+        let paired = removed.len().min(added.len());
+        for k in 0..paired {
+            changes.push(CellChange {
+                cell: j + k + 1,
+                cell_type: added[k].cell_type.clone(),
+                change: CellChangeKind::Modified,
+                diff: line_diff(&removed[k].source, &added[k].source),
+            });
+        }
+        for (k, cell) in removed.iter().enumerate().skip(paired) {
+            changes.push(CellChange {
+                cell: i + k + 1,
+                cell_type: cell.cell_type.clone(),
+                change: CellChangeKind::Removed,
+                diff: cell.source.lines().map(|l| format!("-{}", l)).collect(),
+            });
+        }
+        for (k, cell) in added.iter().enumerate().skip(paired) {
+            changes.push(CellChange {
+                cell: j + k + 1,
+                cell_type: cell.cell_type.clone(),
+                change: CellChangeKind::Added,
+                diff: cell.source.lines().map(|l| format!("+{}", l)).collect(),
+            });
+        }
+        // Matched by id, but the content may still have changed
+        if let (Some(a), Some(b)) = (old.get(mi), new.get(mj)) {
+            if a.source != b.source {
+                changes.push(CellChange {
+                    cell: mj + 1,
+                    cell_type: b.cell_type.clone(),
+                    change: CellChangeKind::Modified,
+                    diff: line_diff(&a.source, &b.source),
+                });
+            }
+        }
+        i = mi + 1;
+        j = mj + 1;
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTEBOOK: &str = r##"{
+  "cells": [
+    {"cell_type": "markdown", "metadata": {}, "source": ["# Analysis\n", "\n", "Load the data."]},
+    {"cell_type": "code", "execution_count": 1, "metadata": {}, "outputs": [],
+     "source": ["import pandas as pd\n", "\n", "def load(path):\n", "    return pd.read_csv(path)"]},
+    {"cell_type": "code", "execution_count": 2, "metadata": {}, "outputs": [], "source": "df = load('x.csv')"}
+  ],
+  "metadata": {"kernelspec": {"language": "python", "name": "python3"}},
+  "nbformat": 4,
+  "nbformat_minor": 4
+}"##;
+
+    #[test]
+    fn renders_cells_as_annotated_python() {
+        let rendered = render(NOTEBOOK).unwrap();
+        assert_eq!(
+            rendered,
+            "# %% [markdown] cell 1\n# # Analysis\n#\n# Load the data.\n\n\
+             # %% [code] cell 2 (In [1])\nimport pandas as pd\n\ndef load(path):\n    return pd.read_csv(path)\n\n\
+             # %% [code] cell 3 (In [2])\ndf = load('x.csv')\n"
+        );
+
+        let symbols =
+            crate::symbols::extract_symbols(NOTEBOOK, SupportedLanguage::Notebook).unwrap();
+        let load = symbols.iter().find(|s| s.name == "load").unwrap();
+        assert_eq!((load.start_line, load.end_line), (9, 10));
+    }
+
+    #[test]
+    fn diff_reports_cell_changes() {
+        let before = Notebook::parse(NOTEBOOK).unwrap();
+        let mut after = before.clone();
+        after.cells[2].source = "df = load('y.csv')\ndf.head()".into();
+        after.cells.remove(0);
+
+        let changes = diff(Some(&before), Some(&after));
+        let summary: Vec<(usize, CellChangeKind)> =
+            changes.iter().map(|c| (c.cell, c.change)).collect();
+        assert_eq!(
+            summary,
+            vec![(1, CellChangeKind::Removed), (2, CellChangeKind::Modified)]
+        );
+        assert_eq!(
+            changes[1].diff,
+            vec!["-df = load('x.csv')", "+df = load('y.csv')", "+df.head()"]
+        );
+        assert_eq!((changes[1].additions(), changes[1].deletions()), (2, 1));
+    }
+}
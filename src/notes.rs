@@ -0,0 +1,270 @@
+// ABOUTME: Notes agents leave on files, lines, and symbols for later sessions (.agent/notes/<id>.json)
+// ABOUTME: Shown alongside the code they describe by context, read, and orient
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+
+/// A note attached to a file, one line of it, or a symbol in it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Note {
+    pub id: String,
+    pub file: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+    /// 1-based line
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    pub text: String,
+    pub author: String,
+    pub created_at: String,
+}
+
+/// What a note is attached to, as given to `note add --on`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    pub file: String,
+    pub symbol: Option<String>,
+    pub line: Option<usize>,
+}
+
+impl Target {
+    /// `src/api.rs::process` (symbol), `src/api.rs:42` (line), or `src/api.rs`
+    pub fn parse(on: &str) -> Result<Self> {
+        let on = on.trim().trim_start_matches("./");
+        let (file, symbol, line) = if let Some((file, symbol)) = on.split_once("::") {
+            (file, Some(symbol.to_string()), None)
+        } else if let Some((file, line)) = on
+            .rsplit_once(':')
+            .and_then(|(file, line)| Some((file, line.parse::<usize>().ok()?)))
+        {
+            if line == 0 {
+                return Err(Error::Config {
+                    message: format!("line numbers start at 1: {}", on),
+                });
+            }
+            (file, None, Some(line))
+        } else {
+            (on, None, None)
+        };
+        if file.is_empty() || symbol.as_deref() == Some("") {
+            return Err(Error::Config {
+                message: format!(
+                    "note target must be a file, file:line, or file::symbol, not '{}'",
+                    on
+                ),
+            });
+        }
+        Ok(Self {
+            file: file.to_string(),
+            symbol,
+            line,
+        })
+    }
+}
+
+impl Note {
+    /// A new note, with an ID derived from its contents
+    pub fn new(
+        target: Target,
+        text: impl Into<String>,
+        author: impl Into<String>,
+        created_at: impl Into<String>,
+    ) -> Self {
+        let text = text.into();
+        let author = author.into();
+        let created_at = created_at.into();
+
+        let mut hasher = Sha256::new();
+        for part in [&target.file, &text, &author, &created_at] {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+        hasher.update(target.symbol.as_deref().unwrap_or("").as_bytes());
+        hasher.update(target.line.unwrap_or(0).to_le_bytes());
+        hasher.update(std::process::id().to_le_bytes());
+        let id = hex::encode(hasher.finalize())[..12].to_string();
+
+        Self {
+            id,
+            file: target.file,
+            symbol: target.symbol,
+            line: target.line,
+            text,
+            author,
+            created_at,
+        }
+    }
+
+    /// The note's target in `--on` form
+    pub fn target(&self) -> String {
+        match (&self.symbol, self.line) {
+            (Some(symbol), _) => format!("{}::{}", self.file, symbol),
+            (None, Some(line)) => format!("{}:{}", self.file, line),
+            (None, None) => self.file.clone(),
+        }
+    }
+
+    /// Whether the note belongs with a view of `file`: the whole file when
+    /// `symbol` and `lines` are both `None`, else the named symbol or the
+    /// inclusive line range. Notes on the file itself go with every view.
+    pub fn applies_to(
+        &self,
+        file: &str,
+        symbol: Option<&str>,
+        lines: Option<(usize, usize)>,
+    ) -> bool {
+        if self.file != file {
+            return false;
+        }
+        if symbol.is_none() && lines.is_none() {
+            return true;
+        }
+        match (&self.symbol, self.line) {
+            (None, None) => true,
+            (Some(name), _) => symbol == Some(name.as_str()),
+            (None, Some(line)) => lines.is_some_and(|(start, end)| start <= line && line <= end),
+        }
+    }
+}
+
+/// The notes directory of a repository
+pub struct NoteStore {
+    dir: PathBuf,
+}
+
+impl NoteStore {
+    pub const DEFAULT_DIR: &'static str = ".agent/notes";
+
+    pub fn open(repo_root: impl AsRef<Path>) -> Self {
+        Self {
+            dir: repo_root.as_ref().join(Self::DEFAULT_DIR),
+        }
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+
+    pub fn save(&self, note: &Note) -> Result<()> {
+        crate::agent_dir::write_json(&self.path(&note.id), note)
+    }
+
+    /// Delete a note, returning it
+    pub fn remove(&self, id: &str) -> Result<Note> {
+        let note = self
+            .list()?
+            .into_iter()
+            .find(|n| n.id == id)
+            .ok_or_else(|| Error::NoteNotFound {
+                note_id: id.to_string(),
+            })?;
+        std::fs::remove_file(self.path(&note.id))?;
+        Ok(note)
+    }
+
+    /// All notes, oldest first
+    pub fn list(&self) -> Result<Vec<Note>> {
+        let mut notes = Vec::new();
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return Ok(notes);
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|x| x.to_str()) != Some("json") {
+                continue;
+            }
+            let content = std::fs::read_to_string(&path)?;
+            notes.push(serde_json::from_str(&content).map_err(|e| Error::Io {
+                message: format!("corrupt note {}: {}", path.display(), e),
+            })?);
+        }
+        notes.sort_by(|a: &Note, b: &Note| {
+            a.created_at
+                .cmp(&b.created_at)
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        Ok(notes)
+    }
+
+    /// Notes for a view of `file`, as in `Note::applies_to`
+    pub fn for_view(
+        &self,
+        file: &str,
+        symbol: Option<&str>,
+        lines: Option<(usize, usize)>,
+    ) -> Result<Vec<Note>> {
+        Ok(self
+            .list()?
+            .into_iter()
+            .filter(|n| n.applies_to(file, symbol, lines))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notes_attach_to_files_lines_and_symbols() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = NoteStore::open(tmp.path());
+        let add = |on: &str, text: &str, at: &str| {
+            let note = Note::new(Target::parse(on).unwrap(), text, "agent-1", at);
+            store.save(&note).unwrap();
+            note
+        };
+        let symbol = add(
+            "src/api.rs::process",
+            "rate limiter is deliberately naive",
+            "2026-01-01T00:00:00Z",
+        );
+        add(
+            "./src/api.rs:40",
+            "off-by-one guard",
+            "2026-01-02T00:00:00Z",
+        );
+        add(
+            "src/api.rs",
+            "generated parts are marked",
+            "2026-01-03T00:00:00Z",
+        );
+        add(
+            "src/db.rs::connect",
+            "pool size is tuned",
+            "2026-01-04T00:00:00Z",
+        );
+        assert_eq!(symbol.id.len(), 12);
+        assert_eq!(symbol.target(), "src/api.rs::process");
+
+        let texts =
+            |notes: Vec<Note>| -> Vec<String> { notes.into_iter().map(|n| n.text).collect() };
+        assert_eq!(
+            texts(
+                store
+                    .for_view("src/api.rs", Some("process"), Some((10, 30)))
+                    .unwrap()
+            ),
+            vec![
+                "rate limiter is deliberately naive",
+                "generated parts are marked"
+            ]
+        );
+        assert_eq!(
+            texts(store.for_view("src/api.rs", None, Some((35, 45))).unwrap()),
+            vec!["off-by-one guard", "generated parts are marked"]
+        );
+        assert_eq!(store.for_view("src/api.rs", None, None).unwrap().len(), 3);
+
+        store.remove(&symbol.id).unwrap();
+        assert_eq!(store.list().unwrap().len(), 3);
+        assert!(store.remove(&symbol.id).is_err());
+
+        assert!(Target::parse("src/api.rs::").is_err());
+        assert!(Target::parse("src/api.rs:0").is_err());
+        assert_eq!(Target::parse("C:notes.txt").unwrap().file, "C:notes.txt");
+    }
+}
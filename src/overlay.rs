@@ -0,0 +1,271 @@
+// ABOUTME: In-memory application of file operations over files read from a revision
+// ABOUTME: Backs `apply`/`edit --onto`: no filesystem writes and no patch binary for Files specs
+
+use std::collections::{BTreeMap, HashSet};
+
+use crate::error::{Error, Result};
+use crate::intent::FileOperation;
+
+/// A file as a tree stores it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Entry {
+    File { content: Vec<u8>, executable: bool },
+    Symlink { target: String },
+}
+
+impl Entry {
+    /// The bytes a reader sees: file content, or a symlink's target
+    pub fn bytes(&self) -> &[u8] {
+        match self {
+            Entry::File { content, .. } => content,
+            Entry::Symlink { target } => target.as_bytes(),
+        }
+    }
+}
+
+/// Pending changes over a set of base files. Paths not in the base are
+/// treated as absent, so the base must hold every path the operations read.
+#[derive(Debug, Clone, Default)]
+pub struct Overlay {
+    base: BTreeMap<String, Entry>,
+    changes: BTreeMap<String, Option<Entry>>,
+}
+
+impl Overlay {
+    pub fn new(base: BTreeMap<String, Entry>) -> Self {
+        Self {
+            base,
+            changes: BTreeMap::new(),
+        }
+    }
+
+    /// The current entry at `path`, with changes applied
+    pub fn get(&self, path: &str) -> Option<&Entry> {
+        match self.changes.get(path) {
+            Some(change) => change.as_ref(),
+            None => self.base.get(path),
+        }
+    }
+
+    /// The entry at `path` before any change
+    pub fn base(&self, path: &str) -> Option<&Entry> {
+        self.base.get(path)
+    }
+
+    /// Every path written or removed, with its new entry (`None` = removed)
+    pub fn changes(&self) -> &BTreeMap<String, Option<Entry>> {
+        &self.changes
+    }
+
+    /// Record `entry` (or a removal) at `path`
+    pub fn set(&mut self, path: &str, entry: Option<Entry>) {
+        self.changes.insert(path.to_string(), entry);
+    }
+
+    /// Apply file operations the way they apply to the working copy, and
+    /// return the paths they touched
+    pub fn apply(&mut self, operations: &[FileOperation]) -> Result<Vec<String>> {
+        let mut files = Vec::new();
+        let mut edited = HashSet::new();
+
+        for op in operations {
+            match op {
+                FileOperation::Create {
+                    path,
+                    content,
+                    executable,
+                } => {
+                    let executable = executable.unwrap_or_else(|| self.executable_at(path));
+                    self.set(
+                        path,
+                        Some(Entry::File {
+                            content: content.as_bytes().to_vec(),
+                            executable,
+                        }),
+                    );
+                    files.push(path.clone());
+                }
+                FileOperation::CreateSymlink { path, target } => {
+                    if crate::repo::symlink_escapes_repo(path, target) {
+                        return Err(Error::PermissionDenied {
+                            action: format!("symlink to '{}'", target),
+                            path: path.clone(),
+                        });
+                    }
+                    self.set(
+                        path,
+                        Some(Entry::Symlink {
+                            target: target.clone(),
+                        }),
+                    );
+                    files.push(path.clone());
+                }
+                FileOperation::Replace {
+                    path,
+                    content,
+                    executable,
+                    ..
+                } => {
+                    // Keep the file's encoding, BOM, and line endings
+                    let format = self
+                        .get(path)
+                        .and_then(|entry| crate::encoding::decode(entry.bytes()))
+                        .map(|(_, format)| format)
+                        .unwrap_or_default();
+                    let executable = executable.unwrap_or_else(|| self.executable_at(path));
+                    self.set(
+                        path,
+                        Some(Entry::File {
+                            content: crate::encoding::encode(content, &format),
+                            executable,
+                        }),
+                    );
+                    files.push(path.clone());
+                }
+                FileOperation::Edit { path, .. } => {
+                    // All edits to a file apply together, against its pre-image
+                    if edited.insert(path.clone()) {
+                        let entry = self.get(path).ok_or_else(|| missing(path))?;
+                        let (content, format) =
+                            crate::encoding::decode(entry.bytes()).ok_or_else(|| {
+                                Error::Repository {
+                                    message: format!("cannot edit binary file '{}'", path),
+                                }
+                            })?;
+                        let content = edit_lines(content, path, operations)?;
+                        let executable = self.executable_at(path);
+                        self.set(
+                            path,
+                            Some(Entry::File {
+                                content: crate::encoding::encode(&content, &format),
+                                executable,
+                            }),
+                        );
+                        files.push(path.clone());
+                    }
+                }
+                FileOperation::Delete { path, .. } => {
+                    self.get(path).ok_or_else(|| missing(path))?;
+                    self.set(path, None);
+                    files.push(path.clone());
+                }
+                FileOperation::Rename { from, to } => {
+                    let entry = self.get(from).cloned().ok_or_else(|| missing(from))?;
+                    self.set(from, None);
+                    self.set(to, Some(entry));
+                    files.push(from.clone());
+                    files.push(to.clone());
+                }
+            }
+        }
+
+        Ok(files)
+    }
+
+    /// Whether the file this overlay holds at `path` carries the executable
+    /// bit, so edits keep it (the disk is checked by `repo::is_executable`)
+    fn executable_at(&self, path: &str) -> bool {
+        matches!(
+            self.get(path),
+            Some(Entry::File {
+                executable: true,
+                ..
+            })
+        )
+    }
+}
+
+fn missing(path: &str) -> Error {
+    Error::Repository {
+        message: format!("'{}' does not exist", path),
+    }
+}
+
+/// Apply every `Edit` operation for `path` to its content, bottom-up so
+/// that line numbers keep referring to the file as it was before the change
+pub fn edit_lines(mut content: String, path: &str, operations: &[FileOperation]) -> Result<String> {
+    let mut edits: Vec<(usize, usize, &str)> = operations
+        .iter()
+        .filter_map(|op| match op {
+            FileOperation::Edit {
+                path: p,
+                start_line,
+                end_line,
+                content,
+                ..
+            } if p == path => Some((*start_line, *end_line, content.as_str())),
+            _ => None,
+        })
+        .collect();
+    edits.sort_by_key(|(start, _, _)| std::cmp::Reverse(*start));
+
+    let mut floor = usize::MAX;
+    for (start, end, replacement) in edits {
+        if start > end || end >= floor {
+            return Err(Error::Repository {
+                message: format!("overlapping or invalid edit ranges in '{}'", path),
+            });
+        }
+        let range = crate::snippet::line_range(&content, start, end)
+            .filter(|r| r.end_line == end)
+            .ok_or_else(|| Error::Repository {
+                message: format!("lines {}-{} are out of range in '{}'", start, end, path),
+            })?;
+        content.replace_range(range.start_byte..range.end_byte, replacement);
+        floor = start;
+    }
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(content: &str, executable: bool) -> Entry {
+        Entry::File {
+            content: content.as_bytes().to_vec(),
+            executable,
+        }
+    }
+
+    #[test]
+    fn applies_operations_without_touching_base() {
+        let base = BTreeMap::from([
+            (
+                "lib.rs".to_string(),
+                file("fn a() {\r\n    1\r\n}\r\n", false),
+            ),
+            ("run.sh".to_string(), file("echo hi\n", true)),
+            ("old.txt".to_string(), file("bye\n", false)),
+        ]);
+        let mut overlay = Overlay::new(base);
+        let ops: Vec<FileOperation> = serde_json::from_value(serde_json::json!([
+            { "op": "edit", "path": "lib.rs", "start_line": 2, "end_line": 2, "content": "    2\n" },
+            { "op": "replace", "path": "run.sh", "content": "echo bye\n" },
+            { "op": "rename", "from": "old.txt", "to": "new.txt" },
+            { "op": "create", "path": "docs/a.md", "content": "# A\n" },
+        ]))
+        .unwrap();
+
+        let files = overlay.apply(&ops).unwrap();
+        assert_eq!(
+            files,
+            vec!["lib.rs", "run.sh", "old.txt", "new.txt", "docs/a.md"]
+        );
+        // Line endings and the executable bit carry over
+        assert_eq!(
+            overlay.get("lib.rs"),
+            Some(&file("fn a() {\r\n    2\r\n}\r\n", false))
+        );
+        assert_eq!(overlay.get("run.sh"), Some(&file("echo bye\n", true)));
+        assert_eq!(overlay.get("old.txt"), None);
+        assert_eq!(overlay.get("new.txt"), Some(&file("bye\n", false)));
+        assert_eq!(overlay.base("old.txt"), Some(&file("bye\n", false)));
+        assert_eq!(overlay.changes().len(), 5);
+
+        let delete_missing: Vec<FileOperation> =
+            serde_json::from_value(serde_json::json!([{ "op": "delete", "path": "nope" }]))
+                .unwrap();
+        assert!(overlay.apply(&delete_missing).is_err());
+    }
+}
@@ -0,0 +1,376 @@
+// ABOUTME: `who-touched`: which sessions, agents, and changes last modified a path
+// ABOUTME: Joins commit history, typed-change metadata, and the audit log by change ID
+
+use serde::Serialize;
+
+use crate::audit::AuditRecord;
+use crate::change::TypedChange;
+use crate::error::Result;
+use crate::globs::Globs;
+
+/// A commit in the working copy's history and the files it changed
+#[derive(Debug, Clone)]
+pub struct CommitFiles {
+    pub change_id: String,
+    pub commit_id: String,
+    pub description: String,
+    pub timestamp: String,
+    pub author: String,
+    pub files: Vec<String>,
+}
+
+/// One change that modified the path
+#[derive(Debug, Clone, Serialize)]
+pub struct Touch {
+    /// Absent for audit records written before change IDs were recorded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub change_id: Option<String>,
+    /// Absent when the change is not in the working copy's history (abandoned or rewritten)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    /// The `AGENTJJ_SESSION` it was made in (or the user, outside one);
+    /// absent for commits made without agentjj
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session: Option<String>,
+    /// Commit author
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub agent: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Files under the path it changed
+    pub files: Vec<String>,
+    /// Where it was found: "history", "typed", "audit"
+    pub sources: Vec<&'static str>,
+}
+
+/// Everything one session did to the path
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionTouches {
+    pub session: String,
+    pub agents: Vec<String>,
+    pub change_ids: Vec<String>,
+    pub changes: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_touched: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub since: Option<String>,
+    /// The most recent change, whose session owns the path now
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last: Option<Touch>,
+    /// Sessions by most recent touch
+    pub sessions: Vec<SessionTouches>,
+    /// Every change, newest first
+    pub changes: Vec<Touch>,
+    /// Changes with no known session (made without agentjj)
+    pub unattributed: usize,
+}
+
+/// A file, a directory (the files under it), or a glob
+struct PathFilter {
+    globs: Globs,
+    dir: String,
+}
+
+impl PathFilter {
+    fn new(path: &str) -> Result<Self> {
+        let path = path.trim_start_matches("./").trim_end_matches('/');
+        // The repository root: everything
+        if path.is_empty() || path == "." {
+            return Ok(Self {
+                globs: Globs::new(["**"])?,
+                dir: String::new(),
+            });
+        }
+        Ok(Self {
+            globs: Globs::new([path])?,
+            dir: format!("{}/", path),
+        })
+    }
+
+    fn matching(&self, files: impl IntoIterator<Item = impl AsRef<str>>) -> Vec<String> {
+        let mut matched: Vec<String> = files
+            .into_iter()
+            .map(|f| f.as_ref().to_string())
+            .filter(|f| self.globs.is_match(f) || f.starts_with(&self.dir))
+            .collect();
+        matched.sort();
+        matched.dedup();
+        matched
+    }
+}
+
+fn same_change(a: &str, b: &str) -> bool {
+    a.starts_with(b) || b.starts_with(a)
+}
+
+fn add_files(touch: &mut Touch, files: Vec<String>) {
+    touch.files.extend(files);
+    touch.files.sort();
+    touch.files.dedup();
+}
+
+/// Who modified `path` (at or after `since`, a timestamp or date prefix),
+/// newest first. Changes are joined across sources by change ID.
+pub fn who_touched(
+    path: &str,
+    since: Option<&str>,
+    commits: &[CommitFiles],
+    typed: &[&TypedChange],
+    audit: &[AuditRecord],
+) -> Result<Report> {
+    let filter = PathFilter::new(path)?;
+    let mut touches: Vec<Touch> = Vec::new();
+
+    for commit in commits {
+        let files = filter.matching(&commit.files);
+        if files.is_empty() {
+            continue;
+        }
+        touches.push(Touch {
+            change_id: Some(commit.change_id.clone()),
+            commit: Some(commit.commit_id.clone()),
+            session: None,
+            agent: Some(commit.author.clone()),
+            timestamp: Some(commit.timestamp.clone()),
+            description: commit.description.lines().next().map(str::to_string),
+            files,
+            sources: vec!["history"],
+        });
+    }
+
+    for change in typed {
+        let recorded = change.files.iter().chain(
+            change
+                .metrics
+                .iter()
+                .flat_map(|m| m.files.iter().map(|f| &f.path)),
+        );
+        let files = filter.matching(recorded);
+        if files.is_empty() {
+            continue;
+        }
+        let existing = touches.iter_mut().find(|t| {
+            t.change_id
+                .as_deref()
+                .is_some_and(|id| same_change(id, &change.change_id))
+        });
+        let touch = match existing {
+            Some(touch) => touch,
+            None => {
+                touches.push(Touch {
+                    change_id: Some(change.change_id.clone()),
+                    commit: None,
+                    session: None,
+                    agent: None,
+                    timestamp: None,
+                    description: change.intent.lines().next().map(str::to_string),
+                    files: Vec::new(),
+                    sources: Vec::new(),
+                });
+                touches.last_mut().expect("just pushed")
+            }
+        };
+        if touch.session.is_none() {
+            touch.session = change.author.clone();
+        }
+        if touch.timestamp.is_none() {
+            touch.timestamp = change.created_at.clone();
+        }
+        add_files(touch, files);
+        touch.sources.push("typed");
+    }
+
+    for record in audit.iter().filter(|r| r.success && r.command == "commit") {
+        let Some(inputs) = &record.inputs else {
+            continue;
+        };
+        let recorded = inputs["files"]
+            .as_object()
+            .map(|files| files.keys().cloned().collect::<Vec<_>>())
+            .unwrap_or_default();
+        let files = filter.matching(recorded);
+        if files.is_empty() {
+            continue;
+        }
+        let change_id = inputs["change_id"].as_str();
+        let existing = touches.iter_mut().find(|t| match change_id {
+            Some(id) => t.change_id.as_deref().is_some_and(|c| same_change(c, id)),
+            // Older records: the typed change written by the same commit
+            None => {
+                t.session.as_deref() == Some(record.session.as_str())
+                    && t.timestamp.as_deref() == Some(record.timestamp.as_str())
+            }
+        });
+        let touch = match existing {
+            Some(touch) => touch,
+            None => {
+                touches.push(Touch {
+                    change_id: change_id.map(str::to_string),
+                    commit: None,
+                    session: None,
+                    agent: None,
+                    timestamp: None,
+                    description: None,
+                    files: Vec::new(),
+                    sources: Vec::new(),
+                });
+                touches.last_mut().expect("just pushed")
+            }
+        };
+        // The audit log knows the session even when the typed change only
+        // recorded the user
+        touch.session = Some(record.session.clone());
+        if touch.timestamp.is_none() {
+            touch.timestamp = Some(record.timestamp.clone());
+        }
+        add_files(touch, files);
+        touch.sources.push("audit");
+    }
+
+    if let Some(since) = since {
+        touches.retain(|t| t.timestamp.as_deref().is_some_and(|ts| ts >= since));
+    }
+    // Newest first; undated last
+    touches.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+
+    // Touches are newest first, so sessions come out by most recent touch
+    let mut sessions: Vec<SessionTouches> = Vec::new();
+    for touch in &touches {
+        let Some(session) = &touch.session else {
+            continue;
+        };
+        let index = match sessions.iter().position(|s| &s.session == session) {
+            Some(index) => index,
+            None => {
+                sessions.push(SessionTouches {
+                    session: session.clone(),
+                    agents: Vec::new(),
+                    change_ids: Vec::new(),
+                    changes: 0,
+                    last_touched: touch.timestamp.clone(),
+                });
+                sessions.len() - 1
+            }
+        };
+        let entry = &mut sessions[index];
+        entry.changes += 1;
+        if let Some(agent) = &touch.agent {
+            if !entry.agents.contains(agent) {
+                entry.agents.push(agent.clone());
+            }
+        }
+        if let Some(id) = &touch.change_id {
+            entry.change_ids.push(id.clone());
+        }
+    }
+
+    Ok(Report {
+        path: path.to_string(),
+        since: since.map(str::to_string),
+        last: touches.first().cloned(),
+        unattributed: touches.iter().filter(|t| t.session.is_none()).count(),
+        sessions,
+        changes: touches,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::change::ChangeType;
+
+    fn commit(change: &str, ts: &str, author: &str, files: &[&str]) -> CommitFiles {
+        CommitFiles {
+            change_id: change.into(),
+            commit_id: format!("c{}", change),
+            description: format!("change {}\n\nbody", change),
+            timestamp: ts.into(),
+            author: author.into(),
+            files: files.iter().map(|f| f.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn joins_history_typed_changes_and_audit_by_change() {
+        let commits = vec![
+            commit("aaaa1111", "2026-01-01T10:00:00Z", "alice", &["src/api.rs"]),
+            commit(
+                "bbbb2222",
+                "2026-01-02T10:00:00Z",
+                "bot",
+                &["src/api/v2.rs", "README.md"],
+            ),
+            commit(
+                "cccc3333",
+                "2026-01-03T10:00:00Z",
+                "bot",
+                &["docs/guide.md"],
+            ),
+        ];
+        let mut typed = TypedChange::new("bbbb2222", ChangeType::Behavioral, "add v2");
+        typed.author = Some("ci-user".into());
+        typed.files = vec!["src/api/v2.rs".into()];
+        let audit = vec![
+            AuditRecord {
+                session: "fix-api-1a2b3c".into(),
+                timestamp: "2026-01-02T10:00:01Z".into(),
+                command: "commit".into(),
+                args: vec!["commit".into()],
+                success: true,
+                error: None,
+                inputs: Some(serde_json::json!({
+                    "files": { "src/api/v2.rs": { "content": "" } },
+                    "change_id": "bbbb2222",
+                })),
+            },
+            // A commit that never landed in history
+            AuditRecord {
+                session: "spike".into(),
+                timestamp: "2025-12-30T09:00:00Z".into(),
+                command: "commit".into(),
+                args: vec!["commit".into()],
+                success: true,
+                error: None,
+                inputs: Some(serde_json::json!({ "files": { "src/api.rs": {} } })),
+            },
+        ];
+
+        let report = who_touched("src/", None, &commits, &[&typed], &audit).unwrap();
+        let last = report.last.as_ref().unwrap();
+        assert_eq!(last.change_id.as_deref(), Some("bbbb2222"));
+        assert_eq!(last.session.as_deref(), Some("fix-api-1a2b3c"));
+        assert_eq!(last.agent.as_deref(), Some("bot"));
+        assert_eq!(last.files, vec!["src/api/v2.rs"]);
+        assert_eq!(last.sources, vec!["history", "typed", "audit"]);
+        assert_eq!(last.description.as_deref(), Some("change bbbb2222"));
+        assert_eq!(report.changes.len(), 3);
+        assert_eq!(report.unattributed, 1);
+        assert_eq!(report.sessions.len(), 2);
+        assert_eq!(report.sessions[0].session, "fix-api-1a2b3c");
+        assert_eq!(report.sessions[0].agents, vec!["bot"]);
+        assert_eq!(report.sessions[1].session, "spike");
+        assert!(report.sessions[1].change_ids.is_empty());
+
+        let recent =
+            who_touched("src/api", Some("2026-01-02"), &commits, &[&typed], &audit).unwrap();
+        assert_eq!(recent.changes.len(), 1);
+        assert_eq!(
+            who_touched("./", None, &commits, &[], &[])
+                .unwrap()
+                .changes
+                .len(),
+            3
+        );
+        assert!(who_touched("lib", None, &commits, &[], &[])
+            .unwrap()
+            .last
+            .is_none());
+    }
+}
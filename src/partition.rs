@@ -0,0 +1,259 @@
+// ABOUTME: Suggested split of an oversized change into smaller commits (review.max_change_loc / max_change_files)
+// ABOUTME: Files that share symbols stay together, then by directory, packed into groups under the limits
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::callers::is_ident;
+use crate::custom::shell_quote;
+use crate::symbols::{extract_symbols, SupportedLanguage, SymbolKind};
+
+/// Names shorter than this are too common to link files by
+const MIN_SYMBOL_LEN: usize = 4;
+
+/// A file in the change being split
+#[derive(Debug, Clone)]
+pub struct ChangedFile {
+    pub path: String,
+    /// Lines added plus lines removed
+    pub lines: usize,
+    /// Content after the change, None when deleted or binary
+    pub content: Option<String>,
+}
+
+/// One commit's worth of files
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileGroup {
+    pub paths: Vec<String>,
+    pub lines: usize,
+    /// Why these files are together
+    pub reason: String,
+    /// A single coherent unit that is still over a limit on its own
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub over_limit: bool,
+    /// Commits exactly these files
+    pub command: String,
+}
+
+/// Union-find over file indexes
+struct Components {
+    parent: Vec<usize>,
+}
+
+impl Components {
+    fn new(n: usize) -> Self {
+        Self {
+            parent: (0..n).collect(),
+        }
+    }
+
+    fn find(&mut self, i: usize) -> usize {
+        let mut root = i;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        self.parent[i] = root;
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            self.parent[a.max(b)] = a.min(b);
+        }
+    }
+}
+
+/// Top-level names a file defines
+fn defined_names(file: &ChangedFile) -> BTreeSet<String> {
+    let (Some(content), Some(lang)) = (
+        &file.content,
+        SupportedLanguage::from_path(std::path::Path::new(&file.path)),
+    ) else {
+        return BTreeSet::new();
+    };
+    extract_symbols(content, lang)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|s| s.kind != SymbolKind::Import && s.name.len() >= MIN_SYMBOL_LEN)
+        .map(|s| s.name)
+        .collect()
+}
+
+fn uses(content: &str, name: &str) -> bool {
+    content.match_indices(name).any(|(at, _)| {
+        let end = at + name.len();
+        !content[..at].chars().next_back().is_some_and(is_ident)
+            && !content[end..].chars().next().is_some_and(is_ident)
+    })
+}
+
+fn parent_dir(path: &str) -> &str {
+    path.rsplit_once('/').map_or(".", |(dir, _)| dir)
+}
+
+/// Whether a group of `files` files and `lines` lines fits the limits
+fn fits(files: usize, lines: usize, max_lines: Option<usize>, max_files: Option<usize>) -> bool {
+    max_lines.is_none_or(|max| lines <= max) && max_files.is_none_or(|max| files <= max)
+}
+
+/// Split `files` into groups that each fit the limits where possible.
+/// A file that uses a symbol another file defines goes with it, files in
+/// one directory go together, and small groups are then packed together.
+pub fn partition(
+    files: &[ChangedFile],
+    max_lines: Option<usize>,
+    max_files: Option<usize>,
+) -> Vec<FileGroup> {
+    let mut components = Components::new(files.len());
+    let mut linked = vec![false; files.len()];
+
+    // Symbol references: splitting them apart would leave a commit broken
+    let names: Vec<BTreeSet<String>> = files.iter().map(defined_names).collect();
+    for (user, file) in files.iter().enumerate() {
+        let Some(content) = &file.content else {
+            continue;
+        };
+        for (definer, defined) in names.iter().enumerate() {
+            if definer != user && defined.iter().any(|name| uses(content, name)) {
+                components.union(user, definer);
+                linked[user] = true;
+                linked[definer] = true;
+            }
+        }
+    }
+
+    // Then directories, as long as the merged unit still fits
+    let mut by_dir: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    for (i, file) in files.iter().enumerate() {
+        by_dir.entry(parent_dir(&file.path)).or_default().push(i);
+    }
+    let size = |components: &mut Components, root: usize| {
+        let members: Vec<usize> = (0..files.len())
+            .filter(|&i| components.find(i) == root)
+            .collect();
+        (
+            members.len(),
+            members.iter().map(|&i| files[i].lines).sum::<usize>(),
+        )
+    };
+    for indexes in by_dir.values() {
+        for pair in indexes.windows(2) {
+            let (a, b) = (components.find(pair[0]), components.find(pair[1]));
+            if a == b {
+                continue;
+            }
+            let ((a_files, a_lines), (b_files, b_lines)) =
+                (size(&mut components, a), size(&mut components, b));
+            if fits(a_files + b_files, a_lines + b_lines, max_lines, max_files) {
+                components.union(a, b);
+            }
+        }
+    }
+
+    let mut units: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for i in 0..files.len() {
+        let root = components.find(i);
+        units.entry(root).or_default().push(i);
+    }
+
+    // Pack whole units into as few groups as the limits allow, in path order
+    let mut groups: Vec<(Vec<usize>, bool)> = Vec::new();
+    for unit in units.into_values() {
+        let shared = unit.iter().any(|&i| linked[i]);
+        let open = groups.last_mut().filter(|(group, _)| {
+            let lines: usize = group.iter().chain(&unit).map(|&i| files[i].lines).sum();
+            fits(group.len() + unit.len(), lines, max_lines, max_files)
+        });
+        match open {
+            Some((group, group_shared)) => {
+                group.extend(&unit);
+                *group_shared |= shared;
+            }
+            None => groups.push((unit, shared)),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(indexes, shared)| {
+            let paths: Vec<String> = indexes.iter().map(|&i| files[i].path.clone()).collect();
+            let lines = indexes.iter().map(|&i| files[i].lines).sum();
+            let dirs: BTreeSet<&str> = paths.iter().map(|p| parent_dir(p)).collect();
+            let mut reason = match dirs.len() {
+                1 => match dirs.iter().next() {
+                    Some(&".") | None => "top-level files".to_string(),
+                    Some(dir) => format!("files in {}", dir),
+                },
+                n => format!("files in {} directories", n),
+            };
+            if shared {
+                reason.push_str(", kept with the files whose symbols they use");
+            }
+            let command = format!(
+                "agentjj commit --paths {} -m \"...\"",
+                paths
+                    .iter()
+                    .map(|p| shell_quote(p))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            );
+            FileGroup {
+                over_limit: !fits(paths.len(), lines, max_lines, max_files),
+                paths,
+                lines,
+                reason,
+                command,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, lines: usize, content: &str) -> ChangedFile {
+        ChangedFile {
+            path: path.into(),
+            lines,
+            content: Some(content.into()),
+        }
+    }
+
+    #[test]
+    fn keeps_symbol_users_with_definers_and_packs_by_directory() {
+        let files = vec![
+            file("api/routes.py", 40, "from core.models import Invoice\n"),
+            file("core/models.py", 40, "class Invoice:\n    pass\n"),
+            file("docs/a.md", 30, "# A\n"),
+            file("docs/b.md", 30, "# B\n"),
+            file("tools/gen.py", 30, "print(1)\n"),
+        ];
+        let groups = partition(&files, Some(100), None);
+        let paths: Vec<Vec<&str>> = groups
+            .iter()
+            .map(|g| g.paths.iter().map(String::as_str).collect())
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                vec!["api/routes.py", "core/models.py"],
+                vec!["docs/a.md", "docs/b.md", "tools/gen.py"],
+            ]
+        );
+        assert!(groups[0].reason.contains("symbols"));
+        assert_eq!(groups[1].lines, 90);
+        assert!(groups.iter().all(|g| !g.over_limit));
+        assert_eq!(
+            groups[0].command,
+            "agentjj commit --paths api/routes.py core/models.py -m \"...\""
+        );
+
+        // One file over the limit on its own is flagged, not split
+        let groups = partition(&[file("big.rs", 500, "")], Some(100), None);
+        assert!(groups[0].over_limit);
+        let groups = partition(&files, None, Some(2));
+        assert!(groups.iter().all(|g| g.paths.len() <= 2));
+    }
+}
@@ -0,0 +1,544 @@
+// ABOUTME: Goal-conditioned planning for `suggest --for`: keyword goals mapped to command plans
+// ABOUTME: Pure functions over a snapshot of repo state so plans are easy to test
+
+use serde::Serialize;
+
+use crate::change::{ChangeType, TypedChange};
+use crate::custom::shell_quote;
+
+/// What an agent is trying to get done
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Goal {
+    Release,
+    Schema,
+    Endpoint,
+    Deps,
+    Docs,
+    Fix,
+    Refactor,
+    Feature,
+}
+
+/// Keywords per goal, in matching priority: "add api endpoint" is an
+/// endpoint, not a generic feature
+const KEYWORDS: [(Goal, &[&str]); 8] = [
+    (
+        Goal::Release,
+        &["release", "ship", "publish", "version", "tag"],
+    ),
+    (
+        Goal::Schema,
+        &["schema", "migration", "migrate", "table", "column"],
+    ),
+    (Goal::Endpoint, &["endpoint", "api", "route", "handler"]),
+    (
+        Goal::Deps,
+        &["dependency", "dependencies", "deps", "upgrade", "bump"],
+    ),
+    (Goal::Docs, &["doc", "docs", "documentation", "readme"]),
+    (Goal::Fix, &["fix", "bug", "bugfix", "regression", "crash"]),
+    (
+        Goal::Refactor,
+        &[
+            "refactor", "cleanup", "rename", "extract", "simplify", "dedupe",
+        ],
+    ),
+    (
+        Goal::Feature,
+        &["feature", "add", "implement", "new", "support"],
+    ),
+];
+
+impl Goal {
+    /// Match a free-text goal by keyword, e.g. "add api endpoint" or "release"
+    pub fn parse(text: &str) -> Option<Goal> {
+        let words: Vec<String> = text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .map(str::to_lowercase)
+            .collect();
+        KEYWORDS
+            .iter()
+            .find(|(_, keywords)| words.iter().any(|w| keywords.contains(&w.as_str())))
+            .map(|(goal, _)| *goal)
+    }
+
+    /// One example keyword per goal, for error messages
+    pub fn examples() -> Vec<&'static str> {
+        KEYWORDS.iter().map(|(_, keywords)| keywords[0]).collect()
+    }
+
+    /// Change type, category, and conventional-commit prefix for the work
+    fn change_kind(self) -> (&'static str, Option<&'static str>, &'static str) {
+        match self {
+            Goal::Release => ("config", Some("chore"), "chore"),
+            Goal::Schema => ("schema", Some("feature"), "feat"),
+            Goal::Endpoint | Goal::Feature => ("behavioral", Some("feature"), "feat"),
+            Goal::Deps => ("deps", Some("chore"), "chore"),
+            Goal::Docs => ("docs", None, "docs"),
+            Goal::Fix => ("behavioral", Some("fix"), "fix"),
+            Goal::Refactor => ("refactor", None, "refactor"),
+        }
+    }
+}
+
+/// Semantic version component to increment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Bump {
+    Major,
+    Minor,
+    Patch,
+}
+
+/// The bump a set of typed changes calls for: major if any is breaking,
+/// minor for features and other behavioral or schema changes, else patch
+pub fn bump_for(changes: &[&TypedChange]) -> Bump {
+    if changes.iter().any(|c| c.is_breaking()) {
+        Bump::Major
+    } else if changes.iter().any(|c| {
+        matches!(c.change_type, ChangeType::Behavioral | ChangeType::Schema)
+            && !c.has_category(&crate::change::ChangeCategory::Fix)
+    }) {
+        Bump::Minor
+    } else {
+        Bump::Patch
+    }
+}
+
+/// Apply a bump to a version or tag such as `v1.2.3`, keeping any prefix
+pub fn next_version(current: &str, bump: Bump) -> Option<String> {
+    let start = current.find(|c: char| c.is_ascii_digit())?;
+    let (prefix, version) = current.split_at(start);
+    let core = version.split(['-', '+']).next()?;
+    let mut parts = core.split('.').map(|p| p.parse::<u64>().ok());
+    let (major, minor, patch) = (parts.next()??, parts.next()??, parts.next()??);
+    let (major, minor, patch) = match bump {
+        Bump::Major => (major + 1, 0, 0),
+        Bump::Minor => (major, minor + 1, 0),
+        Bump::Patch => (major, minor, patch + 1),
+    };
+    Some(format!("{}{}.{}.{}", prefix, major, minor, patch))
+}
+
+/// Where a project declares its version
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VersionLine {
+    pub path: String,
+    /// 1-based
+    pub line: usize,
+    pub version: String,
+    /// The whole line, with the version in it
+    pub text: String,
+}
+
+/// The first `version = "..."` (TOML) or `"version": "..."` (JSON) line of a
+/// build manifest
+pub fn find_version_line(path: &str, content: &str) -> Option<VersionLine> {
+    content.lines().enumerate().find_map(|(index, line)| {
+        let trimmed = line.trim_start();
+        let rest = trimmed
+            .strip_prefix("version")
+            .or_else(|| trimmed.strip_prefix("\"version\""))?;
+        let rest = rest.trim_start().strip_prefix(['=', ':'])?.trim_start();
+        let version = rest.strip_prefix('"')?.split('"').next()?;
+        Some(VersionLine {
+            path: path.to_string(),
+            line: index + 1,
+            version: version.to_string(),
+            text: line.to_string(),
+        })
+    })
+}
+
+/// What a plan is built from
+#[derive(Debug, Clone, Default)]
+pub struct PlanContext {
+    pub has_manifest: bool,
+    pub files_changed: usize,
+    /// Source files the manifest names as interfaces
+    pub interfaces: Vec<String>,
+    /// Build manifest (Cargo.toml, package.json, ...) of the primary build system
+    pub build_manifest: Option<String>,
+    pub test_command: Option<String>,
+    pub last_tag: Option<String>,
+    /// Typed changes since the last tag
+    pub unreleased: usize,
+    pub breaking: usize,
+    pub bump: Option<Bump>,
+    pub next_version: Option<String>,
+    pub version_line: Option<VersionLine>,
+}
+
+/// One command in a plan. `<...>` marks a value the agent must fill in.
+#[derive(Debug, Clone, Serialize)]
+pub struct Step {
+    pub step: usize,
+    pub action: String,
+    pub command: String,
+    pub reason: String,
+}
+
+struct Plan {
+    steps: Vec<Step>,
+}
+
+impl Plan {
+    fn push(&mut self, action: &str, command: impl Into<String>, reason: impl Into<String>) {
+        self.steps.push(Step {
+            step: self.steps.len() + 1,
+            action: action.to_string(),
+            command: command.into(),
+            reason: reason.into(),
+        });
+    }
+}
+
+/// Ordered, executable steps towards `goal` given the repo's state
+pub fn plan(goal: Goal, text: &str, ctx: &PlanContext) -> Vec<Step> {
+    let mut plan = Plan { steps: Vec::new() };
+    let (change_type, category, prefix) = goal.change_kind();
+
+    if !ctx.has_manifest {
+        plan.push(
+            "init",
+            "agentjj init",
+            "No manifest - initialize so validate, permissions, and invariants apply",
+        );
+    }
+
+    if goal == Goal::Release {
+        release(&mut plan, ctx);
+        return plan.steps;
+    }
+
+    if ctx.files_changed == 0 {
+        plan.push(
+            "checkpoint",
+            format!("agentjj checkpoint create before-{}", goal_slug(goal)),
+            "Save a restore point before starting",
+        );
+    } else {
+        plan.push(
+            "diff",
+            "agentjj diff",
+            format!(
+                "{} file(s) already changed - review them before adding more",
+                ctx.files_changed
+            ),
+        );
+    }
+
+    match goal {
+        Goal::Endpoint => {
+            if ctx.interfaces.is_empty() {
+                plan.push(
+                    "explore",
+                    "agentjj files --symbols",
+                    "Find where existing endpoints are defined",
+                );
+            }
+            for interface in ctx.interfaces.iter().take(3) {
+                plan.push(
+                    "explore",
+                    format!("agentjj symbol {}", interface),
+                    format!("Study the existing endpoints in {}", interface),
+                );
+            }
+        }
+        Goal::Fix => {
+            plan.push(
+                "locate",
+                "agentjj symbol <file>::<function>",
+                "Read the code the bug is in",
+            );
+            plan.push(
+                "impact",
+                "agentjj affected <file>::<function>",
+                "See which callers the fix can change",
+            );
+        }
+        Goal::Refactor => {
+            plan.push(
+                "targets",
+                "agentjj dupes",
+                "Find duplicated code worth consolidating",
+            );
+            plan.push(
+                "impact",
+                "agentjj affected <file>::<symbol>",
+                "Check who depends on what you are about to move",
+            );
+        }
+        Goal::Docs => {
+            plan.push(
+                "explore",
+                "agentjj files --pattern '**/*.md' --symbols",
+                "List the documentation and its sections",
+            );
+        }
+        Goal::Deps => {
+            let manifest = ctx.build_manifest.as_deref().unwrap_or("<build manifest>");
+            plan.push(
+                "read",
+                format!("agentjj read {}", manifest),
+                "See the current dependency versions",
+            );
+        }
+        Goal::Schema => {
+            plan.push(
+                "explore",
+                "agentjj files --pattern '**/*.sql' --symbols",
+                "Find the tables and migrations the change touches",
+            );
+        }
+        Goal::Feature => {
+            plan.push(
+                "explore",
+                "agentjj orient",
+                "Get the codebase layout and entry points",
+            );
+        }
+        Goal::Release => unreachable!(),
+    }
+
+    let category = category.map(|c| format!(" -c {}", c)).unwrap_or_default();
+    plan.push(
+        "edit",
+        format!(
+            "agentjj edit -i {} -t {}{} --ops <ops.json>",
+            shell_quote(text),
+            change_type,
+            category
+        ),
+        "Make the change; the intent and type are recorded with it",
+    );
+
+    if matches!(goal, Goal::Endpoint | Goal::Feature | Goal::Refactor) {
+        plan.push(
+            "dupes",
+            "agentjj dupes --against-change",
+            "Make sure the new code does not copy existing code",
+        );
+    }
+    if let Some(test) = &ctx.test_command {
+        plan.push("test", test.clone(), "Run the test suite");
+    }
+    plan.push(
+        "validate",
+        "agentjj validate",
+        if goal == Goal::Schema {
+            "Check the change, including destructive schema operations"
+        } else {
+            "Check the change is complete"
+        },
+    );
+    plan.push(
+        "commit",
+        format!(
+            "agentjj commit -m {}",
+            shell_quote(&format!("{}: {}", prefix, text))
+        ),
+        "Record the change",
+    );
+    plan.push("push", "agentjj push", "Publish it for review");
+    plan.steps
+}
+
+fn release(plan: &mut Plan, ctx: &PlanContext) {
+    if ctx.files_changed > 0 {
+        plan.push(
+            "validate",
+            "agentjj validate",
+            format!(
+                "{} file(s) have uncommitted changes - make sure they belong in the release",
+                ctx.files_changed
+            ),
+        );
+        plan.push(
+            "commit",
+            "agentjj commit -m '<message>'",
+            "Commit pending work before cutting the release",
+        );
+    }
+
+    let since = ctx
+        .last_tag
+        .as_deref()
+        .map(|tag| format!(" since {}", tag))
+        .unwrap_or_default();
+    if ctx.breaking > 0 {
+        plan.push(
+            "breaking",
+            "agentjj change list --breaking",
+            format!(
+                "{} breaking change(s){} - call them out in the notes",
+                ctx.breaking, since
+            ),
+        );
+    }
+    plan.push(
+        "changelog",
+        "agentjj change list",
+        format!(
+            "Draft the changelog from the {} typed change(s){}",
+            ctx.unreleased, since
+        ),
+    );
+
+    let version = ctx.next_version.as_deref().unwrap_or("<version>");
+    let bump = ctx
+        .bump
+        .map(|b| format!(" ({:?} bump)", b).to_lowercase())
+        .unwrap_or_default();
+    if let Some(line) = &ctx.version_line {
+        let bare = version.trim_start_matches(|c: char| !c.is_ascii_digit());
+        let ops = serde_json::json!([{
+            "op": "edit",
+            "path": line.path,
+            "start_line": line.line,
+            "end_line": line.line,
+            "content": format!("{}\n", line.text.replacen(&line.version, bare, 1)),
+        }]);
+        plan.push(
+            "version",
+            format!(
+                "printf '%s' {} | agentjj edit -i {} -t config -c chore --ops -",
+                shell_quote(&ops.to_string()),
+                shell_quote(&format!("Release {}", version))
+            ),
+            format!(
+                "Bump {} from {} to {}{}",
+                line.path, line.version, bare, bump
+            ),
+        );
+        plan.push(
+            "commit",
+            format!(
+                "agentjj commit -m {}",
+                shell_quote(&format!("chore: release {}", version))
+            ),
+            "Commit the version bump",
+        );
+    }
+    plan.push("push", "agentjj push", "Publish the release commit");
+    plan.push(
+        "tag",
+        format!(
+            "agentjj tag {} -m {} --push",
+            version,
+            shell_quote(&format!("Release {}", version))
+        ),
+        format!("Tag the release{} and push the tag", bump),
+    );
+}
+
+fn goal_slug(goal: Goal) -> &'static str {
+    match goal {
+        Goal::Release => "release",
+        Goal::Schema => "schema",
+        Goal::Endpoint => "endpoint",
+        Goal::Deps => "deps",
+        Goal::Docs => "docs",
+        Goal::Fix => "fix",
+        Goal::Refactor => "refactor",
+        Goal::Feature => "feature",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_goals_by_keyword_priority() {
+        assert_eq!(Goal::parse("add api endpoint"), Some(Goal::Endpoint));
+        assert_eq!(Goal::parse("release"), Some(Goal::Release));
+        assert_eq!(Goal::parse("Fix the login crash"), Some(Goal::Fix));
+        assert_eq!(Goal::parse("add a users table"), Some(Goal::Schema));
+        assert_eq!(Goal::parse("implement search"), Some(Goal::Feature));
+        assert_eq!(Goal::parse("make it nicer"), None);
+    }
+
+    #[test]
+    fn bumps_versions() {
+        assert_eq!(
+            next_version("v1.2.3", Bump::Minor).as_deref(),
+            Some("v1.3.0")
+        );
+        assert_eq!(
+            next_version("1.2.3-rc.1", Bump::Patch).as_deref(),
+            Some("1.2.4")
+        );
+        assert_eq!(
+            next_version("release-0.9.9", Bump::Major).as_deref(),
+            Some("release-1.0.0")
+        );
+        assert_eq!(next_version("latest", Bump::Major), None);
+
+        let line = find_version_line(
+            "Cargo.toml",
+            "[package]\nname = \"x\"\nversion = \"0.3.1\"\n",
+        )
+        .unwrap();
+        assert_eq!((line.line, line.version.as_str()), (3, "0.3.1"));
+        let json = find_version_line("package.json", "{\n  \"version\": \"2.0.0\",\n}").unwrap();
+        assert_eq!(json.line, 2);
+    }
+
+    #[test]
+    fn release_plan_bumps_commits_and_tags() {
+        let ctx = PlanContext {
+            has_manifest: true,
+            last_tag: Some("v0.3.1".into()),
+            unreleased: 4,
+            breaking: 0,
+            bump: Some(Bump::Minor),
+            next_version: Some("v0.4.0".into()),
+            version_line: find_version_line("Cargo.toml", "version = \"0.3.1\"\n"),
+            ..Default::default()
+        };
+        let steps = plan(Goal::Release, "release", &ctx);
+        let actions: Vec<&str> = steps.iter().map(|s| s.action.as_str()).collect();
+        assert_eq!(
+            actions,
+            vec!["changelog", "version", "commit", "push", "tag"]
+        );
+        assert!(steps[1].command.contains(r#"version = \"0.4.0\"\n"#));
+        assert_eq!(
+            steps[4].command,
+            "agentjj tag v0.4.0 -m 'Release v0.4.0' --push"
+        );
+    }
+
+    #[test]
+    fn coding_plans_record_type_and_quote_the_goal() {
+        let ctx = PlanContext {
+            has_manifest: false,
+            interfaces: vec!["src/api.rs".into()],
+            test_command: Some("cargo test".into()),
+            ..Default::default()
+        };
+        let steps = plan(Goal::Endpoint, "add user's api endpoint", &ctx);
+        let actions: Vec<&str> = steps.iter().map(|s| s.action.as_str()).collect();
+        assert_eq!(
+            actions,
+            vec![
+                "init",
+                "checkpoint",
+                "explore",
+                "edit",
+                "dupes",
+                "test",
+                "validate",
+                "commit",
+                "push"
+            ]
+        );
+        assert_eq!(steps[2].command, "agentjj symbol src/api.rs");
+        assert!(steps[3]
+            .command
+            .starts_with(r"agentjj edit -i 'add user'\''s api endpoint' -t behavioral -c feature"));
+    }
+}
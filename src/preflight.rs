@@ -0,0 +1,115 @@
+// ABOUTME: preflight: go/no-go for a set of paths before an agent starts editing them
+// ABOUTME: Reasons from upstream and branch changes, parked approvals, churn, and manifest rules
+
+use serde::Serialize;
+
+/// Which check produced a reason
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Check {
+    /// The trunk (local or remote) has changes to the path the working copy lacks
+    Upstream,
+    /// A parked intent, pending or approved but not applied, edits the path
+    Approval,
+    /// The manifest does not allow changing the path
+    Permission,
+    /// Another bookmark or remote branch has unmerged changes to the path
+    Branch,
+    /// The path changed in many recent changes
+    Churn,
+    /// The manifest wants a human to review or approve changes to the path
+    Review,
+}
+
+impl Check {
+    /// Upstream changes, parked edits, and denied paths stop the edit; the
+    /// rest are worth knowing before starting
+    pub fn blocks(self) -> bool {
+        matches!(self, Check::Upstream | Check::Approval | Check::Permission)
+    }
+}
+
+/// One reason to hold off on, or be careful with, a path
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Reason {
+    pub check: Check,
+    /// `block` or `warn`
+    pub severity: &'static str,
+    pub path: String,
+    pub message: String,
+    /// Change IDs (or approval IDs) behind the reason
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<String>,
+}
+
+/// Changes among the recent ones scanned at which a path counts as hot
+pub const HOT_CHURN: usize = 3;
+
+/// Whether `file` is `path`, or lies under it when `path` is a directory
+pub fn touches(path: &str, file: &str) -> bool {
+    let path = path.trim_end_matches('/');
+    path.is_empty()
+        || file == path
+        || file
+            .strip_prefix(path)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// The verdict for a set of paths
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    /// No blocking reasons
+    pub go: bool,
+    pub paths: Vec<String>,
+    /// Blocking reasons first
+    pub reasons: Vec<Reason>,
+}
+
+impl Report {
+    pub fn new(paths: Vec<String>) -> Self {
+        Self {
+            go: true,
+            paths,
+            reasons: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, check: Check, path: &str, message: String, changes: Vec<String>) {
+        self.go &= !check.blocks();
+        self.reasons.push(Reason {
+            check,
+            severity: if check.blocks() { "block" } else { "warn" },
+            path: path.to_string(),
+            message,
+            changes,
+        });
+        self.reasons.sort_by_key(|r| r.check);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocking_reasons_decide_go_and_sort_first() {
+        assert!(touches("src/api", "src/api/handlers.rs"));
+        assert!(touches("src/api/", "src/api/handlers.rs"));
+        assert!(touches("src/api.rs", "src/api.rs"));
+        assert!(!touches("src/api", "src/api_v2.rs"));
+
+        let mut report = Report::new(vec!["src/api.rs".into()]);
+        report.add(Check::Churn, "src/api.rs", "hot".into(), vec![]);
+        assert!(report.go);
+        report.add(
+            Check::Upstream,
+            "src/api.rs",
+            "behind".into(),
+            vec!["abc".into()],
+        );
+        assert!(!report.go);
+        assert_eq!(report.reasons[0].check, Check::Upstream);
+        assert_eq!(report.reasons[0].severity, "block");
+        assert_eq!(report.reasons[1].severity, "warn");
+    }
+}
@@ -0,0 +1,208 @@
+// ABOUTME: Pre-images of the files `apply` writes, saved before each write
+// ABOUTME: A failed apply puts them back so the working copy is left as it was found
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::error::Result;
+
+/// What was at a path before `apply` touched it
+#[derive(Debug, Clone, PartialEq)]
+enum PreImage {
+    Absent,
+    File { content: Vec<u8>, executable: bool },
+    Symlink { target: PathBuf },
+}
+
+impl PreImage {
+    fn read(path: &Path) -> Result<Self> {
+        let Ok(meta) = std::fs::symlink_metadata(path) else {
+            return Ok(PreImage::Absent);
+        };
+        if meta.file_type().is_symlink() {
+            return Ok(PreImage::Symlink {
+                target: std::fs::read_link(path)?,
+            });
+        }
+        Ok(PreImage::File {
+            content: std::fs::read(path)?,
+            executable: crate::repo::is_executable(&meta),
+        })
+    }
+
+    fn write(&self, path: &Path) -> Result<()> {
+        if std::fs::symlink_metadata(path).is_ok_and(|m| !m.is_dir()) {
+            std::fs::remove_file(path)?;
+        }
+        match self {
+            PreImage::Absent => {}
+            PreImage::File {
+                content,
+                executable,
+            } => {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(path, content)?;
+                crate::repo::set_executable(path, *executable)?;
+            }
+            PreImage::Symlink { target } => {
+                crate::repo::create_symlink(&target.to_string_lossy(), path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What undoing a failed apply did to the working copy
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Cleanup {
+    /// Files put back to their content before the apply
+    pub restored: Vec<String>,
+    /// Files (and directories) the apply created, deleted again
+    pub removed: Vec<String>,
+    /// Paths that could not be put back, with why
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failed: Vec<String>,
+}
+
+impl Cleanup {
+    pub fn is_empty(&self) -> bool {
+        self.restored.is_empty() && self.removed.is_empty() && self.failed.is_empty()
+    }
+}
+
+impl std::fmt::Display for Cleanup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let parts: Vec<String> = [
+            ("restored", &self.restored),
+            ("removed", &self.removed),
+            ("could not restore", &self.failed),
+        ]
+        .into_iter()
+        .filter(|(_, paths)| !paths.is_empty())
+        .map(|(what, paths)| format!("{} {}", what, paths.join(", ")))
+        .collect();
+        write!(f, "{}", parts.join("; "))
+    }
+}
+
+/// The pre-images of every path an apply has written so far
+pub struct PreImages {
+    root: PathBuf,
+    saved: BTreeMap<String, PreImage>,
+    /// Directories the apply created, outermost first
+    created_dirs: Vec<PathBuf>,
+}
+
+impl PreImages {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            saved: BTreeMap::new(),
+            created_dirs: Vec::new(),
+        }
+    }
+
+    /// Save `path` (relative to the root) before it is first written, along
+    /// with which of its parent directories do not exist yet
+    pub fn save(&mut self, path: &str) -> Result<()> {
+        if self.saved.contains_key(path) {
+            return Ok(());
+        }
+        let full = self.root.join(path);
+        let mut missing: Vec<PathBuf> = full
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| dir.starts_with(&self.root) && !dir.exists())
+            .map(Path::to_path_buf)
+            .collect();
+        missing.reverse();
+        for dir in missing {
+            if !self.created_dirs.contains(&dir) {
+                self.created_dirs.push(dir);
+            }
+        }
+        self.saved.insert(path.to_string(), PreImage::read(&full)?);
+        Ok(())
+    }
+
+    /// Put every saved path back as it was and delete the directories the
+    /// apply created. Paths that are already as they were are left alone.
+    pub fn restore(self) -> Cleanup {
+        let mut cleanup = Cleanup::default();
+        for (path, before) in &self.saved {
+            let full = self.root.join(path);
+            let now = PreImage::read(&full).unwrap_or(PreImage::Absent);
+            if &now == before {
+                continue;
+            }
+            match before.write(&full) {
+                Ok(()) if *before == PreImage::Absent => cleanup.removed.push(path.clone()),
+                Ok(()) => cleanup.restored.push(path.clone()),
+                Err(e) => cleanup.failed.push(format!("{}: {}", path, e)),
+            }
+        }
+        // Innermost first, and only what is empty again
+        for dir in self.created_dirs.iter().rev() {
+            if std::fs::remove_dir(dir).is_ok() {
+                let relative = dir.strip_prefix(&self.root).unwrap_or(dir);
+                cleanup
+                    .removed
+                    .push(format!("{}/", relative.to_string_lossy()));
+            }
+        }
+        cleanup
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn restores_written_files_and_removes_created_ones() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        std::fs::write(root.join("kept.txt"), "kept\n").unwrap();
+        std::fs::write(root.join("edited.txt"), "before\n").unwrap();
+        std::fs::write(root.join("deleted.txt"), "gone\n").unwrap();
+
+        let mut saved = PreImages::new(root);
+        for path in [
+            "kept.txt",
+            "edited.txt",
+            "deleted.txt",
+            "new/dir/created.txt",
+        ] {
+            saved.save(path).unwrap();
+        }
+        std::fs::write(root.join("edited.txt"), "after\n").unwrap();
+        std::fs::remove_file(root.join("deleted.txt")).unwrap();
+        std::fs::create_dir_all(root.join("new/dir")).unwrap();
+        std::fs::write(root.join("new/dir/created.txt"), "new\n").unwrap();
+
+        let cleanup = saved.restore();
+        assert_eq!(cleanup.restored, vec!["deleted.txt", "edited.txt"]);
+        assert_eq!(
+            cleanup.removed,
+            vec!["new/dir/created.txt", "new/dir/", "new/"]
+        );
+        assert!(cleanup.failed.is_empty());
+        assert_eq!(
+            std::fs::read_to_string(root.join("edited.txt")).unwrap(),
+            "before\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(root.join("deleted.txt")).unwrap(),
+            "gone\n"
+        );
+        assert!(!root.join("new").exists());
+        assert_eq!(
+            cleanup.to_string(),
+            "restored deleted.txt, edited.txt; removed new/dir/created.txt, new/dir/, new/"
+        );
+    }
+}
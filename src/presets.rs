@@ -0,0 +1,73 @@
+// ABOUTME: Built-in invariant presets (`preset = ["rust-basic"]` in the manifest)
+// ABOUTME: Each expands to build, test, lint, and format-check invariants for one toolchain
+
+use serde::Serialize;
+
+/// A named set of default invariants
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Preset {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Invariant names and commands, in the order they are listed
+    pub invariants: &'static [(&'static str, &'static str)],
+}
+
+pub const PRESETS: &[Preset] = &[
+    Preset {
+        name: "rust-basic",
+        description: "Cargo workspace: build, test, clippy, rustfmt",
+        invariants: &[
+            ("rust_build", "cargo build --workspace"),
+            ("rust_tests", "cargo test --workspace"),
+            (
+                "rust_lint",
+                "cargo clippy --workspace --all-targets -- -D warnings",
+            ),
+            ("rust_fmt", "cargo fmt --all -- --check"),
+        ],
+    },
+    Preset {
+        name: "node-basic",
+        description: "npm package: build and lint scripts if present, test, prettier",
+        invariants: &[
+            ("node_build", "npm run build --if-present"),
+            ("node_tests", "npm test"),
+            ("node_lint", "npm run lint --if-present"),
+            ("node_fmt", "npx --no-install prettier --check ."),
+        ],
+    },
+    Preset {
+        name: "python-basic",
+        description: "Python project: byte-compile, pytest, ruff lint and format check",
+        invariants: &[
+            ("python_build", "python -m compileall -q ."),
+            ("python_tests", "pytest -q"),
+            ("python_lint", "ruff check ."),
+            ("python_fmt", "ruff format --check ."),
+        ],
+    },
+];
+
+/// The preset called `name`
+pub fn find(name: &str) -> Option<&'static Preset> {
+    PRESETS.iter().find(|p| p.name == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_have_unique_invariant_names() {
+        let mut names: Vec<&str> = PRESETS
+            .iter()
+            .flat_map(|p| p.invariants.iter().map(|(name, _)| *name))
+            .collect();
+        let count = names.len();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), count);
+        assert_eq!(find("rust-basic").unwrap().invariants.len(), 4);
+        assert!(find("go-basic").is_none());
+    }
+}
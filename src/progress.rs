@@ -0,0 +1,146 @@
+// ABOUTME: Machine-readable progress records for long-running operations (--progress jsonl)
+// ABOUTME: Emits throttled phase/done/total/ETA lines on stderr, leaving stdout for the result
+
+use serde::Serialize;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// Whether progress records are written at all; off unless the CLI enables it
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Minimum time between two records of the same phase
+const EMIT_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Turn progress reporting on for the rest of the process
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// One progress line
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProgressRecord {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub phase: String,
+    pub done: usize,
+    /// None when the amount of work is not known up front
+    pub total: Option<usize>,
+    pub elapsed_ms: u64,
+    /// Estimated time left, once at least one item is done
+    pub eta_ms: Option<u64>,
+    pub finished: bool,
+}
+
+/// Progress of one phase of work (e.g. "scan", "churn", "invariants")
+pub struct Progress {
+    phase: String,
+    total: Option<usize>,
+    done: usize,
+    started: Instant,
+    last_emit: Option<Instant>,
+}
+
+impl Progress {
+    /// Start a phase and report it
+    pub fn start(phase: impl Into<String>, total: Option<usize>) -> Self {
+        let mut progress = Self {
+            phase: phase.into(),
+            total,
+            done: 0,
+            started: Instant::now(),
+            last_emit: None,
+        };
+        progress.emit(false);
+        progress
+    }
+
+    /// One more item done
+    pub fn tick(&mut self) {
+        self.advance(1);
+    }
+
+    /// `n` more items done; reported at most every EMIT_INTERVAL
+    pub fn advance(&mut self, n: usize) {
+        self.done += n;
+        let due = self
+            .last_emit
+            .is_none_or(|last| last.elapsed() >= EMIT_INTERVAL);
+        if due {
+            self.emit(false);
+        }
+    }
+
+    /// End the phase, always reporting the final count
+    pub fn finish(mut self) {
+        self.emit(true);
+    }
+
+    /// The record describing the current state
+    pub fn record(&self, finished: bool) -> ProgressRecord {
+        let elapsed = self.started.elapsed();
+        ProgressRecord {
+            kind: "progress",
+            phase: self.phase.clone(),
+            done: self.done,
+            total: self.total,
+            elapsed_ms: elapsed.as_millis() as u64,
+            eta_ms: eta(elapsed, self.done, self.total).map(|d| d.as_millis() as u64),
+            finished,
+        }
+    }
+
+    fn emit(&mut self, finished: bool) {
+        if !is_enabled() {
+            return;
+        }
+        self.last_emit = Some(Instant::now());
+        if let Ok(line) = serde_json::to_string(&self.record(finished)) {
+            let mut stderr = std::io::stderr().lock();
+            let _ = writeln!(stderr, "{}", line);
+        }
+    }
+}
+
+/// Linear estimate of the time left from the average time per item so far
+pub fn eta(elapsed: Duration, done: usize, total: Option<usize>) -> Option<Duration> {
+    let total = total?;
+    if done == 0 {
+        return None;
+    }
+    let remaining = total.saturating_sub(done) as u32;
+    Some(elapsed / done as u32 * remaining)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eta_is_linear_in_remaining_items() {
+        let elapsed = Duration::from_secs(10);
+        assert_eq!(eta(elapsed, 5, Some(10)), Some(Duration::from_secs(10)));
+        assert_eq!(eta(elapsed, 10, Some(10)), Some(Duration::ZERO));
+        assert_eq!(eta(elapsed, 0, Some(10)), None);
+        assert_eq!(eta(elapsed, 3, None), None);
+    }
+
+    #[test]
+    fn records_track_counts() {
+        let mut progress = Progress::start("scan", Some(3));
+        progress.tick();
+        progress.advance(2);
+
+        let record = progress.record(true);
+        assert_eq!(record.phase, "scan");
+        assert_eq!((record.done, record.total), (3, Some(3)));
+        assert!(record.finished);
+
+        let json = serde_json::to_value(&record).unwrap();
+        assert_eq!(json["type"], "progress");
+    }
+}
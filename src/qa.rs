@@ -0,0 +1,255 @@
+// ABOUTME: Answers about the codebase recorded with the repo state they were true at (.agent/qa/<id>.json)
+// ABOUTME: Keyword search over them, and staleness from the files each answer rests on
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+
+/// A file an answer rests on, with its content hash when the answer was recorded
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileHash {
+    pub path: String,
+    pub hash: String,
+}
+
+/// A question and its answer, as true at `tree`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    pub id: String,
+    pub question: String,
+    pub answer: String,
+    /// Files the answer depends on; when empty, any change to the tree
+    /// makes it stale
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<FileHash>,
+    /// Working-copy tree ID when recorded
+    pub tree: String,
+    pub change_id: String,
+    pub author: String,
+    pub recorded_at: String,
+}
+
+impl Entry {
+    /// A new entry, with an ID derived from its contents
+    pub fn new(
+        question: impl Into<String>,
+        answer: impl Into<String>,
+        files: Vec<FileHash>,
+        tree: impl Into<String>,
+        change_id: impl Into<String>,
+        author: impl Into<String>,
+        recorded_at: impl Into<String>,
+    ) -> Self {
+        let question = question.into();
+        let answer = answer.into();
+        let tree = tree.into();
+        let recorded_at = recorded_at.into();
+
+        let mut hasher = Sha256::new();
+        for part in [&question, &answer, &tree, &recorded_at] {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+        hasher.update(std::process::id().to_le_bytes());
+        let id = hex::encode(hasher.finalize())[..12].to_string();
+
+        Self {
+            id,
+            question,
+            answer,
+            files,
+            tree,
+            change_id: change_id.into(),
+            author: author.into(),
+            recorded_at,
+        }
+    }
+
+    /// Files whose content no longer matches, given the current hash of a
+    /// path (`None` when it is gone). Entries without files compare trees,
+    /// reporting `*` when `tree` differs.
+    pub fn changed_files(
+        &self,
+        tree: &str,
+        mut current_hash: impl FnMut(&str) -> Option<String>,
+    ) -> Vec<String> {
+        if self.files.is_empty() {
+            return if self.tree == tree {
+                Vec::new()
+            } else {
+                vec!["*".to_string()]
+            };
+        }
+        self.files
+            .iter()
+            .filter(|f| current_hash(&f.path).as_deref() != Some(f.hash.as_str()))
+            .map(|f| f.path.clone())
+            .collect()
+    }
+
+    /// Whether two questions are the same once case and spacing are ignored
+    pub fn same_question(&self, question: &str) -> bool {
+        normalize(&self.question) == normalize(question)
+    }
+}
+
+fn normalize(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Lowercase words of two or more letters or digits
+fn terms(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|w| w.chars().count() >= 2)
+        .map(|w| w.to_lowercase())
+        .collect()
+}
+
+/// How well an entry matches a query: each query term found in the
+/// question counts 2, in the answer 1
+pub fn score(entry: &Entry, query: &str) -> usize {
+    let question = terms(&entry.question);
+    let answer = terms(&entry.answer);
+    let mut query = terms(query);
+    query.sort();
+    query.dedup();
+    query
+        .iter()
+        .map(|term| {
+            if question.contains(term) {
+                2
+            } else if answer.contains(term) {
+                1
+            } else {
+                0
+            }
+        })
+        .sum()
+}
+
+/// Paths named in free text (`src/api.rs`, `src/api.rs::process`,
+/// `src/api.rs:42`, in backticks or not) for which `exists` holds
+pub fn mentioned_files(text: &str, exists: impl Fn(&str) -> bool) -> Vec<String> {
+    let mut files: Vec<String> = Vec::new();
+    for word in text.split_whitespace() {
+        let word = word.trim_matches(|c: char| "`'\"()[]{},;!?".contains(c));
+        let word = word.split("::").next().unwrap_or(word);
+        let word = word.split(':').next().unwrap_or(word);
+        let word = word.trim_end_matches('.').trim_start_matches("./");
+        if word.contains('.') && !files.iter().any(|f| f == word) && exists(word) {
+            files.push(word.to_string());
+        }
+    }
+    files
+}
+
+/// The QA directory of a repository
+pub struct QaStore {
+    dir: PathBuf,
+}
+
+impl QaStore {
+    pub const DEFAULT_DIR: &'static str = ".agent/qa";
+
+    pub fn open(repo_root: impl AsRef<Path>) -> Self {
+        Self {
+            dir: repo_root.as_ref().join(Self::DEFAULT_DIR),
+        }
+    }
+
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+
+    pub fn save(&self, entry: &Entry) -> Result<()> {
+        crate::agent_dir::write_json(&self.path(&entry.id), entry)
+    }
+
+    pub fn remove(&self, id: &str) -> Result<()> {
+        std::fs::remove_file(self.path(id))?;
+        Ok(())
+    }
+
+    /// All entries, newest first
+    pub fn list(&self) -> Result<Vec<Entry>> {
+        let mut entries = Vec::new();
+        let Ok(dir) = std::fs::read_dir(&self.dir) else {
+            return Ok(entries);
+        };
+        for item in dir.flatten() {
+            let path = item.path();
+            if path.extension().and_then(|x| x.to_str()) != Some("json") {
+                continue;
+            }
+            let content = std::fs::read_to_string(&path)?;
+            entries.push(serde_json::from_str(&content).map_err(|e| Error::Io {
+                message: format!("corrupt QA entry {}: {}", path.display(), e),
+            })?);
+        }
+        entries.sort_by(|a: &Entry, b: &Entry| {
+            b.recorded_at
+                .cmp(&a.recorded_at)
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(question: &str, answer: &str, files: Vec<FileHash>) -> Entry {
+        Entry::new(
+            question,
+            answer,
+            files,
+            "tree1",
+            "change1",
+            "agent-1",
+            "2026-01-01T00:00:00Z",
+        )
+    }
+
+    #[test]
+    fn search_ranks_question_terms_and_staleness_follows_files() {
+        let retries = entry(
+            "Where are HTTP retries configured?",
+            "In `src/net/client.rs::with_retries`, three attempts",
+            vec![FileHash {
+                path: "src/net/client.rs".into(),
+                hash: "h1".into(),
+            }],
+        );
+        let schema = entry(
+            "How is the schema migrated?",
+            "Retries are not used",
+            vec![],
+        );
+
+        assert_eq!(score(&retries, "retries config"), 2);
+        assert_eq!(score(&schema, "retries config"), 1);
+        assert_eq!(score(&schema, "unrelated"), 0);
+        assert!(retries.same_question("  where are http RETRIES configured? "));
+
+        assert!(retries
+            .changed_files("tree2", |_| Some("h1".into()))
+            .is_empty());
+        assert_eq!(
+            retries.changed_files("tree1", |_| None),
+            vec!["src/net/client.rs"]
+        );
+        assert!(schema.changed_files("tree1", |_| None).is_empty());
+        assert_eq!(schema.changed_files("tree2", |_| None), vec!["*"]);
+
+        let files = mentioned_files(&format!("{} {}", retries.question, retries.answer), |p| {
+            p == "src/net/client.rs"
+        });
+        assert_eq!(files, vec!["src/net/client.rs"]);
+    }
+}
@@ -0,0 +1,182 @@
+// ABOUTME: Named queries (.agent/queries.toml): read-only agentjj command lines saved under a name
+// ABOUTME: `query save/run/list`; the file is tracked, so a team's common investigations are shared
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+
+/// A saved command line, e.g. `change list --path src/api --since 7d`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NamedQuery {
+    pub command: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+impl NamedQuery {
+    /// The command line as arguments, without a leading `agentjj`
+    pub fn args(&self) -> Result<Vec<String>> {
+        split(&self.command)
+    }
+}
+
+/// Split a command line the way a POSIX shell would, dropping a leading
+/// `agentjj`
+pub fn split(command: &str) -> Result<Vec<String>> {
+    let mut args = shlex::split(command).ok_or_else(|| Error::Config {
+        message: format!("unbalanced quotes in query: {}", command),
+    })?;
+    if args.first().is_some_and(|arg| arg == "agentjj") {
+        args.remove(0);
+    }
+    if args.is_empty() {
+        return Err(Error::Config {
+            message: "a query needs a command, e.g. 'change list --since 7d'".into(),
+        });
+    }
+    Ok(args)
+}
+
+/// Arguments back as a command line, quoted where a shell would need it
+pub fn join(args: &[String]) -> String {
+    shlex::try_join(args.iter().map(String::as_str)).unwrap_or_else(|_| args.join(" "))
+}
+
+/// A query as listed
+#[derive(Debug, Clone, Serialize)]
+pub struct QueryInfo {
+    pub name: String,
+    #[serde(flatten)]
+    pub query: NamedQuery,
+}
+
+/// The contents of `.agent/queries.toml`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Queries {
+    #[serde(default)]
+    pub queries: BTreeMap<String, NamedQuery>,
+}
+
+impl Queries {
+    pub const DEFAULT_PATH: &'static str = ".agent/queries.toml";
+
+    fn path(repo_root: &Path) -> PathBuf {
+        repo_root.join(Self::DEFAULT_PATH)
+    }
+
+    /// The saved queries (none when the file does not exist)
+    pub fn load(repo_root: impl AsRef<Path>) -> Result<Self> {
+        let path = Self::path(repo_root.as_ref());
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            return Ok(Self::default());
+        };
+        toml::from_str(&content).map_err(|e| Error::Config {
+            message: format!("invalid {}: {}", Self::DEFAULT_PATH, e),
+        })
+    }
+
+    pub fn save(&self, repo_root: impl AsRef<Path>) -> Result<()> {
+        let path = Self::path(repo_root.as_ref());
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let content = toml::to_string_pretty(self).map_err(|e| Error::Config {
+            message: e.to_string(),
+        })?;
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+
+    /// Add or replace a query; returns the one it replaced
+    pub fn insert(&mut self, name: &str, query: NamedQuery) -> Result<Option<NamedQuery>> {
+        let valid = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(Error::Config {
+                message: format!(
+                    "invalid query name '{}' - use letters, digits, '-' and '_'",
+                    name
+                ),
+            });
+        }
+        split(&query.command)?;
+        Ok(self.queries.insert(name.to_string(), query))
+    }
+
+    pub fn get(&self, name: &str) -> Result<&NamedQuery> {
+        self.queries.get(name).ok_or_else(|| Error::Config {
+            message: if self.queries.is_empty() {
+                "no saved queries - add one with `agentjj query save <name> '<command>'`".into()
+            } else {
+                format!(
+                    "'{}' is not a saved query (available: {})",
+                    name,
+                    self.queries
+                        .keys()
+                        .map(String::as_str)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            },
+        })
+    }
+
+    pub fn list(&self) -> Vec<QueryInfo> {
+        self.queries
+            .iter()
+            .map(|(name, query)| QueryInfo {
+                name: name.clone(),
+                query: query.clone(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn saves_and_splits_named_queries() {
+        let tmp = TempDir::new().unwrap();
+        let mut queries = Queries::load(tmp.path()).unwrap();
+        assert!(queries.list().is_empty());
+
+        let query = NamedQuery {
+            command: "agentjj change list --path 'src/api dir' --since 7d".into(),
+            description: Some("API behaviour changes this week".into()),
+        };
+        assert!(queries
+            .insert("recent-api-changes", query.clone())
+            .unwrap()
+            .is_none());
+        assert!(queries.insert("bad name", query.clone()).is_err());
+        let unbalanced = NamedQuery {
+            command: "files --glob 'src/*".into(),
+            description: None,
+        };
+        assert!(queries.insert("open-quote", unbalanced).is_err());
+        queries.save(tmp.path()).unwrap();
+
+        let loaded = Queries::load(tmp.path()).unwrap();
+        assert_eq!(
+            loaded.get("recent-api-changes").unwrap().args().unwrap(),
+            vec!["change", "list", "--path", "src/api dir", "--since", "7d"]
+        );
+        assert_eq!(
+            join(&loaded.get("recent-api-changes").unwrap().args().unwrap()),
+            "change list --path 'src/api dir' --since 7d"
+        );
+        let missing = loaded.get("missing").unwrap_err().to_string();
+        assert!(
+            missing.contains("available: recent-api-changes"),
+            "{}",
+            missing
+        );
+    }
+}
@@ -0,0 +1,266 @@
+// ABOUTME: `reconcile <a> <b>`: combine two competing changes into one, resolving overlaps by strategy
+// ABOUTME: Hunk-level conflict reports and resolution; repo.rs builds the merge change itself
+
+use jj_lib::files::{FileMergeHunkLevel, MergeResult};
+use jj_lib::merge::{Merge, SameChange};
+use jj_lib::tree_merge::MergeOptions;
+use serde::Serialize;
+
+use crate::overlay::Entry;
+
+/// How overlapping edits are settled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Strategy {
+    /// Take `a`'s side of every conflicting hunk
+    PreferA,
+    /// Take `b`'s side of every conflicting hunk
+    PreferB,
+    /// Keep both sides, `a` first
+    Union,
+}
+
+impl Strategy {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "prefer-a" => Some(Self::PreferA),
+            "prefer-b" => Some(Self::PreferB),
+            "union" => Some(Self::Union),
+            _ => None,
+        }
+    }
+}
+
+/// What kind of disagreement a conflicted path has
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictKind {
+    /// Both edited overlapping lines of a text file
+    Content,
+    /// One side deleted the file, the other changed it
+    Delete,
+    /// Binary content, symlinks, or a file on one side and a symlink on the other
+    Other,
+}
+
+/// One overlapping hunk: the text each side has where they disagree
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConflictHunk {
+    /// 1-based line in `a`'s version where the hunk starts
+    pub line: usize,
+    pub base: String,
+    pub a: String,
+    pub b: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileConflict {
+    pub path: String,
+    pub kind: ConflictKind,
+    /// How it was settled; absent when left as a conflict in the change
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolved_with: Option<Strategy>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub hunks: Vec<ConflictHunk>,
+}
+
+/// The change `reconcile` created
+#[derive(Debug, Clone, Serialize)]
+pub struct Reconciliation {
+    pub change_id: String,
+    pub commit_id: String,
+    /// Paths where the two changes overlapped
+    pub conflicts: Vec<FileConflict>,
+}
+
+impl Reconciliation {
+    /// Conflicts left in the change for someone to resolve
+    pub fn unresolved(&self) -> Vec<&str> {
+        self.conflicts
+            .iter()
+            .filter(|c| c.resolved_with.is_none())
+            .map(|c| c.path.as_str())
+            .collect()
+    }
+}
+
+fn text(entry: Option<&Entry>) -> Option<&[u8]> {
+    match entry {
+        Some(Entry::File { content, .. }) if !content.contains(&0) => Some(content),
+        _ => None,
+    }
+}
+
+fn line_count(bytes: &[u8]) -> usize {
+    bytes.split_inclusive(|b| *b == b'\n').count()
+}
+
+/// The executable bit of the preferred side: `b`'s for prefer-b, else `a`'s
+fn executable(strategy: Option<Strategy>, a: Option<&Entry>, b: Option<&Entry>) -> bool {
+    let side = if strategy == Some(Strategy::PreferB) {
+        b
+    } else {
+        a
+    };
+    matches!(
+        side,
+        Some(Entry::File {
+            executable: true,
+            ..
+        })
+    )
+}
+
+/// Report a conflicted path and, given a strategy, settle it. The second
+/// value is the resolved entry (`Some(None)` deletes the path), or `None`
+/// when the conflict stays.
+pub fn resolve(
+    path: &str,
+    base: Option<&Entry>,
+    a: Option<&Entry>,
+    b: Option<&Entry>,
+    strategy: Option<Strategy>,
+) -> (FileConflict, Option<Option<Entry>>) {
+    let mut conflict = FileConflict {
+        path: path.to_string(),
+        kind: ConflictKind::Other,
+        resolved_with: strategy,
+        hunks: Vec::new(),
+    };
+
+    let (Some(a_text), Some(b_text)) = (text(a), text(b)) else {
+        if a.is_none() || b.is_none() {
+            conflict.kind = ConflictKind::Delete;
+        }
+        // Whole-file choice: a union keeps whichever side still has the file
+        let chosen = strategy.map(|s| match s {
+            Strategy::PreferA => a.cloned(),
+            Strategy::PreferB => b.cloned(),
+            Strategy::Union => a.or(b).cloned(),
+        });
+        return (conflict, chosen);
+    };
+    conflict.kind = ConflictKind::Content;
+    let base_text = text(base).unwrap_or_default();
+
+    let options = MergeOptions {
+        hunk_level: FileMergeHunkLevel::Line,
+        same_change: SameChange::Accept,
+    };
+    let hunks = match jj_lib::files::merge_hunks(
+        &Merge::from_vec(vec![a_text, base_text, b_text]),
+        &options,
+    ) {
+        MergeResult::Resolved(content) => {
+            // Only the file mode disagreed
+            let entry = Entry::File {
+                content: content.to_vec(),
+                executable: executable(strategy, a, b),
+            };
+            return (conflict, strategy.map(|_| Some(entry)));
+        }
+        MergeResult::Conflict(hunks) => hunks,
+    };
+
+    let mut merged = Vec::new();
+    let mut line = 1;
+    for hunk in &hunks {
+        if let Some(resolved) = hunk.as_resolved() {
+            merged.extend_from_slice(resolved);
+            line += line_count(resolved);
+            continue;
+        }
+        let side = |i: usize| hunk.get_add(i).map(|s| s.to_vec()).unwrap_or_default();
+        let (a_side, b_side) = (side(0), side(hunk.num_sides() - 1));
+        let base_side = hunk.get_remove(0).map(|s| s.to_vec()).unwrap_or_default();
+        conflict.hunks.push(ConflictHunk {
+            line,
+            base: String::from_utf8_lossy(&base_side).to_string(),
+            a: String::from_utf8_lossy(&a_side).to_string(),
+            b: String::from_utf8_lossy(&b_side).to_string(),
+        });
+        line += line_count(&a_side);
+        match strategy {
+            Some(Strategy::PreferA) => merged.extend_from_slice(&a_side),
+            Some(Strategy::PreferB) => merged.extend_from_slice(&b_side),
+            Some(Strategy::Union) => {
+                merged.extend_from_slice(&a_side);
+                merged.extend_from_slice(&b_side);
+            }
+            None => {}
+        }
+    }
+
+    let entry = Entry::File {
+        content: merged,
+        executable: executable(strategy, a, b),
+    };
+    (conflict, strategy.map(|_| Some(entry)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(content: &str) -> Entry {
+        Entry::File {
+            content: content.as_bytes().to_vec(),
+            executable: false,
+        }
+    }
+
+    fn content(entry: Option<Option<Entry>>) -> String {
+        match entry {
+            Some(Some(Entry::File { content, .. })) => String::from_utf8(content).unwrap(),
+            other => panic!("expected a file, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reports_and_resolves_overlapping_hunks() {
+        let base = file("a\nretries = 1\nz\n");
+        let a = file("a\nretries = 3\nz\n");
+        let b = file("a\nretries = 5\nz\n");
+
+        let (conflict, resolved) = resolve("cfg.py", Some(&base), Some(&a), Some(&b), None);
+        assert_eq!(conflict.kind, ConflictKind::Content);
+        assert_eq!(
+            conflict.hunks,
+            vec![ConflictHunk {
+                line: 2,
+                base: "retries = 1\n".into(),
+                a: "retries = 3\n".into(),
+                b: "retries = 5\n".into(),
+            }]
+        );
+        assert!(resolved.is_none());
+
+        let resolve_with = |s| resolve("cfg.py", Some(&base), Some(&a), Some(&b), Some(s)).1;
+        assert_eq!(
+            content(resolve_with(Strategy::PreferA)),
+            "a\nretries = 3\nz\n"
+        );
+        assert_eq!(
+            content(resolve_with(Strategy::PreferB)),
+            "a\nretries = 5\nz\n"
+        );
+        assert_eq!(
+            content(resolve_with(Strategy::Union)),
+            "a\nretries = 3\nretries = 5\nz\n"
+        );
+
+        // Deleted on one side: a union keeps the file
+        let (conflict, resolved) =
+            resolve("old.py", Some(&base), None, Some(&b), Some(Strategy::Union));
+        assert_eq!(conflict.kind, ConflictKind::Delete);
+        assert_eq!(content(resolved), "a\nretries = 5\nz\n");
+        let (_, resolved) = resolve(
+            "old.py",
+            Some(&base),
+            None,
+            Some(&b),
+            Some(Strategy::PreferA),
+        );
+        assert_eq!(resolved, Some(None));
+    }
+}
@@ -0,0 +1,249 @@
+// ABOUTME: Divergence between git refs and jj bookmarks in colocated repositories (doctor --sync-check)
+// ABOUTME: Both sides are compared to what jj last imported from git, so each mismatch says which side moved
+
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Both sides of a colocated repository's refs, as commit IDs
+#[derive(Debug, Clone, Default)]
+pub struct RefSides {
+    /// Git branches (`refs/heads/*`)
+    pub git: BTreeMap<String, String>,
+    /// jj local bookmarks; `None` when the bookmark is conflicted
+    pub jj: BTreeMap<String, Option<String>>,
+    /// Each git branch as jj last imported or exported it
+    pub last_seen: BTreeMap<String, String>,
+    /// The commit git HEAD resolves to
+    pub git_head: Option<String>,
+    /// The parent of jj's working-copy commit (`@-`), which HEAD should be
+    pub wc_parent: Option<String>,
+    /// HEAD is an ancestor of `@-`: left behind by commits made through jj,
+    /// not moved by git
+    pub head_behind: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DivergenceKind {
+    /// Branch and bookmark point at different commits
+    BranchMoved,
+    /// A git branch jj has no bookmark for
+    GitOnly,
+    /// A jj bookmark git has no branch for
+    JjOnly,
+    /// The bookmark is conflicted in jj (both sides moved it)
+    Conflicted,
+    /// Git HEAD is not the working copy's parent
+    HeadMoved,
+}
+
+/// One ref the two sides disagree on
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Divergence {
+    pub kind: DivergenceKind,
+    /// Branch name, or `HEAD`
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jj: Option<String>,
+    /// Which side changed it since jj last synced: `git`, `jj`, or `both`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub moved_by: Option<&'static str>,
+}
+
+/// Result of comparing the two sides
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SyncReport {
+    pub in_sync: bool,
+    pub divergences: Vec<Divergence>,
+    /// Command that reconciles them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fix: Option<String>,
+}
+
+/// What `agentjj sync` reconciled
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncOutcome {
+    pub refs_only: bool,
+    /// Divergences the sync resolved
+    pub fixed: Vec<Divergence>,
+    /// What still disagrees afterwards, such as a bookmark both sides moved
+    pub remaining: Vec<Divergence>,
+    /// Bookmarks jj could not export to git, with why
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub failed: Vec<String>,
+    /// Commit the working copy was rebased onto, when git HEAD had moved
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rebased_onto: Option<String>,
+}
+
+impl DivergenceKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DivergenceKind::BranchMoved => "branch_moved",
+            DivergenceKind::GitOnly => "git_only",
+            DivergenceKind::JjOnly => "jj_only",
+            DivergenceKind::Conflicted => "conflicted",
+            DivergenceKind::HeadMoved => "head_moved",
+        }
+    }
+}
+
+impl SyncOutcome {
+    pub fn new(before: SyncReport, after: SyncReport, refs_only: bool) -> Self {
+        let fixed = before
+            .divergences
+            .into_iter()
+            .filter(|d| {
+                !after
+                    .divergences
+                    .iter()
+                    .any(|a| a.name == d.name && a.kind == d.kind)
+            })
+            .collect();
+        Self {
+            refs_only,
+            fixed,
+            remaining: after.divergences,
+            failed: Vec::new(),
+            rebased_onto: None,
+        }
+    }
+}
+
+fn moved_by(git_moved: bool, jj_moved: bool) -> Option<&'static str> {
+    match (git_moved, jj_moved) {
+        (true, true) => Some("both"),
+        (true, false) => Some("git"),
+        (false, true) => Some("jj"),
+        (false, false) => None,
+    }
+}
+
+/// Compare git branches with jj bookmarks, and git HEAD with `@-`
+pub fn compare(sides: &RefSides) -> SyncReport {
+    let names: BTreeSet<&String> = sides.git.keys().chain(sides.jj.keys()).collect();
+    let mut divergences = Vec::new();
+    for name in names {
+        let last = sides.last_seen.get(name);
+        let git = sides.git.get(name);
+        let (kind, moved) = match (git, sides.jj.get(name)) {
+            (_, Some(None)) => (DivergenceKind::Conflicted, Some("both")),
+            (Some(git), Some(Some(jj))) if git != jj => (
+                DivergenceKind::BranchMoved,
+                moved_by(last != Some(git), last != Some(jj)),
+            ),
+            (Some(_), None) => (
+                DivergenceKind::GitOnly,
+                Some(if last.is_some() { "jj" } else { "git" }),
+            ),
+            (None, Some(Some(_))) => (
+                DivergenceKind::JjOnly,
+                Some(if last.is_some() { "git" } else { "jj" }),
+            ),
+            _ => continue,
+        };
+        divergences.push(Divergence {
+            kind,
+            name: name.clone(),
+            git: git.cloned(),
+            jj: sides.jj.get(name).cloned().flatten(),
+            moved_by: moved,
+        });
+    }
+
+    let refs_diverged = !divergences.is_empty();
+    let head_moved =
+        sides.git_head.is_some() && sides.git_head != sides.wc_parent && !sides.head_behind;
+    if head_moved {
+        divergences.push(Divergence {
+            kind: DivergenceKind::HeadMoved,
+            name: "HEAD".to_string(),
+            git: sides.git_head.clone(),
+            jj: sides.wc_parent.clone(),
+            moved_by: Some("git"),
+        });
+    }
+
+    let fix = match (refs_diverged, head_moved) {
+        (_, true) => Some("agentjj sync".to_string()),
+        (true, false) => Some("agentjj sync --refs-only".to_string()),
+        (false, false) => None,
+    };
+    SyncReport {
+        in_sync: divergences.is_empty(),
+        divergences,
+        fix,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn reports_which_side_moved_each_ref() {
+        let mut sides = RefSides {
+            git: map(&[("main", "a1"), ("hotfix", "b1"), ("both", "c2")]),
+            last_seen: map(&[("main", "a0"), ("gone", "d1"), ("both", "c0")]),
+            git_head: Some("a1".into()),
+            wc_parent: Some("a1".into()),
+            ..Default::default()
+        };
+        sides.jj = [
+            ("main", Some("a0")),
+            ("gone", Some("d1")),
+            ("both", Some("c1")),
+            ("feature", Some("e1")),
+            ("tangled", None),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.map(str::to_string)))
+        .collect();
+
+        let report = compare(&sides);
+        assert!(!report.in_sync);
+        let found: Vec<(&str, DivergenceKind, Option<&str>)> = report
+            .divergences
+            .iter()
+            .map(|d| (d.name.as_str(), d.kind, d.moved_by))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("both", DivergenceKind::BranchMoved, Some("both")),
+                ("feature", DivergenceKind::JjOnly, Some("jj")),
+                ("gone", DivergenceKind::JjOnly, Some("git")),
+                ("hotfix", DivergenceKind::GitOnly, Some("git")),
+                ("main", DivergenceKind::BranchMoved, Some("git")),
+                ("tangled", DivergenceKind::Conflicted, Some("both")),
+            ]
+        );
+        assert_eq!(report.fix.as_deref(), Some("agentjj sync --refs-only"));
+
+        // HEAD moved by an outside `git commit`
+        let sides = RefSides {
+            git_head: Some("f2".into()),
+            wc_parent: Some("f1".into()),
+            ..Default::default()
+        };
+        let report = compare(&sides);
+        assert_eq!(report.divergences[0].kind, DivergenceKind::HeadMoved);
+        assert_eq!(report.fix.as_deref(), Some("agentjj sync"));
+        // HEAD merely behind `@-` is not a divergence
+        let sides = RefSides {
+            head_behind: true,
+            ..sides
+        };
+        assert!(compare(&sides).in_sync);
+        assert!(compare(&RefSides::default()).in_sync);
+    }
+}
@@ -1,9 +1,10 @@
 // ABOUTME: Repository operations using jj-lib directly
 // ABOUTME: Provides high-level operations for agent workflows without requiring jj CLI
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 use jj_lib::backend::CommitId;
@@ -19,10 +20,40 @@ use jj_lib::working_copy::SnapshotOptions;
 use jj_lib::workspace::{default_working_copy_factories, WorkingCopyFactories, Workspace};
 use pollster::FutureExt as _;
 
-use crate::change::{ChangeCategory, ChangeType, InvariantStatus, InvariantsResult, TypedChange};
+use crate::agentignore::AgentIgnore;
+use crate::approval::{Approval, ApprovalStatus, ApprovalStore};
+use crate::blobcache::BlobCache;
+use crate::change::{
+    ChangeCategory, ChangeMetrics, ChangeType, InvariantStatus, InvariantsResult, TypedChange,
+    UnmetDependency,
+};
+use crate::config::Config;
+use crate::custom::StepResult;
+use crate::encoding::TextFormat;
+use crate::env::Bookmark;
 use crate::error::{ConflictDetail, Error, Result};
-use crate::intent::{ChangeSpec, FileOperation, Intent, IntentResult};
-use crate::manifest::{InvariantTrigger, Manifest};
+use crate::fix::{FixReport, FixerFailure};
+use crate::globs::Globs;
+use crate::history::HistoryCache;
+use crate::intent::{ChangeSpec, FileOperation, Intent, IntentResult, StaleFile};
+use crate::manifest::{HookEvent, Invariant, InvariantTrigger, Manifest};
+use crate::overlay::{Entry, Overlay};
+use crate::preimage::PreImages;
+use crate::progress::Progress;
+use crate::refsync::{RefSides, SyncOutcome, SyncReport};
+use crate::secrets::{ResolvedEnv, Secrets};
+use crate::series::{Author, ImportedChange, Series, SeriesImport, SeriesPatch};
+use crate::stash::{StashEntry, StashPop};
+use crate::testrun::{Outcome, TestCommandResult};
+
+/// Whether `Repo::discover` may set up jj in a git repository that has
+/// none. The CLI clears it for read-only commands and `--no-auto-init`.
+static AUTO_COLOCATE: AtomicBool = AtomicBool::new(true);
+
+/// Allow or forbid `Repo::discover` to colocate jj with a plain git repository
+pub fn set_auto_colocate(enabled: bool) {
+    AUTO_COLOCATE.store(enabled, Ordering::Relaxed);
+}
 
 /// A repository handle for agent operations
 pub struct Repo {
@@ -32,8 +63,25 @@ pub struct Repo {
     workspace: Option<Workspace>,
     /// Cached manifest (loaded lazily)
     manifest: Option<Manifest>,
+    /// Cached config (loaded lazily)
+    config: Option<Config>,
+    /// File contents read at revisions (opened lazily)
+    blobs: Option<BlobCache>,
+}
+
+/// The invariant that stopped `run_invariants`
+struct InvariantFailure {
+    name: String,
+    command: String,
+    exit_code: i32,
+    stdout: String,
+    stderr: String,
+    known_flakes: Vec<String>,
 }
 
+/// Every invariant's status, or the one that failed
+type InvariantsRun = std::result::Result<HashMap<String, InvariantStatus>, Box<InvariantFailure>>;
+
 /// Structured log entry for graph commands and other operations.
 #[derive(Debug, Clone)]
 pub struct LogEntry {
@@ -45,6 +93,32 @@ pub struct LogEntry {
     pub timestamp: Option<String>,
     pub author: Option<String>,
     pub full_commit_id: String,
+    /// Already pushed (or configured immutable): see `Repo::immutable_commits`
+    pub immutable: bool,
+}
+
+/// A commit in the working copy's history, from `Repo::history_since`
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub change_id: String,
+    pub commit_id: String,
+    pub description: String,
+    /// Author time, as a `utc_timestamp`
+    pub timestamp: String,
+    /// Author name, else email
+    pub author: String,
+}
+
+/// The change that last touched a line, from `Repo::blame`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineBlame {
+    /// Short change ID (8 hex chars)
+    pub change_id: String,
+    pub commit_id: String,
+    /// Author time, seconds since the Unix epoch
+    pub timestamp: i64,
+    /// The line is new in the working-copy change
+    pub is_working_copy: bool,
 }
 
 /// Operation info for undo and operation history commands.
@@ -54,17 +128,151 @@ pub struct OperationInfo {
     pub description: String,
 }
 
+/// How an operation brought back an earlier repository state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperationEdgeKind {
+    Undo,
+    Restore,
+}
+
+impl OperationEdgeKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Undo => "undo",
+            Self::Restore => "restore",
+        }
+    }
+}
+
+/// A node in the operation DAG, for `graph --kind ops`.
+#[derive(Debug, Clone)]
+pub struct OperationNode {
+    /// Short operation ID (12 hex chars)
+    pub id: String,
+    pub full_id: String,
+    pub description: String,
+    /// Short IDs of the parent operations; more than one after concurrent operations merged
+    pub parents: Vec<String>,
+    /// When the operation finished, `YYYY-MM-DDTHH:MM:SSZ`
+    pub timestamp: String,
+    /// The repository's current operation
+    pub is_head: bool,
+    /// For undo and restore operations: how, and the short ID of the operation restored to
+    pub restores: Option<(OperationEdgeKind, String)>,
+}
+
+impl OperationNode {
+    /// Recognize undo/restore operations by the descriptions agentjj and jj give them
+    fn restores(description: &str) -> Option<(OperationEdgeKind, String)> {
+        let (kind, target) = [
+            ("undo to operation ", OperationEdgeKind::Undo),
+            ("undo operation ", OperationEdgeKind::Undo),
+            ("restore to operation ", OperationEdgeKind::Restore),
+        ]
+        .into_iter()
+        .find_map(|(prefix, kind)| Some((kind, description.strip_prefix(prefix)?)))?;
+        let target = target.split_whitespace().next()?;
+        target
+            .chars()
+            .all(|c| c.is_ascii_hexdigit())
+            .then(|| (kind, target[..12.min(target.len())].to_string()))
+    }
+}
+
+/// How a file differs between two trees.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileChangeKind {
+    Added,
+    Modified,
+    Deleted,
+}
+
+impl FileChangeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileChangeKind::Added => "added",
+            FileChangeKind::Modified => "modified",
+            FileChangeKind::Deleted => "deleted",
+        }
+    }
+}
+
+/// VCS state of a file in the working copy, relative to the working-copy
+/// commit's parent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileStatus {
+    Clean,
+    Modified,
+    Added,
+    Deleted,
+    Conflicted,
+    Ignored,
+    Untracked,
+}
+
+impl FileStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileStatus::Clean => "clean",
+            FileStatus::Modified => "modified",
+            FileStatus::Added => "added",
+            FileStatus::Deleted => "deleted",
+            FileStatus::Conflicted => "conflicted",
+            FileStatus::Ignored => "ignored",
+            FileStatus::Untracked => "untracked",
+        }
+    }
+
+    /// Whether the file differs from what is committed
+    pub fn is_dirty(&self) -> bool {
+        !matches!(self, FileStatus::Clean | FileStatus::Ignored)
+    }
+}
+
+/// Outcome of one hook command.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct HookResult {
+    pub event: HookEvent,
+    pub command: String,
+    pub success: bool,
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Everything that happened in the repository since a recorded operation.
+/// Used by `checkpoint status` to decide whether restoring would lose work.
+#[derive(Debug, Clone)]
+pub struct OperationDiff {
+    /// Whether the recorded operation was found in the operation log
+    pub found: bool,
+    /// Operations performed since, newest first
+    pub operations_since: Vec<OperationInfo>,
+    /// Change IDs that did not exist at the recorded operation
+    pub changes_created: Vec<String>,
+    /// Change IDs that existed but now point at a different commit
+    pub changes_rewritten: Vec<String>,
+    /// Change IDs that were visible then and are gone now
+    pub changes_abandoned: Vec<String>,
+    /// Working-copy files that differ from the recorded working-copy commit
+    pub files_modified: Vec<(String, FileChangeKind)>,
+}
+
 /// Options for commit_working_copy
 pub struct CommitOptions {
     pub message: String,
     pub no_new: bool,
     pub run_invariants: bool,
     pub change_type: ChangeType,
-    pub category: Option<ChangeCategory>,
+    pub categories: Vec<ChangeCategory>,
     pub breaking: bool,
     /// When set, only changes to these paths are included in the commit.
     /// Unlisted changes remain in the working copy.
     pub paths: Option<Vec<String>>,
+    /// Repository-defined typed-change fields
+    pub fields: BTreeMap<String, String>,
+    /// Full change IDs that must be pushed or merged before this one
+    pub depends_on: Vec<String>,
 }
 
 /// Result of a successful commit via jj-lib
@@ -73,9 +281,74 @@ pub struct CommitResult {
     pub commit_id: String,
     pub operation_id: String,
     pub files_changed: Vec<String>,
+    /// Per-file line counts of what was committed
+    pub stats: ChangeMetrics,
     pub invariants: HashMap<String, InvariantStatus>,
 }
 
+/// A change whose description `reword` replaced
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Reworded {
+    pub change_id: String,
+    /// The commit now holding the change; absent on a dry run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit_id: Option<String>,
+    pub previous: String,
+    pub description: String,
+}
+
+/// Files that differ between two trees from the same store, with the
+/// content of each side
+fn file_changes(
+    store: &Arc<jj_lib::store::Store>,
+    before: &jj_lib::merged_tree::MergedTree,
+    after: &jj_lib::merged_tree::MergedTree,
+) -> Result<Vec<crate::unified::FileChange>> {
+    let side = |path: &RepoPath, value: &jj_lib::merge::MergedTreeValue| -> Result<_> {
+        Ok(match value.as_resolved() {
+            Some(Some(jj_lib::backend::TreeValue::File { id, executable, .. })) => {
+                Some(crate::unified::Side {
+                    content: read_file_bytes(store, path, id)?,
+                    mode: if *executable { "100755" } else { "100644" },
+                })
+            }
+            Some(Some(jj_lib::backend::TreeValue::Symlink(id))) => Some(crate::unified::Side {
+                content: store
+                    .read_symlink(path, id)
+                    .block_on()
+                    .map_err(|e| Error::Repository {
+                        message: format!("failed to read symlink: {}", e),
+                    })?
+                    .into_bytes(),
+                mode: "120000",
+            }),
+            // Absent, conflicted, or not a file
+            _ => None,
+        })
+    };
+
+    let _span = tracing::info_span!("tree_diff").entered();
+    let mut changes = Vec::new();
+    let diff_iter = jj_lib::merged_tree::TreeDiffIterator::new(before, after, &EverythingMatcher);
+    for entry in diff_iter {
+        let diff = entry.values.map_err(|e| Error::Repository {
+            message: format!("failed to diff trees: {}", e),
+        })?;
+        let (before, after) = (
+            side(&entry.path, &diff.before)?,
+            side(&entry.path, &diff.after)?,
+        );
+        if before.is_some() || after.is_some() {
+            changes.push(crate::unified::FileChange {
+                path: entry.path.as_internal_file_string().to_string(),
+                before,
+                after,
+            });
+        }
+    }
+    Ok(changes)
+}
+
 /// Load base gitignore rules for working copy snapshots. Mirrors what the
 /// jj CLI does: reads the global gitignore and .git/info/exclude so that
 /// the snapshot respects all ignore layers (global, repo-level, per-dir).
@@ -119,9 +392,193 @@ fn load_base_ignores(root: &Path) -> Arc<GitIgnoreFile> {
         ignores = chained;
     }
 
+    // 3. Invariant secrets are never snapshotted, gitignored or not
+    if let Ok(chained) = ignores.chain(
+        "",
+        Path::new(""),
+        format!("/{}\n", crate::secrets::FILE).as_bytes(),
+    ) {
+        ignores = chained;
+    }
+
     ignores
 }
 
+/// What `walk_files` leaves out besides `.git` and `.jj`. Directories that
+/// can only hold excluded files are never entered.
+#[derive(Clone, Default)]
+pub struct WalkFilters {
+    /// Skip files ignored by `.gitignore` (at every level),
+    /// `.git/info/exclude`, and the global excludes file
+    pub gitignore: bool,
+    /// `.agentignore` rules
+    pub agentignore: Option<AgentIgnore>,
+    /// Only files these globs match; nothing outside their base is entered
+    pub include: Option<Globs>,
+    /// Path fragments to skip (`index.exclude`); a directory is tested with
+    /// a trailing slash
+    pub exclude: Vec<String>,
+    /// The working copy's sparse patterns (directories or files); empty for
+    /// everything
+    pub sparse: Vec<String>,
+}
+
+impl WalkFilters {
+    /// The repository's own filters: gitignore, `.agentignore`,
+    /// `index.exclude`, and the working copy's sparse patterns
+    pub fn for_repo(repo: &mut Repo) -> Result<Self> {
+        Ok(Self {
+            gitignore: true,
+            agentignore: Some(AgentIgnore::load(repo.root())?),
+            include: None,
+            exclude: repo.config()?.index.exclude.clone(),
+            sparse: repo.sparse_patterns()?,
+        })
+    }
+
+    /// Only files `include` matches
+    pub fn including(mut self, include: Globs) -> Self {
+        self.include = Some(include);
+        self
+    }
+
+    fn skips_dir(&self, rel_dir: &str) -> bool {
+        let base = self
+            .include
+            .as_ref()
+            .map(|g| g.base().to_string_lossy().replace('\\', "/"))
+            .unwrap_or_default();
+        !on_path(&base, rel_dir)
+            || !(self.sparse.is_empty() || self.sparse.iter().any(|p| on_path(p, rel_dir)))
+            || self.exclude.iter().any(|p| rel_dir.contains(p.as_str()))
+            || self
+                .agentignore
+                .as_ref()
+                .is_some_and(|a| a.is_ignored(rel_dir))
+    }
+
+    /// Whether the file at `rel` is left out, gitignore aside
+    pub fn skips_file(&self, rel: &str) -> bool {
+        !(self.sparse.is_empty()
+            || self
+                .sparse
+                .iter()
+                .any(|p| rel == p || rel.starts_with(&format!("{}/", p))))
+            || self.exclude.iter().any(|p| rel.contains(p.as_str()))
+            || self.agentignore.as_ref().is_some_and(|a| a.is_ignored(rel))
+            || self.include.as_ref().is_some_and(|g| !g.is_match(rel))
+    }
+}
+
+/// Whether `dir` is a bare git repository (its own git directory, no
+/// working tree)
+fn is_bare_git(dir: &Path) -> bool {
+    dir.join("HEAD").is_file() && dir.join("objects").is_dir() && dir.join("refs").is_dir()
+}
+
+/// Whether the directory `rel_dir` (with a trailing slash) is inside
+/// `prefix` or on the way to it; an empty prefix is everything
+fn on_path(prefix: &str, rel_dir: &str) -> bool {
+    let prefix = prefix.trim_end_matches('/');
+    prefix.is_empty() || rel_dir.starts_with(&format!("{}/", prefix)) || prefix.starts_with(rel_dir)
+}
+
+/// Files under `root` that pass `filters`, in sorted path order (as
+/// `globs::walk` returns them). Ignored and excluded directories are pruned
+/// as the walk reaches them rather than read and filtered afterwards.
+pub fn walk_files(root: &Path, filters: WalkFilters) -> WalkFiles {
+    let ignores = if filters.gitignore {
+        load_base_ignores(root)
+    } else {
+        GitIgnoreFile::empty()
+    };
+    WalkFiles {
+        filters,
+        pending: vec![Pending::Dir {
+            path: root.to_path_buf(),
+            rel: String::new(),
+            ignores,
+        }],
+    }
+}
+
+enum Pending {
+    Dir {
+        path: PathBuf,
+        /// Slash-separated, with a trailing slash; empty for the root
+        rel: String,
+        /// The parent's rules; the directory's own `.gitignore` is added
+        /// when it is read
+        ignores: Arc<GitIgnoreFile>,
+    },
+    File(PathBuf),
+}
+
+/// The iterator `walk_files` returns
+pub struct WalkFiles {
+    filters: WalkFilters,
+    /// Next entry last
+    pending: Vec<Pending>,
+}
+
+impl WalkFiles {
+    fn read_dir(&mut self, dir: &Path, prefix: &str, ignores: &Arc<GitIgnoreFile>) {
+        let ignores = if self.filters.gitignore {
+            ignores
+                .chain_with_file(prefix, dir.join(".gitignore"))
+                .unwrap_or_else(|_| ignores.clone())
+        } else {
+            ignores.clone()
+        };
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return;
+        };
+        let mut entries: Vec<_> = entries.flatten().collect();
+        entries.sort_by_key(|e| e.file_name());
+        for entry in entries.into_iter().rev() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                let rel = format!("{}{}/", prefix, name);
+                if name == ".git"
+                    || name == ".jj"
+                    || ignores.matches(&rel)
+                    || self.filters.skips_dir(&rel)
+                {
+                    continue;
+                }
+                self.pending.push(Pending::Dir {
+                    path: entry.path(),
+                    rel,
+                    ignores: ignores.clone(),
+                });
+            } else {
+                let rel = format!("{}{}", prefix, name);
+                let path = entry.path();
+                // Symlinks are followed to files, never to directories
+                if path.is_file() && !ignores.matches(&rel) && !self.filters.skips_file(&rel) {
+                    self.pending.push(Pending::File(path));
+                }
+            }
+        }
+    }
+}
+
+impl Iterator for WalkFiles {
+    type Item = PathBuf;
+
+    fn next(&mut self) -> Option<PathBuf> {
+        loop {
+            match self.pending.pop()? {
+                Pending::File(path) => return Some(path),
+                Pending::Dir { path, rel, ignores } => self.read_dir(&path, &rel, &ignores),
+            }
+        }
+    }
+}
+
 /// Creates minimal UserSettings for agentjj operations.
 /// These settings are used when we don't need user's full config.
 fn create_minimal_settings() -> std::result::Result<UserSettings, Error> {
@@ -172,11 +629,14 @@ impl Repo {
             root,
             workspace: None,
             manifest: None,
+            config: None,
+            blobs: None,
         })
     }
 
     /// Discover and open a repository from the current directory or ancestors.
-    /// If a git repo is found without jj, automatically colocates jj with it.
+    /// If a git repo is found without jj, colocates jj with it when
+    /// `set_auto_colocate` allows; otherwise that is a precondition failure.
     /// Resolves symlinks to ensure consistent paths (jj's working copy tracking
     /// uses filesystem paths, so symlinked working directories fail silently).
     pub fn discover() -> Result<Self> {
@@ -187,6 +647,7 @@ impl Repo {
 
         // Track if we find a git repo without jj
         let mut found_git_without_jj: Option<PathBuf> = None;
+        let mut found_bare_git: Option<PathBuf> = None;
 
         loop {
             let has_jj = current.join(".jj").exists();
@@ -195,6 +656,9 @@ impl Repo {
             if has_jj {
                 return Self::open(current);
             }
+            if found_bare_git.is_none() && is_bare_git(current) {
+                found_bare_git = Some(current.to_path_buf());
+            }
 
             // Record git repo without jj for auto-colocate
             if has_git && found_git_without_jj.is_none() {
@@ -206,7 +670,29 @@ impl Repo {
                 None => {
                     // No jj repo found - auto-colocate if git repo exists
                     if let Some(git_path) = found_git_without_jj {
-                        return Self::init_colocated_git(&git_path);
+                        if AUTO_COLOCATE.load(Ordering::Relaxed) {
+                            return Self::init_colocated_git(&git_path);
+                        }
+                        return Err(Error::PreconditionFailed {
+                            reason: format!(
+                                "{} is a git repository without jj; run `agentjj init --colocate` to set it up",
+                                git_path.display()
+                            ),
+                            expected: "a jj workspace (.jj/)".into(),
+                            actual: "git only".into(),
+                        });
+                    }
+                    if let Some(bare_path) = found_bare_git {
+                        // Even `--onto`, which never writes the working copy,
+                        // opens the repository through a jj workspace
+                        return Err(Error::PreconditionFailed {
+                            reason: format!(
+                                "{} is a bare git repository; agentjj needs a jj workspace, even for --onto",
+                                bare_path.display()
+                            ),
+                            expected: "a jj workspace (.jj/)".into(),
+                            actual: "bare git repository".into(),
+                        });
                     }
                     return Err(Error::Repository {
                         message: "No git or jj repository found (or any parent)".into(),
@@ -216,9 +702,9 @@ impl Repo {
         }
     }
 
-    /// Initialize jj colocated with an existing git repository.
-    /// This is called automatically when discover() finds a git repo without jj.
-    fn init_colocated_git(git_repo_path: &Path) -> Result<Self> {
+    /// Initialize jj colocated with an existing git repository. `discover`
+    /// calls this for a git repo without jj when auto-colocation is allowed.
+    pub fn init_colocated_git(git_repo_path: &Path) -> Result<Self> {
         let settings = create_minimal_settings()?;
 
         // Use init_external_git for existing git repos - pass the .git path
@@ -237,6 +723,7 @@ impl Repo {
             abandon_unreachable_commits: false,
             remote_auto_track_bookmarks: Default::default(),
         };
+        let _critical = crate::deadline::critical();
         let mut tx = repo.start_transaction();
         if let Err(e) = jj_lib::git::import_refs(tx.repo_mut(), &import_options) {
             eprintln!("warning: failed to import git refs: {}", e);
@@ -306,6 +793,16 @@ impl Repo {
         }
     }
 
+    /// Initialize a jj repository with jj's native backend at `path`: no git
+    /// store at all, so git-only features report `GitRequired`
+    pub fn init_native(path: impl AsRef<Path>) -> Result<Self> {
+        let settings = create_minimal_settings()?;
+        Workspace::init_simple(&settings, path.as_ref()).map_err(|e| Error::Repository {
+            message: format!("failed to initialize jj repository: {}", e),
+        })?;
+        Self::open(path)
+    }
+
     /// Get the repository root path
     pub fn root(&self) -> &Path {
         &self.root
@@ -314,6 +811,7 @@ impl Repo {
     /// Load the workspace lazily
     fn load_workspace(&mut self) -> Result<&Workspace> {
         if self.workspace.is_none() {
+            let _span = tracing::info_span!("workspace_load").entered();
             let settings = create_minimal_settings()?;
             let store_factories = get_store_factories();
             let wc_factories = get_working_copy_factories();
@@ -339,6 +837,31 @@ impl Repo {
             })
     }
 
+    /// Load the repository as it was at a specific operation
+    fn load_repo_at_operation(&mut self, op_id: &str) -> Result<Arc<ReadonlyRepo>> {
+        let workspace = self.load_workspace()?;
+
+        let op_id_obj = jj_lib::op_store::OperationId::try_from_hex(op_id).ok_or_else(|| {
+            Error::Repository {
+                message: format!("invalid operation ID: {}", op_id),
+            }
+        })?;
+
+        let op = workspace
+            .repo_loader()
+            .load_operation(&op_id_obj)
+            .map_err(|e| Error::Repository {
+                message: format!("failed to load operation: {}", e),
+            })?;
+
+        workspace
+            .repo_loader()
+            .load_at(&op)
+            .map_err(|e| Error::Repository {
+                message: format!("failed to load repository at operation: {}", e),
+            })
+    }
+
     /// Get or load the manifest
     pub fn manifest(&mut self) -> Result<&Manifest> {
         if self.manifest.is_none() {
@@ -347,6 +870,14 @@ impl Repo {
         Ok(self.manifest.as_ref().unwrap())
     }
 
+    /// Get the effective config (file, env, and --config layers)
+    pub fn config(&mut self) -> Result<&Config> {
+        if self.config.is_none() {
+            self.config = Some(Config::load(&self.root)?);
+        }
+        Ok(self.config.as_ref().unwrap())
+    }
+
     /// Check if a manifest exists
     pub fn has_manifest(&self) -> bool {
         self.root.join(Manifest::DEFAULT_PATH).exists()
@@ -390,6 +921,26 @@ impl Repo {
         Ok(wc_commit_id.hex())
     }
 
+    /// The working copy's sparse patterns as slash-separated paths; empty
+    /// when the whole tree is checked out
+    pub fn sparse_patterns(&mut self) -> Result<Vec<String>> {
+        let workspace = self.load_workspace()?;
+        let patterns =
+            workspace
+                .working_copy()
+                .sparse_patterns()
+                .map_err(|e| Error::Repository {
+                    message: format!("failed to read sparse patterns: {}", e),
+                })?;
+        if patterns.iter().any(|p| p.is_root()) {
+            return Ok(Vec::new());
+        }
+        Ok(patterns
+            .iter()
+            .map(|p| p.as_internal_file_string().to_string())
+            .collect())
+    }
+
     /// Get current operation ID
     pub fn current_operation_id(&mut self) -> Result<String> {
         let repo = self.load_repo_at_head()?;
@@ -398,37 +949,44 @@ impl Repo {
 
     /// Read file content at a specific change or branch
     pub fn read_file(&mut self, path: &str, at: Option<&str>) -> Result<String> {
+        Ok(self.read_text(path, at)?.0)
+    }
+
+    /// Read a file as text along with how it is stored (encoding, BOM, line
+    /// endings). UTF-16 and latin-1 files are decoded; binary files are an error.
+    pub fn read_text(&mut self, path: &str, at: Option<&str>) -> Result<(String, TextFormat)> {
+        let bytes = self.read_raw(path, at)?;
+        crate::encoding::decode(&bytes).ok_or_else(|| Error::Repository {
+            message: format!("file '{}' is binary", path),
+        })
+    }
+
+    /// Read a file's bytes at a specific change or branch
+    pub fn read_raw(&mut self, path: &str, at: Option<&str>) -> Result<Vec<u8>> {
         // If no revision specified, just read from working copy on disk
         // This handles both tracked and untracked files
         if at.is_none() {
             let full_path = self.root.join(path);
-            return std::fs::read_to_string(&full_path).map_err(|e| Error::Repository {
+            return std::fs::read(&full_path).map_err(|e| Error::Repository {
                 message: format!("failed to read file '{}': {}", path, e),
             });
         }
 
         // For specific revisions, we need to look up in the repository
-        let repo = self.load_repo_at_head()?;
-        let workspace = self.workspace.as_ref().unwrap();
         let rev = at.unwrap();
+        // Change IDs and bookmarks first, then a commit ID as given
+        let resolved = self.resolve_revision(rev).ok().map(|(_, commit)| commit);
+        let repo = self.load_repo_at_head()?;
 
         // Get the commit to read from
-        let commit_id = if rev == "@" {
-            repo.view()
-                .get_wc_commit_id(workspace.workspace_name())
-                .cloned()
-                .ok_or_else(|| Error::Repository {
-                    message: "no working copy commit found".into(),
-                })?
-        } else {
-            // Try to parse as commit ID hex prefix
-            CommitId::try_from_hex(rev).ok_or_else(|| Error::Repository {
+        let commit_id = CommitId::try_from_hex(resolved.as_deref().unwrap_or(rev)).ok_or_else(
+            || Error::Repository {
                 message: format!(
-                    "cannot resolve revision '{}' - only @ and commit IDs are supported via jj-lib",
+                    "cannot resolve revision '{}' - use @, @-, a bookmark, a change ID, or a commit ID",
                     rev
                 ),
-            })?
-        };
+            },
+        )?;
 
         let commit = repo
             .store()
@@ -439,6 +997,10 @@ impl Repo {
 
         // Get the tree and read the file
         let tree = commit.tree();
+        let key = BlobCache::key(&tree_key(&tree), path);
+        if let Some(bytes) = self.blob_cache()?.get(&key) {
+            return Ok(bytes);
+        }
         let repo_path =
             jj_lib::repo_path::RepoPathBuf::from_internal_string(path).map_err(|e| {
                 Error::Repository {
@@ -467,28 +1029,33 @@ impl Repo {
                 message: format!("file '{}' not found at revision '{}'", path, rev),
             })?;
 
-        match content {
-            jj_lib::backend::TreeValue::File { .. } => {
-                // For specific revisions, we still read from working copy
-                // (In a full implementation, we'd read from the store)
-                let full_path = self.root.join(path);
-                std::fs::read_to_string(&full_path).map_err(|e| Error::Repository {
-                    message: format!("failed to read file: {}", e),
-                })
-            }
-            jj_lib::backend::TreeValue::Symlink(_target_id) => {
-                // Read symlink target from working copy
-                let full_path = self.root.join(path);
-                std::fs::read_link(&full_path)
-                    .map(|p| p.to_string_lossy().to_string())
-                    .map_err(|e| Error::Repository {
-                        message: format!("failed to read symlink: {}", e),
-                    })
+        let bytes = match content {
+            jj_lib::backend::TreeValue::File { id, .. } => {
+                read_file_bytes(repo.store(), &repo_path, &id)
             }
+            jj_lib::backend::TreeValue::Symlink(id) => repo
+                .store()
+                .read_symlink(&repo_path, &id)
+                .block_on()
+                .map(String::into_bytes)
+                .map_err(|e| Error::Repository {
+                    message: format!("failed to read symlink: {}", e),
+                }),
             _ => Err(Error::Repository {
                 message: format!("'{}' is not a regular file", path),
             }),
+        }?;
+        self.blob_cache()?.insert(&key, &bytes);
+        Ok(bytes)
+    }
+
+    /// The read cache, opened with the repo's `cache` config on first use
+    pub fn blob_cache(&mut self) -> Result<&mut BlobCache> {
+        if self.blobs.is_none() {
+            let config = self.config()?.cache.clone();
+            self.blobs = Some(BlobCache::open(&self.root, &config));
         }
+        Ok(self.blobs.as_mut().unwrap())
     }
 
     /// List files changed in a specific change
@@ -528,25 +1095,49 @@ impl Repo {
                 message: format!("failed to get commit: {}", e),
             })?;
 
-        // Get parent tree for diff
-        let parent_tree = commit.parent_tree(&*repo).map_err(|e| Error::Repository {
-            message: format!("failed to get parent tree: {}", e),
-        })?;
+        files_changed_in(&repo, &commit)
+    }
 
-        let tree = commit.tree();
+    /// List files changed in a specific commit, by full commit ID
+    pub fn commit_changed_files(&mut self, commit_id: &str) -> Result<Vec<String>> {
+        let repo = self.load_repo_at_head()?;
 
-        // Diff the trees using synchronous iterator
-        let mut files = Vec::new();
-        let diff_iter = jj_lib::merged_tree::TreeDiffIterator::new(
-            &parent_tree,
-            &tree,
-            &jj_lib::matchers::EverythingMatcher,
-        );
-        for diff_entry in diff_iter {
-            files.push(diff_entry.path.as_internal_file_string().to_string());
-        }
+        let commit_id_obj =
+            jj_lib::backend::CommitId::try_from_hex(commit_id).ok_or_else(|| {
+                Error::Repository {
+                    message: format!("invalid commit ID: {}", commit_id),
+                }
+            })?;
 
-        Ok(files)
+        let commit = repo
+            .store()
+            .get_commit(&commit_id_obj)
+            .map_err(|e| Error::Repository {
+                message: format!("failed to get commit: {}", e),
+            })?;
+
+        files_changed_in(&repo, &commit)
+    }
+
+    /// Change ID of a commit, by full commit ID
+    pub fn commit_change_id(&mut self, commit_id: &str) -> Result<String> {
+        let repo = self.load_repo_at_head()?;
+
+        let commit_id_obj =
+            jj_lib::backend::CommitId::try_from_hex(commit_id).ok_or_else(|| {
+                Error::Repository {
+                    message: format!("invalid commit ID: {}", commit_id),
+                }
+            })?;
+
+        let commit = repo
+            .store()
+            .get_commit(&commit_id_obj)
+            .map_err(|e| Error::Repository {
+                message: format!("failed to get commit: {}", e),
+            })?;
+
+        Ok(commit.change_id().hex())
     }
 
     /// Check if a branch/bookmark exists and get its change ID
@@ -575,6 +1166,70 @@ impl Repo {
         Ok(Some(commit.change_id().hex()))
     }
 
+    /// Local bookmarks and the changes they point at, by name
+    pub fn local_bookmarks(&mut self) -> Result<Vec<Bookmark>> {
+        let repo = self.load_repo_at_head()?;
+
+        let mut bookmarks = Vec::new();
+        for (name, target) in repo.view().local_bookmarks() {
+            // Conflicted bookmarks have no single change to report
+            let Some(commit_id) = target.as_normal() else {
+                continue;
+            };
+            let commit = repo
+                .store()
+                .get_commit(commit_id)
+                .map_err(|e| Error::Repository {
+                    message: format!("failed to get commit: {}", e),
+                })?;
+            bookmarks.push(Bookmark {
+                name: name.as_str().to_string(),
+                change_id: commit.change_id().hex(),
+                commit_id: commit.id().hex(),
+                description: commit
+                    .description()
+                    .lines()
+                    .next()
+                    .unwrap_or("")
+                    .to_string(),
+            });
+        }
+        bookmarks.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(bookmarks)
+    }
+
+    /// Point local bookmarks at commits (name, commit ID), creating them if
+    /// needed, in one operation, and export them to the colocated git branches
+    pub fn set_bookmarks(&mut self, targets: &[(String, String)], description: &str) -> Result<()> {
+        let repo = self.load_repo_at_head()?;
+
+        let _critical = crate::deadline::critical();
+        let mut tx = repo.start_transaction();
+        for (name, commit_id) in targets {
+            let commit_id = CommitId::try_from_hex(commit_id).ok_or_else(|| Error::Repository {
+                message: format!("invalid commit ID: {}", commit_id),
+            })?;
+            let ref_name: &jj_lib::ref_name::RefName = name.as_str().as_ref();
+            tx.repo_mut().set_local_bookmark_target(
+                ref_name,
+                jj_lib::op_store::RefTarget::normal(commit_id),
+            );
+        }
+        if is_git_backed(tx.repo().store()) {
+            if let Err(e) = jj_lib::git::export_refs(tx.repo_mut()) {
+                eprintln!("warning: failed to export jj refs to git: {}", e);
+            }
+        }
+        tx.commit(description).map_err(|e| Error::Repository {
+            message: format!("failed to commit transaction: {}", e),
+        })?;
+
+        // Clear cached workspace
+        self.workspace = None;
+
+        Ok(())
+    }
+
     /// Check if a change has conflicts
     pub fn has_conflicts(&mut self, change_id: &str) -> Result<bool> {
         let repo = self.load_repo_at_head()?;
@@ -658,32 +1313,109 @@ impl Repo {
 
     /// Apply an intent to the repository
     pub fn apply(&mut self, intent: Intent) -> Result<IntentResult> {
-        // 1. Check preconditions
-        if let Err(e) = self.check_preconditions(&intent) {
-            return Ok(e);
+        self.apply_intent(intent, false)
+    }
+
+    /// Resume an intent that was parked for approval, once a human has
+    /// approved it. The approval is marked applied when the intent succeeds.
+    pub fn apply_approved(&mut self, approval_id: &str) -> Result<IntentResult> {
+        let store = ApprovalStore::open(&self.root);
+        let mut approval = store.load(approval_id)?;
+        approval.expect_status(ApprovalStatus::Approved)?;
+
+        let result = self.apply_intent(approval.intent.clone(), true)?;
+        if let IntentResult::Success { change_id, .. } = &result {
+            approval.status = ApprovalStatus::Applied;
+            approval.change_id = Some(change_id.clone());
+            store.save(&approval)?;
+        }
+        Ok(result)
+    }
+
+    fn apply_intent(&mut self, intent: Intent, approved: bool) -> Result<IntentResult> {
+        // With `onto`, files are read from the base revision into memory and
+        // checked and edited there rather than in the working copy
+        let onto = match &intent.onto {
+            Some(rev) => Some(self.onto_base(rev, &intent)?),
+            None => None,
+        };
+        let root = self.root.clone();
+        let view = match &onto {
+            Some(base) => FileView::Tree(&base.overlay),
+            None => FileView::Disk(&root),
+        };
+
+        // 1. Check preconditions
+        if let Err(e) = self.check_preconditions(&intent, &view) {
+            return Ok(e);
         }
 
-        // 2. Check permissions if manifest exists
+        // 2. Check permissions if manifest exists; `ask` paths are parked
+        // for a human unless this intent was already approved
         if self.has_manifest() {
             if let Err(e) = self.check_permissions(&intent) {
                 return Ok(e);
             }
+            if !approved {
+                let manifest = self.manifest()?.clone();
+                let paths: Vec<String> = intent
+                    .changes
+                    .paths()
+                    .into_iter()
+                    .filter(|p| manifest.permissions.needs_approval(p))
+                    .collect();
+                if !paths.is_empty() {
+                    let approval = Approval::new(
+                        intent,
+                        paths.clone(),
+                        crate::audit::current_session(),
+                        utc_now(),
+                    );
+                    ApprovalStore::open(&self.root).save(&approval)?;
+                    // Tell a human (e.g. a webhook); the approval stands either way
+                    let mut payload = serde_json::to_value(&approval).unwrap_or_default();
+                    payload["kind"] = serde_json::json!("approval");
+                    payload["resume"] =
+                        serde_json::json!(format!("agentjj apply --resume {}", approval.id));
+                    let _ = self.run_hooks(HookEvent::Confirmation, payload);
+                    return Ok(IntentResult::PendingApproval {
+                        message: format!(
+                            "Waiting for approval: run `agentjj approvals approve {}`, then `agentjj apply --resume {}`",
+                            approval.id, approval.id
+                        ),
+                        approval_id: approval.id,
+                        paths,
+                    });
+                }
+            }
         }
 
-        // 3. Create a new change using jj-lib transaction
+        // 3. Verify edit pre-images so nothing is written over drifted files
+        let drifted = self.stale_edits(&view, &intent.changes);
+        if !drifted.is_empty() {
+            return Ok(IntentResult::StaleEdit { drifted });
+        }
+
+        // 4. Create a new change using jj-lib transaction
+        crate::deadline::check("apply")?;
+        if let Some(base) = onto {
+            return self.apply_onto(&intent, base);
+        }
         let (change_id, operation_id) = self.create_new_change(&intent.description)?;
 
-        // 4. Apply changes
-        let files_changed = match self.apply_changes(&intent.changes) {
+        // 5. Apply changes, saving what each written file held before so a
+        // failure partway leaves the working copy as it was
+        let mut saved = PreImages::new(&self.root);
+        let files_changed = match self.apply_changes(&intent.changes, &mut saved) {
             Ok(files) => files,
             Err(e) => {
                 // Rollback on error - undo the last operation
                 let _ = self.undo_operation();
-                return Err(e);
+                return Err(e.rolled_back(saved.restore()));
             }
         };
 
-        // 5. Check for conflicts
+        // 6. Check for conflicts
         if self.has_conflicts(&change_id)? {
             let conflicts = self.get_conflicts(&change_id)?;
             let prev_op = self.get_previous_op_id()?;
@@ -695,36 +1427,40 @@ impl Repo {
             });
         }
 
-        // 6. Check for paths requiring human review
-        if self.has_manifest() {
-            let manifest = self.manifest()?.clone();
-            let review_paths: Vec<String> = files_changed
-                .iter()
-                .filter(|f| manifest.requires_human_review(f))
-                .cloned()
-                .collect();
-
-            if !review_paths.is_empty() {
-                return Ok(IntentResult::RequiresReview {
-                    change_id,
-                    paths: review_paths,
-                    message: "These paths require human review before merge".to_string(),
-                });
-            }
+        // 7. Check for paths requiring human review
+        let review_paths = self.review_paths(&files_changed)?;
+        if !review_paths.is_empty() {
+            return Ok(IntentResult::RequiresReview {
+                change_id,
+                paths: review_paths,
+                message: "These paths require human review before merge".to_string(),
+            });
         }
 
-        // 7. Run invariants
+        // 8. Run invariants
         let invariants = if intent.run_invariants && self.has_manifest() {
-            match self.run_invariants(InvariantTrigger::PreCommit) {
-                Ok(results) => results,
-                Err((name, cmd, code, stdout, stderr)) => {
+            let tree_id = self.current_tree_id()?;
+            match self.run_invariants(InvariantTrigger::PreCommit, Some(&tree_id)) {
+                Ok(Ok(results)) => results,
+                Err(e) => {
+                    // Invariants could not start (missing environment)
+                    let _ = self.undo_operation();
+                    return Err(e.rolled_back(saved.restore()));
+                }
+                Ok(Err(failure)) => {
+                    // Out of time: drop the half-checked change entirely
+                    if let Err(e) = crate::deadline::check("invariants") {
+                        let _ = self.undo_operation();
+                        return Err(e.rolled_back(saved.restore()));
+                    }
                     let prev_op = self.get_previous_op_id()?;
                     return Ok(IntentResult::InvariantFailed {
-                        invariant: name,
-                        command: cmd,
-                        exit_code: code,
-                        stdout,
-                        stderr,
+                        invariant: failure.name,
+                        command: failure.command,
+                        exit_code: failure.exit_code,
+                        stdout: failure.stdout,
+                        stderr: failure.stderr,
+                        known_flakes: failure.known_flakes,
                         change_id,
                         rollback_command: format!("jj op restore {}", prev_op),
                     });
@@ -734,25 +1470,8 @@ impl Repo {
             HashMap::new()
         };
 
-        // 8. Save typed change metadata
-        let typed_change =
-            TypedChange::new(change_id.clone(), intent.change_type, &intent.description)
-                .with_files(files_changed.clone());
-        let typed_change = if intent.breaking {
-            typed_change.breaking()
-        } else {
-            typed_change
-        };
-        let mut typed_change = typed_change;
-        typed_change.invariants = InvariantsResult {
-            checked: invariants.keys().cloned().collect(),
-            status: if invariants.values().all(|s| *s == InvariantStatus::Passed) {
-                InvariantStatus::Passed
-            } else {
-                InvariantStatus::Failed
-            },
-            details: invariants.clone(),
-        };
+        // 9. Save typed change metadata
+        let typed_change = typed_change_for(&intent, &change_id, &files_changed, &invariants);
         self.save_typed_change(&typed_change)?;
 
         Ok(IntentResult::Success {
@@ -764,6 +1483,114 @@ impl Repo {
         })
     }
 
+    /// Paths among `files` that the manifest says need human review
+    fn review_paths(&mut self, files: &[String]) -> Result<Vec<String>> {
+        if !self.has_manifest() {
+            return Ok(Vec::new());
+        }
+        let manifest = self.manifest()?;
+        Ok(files
+            .iter()
+            .filter(|f| manifest.requires_human_review(f))
+            .cloned()
+            .collect())
+    }
+
+    /// Read the files an intent touches (or checks) at `rev` into memory
+    fn onto_base(&mut self, rev: &str, intent: &Intent) -> Result<OntoBase> {
+        let (_, commit_hex) = self.resolve_revision(rev)?;
+        let repo = self.load_repo_at_head()?;
+        let commit_id = CommitId::try_from_hex(&commit_hex).ok_or_else(|| Error::Repository {
+            message: format!("invalid commit id: {}", commit_hex),
+        })?;
+        let commit = repo
+            .store()
+            .get_commit(&commit_id)
+            .map_err(|e| Error::Repository {
+                message: format!("failed to get commit: {}", e),
+            })?;
+
+        let mut paths = match &intent.changes {
+            ChangeSpec::PatchFile { path } => {
+                crate::intent::patch_paths(&std::fs::read_to_string(path)?)
+            }
+            changes => changes.paths(),
+        };
+        let preconds = &intent.preconditions;
+        paths.extend(preconds.files_exist.iter().cloned());
+        paths.extend(preconds.files_absent.iter().cloned());
+        paths.extend(preconds.file_hashes.keys().cloned());
+
+        let overlay = tree_overlay(repo.store(), &commit.tree(), paths, rev)?;
+        Ok(OntoBase { commit, overlay })
+    }
+
+    /// Apply an intent to its base revision's files in memory and record
+    /// the result as a new change on that revision, in one transaction.
+    /// The working copy, and which change it points at, stay as they were.
+    /// Invariants are not run: they need the whole tree checked out.
+    fn apply_onto(&mut self, intent: &Intent, base: OntoBase) -> Result<IntentResult> {
+        let OntoBase {
+            commit: base,
+            mut overlay,
+        } = base;
+        let repo = self.load_repo_at_head()?;
+        let store = repo.store().clone();
+        let _critical = crate::deadline::critical();
+        let mut tx = repo.start_transaction();
+        let builder = tx
+            .repo_mut()
+            .new_commit(vec![base.id().clone()], base.tree())
+            .set_description(&intent.description);
+        let change_id = builder.change_id().hex();
+
+        let mut files_changed = match &intent.changes {
+            ChangeSpec::Files { operations } => overlay.apply(operations)?,
+            ChangeSpec::Patch { content } => patch_overlay(&mut overlay, content)?,
+            ChangeSpec::PatchFile { path } => {
+                patch_overlay(&mut overlay, &std::fs::read_to_string(path)?)?
+            }
+        };
+        files_changed.dedup();
+        let review_paths = self.review_paths(&files_changed)?;
+
+        let invariants = HashMap::new();
+        let typed_change = typed_change_for(intent, &change_id, &files_changed, &invariants);
+        overlay.set(
+            &typed_change.storage_path(),
+            Some(Entry::File {
+                content: typed_change.to_toml()?.into_bytes(),
+                executable: false,
+            }),
+        );
+
+        builder
+            .set_tree(write_overlay(&store, &base.tree(), &overlay)?)
+            .write()
+            .map_err(|e| Error::Repository {
+                message: format!("failed to create commit: {}", e),
+            })?;
+        let new_repo = tx.commit("apply onto").map_err(|e| Error::Repository {
+            message: format!("failed to commit transaction: {}", e),
+        })?;
+        self.workspace = None;
+
+        if !review_paths.is_empty() {
+            return Ok(IntentResult::RequiresReview {
+                change_id,
+                paths: review_paths,
+                message: "These paths require human review before merge".to_string(),
+            });
+        }
+        Ok(IntentResult::Success {
+            change_id,
+            operation_id: new_repo.op_id().hex(),
+            files_changed,
+            invariants,
+            pr_url: None,
+        })
+    }
+
     /// Create a new change using jj-lib
     fn create_new_change(&mut self, description: &str) -> Result<(String, String)> {
         let settings = create_minimal_settings()?;
@@ -800,6 +1627,7 @@ impl Repo {
                 })?;
 
         // Start a transaction
+        let _critical = crate::deadline::critical();
         let mut tx = repo.start_transaction();
 
         // Create new commit with the same tree as parent (empty change)
@@ -885,7 +1713,11 @@ impl Repo {
 
     /// Check preconditions for an intent
     #[allow(clippy::result_large_err)]
-    fn check_preconditions(&mut self, intent: &Intent) -> std::result::Result<(), IntentResult> {
+    fn check_preconditions(
+        &mut self,
+        intent: &Intent,
+        view: &FileView,
+    ) -> std::result::Result<(), IntentResult> {
         let preconds = &intent.preconditions;
 
         // Check operation ID
@@ -924,8 +1756,7 @@ impl Repo {
 
         // Check file existence
         for path in &preconds.files_exist {
-            let full_path = self.root.join(path);
-            if !full_path.exists() {
+            if !view.exists(path) {
                 return Err(IntentResult::PreconditionFailed {
                     reason: format!("file '{}' does not exist", path),
                     expected: "exists".to_string(),
@@ -935,8 +1766,7 @@ impl Repo {
         }
 
         for path in &preconds.files_absent {
-            let full_path = self.root.join(path);
-            if full_path.exists() {
+            if view.exists(path) {
                 return Err(IntentResult::PreconditionFailed {
                     reason: format!("file '{}' should not exist", path),
                     expected: "absent".to_string(),
@@ -947,8 +1777,7 @@ impl Repo {
 
         // Check file hashes
         for (path, expected_hash) in &preconds.file_hashes {
-            let full_path = self.root.join(path);
-            if !full_path.exists() {
+            if !view.exists(path) {
                 return Err(IntentResult::PreconditionFailed {
                     reason: format!("file '{}' not found for hash check", path),
                     expected: expected_hash.clone(),
@@ -956,7 +1785,7 @@ impl Repo {
                 });
             }
 
-            match std::fs::read(&full_path) {
+            match view.read(path) {
                 Ok(content) => {
                     use sha2::{Digest, Sha256};
                     let mut hasher = Sha256::new();
@@ -992,21 +1821,7 @@ impl Repo {
             Err(_) => return Ok(()), // No manifest means no permission restrictions
         };
 
-        // Get files that will be changed
-        let files = match &intent.changes {
-            ChangeSpec::Files { operations } => operations
-                .iter()
-                .map(|op| match op {
-                    FileOperation::Create { path, .. } => path.clone(),
-                    FileOperation::Replace { path, .. } => path.clone(),
-                    FileOperation::Delete { path } => path.clone(),
-                    FileOperation::Rename { from, to } => format!("{} -> {}", from, to),
-                })
-                .collect::<Vec<_>>(),
-            _ => vec![], // Can't easily know files from a patch
-        };
-
-        for file in files {
+        for file in intent.changes.paths() {
             if !manifest.permissions.can_change(&file) {
                 return Err(IntentResult::PermissionDenied {
                     action: "change".to_string(),
@@ -1019,10 +1834,18 @@ impl Repo {
         Ok(())
     }
 
-    /// Apply changes from a ChangeSpec
-    fn apply_changes(&self, changes: &ChangeSpec) -> Result<Vec<String>> {
+    /// Apply changes from a ChangeSpec, saving each path's pre-image in
+    /// `saved` before it is first written
+    fn apply_changes(&self, changes: &ChangeSpec, saved: &mut PreImages) -> Result<Vec<String>> {
         match changes {
             ChangeSpec::Patch { content } => {
+                // patch(1) leaves .orig backups and .rej hunks beside files it fails on
+                for path in crate::intent::patch_paths(content) {
+                    saved.save(&path)?;
+                    saved.save(&format!("{}.orig", path))?;
+                    saved.save(&format!("{}.rej", path))?;
+                }
+
                 // Write patch to temp file and apply
                 let patch_path = self.root.join(".agent/temp.patch");
                 if let Some(parent) = patch_path.parent() {
@@ -1056,33 +1879,83 @@ impl Repo {
 
             ChangeSpec::PatchFile { path } => {
                 let content = std::fs::read_to_string(path)?;
-                self.apply_changes(&ChangeSpec::Patch { content })
+                self.apply_changes(&ChangeSpec::Patch { content }, saved)
             }
 
             ChangeSpec::Files { operations } => {
                 let mut files = Vec::new();
+                let mut edited = std::collections::HashSet::new();
 
                 for op in operations {
                     match op {
-                        FileOperation::Create { path, content } => {
+                        FileOperation::Create {
+                            path,
+                            content,
+                            executable,
+                        } => {
+                            saved.save(path)?;
                             let full_path = self.root.join(path);
                             if let Some(parent) = full_path.parent() {
                                 std::fs::create_dir_all(parent)?;
                             }
                             std::fs::write(&full_path, content)?;
+                            if let Some(executable) = executable {
+                                set_executable(&full_path, *executable)?;
+                            }
+                            files.push(path.clone());
+                        }
+                        FileOperation::CreateSymlink { path, target } => {
+                            if symlink_escapes_repo(path, target) {
+                                return Err(Error::PermissionDenied {
+                                    action: format!("symlink to '{}'", target),
+                                    path: path.clone(),
+                                });
+                            }
+                            saved.save(path)?;
+                            let full_path = self.root.join(path);
+                            if let Some(parent) = full_path.parent() {
+                                std::fs::create_dir_all(parent)?;
+                            }
+                            create_symlink(target, &full_path)?;
                             files.push(path.clone());
                         }
-                        FileOperation::Replace { path, content } => {
+                        FileOperation::Replace {
+                            path,
+                            content,
+                            executable,
+                            ..
+                        } => {
+                            // Keep the file's encoding, BOM, and line endings
+                            saved.save(path)?;
                             let full_path = self.root.join(path);
-                            std::fs::write(&full_path, content)?;
+                            let format = std::fs::read(&full_path)
+                                .ok()
+                                .and_then(|bytes| crate::encoding::decode(&bytes))
+                                .map(|(_, format)| format)
+                                .unwrap_or_default();
+                            std::fs::write(&full_path, crate::encoding::encode(content, &format))?;
+                            if let Some(executable) = executable {
+                                set_executable(&full_path, *executable)?;
+                            }
                             files.push(path.clone());
                         }
-                        FileOperation::Delete { path } => {
+                        FileOperation::Edit { path, .. } => {
+                            // All edits to a file apply together, against its pre-image
+                            if edited.insert(path.clone()) {
+                                saved.save(path)?;
+                                self.apply_line_edits(path, operations)?;
+                                files.push(path.clone());
+                            }
+                        }
+                        FileOperation::Delete { path, .. } => {
+                            saved.save(path)?;
                             let full_path = self.root.join(path);
                             std::fs::remove_file(&full_path)?;
                             files.push(path.clone());
                         }
                         FileOperation::Rename { from, to } => {
+                            saved.save(from)?;
+                            saved.save(to)?;
                             let from_path = self.root.join(from);
                             let to_path = self.root.join(to);
                             std::fs::rename(&from_path, &to_path)?;
@@ -1097,141 +1970,663 @@ impl Repo {
         }
     }
 
-    /// Run invariants and return results
-    #[allow(clippy::type_complexity)]
+    /// Apply every `Edit` operation for `path` to the file on disk
+    fn apply_line_edits(&self, path: &str, operations: &[FileOperation]) -> Result<()> {
+        let full_path = self.root.join(path);
+        let (content, format) =
+            crate::encoding::decode(&std::fs::read(&full_path)?).ok_or_else(|| {
+                Error::Repository {
+                    message: format!("cannot edit binary file '{}'", path),
+                }
+            })?;
+        let content = crate::overlay::edit_lines(content, path, operations)?;
+        std::fs::write(&full_path, crate::encoding::encode(&content, &format))?;
+        Ok(())
+    }
+
+    /// Files whose current content no longer matches the pre-image hashes
+    /// carried by the operations in `changes`
+    fn stale_edits(&self, view: &FileView, changes: &ChangeSpec) -> Vec<StaleFile> {
+        let ChangeSpec::Files { operations } = changes else {
+            return Vec::new();
+        };
+
+        operations
+            .iter()
+            .filter_map(|op| {
+                let (path, expected, lines) = op.pre_image()?;
+                let current = view.read(path).ok();
+                let actual = match (current, lines) {
+                    (Some(bytes), None) => Some(crate::snippet::content_hash(&bytes)),
+                    (Some(bytes), Some((start, end))) => crate::encoding::decode(&bytes)
+                        .and_then(|(text, _)| crate::snippet::line_range(&text, start, end))
+                        .filter(|r| r.end_line == end)
+                        .map(|r| r.hash),
+                    (None, _) => None,
+                };
+                if actual.as_deref() == Some(expected.to_lowercase().as_str()) {
+                    return None;
+                }
+                Some(StaleFile {
+                    path: path.to_string(),
+                    lines,
+                    expected: expected.to_string(),
+                    actual,
+                })
+            })
+            .collect()
+    }
+
+    /// Run the invariants for `trigger`, stopping at the first failure. With
+    /// the working-copy tree ID, outcomes go into the test history and the
+    /// failure is checked against known flakes. Errs before running anything
+    /// when an invariant's environment cannot be resolved.
     fn run_invariants(
         &mut self,
         trigger: InvariantTrigger,
-    ) -> std::result::Result<HashMap<String, InvariantStatus>, (String, String, i32, String, String)>
-    {
+        tree_id: Option<&str>,
+    ) -> Result<InvariantsRun> {
+        let _span = tracing::info_span!("invariants", ?trigger).entered();
         let manifest = match self.manifest() {
             Ok(m) => m.clone(),
-            Err(_) => return Ok(HashMap::new()), // No manifest means no invariants
+            Err(_) => return Ok(Ok(HashMap::new())), // No manifest means no invariants
         };
+        let timeout = self.invariant_timeout();
         let invariants = manifest.invariants_for(trigger);
+        let envs = self.invariant_envs(&invariants)?;
+        let change_id = match tree_id {
+            Some(_) => self.current_change_id().unwrap_or_default(),
+            None => String::new(),
+        };
+        let mut outcomes = Vec::new();
         let mut results = HashMap::new();
+        let mut progress = Progress::start("invariants", Some(invariants.len()));
 
         for (name, invariant) in invariants {
+            let _invariant = tracing::info_span!("invariant", name).entered();
             let cmd = invariant.command();
+            let env = envs.get(name).cloned().unwrap_or_default();
 
-            // Run the command via shell
-            let output = Command::new("sh")
-                .args(["-c", cmd])
-                .current_dir(&self.root)
-                .output();
-
-            match output {
-                Ok(out) if out.status.success() => {
+            // Run the command via shell; secrets never leave in its output
+            let ran = run_shell(cmd, &self.root, timeout, None, &env.pairs()).map(|mut out| {
+                out.stdout = env.redact(&out.stdout);
+                out.stderr = env.redact(&out.stderr);
+                out
+            });
+            let (code, stdout, stderr) = match ran {
+                Ok(out) if out.success => {
+                    if let Some(tree_id) = tree_id {
+                        let summary = crate::testrun::parse_output(&out.stdout);
+                        outcomes.push(Outcome::new(name, cmd, tree_id, &change_id, true, &summary));
+                    }
                     results.insert(name.to_string(), InvariantStatus::Passed);
+                    progress.tick();
+                    continue;
                 }
                 Ok(out) => {
-                    return Err((
-                        name.to_string(),
-                        cmd.to_string(),
-                        out.status.code().unwrap_or(-1),
-                        String::from_utf8_lossy(&out.stdout).to_string(),
-                        String::from_utf8_lossy(&out.stderr).to_string(),
-                    ));
-                }
-                Err(e) => {
-                    return Err((
-                        name.to_string(),
-                        cmd.to_string(),
-                        -1,
-                        String::new(),
-                        e.to_string(),
-                    ));
+                    let stderr = match timeout.filter(|_| out.timed_out) {
+                        Some(limit) => {
+                            format!("{}timed out after {}s", out.stderr, limit.as_secs())
+                        }
+                        None => out.stderr,
+                    };
+                    (out.code.unwrap_or(-1), out.stdout, stderr)
                 }
+                Err(e) => (-1, String::new(), e.to_string()),
+            };
+
+            let mut known_flakes = Vec::new();
+            if let Some(tree_id) = tree_id {
+                let summary = crate::testrun::parse_output(&format!("{}\n{}", stdout, stderr));
+                outcomes.push(Outcome::new(
+                    name, cmd, tree_id, &change_id, false, &summary,
+                ));
+                known_flakes = self.record_outcomes(&outcomes, name, &summary.failing);
             }
+            return Ok(Err(Box::new(InvariantFailure {
+                name: name.to_string(),
+                command: cmd.to_string(),
+                exit_code: code,
+                stdout,
+                stderr,
+                known_flakes,
+            })));
         }
+        progress.finish();
+        self.record_outcomes(&outcomes, "", &[]);
 
-        Ok(results)
+        Ok(Ok(results))
     }
 
-    /// Get the previous operation ID (for rollback)
-    fn get_previous_op_id(&mut self) -> Result<String> {
-        let repo = self.load_repo_at_head()?;
-
-        let current_op = repo.operation();
-        let parent_ops: Vec<_> = current_op
-            .parents()
-            .collect::<std::result::Result<_, _>>()
-            .map_err(|e| Error::Repository {
-                message: format!("failed to get parent operations: {}", e),
-            })?;
-
-        if parent_ops.is_empty() {
-            Ok(current_op.id().hex())
-        } else {
-            Ok(parent_ops[0].id().hex())
+    /// Each invariant's environment with `${VAR}` references resolved. Errs
+    /// with every variable that cannot be found, across all of them.
+    fn invariant_envs(
+        &self,
+        invariants: &[(&str, &Invariant)],
+    ) -> Result<HashMap<String, ResolvedEnv>> {
+        let declared: Vec<_> = invariants
+            .iter()
+            .filter_map(|(name, inv)| Some((*name, inv.env().filter(|e| !e.is_empty())?)))
+            .collect();
+        if declared.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let secrets = Secrets::load(&self.root)?;
+        let mut envs = HashMap::new();
+        let (mut variables, mut names) = (Vec::new(), Vec::new());
+        for (name, env) in declared {
+            match secrets.resolve(env) {
+                Ok(resolved) => {
+                    envs.insert(name.to_string(), resolved);
+                }
+                Err(missing) => {
+                    variables.extend(missing);
+                    names.push(name.to_string());
+                }
+            }
         }
+        if !variables.is_empty() {
+            variables.sort();
+            variables.dedup();
+            names.sort();
+            return Err(Error::MissingEnv {
+                variables,
+                invariants: names,
+                secrets_file: crate::secrets::FILE.to_string(),
+            });
+        }
+        Ok(envs)
     }
 
-    /// Get typed change metadata by change ID
-    pub fn get_typed_change(&self, change_id: &str) -> Result<TypedChange> {
-        TypedChange::load_from_repo(&self.root, change_id)
+    /// Append outcomes to the test history (best-effort) and return which of
+    /// `invariant`'s `failing` tests are known flakes
+    fn record_outcomes(
+        &self,
+        outcomes: &[Outcome],
+        invariant: &str,
+        failing: &[String],
+    ) -> Vec<String> {
+        if outcomes.is_empty() {
+            return Vec::new();
+        }
+        let store = crate::testrun::TestStore::open(&self.root);
+        if let Err(e) = store.record(outcomes) {
+            eprintln!("warning: failed to record test history: {}", e);
+        }
+        if invariant.is_empty() {
+            return Vec::new();
+        }
+        let flakes = crate::testrun::flaky(&store.history());
+        crate::testrun::known_flakes(&flakes, invariant, failing)
     }
 
-    /// Save typed change metadata
-    pub fn save_typed_change(&self, change: &TypedChange) -> Result<()> {
-        change.save(&self.root)
-    }
+    /// Run every invariant for a trigger and report each status, without
+    /// stopping at the first failure (for reports rather than gating).
+    /// Keyed by name, so reports list invariants in the same order every run.
+    pub fn invariant_report(
+        &mut self,
+        trigger: InvariantTrigger,
+    ) -> Result<BTreeMap<String, InvariantStatus>> {
+        let _span = tracing::info_span!("invariants", ?trigger).entered();
+        let manifest = match self.manifest() {
+            Ok(m) => m.clone(),
+            Err(_) => return Ok(BTreeMap::new()),
+        };
 
-    /// Describe the current change
-    pub fn describe(&mut self, message: &str) -> Result<()> {
-        let settings = create_minimal_settings()?;
-        let store_factories = get_store_factories();
-        let wc_factories = get_working_copy_factories();
+        let timeout = self.invariant_timeout();
+        let invariants = manifest.invariants_for(trigger);
+        let envs = self.invariant_envs(&invariants)?;
+
+        Ok(invariants
+            .into_iter()
+            .map(|(name, invariant)| {
+                let env = envs.get(name).map(ResolvedEnv::pairs).unwrap_or_default();
+                let passed = run_shell(invariant.command(), &self.root, timeout, None, &env)
+                    .map(|out| out.success)
+                    .unwrap_or(false);
+                let status = if passed {
+                    InvariantStatus::Passed
+                } else {
+                    InvariantStatus::Failed
+                };
+                (name.to_string(), status)
+            })
+            .collect())
+    }
 
-        let workspace = Workspace::load(&settings, &self.root, &store_factories, &wc_factories)
-            .map_err(|e| Error::Repository {
-                message: format!("failed to load workspace: {}", e),
-            })?;
+    /// Run one test command under the invariant time limit, parse its
+    /// pass/fail counts from the output, and add it to the test history.
+    /// A command named after an invariant gets that invariant's environment.
+    pub fn run_test_command(
+        &mut self,
+        name: &str,
+        command: &str,
+        tree_id: &str,
+        change_id: &str,
+    ) -> Result<TestCommandResult> {
+        let root = self.root.clone();
+        let mut result = self.run_test_in(name, command, &root)?;
+        let outcome = Outcome::new(
+            name,
+            command,
+            tree_id,
+            change_id,
+            result.success,
+            &result.summary,
+        );
+        result.known_flakes = if result.success {
+            self.record_outcomes(&[outcome], "", &[])
+        } else {
+            self.record_outcomes(&[outcome], name, &result.summary.failing)
+        };
+        Ok(result)
+    }
 
-        let repo = workspace
-            .repo_loader()
-            .load_at_head()
-            .map_err(|e| Error::Repository {
-                message: format!("failed to load repository: {}", e),
-            })?;
+    /// Run one test command in `dir` and parse its output, without recording
+    /// anything
+    fn run_test_in(&mut self, name: &str, command: &str, dir: &Path) -> Result<TestCommandResult> {
+        let timeout = self.invariant_timeout();
+        let invariant = self
+            .manifest()
+            .ok()
+            .and_then(|m| m.invariants.get(name).cloned());
+        let env = match &invariant {
+            Some(invariant) => self
+                .invariant_envs(&[(name, invariant)])?
+                .remove(name)
+                .unwrap_or_default(),
+            None => ResolvedEnv::default(),
+        };
+        let started = std::time::Instant::now();
+        let mut out = run_shell(command, dir, timeout, None, &env.pairs())?;
+        out.stdout = env.redact(&out.stdout);
+        out.stderr = env.redact(&out.stderr);
+        let summary = crate::testrun::parse_output(&format!("{}\n{}", out.stdout, out.stderr));
+        Ok(TestCommandResult {
+            name: name.to_string(),
+            command: command.to_string(),
+            success: out.success,
+            exit_code: out.code,
+            timed_out: out.timed_out,
+            duration_ms: started.elapsed().as_millis() as u64,
+            summary,
+            output_tail: if out.success {
+                Vec::new()
+            } else {
+                crate::testrun::output_tail(&out.stdout, &out.stderr)
+            },
+            known_flakes: Vec::new(),
+        })
+    }
 
-        let wc_commit_id = repo
-            .view()
-            .get_wc_commit_id(workspace.workspace_name())
-            .cloned()
-            .ok_or_else(|| Error::Repository {
-                message: "no working copy commit found".into(),
-            })?;
+    /// Run test commands against `rev`, checked out into a temporary
+    /// directory that is removed afterwards. The working copy and the test
+    /// history are left alone, so another revision's failures never count
+    /// against the current change.
+    pub fn run_tests_at(
+        &mut self,
+        rev: &str,
+        commands: &[(String, String)],
+    ) -> Result<crate::testrun::TestRun> {
+        let (_, commit_hex) = self.resolve_revision(rev)?;
+        let change_id = self.commit_change_id(&commit_hex)?;
+        let dir = std::env::temp_dir().join(format!(
+            "agentjj-at-{}-{}",
+            std::process::id(),
+            &commit_hex[..12.min(commit_hex.len())]
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        let result: Result<Vec<TestCommandResult>> = (|| {
+            self.export_revision(rev, &dir)?;
+            let mut results = Vec::new();
+            let mut progress = Progress::start("tests", Some(commands.len()));
+            for (name, command) in commands {
+                results.push(self.run_test_in(name, command, &dir)?);
+                progress.tick();
+            }
+            progress.finish();
+            Ok(results)
+        })();
+        let _ = std::fs::remove_dir_all(&dir);
+        let results = result?;
+        Ok(crate::testrun::TestRun {
+            change_id,
+            commit_id: commit_hex,
+            ran_at: utc_now(),
+            affected_only: false,
+            selected: None,
+            success: results.iter().all(|r| r.success),
+            commands: results,
+        })
+    }
 
+    /// Write the files and symlinks of `rev` under `dest`, straight from the
+    /// store. Conflicted paths have no single content and are left out.
+    pub fn export_revision(&mut self, rev: &str, dest: &Path) -> Result<()> {
+        let (_, commit_hex) = self.resolve_revision(rev)?;
+        let repo = self.load_repo_at_head()?;
+        let commit_id = CommitId::try_from_hex(&commit_hex).ok_or_else(|| Error::Repository {
+            message: format!("invalid commit id: {}", commit_hex),
+        })?;
         let commit = repo
             .store()
-            .get_commit(&wc_commit_id)
+            .get_commit(&commit_id)
             .map_err(|e| Error::Repository {
                 message: format!("failed to get commit: {}", e),
             })?;
 
-        // Start transaction
-        let mut tx = repo.start_transaction();
+        std::fs::create_dir_all(dest)?;
+        for (path, value) in commit.tree().entries() {
+            let Ok(Some(value)) = value.map(|v| v.into_resolved().ok().flatten()) else {
+                continue;
+            };
+            let Some(entry) = read_entry(repo.store(), &path, &Some(value))? else {
+                continue;
+            };
+            let target = dest.join(path.as_internal_file_string());
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            match entry {
+                Entry::File {
+                    content,
+                    executable,
+                } => {
+                    std::fs::write(&target, content)?;
+                    set_executable(&target, executable)?;
+                }
+                Entry::Symlink { target: link } => create_symlink(&link, &target)?,
+            }
+        }
+        Ok(())
+    }
 
-        // Rewrite commit with new description
-        let new_commit = tx
-            .repo_mut()
-            .rewrite_commit(&commit)
-            .set_description(message)
-            .write()
-            .map_err(|e| Error::Repository {
-                message: format!("failed to rewrite commit: {}", e),
-            })?;
+    /// Run a custom command's rendered steps in order, stopping at the
+    /// first failure. `agentjj` inside a step resolves to this binary and
+    /// prints JSON, so composed calls hand structured output along.
+    pub fn run_custom_steps(&mut self, steps: &[String]) -> Result<Vec<StepResult>> {
+        let mut path = std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(Path::to_path_buf))
+            .map(|dir| dir.display().to_string())
+            .unwrap_or_default();
+        if let Ok(existing) = std::env::var("PATH") {
+            path = format!("{}:{}", path, existing);
+        }
+        let env = [("PATH", path), ("AGENTJJ_OUTPUT_JSON", "1".to_string())];
 
-        // Update working copy
-        tx.repo_mut()
-            .set_wc_commit(
-                workspace.workspace_name().to_owned(),
-                new_commit.id().clone(),
-            )
-            .map_err(|e| Error::Repository {
-                message: format!("failed to set working copy: {}", e),
-            })?;
+        let mut results = Vec::new();
+        for step in steps {
+            let out = run_shell(step, &self.root, None, None, &env)?;
+            if out.timed_out {
+                crate::deadline::check("custom command")?;
+            }
+            results.push(StepResult {
+                command: step.clone(),
+                success: out.success,
+                exit_code: out.code,
+                stdout: out.stdout,
+                stderr: out.stderr,
+            });
+            if !out.success {
+                break;
+            }
+        }
+        Ok(results)
+    }
+
+    /// Fix `files` in place: the built-in whitespace fixers first, then each
+    /// manifest fixer (by name) over the files it handles. Deleted files are
+    /// skipped. Only rewrites the working copy; a snapshot records the fixes.
+    pub fn run_fixers(&mut self, files: &[String]) -> Result<FixReport> {
+        let files: Vec<String> = files
+            .iter()
+            .filter(|f| self.root.join(f).is_file())
+            .cloned()
+            .collect();
+        let mut report = FixReport::default();
+        for file in &files {
+            let path = self.root.join(file);
+            if let Some((fixed, applied)) = crate::fix::apply_builtins(file, &std::fs::read(&path)?)
+            {
+                std::fs::write(&path, fixed)?;
+                for fixer in applied {
+                    report.record(file, fixer);
+                }
+            }
+        }
+
+        let mut fixers: Vec<(String, crate::manifest::Fixer)> = match self.manifest() {
+            Ok(m) => m.fixers.clone().into_iter().collect(),
+            Err(_) => Vec::new(),
+        };
+        fixers.sort_by(|a, b| a.0.cmp(&b.0));
+        let timeout = self.invariant_timeout();
+        for (name, fixer) in fixers {
+            let matching: Vec<String> = files
+                .iter()
+                .filter(|f| fixer.applies_to(f))
+                .cloned()
+                .collect();
+            if matching.is_empty() {
+                continue;
+            }
+            let read = |f: &String| std::fs::read(self.root.join(f)).ok();
+            let before: Vec<Option<Vec<u8>>> = matching.iter().map(read).collect();
+            let command = crate::fix::command_for(&fixer.cmd, &matching);
+            let out = run_shell(&command, &self.root, timeout, None, &[])?;
+            if out.timed_out {
+                crate::deadline::check("fixer")?;
+            }
+            for (file, before) in matching.iter().zip(before) {
+                if read(file) != before {
+                    report.record(file, &name);
+                }
+            }
+            if !out.success {
+                report.failed.push(FixerFailure {
+                    fixer: name,
+                    command,
+                    exit_code: out.code,
+                    stderr: out.stderr,
+                });
+            }
+        }
+        Ok(report)
+    }
+
+    /// Ask `change.message_command` for a commit message, feeding it `payload`
+    /// as JSON on stdin. `None` when no command is configured, or it fails,
+    /// times out, or prints nothing.
+    pub fn message_from_command(&mut self, payload: &serde_json::Value) -> Result<Option<String>> {
+        let config = self.config()?;
+        let Some(command) = config.change.message_command.clone() else {
+            return Ok(None);
+        };
+        let timeout = config
+            .hooks
+            .timeout_secs
+            .map(std::time::Duration::from_secs);
+        let input = serde_json::to_vec(payload).map_err(|e| Error::Io {
+            message: e.to_string(),
+        })?;
+        let out = run_shell(&command, &self.root, timeout, Some(input), &[])?;
+        if out.timed_out {
+            crate::deadline::check("message command")?;
+        }
+        let message = out.stdout.trim();
+        Ok((out.success && !message.is_empty()).then(|| format!("{}\n", message)))
+    }
+
+    /// Run the manifest hooks for `event`, each receiving `payload` (with an
+    /// added `event` field) as JSON on stdin. A failing pre-hook stops with
+    /// `Error::HookRejected`; post-hook failures are only reported.
+    pub fn run_hooks(
+        &mut self,
+        event: HookEvent,
+        payload: serde_json::Value,
+    ) -> Result<Vec<HookResult>> {
+        let commands: Vec<String> = match self.manifest() {
+            Ok(m) => m
+                .hooks
+                .for_event(event)
+                .into_iter()
+                .map(str::to_string)
+                .collect(),
+            Err(_) => return Ok(Vec::new()), // No manifest means no hooks
+        };
+        if commands.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let timeout = self
+            .config()?
+            .hooks
+            .timeout_secs
+            .map(std::time::Duration::from_secs);
+        let mut payload = payload;
+        if let Some(obj) = payload.as_object_mut() {
+            obj.insert("event".into(), serde_json::json!(event.as_str()));
+        }
+        let input = serde_json::to_vec(&payload).map_err(|e| Error::Io {
+            message: e.to_string(),
+        })?;
+
+        let mut results = Vec::new();
+        for command in commands {
+            let out = run_shell(&command, &self.root, timeout, Some(input.clone()), &[])?;
+            if out.timed_out {
+                crate::deadline::check(&format!("{} hook", event.as_str()))?;
+            }
+            let stderr = if out.timed_out {
+                format!("{}timed out", out.stderr)
+            } else {
+                out.stderr
+            };
+
+            if !out.success && event.can_veto() {
+                let reason = stderr
+                    .lines()
+                    .chain(out.stdout.lines())
+                    .map(str::trim)
+                    .find(|l| !l.is_empty())
+                    .unwrap_or("no reason given")
+                    .to_string();
+                return Err(Error::HookRejected {
+                    event: event.as_str().to_string(),
+                    command,
+                    exit_code: out.code.unwrap_or(-1),
+                    reason,
+                    stderr,
+                });
+            }
+
+            results.push(HookResult {
+                event,
+                command,
+                success: out.success,
+                exit_code: out.code,
+                stdout: out.stdout,
+                stderr,
+            });
+        }
+
+        Ok(results)
+    }
+
+    /// Per-invariant time limit from config, if any
+    fn invariant_timeout(&mut self) -> Option<std::time::Duration> {
+        self.config()
+            .ok()
+            .and_then(|c| c.invariants.timeout_secs)
+            .map(std::time::Duration::from_secs)
+    }
+
+    /// Get the previous operation ID (for rollback)
+    fn get_previous_op_id(&mut self) -> Result<String> {
+        let repo = self.load_repo_at_head()?;
+
+        let current_op = repo.operation();
+        let parent_ops: Vec<_> = current_op
+            .parents()
+            .collect::<std::result::Result<_, _>>()
+            .map_err(|e| Error::Repository {
+                message: format!("failed to get parent operations: {}", e),
+            })?;
+
+        if parent_ops.is_empty() {
+            Ok(current_op.id().hex())
+        } else {
+            Ok(parent_ops[0].id().hex())
+        }
+    }
+
+    /// Get typed change metadata by change ID
+    pub fn get_typed_change(&self, change_id: &str) -> Result<TypedChange> {
+        TypedChange::load_from_repo(&self.root, change_id)
+    }
+
+    /// Save typed change metadata
+    pub fn save_typed_change(&self, change: &TypedChange) -> Result<()> {
+        change.save(&self.root)
+    }
+
+    /// Describe the current change
+    pub fn describe(&mut self, message: &str) -> Result<()> {
+        let settings = create_minimal_settings()?;
+        let store_factories = get_store_factories();
+        let wc_factories = get_working_copy_factories();
+
+        let workspace = Workspace::load(&settings, &self.root, &store_factories, &wc_factories)
+            .map_err(|e| Error::Repository {
+                message: format!("failed to load workspace: {}", e),
+            })?;
+
+        let repo = workspace
+            .repo_loader()
+            .load_at_head()
+            .map_err(|e| Error::Repository {
+                message: format!("failed to load repository: {}", e),
+            })?;
+
+        let wc_commit_id = repo
+            .view()
+            .get_wc_commit_id(workspace.workspace_name())
+            .cloned()
+            .ok_or_else(|| Error::Repository {
+                message: "no working copy commit found".into(),
+            })?;
+
+        let commit = repo
+            .store()
+            .get_commit(&wc_commit_id)
+            .map_err(|e| Error::Repository {
+                message: format!("failed to get commit: {}", e),
+            })?;
+
+        // Start transaction
+        let _critical = crate::deadline::critical();
+        let mut tx = repo.start_transaction();
+
+        // Rewrite commit with new description
+        let new_commit = tx
+            .repo_mut()
+            .rewrite_commit(&commit)
+            .set_description(message)
+            .write()
+            .map_err(|e| Error::Repository {
+                message: format!("failed to rewrite commit: {}", e),
+            })?;
+
+        // Update working copy
+        tx.repo_mut()
+            .set_wc_commit(
+                workspace.workspace_name().to_owned(),
+                new_commit.id().clone(),
+            )
+            .map_err(|e| Error::Repository {
+                message: format!("failed to set working copy: {}", e),
+            })?;
 
         // Rebase descendants
         tx.repo_mut()
@@ -1251,6 +2646,112 @@ impl Repo {
         Ok(())
     }
 
+    /// Replace the descriptions of existing changes, given as full change
+    /// IDs, in one operation. Descendants are rebased onto the rewritten
+    /// commits; changes whose description already matches are left alone.
+    /// A dry run reports what would change without writing anything.
+    pub fn reword(
+        &mut self,
+        descriptions: &[(String, String)],
+        dry_run: bool,
+    ) -> Result<Vec<Reworded>> {
+        let repo = self.load_repo_at_head()?;
+        let _critical = crate::deadline::critical();
+        let mut tx = repo.start_transaction();
+        let mut reworded = Vec::new();
+        // One at a time, so a change below another is rebased before the
+        // upper one is looked up and rewritten
+        for (change_id, description) in descriptions {
+            let id = jj_lib::backend::ChangeId::try_from_hex(change_id).ok_or_else(|| {
+                Error::Repository {
+                    message: format!("invalid change ID: {}", change_id),
+                }
+            })?;
+            let commit_id = tx
+                .repo()
+                .resolve_change_id(&id)
+                .map_err(|e| Error::Repository {
+                    message: format!("failed to resolve change ID: {}", e),
+                })?
+                .and_then(|targets| {
+                    targets
+                        .visible_with_offsets()
+                        .next()
+                        .map(|(_, id)| id.clone())
+                })
+                .ok_or_else(|| Error::Repository {
+                    message: format!("change '{}' not found", change_id),
+                })?;
+            if &commit_id == tx.repo().store().root_commit_id() {
+                return Err(Error::Repository {
+                    message: "the root commit cannot be reworded".into(),
+                });
+            }
+            let commit =
+                tx.repo()
+                    .store()
+                    .get_commit(&commit_id)
+                    .map_err(|e| Error::Repository {
+                        message: format!("failed to get commit: {}", e),
+                    })?;
+            if commit.description() == description {
+                continue;
+            }
+            if dry_run {
+                reworded.push(Reworded {
+                    change_id: change_id.clone(),
+                    commit_id: None,
+                    previous: commit.description().to_string(),
+                    description: description.clone(),
+                });
+                continue;
+            }
+            let rewritten = tx
+                .repo_mut()
+                .rewrite_commit(&commit)
+                .set_description(description)
+                .write()
+                .map_err(|e| Error::Repository {
+                    message: format!("failed to rewrite commit: {}", e),
+                })?;
+            tx.repo_mut()
+                .rebase_descendants()
+                .map_err(|e| Error::Repository {
+                    message: format!("failed to rebase descendants: {}", e),
+                })?;
+            reworded.push(Reworded {
+                change_id: change_id.clone(),
+                commit_id: Some(rewritten.id().hex()),
+                previous: commit.description().to_string(),
+                description: description.clone(),
+            });
+        }
+        if reworded.is_empty() || dry_run {
+            return Ok(reworded);
+        }
+
+        if is_git_backed(tx.repo().store()) {
+            if let Err(e) = jj_lib::git::export_refs(tx.repo_mut()) {
+                eprintln!("warning: failed to export jj refs to git: {}", e);
+            }
+        }
+        let description = match reworded.as_slice() {
+            [one] => format!("reword {}", &one.change_id[..12.min(one.change_id.len())]),
+            many => format!("reword {} changes", many.len()),
+        };
+        tx.commit(description).map_err(|e| Error::Repository {
+            message: format!("failed to commit transaction: {}", e),
+        })?;
+        self.workspace = None;
+
+        // Descendants were rebased, so report where each change ended up
+        for change in &mut reworded {
+            let (_, commit_id) = self.resolve_revision(&change.change_id)?;
+            change.commit_id = Some(commit_id);
+        }
+        Ok(reworded)
+    }
+
     /// Create a new change
     pub fn new_change(&mut self, message: Option<&str>) -> Result<String> {
         let desc = message.unwrap_or("");
@@ -1307,6 +2808,7 @@ impl Repo {
             })?;
 
         // Start transaction
+        let _critical = crate::deadline::critical();
         let mut tx = repo.start_transaction();
 
         // Create new commit with current tree but parent's parents
@@ -1362,7 +2864,7 @@ impl Repo {
     }
 
     /// Resolve a jj revision spec to its commit ID hex and parent commit ID hex.
-    /// Supports @, @-, and jj change ID hex prefixes.
+    /// Supports @, @-, local bookmark names, and jj change ID hex prefixes.
     /// In colocated mode, jj commit IDs are git commit IDs.
     pub fn resolve_revision(&mut self, rev: &str) -> Result<(Option<String>, String)> {
         let repo = self.load_repo_at_head()?;
@@ -1398,6 +2900,15 @@ impl Repo {
                         message: "working copy has no parent".into(),
                     })?
             }
+            other if repo.view().get_local_bookmark(other.as_ref()).is_present() => repo
+                .view()
+                .get_local_bookmark(other.as_ref())
+                .added_ids()
+                .next()
+                .cloned()
+                .ok_or_else(|| Error::Repository {
+                    message: format!("bookmark '{}' has no commits", other),
+                })?,
             other => {
                 let change_id_obj =
                     jj_lib::backend::ChangeId::try_from_hex(other).ok_or_else(|| {
@@ -1436,65 +2947,509 @@ impl Repo {
         Ok((parent_hex, commit_id.hex()))
     }
 
-    /// Get structured log entries from the repository.
-    pub fn log_entries(&mut self, limit: usize, all: bool) -> Result<Vec<LogEntry>> {
+    /// Read the regular files at a revision whose paths satisfy `select`,
+    /// straight from the store. Accepts the same specs as `resolve_revision`.
+    pub fn files_at(
+        &mut self,
+        rev: &str,
+        select: impl Fn(&str) -> bool,
+    ) -> Result<Vec<(String, Vec<u8>)>> {
+        let (_, commit_hex) = self.resolve_revision(rev)?;
         let repo = self.load_repo_at_head()?;
-        let workspace = self.workspace.as_ref().unwrap();
-
-        let wc_commit_id = repo.view().get_wc_commit_id(workspace.workspace_name());
-
-        let mut entries = Vec::new();
-        let mut count = 0;
-
-        // Collect all heads into a single traversal to avoid duplicates
-        let mut to_visit: Vec<_> = repo.view().heads().iter().cloned().collect();
-        let mut visited = std::collections::HashSet::new();
-
-        while let Some(commit_id) = to_visit.pop() {
-            if !all && count >= limit {
-                break;
-            }
+        let commit_id = CommitId::try_from_hex(&commit_hex).ok_or_else(|| Error::Repository {
+            message: format!("invalid commit id: {}", commit_hex),
+        })?;
+        let commit = repo
+            .store()
+            .get_commit(&commit_id)
+            .map_err(|e| Error::Repository {
+                message: format!("failed to get commit: {}", e),
+            })?;
 
-            if !visited.insert(commit_id.clone()) {
+        let mut files = Vec::new();
+        for (path, value) in commit.tree().entries() {
+            let name = path.as_internal_file_string().to_string();
+            if !select(&name) {
                 continue;
             }
+            // Conflicted and non-file entries have no single content to read
+            if let Ok(Some(jj_lib::backend::TreeValue::File { id, .. })) =
+                value.map(|v| v.into_resolved().ok().flatten())
+            {
+                files.push((name, read_file_bytes(repo.store(), &path, &id)?));
+            }
+        }
+        Ok(files)
+    }
 
-            let commit = match repo.store().get_commit(&commit_id) {
-                Ok(c) => c,
-                Err(_) => continue,
-            };
+    /// Write the files and symlinks at a revision whose paths satisfy
+    /// `select` under `dest`, straight from the store; the working copy is
+    /// left alone. Returns each path written with its size in bytes.
+    pub fn export_at(
+        &mut self,
+        rev: &str,
+        select: impl Fn(&str) -> bool,
+        dest: &Path,
+    ) -> Result<Vec<(String, u64)>> {
+        let (_, commit_hex) = self.resolve_revision(rev)?;
+        let repo = self.load_repo_at_head()?;
+        let commit_id = CommitId::try_from_hex(&commit_hex).ok_or_else(|| Error::Repository {
+            message: format!("invalid commit id: {}", commit_hex),
+        })?;
+        let commit = repo
+            .store()
+            .get_commit(&commit_id)
+            .map_err(|e| Error::Repository {
+                message: format!("failed to get commit: {}", e),
+            })?;
 
-            // Skip root commit
-            if commit.change_id().hex().starts_with("zzzzzzzz") {
+        let mut written = Vec::new();
+        for (path, value) in commit.tree().entries() {
+            let name = path.as_internal_file_string().to_string();
+            if !select(&name) {
                 continue;
             }
+            // Conflicted entries have no single content to write
+            let Ok(Some(value)) = value.map(|v| v.into_resolved().ok().flatten()) else {
+                continue;
+            };
+            let target = dest.join(&name);
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            match read_entry(repo.store(), &path, &Some(value))? {
+                Some(Entry::File {
+                    content,
+                    executable,
+                }) => {
+                    std::fs::write(&target, &content)?;
+                    set_executable(&target, executable)?;
+                    written.push((name, content.len() as u64));
+                }
+                Some(Entry::Symlink { target: link }) => {
+                    create_symlink(&link, &target)?;
+                    written.push((name, 0));
+                }
+                None => {}
+            }
+        }
+        Ok(written)
+    }
 
-            let is_working_copy = wc_commit_id.map(|id| id == &commit_id).unwrap_or(false);
-
-            let parent_change_ids: Vec<String> = commit
-                .parent_ids()
-                .iter()
-                .filter_map(|pid| {
-                    repo.store().get_commit(pid).ok().map(|p| {
-                        let hex = p.change_id().hex();
-                        if hex.len() > 8 {
-                            hex[..8].to_string()
-                        } else {
-                            hex
-                        }
-                    })
+    /// Attribute each line of a file in the working-copy commit to the
+    /// change that last touched it. Snapshot first so the commit matches the
+    /// files on disk.
+    pub fn blame(&mut self, path: &str) -> Result<Vec<LineBlame>> {
+        let max_depth = self.max_history();
+        let repo = self.load_repo_at_head()?;
+        let workspace = self.workspace.as_ref().unwrap();
+        let wc_id = repo
+            .view()
+            .get_wc_commit_id(workspace.workspace_name())
+            .cloned()
+            .ok_or_else(|| Error::Repository {
+                message: "no working copy commit found".into(),
+            })?;
+        let wc_commit = repo
+            .store()
+            .get_commit(&wc_id)
+            .map_err(|e| Error::Repository {
+                message: format!("failed to get commit: {}", e),
+            })?;
+        let repo_path =
+            jj_lib::repo_path::RepoPathBuf::from_internal_string(path).map_err(|e| {
+                Error::Repository {
+                    message: format!("invalid path '{}': {}", path, e),
+                }
+            })?;
+
+        let mut annotator = jj_lib::annotate::FileAnnotator::from_commit(&wc_commit, &repo_path)
+            .map_err(|e| Error::Repository {
+                message: format!("failed to read '{}': {}", path, e),
+            })?;
+        // Past the depth limit, lines stay with the oldest commit walked
+        let domain = match max_depth {
+            Some(depth) => jj_lib::revset::RevsetExpression::commit(wc_id.clone())
+                .ancestors_range(0..depth as u64),
+            None => jj_lib::revset::RevsetExpression::all(),
+        };
+        annotator
+            .compute(repo.as_ref(), &domain)
+            .map_err(|e| Error::Repository {
+                message: format!("failed to annotate '{}': {}", path, e),
+            })?;
+
+        let mut by_commit: HashMap<CommitId, LineBlame> = HashMap::new();
+        let mut lines = Vec::new();
+        for (origin, _) in annotator.to_annotation().lines() {
+            let commit_id = origin.unwrap_or_else(|id| id);
+            if !by_commit.contains_key(commit_id) {
+                let commit = repo
+                    .store()
+                    .get_commit(commit_id)
+                    .map_err(|e| Error::Repository {
+                        message: format!("failed to get commit: {}", e),
+                    })?;
+                let change_hex = commit.change_id().hex();
+                by_commit.insert(
+                    commit_id.clone(),
+                    LineBlame {
+                        change_id: change_hex[..change_hex.len().min(8)].to_string(),
+                        commit_id: commit_id.hex(),
+                        timestamp: commit.author().timestamp.timestamp.0 / 1000,
+                        is_working_copy: *commit_id == wc_id,
+                    },
+                );
+            }
+            lines.push(by_commit[commit_id].clone());
+        }
+        Ok(lines)
+    }
+    /// Full change IDs of the working-copy commit and its ancestors that are
+    /// not ancestors of commit `since` (e.g. the last release tag), newest
+    /// first. Without `since`, every change in the working copy's history.
+    pub fn change_ids_since(&mut self, since: Option<&str>) -> Result<Vec<String>> {
+        Ok(self
+            .history_since(since)?
+            .into_iter()
+            .map(|entry| entry.change_id)
+            .collect())
+    }
+
+    /// The commits `change_ids_since` covers, with their descriptions and
+    /// authors, newest first
+    pub fn history_since(&mut self, since: Option<&str>) -> Result<Vec<HistoryEntry>> {
+        use jj_lib::revset::RevsetExpression;
+
+        let max_depth = self.max_history();
+        let mut history = self.history_cache()?;
+        let repo = self.load_repo_at_head()?;
+        let workspace = self.workspace.as_ref().unwrap();
+        let wc_id = repo
+            .view()
+            .get_wc_commit_id(workspace.workspace_name())
+            .cloned()
+            .ok_or_else(|| Error::Repository {
+                message: "no working copy commit found".into(),
+            })?;
+        let roots = match since {
+            Some(hex) => {
+                RevsetExpression::commit(CommitId::try_from_hex(hex).ok_or_else(|| {
+                    Error::Repository {
+                        message: format!("invalid commit id: {}", hex),
+                    }
+                })?)
+            }
+            None => RevsetExpression::root(),
+        };
+        let revset = roots
+            .range(&RevsetExpression::commit(wc_id))
+            .evaluate(repo.as_ref())
+            .map_err(|e| Error::Repository {
+                message: format!("failed to evaluate revset: {}", e),
+            })?;
+        revset
+            .iter()
+            .take(max_depth.unwrap_or(usize::MAX))
+            .map(|id| {
+                let id = id.map_err(|e| Error::Repository {
+                    message: format!("failed to walk history: {}", e),
+                })?;
+                Ok(history_entry(&history.get(repo.store(), &id)?))
+            })
+            .collect()
+    }
+
+    /// Commit `head` (a full commit ID) and its ancestors that the working
+    /// copy's history lacks, newest first: what another branch would bring
+    /// in. Commits a fetch added that jj has not imported are included.
+    pub fn commits_missing(&mut self, head: &str) -> Result<Vec<HistoryEntry>> {
+        let repo = self.load_repo_at_head()?;
+        let workspace = self.workspace.as_ref().unwrap();
+        let wc_id = repo
+            .view()
+            .get_wc_commit_id(workspace.workspace_name())
+            .cloned()
+            .ok_or_else(|| Error::Repository {
+                message: "no working copy commit found".into(),
+            })?;
+        let head = CommitId::try_from_hex(head).ok_or_else(|| Error::Repository {
+            message: format!("invalid commit id: {}", head),
+        })?;
+
+        // Unindexed commits cannot be in the working copy's history, so the
+        // walk stops only at indexed ancestors of it
+        let index = repo.index();
+        let shared = |id: &CommitId| -> bool {
+            index.has_id(id).unwrap_or(false) && index.is_ancestor(id, &wc_id).unwrap_or(false)
+        };
+        let mut commits = Vec::new();
+        let mut seen = HashSet::new();
+        let mut to_visit = vec![head];
+        while let Some(id) = to_visit.pop() {
+            if id == *repo.store().root_commit_id() || !seen.insert(id.clone()) || shared(&id) {
+                continue;
+            }
+            let commit = repo
+                .store()
+                .get_commit(&id)
+                .map_err(|e| Error::Repository {
+                    message: format!("failed to get commit: {}", e),
+                })?;
+            to_visit.extend(commit.parent_ids().iter().cloned());
+            commits.push(commit);
+        }
+        commits.sort_by_key(|c| std::cmp::Reverse(c.committer().timestamp.timestamp.0));
+        Ok(commits
+            .iter()
+            .map(|c| history_entry(&crate::history::CommitRecord::from_commit(c)))
+            .collect())
+    }
+    /// Remote-tracking branches as (`remote/name`, full commit ID): jj's
+    /// remote bookmarks, and `refs/remotes/*` in a colocated git repository
+    /// (pushes go through git directly, so jj may not have imported them yet)
+    pub fn remote_branches(&mut self) -> Result<Vec<(String, String)>> {
+        let repo = self.load_repo_at_head()?;
+
+        let mut branches: BTreeMap<String, String> = BTreeMap::new();
+        for (symbol, remote_ref) in repo.view().all_remote_bookmarks() {
+            if symbol.remote == jj_lib::git::REMOTE_NAME_FOR_LOCAL_GIT_REPO {
+                continue;
+            }
+            if let Some(commit_id) = remote_ref.target.as_normal() {
+                branches.insert(
+                    format!("{}/{}", symbol.remote.as_str(), symbol.name.as_str()),
+                    commit_id.hex(),
+                );
+            }
+        }
+        if is_git_backed(repo.store()) && self.root.join(".git").exists() {
+            let output = Command::new("git")
+                .current_dir(&self.root)
+                .args([
+                    "for-each-ref",
+                    "--format=%(refname:strip=2) %(objectname)",
+                    "refs/remotes",
+                ])
+                .output()
+                .ok()
+                .filter(|o| o.status.success());
+            if let Some(output) = output {
+                for line in String::from_utf8_lossy(&output.stdout).lines() {
+                    // `origin/HEAD` only repeats another branch
+                    if let Some((name, hex)) = line.split_once(' ') {
+                        if !name.ends_with("/HEAD") {
+                            branches.insert(name.to_string(), hex.trim().to_string());
+                        }
+                    }
+                }
+            }
+        }
+        Ok(branches.into_iter().collect())
+    }
+
+    /// Commits that must not be rewritten, by full commit ID: ancestors of
+    /// `remote_branches` and of the manifest's `branches.immutable`
+    /// revisions. The root commit is not included.
+    pub fn immutable_commits(&mut self) -> Result<HashSet<String>> {
+        let configured = self
+            .manifest()
+            .map(|m| m.branches.immutable.clone())
+            .unwrap_or_default();
+        let repo = self.load_repo_at_head()?;
+
+        let mut heads: Vec<CommitId> = self
+            .remote_branches()?
+            .iter()
+            .filter_map(|(_, hex)| CommitId::try_from_hex(hex))
+            .collect();
+        // A configured bookmark that does not exist yet protects nothing
+        for rev in &configured {
+            if let Ok((_, hex)) = self.resolve_revision(rev) {
+                heads.extend(CommitId::try_from_hex(&hex));
+            }
+        }
+
+        let root_id = repo.store().root_commit_id().clone();
+        let mut immutable = HashSet::new();
+        let mut seen = HashSet::new();
+        while let Some(commit_id) = heads.pop() {
+            if commit_id == root_id || !seen.insert(commit_id.clone()) {
+                continue;
+            }
+            // Remote refs can name objects jj cannot read (e.g. tags)
+            let Ok(commit) = repo.store().get_commit(&commit_id) else {
+                continue;
+            };
+            immutable.insert(commit_id.hex());
+            heads.extend(commit.parent_ids().iter().cloned());
+        }
+        Ok(immutable)
+    }
+
+    /// Fail with `ImmutableCommit` if any of the changes is in
+    /// `immutable_commits`
+    pub fn require_mutable(&mut self, change_ids: &[String]) -> Result<()> {
+        let immutable = self.immutable_commits()?;
+        if immutable.is_empty() {
+            return Ok(());
+        }
+        let mut blocked = Vec::new();
+        for change_id in change_ids {
+            let (_, commit_id) = self.resolve_revision(change_id)?;
+            if immutable.contains(&commit_id) {
+                blocked.push(change_id.clone());
+            }
+        }
+        if blocked.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::ImmutableCommit {
+                change_ids: blocked,
+            })
+        }
+    }
+
+    /// Change IDs a push of `head` (a full commit ID) would send: its
+    /// ancestors not yet in `immutable_commits`, newest first
+    pub fn unpushed_changes(&mut self, head: &str) -> Result<Vec<String>> {
+        let immutable = self.immutable_commits()?;
+        let repo = self.load_repo_at_head()?;
+        let root_id = repo.store().root_commit_id().clone();
+
+        let mut pushing: Vec<String> = Vec::new();
+        let mut to_visit: Vec<CommitId> = CommitId::try_from_hex(head).into_iter().collect();
+        let mut seen = HashSet::new();
+        while let Some(commit_id) = to_visit.pop() {
+            if commit_id == root_id
+                || immutable.contains(&commit_id.hex())
+                || !seen.insert(commit_id.clone())
+            {
+                continue;
+            }
+            let Ok(commit) = repo.store().get_commit(&commit_id) else {
+                continue;
+            };
+            pushing.push(commit.change_id().hex());
+            to_visit.extend(commit.parent_ids().iter().cloned());
+        }
+        Ok(pushing)
+    }
+
+    /// Declared dependencies (`depends_on`) that pushing `head` (a full
+    /// commit ID) would leave unmet. The push sends the ancestors of `head`
+    /// not yet in `immutable_commits`; each of their dependencies must be
+    /// among them or already immutable.
+    pub fn unmet_dependencies(&mut self, head: &str) -> Result<Vec<UnmetDependency>> {
+        let immutable = self.immutable_commits()?;
+        let pushing = self.unpushed_changes(head)?;
+
+        let mut unmet = Vec::new();
+        for change_id in &pushing {
+            let Ok(typed) = self.get_typed_change(change_id) else {
+                continue;
+            };
+            for dep in typed.depends_on {
+                if pushing.contains(&dep) {
+                    continue;
+                }
+                let reason = match self.resolve_revision(&dep) {
+                    Ok((_, commit_id)) if immutable.contains(&commit_id) => continue,
+                    Ok(_) => "not_pushed",
+                    Err(_) => "not_found",
+                };
+                unmet.push(UnmetDependency {
+                    change_id: change_id.clone(),
+                    depends_on: dep,
+                    reason: reason.to_string(),
+                });
+            }
+        }
+        Ok(unmet)
+    }
+
+    /// How far back history walks go: `--max-history`, else the manifest's
+    /// `[history] max_depth`; None walks everything
+    pub fn max_history(&mut self) -> Option<usize> {
+        crate::history::max_depth_override()
+            .or_else(|| self.manifest().ok().and_then(|m| m.history.max_depth))
+            .filter(|&depth| depth > 0)
+    }
+
+    /// Commit records for history walks, kept on disk unless `cache.history`
+    /// is off
+    fn history_cache(&mut self) -> Result<HistoryCache> {
+        let persist = self.config()?.cache.history;
+        Ok(HistoryCache::open(&self.root, persist))
+    }
+
+    /// Get structured log entries from the repository.
+    pub fn log_entries(&mut self, limit: usize, all: bool) -> Result<Vec<LogEntry>> {
+        let immutable = self.immutable_commits()?;
+        let max_depth = self.max_history();
+        let mut history = self.history_cache()?;
+        let repo = self.load_repo_at_head()?;
+        let workspace = self.workspace.as_ref().unwrap();
+
+        let wc_commit_id = repo.view().get_wc_commit_id(workspace.workspace_name());
+
+        let mut entries = Vec::new();
+        let mut count = 0;
+
+        // Collect all heads into a single traversal to avoid duplicates,
+        // each with how many parent steps it is from a head
+        let mut to_visit: Vec<(CommitId, usize)> = repo
+            .view()
+            .heads()
+            .iter()
+            .map(|id| (id.clone(), 0))
+            .collect();
+        let mut visited = std::collections::HashSet::new();
+
+        while let Some((commit_id, depth)) = to_visit.pop() {
+            if !all && count >= limit {
+                break;
+            }
+
+            if max_depth.is_some_and(|max| depth >= max) || !visited.insert(commit_id.clone()) {
+                continue;
+            }
+
+            let commit = match history.get(repo.store(), &commit_id) {
+                Ok(c) => c,
+                Err(_) => continue,
+            };
+
+            // Skip root commit
+            if commit.change_id.starts_with("zzzzzzzz") {
+                continue;
+            }
+
+            let is_working_copy = wc_commit_id.map(|id| id == &commit_id).unwrap_or(false);
+
+            let parent_ids = commit.parent_ids();
+            let parent_change_ids: Vec<String> = parent_ids
+                .iter()
+                .filter_map(|pid| {
+                    history.get(repo.store(), pid).ok().map(|p| {
+                        let hex = p.change_id;
+                        if hex.len() > 8 {
+                            hex[..8].to_string()
+                        } else {
+                            hex
+                        }
+                    })
                 })
                 .collect();
 
-            let change_hex = commit.change_id().hex();
+            let change_hex = commit.change_id.clone();
             let commit_hex = commit_id.hex();
 
             // Extract author timestamp as ISO 8601 string
-            let author_sig = commit.author();
             let timestamp = {
-                let millis = author_sig.timestamp.timestamp.0;
+                let millis = commit.timestamp_ms;
                 let secs = millis / 1000;
-                let tz_offset_mins = author_sig.timestamp.tz_offset;
+                let tz_offset_mins = commit.tz_offset;
                 let tz_offset_secs = (tz_offset_mins as i64) * 60;
                 let abs_offset = tz_offset_mins.unsigned_abs();
                 let tz_sign = if tz_offset_mins >= 0 { '+' } else { '-' };
@@ -1515,8 +3470,8 @@ impl Repo {
 
             // Extract author name, falling back to email
             let author = {
-                let name = &author_sig.name;
-                let email = &author_sig.email;
+                let name = &commit.author_name;
+                let email = &commit.author_email;
                 if !name.is_empty() {
                     Some(name.clone())
                 } else if !email.is_empty() {
@@ -1539,25 +3494,21 @@ impl Repo {
                 } else {
                     commit_hex
                 },
-                description: commit
-                    .description()
-                    .lines()
-                    .next()
-                    .unwrap_or("")
-                    .to_string(),
+                description: commit.description.lines().next().unwrap_or("").to_string(),
                 parent_change_ids,
                 is_working_copy,
                 timestamp,
                 author,
+                immutable: immutable.contains(&full_commit_id),
                 full_commit_id,
             });
 
             count += 1;
 
             // Add parents to visit
-            for parent_id in commit.parent_ids() {
-                if !visited.contains(parent_id) {
-                    to_visit.push(parent_id.clone());
+            for parent_id in parent_ids {
+                if !visited.contains(&parent_id) {
+                    to_visit.push((parent_id, depth + 1));
                 }
             }
         }
@@ -1585,15 +3536,1215 @@ impl Repo {
 
             count += 1;
 
-            // Get parent operation
-            current_op = op.parents().next().and_then(|r| r.ok());
-        }
+            // Get parent operation
+            current_op = op.parents().next().and_then(|r| r.ok());
+        }
+
+        Ok(operations)
+    }
+
+    /// The operation DAG back from the current operation, newest first.
+    /// Unlike `operation_log`, follows every parent of a merged operation.
+    pub fn operation_graph(&mut self, limit: usize) -> Result<Vec<OperationNode>> {
+        let repo = self.load_repo_at_head()?;
+        let head = repo.operation().clone();
+        let short = |id: &jj_lib::op_store::OperationId| {
+            let hex = id.hex();
+            hex[..12.min(hex.len())].to_string()
+        };
+
+        let mut nodes = Vec::new();
+        let mut seen = std::collections::HashSet::new();
+        let mut frontier = vec![head.clone()];
+        while nodes.len() < limit {
+            // Newest operation first, so concurrent branches interleave by time
+            frontier.sort_by_key(|op| op.metadata().time.end.timestamp.0);
+            let Some(op) = frontier.pop() else {
+                break;
+            };
+            if !seen.insert(op.id().clone()) {
+                continue;
+            }
+            let description = op.metadata().description.clone();
+            nodes.push(OperationNode {
+                id: short(op.id()),
+                full_id: op.id().hex(),
+                restores: OperationNode::restores(&description),
+                description,
+                parents: op.parent_ids().iter().map(short).collect(),
+                timestamp: utc_timestamp(op.metadata().time.end.timestamp.0.div_euclid(1000)),
+                is_head: op.id() == head.id(),
+            });
+            for parent in op.parents() {
+                let parent = parent.map_err(|e| Error::Repository {
+                    message: format!("failed to load operation: {}", e),
+                })?;
+                if !seen.contains(parent.id()) {
+                    frontier.push(parent);
+                }
+            }
+        }
+        Ok(nodes)
+    }
+
+    /// Whether an operation can still be loaded from the op store
+    pub fn operation_exists(&mut self, op_id: &str) -> bool {
+        self.load_repo_at_operation(op_id).is_ok()
+    }
+
+    /// Whether a change ID still has a visible commit
+    pub fn change_is_visible(&mut self, change_id: &str) -> Result<bool> {
+        let repo = self.load_repo_at_head()?;
+        let Some(change_id_obj) = jj_lib::backend::ChangeId::try_from_hex(change_id) else {
+            return Ok(false);
+        };
+        let targets = repo
+            .resolve_change_id(&change_id_obj)
+            .map_err(|e| Error::Repository {
+                message: format!("failed to resolve change ID: {}", e),
+            })?;
+        Ok(targets.is_some_and(|t| t.visible_with_offsets().next().is_some()))
+    }
+
+    /// Garbage-collect unreachable operations, views, and objects older than
+    /// `keep_newer` (jj's `util gc`; the git backend also runs `git gc`).
+    pub fn gc(&mut self, keep_newer: std::time::SystemTime) -> Result<()> {
+        let repo = self.load_repo_at_head()?;
+        repo.op_store()
+            .gc(std::slice::from_ref(repo.op_id()), keep_newer)
+            .map_err(|e| Error::Repository {
+                message: format!("failed to gc operations: {}", e),
+            })?;
+        repo.store()
+            .gc(repo.index(), keep_newer)
+            .map_err(|e| Error::Repository {
+                message: format!("failed to gc store: {}", e),
+            })?;
+        Ok(())
+    }
+
+    /// Restore the repository to a specific operation.
+    pub fn restore_operation(&mut self, op_id: &str) -> Result<()> {
+        self.restore_operation_as(op_id, &format!("restore to operation {}", op_id))
+    }
+
+    /// Restore to an earlier operation as an undo, recorded as such in the
+    /// operation log
+    pub fn undo_to_operation(&mut self, op_id: &str) -> Result<()> {
+        self.restore_operation_as(op_id, &format!("undo to operation {}", op_id))
+    }
+
+    fn restore_operation_as(&mut self, op_id: &str, description: &str) -> Result<()> {
+        let settings = create_minimal_settings()?;
+        let store_factories = get_store_factories();
+        let wc_factories = get_working_copy_factories();
+
+        let workspace = Workspace::load(&settings, &self.root, &store_factories, &wc_factories)
+            .map_err(|e| Error::Repository {
+                message: format!("failed to load workspace: {}", e),
+            })?;
+
+        let repo = workspace
+            .repo_loader()
+            .load_at_head()
+            .map_err(|e| Error::Repository {
+                message: format!("failed to load repository: {}", e),
+            })?;
+
+        // Find the operation by ID
+        let op_id_obj = jj_lib::op_store::OperationId::try_from_hex(op_id).ok_or_else(|| {
+            Error::Repository {
+                message: format!("invalid operation ID: {}", op_id),
+            }
+        })?;
+
+        let target_op = workspace
+            .repo_loader()
+            .load_operation(&op_id_obj)
+            .map_err(|e| Error::Repository {
+                message: format!("failed to load operation: {}", e),
+            })?;
+
+        // Load repo at target operation
+        let target_repo =
+            workspace
+                .repo_loader()
+                .load_at(&target_op)
+                .map_err(|e| Error::Repository {
+                    message: format!("failed to load repository at operation: {}", e),
+                })?;
+
+        // Create a transaction to record the restore
+        let _critical = crate::deadline::critical();
+        let mut tx = repo.start_transaction();
+
+        // Merge in the target operation's view
+        tx.repo_mut()
+            .merge(&repo, &target_repo)
+            .map_err(|e| Error::Repository {
+                message: format!("failed to merge operation: {}", e),
+            })?;
+        // Merging views can record rewrites (e.g. a re-created working-copy
+        // commit); jj requires descendants to be rebased before committing
+        tx.repo_mut()
+            .rebase_descendants()
+            .map_err(|e| Error::Repository {
+                message: format!("failed to rebase descendants: {}", e),
+            })?;
+
+        // Commit the restore transaction
+        tx.commit(description).map_err(|e| Error::Repository {
+            message: format!("failed to commit restore: {}", e),
+        })?;
+
+        // Clear cached workspace
+        self.workspace = None;
+
+        Ok(())
+    }
+
+    /// Snapshot the working copy into a tree without recording an operation.
+    /// The working copy lock is dropped without finishing, so nothing about
+    /// the repository state changes; this is safe for read-only inspection.
+    fn snapshot_working_copy(&mut self) -> Result<jj_lib::merged_tree::MergedTree> {
+        let _span = tracing::info_span!("snapshot", record = false).entered();
+        let settings = create_minimal_settings()?;
+        let store_factories = get_store_factories();
+        let wc_factories = get_working_copy_factories();
+
+        let mut workspace = Workspace::load(&settings, &self.root, &store_factories, &wc_factories)
+            .map_err(|e| Error::Repository {
+                message: format!("failed to load workspace: {}", e),
+            })?;
+
+        let _critical = crate::deadline::critical();
+        let mut locked_ws =
+            workspace
+                .start_working_copy_mutation()
+                .map_err(|e| Error::Repository {
+                    message: format!("failed to start working copy mutation: {}", e),
+                })?;
+
+        let snapshot_options = SnapshotOptions {
+            base_ignores: load_base_ignores(&self.root),
+            progress: None,
+            start_tracking_matcher: &EverythingMatcher,
+            force_tracking_matcher: &NothingMatcher,
+            max_new_file_size: 1_000_000_000,
+        };
+
+        let (tree, _stats) = locked_ws
+            .locked_wc()
+            .snapshot(&snapshot_options)
+            .block_on()
+            .map_err(|e| Error::Repository {
+                message: format!("failed to snapshot working copy: {}", e),
+            })?;
+
+        Ok(tree)
+    }
+
+    /// ID of the tree on disk right now (snapshotted without recording an
+    /// operation): equal IDs mean identical tracked content
+    pub fn current_tree_id(&mut self) -> Result<String> {
+        Ok(tree_key(&self.snapshot_working_copy()?))
+    }
+
+    /// Snapshot the working copy into the working-copy commit and record it
+    /// as an operation (what jj does before every command). Returns the
+    /// resulting operation ID, which is unchanged when the disk state already
+    /// matches the working-copy commit.
+    pub fn snapshot(&mut self) -> Result<String> {
+        let _span = tracing::info_span!("snapshot", record = true).entered();
+        let settings = create_minimal_settings()?;
+        let store_factories = get_store_factories();
+        let wc_factories = get_working_copy_factories();
+
+        let mut workspace = Workspace::load(&settings, &self.root, &store_factories, &wc_factories)
+            .map_err(|e| Error::Repository {
+                message: format!("failed to load workspace: {}", e),
+            })?;
+
+        let workspace_name = workspace.workspace_name().to_owned();
+        let repo = workspace
+            .repo_loader()
+            .load_at_head()
+            .map_err(|e| Error::Repository {
+                message: format!("failed to load repository: {}", e),
+            })?;
+
+        let wc_commit_id = repo
+            .view()
+            .get_wc_commit_id(&workspace_name)
+            .cloned()
+            .ok_or_else(|| Error::Repository {
+                message: "no working copy commit found".into(),
+            })?;
+
+        let wc_commit = repo
+            .store()
+            .get_commit(&wc_commit_id)
+            .map_err(|e| Error::Repository {
+                message: format!("failed to get working copy commit: {}", e),
+            })?;
+
+        let _critical = crate::deadline::critical();
+        let mut locked_ws =
+            workspace
+                .start_working_copy_mutation()
+                .map_err(|e| Error::Repository {
+                    message: format!("failed to start working copy mutation: {}", e),
+                })?;
+
+        let snapshot_options = SnapshotOptions {
+            base_ignores: load_base_ignores(&self.root),
+            progress: None,
+            start_tracking_matcher: &EverythingMatcher,
+            force_tracking_matcher: &NothingMatcher,
+            max_new_file_size: 1_000_000_000,
+        };
+
+        let (new_tree, _stats) = locked_ws
+            .locked_wc()
+            .snapshot(&snapshot_options)
+            .block_on()
+            .map_err(|e| Error::Repository {
+                message: format!("failed to snapshot working copy: {}", e),
+            })?;
+
+        if new_tree.tree_ids() == wc_commit.tree().tree_ids() {
+            // Nothing changed on disk; release the lock without a new operation
+            return Ok(repo.op_id().hex());
+        }
+
+        let _critical = crate::deadline::critical();
+        let mut tx = repo.start_transaction();
+        let new_wc = tx
+            .repo_mut()
+            .rewrite_commit(&wc_commit)
+            .set_tree(new_tree)
+            .write()
+            .map_err(|e| Error::Repository {
+                message: format!("failed to write snapshot commit: {}", e),
+            })?;
+        tx.repo_mut()
+            .set_wc_commit(workspace_name, new_wc.id().clone())
+            .map_err(|e| Error::Repository {
+                message: format!("failed to set working copy: {}", e),
+            })?;
+        tx.repo_mut()
+            .rebase_descendants()
+            .map_err(|e| Error::Repository {
+                message: format!("failed to rebase descendants: {}", e),
+            })?;
+
+        let new_repo = tx
+            .commit("snapshot working copy")
+            .map_err(|e| Error::Repository {
+                message: format!("failed to commit transaction: {}", e),
+            })?;
+
+        locked_ws
+            .finish(new_repo.op_id().clone())
+            .map_err(|e| Error::Repository {
+                message: format!("failed to finish working copy: {}", e),
+            })?;
+
+        self.workspace = None;
+
+        Ok(new_repo.op_id().hex())
+    }
+
+    /// Status of every file that is not clean: tracked changes against the
+    /// parent (after snapshotting the working copy), conflicts, and files on
+    /// disk the snapshot did not track (ignored, or untracked e.g. too large).
+    /// Paths not in the map are clean.
+    pub fn working_copy_status(&mut self) -> Result<HashMap<String, FileStatus>> {
+        self.snapshot()?;
+        let repo = self.load_repo_at_head()?;
+        let workspace_name = self.workspace.as_ref().unwrap().workspace_name().to_owned();
+
+        let wc_commit_id = repo
+            .view()
+            .get_wc_commit_id(&workspace_name)
+            .ok_or_else(|| Error::Repository {
+                message: "no working copy commit found".into(),
+            })?;
+        let wc_commit = repo
+            .store()
+            .get_commit(wc_commit_id)
+            .map_err(|e| Error::Repository {
+                message: format!("failed to get working copy commit: {}", e),
+            })?;
+        let parent_tree = wc_commit
+            .parent_tree(&*repo)
+            .map_err(|e| Error::Repository {
+                message: format!("failed to get parent tree: {}", e),
+            })?;
+        let tree = wc_commit.tree();
+
+        let mut statuses = HashMap::new();
+        let diff_iter =
+            jj_lib::merged_tree::TreeDiffIterator::new(&parent_tree, &tree, &EverythingMatcher);
+        for entry in diff_iter {
+            let status = match entry.values {
+                Ok(diff) if diff.after.is_absent() => FileStatus::Deleted,
+                Ok(diff) if diff.before.is_absent() => FileStatus::Added,
+                _ => FileStatus::Modified,
+            };
+            statuses.insert(entry.path.as_internal_file_string().to_string(), status);
+        }
+        for (path, _) in tree.conflicts() {
+            statuses.insert(
+                path.as_internal_file_string().to_string(),
+                FileStatus::Conflicted,
+            );
+        }
+
+        let tracked: std::collections::HashSet<String> = tree
+            .entries()
+            .map(|(path, _)| path.as_internal_file_string().to_string())
+            .collect();
+        collect_untracked(
+            &self.root,
+            "",
+            &load_base_ignores(&self.root),
+            false,
+            &tracked,
+            &mut statuses,
+        );
+
+        Ok(statuses)
+    }
+
+    /// Describe everything that happened since `op_id`: operations run,
+    /// changes created, rewritten or abandoned, and working-copy files that
+    /// differ from the working-copy commit recorded at that operation.
+    pub fn diff_since_operation(&mut self, op_id: &str) -> Result<OperationDiff> {
+        let repo = self.load_repo_at_head()?;
+        let then_repo = self.load_repo_at_operation(op_id)?;
+        let workspace_name = self.workspace.as_ref().unwrap().workspace_name().to_owned();
+        let then_op = then_repo.operation();
+
+        // Walk the op log back from head until we reach the recorded operation
+        let mut operations_since = Vec::new();
+        let mut found = false;
+        let mut current_op = Some(repo.operation().clone());
+        while let Some(op) = current_op {
+            if op.id() == then_op.id() {
+                found = true;
+                break;
+            }
+            operations_since.push(OperationInfo {
+                id: op.id().hex(),
+                description: op.metadata().description.clone(),
+            });
+            current_op = op.parents().next().and_then(|r| r.ok());
+        }
+
+        let index = repo.index();
+        let now_heads: Vec<CommitId> = repo.view().heads().iter().cloned().collect();
+        let then_heads: Vec<CommitId> = then_repo.view().heads().iter().cloned().collect();
+
+        // Commits reachable from `from` that are not ancestors of any of `stop`
+        let exclusive_commits = |from: &[CommitId], stop: &[CommitId]| -> Vec<CommitId> {
+            let mut result = Vec::new();
+            let mut to_visit: Vec<CommitId> = from.to_vec();
+            let mut visited = std::collections::HashSet::new();
+            while let Some(id) = to_visit.pop() {
+                if !visited.insert(id.clone()) {
+                    continue;
+                }
+                let reachable = stop
+                    .iter()
+                    .any(|s| index.is_ancestor(&id, s).unwrap_or(false));
+                if reachable {
+                    continue;
+                }
+                if let Ok(commit) = repo.store().get_commit(&id) {
+                    to_visit.extend(commit.parent_ids().iter().cloned());
+                }
+                result.push(id);
+            }
+            result
+        };
+
+        let mut changes_created = Vec::new();
+        let mut changes_rewritten = Vec::new();
+        for id in exclusive_commits(&now_heads, &then_heads) {
+            let Ok(commit) = repo.store().get_commit(&id) else {
+                continue;
+            };
+            let change_hex = commit.change_id().hex();
+            let existed = then_repo
+                .resolve_change_id(commit.change_id())
+                .ok()
+                .flatten()
+                .is_some_and(|t| t.visible_with_offsets().next().is_some());
+            let bucket = if existed {
+                &mut changes_rewritten
+            } else {
+                &mut changes_created
+            };
+            if !bucket.contains(&change_hex) {
+                bucket.push(change_hex);
+            }
+        }
+
+        let mut changes_abandoned = Vec::new();
+        for id in exclusive_commits(&then_heads, &now_heads) {
+            let Ok(commit) = repo.store().get_commit(&id) else {
+                continue;
+            };
+            let change_hex = commit.change_id().hex();
+            let still_visible = repo
+                .resolve_change_id(commit.change_id())
+                .ok()
+                .flatten()
+                .is_some_and(|t| t.visible_with_offsets().next().is_some());
+            if !still_visible && !changes_abandoned.contains(&change_hex) {
+                changes_abandoned.push(change_hex);
+            }
+        }
+
+        // Compare the recorded working-copy tree against what is on disk now
+        let then_wc_id = then_repo
+            .view()
+            .get_wc_commit_id(&workspace_name)
+            .cloned()
+            .ok_or_else(|| Error::Repository {
+                message: "no working copy commit found at recorded operation".into(),
+            })?;
+        // Tree diffs require both sides to come from the same store instance
+        let now_tree = self.snapshot_working_copy()?;
+        let then_tree = now_tree
+            .store()
+            .get_commit(&then_wc_id)
+            .map_err(|e| Error::Repository {
+                message: format!("failed to get commit: {}", e),
+            })?
+            .tree();
+
+        let mut files_modified = Vec::new();
+        for entry in
+            jj_lib::merged_tree::TreeDiffIterator::new(&then_tree, &now_tree, &EverythingMatcher)
+        {
+            let kind = match &entry.values {
+                Ok(diff) if diff.before.is_absent() => FileChangeKind::Added,
+                Ok(diff) if diff.after.is_absent() => FileChangeKind::Deleted,
+                _ => FileChangeKind::Modified,
+            };
+            files_modified.push((entry.path.as_internal_file_string().to_string(), kind));
+        }
+
+        Ok(OperationDiff {
+            found,
+            operations_since,
+            changes_created,
+            changes_rewritten,
+            changes_abandoned,
+            files_modified,
+        })
+    }
+
+    /// Restore selected paths in the working copy to their content at the
+    /// working-copy commit recorded by `op_id`, leaving every other file
+    /// untouched. Paths may name files or directories. Every path that would
+    /// be written is checked against the manifest permissions first, so a
+    /// denied path aborts the restore before anything is modified.
+    pub fn restore_paths_from_operation(
+        &mut self,
+        op_id: &str,
+        paths: &[String],
+    ) -> Result<Vec<(String, FileChangeKind)>> {
+        let then_repo = self.load_repo_at_operation(op_id)?;
+        let workspace_name = self.workspace.as_ref().unwrap().workspace_name().to_owned();
+
+        let then_wc_id = then_repo
+            .view()
+            .get_wc_commit_id(&workspace_name)
+            .cloned()
+            .ok_or_else(|| Error::Repository {
+                message: "no working copy commit found at recorded operation".into(),
+            })?;
+
+        let now_tree = self.snapshot_working_copy()?;
+        let store = now_tree.store().clone();
+        let then_tree = store
+            .get_commit(&then_wc_id)
+            .map_err(|e| Error::Repository {
+                message: format!("failed to get commit: {}", e),
+            })?
+            .tree();
+
+        let mut prefixes = Vec::new();
+        for p in paths {
+            let trimmed = p.trim_end_matches('/');
+            prefixes.push(
+                jj_lib::repo_path::RepoPathBuf::from_internal_string(trimmed).map_err(|e| {
+                    Error::Repository {
+                        message: format!("invalid path '{}': {}", p, e),
+                    }
+                })?,
+            );
+        }
+        let matcher = jj_lib::matchers::PrefixMatcher::new(&prefixes);
+
+        // Collect the differing paths first so permissions can be checked
+        // before any file is written
+        let mut pending = Vec::new();
+        for entry in jj_lib::merged_tree::TreeDiffIterator::new(&now_tree, &then_tree, &matcher) {
+            let diff = entry.values.map_err(|e| Error::Repository {
+                message: format!("failed to diff trees: {}", e),
+            })?;
+            pending.push((entry.path, diff.after));
+        }
+
+        if self.has_manifest() {
+            let manifest = self.manifest()?.clone();
+            for (path, _) in &pending {
+                let path_str = path.as_internal_file_string();
+                if !manifest.permissions.can_change(path_str) {
+                    return Err(Error::PermissionDenied {
+                        action: "restore".to_string(),
+                        path: path_str.to_string(),
+                    });
+                }
+            }
+        }
+
+        let mut restored = Vec::new();
+        for (path, value) in pending {
+            let path_str = path.as_internal_file_string().to_string();
+            let full_path = self.root.join(&path_str);
+            let existed = full_path.exists() || full_path.is_symlink();
+
+            let value = value.into_resolved().map_err(|_| Error::Repository {
+                message: format!("'{}' is conflicted at the recorded operation", path_str),
+            })?;
+
+            match value {
+                None => {
+                    if existed {
+                        std::fs::remove_file(&full_path)?;
+                    }
+                    restored.push((path_str, FileChangeKind::Deleted));
+                }
+                Some(tree_value) => {
+                    if let Some(parent) = full_path.parent() {
+                        std::fs::create_dir_all(parent)?;
+                    }
+                    if full_path.is_symlink() {
+                        std::fs::remove_file(&full_path)?;
+                    }
+                    write_tree_value(&store, &path, &tree_value, &full_path)?;
+                    let kind = if existed {
+                        FileChangeKind::Modified
+                    } else {
+                        FileChangeKind::Added
+                    };
+                    restored.push((path_str, kind));
+                }
+            }
+        }
+
+        Ok(restored)
+    }
+
+    /// Park the working copy's changes: snapshot them into the working-copy
+    /// change, hide that change (it stays in the store, off every head), and
+    /// start a new empty change on the same parents. Files on disk are reset
+    /// to the parent tree; ignored files are left alone.
+    pub fn stash_push(&mut self, name: &str, message: Option<&str>) -> Result<StashEntry> {
+        let (mut workspace, repo, wc_commit) = self.load_working_copy_commit()?;
+        let workspace_name = workspace.workspace_name().to_owned();
+
+        let _critical = crate::deadline::critical();
+        let mut locked_ws =
+            workspace
+                .start_working_copy_mutation()
+                .map_err(|e| Error::Repository {
+                    message: format!("failed to start working copy mutation: {}", e),
+                })?;
+        let snapshot_options = SnapshotOptions {
+            base_ignores: load_base_ignores(&self.root),
+            progress: None,
+            start_tracking_matcher: &EverythingMatcher,
+            force_tracking_matcher: &NothingMatcher,
+            max_new_file_size: 1_000_000_000,
+        };
+        let (tree, _stats) = locked_ws
+            .locked_wc()
+            .snapshot(&snapshot_options)
+            .block_on()
+            .map_err(|e| Error::Repository {
+                message: format!("failed to snapshot working copy: {}", e),
+            })?;
+        let parent_tree = wc_commit
+            .parent_tree(repo.as_ref())
+            .map_err(|e| Error::Repository {
+                message: format!("failed to get parent tree: {}", e),
+            })?;
+        if tree.tree_ids() == parent_tree.tree_ids() {
+            return Err(Error::Repository {
+                message: "nothing to stash: the working copy has no changes".into(),
+            });
+        }
+
+        let _critical = crate::deadline::critical();
+        let mut tx = repo.start_transaction();
+        let mut stash_builder = tx.repo_mut().rewrite_commit(&wc_commit).set_tree(tree);
+        if let Some(message) = message {
+            stash_builder = stash_builder.set_description(message);
+        }
+        let stashed = stash_builder.write().map_err(|e| Error::Repository {
+            message: format!("failed to write stash commit: {}", e),
+        })?;
+        let new_wc = tx
+            .repo_mut()
+            .new_commit(wc_commit.parent_ids().to_vec(), parent_tree)
+            .write()
+            .map_err(|e| Error::Repository {
+                message: format!("failed to create new working copy commit: {}", e),
+            })?;
+        tx.repo_mut()
+            .set_wc_commit(workspace_name, new_wc.id().clone())
+            .map_err(|e| Error::Repository {
+                message: format!("failed to set working copy: {}", e),
+            })?;
+        tx.repo_mut().record_abandoned_commit(&stashed);
+        tx.repo_mut()
+            .rebase_descendants()
+            .map_err(|e| Error::Repository {
+                message: format!("failed to rebase descendants: {}", e),
+            })?;
+        let files = files_changed_in(tx.repo().base_repo(), &stashed)?;
+        let new_repo =
+            tx.commit(format!("stash push {}", name))
+                .map_err(|e| Error::Repository {
+                    message: format!("failed to commit transaction: {}", e),
+                })?;
+
+        locked_ws
+            .locked_wc()
+            .check_out(&new_wc)
+            .block_on()
+            .map_err(|e| Error::Repository {
+                message: format!("failed to check out working copy: {}", e),
+            })?;
+        locked_ws
+            .finish(new_repo.op_id().clone())
+            .map_err(|e| Error::Repository {
+                message: format!("failed to finish working copy: {}", e),
+            })?;
+        self.workspace = None;
+
+        Ok(StashEntry {
+            name: name.to_string(),
+            message: message.map(str::to_string),
+            session: crate::audit::current_session(),
+            change_id: stashed.change_id().hex(),
+            commit_id: stashed.id().hex(),
+            created_at: utc_now(),
+            files,
+        })
+    }
+
+    /// Reapply a stash to the working copy with a 3-way merge (stash parent
+    /// as base). When the working-copy change is empty, the stashed change
+    /// itself comes back as `@`, so its change ID and typed-change metadata
+    /// are restored; otherwise the stashed diff is merged into `@`. Paths
+    /// that do not merge cleanly are written with conflict markers.
+    pub fn stash_pop(&mut self, entry: &StashEntry) -> Result<StashPop> {
+        let (mut workspace, repo, wc_commit) = self.load_working_copy_commit()?;
+        let workspace_name = workspace.workspace_name().to_owned();
+
+        let stash_id =
+            CommitId::try_from_hex(&entry.commit_id).ok_or_else(|| Error::Repository {
+                message: format!("invalid commit ID in stash '{}'", entry.name),
+            })?;
+        let stashed = repo
+            .store()
+            .get_commit(&stash_id)
+            .map_err(|e| Error::Repository {
+                message: format!("stash '{}' commit is missing: {}", entry.name, e),
+            })?;
+        let stash_base = stashed
+            .parent_tree(repo.as_ref())
+            .map_err(|e| Error::Repository {
+                message: format!("failed to get parent tree: {}", e),
+            })?;
+
+        let _critical = crate::deadline::critical();
+        let mut locked_ws =
+            workspace
+                .start_working_copy_mutation()
+                .map_err(|e| Error::Repository {
+                    message: format!("failed to start working copy mutation: {}", e),
+                })?;
+        let snapshot_options = SnapshotOptions {
+            base_ignores: load_base_ignores(&self.root),
+            progress: None,
+            start_tracking_matcher: &EverythingMatcher,
+            force_tracking_matcher: &NothingMatcher,
+            max_new_file_size: 1_000_000_000,
+        };
+        let (current, _stats) = locked_ws
+            .locked_wc()
+            .snapshot(&snapshot_options)
+            .block_on()
+            .map_err(|e| Error::Repository {
+                message: format!("failed to snapshot working copy: {}", e),
+            })?;
+        let wc_parent_tree =
+            wc_commit
+                .parent_tree(repo.as_ref())
+                .map_err(|e| Error::Repository {
+                    message: format!("failed to get parent tree: {}", e),
+                })?;
+        let wc_is_empty =
+            current.tree_ids() == wc_parent_tree.tree_ids() && wc_commit.description().is_empty();
+
+        let merged = jj_lib::merged_tree::MergedTree::merge(jj_lib::merge::Merge::from_vec(vec![
+            (current, "working copy".to_string()),
+            (stash_base, "stash base".to_string()),
+            (stashed.tree(), format!("stash {}", entry.name)),
+        ]))
+        .block_on()
+        .map_err(|e| Error::Repository {
+            message: format!("failed to merge stash: {}", e),
+        })?;
+        let conflicts: Vec<String> = merged
+            .conflicts()
+            .map(|(path, _)| path.as_internal_file_string().to_string())
+            .collect();
+
+        let _critical = crate::deadline::critical();
+        let mut tx = repo.start_transaction();
+        let popped = if wc_is_empty {
+            tx.repo_mut()
+                .rewrite_commit(&stashed)
+                .set_parents(wc_commit.parent_ids().to_vec())
+                .set_tree(merged)
+                .write()
+        } else {
+            tx.repo_mut()
+                .rewrite_commit(&wc_commit)
+                .set_tree(merged)
+                .write()
+        }
+        .map_err(|e| Error::Repository {
+            message: format!("failed to write working copy commit: {}", e),
+        })?;
+        tx.repo_mut()
+            .set_wc_commit(workspace_name, popped.id().clone())
+            .map_err(|e| Error::Repository {
+                message: format!("failed to set working copy: {}", e),
+            })?;
+        if wc_is_empty {
+            tx.repo_mut().record_abandoned_commit(&wc_commit);
+        }
+        tx.repo_mut()
+            .rebase_descendants()
+            .map_err(|e| Error::Repository {
+                message: format!("failed to rebase descendants: {}", e),
+            })?;
+        let files = files_changed_in(tx.repo().base_repo(), &popped)?;
+        let new_repo = tx
+            .commit(format!("stash pop {}", entry.name))
+            .map_err(|e| Error::Repository {
+                message: format!("failed to commit transaction: {}", e),
+            })?;
+
+        locked_ws
+            .locked_wc()
+            .check_out(&popped)
+            .block_on()
+            .map_err(|e| Error::Repository {
+                message: format!("failed to check out working copy: {}", e),
+            })?;
+        locked_ws
+            .finish(new_repo.op_id().clone())
+            .map_err(|e| Error::Repository {
+                message: format!("failed to finish working copy: {}", e),
+            })?;
+        self.workspace = None;
+
+        Ok(StashPop {
+            name: entry.name.clone(),
+            change_id: popped.change_id().hex(),
+            files,
+            conflicts,
+        })
+    }
+
+    /// Create a change whose parents are `a` and `b` and check it out as `@`.
+    /// Edits that do not overlap merge on their own; each overlapping path is
+    /// reported hunk by hunk and, given a strategy, settled by it. Without
+    /// one, overlaps stay as conflicts in the new change.
+    pub fn reconcile(
+        &mut self,
+        a: &str,
+        b: &str,
+        description: &str,
+        strategy: Option<crate::reconcile::Strategy>,
+    ) -> Result<crate::reconcile::Reconciliation> {
+        self.snapshot()?;
+        let (_, a_hex) = self.resolve_revision(a)?;
+        let (_, b_hex) = self.resolve_revision(b)?;
+        let (mut workspace, repo, _) = self.load_working_copy_commit()?;
+        let workspace_name = workspace.workspace_name().to_owned();
+        let commit = |hex: &str| -> Result<jj_lib::commit::Commit> {
+            let id = CommitId::try_from_hex(hex).ok_or_else(|| Error::Repository {
+                message: format!("invalid commit ID: {}", hex),
+            })?;
+            repo.store().get_commit(&id).map_err(|e| Error::Repository {
+                message: format!("failed to get commit: {}", e),
+            })
+        };
+        let parents = [commit(&a_hex)?, commit(&b_hex)?];
+
+        let merged = jj_lib::rewrite::merge_commit_trees(repo.as_ref(), &parents)
+            .block_on()
+            .map_err(|e| Error::Repository {
+                message: format!("failed to merge changes: {}", e),
+            })?;
+        let mut conflicts = Vec::new();
+        let mut overlay = Overlay::new(std::collections::BTreeMap::new());
+        for (path, value) in merged.conflicts() {
+            let value = value.map_err(|e| Error::Repository {
+                message: format!("failed to read conflict: {}", e),
+            })?;
+            let term = |t: Option<&Option<jj_lib::backend::TreeValue>>| match t {
+                Some(value) => read_entry(repo.store(), &path, value),
+                None => Ok(None),
+            };
+            let base = term(value.removes().next())?;
+            let a_entry = term(value.adds().next())?;
+            let b_entry = term(value.adds().last())?;
+            let name = path.as_internal_file_string().to_string();
+            let (conflict, resolved) = crate::reconcile::resolve(
+                &name,
+                base.as_ref(),
+                a_entry.as_ref(),
+                b_entry.as_ref(),
+                strategy,
+            );
+            if let Some(entry) = resolved {
+                overlay.set(&name, entry);
+            }
+            conflicts.push(conflict);
+        }
+        let tree = write_overlay(repo.store(), &merged, &overlay)?;
+
+        let _critical = crate::deadline::critical();
+        let mut tx = repo.start_transaction();
+        let reconciled = tx
+            .repo_mut()
+            .new_commit(parents.iter().map(|c| c.id().clone()).collect(), tree)
+            .set_description(description)
+            .write()
+            .map_err(|e| Error::Repository {
+                message: format!("failed to create commit: {}", e),
+            })?;
+        tx.repo_mut()
+            .edit(workspace_name, &reconciled)
+            .map_err(|e| Error::Repository {
+                message: format!("failed to set working copy: {}", e),
+            })?;
+        tx.repo_mut()
+            .rebase_descendants()
+            .map_err(|e| Error::Repository {
+                message: format!("failed to rebase descendants: {}", e),
+            })?;
+        let new_repo = tx
+            .commit(format!("reconcile {} and {}", a, b))
+            .map_err(|e| Error::Repository {
+                message: format!("failed to commit transaction: {}", e),
+            })?;
+
+        let _critical = crate::deadline::critical();
+        let mut locked_ws =
+            workspace
+                .start_working_copy_mutation()
+                .map_err(|e| Error::Repository {
+                    message: format!("failed to start working copy mutation: {}", e),
+                })?;
+        locked_ws
+            .locked_wc()
+            .check_out(&reconciled)
+            .block_on()
+            .map_err(|e| Error::Repository {
+                message: format!("failed to check out working copy: {}", e),
+            })?;
+        locked_ws
+            .finish(new_repo.op_id().clone())
+            .map_err(|e| Error::Repository {
+                message: format!("failed to finish working copy: {}", e),
+            })?;
+        self.workspace = None;
+
+        Ok(crate::reconcile::Reconciliation {
+            change_id: reconciled.change_id().hex(),
+            commit_id: reconciled.id().hex(),
+            conflicts,
+        })
+    }
+
+    /// Gather what `rollback` needs to plan taking a change back out: its
+    /// descendants, the bookmarks that include it, whether its inverse
+    /// applies cleanly to the working copy, and the last operation before it
+    pub fn rollback_facts(&mut self, change_id: &str) -> Result<crate::rollback::Facts> {
+        let (_, commit_hex) = self.resolve_revision(change_id)?;
+        let bookmarks = self.local_bookmarks()?;
+        let (workspace, repo, wc_commit) = self.load_working_copy_commit()?;
+        let commit_id = CommitId::try_from_hex(&commit_hex).ok_or_else(|| Error::Repository {
+            message: format!("invalid commit ID: {}", commit_hex),
+        })?;
+        let commit = repo
+            .store()
+            .get_commit(&commit_id)
+            .map_err(|e| Error::Repository {
+                message: format!("failed to get commit: {}", e),
+            })?;
+        let index = repo.index();
+        let descends = |id: &CommitId| index.is_ancestor(&commit_id, id).unwrap_or(false);
+
+        let is_working_copy = wc_commit.id() == commit.id();
+        let in_working_copy_ancestry = !is_working_copy && descends(wc_commit.id());
+        let wc_parent_tree =
+            wc_commit
+                .parent_tree(repo.as_ref())
+                .map_err(|e| Error::Repository {
+                    message: format!("failed to get parent tree: {}", e),
+                })?;
+        // Typed-change records left behind by `commit` don't count as work
+        let working_copy_dirty = wc_commit.tree().tree_ids() != wc_parent_tree.tree_ids()
+            && files_changed_in(&repo, &wc_commit)?
+                .iter()
+                .any(|f| !f.starts_with(".agent/"));
+
+        // Walk back from the heads while still inside the change's descendants
+        let mut descendants = Vec::new();
+        let mut to_visit: Vec<CommitId> = repo.view().heads().iter().cloned().collect();
+        let mut visited = std::collections::HashSet::new();
+        while let Some(id) = to_visit.pop() {
+            if id == commit_id || !visited.insert(id.clone()) || !descends(&id) {
+                continue;
+            }
+            let Ok(descendant) = repo.store().get_commit(&id) else {
+                continue;
+            };
+            to_visit.extend(descendant.parent_ids().iter().cloned());
+            if id != *wc_commit.id() {
+                descendants.push(crate::rollback::Descendant {
+                    change_id: descendant.change_id().hex(),
+                    description: descendant
+                        .description()
+                        .lines()
+                        .next()
+                        .unwrap_or("")
+                        .to_string(),
+                });
+            }
+        }
+        descendants.sort_by(|a, b| a.change_id.cmp(&b.change_id));
+
+        let bookmarks = bookmarks
+            .into_iter()
+            .filter(|b| CommitId::try_from_hex(&b.commit_id).is_some_and(|id| descends(&id)))
+            .collect();
+
+        let parent_tree = commit
+            .parent_tree(repo.as_ref())
+            .map_err(|e| Error::Repository {
+                message: format!("failed to get parent tree: {}", e),
+            })?;
+        let revert_conflicts = if in_working_copy_ancestry {
+            revert_tree(&wc_commit, &commit, parent_tree)?
+                .conflicts()
+                .map(|(path, _)| path.as_internal_file_string().to_string())
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        // The newest operation in which the change did not exist yet, or
+        // was still the empty working copy left by the previous commit
+        let mut restore_point = None;
+        let mut current_op = Some(repo.operation().clone());
+        let mut undone = 0;
+        while let Some(op) = current_op.filter(|_| undone < 1000) {
+            let then = workspace
+                .repo_loader()
+                .load_at(&op)
+                .map_err(|e| Error::Repository {
+                    message: format!("failed to load repository at operation: {}", e),
+                })?;
+            let existed = then
+                .resolve_change_id(commit.change_id())
+                .ok()
+                .flatten()
+                .and_then(|targets| {
+                    targets
+                        .visible_with_offsets()
+                        .next()
+                        .map(|(_, id)| id.clone())
+                })
+                .and_then(|id| then.store().get_commit(&id).ok())
+                .is_some_and(|c| !c.is_discardable(then.as_ref()).unwrap_or(false));
+            if !existed {
+                restore_point = Some((op.id().hex(), undone));
+                break;
+            }
+            undone += 1;
+            current_op = op.parents().next().and_then(|r| r.ok());
+        }
+
+        Ok(crate::rollback::Facts {
+            change_id: commit.change_id().hex(),
+            commit_id: commit.id().hex(),
+            description: commit
+                .description()
+                .lines()
+                .next()
+                .unwrap_or("")
+                .to_string(),
+            parent_commit_id: commit.parent_ids().first().map(|id| id.hex()),
+            files: files_changed_in(&repo, &commit)?,
+            is_working_copy,
+            in_working_copy_ancestry,
+            working_copy_dirty,
+            descendants,
+            bookmarks,
+            revert_conflicts,
+            restore_point,
+        })
+    }
+
+    /// Update files on disk to match the working-copy commit, e.g. after an
+    /// operation restore moved `@`
+    pub fn check_out_working_copy(&mut self) -> Result<()> {
+        let (mut workspace, repo, wc_commit) = self.load_working_copy_commit()?;
+        let _critical = crate::deadline::critical();
+        let mut locked_ws =
+            workspace
+                .start_working_copy_mutation()
+                .map_err(|e| Error::Repository {
+                    message: format!("failed to start working copy mutation: {}", e),
+                })?;
+        locked_ws
+            .locked_wc()
+            .check_out(&wc_commit)
+            .block_on()
+            .map_err(|e| Error::Repository {
+                message: format!("failed to check out working copy: {}", e),
+            })?;
+        locked_ws
+            .finish(repo.op_id().clone())
+            .map_err(|e| Error::Repository {
+                message: format!("failed to finish working copy: {}", e),
+            })?;
+        self.workspace = None;
+        Ok(())
+    }
+
+    /// Apply the inverse of a commit to the working copy and describe it.
+    /// Returns the working-copy change ID; fails on conflicts.
+    pub fn revert_into_working_copy(
+        &mut self,
+        commit_hex: &str,
+        description: &str,
+    ) -> Result<String> {
+        let (mut workspace, repo, wc_commit) = self.load_working_copy_commit()?;
+        let workspace_name = workspace.workspace_name().to_owned();
+        let commit_id = CommitId::try_from_hex(commit_hex).ok_or_else(|| Error::Repository {
+            message: format!("invalid commit ID: {}", commit_hex),
+        })?;
+        let commit = repo
+            .store()
+            .get_commit(&commit_id)
+            .map_err(|e| Error::Repository {
+                message: format!("failed to get commit: {}", e),
+            })?;
+        let parent_tree = commit
+            .parent_tree(repo.as_ref())
+            .map_err(|e| Error::Repository {
+                message: format!("failed to get parent tree: {}", e),
+            })?;
+        let reverted = revert_tree(&wc_commit, &commit, parent_tree)?;
+        let conflicts: Vec<ConflictDetail> = reverted
+            .conflicts()
+            .map(|(path, _)| ConflictDetail {
+                file: path.as_internal_file_string().to_string(),
+                ours: "working copy".into(),
+                theirs: format!("revert of {}", commit.change_id().hex()),
+                base: None,
+            })
+            .collect();
+        if !conflicts.is_empty() {
+            return Err(Error::Conflict {
+                file_count: conflicts.len(),
+                conflicts,
+                operation_id: repo.op_id().hex(),
+            });
+        }
+
+        let _critical = crate::deadline::critical();
+        let mut locked_ws =
+            workspace
+                .start_working_copy_mutation()
+                .map_err(|e| Error::Repository {
+                    message: format!("failed to start working copy mutation: {}", e),
+                })?;
+        let _critical = crate::deadline::critical();
+        let mut tx = repo.start_transaction();
+        let rewritten = tx
+            .repo_mut()
+            .rewrite_commit(&wc_commit)
+            .set_tree(reverted)
+            .set_description(description)
+            .write()
+            .map_err(|e| Error::Repository {
+                message: format!("failed to write working copy commit: {}", e),
+            })?;
+        tx.repo_mut()
+            .set_wc_commit(workspace_name, rewritten.id().clone())
+            .map_err(|e| Error::Repository {
+                message: format!("failed to set working copy: {}", e),
+            })?;
+        tx.repo_mut()
+            .rebase_descendants()
+            .map_err(|e| Error::Repository {
+                message: format!("failed to rebase descendants: {}", e),
+            })?;
+        let new_repo = tx
+            .commit(format!("rollback {}", commit.change_id().hex()))
+            .map_err(|e| Error::Repository {
+                message: format!("failed to commit transaction: {}", e),
+            })?;
+
+        locked_ws
+            .locked_wc()
+            .check_out(&rewritten)
+            .block_on()
+            .map_err(|e| Error::Repository {
+                message: format!("failed to check out working copy: {}", e),
+            })?;
+        locked_ws
+            .finish(new_repo.op_id().clone())
+            .map_err(|e| Error::Repository {
+                message: format!("failed to finish working copy: {}", e),
+            })?;
+        self.workspace = None;
 
-        Ok(operations)
+        Ok(rewritten.change_id().hex())
     }
 
-    /// Restore the repository to a specific operation.
-    pub fn restore_operation(&mut self, op_id: &str) -> Result<()> {
+    /// A freshly loaded workspace, the repo at head, and its working-copy commit
+    fn load_working_copy_commit(
+        &self,
+    ) -> Result<(Workspace, Arc<ReadonlyRepo>, jj_lib::commit::Commit)> {
         let settings = create_minimal_settings()?;
         let store_factories = get_store_factories();
         let wc_factories = get_working_copy_factories();
@@ -1602,57 +4753,26 @@ impl Repo {
             .map_err(|e| Error::Repository {
                 message: format!("failed to load workspace: {}", e),
             })?;
-
         let repo = workspace
             .repo_loader()
             .load_at_head()
             .map_err(|e| Error::Repository {
                 message: format!("failed to load repository: {}", e),
             })?;
-
-        // Find the operation by ID
-        let op_id_obj = jj_lib::op_store::OperationId::try_from_hex(op_id).ok_or_else(|| {
-            Error::Repository {
-                message: format!("invalid operation ID: {}", op_id),
-            }
-        })?;
-
-        let target_op = workspace
-            .repo_loader()
-            .load_operation(&op_id_obj)
-            .map_err(|e| Error::Repository {
-                message: format!("failed to load operation: {}", e),
-            })?;
-
-        // Load repo at target operation
-        let target_repo =
-            workspace
-                .repo_loader()
-                .load_at(&target_op)
-                .map_err(|e| Error::Repository {
-                    message: format!("failed to load repository at operation: {}", e),
-                })?;
-
-        // Create a transaction to record the restore
-        let mut tx = repo.start_transaction();
-
-        // Merge in the target operation's view
-        tx.repo_mut()
-            .merge(&repo, &target_repo)
-            .map_err(|e| Error::Repository {
-                message: format!("failed to merge operation: {}", e),
+        let wc_commit_id = repo
+            .view()
+            .get_wc_commit_id(workspace.workspace_name())
+            .cloned()
+            .ok_or_else(|| Error::Repository {
+                message: "no working copy commit found".into(),
             })?;
-
-        // Commit the restore transaction
-        tx.commit(format!("restore to operation {}", op_id))
+        let wc_commit = repo
+            .store()
+            .get_commit(&wc_commit_id)
             .map_err(|e| Error::Repository {
-                message: format!("failed to commit restore: {}", e),
+                message: format!("failed to get working copy commit: {}", e),
             })?;
-
-        // Clear cached workspace
-        self.workspace = None;
-
-        Ok(())
+        Ok((workspace, repo, wc_commit))
     }
 
     /// Commit the working copy via jj-lib: snapshot, run invariants, commit
@@ -1700,6 +4820,7 @@ impl Repo {
             })?;
 
         // Snapshot the working copy to capture filesystem changes
+        let _critical = crate::deadline::critical();
         let mut locked_ws =
             workspace
                 .start_working_copy_mutation()
@@ -1745,6 +4866,8 @@ impl Repo {
 
         // When --paths is specified, filter to only the requested paths and
         // build a selective tree containing just those changes.
+        // Invariants run against everything on disk, not just --paths
+        let tree_id = tree_key(&new_tree);
         let commit_tree = if let Some(ref paths) = opts.paths {
             // Validate each requested path: must exist in the diff (changed)
             // or at least exist in new_tree (unchanged => skip silently).
@@ -1812,23 +4935,29 @@ impl Repo {
         } else {
             new_tree
         };
+        let stats =
+            ChangeMetrics::from_changes(&file_changes(repo.store(), &parent_tree, &commit_tree)?);
 
         // Run invariants between snapshot and commit (safe: no commit yet)
         let invariants = if opts.run_invariants && self.has_manifest() {
-            match self.run_invariants(InvariantTrigger::PreCommit) {
-                Ok(results) => results,
-                Err((name, cmd, code, stdout, stderr)) => {
+            match self.run_invariants(InvariantTrigger::PreCommit, Some(&tree_id)) {
+                Ok(Ok(results)) => results,
+                ran => {
                     // Finish locked workspace before returning error (best-effort:
                     // if this fails the working copy may need manual recovery)
                     if let Err(e) = locked_ws.finish(repo.op_id().clone()) {
                         eprintln!("warning: failed to release working copy lock: {}", e);
                     }
+                    // An unresolved environment stops before any invariant runs
+                    let failure = ran?.unwrap_err();
+                    crate::deadline::check("invariants")?;
                     return Err(Error::InvariantFailed {
-                        name,
-                        command: cmd,
-                        exit_code: code,
-                        stdout,
-                        stderr,
+                        name: failure.name,
+                        command: failure.command,
+                        exit_code: failure.exit_code,
+                        stdout: failure.stdout,
+                        stderr: failure.stderr,
+                        known_flakes: failure.known_flakes.into(),
                     });
                 }
             }
@@ -1836,7 +4965,17 @@ impl Repo {
             HashMap::new()
         };
 
+        // Out of time: leave the working copy as it was rather than start a
+        // transaction that would be cut short
+        if let Err(e) = crate::deadline::check("commit") {
+            if let Err(e) = locked_ws.finish(repo.op_id().clone()) {
+                eprintln!("warning: failed to release working copy lock: {}", e);
+            }
+            return Err(e);
+        }
+
         // Start jj-lib transaction
+        let _critical = crate::deadline::critical();
         let mut tx = repo.start_transaction();
 
         // Rewrite WC commit with the (possibly selective) tree and commit message
@@ -1889,8 +5028,11 @@ impl Repo {
             })?;
 
         // Export jj refs to git (syncs bookmarks → git branches)
-        if let Err(e) = jj_lib::git::export_refs(tx.repo_mut()) {
-            eprintln!("warning: failed to export jj refs to git: {}", e);
+        let git_backed = is_git_backed(tx.repo().store());
+        if git_backed {
+            if let Err(e) = jj_lib::git::export_refs(tx.repo_mut()) {
+                eprintln!("warning: failed to export jj refs to git: {}", e);
+            }
         }
 
         // Commit the transaction
@@ -1908,7 +5050,9 @@ impl Repo {
         // Sync git state directly (in colocated mode, jj detaches HEAD and
         // export_refs may not update the git branch in all scenarios)
         let commit_hex = committed.id().hex();
-        if let Some(branch) = get_current_git_branch(&self.root) {
+        if !git_backed || !self.root.join(".git").exists() {
+            // No colocated git repository: nothing to sync
+        } else if let Some(branch) = get_current_git_branch(&self.root) {
             // Move the git branch ref to the committed change
             match Command::new("git")
                 .current_dir(&self.root)
@@ -1953,57 +5097,652 @@ impl Repo {
                 }
                 _ => {}
             }
-        } else {
-            eprintln!("warning: HEAD is detached; skipping git branch sync");
+        } else {
+            eprintln!("warning: HEAD is detached; skipping git branch sync");
+        }
+
+        // Save TypedChange metadata
+        let mut typed_change =
+            TypedChange::new(committed.change_id().hex(), opts.change_type, &opts.message)
+                .with_files(files_changed.clone())
+                .with_fields(opts.fields.clone())
+                .with_depends_on(opts.depends_on.clone())
+                .with_metrics(stats.clone());
+
+        for category in &opts.categories {
+            typed_change = typed_change.with_category(category.clone());
+        }
+        if opts.breaking {
+            typed_change = typed_change.breaking();
+        }
+        // Notes written while the change was in progress stay with it
+        if let Ok(existing) = self.get_typed_change(&committed.change_id().hex()) {
+            typed_change.notes = existing.notes;
+        }
+
+        typed_change.invariants = InvariantsResult {
+            checked: invariants.keys().cloned().collect(),
+            status: if invariants.is_empty() {
+                InvariantStatus::Skipped
+            } else if invariants.values().all(|s| *s == InvariantStatus::Passed) {
+                InvariantStatus::Passed
+            } else {
+                InvariantStatus::Failed
+            },
+            details: invariants.clone(),
+        };
+
+        self.save_typed_change(&typed_change)?;
+
+        // Invalidate cached workspace
+        self.workspace = None;
+
+        let short_commit = if commit_hex.len() > 12 {
+            &commit_hex[..12]
+        } else {
+            &commit_hex
+        };
+
+        Ok(CommitResult {
+            change_id: committed.change_id().hex(),
+            commit_id: short_commit.to_string(),
+            operation_id: new_repo.op_id().hex(),
+            files_changed,
+            stats,
+            invariants,
+        })
+    }
+
+    /// Whether a git repository colocated at the root backs this workspace,
+    /// so git tooling (push, tags, `git diff`) can operate on it. False for
+    /// jj's native backend and for jj repos that keep their git store internal.
+    pub fn has_git_backend(&mut self) -> Result<bool> {
+        let repo = self.load_repo_at_head()?;
+        Ok(is_git_backed(repo.store()) && self.root.join(".git").exists())
+    }
+
+    /// Fail with `GitRequired` unless `has_git_backend`
+    pub fn require_git(&mut self, feature: &str) -> Result<()> {
+        if self.has_git_backend()? {
+            Ok(())
+        } else {
+            Err(Error::GitRequired {
+                feature: feature.to_string(),
+            })
+        }
+    }
+
+    /// Git branches and HEAD next to jj's bookmarks and `@-`, for a
+    /// colocated repository (None without a colocated git repository)
+    pub fn ref_sides(&mut self) -> Result<Option<RefSides>> {
+        if !self.has_git_backend()? {
+            return Ok(None);
+        }
+        let git = |args: &[&str]| -> Option<String> {
+            let output = Command::new("git")
+                .current_dir(&self.root)
+                .args(args)
+                .output()
+                .ok()?;
+            output
+                .status
+                .success()
+                .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        };
+        let branches = git(&[
+            "for-each-ref",
+            "--format=%(refname:strip=2) %(objectname)",
+            "refs/heads",
+        ])
+        .unwrap_or_default();
+        let git_head = git(&["rev-parse", "--verify", "-q", "HEAD^{commit}"]);
+
+        let (_, repo, wc_commit) = self.load_working_copy_commit()?;
+        let wc_parent = wc_commit
+            .parent_ids()
+            .first()
+            .filter(|id| *id != repo.store().root_commit_id())
+            .cloned();
+        let head_behind = match (&git_head, &wc_parent) {
+            (Some(head), Some(parent)) => CommitId::try_from_hex(head)
+                .filter(|head| repo.index().has_id(head).unwrap_or(false))
+                .is_some_and(|head| repo.index().is_ancestor(&head, parent).unwrap_or(false)),
+            _ => false,
+        };
+        let view = repo.view();
+        let git_remote = jj_lib::git::REMOTE_NAME_FOR_LOCAL_GIT_REPO;
+        Ok(Some(RefSides {
+            git: branches
+                .lines()
+                .filter_map(|line| line.rsplit_once(' '))
+                .map(|(name, id)| (name.to_string(), id.to_string()))
+                .collect(),
+            jj: view
+                .local_bookmarks()
+                .map(|(name, target)| {
+                    let id = target.as_normal().map(|id| id.hex());
+                    (name.as_str().to_string(), id)
+                })
+                .collect(),
+            last_seen: view
+                .remote_bookmarks(git_remote)
+                .filter_map(|(name, remote)| {
+                    let id = remote.target.as_normal()?.hex();
+                    Some((name.as_str().to_string(), id))
+                })
+                .collect(),
+            git_head,
+            wc_parent: wc_parent.map(|id| id.hex()),
+            head_behind,
+        }))
+    }
+
+    /// Compare git refs with jj bookmarks (None without a colocated git
+    /// repository)
+    pub fn ref_sync_report(&mut self) -> Result<Option<SyncReport>> {
+        Ok(self
+            .ref_sides()?
+            .map(|sides| crate::refsync::compare(&sides)))
+    }
+
+    /// Reconcile git and jj: import git's branches into jj, then export
+    /// jj's bookmarks to git. Unless `refs_only`, also import git HEAD and
+    /// rebase the working-copy change onto it when it moved.
+    pub fn sync_refs(&mut self, refs_only: bool) -> Result<SyncOutcome> {
+        self.require_git("sync")?;
+        let before = self
+            .ref_sync_report()?
+            .unwrap_or_else(|| crate::refsync::compare(&RefSides::default()));
+        if !refs_only {
+            self.snapshot()?;
+        }
+        let (mut workspace, repo, wc_commit) = self.load_working_copy_commit()?;
+        let workspace_name = workspace.workspace_name().to_owned();
+
+        let _critical = crate::deadline::critical();
+        let mut tx = repo.start_transaction();
+        let import_options = jj_lib::git::GitImportOptions {
+            auto_local_bookmark: false,
+            abandon_unreachable_commits: false,
+            remote_auto_track_bookmarks: Default::default(),
+        };
+        jj_lib::git::import_refs(tx.repo_mut(), &import_options).map_err(|e| {
+            Error::Repository {
+                message: format!("failed to import git refs: {}", e),
+            }
+        })?;
+        let mut rebased = None;
+        if !refs_only {
+            jj_lib::git::import_head(tx.repo_mut()).map_err(|e| Error::Repository {
+                message: format!("failed to import git HEAD: {}", e),
+            })?;
+            // Only a HEAD git moved elsewhere; one left behind `@-` by jj
+            // commits has nothing to bring in
+            let head = tx.repo().view().git_head().as_normal().cloned();
+            let parent = wc_commit.parent_ids()[0].clone();
+            let moved = head.filter(|head| {
+                *head != parent
+                    && !tx
+                        .repo()
+                        .index()
+                        .is_ancestor(head, &parent)
+                        .unwrap_or(false)
+            });
+            if let Some(head) = moved {
+                let new_wc = jj_lib::rewrite::rebase_commit(tx.repo_mut(), wc_commit, vec![head])
+                    .block_on()
+                    .map_err(|e| Error::Repository {
+                        message: format!("failed to rebase the working copy: {}", e),
+                    })?;
+                tx.repo_mut()
+                    .set_wc_commit(workspace_name, new_wc.id().clone())
+                    .map_err(|e| Error::Repository {
+                        message: format!("failed to set working copy: {}", e),
+                    })?;
+                rebased = Some(new_wc);
+            }
+        }
+        tx.repo_mut()
+            .rebase_descendants()
+            .map_err(|e| Error::Repository {
+                message: format!("failed to rebase descendants: {}", e),
+            })?;
+        let stats = jj_lib::git::export_refs(tx.repo_mut()).map_err(|e| Error::Repository {
+            message: format!("failed to export jj refs to git: {}", e),
+        })?;
+
+        if tx.repo().has_changes() {
+            let new_repo = tx.commit("sync git refs").map_err(|e| Error::Repository {
+                message: format!("failed to commit transaction: {}", e),
+            })?;
+            if let Some(new_wc) = &rebased {
+                let _critical = crate::deadline::critical();
+                let mut locked_ws =
+                    workspace
+                        .start_working_copy_mutation()
+                        .map_err(|e| Error::Repository {
+                            message: format!("failed to start working copy mutation: {}", e),
+                        })?;
+                locked_ws
+                    .locked_wc()
+                    .check_out(new_wc)
+                    .block_on()
+                    .map_err(|e| Error::Repository {
+                        message: format!("failed to check out working copy: {}", e),
+                    })?;
+                locked_ws
+                    .finish(new_repo.op_id().clone())
+                    .map_err(|e| Error::Repository {
+                        message: format!("failed to finish working copy: {}", e),
+                    })?;
+            }
+        }
+        self.workspace = None;
+
+        let after = self.ref_sync_report()?.unwrap_or(before.clone());
+        let mut outcome = SyncOutcome::new(before, after, refs_only);
+        outcome.failed = stats
+            .failed_bookmarks
+            .iter()
+            .map(|(symbol, reason)| format!("{}: {}", symbol.name.as_str(), reason))
+            .collect();
+        outcome.rebased_onto = rebased
+            .and_then(|c| c.parent_ids().first().cloned())
+            .map(|id| id.hex());
+        Ok(outcome)
+    }
+
+    /// Paths a revision changes relative to its parents, with both sides'
+    /// content, read from the store. Accepts the same specs as
+    /// `resolve_revision`; `@` compares the files on disk with the working
+    /// copy's parent, like `git diff HEAD`.
+    pub fn tree_changes(&mut self, rev: &str) -> Result<Vec<crate::unified::FileChange>> {
+        let (_, commit_hex) = self.resolve_revision(rev)?;
+        let repo = self.load_repo_at_head()?;
+        let commit_id = CommitId::try_from_hex(&commit_hex).ok_or_else(|| Error::Repository {
+            message: format!("invalid commit id: {}", commit_hex),
+        })?;
+        let commit = repo
+            .store()
+            .get_commit(&commit_id)
+            .map_err(|e| Error::Repository {
+                message: format!("failed to get commit: {}", e),
+            })?;
+        let (before, after) = if rev == "@" {
+            // Tree diffs require both sides to come from the same store instance
+            let after = self.snapshot_working_copy()?;
+            let parent = commit
+                .parent_ids()
+                .first()
+                .ok_or_else(|| Error::Repository {
+                    message: "working copy has no parent".into(),
+                })?;
+            let before = after
+                .store()
+                .get_commit(parent)
+                .map_err(|e| Error::Repository {
+                    message: format!("failed to get commit: {}", e),
+                })?
+                .tree();
+            (before, after)
+        } else {
+            let before = commit.parent_tree(&*repo).map_err(|e| Error::Repository {
+                message: format!("failed to get parent tree: {}", e),
+            })?;
+            (before, commit.tree())
+        };
+
+        file_changes(repo.store(), &before, &after)
+    }
+
+    /// The nearest common ancestor of two revisions, as a full commit ID:
+    /// where the work in `b` branched off `a`. Errors when the only one is
+    /// the root commit, meaning the histories are unrelated.
+    pub fn merge_base(&mut self, a: &str, b: &str) -> Result<String> {
+        let (_, a_hex) = self.resolve_revision(a)?;
+        let (_, b_hex) = self.resolve_revision(b)?;
+        let repo = self.load_repo_at_head()?;
+        let id = |hex: &str| {
+            CommitId::try_from_hex(hex).ok_or_else(|| Error::Repository {
+                message: format!("invalid commit id: {}", hex),
+            })
+        };
+        let (a_id, b_id) = (id(&a_hex)?, id(&b_hex)?);
+        let bases = repo
+            .index()
+            .common_ancestors(std::slice::from_ref(&a_id), std::slice::from_ref(&b_id))
+            .map_err(|e| Error::Repository {
+                message: format!("failed to find a merge base: {}", e),
+            })?;
+        bases
+            .into_iter()
+            .find(|id| id != repo.store().root_commit_id())
+            .map(|id| id.hex())
+            .ok_or_else(|| Error::Repository {
+                message: format!("{} and {} have no common ancestor", a, b),
+            })
+    }
+
+    /// Files that differ between two revisions' trees, compared directly
+    /// rather than each against its parent. Snapshot first when either is `@`.
+    pub fn tree_changes_between(
+        &mut self,
+        from: &str,
+        to: &str,
+    ) -> Result<Vec<crate::unified::FileChange>> {
+        let (_, from_hex) = self.resolve_revision(from)?;
+        let (_, to_hex) = self.resolve_revision(to)?;
+        let repo = self.load_repo_at_head()?;
+        let tree = |hex: &str| -> Result<_> {
+            let commit_id = CommitId::try_from_hex(hex).ok_or_else(|| Error::Repository {
+                message: format!("invalid commit id: {}", hex),
+            })?;
+            let commit = repo
+                .store()
+                .get_commit(&commit_id)
+                .map_err(|e| Error::Repository {
+                    message: format!("failed to get commit: {}", e),
+                })?;
+            Ok(commit.tree())
+        };
+        file_changes(repo.store(), &tree(&from_hex)?, &tree(&to_hex)?)
+    }
+
+    /// The commits of a `from..to` range, oldest first: `to` and its
+    /// ancestors that `from` lacks. A single revision is just that commit.
+    /// Merges are refused, since a patch series is a line of changes.
+    fn series_commits(&mut self, range: &str) -> Result<Vec<jj_lib::commit::Commit>> {
+        // `..to` walks back to the root; a lone revision stops after one
+        let (from, to, walk) = match range.split_once("..") {
+            Some((from, to)) => (Some(from).filter(|f| !f.is_empty()), to, true),
+            None => (None, range, false),
+        };
+        let to = if to.is_empty() { "@" } else { to };
+        let (_, to_hex) = self.resolve_revision(to)?;
+        let from_hex = from.map(|from| self.resolve_revision(from)).transpose()?;
+        let repo = self.load_repo_at_head()?;
+        let id = |hex: &str| {
+            CommitId::try_from_hex(hex).ok_or_else(|| Error::Repository {
+                message: format!("invalid commit id: {}", hex),
+            })
+        };
+        let from_id = from_hex.map(|(_, hex)| id(&hex)).transpose()?;
+
+        let mut commits = Vec::new();
+        let mut next = Some(id(&to_hex)?);
+        while let Some(commit_id) = next {
+            if commit_id == *repo.store().root_commit_id() {
+                break;
+            }
+            if let Some(from) = &from_id {
+                let shared =
+                    repo.index()
+                        .is_ancestor(&commit_id, from)
+                        .map_err(|e| Error::Repository {
+                            message: format!("failed to walk history: {}", e),
+                        })?;
+                if shared {
+                    break;
+                }
+            }
+            let commit = repo
+                .store()
+                .get_commit(&commit_id)
+                .map_err(|e| Error::Repository {
+                    message: format!("failed to get commit: {}", e),
+                })?;
+            if commit.parent_ids().len() > 1 {
+                return Err(Error::Repository {
+                    message: format!(
+                        "{} is a merge; a patch series can only carry a line of changes",
+                        &commit_id.hex()[..12]
+                    ),
+                });
+            }
+            next = walk.then(|| commit.parent_ids().first().cloned()).flatten();
+            commits.push(commit);
         }
+        commits.reverse();
+        Ok(commits)
+    }
 
-        // Save TypedChange metadata
-        let mut typed_change =
-            TypedChange::new(committed.change_id().hex(), opts.change_type, &opts.message)
-                .with_files(files_changed.clone());
-
-        if let Some(category) = opts.category {
-            typed_change = typed_change.with_category(category);
+    /// Read a range (`main..@`) as a patch series: each change's diff,
+    /// author, description, and typed metadata. Typed-change records in the
+    /// tree (`.agent/changes/`) stay out of the diffs, since the metadata
+    /// travels as trailers; changes left with no files are skipped. Binary
+    /// changes are refused: the text patches cannot carry them, and import
+    /// would silently lose them.
+    pub fn export_series(&mut self, range: &str) -> Result<Series> {
+        self.snapshot()?;
+        let commits = self.series_commits(range)?;
+        let repo = self.load_repo_at_head()?;
+        let mut series = Series {
+            patches: Vec::new(),
+            base: commits
+                .first()
+                .and_then(|c| c.parent_ids().first())
+                .filter(|id| *id != repo.store().root_commit_id())
+                .map(|id| id.hex()),
+            empty: Vec::new(),
+        };
+        let mut binary = Vec::new();
+        for commit in commits {
+            let change_id = commit.change_id().hex();
+            let parent_tree = commit.parent_tree(&*repo).map_err(|e| Error::Repository {
+                message: format!("failed to get parent tree: {}", e),
+            })?;
+            let mut changes = file_changes(repo.store(), &parent_tree, &commit.tree())?;
+            changes.retain(|c| !c.path.starts_with(".agent/changes/"));
+            if changes.is_empty() {
+                series.empty.push(change_id);
+                continue;
+            }
+            binary.extend(
+                changes
+                    .iter()
+                    .filter(|c| c.line_counts().is_none())
+                    .map(|c| format!("{} ({})", c.path, &change_id[..12])),
+            );
+            let author = commit.author();
+            series.patches.push(SeriesPatch {
+                commit_id: commit.id().hex(),
+                author: Author {
+                    name: author.name.clone(),
+                    email: author.email.clone(),
+                    secs: author.timestamp.timestamp.0.div_euclid(1000),
+                    tz_minutes: author.timestamp.tz_offset,
+                },
+                description: commit.description().trim_end().to_string(),
+                change: self.get_typed_change(&change_id).ok(),
+                diff: crate::unified::render(&changes),
+                stat: crate::series::diffstat(&changes),
+            });
         }
-        if opts.breaking {
-            typed_change = typed_change.breaking();
+        if !binary.is_empty() {
+            return Err(Error::PreconditionFailed {
+                reason: format!(
+                    "a patch series cannot carry binary changes: {}",
+                    binary.join(", ")
+                ),
+                expected: "text changes only".into(),
+                actual: format!("{} binary file change(s)", binary.len()),
+            });
         }
+        Ok(series)
+    }
 
-        typed_change.invariants = InvariantsResult {
-            checked: invariants.keys().cloned().collect(),
-            status: if invariants.is_empty() {
-                InvariantStatus::Skipped
-            } else if invariants.values().all(|s| *s == InvariantStatus::Passed) {
-                InvariantStatus::Passed
-            } else {
-                InvariantStatus::Failed
-            },
-            details: invariants.clone(),
+    /// Recreate a patch series as a line of new changes in one transaction,
+    /// applying each patch in memory as `apply --onto` does; the working
+    /// copy stays as it was. The series goes onto `onto`, else its
+    /// `base-commit` when this repository has it, else `@-`. Changes keep
+    /// their change IDs unless one is taken here already, and their typed
+    /// metadata is saved with `depends_on` following any new IDs.
+    pub fn import_series(
+        &mut self,
+        patches: &[SeriesPatch],
+        base: Option<&str>,
+        onto: Option<&str>,
+    ) -> Result<SeriesImport> {
+        use jj_lib::backend::{ChangeId, MillisSinceEpoch, Signature, Timestamp};
+
+        let known_base = match base.and_then(CommitId::try_from_hex) {
+            Some(id) => {
+                let repo = self.load_repo_at_head()?;
+                let index = repo.index();
+                index.has_id(&id).unwrap_or(false).then(|| id.hex())
+            }
+            None => None,
         };
+        let onto_hex = match (onto, known_base) {
+            (Some(rev), _) => self.resolve_revision(rev)?.1,
+            (None, Some(hex)) => hex,
+            (None, None) => self.resolve_revision("@-")?.1,
+        };
+        let repo = self.load_repo_at_head()?;
+        let store = repo.store().clone();
+        let onto_id = CommitId::try_from_hex(&onto_hex).ok_or_else(|| Error::Repository {
+            message: format!("invalid commit id: {}", onto_hex),
+        })?;
+        let mut parent = store.get_commit(&onto_id).map_err(|e| Error::Repository {
+            message: format!("failed to get commit: {}", e),
+        })?;
 
-        self.save_typed_change(&typed_change)?;
+        let _critical = crate::deadline::critical();
+        let mut tx = repo.start_transaction();
+        let mut changes = Vec::new();
+        let mut typed = Vec::new();
+        let mut renamed: HashMap<String, String> = HashMap::new();
+        for (number, patch) in patches.iter().enumerate() {
+            let failed = |e: Error| Error::Repository {
+                message: format!(
+                    "patch {} ({}) does not apply: {}",
+                    number + 1,
+                    patch.subject(),
+                    e
+                ),
+            };
+            let paths = crate::intent::patch_paths(&patch.diff);
+            let at = parent.id().hex();
+            let mut overlay = tree_overlay(&store, &parent.tree(), paths, &at).map_err(failed)?;
+            let mut files_changed = patch_overlay(&mut overlay, &patch.diff).map_err(failed)?;
+            files_changed.dedup();
+            let tree = write_overlay(&store, &parent.tree(), &overlay)?;
+
+            let author = Signature {
+                name: patch.author.name.clone(),
+                email: patch.author.email.clone(),
+                timestamp: Timestamp {
+                    timestamp: MillisSinceEpoch(patch.author.secs * 1000),
+                    tz_offset: patch.author.tz_minutes,
+                },
+            };
+            // The original change ID, unless it is taken here already
+            let mut original_change_id = None;
+            let mut keep_id = None;
+            if let Some(wanted) = patch.change.as_ref().map(|c| c.change_id.clone()) {
+                let free = ChangeId::try_from_hex(&wanted).filter(|id| {
+                    tx.repo()
+                        .resolve_change_id(id)
+                        .ok()
+                        .flatten()
+                        .is_none_or(|targets| targets.visible_with_offsets().next().is_none())
+                });
+                match free {
+                    Some(id) => keep_id = Some(id),
+                    None => original_change_id = Some(wanted),
+                }
+            }
+            let mut builder = tx
+                .repo_mut()
+                .new_commit(vec![parent.id().clone()], tree)
+                .set_description(&patch.description)
+                .set_author(author);
+            if let Some(id) = keep_id {
+                builder = builder.set_change_id(id);
+            }
+            let commit = builder.write().map_err(|e| Error::Repository {
+                message: format!("failed to create commit: {}", e),
+            })?;
+            let change_id = commit.change_id().hex();
+            if let Some(original) = &original_change_id {
+                renamed.insert(original.clone(), change_id.clone());
+            }
 
-        // Invalidate cached workspace
+            let metrics =
+                ChangeMetrics::from_changes(&file_changes(&store, &parent.tree(), &commit.tree())?);
+            let change = match &patch.change {
+                Some(change) => Some(change.clone()),
+                None => crate::change::ConventionalCommit::parse(&patch.description)
+                    .and_then(|c| c.to_typed_change(change_id.clone())),
+            };
+            if let Some(mut change) = change {
+                change.change_id = change_id.clone();
+                change = change
+                    .with_files(files_changed.clone())
+                    .with_metrics(metrics);
+                typed.push(change);
+            }
+            changes.push(ImportedChange {
+                change_id,
+                commit_id: commit.id().hex(),
+                subject: patch.subject().to_string(),
+                files_changed,
+                original_change_id,
+                metadata: patch.change.is_some(),
+            });
+            parent = commit;
+        }
+        tx.commit("series import").map_err(|e| Error::Repository {
+            message: format!("failed to commit transaction: {}", e),
+        })?;
         self.workspace = None;
 
-        let short_commit = if commit_hex.len() > 12 {
-            &commit_hex[..12]
-        } else {
-            &commit_hex
-        };
-
-        Ok(CommitResult {
-            change_id: committed.change_id().hex(),
-            commit_id: short_commit.to_string(),
-            operation_id: new_repo.op_id().hex(),
-            files_changed,
-            invariants,
+        for mut change in typed {
+            for id in change.depends_on.iter_mut() {
+                if let Some(new_id) = renamed.get(id) {
+                    *id = new_id.clone();
+                }
+            }
+            self.save_typed_change(&change)?;
+        }
+        Ok(SeriesImport {
+            onto: onto_hex,
+            changes,
         })
     }
 
-    /// Get the raw ASCII graph output using git (no jj CLI dependency).
+    /// One line per commit, newest first (`@` marks the working copy), for
+    /// repositories without git to draw the graph
+    fn log_ascii_native(&mut self, limit: usize, all: bool) -> Result<String> {
+        let mut out = String::new();
+        for entry in self.log_entries(limit, all)? {
+            let marker = if entry.is_working_copy {
+                '@'
+            } else if entry.immutable {
+                '◆'
+            } else {
+                '*'
+            };
+            let short = &entry.commit_id[..entry.commit_id.len().min(7)];
+            let summary = entry.description.lines().next().unwrap_or("");
+            let summary = if summary.is_empty() {
+                "(no description set)"
+            } else {
+                summary
+            };
+            out.push_str(&format!("{} {} {}\n", marker, short, summary));
+        }
+        Ok(out)
+    }
+
+    /// Get the raw ASCII graph output using git (no jj CLI dependency), or
+    /// from jj's own history when there is no git repository.
     pub fn log_ascii(&mut self, limit: usize, all: bool) -> Result<String> {
+        if !self.has_git_backend()? {
+            return self.log_ascii_native(limit, all);
+        }
         let limit_str = limit.to_string();
+        let max_str = self.max_history().map(|max| max.to_string());
         let mut args = vec!["log", "--graph", "--oneline", "--decorate"];
 
         if !all {
@@ -2011,6 +5750,10 @@ impl Repo {
             args.push(&limit_str);
         } else {
             args.push("--all");
+            if let Some(max) = &max_str {
+                args.push("-n");
+                args.push(max);
+            }
         }
 
         let output = Command::new("git")
@@ -2034,6 +5777,556 @@ impl Repo {
     }
 }
 
+/// Captured result of a shell command run by `run_shell`
+struct ShellOutput {
+    success: bool,
+    code: Option<i32>,
+    timed_out: bool,
+    stdout: String,
+    stderr: String,
+}
+
+/// Run `cmd` via `sh -c` in `dir`, killing it if it outlives `timeout`.
+/// `stdin` is fed to the command when given and `env` is added to its
+/// environment. Output is drained on background threads so a chatty
+/// command cannot block on a full pipe.
+fn run_shell(
+    cmd: &str,
+    dir: &Path,
+    timeout: Option<std::time::Duration>,
+    stdin: Option<Vec<u8>>,
+    env: &[(&str, String)],
+) -> std::io::Result<ShellOutput> {
+    use std::io::{Read, Write};
+    use std::process::Stdio;
+
+    let _span = tracing::info_span!("subprocess", cmd).entered();
+    // Never outlive the command-wide --timeout
+    let timeout = crate::deadline::cap(timeout);
+    let mut command = Command::new("sh");
+    command
+        .args(["-c", cmd])
+        .envs(env.iter().map(|(k, v)| (*k, v)))
+        .current_dir(dir)
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    // Own process group, so a timeout also kills whatever the shell spawned
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+    let mut child = command.spawn()?;
+
+    // Commands that ignore stdin may exit early; a broken pipe is fine
+    if let (Some(input), Some(mut pipe)) = (stdin, child.stdin.take()) {
+        std::thread::spawn(move || {
+            let _ = pipe.write_all(&input);
+        });
+    }
+
+    let drain = |pipe: Option<Box<dyn Read + Send>>| {
+        std::thread::spawn(move || {
+            let mut buf = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut buf);
+            }
+            String::from_utf8_lossy(&buf).to_string()
+        })
+    };
+    let stdout = drain(
+        child
+            .stdout
+            .take()
+            .map(|p| Box::new(p) as Box<dyn Read + Send>),
+    );
+    let stderr = drain(
+        child
+            .stderr
+            .take()
+            .map(|p| Box::new(p) as Box<dyn Read + Send>),
+    );
+
+    let started = std::time::Instant::now();
+    let mut timed_out = false;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if timeout.is_some_and(|limit| started.elapsed() >= limit) {
+            timed_out = true;
+            #[cfg(unix)]
+            let _ = Command::new("kill")
+                .args(["-s", "KILL", "--", &format!("-{}", child.id())])
+                .stderr(Stdio::null())
+                .status();
+            let _ = child.kill();
+            break child.wait()?;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    };
+
+    Ok(ShellOutput {
+        success: status.success() && !timed_out,
+        code: status.code(),
+        timed_out,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+/// Walk `dir` (at slash-separated `prefix` under the root) recording files
+/// missing from the working-copy tree as ignored or untracked. Files inside
+/// an ignored directory are ignored unconditionally, as in git.
+fn collect_untracked(
+    dir: &Path,
+    prefix: &str,
+    ignores: &Arc<GitIgnoreFile>,
+    in_ignored_dir: bool,
+    tracked: &std::collections::HashSet<String>,
+    statuses: &mut HashMap<String, FileStatus>,
+) {
+    let ignores = ignores
+        .chain_with_file(prefix, dir.join(".gitignore"))
+        .unwrap_or_else(|_| ignores.clone());
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let rel = format!("{}{}", prefix, name);
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            if prefix.is_empty() && (name == ".jj" || name == ".git") {
+                continue;
+            }
+            let ignored = in_ignored_dir || ignores.matches(&format!("{}/", rel));
+            collect_untracked(
+                &entry.path(),
+                &format!("{}/", rel),
+                &ignores,
+                ignored,
+                tracked,
+                statuses,
+            );
+        } else if !tracked.contains(&rel) {
+            let status = if in_ignored_dir || ignores.matches(&rel) {
+                FileStatus::Ignored
+            } else {
+                FileStatus::Untracked
+            };
+            statuses.insert(rel, status);
+        }
+    }
+}
+
+/// Paths that differ between a commit and its parent(s).
+/// Stable string ID of a (possibly conflicted) tree
+fn tree_key(tree: &jj_lib::merged_tree::MergedTree) -> String {
+    tree.tree_ids()
+        .iter()
+        .map(|id| id.hex())
+        .collect::<Vec<_>>()
+        .join("+")
+}
+
+/// The working copy's tree with `commit` taken back out: a 3-way merge
+/// with the commit's tree as the base and its parent's tree as the target
+fn revert_tree(
+    wc_commit: &jj_lib::commit::Commit,
+    commit: &jj_lib::commit::Commit,
+    parent_tree: jj_lib::merged_tree::MergedTree,
+) -> Result<jj_lib::merged_tree::MergedTree> {
+    jj_lib::merged_tree::MergedTree::merge(jj_lib::merge::Merge::from_vec(vec![
+        (wc_commit.tree(), "working copy".to_string()),
+        (commit.tree(), "change".to_string()),
+        (parent_tree, "change parent".to_string()),
+    ]))
+    .block_on()
+    .map_err(|e| Error::Repository {
+        message: format!("failed to merge revert: {}", e),
+    })
+}
+
+fn history_entry(commit: &crate::history::CommitRecord) -> HistoryEntry {
+    HistoryEntry {
+        change_id: commit.change_id.clone(),
+        commit_id: commit.id.clone(),
+        description: commit.description.clone(),
+        timestamp: utc_timestamp(commit.timestamp_ms.div_euclid(1000)),
+        author: if commit.author_name.is_empty() {
+            commit.author_email.clone()
+        } else {
+            commit.author_name.clone()
+        },
+    }
+}
+
+fn files_changed_in(repo: &ReadonlyRepo, commit: &jj_lib::commit::Commit) -> Result<Vec<String>> {
+    let _span = tracing::info_span!("tree_diff").entered();
+    let parent_tree = commit.parent_tree(repo).map_err(|e| Error::Repository {
+        message: format!("failed to get parent tree: {}", e),
+    })?;
+
+    let tree = commit.tree();
+
+    // Diff the trees using synchronous iterator
+    let mut files = Vec::new();
+    let diff_iter =
+        jj_lib::merged_tree::TreeDiffIterator::new(&parent_tree, &tree, &EverythingMatcher);
+    for diff_entry in diff_iter {
+        files.push(diff_entry.path.as_internal_file_string().to_string());
+    }
+
+    Ok(files)
+}
+/// Contents of a file blob from the store
+fn read_file_bytes(
+    store: &Arc<jj_lib::store::Store>,
+    path: &RepoPath,
+    id: &jj_lib::backend::FileId,
+) -> Result<Vec<u8>> {
+    use tokio::io::AsyncReadExt as _;
+
+    let mut content = Vec::new();
+    async {
+        let mut reader = store.read_file(path, id).await?;
+        reader
+            .read_to_end(&mut content)
+            .await
+            .map_err(|e| jj_lib::backend::BackendError::Other(e.into()))?;
+        Ok::<_, jj_lib::backend::BackendError>(())
+    }
+    .block_on()
+    .map_err(|e| Error::Repository {
+        message: format!("failed to read '{}': {}", path.as_internal_file_string(), e),
+    })?;
+    Ok(content)
+}
+
+/// Typed change metadata for a change created by an intent
+fn typed_change_for(
+    intent: &Intent,
+    change_id: &str,
+    files_changed: &[String],
+    invariants: &HashMap<String, InvariantStatus>,
+) -> TypedChange {
+    let mut typed_change = TypedChange::new(change_id, intent.change_type, &intent.description)
+        .with_files(files_changed.to_vec());
+    typed_change.categories = intent.categories.clone();
+    if intent.breaking {
+        typed_change = typed_change.breaking();
+    }
+    typed_change.invariants = InvariantsResult {
+        checked: invariants.keys().cloned().collect(),
+        status: if invariants.values().all(|s| *s == InvariantStatus::Passed) {
+            InvariantStatus::Passed
+        } else {
+            InvariantStatus::Failed
+        },
+        details: invariants.clone(),
+    };
+    typed_change
+}
+
+/// Where an intent's files are read from while it is checked
+enum FileView<'a> {
+    /// The working copy on disk
+    Disk(&'a Path),
+    /// A revision's files, read into memory for an `onto` intent
+    Tree(&'a Overlay),
+}
+
+impl FileView<'_> {
+    fn exists(&self, path: &str) -> bool {
+        match self {
+            FileView::Disk(root) => root.join(path).exists(),
+            FileView::Tree(overlay) => overlay.get(path).is_some(),
+        }
+    }
+
+    fn read(&self, path: &str) -> std::io::Result<Vec<u8>> {
+        match self {
+            FileView::Disk(root) => std::fs::read(root.join(path)),
+            FileView::Tree(overlay) => overlay
+                .get(path)
+                .map(|entry| entry.bytes().to_vec())
+                .ok_or_else(|| std::io::ErrorKind::NotFound.into()),
+        }
+    }
+}
+
+/// The revision an `onto` intent builds on, with the files it touches
+struct OntoBase {
+    commit: jj_lib::commit::Commit,
+    overlay: Overlay,
+}
+
+/// The files at `paths` in `tree` (a tree of revision `at`), read into an
+/// overlay
+fn tree_overlay(
+    store: &Arc<jj_lib::store::Store>,
+    tree: &jj_lib::merged_tree::MergedTree,
+    paths: impl IntoIterator<Item = String>,
+    at: &str,
+) -> Result<Overlay> {
+    let mut files = std::collections::BTreeMap::new();
+    for path in paths {
+        let repo_path = RepoPath::from_internal_string(&path).map_err(|e| Error::Repository {
+            message: format!("invalid path '{}': {}", path, e),
+        })?;
+        let value = tree.path_value(repo_path).map_err(|e| Error::Repository {
+            message: format!("failed to read '{}' at {}: {}", path, at, e),
+        })?;
+        let value = value.as_resolved().ok_or_else(|| Error::Repository {
+            message: format!("'{}' is conflicted at {}", path, at),
+        })?;
+        if let Some(entry) = read_entry(store, repo_path, value)? {
+            files.insert(path, entry);
+        }
+    }
+    Ok(Overlay::new(files))
+}
+
+/// A tree value as an overlay entry; `None` when absent or not a file or
+/// symlink
+fn read_entry(
+    store: &Arc<jj_lib::store::Store>,
+    path: &RepoPath,
+    value: &Option<jj_lib::backend::TreeValue>,
+) -> Result<Option<Entry>> {
+    Ok(match value {
+        Some(jj_lib::backend::TreeValue::File { id, executable, .. }) => Some(Entry::File {
+            content: read_file_bytes(store, path, id)?,
+            executable: *executable,
+        }),
+        Some(jj_lib::backend::TreeValue::Symlink(id)) => Some(Entry::Symlink {
+            target: store
+                .read_symlink(path, id)
+                .block_on()
+                .map_err(|e| Error::Repository {
+                    message: format!(
+                        "failed to read symlink '{}': {}",
+                        path.as_internal_file_string(),
+                        e
+                    ),
+                })?,
+        }),
+        _ => None,
+    })
+}
+
+/// `base` with every change in `overlay` written to the store
+fn write_overlay(
+    store: &Arc<jj_lib::store::Store>,
+    base: &jj_lib::merged_tree::MergedTree,
+    overlay: &Overlay,
+) -> Result<jj_lib::merged_tree::MergedTree> {
+    let mut builder = MergedTreeBuilder::new(base.clone());
+    for (path, entry) in overlay.changes() {
+        let repo_path = RepoPath::from_internal_string(path).map_err(|e| Error::Repository {
+            message: format!("invalid path '{}': {}", path, e),
+        })?;
+        let write_error = |e: jj_lib::backend::BackendError| Error::Repository {
+            message: format!("failed to write '{}': {}", path, e),
+        };
+        let value = match entry {
+            Some(Entry::File {
+                content,
+                executable,
+            }) => Some(jj_lib::backend::TreeValue::File {
+                id: store
+                    .write_file(repo_path, &mut content.as_slice())
+                    .block_on()
+                    .map_err(write_error)?,
+                executable: *executable,
+                copy_id: jj_lib::backend::CopyId::placeholder(),
+            }),
+            Some(Entry::Symlink { target }) => Some(jj_lib::backend::TreeValue::Symlink(
+                store
+                    .write_symlink(repo_path, target)
+                    .block_on()
+                    .map_err(write_error)?,
+            )),
+            None => None,
+        };
+        builder.set_or_remove(repo_path.to_owned(), jj_lib::merge::Merge::resolved(value));
+    }
+    builder.write_tree().map_err(|e| Error::Repository {
+        message: format!("failed to write tree: {}", e),
+    })
+}
+
+/// Apply a unified diff to an overlay. `patch` needs real files, so the
+/// paths it names are written to a temporary directory, patched there, and
+/// read back; nothing in the working copy is touched.
+fn patch_overlay(overlay: &mut Overlay, patch: &str) -> Result<Vec<String>> {
+    use sha2::Digest;
+    let dir = std::env::temp_dir().join(format!(
+        "agentjj-patch-{}-{}",
+        std::process::id(),
+        hex::encode(&sha2::Sha256::digest(patch.as_bytes())[..6])
+    ));
+    let paths = crate::intent::patch_paths(patch);
+    let result = (|| {
+        for path in &paths {
+            let dest = dir.join(path);
+            if let Some(parent) = dest.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            match overlay.get(path) {
+                Some(Entry::File {
+                    content,
+                    executable,
+                }) => {
+                    std::fs::write(&dest, content)?;
+                    set_executable(&dest, *executable)?;
+                }
+                Some(Entry::Symlink { target }) => create_symlink(target, &dest)?,
+                None => {}
+            }
+        }
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join(".agentjj.patch"), patch)?;
+        let output = Command::new("patch")
+            .args(["-p1", "-i", ".agentjj.patch"])
+            .current_dir(&dir)
+            .output()
+            .map_err(|e| Error::Repository {
+                message: format!("failed to run patch: {}", e),
+            })?;
+        if !output.status.success() {
+            return Err(Error::Repository {
+                message: format!("patch failed: {}", String::from_utf8_lossy(&output.stderr)),
+            });
+        }
+
+        let mut changed = Vec::new();
+        for path in &paths {
+            let full_path = dir.join(path);
+            let entry = match std::fs::symlink_metadata(&full_path) {
+                Ok(meta) if meta.file_type().is_symlink() => Some(Entry::Symlink {
+                    target: std::fs::read_link(&full_path)?
+                        .to_string_lossy()
+                        .into_owned(),
+                }),
+                Ok(meta) if meta.is_file() => Some(Entry::File {
+                    content: std::fs::read(&full_path)?,
+                    executable: is_executable(&meta),
+                }),
+                _ => None,
+            };
+            if overlay.get(path) != entry.as_ref() {
+                overlay.set(path, entry);
+                changed.push(path.clone());
+            }
+        }
+        Ok(changed)
+    })();
+    let _ = std::fs::remove_dir_all(&dir);
+    result
+}
+
+/// Materialize a single tree value (file or symlink) at `dest` on disk.
+fn write_tree_value(
+    store: &Arc<jj_lib::store::Store>,
+    path: &RepoPath,
+    value: &jj_lib::backend::TreeValue,
+    dest: &Path,
+) -> Result<()> {
+    match value {
+        jj_lib::backend::TreeValue::File { id, executable, .. } => {
+            let content = read_file_bytes(store, path, id)?;
+            std::fs::write(dest, content)?;
+            set_executable(dest, *executable)
+        }
+        jj_lib::backend::TreeValue::Symlink(id) => {
+            let target =
+                store
+                    .read_symlink(path, id)
+                    .block_on()
+                    .map_err(|e| Error::Repository {
+                        message: format!(
+                            "failed to read symlink '{}': {}",
+                            path.as_internal_file_string(),
+                            e
+                        ),
+                    })?;
+            create_symlink(&target, dest)
+        }
+        _ => Err(Error::Repository {
+            message: format!("'{}' is not a regular file", path.as_internal_file_string()),
+        }),
+    }
+}
+
+/// Whether any executable bit is set (always false where the filesystem
+/// has no such bit)
+pub(crate) fn is_executable(meta: &std::fs::Metadata) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        meta.permissions().mode() & 0o111 != 0
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = meta;
+        false
+    }
+}
+
+/// Set or clear the executable bits of a file (a no-op where the
+/// filesystem has no such bit)
+pub(crate) fn set_executable(dest: &Path, executable: bool) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mut perms = std::fs::metadata(dest)?.permissions();
+        let mode = perms.mode();
+        perms.set_mode(if executable {
+            mode | 0o111
+        } else {
+            mode & !0o111
+        });
+        std::fs::set_permissions(dest, perms)?;
+    }
+    #[cfg(not(unix))]
+    let _ = (dest, executable);
+    Ok(())
+}
+
+/// Whether a symlink at repo-relative `path` pointing at `target` would
+/// lead outside the repository (absolute, or too many `..`)
+pub(crate) fn symlink_escapes_repo(path: &str, target: &str) -> bool {
+    let mut depth = Path::new(path).components().count().saturating_sub(1) as isize;
+    for component in Path::new(target).components() {
+        match component {
+            std::path::Component::Normal(_) => depth += 1,
+            std::path::Component::ParentDir => depth -= 1,
+            std::path::Component::CurDir => {}
+            _ => return true,
+        }
+        if depth < 0 {
+            return true;
+        }
+    }
+    false
+}
+
+/// Create a symlink at `dest`; where symlinks are unavailable, a file
+/// holding the target (as git does with `core.symlinks=false`)
+pub(crate) fn create_symlink(target: &str, dest: &Path) -> Result<()> {
+    #[cfg(unix)]
+    std::os::unix::fs::symlink(target, dest)?;
+    #[cfg(not(unix))]
+    std::fs::write(dest, target)?;
+    Ok(())
+}
+
 /// Convert days since Unix epoch to (year, month, day) using civil calendar arithmetic.
 pub fn days_to_ymd(days: i64) -> (i64, u32, u32) {
     // Algorithm from Howard Hinnant's chrono-compatible date calculations
@@ -2050,6 +6343,61 @@ pub fn days_to_ymd(days: i64) -> (i64, u32, u32) {
     (y, m, d)
 }
 
+/// Format seconds since the Unix epoch as `YYYY-MM-DDTHH:MM:SSZ`
+pub fn utc_timestamp(secs: i64) -> String {
+    let (year, month, day) = days_to_ymd(secs.div_euclid(86400));
+    let time_of_day = secs.rem_euclid(86400);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time_of_day / 3600,
+        (time_of_day % 3600) / 60,
+        time_of_day % 60
+    )
+}
+
+/// Seconds since the Unix epoch of a `utc_timestamp` (`YYYY-MM-DDTHH:MM:SSZ`)
+pub fn parse_utc_timestamp(timestamp: &str) -> Option<i64> {
+    let (date, time) = timestamp.strip_suffix('Z')?.split_once('T')?;
+    let mut date = date.splitn(3, '-').map(|p| p.parse::<i64>().ok());
+    let (y, m, d) = (date.next()??, date.next()??, date.next()??);
+    let mut time = time.splitn(3, ':').map(|p| p.parse::<i64>().ok());
+    let (hh, mm, ss) = (time.next()??, time.next()??, time.next()??);
+    // Inverse of days_to_ymd
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = if y >= 0 { y } else { y - 399 } / 400;
+    let yoe = y - era * 400;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+    Some(days * 86400 + hh * 3600 + mm * 60 + ss)
+}
+
+/// The first 12 characters of a change, commit, or operation id, as shown
+/// to people
+pub fn short_id(id: &str) -> &str {
+    &id[..12.min(id.len())]
+}
+
+/// The current time as a `utc_timestamp`
+pub fn utc_now() -> String {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    utc_timestamp(secs as i64)
+}
+
+/// Whether the store keeps commits in git (colocated or internal), so jj
+/// refs can be exported to git branches
+fn is_git_backed(store: &jj_lib::store::Store) -> bool {
+    store
+        .backend_impl::<jj_lib::git_backend::GitBackend>()
+        .is_some()
+}
+
 /// Get the current git branch name from HEAD's symbolic ref. Returns None
 /// when HEAD is detached (common in jj colocated mode) to avoid guessing
 /// which branch to update — guessing wrong can move an unrelated branch.
@@ -2110,6 +6458,14 @@ name = "test-repo"
         assert!(repo.has_manifest());
     }
 
+    #[test]
+    fn symlinks_must_stay_inside_the_repo() {
+        assert!(!symlink_escapes_repo("bin/tool", "../scripts/tool.sh"));
+        assert!(!symlink_escapes_repo("current", "releases/v2"));
+        assert!(symlink_escapes_repo("bin/tool", "../../outside"));
+        assert!(symlink_escapes_repo("key", "/etc/passwd"));
+    }
+
     #[test]
     fn load_manifest() {
         let (_tmp, mut repo) = setup_test_repo();
@@ -2159,7 +6515,7 @@ name = "test-repo"
         .with_preconditions(preconds);
 
         // Should pass
-        let result = repo.check_preconditions(&intent);
+        let result = repo.check_preconditions(&intent, &FileView::Disk(tmp.path()));
         assert!(result.is_ok());
 
         // Now test with wrong hash
@@ -2171,7 +6527,7 @@ name = "test-repo"
         )
         .with_preconditions(bad_preconds);
 
-        let bad_result = repo.check_preconditions(&bad_intent);
+        let bad_result = repo.check_preconditions(&bad_intent, &FileView::Disk(tmp.path()));
         assert!(bad_result.is_err());
 
         match bad_result {
@@ -2191,7 +6547,7 @@ name = "test-repo"
         )
         .with_preconditions(upper_preconds);
 
-        let upper_result = repo.check_preconditions(&upper_intent);
+        let upper_result = repo.check_preconditions(&upper_intent, &FileView::Disk(tmp.path()));
         assert!(upper_result.is_ok(), "Uppercase hash should match");
     }
 
@@ -2215,6 +6571,21 @@ name = "test-repo"
         assert_eq!((y, m, d), (2000, 2, 29));
     }
 
+    #[test]
+    fn utc_timestamp_formats_iso8601() {
+        assert_eq!(super::utc_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(
+            super::utc_timestamp(11016 * 86400 + 3661),
+            "2000-02-29T01:01:01Z"
+        );
+        assert_eq!(
+            super::parse_utc_timestamp("2000-02-29T01:01:01Z"),
+            Some(11016 * 86400 + 3661)
+        );
+        assert_eq!(super::parse_utc_timestamp("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(super::parse_utc_timestamp("2000-02-29"), None);
+    }
+
     #[test]
     fn log_entry_has_new_fields() {
         let entry = LogEntry {
@@ -2226,6 +6597,7 @@ name = "test-repo"
             timestamp: Some("2026-02-14T10:30:00+00:00".to_string()),
             author: Some("Test User".to_string()),
             full_commit_id: "ef567890abcdef1234567890abcdef1234567890".to_string(),
+            immutable: false,
         };
         assert_eq!(
             entry.timestamp.as_deref(),
@@ -2234,4 +6606,100 @@ name = "test-repo"
         assert_eq!(entry.author.as_deref(), Some("Test User"));
         assert_eq!(entry.full_commit_id.len(), 40);
     }
+
+    #[test]
+    fn native_repo_diffs_and_logs_without_git() {
+        let tmp = TempDir::new().unwrap();
+        let mut repo = Repo::init_native(tmp.path()).unwrap();
+        assert!(!repo.has_git_backend().unwrap());
+        assert!(matches!(
+            repo.require_git("push"),
+            Err(Error::GitRequired { .. })
+        ));
+
+        std::fs::write(tmp.path().join("notes.txt"), "one\ntwo\n").unwrap();
+        let changes = repo.tree_changes("@").unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path, "notes.txt");
+        assert!(changes[0].before.is_none());
+        assert_eq!(changes[0].line_counts(), Some((2, 0)));
+
+        let log = repo.log_ascii(10, false).unwrap();
+        assert!(log.starts_with("@ "), "{}", log);
+    }
+
+    #[test]
+    fn walk_files_prunes_ignored_directories() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        for file in [
+            "src/main.rs",
+            "src/gen/out.rs",
+            "src/lib.rs",
+            "target/debug/big.bin",
+            "node_modules/pkg/index.js",
+            "vendor/dep.rs",
+            "docs/a.md",
+            ".jj/repo/store",
+            "README.md",
+        ] {
+            std::fs::create_dir_all(root.join(file).parent().unwrap()).unwrap();
+            std::fs::write(root.join(file), "x").unwrap();
+        }
+        std::fs::write(root.join(".gitignore"), "target/\nnode_modules\n").unwrap();
+        std::fs::write(root.join("src/.gitignore"), "gen/\n").unwrap();
+        let rel = |filters: WalkFilters| -> Vec<String> {
+            walk_files(root, filters)
+                .map(|p| {
+                    p.strip_prefix(root)
+                        .unwrap()
+                        .to_string_lossy()
+                        .replace('\\', "/")
+                })
+                .collect()
+        };
+
+        let filters = WalkFilters {
+            gitignore: true,
+            agentignore: Some(AgentIgnore::parse(b"vendor/\n").unwrap()),
+            ..WalkFilters::default()
+        };
+        assert_eq!(
+            rel(filters.clone()),
+            vec![
+                ".gitignore",
+                "README.md",
+                "docs/a.md",
+                "src/.gitignore",
+                "src/lib.rs",
+                "src/main.rs"
+            ]
+        );
+        assert_eq!(
+            rel(filters.including(Globs::new(["src/**/*.rs"]).unwrap())),
+            vec!["src/lib.rs", "src/main.rs"]
+        );
+
+        // Without gitignore, everything but .git and .jj, as globs::walk sorts it
+        let everything: Vec<String> = crate::globs::walk(root)
+            .into_iter()
+            .map(|p| {
+                p.strip_prefix(root)
+                    .unwrap()
+                    .to_string_lossy()
+                    .replace('\\', "/")
+            })
+            .collect();
+        assert_eq!(rel(WalkFilters::default()), everything);
+
+        let sparse = WalkFilters {
+            sparse: vec!["src".into(), "README.md".into()],
+            exclude: vec!["gen/".into()],
+            ..WalkFilters::default()
+        };
+        assert_eq!(
+            rel(sparse),
+            vec!["README.md", "src/.gitignore", "src/lib.rs", "src/main.rs"]
+        );
+    }
 }
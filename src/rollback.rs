@@ -0,0 +1,266 @@
+// ABOUTME: Rollback planning for `rollback`: revert vs op restore vs bookmark reset for one change
+// ABOUTME: Pure functions over facts gathered by repo.rs so the plan is easy to test
+
+use serde::Serialize;
+
+use crate::env::Bookmark;
+use crate::repo::short_id;
+
+/// Ways to take a change back out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Strategy {
+    /// Apply the inverse of the change in the working copy; history is kept
+    Revert,
+    /// Move bookmarks that include the change back to its parent
+    Reset,
+    /// Restore the operation log to just before the change existed
+    Restore,
+}
+
+impl Strategy {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "revert" => Some(Self::Revert),
+            "reset" => Some(Self::Reset),
+            "restore" => Some(Self::Restore),
+            _ => None,
+        }
+    }
+}
+
+/// A change built on top of the one being rolled back
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Descendant {
+    pub change_id: String,
+    pub description: String,
+}
+
+/// What the repository looks like around the change, gathered by repo.rs
+#[derive(Debug, Clone, Default)]
+pub struct Facts {
+    pub change_id: String,
+    pub commit_id: String,
+    pub description: String,
+    pub parent_commit_id: Option<String>,
+    pub files: Vec<String>,
+    /// Whether the change is the working copy itself
+    pub is_working_copy: bool,
+    /// Whether the working copy builds on the change
+    pub in_working_copy_ancestry: bool,
+    /// Whether the working copy has uncommitted changes
+    pub working_copy_dirty: bool,
+    pub descendants: Vec<Descendant>,
+    /// Bookmarks pointing at the change or one of its descendants
+    pub bookmarks: Vec<Bookmark>,
+    /// Files the inverse would conflict on when applied to the working copy
+    pub revert_conflicts: Vec<String>,
+    /// Last operation before the change existed, and how many operations
+    /// restoring it would undo
+    pub restore_point: Option<(String, usize)>,
+}
+
+/// Who and what the rollback touches
+#[derive(Debug, Clone, Serialize)]
+pub struct BlastRadius {
+    pub files: Vec<String>,
+    pub bookmarks: Vec<Bookmark>,
+    pub descendants: Vec<Descendant>,
+    pub working_copy_affected: bool,
+}
+
+/// One strategy as it would play out here
+#[derive(Debug, Clone, Serialize)]
+pub struct StrategyPlan {
+    pub strategy: Strategy,
+    pub applicable: bool,
+    /// Why it can't be used, when it can't
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blocked_by: Option<String>,
+    /// What executing it does, in order
+    pub effects: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub conflicts: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RollbackPlan {
+    pub change_id: String,
+    pub commit_id: String,
+    pub description: String,
+    pub blast_radius: BlastRadius,
+    pub strategies: Vec<StrategyPlan>,
+    /// Least destructive applicable strategy
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recommended: Option<Strategy>,
+}
+
+impl RollbackPlan {
+    pub fn strategy(&self, strategy: Strategy) -> &StrategyPlan {
+        self.strategies
+            .iter()
+            .find(|s| s.strategy == strategy)
+            .expect("plan covers every strategy")
+    }
+}
+
+/// Lay out every strategy, preferring revert (additive), then reset (moves
+/// bookmarks only), then restore (discards later operations)
+pub fn plan(facts: &Facts) -> RollbackPlan {
+    let change = short_id(&facts.change_id);
+
+    let revert = {
+        let blocked_by = if facts.is_working_copy {
+            Some("the change is the working copy - use `undo` or `checkpoint` to discard it".into())
+        } else if !facts.in_working_copy_ancestry {
+            Some("the working copy does not build on the change".into())
+        } else if facts.working_copy_dirty {
+            Some("the working copy has uncommitted changes - commit or stash them first".into())
+        } else if !facts.revert_conflicts.is_empty() {
+            Some(format!(
+                "the inverse conflicts with later changes in {} file(s)",
+                facts.revert_conflicts.len()
+            ))
+        } else {
+            None
+        };
+        StrategyPlan {
+            strategy: Strategy::Revert,
+            applicable: blocked_by.is_none(),
+            blocked_by,
+            effects: vec![
+                format!(
+                    "apply the inverse of {} to the working copy ({} file(s))",
+                    change,
+                    facts.files.len()
+                ),
+                "record it as a typed `fix` change linked to the original; commit to keep it"
+                    .into(),
+            ],
+            conflicts: facts.revert_conflicts.clone(),
+        }
+    };
+
+    let reset = {
+        let blocked_by = match (&facts.parent_commit_id, facts.bookmarks.is_empty()) {
+            (None, _) => Some("the change has no parent to reset to".into()),
+            (_, true) => Some("no bookmark includes the change".into()),
+            _ => None,
+        };
+        let mut effects: Vec<String> = facts
+            .bookmarks
+            .iter()
+            .map(|b| {
+                format!(
+                    "move bookmark {} from {} to the change's parent",
+                    b.name,
+                    short_id(&b.change_id)
+                )
+            })
+            .collect();
+        if !facts.descendants.is_empty() {
+            effects.push(format!(
+                "{} descendant change(s) drop off those bookmarks",
+                facts.descendants.len()
+            ));
+        }
+        StrategyPlan {
+            strategy: Strategy::Reset,
+            applicable: blocked_by.is_none(),
+            blocked_by,
+            effects,
+            conflicts: Vec::new(),
+        }
+    };
+
+    let restore = match &facts.restore_point {
+        Some((_, 0)) => StrategyPlan {
+            strategy: Strategy::Restore,
+            applicable: false,
+            blocked_by: Some("no operation has recorded content for the change yet".into()),
+            effects: Vec::new(),
+            conflicts: Vec::new(),
+        },
+        Some((op, undone)) => StrategyPlan {
+            strategy: Strategy::Restore,
+            applicable: true,
+            blocked_by: None,
+            effects: vec![
+                format!("restore the repository to operation {}", short_id(op)),
+                format!(
+                    "{} operation(s) since are undone, including all later work",
+                    undone
+                ),
+            ],
+            conflicts: Vec::new(),
+        },
+        None => StrategyPlan {
+            strategy: Strategy::Restore,
+            applicable: false,
+            blocked_by: Some("no operation before the change was found in the log".into()),
+            effects: Vec::new(),
+            conflicts: Vec::new(),
+        },
+    };
+
+    let strategies = vec![revert, reset, restore];
+    let recommended = strategies.iter().find(|s| s.applicable).map(|s| s.strategy);
+    RollbackPlan {
+        change_id: facts.change_id.clone(),
+        commit_id: facts.commit_id.clone(),
+        description: facts.description.clone(),
+        blast_radius: BlastRadius {
+            files: facts.files.clone(),
+            bookmarks: facts.bookmarks.clone(),
+            descendants: facts.descendants.clone(),
+            working_copy_affected: facts.in_working_copy_ancestry || facts.is_working_copy,
+        },
+        strategies,
+        recommended,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn facts() -> Facts {
+        Facts {
+            change_id: "kxqpzmvwtlrs".into(),
+            commit_id: "abc".into(),
+            description: "add retry".into(),
+            parent_commit_id: Some("def".into()),
+            files: vec!["src/retry.rs".into()],
+            in_working_copy_ancestry: true,
+            restore_point: Some(("0123456789abcdef".into(), 3)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn prefers_revert_then_reset_then_restore() {
+        let plan = plan(&facts());
+        assert_eq!(plan.recommended, Some(Strategy::Revert));
+        assert!(!plan.strategy(Strategy::Reset).applicable);
+
+        let mut conflicted = facts();
+        conflicted.revert_conflicts = vec!["src/retry.rs".into()];
+        conflicted.bookmarks = vec![Bookmark {
+            name: "main".into(),
+            change_id: "kxqpzmvwtlrs".into(),
+            commit_id: "abc".into(),
+            description: "add retry".into(),
+        }];
+        let plan = super::plan(&conflicted);
+        assert_eq!(plan.recommended, Some(Strategy::Reset));
+        assert_eq!(plan.strategy(Strategy::Revert).conflicts, ["src/retry.rs"]);
+
+        let mut stuck = facts();
+        stuck.working_copy_dirty = true;
+        stuck.restore_point = None;
+        let plan = super::plan(&stuck);
+        assert_eq!(plan.recommended, None);
+        assert!(!plan.strategy(Strategy::Restore).applicable);
+        assert!(plan.strategy(Strategy::Revert).blocked_by.is_some());
+    }
+}
@@ -0,0 +1,365 @@
+// ABOUTME: `test scaffold`: a new test file for a source file, at its language's conventional location
+// ABOUTME: Built-in templates per language, overridable by the manifest's [test_templates.<language>]
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::manifest::TestTemplate;
+use crate::symbols::{is_public_symbol, SupportedLanguage, Symbol, SymbolKind};
+
+/// A test file ready to write
+#[derive(Debug, Clone, Serialize)]
+pub struct Scaffold {
+    pub source: String,
+    pub test_file: String,
+    pub language: String,
+    /// Public symbols imported and stubbed, in file order
+    pub symbols: Vec<String>,
+    pub content: String,
+}
+
+/// The crate a Rust file belongs to: the directory of its Cargo.toml
+/// (repository-relative, empty at the root) and its library name
+#[derive(Debug, Clone)]
+pub struct RustCrate {
+    pub dir: String,
+    pub name: String,
+}
+
+/// Built-in template for a language; None when there is no convention for it
+pub fn builtin_template(lang: SupportedLanguage) -> Option<TestTemplate> {
+    let (path, header, stub) = match lang {
+        SupportedLanguage::Python => (
+            "tests/test_{name}.py",
+            "from {module} import {symbols}\n",
+            "\n\ndef test_{snake}():\n    pass\n",
+        ),
+        SupportedLanguage::Rust => (
+            "{package}/tests/{name}.rs",
+            "use {module}::{{symbols}};\n",
+            "\n#[test]\nfn {snake}() {}\n",
+        ),
+        SupportedLanguage::JavaScript | SupportedLanguage::TypeScript => (
+            "{dir}/{stem}.test.{ext}",
+            "import { {symbols} } from '{module}';\n",
+            "\ntest('{symbol}', () => {});\n",
+        ),
+        SupportedLanguage::Ruby => (
+            "spec/{name}_spec.rb",
+            "require_relative '{module}'\n",
+            "\nRSpec.describe '{symbol}' do\nend\n",
+        ),
+        _ => return None,
+    };
+    Some(TestTemplate {
+        path: Some(path.to_string()),
+        header: Some(header.to_string()),
+        stub: Some(stub.to_string()),
+    })
+}
+
+/// The template for a language: the manifest's fields over the built-in ones
+pub fn template_for(
+    lang: SupportedLanguage,
+    templates: &HashMap<String, TestTemplate>,
+) -> Option<TestTemplate> {
+    let configured = templates.get(lang.name());
+    let builtin = builtin_template(lang);
+    let field = |f: fn(&TestTemplate) -> &Option<String>| {
+        configured
+            .and_then(|t| f(t).clone())
+            .or_else(|| builtin.as_ref().and_then(|t| f(t).clone()))
+    };
+    Some(TestTemplate {
+        path: Some(field(|t| &t.path)?),
+        header: Some(field(|t| &t.header)?),
+        stub: Some(field(|t| &t.stub)?),
+    })
+}
+
+/// Public, top-level symbols of `source` worth a test
+pub fn testable_symbols(symbols: &[Symbol], lang: SupportedLanguage, source: &str) -> Vec<String> {
+    let lines: Vec<&str> = source.lines().collect();
+    let mut names: Vec<String> = Vec::new();
+    for symbol in symbols {
+        let testable = matches!(
+            symbol.kind,
+            SymbolKind::Function | SymbolKind::Class | SymbolKind::Struct | SymbolKind::Enum
+        );
+        // JS/TS signatures stop at the declaration, after any `export`
+        let exported = matches!(
+            lang,
+            SupportedLanguage::JavaScript | SupportedLanguage::TypeScript
+        ) && lines
+            .get(symbol.start_line.saturating_sub(1))
+            .is_some_and(|line| line.trim_start().starts_with("export "));
+        let public = exported || is_public_symbol(symbol, lang);
+        if testable && public && !names.contains(&symbol.name) {
+            names.push(symbol.name.clone());
+        }
+    }
+    names
+}
+
+/// `parseHTTPRequest` / `ParseRequest` -> `parse_http_request` / `parse_request`
+fn snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut out = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if c.is_uppercase() {
+            let prev_lower =
+                i > 0 && (chars[i - 1].is_lowercase() || chars[i - 1].is_ascii_digit());
+            let next_lower = chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+            let prev_upper = i > 0 && chars[i - 1].is_uppercase();
+            if !out.is_empty() && !out.ends_with('_') && (prev_lower || (prev_upper && next_lower))
+            {
+                out.push('_');
+            }
+            out.extend(c.to_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// `to` relative to the directory `from` (both repository-relative)
+fn relative(from: &str, to: &str) -> String {
+    let from: Vec<&str> = from.split('/').filter(|c| !c.is_empty()).collect();
+    let to: Vec<&str> = to.split('/').filter(|c| !c.is_empty()).collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut parts: Vec<&str> = vec![".."; from.len() - common];
+    parts.extend(&to[common..]);
+    let joined = parts.join("/");
+    if joined.starts_with("..") {
+        joined
+    } else {
+        format!("./{}", joined)
+    }
+}
+
+/// Clean up a rendered path: `{dir}` and `{package}` are empty at the root
+fn tidy_path(path: &str) -> String {
+    path.split('/')
+        .filter(|c| !c.is_empty() && *c != ".")
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn parent(path: &str) -> &str {
+    path.rsplit_once('/').map(|(dir, _)| dir).unwrap_or("")
+}
+
+/// How test code names the module of `source`
+fn module_path(
+    lang: SupportedLanguage,
+    source: &str,
+    test_file: &str,
+    rust_crate: Option<&RustCrate>,
+) -> String {
+    let without_ext = source
+        .rsplit_once('.')
+        .map(|(base, _)| base)
+        .unwrap_or(source);
+    match lang {
+        SupportedLanguage::Python => {
+            let module = without_ext.strip_prefix("src/").unwrap_or(without_ext);
+            let module = module.strip_suffix("/__init__").unwrap_or(module);
+            module.replace('/', ".")
+        }
+        SupportedLanguage::Rust => {
+            let (dir, name) = rust_crate
+                .map(|c| (c.dir.as_str(), c.name.as_str()))
+                .unwrap_or(("", "crate"));
+            let src = tidy_path(&format!("{}/src/", dir));
+            let inner = without_ext
+                .strip_prefix(&src)
+                .and_then(|p| p.strip_prefix('/'))
+                .unwrap_or(without_ext);
+            let mut path: Vec<&str> = vec![name];
+            path.extend(
+                inner
+                    .split('/')
+                    .filter(|c| !matches!(*c, "lib" | "main" | "mod")),
+            );
+            path.join("::")
+        }
+        _ => relative(parent(test_file), without_ext),
+    }
+}
+
+/// Where the test file for `source` (repository-relative) goes. Path
+/// placeholders: `{dir}`, `{stem}`, `{ext}`, `{name}` (the stem, or the
+/// directory for `mod`, `lib`, `main`, `index`, `__init__`), `{package}`
+/// (the Rust crate's directory).
+pub fn test_path(template: &TestTemplate, source: &str, rust_crate: Option<&RustCrate>) -> String {
+    let file = Path::new(source);
+    let stem = file.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    let ext = file.extension().and_then(|e| e.to_str()).unwrap_or("");
+    let dir = parent(source);
+    let name = if matches!(stem, "mod" | "lib" | "main" | "index" | "__init__") {
+        match dir
+            .rsplit('/')
+            .next()
+            .filter(|d| !d.is_empty() && *d != "src")
+        {
+            Some(dir_name) => dir_name,
+            None => rust_crate.map(|c| c.name.as_str()).unwrap_or(stem),
+        }
+    } else {
+        stem
+    };
+
+    let path = template.path.as_deref().unwrap_or_default();
+    tidy_path(
+        &path
+            .replace("{dir}", dir)
+            .replace("{stem}", stem)
+            .replace("{ext}", ext)
+            .replace("{name}", name)
+            .replace(
+                "{package}",
+                rust_crate.map(|c| c.dir.as_str()).unwrap_or(""),
+            ),
+    )
+}
+
+/// A test file for `source` of language `lang` whose public symbols are
+/// `symbols`, at `test_path`. The header gets `{module}` and `{symbols}`,
+/// dropping lines that import symbols when there are none; each symbol gets
+/// a stub with `{symbol}` and `{snake}`.
+pub fn render(
+    lang: SupportedLanguage,
+    template: &TestTemplate,
+    source: &str,
+    symbols: &[String],
+    rust_crate: Option<&RustCrate>,
+) -> Scaffold {
+    let test_file = test_path(template, source, rust_crate);
+    let module = module_path(lang, source, &test_file, rust_crate);
+    let mut content = String::new();
+    for line in template
+        .header
+        .as_deref()
+        .unwrap_or_default()
+        .split_inclusive('\n')
+    {
+        if symbols.is_empty() && line.contains("{symbols}") {
+            continue;
+        }
+        content.push_str(
+            &line
+                .replace("{module}", &module)
+                .replace("{symbols}", &symbols.join(", ")),
+        );
+    }
+    for symbol in symbols {
+        content.push_str(
+            &template
+                .stub
+                .as_deref()
+                .unwrap_or_default()
+                .replace("{symbol}", symbol)
+                .replace("{snake}", &snake_case(symbol)),
+        );
+    }
+
+    Scaffold {
+        source: source.to_string(),
+        test_file,
+        language: lang.name().to_string(),
+        symbols: symbols.to_vec(),
+        content,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbols::extract_symbols;
+
+    fn scaffold(lang: SupportedLanguage, source: &str, code: &str) -> Scaffold {
+        let symbols = testable_symbols(&extract_symbols(code, lang).unwrap(), lang, code);
+        let template = template_for(lang, &HashMap::new()).unwrap();
+        let rust_crate = RustCrate {
+            dir: "crates/billing".into(),
+            name: "billing".into(),
+        };
+        render(lang, &template, source, &symbols, Some(&rust_crate))
+    }
+
+    #[test]
+    fn renders_conventional_test_files() {
+        let py = scaffold(
+            SupportedLanguage::Python,
+            "src/billing/invoice.py",
+            "def total(items):\n    pass\n\nclass InvoiceLine:\n    pass\n\ndef _round(x):\n    pass\n",
+        );
+        assert_eq!(py.test_file, "tests/test_invoice.py");
+        assert_eq!(py.symbols, vec!["total", "InvoiceLine"]);
+        assert!(py
+            .content
+            .starts_with("from billing.invoice import total, InvoiceLine\n"));
+        assert!(py.content.contains("def test_invoice_line():\n    pass\n"));
+
+        let rs = scaffold(
+            SupportedLanguage::Rust,
+            "crates/billing/src/tax/mod.rs",
+            "pub fn vat_rate() -> u32 { 20 }\nfn hidden() {}\npub struct TaxTable;\n",
+        );
+        assert_eq!(rs.test_file, "crates/billing/tests/tax.rs");
+        assert!(rs
+            .content
+            .starts_with("use billing::tax::{vat_rate, TaxTable};\n"));
+        assert!(rs.content.contains("#[test]\nfn tax_table() {}\n"));
+
+        let js = scaffold(
+            SupportedLanguage::JavaScript,
+            "web/cart.js",
+            "export function addItem(cart) {}\n",
+        );
+        assert_eq!(js.test_file, "web/cart.test.js");
+        assert!(js
+            .content
+            .starts_with("import { addItem } from './cart';\n"));
+
+        // Nothing public: no import line, no stubs
+        let empty = scaffold(
+            SupportedLanguage::Python,
+            "app.py",
+            "def _main():\n    pass\n",
+        );
+        assert_eq!(empty.test_file, "tests/test_app.py");
+        assert!(empty.content.is_empty());
+    }
+
+    #[test]
+    fn manifest_templates_override_builtin_fields() {
+        let mut templates = HashMap::new();
+        templates.insert(
+            "python".to_string(),
+            TestTemplate {
+                path: Some("{dir}/{stem}_test.py".into()),
+                header: None,
+                stub: Some("\n\ndef test_{snake}(db):\n    assert {symbol}\n".into()),
+            },
+        );
+        let template = template_for(SupportedLanguage::Python, &templates).unwrap();
+        let out = render(
+            SupportedLanguage::Python,
+            &template,
+            "app/users.py",
+            &["getUser".to_string()],
+            None,
+        );
+        assert_eq!(out.test_file, "app/users_test.py");
+        assert_eq!(
+            out.content,
+            "from app.users import getUser\n\n\ndef test_get_user(db):\n    assert getUser\n"
+        );
+        assert_eq!(relative("spec", "lib/cart"), "../lib/cart");
+        assert_eq!(snake_case("parseHTTPRequest"), "parse_http_request");
+        assert!(template_for(SupportedLanguage::Sql, &templates).is_none());
+    }
+}
@@ -0,0 +1,670 @@
+// ABOUTME: Patch series (series export/import): git format-patch messages with typed-change metadata as trailers
+// ABOUTME: Rendering, mbox splitting, and trailer parsing; repo.rs walks the range and recreates the commits
+
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::change::{enum_name, ChangeType, InvariantStatus, TypedChange};
+use crate::error::{Error, Result};
+use crate::unified::FileChange;
+
+/// Every metadata trailer starts with this, so trailers of other tools stay apart
+pub const TRAILER_PREFIX: &str = "Agentjj-";
+
+/// The fixed date git puts on each message's `From <commit>` line
+const MBOX_DATE: &str = "Mon Sep 17 00:00:00 2001";
+
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// A commit's author as a patch carries it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Author {
+    pub name: String,
+    pub email: String,
+    /// Seconds since the Unix epoch
+    pub secs: i64,
+    /// Offset from UTC in minutes
+    pub tz_minutes: i32,
+}
+
+/// One change of a series
+#[derive(Debug, Clone)]
+pub struct SeriesPatch {
+    /// The commit it was exported from
+    pub commit_id: String,
+    pub author: Author,
+    /// The full commit description, subject first
+    pub description: String,
+    /// Typed-change metadata, when the change had any
+    pub change: Option<TypedChange>,
+    /// `git diff` output for the change
+    pub diff: String,
+    /// Diffstat lines (empty for imported patches)
+    pub stat: String,
+}
+
+impl SeriesPatch {
+    pub fn subject(&self) -> &str {
+        self.description.lines().next().unwrap_or("").trim()
+    }
+}
+
+/// A range of changes read for export
+#[derive(Debug, Clone)]
+pub struct Series {
+    pub patches: Vec<SeriesPatch>,
+    /// The commit the first patch applies to
+    pub base: Option<String>,
+    /// Changes left out because they change no files
+    pub empty: Vec<String>,
+}
+
+/// What `series import` created
+#[derive(Debug, Clone, Serialize)]
+pub struct SeriesImport {
+    /// The commit the series was put on
+    pub onto: String,
+    pub changes: Vec<ImportedChange>,
+}
+
+/// What `series export` wrote
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportedPatch {
+    pub file: String,
+    pub change_id: String,
+    pub commit_id: String,
+    pub subject: String,
+    /// Whether typed-change metadata went along as trailers
+    pub metadata: bool,
+}
+
+/// A change `series import` recreated
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportedChange {
+    pub change_id: String,
+    pub commit_id: String,
+    pub subject: String,
+    pub files_changed: Vec<String>,
+    /// The change ID in the exporting repository, when it was taken already here
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub original_change_id: Option<String>,
+    pub metadata: bool,
+}
+
+/// `0001-Add-retry-limit.patch`, as git format-patch names files
+pub fn file_name(number: usize, subject: &str) -> String {
+    let mut slug = String::new();
+    for c in subject.chars() {
+        if c.is_ascii_alphanumeric() || c == '_' || c == '.' {
+            slug.push(c);
+        } else if !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug: String = slug
+        .trim_matches(|c| c == '-' || c == '.')
+        .chars()
+        .take(52)
+        .collect();
+    let slug = slug.trim_end_matches(['-', '.']);
+    if slug.is_empty() {
+        format!("{:04}.patch", number)
+    } else {
+        format!("{:04}-{}.patch", number, slug)
+    }
+}
+
+/// `git diff --stat` lines for the changes
+pub fn diffstat(changes: &[FileChange]) -> String {
+    let counts: Vec<(&str, Option<(usize, usize)>)> = changes
+        .iter()
+        .map(|c| (c.path.as_str(), c.line_counts()))
+        .collect();
+    let width = counts.iter().map(|(p, _)| p.len()).max().unwrap_or(0);
+    let most = counts
+        .iter()
+        .filter_map(|(_, n)| n.map(|(a, r)| a + r))
+        .max()
+        .unwrap_or(0);
+    let digits = most.to_string().len();
+    let (mut added, mut removed) = (0, 0);
+    let mut out = String::new();
+    for (path, count) in &counts {
+        match count {
+            Some((a, r)) => {
+                added += a;
+                removed += r;
+                // Bars are scaled down so the widest fits in 50 columns
+                let scale = |n: usize| match most {
+                    0..=50 => n,
+                    _ => (n * 50).div_ceil(most),
+                };
+                out.push_str(&format!(
+                    " {:<width$} | {:>digits$} {}{}\n",
+                    path,
+                    a + r,
+                    "+".repeat(scale(*a)),
+                    "-".repeat(scale(*r)),
+                ));
+            }
+            None => out.push_str(&format!(" {:<width$} | Bin\n", path)),
+        }
+    }
+    let plural =
+        |n: usize, one: &str, many: &str| format!("{} {}", n, if n == 1 { one } else { many });
+    out.push_str(&format!(
+        " {}",
+        plural(changes.len(), "file changed", "files changed")
+    ));
+    if added > 0 {
+        out.push_str(&format!(
+            ", {}(+)",
+            plural(added, "insertion", "insertions")
+        ));
+    }
+    if removed > 0 {
+        out.push_str(&format!(
+            ", {}(-)",
+            plural(removed, "deletion", "deletions")
+        ));
+    }
+    out.push('\n');
+    out
+}
+
+/// RFC 2822 date in the author's own time zone
+fn format_date(secs: i64, tz_minutes: i32) -> String {
+    let local = secs + i64::from(tz_minutes) * 60;
+    let days = local.div_euclid(86400);
+    let (year, month, day) = crate::repo::days_to_ymd(days);
+    let time = local.rem_euclid(86400);
+    let offset = tz_minutes.abs();
+    format!(
+        "{}, {} {} {} {:02}:{:02}:{:02} {}{:02}{:02}",
+        WEEKDAYS[(days + 4).rem_euclid(7) as usize],
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        time / 3600,
+        (time % 3600) / 60,
+        time % 60,
+        if tz_minutes < 0 { '-' } else { '+' },
+        offset / 60,
+        offset % 60
+    )
+}
+
+/// Seconds and zone offset of an RFC 2822 date; the weekday is optional
+fn parse_date(date: &str) -> Option<(i64, i32)> {
+    let date = date.split_once(", ").map_or(date, |(_, rest)| rest);
+    let mut parts = date.split_whitespace();
+    let day: u32 = parts.next()?.parse().ok()?;
+    let month = parts.next()?;
+    let month = MONTHS.iter().position(|m| *m == month)? + 1;
+    let year: i64 = parts.next()?.parse().ok()?;
+    let time = parts.next()?;
+    let zone = parts.next()?;
+    let (sign, zone) = match zone.split_at(1) {
+        ("-", zone) => (-1, zone),
+        ("+", zone) => (1, zone),
+        _ => return None,
+    };
+    let zone: i32 = zone.parse().ok()?;
+    let tz_minutes = sign * ((zone / 100) * 60 + zone % 100);
+    let local = crate::repo::parse_utc_timestamp(&format!(
+        "{:04}-{:02}-{:02}T{}Z",
+        year, month, day, time
+    ))?;
+    Some((local - i64::from(tz_minutes) * 60, tz_minutes))
+}
+
+/// Metadata trailers for a typed change, in a fixed order. Multi-line
+/// values continue on lines indented by one space.
+pub fn trailers(change: &TypedChange) -> Vec<(String, String)> {
+    let mut out: Vec<(&str, String)> = vec![
+        ("Change-Id", change.change_id.clone()),
+        ("Type", enum_name(change.change_type)),
+    ];
+    out.extend(
+        change
+            .categories
+            .iter()
+            .map(|c| ("Category", c.name().to_string())),
+    );
+    if change.breaking {
+        out.push(("Breaking", "true".into()));
+    }
+    out.push(("Intent", change.intent.clone()));
+    out.extend(
+        change
+            .depends_on
+            .iter()
+            .map(|id| ("Depends-On", id.clone())),
+    );
+    out.extend(
+        change
+            .fields
+            .iter()
+            .map(|(k, v)| ("Field", format!("{}={}", k, v))),
+    );
+    let mut metadata: Vec<_> = change.metadata.iter().collect();
+    metadata.sort();
+    out.extend(
+        metadata
+            .into_iter()
+            .map(|(k, v)| ("Metadata", format!("{}={}", k, v))),
+    );
+    out.extend(
+        change
+            .dependencies_added
+            .iter()
+            .map(|d| ("Dependency-Added", d.clone())),
+    );
+    out.extend(
+        change
+            .dependencies_removed
+            .iter()
+            .map(|d| ("Dependency-Removed", d.clone())),
+    );
+    let invariants = &change.invariants;
+    if invariants.status != InvariantStatus::Unknown {
+        let mut value = enum_name(invariants.status);
+        if !invariants.checked.is_empty() {
+            value.push_str(&format!(" ({})", invariants.checked.join(", ")));
+        }
+        out.push(("Invariants", value));
+    }
+    if let Some(notes) = &change.notes {
+        out.push(("Notes", notes.clone()));
+    }
+    if let Some(author) = &change.author {
+        out.push(("Author", author.clone()));
+    }
+    if let Some(created_at) = &change.created_at {
+        out.push(("Created-At", created_at.clone()));
+    }
+    out.into_iter()
+        .map(|(key, value)| (format!("{}{}", TRAILER_PREFIX, key), value))
+        .collect()
+}
+
+/// A typed change from its trailers (`None` without `Change-Id` and `Type`)
+pub fn change_from_trailers(trailers: &[(String, String)]) -> Result<Option<TypedChange>> {
+    fn values<'a>(
+        trailers: &'a [(String, String)],
+        key: &'a str,
+    ) -> impl Iterator<Item = String> + 'a {
+        trailers
+            .iter()
+            .filter(move |(k, _)| k.strip_prefix(TRAILER_PREFIX) == Some(key))
+            .map(|(_, v)| v.clone())
+    }
+    let get = |key: &'static str| values(trailers, key);
+    let (Some(change_id), Some(change_type)) = (get("Change-Id").next(), get("Type").next()) else {
+        return Ok(None);
+    };
+    let change_type: ChangeType =
+        serde_json::from_value(serde_json::Value::String(change_type.clone())).map_err(|_| {
+            Error::Config {
+                message: format!(
+                    "unknown change type in {}Type: {}",
+                    TRAILER_PREFIX, change_type
+                ),
+            }
+        })?;
+    let pairs = |key: &'static str| -> Vec<(String, String)> {
+        get(key)
+            .filter_map(|v| {
+                v.split_once('=')
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+            })
+            .collect()
+    };
+
+    let mut change = TypedChange::new(
+        change_id,
+        change_type,
+        get("Intent").next().unwrap_or_default(),
+    );
+    change.categories = get("Category").map(Into::into).collect();
+    change.breaking = get("Breaking").any(|v| v == "true");
+    change.depends_on = get("Depends-On").collect();
+    change.fields = pairs("Field").into_iter().collect();
+    change.metadata = pairs("Metadata").into_iter().collect::<HashMap<_, _>>();
+    change.dependencies_added = get("Dependency-Added").collect();
+    change.dependencies_removed = get("Dependency-Removed").collect();
+    if let Some(value) = get("Invariants").next() {
+        let (status, checked) = value.split_once(" (").unwrap_or((&value, ""));
+        change.invariants.status =
+            serde_json::from_value(serde_json::Value::String(status.to_string()))
+                .unwrap_or_default();
+        change.invariants.checked = checked
+            .trim_end_matches(')')
+            .split(", ")
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+            .collect();
+    }
+    change.notes = get("Notes").next();
+    if let Some(author) = get("Author").next() {
+        change.author = Some(author);
+    }
+    if let Some(created_at) = get("Created-At").next() {
+        change.created_at = Some(created_at);
+    }
+    Ok(Some(change))
+}
+
+/// One message of a series, as `git format-patch` writes it. `base` goes
+/// on the first patch as git's `base-commit:` line.
+pub fn render(patch: &SeriesPatch, number: usize, total: usize, base: Option<&str>) -> String {
+    let label = if total == 1 {
+        "[PATCH]".to_string()
+    } else {
+        format!("[PATCH {}/{}]", number, total)
+    };
+    let mut out = format!(
+        "From {} {}\nFrom: {} <{}>\nDate: {}\nSubject: {} {}\n\
+         MIME-Version: 1.0\nContent-Type: text/plain; charset=UTF-8\n\
+         Content-Transfer-Encoding: 8bit\n\n",
+        patch.commit_id,
+        MBOX_DATE,
+        patch.author.name,
+        patch.author.email,
+        format_date(patch.author.secs, patch.author.tz_minutes),
+        label,
+        patch.subject(),
+    );
+    let body = patch
+        .description
+        .split_once('\n')
+        .map_or("", |(_, body)| body)
+        .trim();
+    if !body.is_empty() {
+        out.push_str(body);
+        out.push_str("\n\n");
+    }
+    if let Some(change) = &patch.change {
+        for (key, value) in trailers(change) {
+            out.push_str(&format!("{}: {}\n", key, value.replace('\n', "\n ")));
+        }
+    }
+    out.push_str("---\n");
+    out.push_str(&patch.stat);
+    out.push('\n');
+    out.push_str(&patch.diff);
+    if let Some(base) = base {
+        out.push_str(&format!("\nbase-commit: {}\n", base));
+    }
+    out.push_str(&format!("-- \nagentjj {}\n\n", env!("CARGO_PKG_VERSION")));
+    out
+}
+
+/// Headers of a message, with folded lines joined
+fn headers(block: &str) -> HashMap<String, String> {
+    let mut headers: HashMap<String, String> = HashMap::new();
+    let mut last: Option<String> = None;
+    for line in block.lines() {
+        if line.starts_with([' ', '\t']) {
+            if let Some(value) = last.as_ref().and_then(|key| headers.get_mut(key)) {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((key, value)) = line.split_once(':') {
+            let key = key.trim().to_ascii_lowercase();
+            headers.insert(key.clone(), value.trim().to_string());
+            last = Some(key);
+        }
+    }
+    headers
+}
+
+/// Split a message body into its text and its trailing metadata trailers
+fn split_trailers(body: &str) -> (String, Vec<(String, String)>) {
+    let lines: Vec<&str> = body.trim_end().lines().collect();
+    let mut start = lines.len();
+    while start > 0 {
+        let line = lines[start - 1];
+        if line.starts_with(TRAILER_PREFIX) || (line.starts_with(' ') && start > 1) {
+            start -= 1;
+        } else {
+            break;
+        }
+    }
+    // A block of bare continuations is not trailers
+    while start < lines.len() && !lines[start].starts_with(TRAILER_PREFIX) {
+        start += 1;
+    }
+    let mut trailers: Vec<(String, String)> = Vec::new();
+    for line in &lines[start..] {
+        match line.strip_prefix(' ') {
+            Some(rest) => {
+                if let Some((_, value)) = trailers.last_mut() {
+                    value.push('\n');
+                    value.push_str(rest);
+                }
+            }
+            None => {
+                if let Some((key, value)) = line.split_once(": ") {
+                    trailers.push((key.to_string(), value.to_string()));
+                }
+            }
+        }
+    }
+    (lines[..start].join("\n").trim().to_string(), trailers)
+}
+
+/// The author header (`Name <email>`)
+fn parse_author(from: &str, date: &str) -> Result<Author> {
+    let (name, email) = match from.rsplit_once('<') {
+        Some((name, email)) => (
+            name.trim().trim_matches('"').to_string(),
+            email.trim_end_matches('>').trim().to_string(),
+        ),
+        None => (String::new(), from.trim().to_string()),
+    };
+    let (secs, tz_minutes) = parse_date(date).ok_or_else(|| Error::Config {
+        message: format!("invalid Date header in patch: {}", date),
+    })?;
+    Ok(Author {
+        name,
+        email,
+        secs,
+        tz_minutes,
+    })
+}
+
+/// Parse one message of a series
+fn parse_message(message: &str) -> Result<(SeriesPatch, Option<String>)> {
+    let (head, rest) = message.split_once("\n\n").unwrap_or((message, ""));
+    let headers = headers(head);
+    let header = |name: &str| headers.get(name).cloned().unwrap_or_default();
+    let subject = header("subject");
+    // `[PATCH 2/5]`, `[PATCH v2]`, `[RFC PATCH]`
+    let subject = match subject.strip_prefix('[') {
+        Some(tagged) => tagged.split_once("] ").map_or(tagged, |(_, s)| s),
+        None => &subject,
+    }
+    .trim()
+    .to_string();
+
+    // The message ends at `---`; the diff starts at the first `diff --git`
+    let diff_start = rest.find("\ndiff --git ").map_or(
+        if rest.starts_with("diff --git ") {
+            0
+        } else {
+            rest.len()
+        },
+        |i| i + 1,
+    );
+    let (text, diff) = rest.split_at(diff_start);
+    let body = match text.find("\n---\n") {
+        Some(i) => &text[..i],
+        None if text.starts_with("---\n") => "",
+        None => text,
+    };
+    let mut diff = diff;
+    if let Some(i) = diff.rfind("\n-- \n") {
+        diff = &diff[..i + 1];
+    }
+    let mut base = None;
+    let mut diff_lines: Vec<&str> = diff.lines().collect();
+    while let Some(last) = diff_lines.last() {
+        if let Some(commit) = last.strip_prefix("base-commit: ") {
+            base = Some(commit.trim().to_string());
+        } else if !last.is_empty() {
+            break;
+        }
+        diff_lines.pop();
+    }
+
+    let (body, trailers) = split_trailers(body);
+    let description = if body.is_empty() {
+        subject
+    } else {
+        format!("{}\n\n{}", subject, body)
+    };
+    let commit_id = head
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("From "))
+        .and_then(|line| line.split_whitespace().next())
+        .unwrap_or_default()
+        .to_string();
+    let patch = SeriesPatch {
+        commit_id,
+        author: parse_author(&header("from"), &header("date"))?,
+        description,
+        change: change_from_trailers(&trailers)?,
+        diff: if diff_lines.is_empty() {
+            String::new()
+        } else {
+            format!("{}\n", diff_lines.join("\n"))
+        },
+        stat: String::new(),
+    };
+    Ok((patch, base))
+}
+
+/// Parse a series: one or more messages, each starting with git's
+/// `From <commit> Mon Sep 17 00:00:00 2001` line (a patch file or an mbox).
+/// Returns the patches in order and the `base-commit`, when one is given.
+pub fn parse(text: &str) -> Result<(Vec<SeriesPatch>, Option<String>)> {
+    let mut messages: Vec<String> = Vec::new();
+    for line in text.lines() {
+        if line.starts_with("From ") && line.ends_with(MBOX_DATE) {
+            messages.push(String::new());
+        }
+        if let Some(message) = messages.last_mut() {
+            message.push_str(line);
+            message.push('\n');
+        }
+    }
+    if messages.is_empty() {
+        return Err(Error::Config {
+            message: "no patches found (expected git format-patch output)".into(),
+        });
+    }
+    let mut base = None;
+    let mut patches = Vec::new();
+    for message in &messages {
+        let (patch, patch_base) = parse_message(message)?;
+        base = base.or(patch_base);
+        patches.push(patch);
+    }
+    Ok((patches, base))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::change::ChangeCategory;
+    use crate::unified::Side;
+
+    #[test]
+    fn round_trips_a_patch_with_its_metadata() {
+        let mut change = TypedChange::new("abc123", ChangeType::Behavioral, "Retry uploads");
+        change.categories = vec![ChangeCategory::Fix, ChangeCategory::Custom("i18n".into())];
+        change.breaking = true;
+        change.depends_on = vec!["def456".into()];
+        change.fields.insert("cve".into(), "CVE-2026-1".into());
+        change.notes = Some("Backoff is linear.\n\nSee #12.".into());
+        change.invariants.status = InvariantStatus::Passed;
+        change.invariants.checked = vec!["fmt".into(), "test".into()];
+
+        let changes = vec![FileChange {
+            path: "src/up.rs".into(),
+            before: Some(Side {
+                content: b"a\nb\n".to_vec(),
+                mode: "100644",
+            }),
+            after: Some(Side {
+                content: b"a\nc\n".to_vec(),
+                mode: "100644",
+            }),
+        }];
+        let patch = SeriesPatch {
+            commit_id: "0123456789abcdef0123456789abcdef01234567".into(),
+            author: Author {
+                name: "Ada".into(),
+                email: "ada@example.com".into(),
+                secs: 1_792_000_000,
+                tz_minutes: -300,
+            },
+            description: "Retry uploads\n\nThree attempts.\n\nSigned-off-by: Ada".into(),
+            change: Some(change),
+            diff: crate::unified::render(&changes),
+            stat: diffstat(&changes),
+        };
+        let text = render(&patch, 1, 2, Some("feedface"));
+        assert!(text.starts_with(
+            "From 0123456789abcdef0123456789abcdef01234567 Mon Sep 17 00:00:00 2001\n\
+             From: Ada <ada@example.com>\n"
+        ));
+        assert!(text.contains("Subject: [PATCH 1/2] Retry uploads\n"));
+        assert!(
+            text.contains(" src/up.rs | 2 +-\n 1 file changed, 1 insertion(+), 1 deletion(-)\n")
+        );
+        assert!(text.contains("Agentjj-Notes: Backoff is linear.\n \n See #12.\n"));
+        assert!(text.contains("Agentjj-Invariants: passed (fmt, test)\n"));
+        assert_eq!(file_name(1, patch.subject()), "0001-Retry-uploads.patch");
+
+        let (parsed, base) = parse(&format!("{}{}", text, text)).unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(base.as_deref(), Some("feedface"));
+        let back = &parsed[0];
+        assert_eq!(back.author, patch.author);
+        assert_eq!(back.description, patch.description);
+        assert_eq!(back.diff, patch.diff);
+        let change = back.change.as_ref().unwrap();
+        let original = patch.change.as_ref().unwrap();
+        assert_eq!(trailers(change), trailers(original));
+    }
+
+    #[test]
+    fn reads_plain_git_patches_without_metadata() {
+        let text = "From 1111111111111111111111111111111111111111 Mon Sep 17 00:00:00 2001\n\
+                    From: \"Bob B\" <bob@example.com>\n\
+                    Date: Tue, 3 Mar 2026 10:00:00 +0100\n\
+                    Subject: [PATCH v2 3/7] Fix the\n very long subject\n\n\
+                    ---\n a.txt | 1 +\n\n\
+                    diff --git a/a.txt b/a.txt\n--- a/a.txt\n+++ b/a.txt\n@@ -0,0 +1 @@\n+x\n\
+                    -- \n2.43.0\n\n";
+        let (patches, base) = parse(text).unwrap();
+        assert!(base.is_none());
+        let patch = &patches[0];
+        assert_eq!(patch.description, "Fix the very long subject");
+        assert_eq!(patch.author.name, "Bob B");
+        assert_eq!(patch.author.tz_minutes, 60);
+        assert_eq!(
+            crate::repo::utc_timestamp(patch.author.secs),
+            "2026-03-03T09:00:00Z"
+        );
+        assert!(patch.change.is_none());
+        assert!(patch.diff.ends_with("+x\n"));
+        assert!(parse("not a patch").is_err());
+    }
+}
@@ -0,0 +1,657 @@
+// ABOUTME: Builds the `skill` document from the clap command tree, output schemas, and manifest
+// ABOUTME: docs/skill.md supplies the prose; the command reference and per-command guides are generated
+
+use serde::Serialize;
+
+use crate::manifest::{HookEvent, Manifest};
+
+/// Heading in docs/skill.md whose body is replaced by the generated reference
+const REFERENCE_HEADING: &str = "## Command Reference";
+
+/// One flag or positional argument of a command
+#[derive(Debug, Clone, Serialize)]
+pub struct ArgDoc {
+    /// `--to`, `-m, --message`, or `<FROM>` for positionals
+    pub name: String,
+    pub help: String,
+    pub required: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub values: Vec<String>,
+}
+
+/// A command as clap defines it, with its full path (e.g. `env promote`)
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandDoc {
+    pub name: String,
+    pub about: String,
+    pub usage: String,
+    pub args: Vec<ArgDoc>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub subcommands: Vec<String>,
+}
+
+/// Focused guidance for `skill --command <name>`
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandGuide {
+    #[serde(flatten)]
+    pub command: CommandDoc,
+    /// Matching lines from the code blocks in docs/skill.md
+    pub examples: Vec<String>,
+    /// Prose paragraphs from the docs/skill.md sections that show the command
+    pub notes: Vec<String>,
+    /// How to call it for machine-readable output
+    pub json_example: String,
+    /// `schema --type` name describing the JSON output, when there is one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schema_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schema: Option<serde_json::Value>,
+}
+
+/// Every command and subcommand under `root`, depth first
+pub fn registry(root: &clap::Command) -> Vec<CommandDoc> {
+    let mut docs = Vec::new();
+    collect(root, "", &mut docs);
+    docs
+}
+
+fn collect(cmd: &clap::Command, prefix: &str, docs: &mut Vec<CommandDoc>) {
+    for sub in cmd.get_subcommands().filter(|s| !s.is_hide_set()) {
+        let name = if prefix.is_empty() {
+            sub.get_name().to_string()
+        } else {
+            format!("{} {}", prefix, sub.get_name())
+        };
+        let args: Vec<&clap::Arg> = sub
+            .get_arguments()
+            .filter(|a| !a.is_hide_set() && !a.is_global_set())
+            .collect();
+        let mut usage = format!("agentjj {}", name);
+        for arg in &args {
+            usage.push(' ');
+            usage.push_str(&usage_token(arg));
+        }
+        if sub.has_subcommands() {
+            usage.push_str(" <COMMAND>");
+        }
+        docs.push(CommandDoc {
+            name: name.clone(),
+            about: sub.get_about().map(|s| s.to_string()).unwrap_or_default(),
+            usage,
+            args: args.iter().map(|a| arg_doc(a)).collect(),
+            subcommands: sub
+                .get_subcommands()
+                .filter(|s| !s.is_hide_set())
+                .map(|s| s.get_name().to_string())
+                .collect(),
+        });
+        collect(sub, &name, docs);
+    }
+}
+
+pub(crate) fn value_name(arg: &clap::Arg) -> String {
+    arg.get_value_names()
+        .and_then(|names| names.first())
+        .map(|n| n.to_string())
+        .unwrap_or_else(|| arg.get_id().as_str().to_uppercase())
+}
+
+fn flag_name(arg: &clap::Arg) -> String {
+    match (arg.get_short(), arg.get_long()) {
+        (Some(s), Some(l)) => format!("-{}, --{}", s, l),
+        (Some(s), None) => format!("-{}", s),
+        (None, Some(l)) => format!("--{}", l),
+        (None, None) => format!("<{}>", value_name(arg)),
+    }
+}
+
+pub(crate) fn usage_token(arg: &clap::Arg) -> String {
+    let many = arg.get_num_args().is_some_and(|n| n.max_values() > 1);
+    let token = if arg.is_positional() {
+        format!("<{}>{}", value_name(arg), if many { "..." } else { "" })
+    } else {
+        let flag = match arg.get_long() {
+            Some(l) => format!("--{}", l),
+            None => format!("-{}", arg.get_short().unwrap_or('?')),
+        };
+        if arg.get_action().takes_values() {
+            format!("{} <{}>", flag, value_name(arg))
+        } else {
+            flag
+        }
+    };
+    if arg.is_required_set() {
+        token
+    } else {
+        format!("[{}]", token)
+    }
+}
+
+fn arg_doc(arg: &clap::Arg) -> ArgDoc {
+    let default = arg
+        .get_default_values()
+        .iter()
+        .map(|v| v.to_string_lossy().into_owned())
+        .collect::<Vec<_>>()
+        .join(",");
+    ArgDoc {
+        name: if arg.is_positional() {
+            format!("<{}>", value_name(arg))
+        } else {
+            flag_name(arg)
+        },
+        help: arg.get_help().map(|s| s.to_string()).unwrap_or_default(),
+        required: arg.is_required_set(),
+        default: (!default.is_empty()).then_some(default),
+        // Switches report true/false as possible values; only list real choices
+        values: if arg.get_action().takes_values() {
+            arg.get_possible_values()
+                .iter()
+                .map(|v| v.get_name().to_string())
+                .collect()
+        } else {
+            Vec::new()
+        },
+    }
+}
+
+/// Find a command by its full path; `env-promote` and `env promote` both work
+pub fn find<'a>(registry: &'a [CommandDoc], name: &str) -> Option<&'a CommandDoc> {
+    let wanted: Vec<&str> = name
+        .split(|c: char| c.is_whitespace() || c == '-' || c == '/')
+        .filter(|s| !s.is_empty())
+        .collect();
+    registry.iter().find(|c| c.name == name.trim()).or_else(|| {
+        registry
+            .iter()
+            .find(|c| c.name.split(' ').eq(wanted.iter().copied()))
+    })
+}
+
+/// The `schema --type` entry describing a command's JSON output
+pub fn schema_for(command: &str) -> Option<&'static str> {
+    match command {
+        "status" => Some("status"),
+        "symbol" => Some("symbol"),
+        "context" => Some("context"),
+        "orient" => Some("orient"),
+        "capabilities" => Some("capabilities"),
+        "stats" => Some("stats"),
+        "todos" => Some("todos"),
+        "dupes" => Some("dupes"),
+        "apply" | "edit" => Some("apply_result"),
+        c if c.starts_with("approvals") => Some("approval"),
+        _ => None,
+    }
+}
+
+/// The command reference table, generated from the registry
+pub fn render_reference(registry: &[CommandDoc]) -> String {
+    let mut out = String::from("| Command | Description |\n|---------|-------------|\n");
+    for cmd in registry {
+        let usage = cmd.usage.trim_start_matches("agentjj ").replace('|', "\\|");
+        out.push_str(&format!(
+            "| `{}` | {} |\n",
+            usage,
+            cmd.about.replace('|', "\\|")
+        ));
+    }
+    out.push_str(
+        "\nAll commands support `--json` for structured output. \
+         `agentjj skill --command <name>` shows flags, examples, and the output schema for one command.\n",
+    );
+    out
+}
+
+/// What the manifest in the current repository configures, for agents
+/// that land in it cold
+pub fn render_manifest(manifest: &Manifest) -> String {
+    let mut out = format!("## This Repository\n\n**{}**", manifest.repo.name);
+    if !manifest.repo.description.is_empty() {
+        out.push_str(&format!(" - {}", manifest.repo.description));
+    }
+    out.push_str("\n\n");
+    if !manifest.repo.languages.is_empty() {
+        out.push_str(&format!(
+            "Languages: {}\n\n",
+            manifest.repo.languages.join(", ")
+        ));
+    }
+
+    if !manifest.invariants.is_empty() {
+        out.push_str("Invariants (`validate`, `test run --invariant`):\n");
+        let mut names: Vec<&String> = manifest.invariants.keys().collect();
+        names.sort();
+        for name in names {
+            let invariant = &manifest.invariants[name];
+            out.push_str(&format!("- `{}`: `{}`", name, invariant.command()));
+            if let Some(env) = invariant.env().filter(|e| !e.is_empty()) {
+                let vars: Vec<&str> = env.keys().map(String::as_str).collect();
+                out.push_str(&format!(" (env: {})", vars.join(", ")));
+            }
+            out.push('\n');
+        }
+        out.push('\n');
+    }
+
+    let hooks: Vec<String> = HookEvent::ALL
+        .iter()
+        .filter_map(|e| {
+            let cmds = manifest.hooks.for_event(*e);
+            (!cmds.is_empty()).then(|| format!("- {}: `{}`", e.as_str(), cmds.join("`, `")))
+        })
+        .collect();
+    if !hooks.is_empty() {
+        out.push_str("Hooks:\n");
+        out.push_str(&hooks.join("\n"));
+        out.push_str("\n\n");
+    }
+
+    let perms = &manifest.permissions;
+    if perms.default_mode == crate::manifest::AccessMode::ReadOnly {
+        out.push_str(
+            "**Read-only**: the manifest sets `default_mode = \"read-only\"`, so commands that change \
+             the repository fail with `read_only_mode` (exit 5). Stick to queries, dry runs, and plans.\n\n",
+        );
+    }
+    if manifest.security.scan_prompts {
+        let consequence = match manifest.security.prompt_policy {
+            crate::manifest::PromptPolicy::Block => {
+                "`commit` refuses them with `prompt_injection` (exit 7)"
+            }
+            crate::manifest::PromptPolicy::Warn => "they are reported as warnings",
+        };
+        out.push_str(&format!(
+            "**Prompt scan**: lines added to markdown, text, and HTML are checked for prompt-injection \
+             patterns; {}.\n\n",
+            consequence
+        ));
+    }
+    let rules = [
+        ("deny_change", &perms.deny_change),
+        ("ask_change", &perms.ask_change),
+        ("allow_change", &perms.allow_change),
+        ("deny_push", &perms.deny_push),
+        ("allow_push", &perms.allow_push),
+    ];
+    let rules: Vec<String> = rules
+        .iter()
+        .filter(|(_, paths)| !paths.is_empty())
+        .map(|(key, paths)| format!("- {}: `{}`", key, paths.join("`, `")))
+        .collect();
+    if !rules.is_empty() {
+        out.push_str("Permissions:\n");
+        out.push_str(&rules.join("\n"));
+        out.push_str("\n\n");
+    }
+
+    let custom = crate::custom::list(&manifest.commands.custom);
+    if !custom.is_empty() {
+        out.push_str("Custom commands (`agentjj --json run` lists them with output schemas):\n");
+        for cmd in custom {
+            out.push_str(&format!("- `{}`", cmd.usage));
+            if !cmd.description.is_empty() {
+                out.push_str(&format!(": {}", cmd.description));
+            }
+            out.push('\n');
+        }
+        out.push('\n');
+    }
+
+    if !manifest.environments.is_empty() {
+        out.push_str("Environments (`env list`, `env promote`):\n");
+        let mut names: Vec<&String> = manifest.environments.keys().collect();
+        names.sort();
+        for name in names {
+            let env = &manifest.environments[name];
+            out.push_str(&format!("- `{}` tracks `{}`", name, env.bookmark()));
+            if !env.promote_from().is_empty() {
+                out.push_str(&format!(", from {}", env.promote_from().join(", ")));
+            }
+            if env.is_frozen() {
+                out.push_str(", frozen");
+            }
+            out.push('\n');
+        }
+        out.push('\n');
+    }
+
+    if !manifest.categories.is_empty() {
+        out.push_str("Change categories beyond the built-in ones (`-c name`):\n");
+        for (name, description) in &manifest.categories {
+            out.push_str(&format!("- `{}`: {}\n", name, description));
+        }
+        out.push('\n');
+    }
+
+    if !manifest.change_templates.is_empty() {
+        out.push_str("Change templates (`commit`/`change set` need these `--field name=value`):\n");
+        let mut names: Vec<&String> = manifest.change_templates.keys().collect();
+        names.sort();
+        for name in names {
+            let template = &manifest.change_templates[name];
+            let fields: Vec<String> = template
+                .required
+                .iter()
+                .cloned()
+                .chain(template.any_of.iter().map(|group| group.join(" or ")))
+                .collect();
+            out.push_str(&format!("- `{}`: {}\n", name, fields.join(", ")));
+        }
+        out.push('\n');
+    }
+
+    if !manifest.fixers.is_empty() {
+        out.push_str("Fixers (`validate --fix` runs them on changed files):\n");
+        let mut names: Vec<&String> = manifest.fixers.keys().collect();
+        names.sort();
+        for name in names {
+            let fixer = &manifest.fixers[name];
+            out.push_str(&format!("- `{}`: `{}`", name, fixer.cmd));
+            if !fixer.files.is_empty() {
+                out.push_str(&format!(" on {}", fixer.files.join(", ")));
+            }
+            out.push('\n');
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// The full skill document: `doc` with its command reference regenerated
+/// and, when given, a section describing the repository's manifest
+pub fn render(
+    doc: &str,
+    registry: &[CommandDoc],
+    schemas: &[String],
+    manifest: Option<&Manifest>,
+) -> String {
+    let mut generated = render_reference(registry);
+    if !schemas.is_empty() {
+        generated.push_str(&format!(
+            "\nOutput schemas (`agentjj schema --type <name>`): {}\n",
+            schemas.join(", ")
+        ));
+    }
+    if let Some(manifest) = manifest {
+        generated.push('\n');
+        generated.push_str(&render_manifest(manifest));
+    }
+
+    let Some(start) = doc.find(REFERENCE_HEADING) else {
+        return format!("{}\n{}\n\n{}", doc.trim_end(), REFERENCE_HEADING, generated);
+    };
+    let body = start + REFERENCE_HEADING.len();
+    let end = doc[body..]
+        .find("\n## ")
+        .map(|i| body + i + 1)
+        .unwrap_or(doc.len());
+    let mut out = String::with_capacity(doc.len() + generated.len());
+    out.push_str(&doc[..body]);
+    out.push_str("\n\n");
+    out.push_str(generated.trim_end());
+    out.push_str("\n\n");
+    out.push_str(&doc[end..]);
+    out
+}
+
+/// Repo-local file pointing agents at the skill (`init --with-skill`)
+pub const POINTER_PATH: &str = "AGENTS.md";
+
+/// Heading of the pointer section; its presence means the file already has one
+const POINTER_HEADING: &str = "## agentjj";
+
+/// The pointer section: short on purpose, the full document comes from
+/// `agentjj skill` so it never goes stale
+pub fn render_pointer(repo_name: &str) -> String {
+    format!(
+        "{}\n\n\
+         `{}` is worked on with agentjj. Before changing anything:\n\n\
+         ```bash\n\
+         agentjj skill                 # Full usage guide, generated for this version\n\
+         agentjj orient --json         # Repository state, manifest, and next steps\n\
+         agentjj start --task \"...\"    # Session, preflight, checkpoint, and plan\n\
+         ```\n\n\
+         Rules for agents live in `.agent/manifest.toml`.\n",
+        POINTER_HEADING, repo_name
+    )
+}
+
+/// `existing` with the pointer section appended, or `None` when it already has one
+pub fn add_pointer(existing: &str, repo_name: &str) -> Option<String> {
+    if existing.lines().any(|l| l.trim_end() == POINTER_HEADING) {
+        return None;
+    }
+    if existing.trim().is_empty() {
+        return Some(format!("# Agents\n\n{}", render_pointer(repo_name)));
+    }
+    Some(format!(
+        "{}\n\n{}",
+        existing.trim_end(),
+        render_pointer(repo_name)
+    ))
+}
+
+/// Whether a line from a code block invokes `command`
+fn invokes(line: &str, command: &str) -> bool {
+    let mut words = line
+        .split_whitespace()
+        .skip_while(|w| *w != "agentjj")
+        .skip(1)
+        .filter(|w| !w.starts_with("--json"));
+    command.split(' ').all(|part| words.next() == Some(part))
+}
+
+/// Keep the prose of a doc section that showed the command
+fn flush_notes(section: &mut Vec<&str>, shows: bool, notes: &mut Vec<String>) {
+    if shows {
+        let prose = section.join("\n");
+        for para in prose.split("\n\n") {
+            let para = para.trim();
+            if !para.is_empty() && !notes.iter().any(|n| n == para) {
+                notes.push(para.to_string());
+            }
+        }
+    }
+    section.clear();
+}
+
+/// Per-command guidance: the registry entry plus examples and notes
+/// lifted from `doc`
+pub fn guide(doc: &str, command: &CommandDoc, schemas: &serde_json::Value) -> CommandGuide {
+    let mut examples = Vec::new();
+    let mut notes = Vec::new();
+    let mut in_code = false;
+    let mut section: Vec<&str> = Vec::new();
+    let mut section_shows = false;
+
+    for line in doc.lines() {
+        if line.starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            if invokes(line, &command.name) {
+                examples.push(line.trim().to_string());
+                section_shows = true;
+            }
+            continue;
+        }
+        if line.starts_with('#') {
+            flush_notes(&mut section, section_shows, &mut notes);
+            section_shows = false;
+            continue;
+        }
+        // Tables are the static reference we replace; skip them
+        if !line.starts_with('|') {
+            section.push(line);
+        }
+    }
+    flush_notes(&mut section, section_shows, &mut notes);
+
+    let schema_name = schema_for(&command.name);
+    let required: Vec<String> = command
+        .args
+        .iter()
+        .filter(|a| a.required)
+        .map(|a| a.name.split(", ").last().unwrap_or(&a.name).to_string())
+        .collect();
+    let mut json_example = format!("agentjj --json {}", command.name);
+    for arg in required {
+        json_example.push(' ');
+        json_example.push_str(&arg);
+        if arg.starts_with("--") {
+            json_example.push_str(" <value>");
+        }
+    }
+
+    CommandGuide {
+        command: command.clone(),
+        examples,
+        notes,
+        json_example,
+        schema_name: schema_name.map(str::to_string),
+        schema: schema_name.and_then(|n| schemas.get(n).cloned()),
+    }
+}
+
+/// Markdown for one command's guide
+pub fn render_guide(guide: &CommandGuide) -> String {
+    let cmd = &guide.command;
+    let mut out = format!("# agentjj {}\n\n", cmd.name);
+    if !cmd.about.is_empty() {
+        out.push_str(&format!("{}\n\n", cmd.about));
+    }
+    out.push_str(&format!("```bash\n{}\n```\n\n", cmd.usage));
+
+    if !cmd.args.is_empty() {
+        out.push_str("## Arguments\n\n");
+        for arg in &cmd.args {
+            out.push_str(&format!("- `{}`", arg.name));
+            if arg.required {
+                out.push_str(" (required)");
+            }
+            if !arg.help.is_empty() {
+                out.push_str(&format!(": {}", arg.help.replace('\n', " ")));
+            }
+            if !arg.values.is_empty() {
+                out.push_str(&format!(" [values: {}]", arg.values.join(", ")));
+            }
+            if let Some(default) = &arg.default {
+                out.push_str(&format!(" [default: {}]", default));
+            }
+            out.push('\n');
+        }
+        out.push('\n');
+    }
+
+    if !cmd.subcommands.is_empty() {
+        out.push_str("## Subcommands\n\n");
+        for sub in &cmd.subcommands {
+            out.push_str(&format!("- `agentjj {} {}`\n", cmd.name, sub));
+        }
+        out.push('\n');
+    }
+
+    if !guide.examples.is_empty() {
+        out.push_str("## Examples\n\n```bash\n");
+        for example in &guide.examples {
+            out.push_str(example);
+            out.push('\n');
+        }
+        out.push_str("```\n\n");
+    }
+
+    if !guide.notes.is_empty() {
+        out.push_str("## Notes\n\n");
+        out.push_str(&guide.notes.join("\n\n"));
+        out.push_str("\n\n");
+    }
+
+    out.push_str(&format!(
+        "## JSON\n\n```bash\n{}\n```\n",
+        guide.json_example
+    ));
+    if let (Some(name), Some(schema)) = (&guide.schema_name, &guide.schema) {
+        out.push_str(&format!(
+            "\nOutput schema (`agentjj schema --type {}`):\n\n```json\n{}\n```\n",
+            name,
+            serde_json::to_string_pretty(schema).unwrap_or_default()
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{Arg, ArgAction, Command};
+
+    fn cli() -> Command {
+        Command::new("agentjj")
+            .arg(
+                Arg::new("json")
+                    .long("json")
+                    .global(true)
+                    .action(ArgAction::SetTrue),
+            )
+            .subcommand(Command::new("status").about("Show current state"))
+            .subcommand(
+                Command::new("env")
+                    .about("Deployment environments")
+                    .subcommand(
+                        Command::new("promote")
+                            .about("Move an environment's bookmark")
+                            .arg(Arg::new("from").required(true))
+                            .arg(Arg::new("to").long("to").required(true))
+                            .arg(
+                                Arg::new("dry_run")
+                                    .long("dry-run")
+                                    .action(ArgAction::SetTrue)
+                                    .help("Check policy without moving anything"),
+                            ),
+                    ),
+            )
+    }
+
+    const DOC: &str = "# agentjj\n\n### Environments\n\n```bash\nagentjj env promote staging --to prod\nagentjj status\n```\n\nFrozen environments fail with exit 7.\n\n## Command Reference\n\n| Command | Description |\n| `stale` | Gone |\n\n## Pro Tips\n\nUse --json.\n";
+
+    #[test]
+    fn reference_is_generated_from_the_command_tree() {
+        let registry = registry(&cli());
+        let names: Vec<&str> = registry.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["status", "env", "env promote"]);
+        assert_eq!(
+            registry[2].usage,
+            "agentjj env promote <FROM> --to <TO> [--dry-run]"
+        );
+        assert_eq!(registry[1].subcommands, ["promote"]);
+
+        let doc = render(DOC, &registry, &["status".into()], None);
+        assert!(doc.contains("| `env promote <FROM> --to <TO> [--dry-run]` |"));
+        assert!(!doc.contains("`stale`"));
+        assert!(doc.contains("## Pro Tips\n\nUse --json."));
+        assert!(doc.contains("schema --type <name>`): status"));
+    }
+
+    #[test]
+    fn guide_pulls_examples_and_notes_for_one_command() {
+        let registry = registry(&cli());
+        let promote = find(&registry, "env-promote").unwrap();
+        let guide = guide(DOC, promote, &serde_json::json!({}));
+        assert_eq!(guide.examples, ["agentjj env promote staging --to prod"]);
+        assert_eq!(guide.notes, ["Frozen environments fail with exit 7."]);
+        assert_eq!(
+            guide.json_example,
+            "agentjj --json env promote <FROM> --to <value>"
+        );
+        assert!(guide.schema.is_none());
+
+        let schemas = serde_json::json!({ "status": { "type": "object" } });
+        let status = super::guide(DOC, find(&registry, "status").unwrap(), &schemas);
+        assert_eq!(status.schema_name.as_deref(), Some("status"));
+        assert!(render_guide(&status).contains("schema --type status"));
+    }
+}
@@ -0,0 +1,153 @@
+// ABOUTME: Slices of files (a symbol's body or lines around a line) with their spans and hashes
+// ABOUTME: Lets agents read only what they need and later assert the pre-image before editing
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::symbols::Symbol;
+
+/// A contiguous range of whole lines from a file
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Snippet {
+    pub content: String,
+    /// First line, 1-based
+    pub start_line: usize,
+    /// Last line, 1-based and inclusive
+    pub end_line: usize,
+    /// Byte offset of the first line
+    pub start_byte: usize,
+    /// Byte offset just past the last line (including its newline)
+    pub end_byte: usize,
+    /// Innermost symbol containing the slice, if any
+    pub symbol: Option<String>,
+    /// sha256 of `content`
+    pub hash: String,
+}
+
+/// Hex sha256 of some content, as used for edit preconditions
+pub fn content_hash(content: impl AsRef<[u8]>) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(content.as_ref());
+    hex::encode(hasher.finalize())
+}
+
+/// Lines `start_line..=end_line` (1-based) of `source`, clamped to the file.
+/// Returns None when `start_line` is past the end.
+pub fn line_range(source: &str, start_line: usize, end_line: usize) -> Option<Snippet> {
+    let start_line = start_line.max(1);
+    let mut offset = 0;
+    let mut start_byte = None;
+    let mut end_byte = 0;
+    let mut last_line = 0;
+
+    for (index, line) in source.split_inclusive('\n').enumerate() {
+        let line_no = index + 1;
+        if line_no == start_line {
+            start_byte = Some(offset);
+        }
+        offset += line.len();
+        if line_no >= start_line {
+            end_byte = offset;
+            last_line = line_no;
+        }
+        if line_no >= end_line {
+            break;
+        }
+    }
+
+    let start_byte = start_byte?;
+    let content = source[start_byte..end_byte].to_string();
+    Some(Snippet {
+        hash: content_hash(&content),
+        content,
+        start_line,
+        end_line: last_line,
+        start_byte,
+        end_byte,
+        symbol: None,
+    })
+}
+
+/// The smallest symbol whose span contains `line`
+pub fn enclosing_symbol(symbols: &[Symbol], line: usize) -> Option<&Symbol> {
+    symbols
+        .iter()
+        .filter(|s| s.start_line <= line && line <= s.end_line)
+        .min_by_key(|s| s.end_line - s.start_line)
+}
+
+/// The full body of the named symbol
+pub fn symbol_snippet(source: &str, symbols: &[Symbol], name: &str) -> Option<Snippet> {
+    let symbol = symbols.iter().find(|s| s.name == name)?;
+    let mut snippet = line_range(source, symbol.start_line, symbol.end_line)?;
+    snippet.symbol = Some(symbol.name.clone());
+    Some(snippet)
+}
+
+/// `context` lines either side of `line`, annotated with the symbol
+/// enclosing `line`
+pub fn around_line(
+    source: &str,
+    symbols: &[Symbol],
+    line: usize,
+    context: usize,
+) -> Option<Snippet> {
+    let mut snippet = line_range(source, line.saturating_sub(context), line + context)?;
+    if line > snippet.end_line {
+        return None;
+    }
+    snippet.symbol = enclosing_symbol(symbols, line).map(|s| s.name.clone());
+    Some(snippet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbols::{extract_symbols, SupportedLanguage};
+
+    const SOURCE: &str = "fn outer() {\n    let a = 1;\n}\n\nfn other() {\n    let b = 2;\n}\n";
+
+    #[test]
+    fn line_range_reports_spans() {
+        let snippet = line_range(SOURCE, 2, 3).unwrap();
+        assert_eq!(snippet.content, "    let a = 1;\n}\n");
+        assert_eq!((snippet.start_line, snippet.end_line), (2, 3));
+        assert_eq!(
+            &SOURCE[snippet.start_byte..snippet.end_byte],
+            snippet.content
+        );
+        assert_eq!(snippet.hash, content_hash(&snippet.content));
+    }
+
+    #[test]
+    fn line_range_clamps_to_file() {
+        let snippet = line_range(SOURCE, 6, 100).unwrap();
+        assert_eq!(snippet.end_line, 7);
+        assert!(line_range(SOURCE, 50, 60).is_none());
+    }
+
+    #[test]
+    fn symbol_and_around_line() {
+        let symbols = extract_symbols(SOURCE, SupportedLanguage::Rust).unwrap();
+
+        let snippet = symbol_snippet(SOURCE, &symbols, "other").unwrap();
+        assert_eq!((snippet.start_line, snippet.end_line), (5, 7));
+        assert_eq!(snippet.symbol.as_deref(), Some("other"));
+
+        let around = around_line(SOURCE, &symbols, 2, 1).unwrap();
+        assert_eq!((around.start_line, around.end_line), (1, 3));
+        assert_eq!(around.symbol.as_deref(), Some("outer"));
+
+        let between = around_line(SOURCE, &symbols, 4, 0).unwrap();
+        assert_eq!(between.symbol, None);
+        assert!(around_line(SOURCE, &symbols, 40, 5).is_none());
+    }
+
+    #[test]
+    fn content_hash_is_sha256_hex() {
+        assert_eq!(
+            content_hash(""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+}
@@ -0,0 +1,103 @@
+// ABOUTME: Spill-to-file for oversized JSON results (.agent/out/<id>.json)
+// ABOUTME: Commands print a stub with the path, size, and a summary instead of the payload
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+use crate::error::Result;
+
+/// Where spilled payloads are written, relative to the repo root
+pub const DEFAULT_DIR: &str = ".agent/out";
+
+/// Strings up to this many bytes are kept verbatim in a summary
+const SUMMARY_STRING_BYTES: usize = 200;
+
+/// What a command prints in place of a payload that was too large
+#[derive(Debug, Clone, Serialize)]
+pub struct Stub {
+    pub spilled: bool,
+    /// Repo-relative path of the full payload
+    pub path: String,
+    /// Size of the full payload in bytes
+    pub bytes: usize,
+    /// The `output.max_output_bytes` limit it exceeded
+    pub max_output_bytes: usize,
+    pub summary: serde_json::Value,
+}
+
+/// A compact view of a result: scalars and short strings as-is, arrays as
+/// `{"items": n}`, long strings as `{"bytes": n}`, objects summarized
+/// field by field
+pub fn summarize(value: &serde_json::Value) -> serde_json::Value {
+    use serde_json::{json, Value};
+    match value {
+        Value::Array(items) => json!({ "items": items.len() }),
+        Value::String(s) if s.len() > SUMMARY_STRING_BYTES => json!({ "bytes": s.len() }),
+        Value::Object(fields) => fields
+            .iter()
+            .map(|(key, value)| (key.clone(), summarize(value)))
+            .collect::<serde_json::Map<_, _>>()
+            .into(),
+        other => other.clone(),
+    }
+}
+
+/// Write `payload` (the serialized `value`) under `.agent/out/` and return
+/// the stub to print. The file name is `<command>-<content hash>`, so the
+/// same output lands in the same file.
+pub fn spill(
+    repo_root: impl AsRef<Path>,
+    command: &str,
+    payload: &str,
+    value: &serde_json::Value,
+    max_output_bytes: usize,
+) -> Result<Stub> {
+    let dir = repo_root.as_ref().join(DEFAULT_DIR);
+    crate::agent_dir::ensure_private(&dir)?;
+    let hash = hex::encode(Sha256::digest(payload.as_bytes()));
+    let name = format!("{}-{}.json", command.replace(' ', "-"), &hash[..12]);
+    std::fs::write(dir.join(&name), payload)?;
+
+    Ok(Stub {
+        spilled: true,
+        path: format!("{}/{}", DEFAULT_DIR, name),
+        bytes: payload.len(),
+        max_output_bytes,
+        summary: summarize(value),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spills_payload_and_summarizes_it() {
+        let tmp = tempfile::tempdir().unwrap();
+        let value = serde_json::json!({
+            "files": [{ "path": "a.rs" }, { "path": "b.rs" }],
+            "raw_diff": "x".repeat(500),
+            "summary": { "read": 2, "failed": 0 },
+            "at": null,
+        });
+        let payload = serde_json::to_string_pretty(&value).unwrap();
+
+        let stub = spill(tmp.path(), "bulk read", &payload, &value, 100).unwrap();
+        assert!(stub.path.starts_with(".agent/out/bulk-read-"));
+        assert_eq!(stub.bytes, payload.len());
+        assert_eq!(
+            std::fs::read_to_string(tmp.path().join(&stub.path)).unwrap(),
+            payload
+        );
+        assert_eq!(
+            stub.summary,
+            serde_json::json!({
+                "files": { "items": 2 },
+                "raw_diff": { "bytes": 500 },
+                "summary": { "read": 2, "failed": 0 },
+                "at": null,
+            })
+        );
+    }
+}
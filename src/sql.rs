@@ -0,0 +1,471 @@
+// ABOUTME: SQL schema structure (CREATE TABLE/INDEX/VIEW, ALTER) from a light statement scanner
+// ABOUTME: Detects destructive schema operations (DROP TABLE/COLUMN, TRUNCATE) between file versions
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+use crate::symbols::{Symbol, SymbolKind};
+
+/// One `;`-terminated statement with its 0-based line span
+struct Statement {
+    text: String,
+    start: usize,
+    end: usize,
+}
+
+impl Statement {
+    /// Uppercased words, with quoting removed from identifiers
+    fn words(&self) -> Vec<String> {
+        self.text
+            .split(|c: char| c.is_whitespace() || c == '(' || c == ')' || c == ',')
+            .filter(|w| !w.is_empty())
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Whitespace-normalized text, for comparing statements across versions
+    fn normalized(&self) -> String {
+        self.text.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+}
+
+/// Split SQL into statements, skipping `--` and `/* */` comments and not
+/// splitting on `;` inside quotes
+fn statements(source: &str) -> Vec<Statement> {
+    let mut statements = Vec::new();
+    let mut text = String::new();
+    let mut start = None;
+    let mut line = 0;
+    let mut chars = source.chars().peekable();
+    let mut quote: Option<char> = None;
+
+    while let Some(c) = chars.next() {
+        if let Some(q) = quote {
+            text.push(c);
+            if c == '\n' {
+                line += 1;
+            }
+            if c == q {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            '-' if chars.peek() == Some(&'-') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        line += 1;
+                        text.push('\n');
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = ' ';
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        line += 1;
+                    }
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+                text.push(' ');
+            }
+            ';' => {
+                if let Some(start) = start.take() {
+                    statements.push(Statement {
+                        text: std::mem::take(&mut text).trim().to_string(),
+                        start,
+                        end: line,
+                    });
+                }
+                text.clear();
+            }
+            _ => {
+                if c == '\n' {
+                    line += 1;
+                } else if !c.is_whitespace() && start.is_none() {
+                    start = Some(line);
+                }
+                if matches!(c, '\'' | '"' | '`') {
+                    quote = Some(c);
+                }
+                text.push(c);
+            }
+        }
+    }
+    if let Some(start) = start {
+        statements.push(Statement {
+            text: text.trim().to_string(),
+            start,
+            end: line,
+        });
+    }
+    statements
+}
+
+/// An identifier without quoting (`"users"`, `` `users` ``, `[users]`)
+fn ident(word: &str) -> String {
+    word.trim_matches(['"', '`', '[', ']', ';']).to_string()
+}
+
+/// Skip optional keywords (e.g. `IF NOT EXISTS`) from `at`, returning the next index
+fn skip(words: &[String], mut at: usize, optional: &[&str]) -> usize {
+    while words
+        .get(at)
+        .is_some_and(|w| optional.contains(&w.to_uppercase().as_str()))
+    {
+        at += 1;
+    }
+    at
+}
+
+/// The object a statement creates/alters/drops: (verb, object type, name)
+fn classify(words: &[String]) -> Option<(String, String, String)> {
+    let upper: Vec<String> = words.iter().map(|w| w.to_uppercase()).collect();
+    let verb = upper.first()?.clone();
+    let at = skip(
+        &upper,
+        1,
+        &[
+            "OR",
+            "REPLACE",
+            "TEMP",
+            "TEMPORARY",
+            "UNLOGGED",
+            "UNIQUE",
+            "MATERIALIZED",
+        ],
+    );
+    let object = match verb.as_str() {
+        "CREATE" | "ALTER" | "DROP" => upper.get(at)?.clone(),
+        "TRUNCATE" => "TABLE".to_string(),
+        _ => return None,
+    };
+    let name_at = if verb == "TRUNCATE" {
+        skip(&upper, 1, &["TABLE", "ONLY"])
+    } else {
+        skip(
+            &upper,
+            at + 1,
+            &["IF", "NOT", "EXISTS", "ONLY", "CONCURRENTLY"],
+        )
+    };
+    Some((verb, object, ident(words.get(name_at)?)))
+}
+
+/// `(name, type, line offset)` for each column in a CREATE TABLE body
+fn columns(statement: &Statement) -> Vec<(String, String, usize)> {
+    let Some(open) = statement.text.find('(') else {
+        return Vec::new();
+    };
+    let body = &statement.text[open + 1..];
+    let mut columns = Vec::new();
+    let mut depth = 0;
+    let mut entry = String::new();
+    let mut entry_line = statement.text[..open + 1].matches('\n').count();
+    let mut line = entry_line;
+
+    let mut finish = |entry: &str, entry_line: usize| {
+        let mut words = entry.split_whitespace();
+        let Some(first) = words.next() else {
+            return;
+        };
+        let constraint = [
+            "CONSTRAINT",
+            "PRIMARY",
+            "UNIQUE",
+            "FOREIGN",
+            "CHECK",
+            "KEY",
+            "INDEX",
+            "EXCLUDE",
+        ];
+        if !constraint.contains(&first.to_uppercase().as_str()) {
+            let ty = words.next().unwrap_or("").to_string();
+            columns.push((ident(first), ty, entry_line));
+        }
+    };
+
+    for c in body.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => {
+                finish(&entry, entry_line);
+                return columns;
+            }
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                finish(&entry, entry_line);
+                entry.clear();
+                continue;
+            }
+            '\n' => line += 1,
+            _ => {}
+        }
+        if entry.trim().is_empty() && !c.is_whitespace() {
+            entry_line = line;
+        }
+        entry.push(c);
+    }
+    columns
+}
+
+fn symbol(name: String, kind: SymbolKind, signature: &str, start: usize, end: usize) -> Symbol {
+    Symbol {
+        name,
+        kind,
+        signature: Some(signature.lines().next().unwrap_or("").trim().to_string()),
+        docstring: None,
+        start_line: start + 1,
+        end_line: end + 1,
+        children: Vec::new(),
+    }
+}
+
+/// Tables (with their columns as `table.column`), indexes, views, and ALTER
+/// statements of a SQL file
+pub fn sql_symbols(source: &str) -> Vec<Symbol> {
+    let mut symbols = Vec::new();
+    for statement in statements(source) {
+        let Some((verb, object, name)) = classify(&statement.words()) else {
+            continue;
+        };
+        let kind = match (verb.as_str(), object.as_str()) {
+            ("CREATE", "TABLE") => SymbolKind::Table,
+            ("CREATE", "INDEX") => SymbolKind::Index,
+            ("CREATE", "VIEW") => SymbolKind::View,
+            ("ALTER", _) => SymbolKind::Alter,
+            _ => continue,
+        };
+        symbols.push(symbol(
+            name.clone(),
+            kind,
+            &statement.text,
+            statement.start,
+            statement.end,
+        ));
+        if kind == SymbolKind::Table {
+            for (column, ty, offset) in columns(&statement) {
+                let line = statement.start + offset;
+                symbols.push(symbol(
+                    format!("{}.{}", name, column),
+                    SymbolKind::Column,
+                    &format!("{} {}", column, ty),
+                    line,
+                    line,
+                ));
+            }
+        }
+    }
+    symbols
+}
+
+/// A schema operation that can lose data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SchemaOp {
+    DropTable,
+    DropColumn,
+    DropView,
+    DropSchema,
+    Truncate,
+    /// A CREATE TABLE disappeared from a declarative schema file
+    RemovedTable,
+    /// A column disappeared from a CREATE TABLE
+    RemovedColumn,
+}
+
+/// A destructive operation found in a change to a SQL file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DestructiveOp {
+    pub file: String,
+    pub operation: SchemaOp,
+    /// Table, `table.column`, view, or schema
+    pub object: String,
+    /// 1-based line in the new version (the old one for removals)
+    pub line: usize,
+    pub statement: String,
+}
+
+/// Declarative view of a file: table -> columns
+fn tables(source: &str) -> BTreeMap<String, (usize, BTreeSet<String>)> {
+    statements(source)
+        .into_iter()
+        .filter_map(|statement| {
+            let (verb, object, name) = classify(&statement.words())?;
+            (verb == "CREATE" && object == "TABLE").then(|| {
+                let cols = columns(&statement).into_iter().map(|c| c.0).collect();
+                (name, (statement.start + 1, cols))
+            })
+        })
+        .collect()
+}
+
+/// Destructive operations introduced by changing `file` from `before` to
+/// `after`: new DROP/TRUNCATE statements, plus tables or columns removed
+/// from CREATE TABLE definitions. Deleting the file itself is not reported.
+pub fn destructive_ops(
+    file: &str,
+    before: Option<&str>,
+    after: Option<&str>,
+) -> Vec<DestructiveOp> {
+    let Some(after) = after else {
+        return Vec::new();
+    };
+    let existing: HashSet<String> = before
+        .map(|b| statements(b).iter().map(Statement::normalized).collect())
+        .unwrap_or_default();
+
+    let mut ops = Vec::new();
+    for statement in statements(after) {
+        if existing.contains(&statement.normalized()) {
+            continue;
+        }
+        let words = statement.words();
+        let Some((verb, object, name)) = classify(&words) else {
+            continue;
+        };
+        let op = |operation, object: String| DestructiveOp {
+            file: file.to_string(),
+            operation,
+            object,
+            line: statement.start + 1,
+            statement: statement.normalized(),
+        };
+        match (verb.as_str(), object.as_str()) {
+            ("DROP", "TABLE") => ops.push(op(SchemaOp::DropTable, name)),
+            ("DROP", "VIEW") => ops.push(op(SchemaOp::DropView, name)),
+            ("DROP", "SCHEMA" | "DATABASE") => ops.push(op(SchemaOp::DropSchema, name)),
+            ("TRUNCATE", _) => ops.push(op(SchemaOp::Truncate, name)),
+            ("ALTER", "TABLE") => {
+                // ALTER TABLE t DROP [COLUMN] [IF EXISTS] c, DROP c2
+                let upper: Vec<String> = words.iter().map(|w| w.to_uppercase()).collect();
+                for (i, word) in upper.iter().enumerate() {
+                    if word != "DROP" {
+                        continue;
+                    }
+                    let at = skip(&upper, i + 1, &["COLUMN", "IF", "EXISTS"]);
+                    let dropped_constraint = upper.get(i + 1).is_some_and(|w| {
+                        matches!(
+                            w.as_str(),
+                            "CONSTRAINT"
+                                | "INDEX"
+                                | "KEY"
+                                | "PRIMARY"
+                                | "FOREIGN"
+                                | "DEFAULT"
+                                | "NOT"
+                        )
+                    });
+                    if let (false, Some(column)) = (dropped_constraint, words.get(at)) {
+                        ops.push(op(
+                            SchemaOp::DropColumn,
+                            format!("{}.{}", name, ident(column)),
+                        ));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(before) = before {
+        let after_tables = tables(after);
+        for (table, (line, columns)) in tables(before) {
+            let op = |operation, object: String| DestructiveOp {
+                file: file.to_string(),
+                operation,
+                object,
+                line,
+                statement: format!("CREATE TABLE {}", table),
+            };
+            match after_tables.get(&table) {
+                None => ops.push(op(SchemaOp::RemovedTable, table.clone())),
+                Some((_, remaining)) => {
+                    for column in columns.difference(remaining) {
+                        ops.push(op(SchemaOp::RemovedColumn, format!("{}.{}", table, column)));
+                    }
+                }
+            }
+        }
+    }
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA: &str = "\
+-- users; with a comment
+CREATE TABLE IF NOT EXISTS \"users\" (
+    id BIGINT PRIMARY KEY,
+    email TEXT NOT NULL,
+    CONSTRAINT email_unique UNIQUE (email)
+);
+
+CREATE UNIQUE INDEX idx_users_email ON users (email);
+CREATE OR REPLACE VIEW active_users AS SELECT * FROM users WHERE note = 'a;b';
+ALTER TABLE users ADD COLUMN name TEXT;
+";
+
+    #[test]
+    fn sql_symbols_cover_tables_columns_indexes_views() {
+        let symbols = sql_symbols(SCHEMA);
+        let found: Vec<(&str, SymbolKind, usize)> = symbols
+            .iter()
+            .map(|s| (s.name.as_str(), s.kind, s.start_line))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("users", SymbolKind::Table, 2),
+                ("users.id", SymbolKind::Column, 3),
+                ("users.email", SymbolKind::Column, 4),
+                ("idx_users_email", SymbolKind::Index, 8),
+                ("active_users", SymbolKind::View, 9),
+                ("users", SymbolKind::Alter, 10),
+            ]
+        );
+        assert_eq!(symbols[0].end_line, 6);
+    }
+
+    #[test]
+    fn destructive_ops_in_migrations_and_schema_edits() {
+        let migration = "ALTER TABLE users DROP COLUMN email, DROP CONSTRAINT x;\nDROP TABLE IF EXISTS sessions;\nTRUNCATE audit_log;\n";
+        let ops: Vec<(SchemaOp, String)> = destructive_ops("m.sql", None, Some(migration))
+            .into_iter()
+            .map(|op| (op.operation, op.object))
+            .collect();
+        assert_eq!(
+            ops,
+            vec![
+                (SchemaOp::DropColumn, "users.email".to_string()),
+                (SchemaOp::DropTable, "sessions".to_string()),
+                (SchemaOp::Truncate, "audit_log".to_string()),
+            ]
+        );
+
+        // Statements that already existed are not new operations
+        assert!(destructive_ops("m.sql", Some(migration), Some(migration)).is_empty());
+
+        // Declarative schema: a column and a table disappear
+        let before = "CREATE TABLE a (id INT, gone INT);\nCREATE TABLE b (id INT);\n";
+        let after = "CREATE TABLE a (id INT);\n";
+        let ops: Vec<(SchemaOp, String)> = destructive_ops("schema.sql", Some(before), Some(after))
+            .into_iter()
+            .map(|op| (op.operation, op.object))
+            .collect();
+        assert_eq!(
+            ops,
+            vec![
+                (SchemaOp::RemovedColumn, "a.gone".to_string()),
+                (SchemaOp::RemovedTable, "b".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,147 @@
+// ABOUTME: `--stable-output`: JSON normalized so agent pipelines can be golden-file tested
+// ABOUTME: Arrays sorted, IDs replaced by placeholders, timestamps pinned to one epoch, durations zeroed
+
+use serde_json::Value;
+
+use crate::repo::utc_timestamp;
+
+/// Placeholder for the IDs under `key`, which the next run won't reproduce
+fn placeholder(key: &str) -> Option<&'static str> {
+    let key = key.strip_suffix('s').unwrap_or(key);
+    match key {
+        "op_id" | "operation_id" | "restored_to" | "would_restore_to" => Some("<op-id>"),
+        "change_id" | "parent_change_id" => Some("<change-id>"),
+        "commit" | "commit_id" | "full_commit_id" | "parent" | "parent_commit_id"
+        | "pre_commit" | "post_commit" => Some("<commit-id>"),
+        "tree_id" => Some("<tree-id>"),
+        _ => None,
+    }
+}
+
+/// Hex or jj's reverse-hex letters: an ID or a prefix of one
+fn looks_like_id(value: &str) -> bool {
+    !value.is_empty()
+        && value
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit())
+}
+
+/// Other `id` and `*_id` keys hold generated IDs (approvals, intents,
+/// stashes) when their value looks like one
+fn generated_id(key: &str, value: &str) -> bool {
+    (key == "id" || key.ends_with("_id")) && value.len() >= 8 && looks_like_id(value)
+}
+
+/// Durations and elapsed times, in whatever unit
+fn duration(key: &str) -> bool {
+    key.starts_with("duration")
+        || key.starts_with("elapsed")
+        || (key.ends_with("_ms") && key != "timestamp_ms")
+}
+
+/// `YYYY-MM-DDTHH:MM:SS`, with any fraction or offset after it
+fn timestamp(value: &str) -> bool {
+    let b = value.as_bytes();
+    b.len() >= 19
+        && [4, 7].iter().all(|&i| b[i] == b'-')
+        && b[10] == b'T'
+        && [13, 16].iter().all(|&i| b[i] == b':')
+        && [0, 1, 2, 3, 5, 6, 8, 9, 11, 12, 14, 15, 17, 18]
+            .iter()
+            .all(|&i| b[i].is_ascii_digit())
+}
+
+fn normalize(key: Option<&str>, value: &mut Value, epoch: i64) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                normalize(Some(key), value, epoch);
+            }
+        }
+        Value::Array(items) => {
+            for item in items.iter_mut() {
+                normalize(key, item, epoch);
+            }
+            items.sort_by_cached_key(|item| item.to_string());
+        }
+        Value::String(s) => {
+            let key = key.unwrap_or_default();
+            if let Some(placeholder) = placeholder(key).filter(|_| looks_like_id(s)) {
+                *s = placeholder.to_string();
+            } else if generated_id(key, s) {
+                *s = "<id>".to_string();
+            } else if timestamp(s) {
+                *s = utc_timestamp(epoch);
+            }
+        }
+        Value::Number(_) => {
+            let key = key.unwrap_or_default();
+            if key == "timestamp_ms" {
+                *value = Value::from(epoch.saturating_mul(1000));
+            } else if duration(key) {
+                *value = Value::from(0);
+            }
+        }
+        Value::Null | Value::Bool(_) => {}
+    }
+}
+
+/// Rewrite `value` so the same repository state prints the same JSON:
+/// every array sorted, operation, change, commit, and generated IDs replaced
+/// by placeholders (`"<op-id>"`), timestamps set to `epoch`, and durations
+/// zeroed. Object keys already serialize sorted.
+pub fn stabilize(value: &mut Value, epoch: i64) {
+    normalize(None, value, epoch);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn replaces_ephemeral_fields_and_sorts_arrays() {
+        let mut value = json!({
+            "operation_id": "3f9a0c1d2e",
+            "restored_to": "3f9a0c1d2e",
+            "commit": null,
+            "change_id": "kxqpmzvw",
+            "parents": ["b3c4", "a1b2"],
+            "approval_id": "0a1b2c3d4e5f",
+            "id": "lint",
+            "created_at": "2026-03-04T05:06:07Z",
+            "committed": "2026-03-04T05:06:07.123+02:00",
+            "since": "2026-03-04",
+            "duration_ms": 1234,
+            "timestamp_ms": 1,
+            "files": ["src/b.rs", "src/a.rs"],
+            "changes": [
+                { "change_id": "zzz", "description": "second" },
+                { "change_id": "yyy", "description": "first" },
+            ],
+        });
+        stabilize(&mut value, 86_400);
+        assert_eq!(
+            value,
+            json!({
+                "operation_id": "<op-id>",
+                "restored_to": "<op-id>",
+                "commit": null,
+                "change_id": "<change-id>",
+                "parents": ["<commit-id>", "<commit-id>"],
+                "approval_id": "<id>",
+                "id": "lint",
+                "created_at": "1970-01-02T00:00:00Z",
+                "committed": "1970-01-02T00:00:00Z",
+                "since": "2026-03-04",
+                "duration_ms": 0,
+                "timestamp_ms": 86_400_000,
+                "files": ["src/a.rs", "src/b.rs"],
+                "changes": [
+                    { "change_id": "<change-id>", "description": "first" },
+                    { "change_id": "<change-id>", "description": "second" },
+                ],
+            })
+        );
+    }
+}
@@ -0,0 +1,269 @@
+// ABOUTME: `start --task`: the session id, checkpoint name, and likely paths for a new task
+// ABOUTME: Pure helpers over the task text and file list; main.rs composes them with orient, preflight, and suggest
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+/// Most likely paths preflighted by `start` when `--paths` is not given
+pub const MAX_LIKELY_PATHS: usize = 10;
+
+/// Words that say nothing about where the work is, and top-level directory
+/// names nearly every path shares
+const STOPWORDS: &[&str] = &[
+    "src",
+    "lib",
+    "pkg",
+    "the",
+    "and",
+    "for",
+    "with",
+    "from",
+    "into",
+    "onto",
+    "that",
+    "this",
+    "these",
+    "those",
+    "when",
+    "then",
+    "than",
+    "not",
+    "but",
+    "all",
+    "any",
+    "are",
+    "was",
+    "were",
+    "has",
+    "have",
+    "had",
+    "can",
+    "should",
+    "must",
+    "will",
+    "make",
+    "makes",
+    "use",
+    "uses",
+    "using",
+    "add",
+    "adds",
+    "fix",
+    "fixes",
+    "new",
+    "update",
+    "updates",
+    "change",
+    "changes",
+    "implement",
+    "support",
+    "bug",
+    "issue",
+    "task",
+    "work",
+    "code",
+    "file",
+    "files",
+    "some",
+    "more",
+    "less",
+    "also",
+    "only",
+    "out",
+    "via",
+    "per",
+    "its",
+    "our",
+    "their",
+    "there",
+    "where",
+    "which",
+    "what",
+    "why",
+    "how",
+];
+
+/// A path `start` will preflight, and why it was picked
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LikelyPath {
+    pub path: String,
+    /// "mentioned" (named in the task), "name" (file or directory name
+    /// matches task words), or "given" (`--paths`)
+    pub reason: String,
+    /// Task words found in the path
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub matched: Vec<String>,
+}
+
+/// Lowercased task words worth looking for in paths, in order, without repeats
+pub fn keywords(task: &str) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+    for word in task
+        .split(|c: char| !c.is_alphanumeric())
+        .map(str::to_lowercase)
+    {
+        if word.len() >= 3
+            && !word.chars().all(|c| c.is_ascii_digit())
+            && !STOPWORDS.contains(&word.as_str())
+            && !words.contains(&word)
+        {
+            words.push(word);
+        }
+    }
+    words
+}
+
+/// Short, filesystem-safe name for the task: its first keywords
+pub fn task_slug(task: &str) -> String {
+    let mut slug = String::new();
+    for word in keywords(task).iter().take(4) {
+        if slug.len() + word.len() > 40 {
+            break;
+        }
+        if !slug.is_empty() {
+            slug.push('-');
+        }
+        slug.push_str(word);
+    }
+    if slug.is_empty() {
+        slug.push_str("task");
+    }
+    slug
+}
+
+/// `<slug>-<6 hex>`: unique per task and start time, used for the session id
+/// and the checkpoint name
+pub fn task_id(task: &str, started_at: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(task.as_bytes());
+    hasher.update(started_at.as_bytes());
+    hasher.update(std::process::id().to_le_bytes());
+    format!(
+        "{}-{}",
+        task_slug(task),
+        &hex::encode(hasher.finalize())[..6]
+    )
+}
+
+/// Repository files the task probably touches: paths it names first, then
+/// files whose name or directories match its words, most matches first.
+/// `files` are relative to the repository root.
+pub fn likely_paths(task: &str, files: &[String], limit: usize) -> Vec<LikelyPath> {
+    let mut found: Vec<LikelyPath> = Vec::new();
+
+    // Named outright: `src/api.rs`, `./docs/`, `Cargo.toml`
+    for token in task.split(|c: char| c.is_whitespace() || "`'\",;()[]".contains(c)) {
+        let raw = token.trim_start_matches("./").trim_end_matches(['.', ':']);
+        if !(raw.contains('/') || raw.contains('.')) {
+            continue;
+        }
+        let token = raw.trim_end_matches('/');
+        if token.is_empty() {
+            continue;
+        }
+        let dir = format!("{}/", token);
+        let exists = files.iter().any(|f| f == token || f.starts_with(&dir));
+        if exists && !found.iter().any(|p| p.path == token) {
+            found.push(LikelyPath {
+                path: token.to_string(),
+                reason: "mentioned".to_string(),
+                matched: Vec::new(),
+            });
+        }
+    }
+
+    let words = keywords(task);
+    let mut by_name: Vec<(usize, LikelyPath)> = files
+        .iter()
+        .filter(|f| !found.iter().any(|p| p.path == **f))
+        .filter_map(|file| {
+            let parts: Vec<String> = file
+                .split(['/', '.', '_', '-'])
+                .map(str::to_lowercase)
+                .collect();
+            let matched: Vec<String> = words
+                .iter()
+                .filter(|w| {
+                    parts
+                        .iter()
+                        .any(|p| p == *w || (w.len() >= 4 && p.starts_with(w.as_str())))
+                })
+                .cloned()
+                .collect();
+            (!matched.is_empty()).then(|| {
+                (
+                    matched.len(),
+                    LikelyPath {
+                        path: file.clone(),
+                        reason: "name".to_string(),
+                        matched,
+                    },
+                )
+            })
+        })
+        .collect();
+    // More task words first, then shallower and shorter paths
+    by_name.sort_by(|(a, pa), (b, pb)| {
+        b.cmp(a)
+            .then_with(|| {
+                pa.path
+                    .matches('/')
+                    .count()
+                    .cmp(&pb.path.matches('/').count())
+            })
+            .then_with(|| pa.path.len().cmp(&pb.path.len()))
+            .then_with(|| pa.path.cmp(&pb.path))
+    });
+    found.extend(by_name.into_iter().map(|(_, p)| p));
+    found.truncate(limit);
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_mentioned_then_name_matched_paths() {
+        let files: Vec<String> = [
+            "README.md",
+            "src/auth/login.rs",
+            "src/auth/session.rs",
+            "src/api.rs",
+            "tests/login_timeout.rs",
+            "docs/guide.md",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        let task = "Fix the login timeout in `src/api.rs` and the auth docs/";
+
+        assert_eq!(
+            keywords(task),
+            vec!["login", "timeout", "api", "auth", "docs"]
+        );
+        let paths = likely_paths(task, &files, MAX_LIKELY_PATHS);
+        let names: Vec<&str> = paths.iter().map(|p| p.path.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "src/api.rs",
+                "docs",
+                "tests/login_timeout.rs",
+                "src/auth/login.rs",
+                "docs/guide.md",
+                "src/auth/session.rs",
+            ]
+        );
+        assert_eq!(paths[0].reason, "mentioned");
+        assert_eq!(paths[3].matched, vec!["login", "auth"]);
+        assert_eq!(likely_paths(task, &files, 2).len(), 2);
+        assert!(likely_paths("the and for", &files, 10).is_empty());
+
+        assert_eq!(task_slug(task), "login-timeout-api-auth");
+        assert_eq!(task_slug("fix it"), "task");
+        let id = task_id(task, "2026-01-02T03:04:05Z");
+        assert!(id.starts_with("login-timeout-api-auth-"));
+        assert_eq!(id.len(), "login-timeout-api-auth-".len() + 6);
+    }
+}
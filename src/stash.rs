@@ -0,0 +1,151 @@
+// ABOUTME: Named stashes of working-copy changes, parked as hidden jj changes
+// ABOUTME: Records (.agent/stash/<name>.json) point at the hidden commit; repo.rs moves the trees
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+
+/// One parked set of working-copy changes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StashEntry {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Session that parked the work (`AGENTJJ_SESSION`)
+    pub session: String,
+    /// The stashed change keeps the working copy's change ID, so typed-change
+    /// metadata recorded for it still applies after a pop
+    pub change_id: String,
+    /// Hidden commit holding the stashed tree
+    pub commit_id: String,
+    pub created_at: String,
+    /// Paths the stash changes relative to its parent
+    pub files: Vec<String>,
+}
+
+/// Result of reapplying a stash to the working copy
+#[derive(Debug, Clone, Serialize)]
+pub struct StashPop {
+    pub name: String,
+    /// Working-copy change the stash was applied to
+    pub change_id: String,
+    pub files: Vec<String>,
+    /// Paths left with conflict markers; the stash is kept when non-empty
+    pub conflicts: Vec<String>,
+}
+
+/// The stash records of a repository
+pub struct StashStore {
+    dir: PathBuf,
+}
+
+impl StashStore {
+    pub const DEFAULT_DIR: &'static str = ".agent/stash";
+
+    pub fn open(repo_root: impl AsRef<Path>) -> Self {
+        Self {
+            dir: repo_root.as_ref().join(Self::DEFAULT_DIR),
+        }
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", name))
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.path(name).exists()
+    }
+
+    pub fn save(&self, entry: &StashEntry) -> Result<()> {
+        crate::agent_dir::write_json(&self.path(&entry.name), entry)
+    }
+
+    pub fn get(&self, name: &str) -> Result<StashEntry> {
+        let content = std::fs::read_to_string(self.path(name)).map_err(|_| Error::Io {
+            message: format!("no stash named '{}'", name),
+        })?;
+        serde_json::from_str(&content).map_err(|e| Error::Io {
+            message: format!("corrupt stash record '{}': {}", name, e),
+        })
+    }
+
+    pub fn remove(&self, name: &str) -> Result<()> {
+        std::fs::remove_file(self.path(name))?;
+        Ok(())
+    }
+
+    /// All stashes, newest first
+    pub fn list(&self) -> Vec<StashEntry> {
+        let mut entries: Vec<StashEntry> = std::fs::read_dir(&self.dir)
+            .map(|dir| {
+                dir.flatten()
+                    .filter(|e| e.path().extension().and_then(|x| x.to_str()) == Some("json"))
+                    .filter_map(|e| std::fs::read_to_string(e.path()).ok())
+                    .filter_map(|content| serde_json::from_str(&content).ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        entries.sort_by(|a, b| {
+            b.created_at
+                .cmp(&a.created_at)
+                .then_with(|| b.name.cmp(&a.name))
+        });
+        entries
+    }
+
+    /// The first unused `stash-<n>` name
+    pub fn next_name(&self) -> String {
+        (1..)
+            .map(|n| format!("stash-{}", n))
+            .find(|name| !self.contains(name))
+            .unwrap()
+    }
+}
+
+/// Stash names become file names, so keep them to `[A-Za-z0-9._-]`
+pub fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, created_at: &str) -> StashEntry {
+        StashEntry {
+            name: name.to_string(),
+            message: None,
+            session: "default".to_string(),
+            change_id: "abc".to_string(),
+            commit_id: "def".to_string(),
+            created_at: created_at.to_string(),
+            files: vec!["a.txt".to_string()],
+        }
+    }
+
+    #[test]
+    fn stores_and_lists_newest_first() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = StashStore::open(tmp.path());
+        assert!(store.list().is_empty());
+        assert_eq!(store.next_name(), "stash-1");
+
+        store
+            .save(&entry("stash-1", "2026-01-01T00:00:00Z"))
+            .unwrap();
+        store.save(&entry("wip", "2026-01-02T00:00:00Z")).unwrap();
+        assert_eq!(store.next_name(), "stash-2");
+        let names: Vec<String> = store.list().into_iter().map(|e| e.name).collect();
+        assert_eq!(names, vec!["wip", "stash-1"]);
+        assert!(tmp.path().join(".agent/stash/.gitignore").exists());
+
+        store.remove("wip").unwrap();
+        assert!(store.get("wip").is_err());
+        assert!(!valid_name("../x") && !valid_name(".hidden") && valid_name("fix-1.2_b"));
+    }
+}
@@ -0,0 +1,155 @@
+// ABOUTME: Repository analytics helpers: language detection, line counts, and aggregation
+// ABOUTME: Backs `agentjj stats` with pure functions that are easy to test
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Lines of a file split by kind
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct LineCount {
+    pub total: usize,
+    pub blank: usize,
+    pub code: usize,
+}
+
+/// Aggregated size of one language
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LanguageStats {
+    pub files: usize,
+    pub lines: usize,
+    pub code_lines: usize,
+    pub blank_lines: usize,
+    pub bytes: u64,
+}
+
+impl LanguageStats {
+    pub fn add(&mut self, lines: LineCount, bytes: u64) {
+        self.files += 1;
+        self.lines += lines.total;
+        self.code_lines += lines.code;
+        self.blank_lines += lines.blank;
+        self.bytes += bytes;
+    }
+}
+
+/// Language name for a path, from its extension or well-known file name
+pub fn language_for(path: &Path) -> Option<&'static str> {
+    let name = path.file_name()?.to_str()?;
+    match name {
+        "Dockerfile" => return Some("dockerfile"),
+        "Makefile" | "makefile" | "GNUmakefile" => return Some("make"),
+        _ => {}
+    }
+
+    let lang = match path.extension()?.to_str()? {
+        "rs" => "rust",
+        "py" | "pyi" => "python",
+        "js" | "mjs" | "cjs" | "jsx" => "javascript",
+        "ts" | "tsx" | "mts" | "cts" => "typescript",
+        "go" => "go",
+        "java" => "java",
+        "kt" | "kts" => "kotlin",
+        "c" | "h" => "c",
+        "cc" | "cpp" | "cxx" | "hpp" | "hh" | "hxx" => "cpp",
+        "cs" => "csharp",
+        "rb" => "ruby",
+        "php" => "php",
+        "swift" => "swift",
+        "scala" => "scala",
+        "sh" | "bash" | "zsh" => "shell",
+        "sql" => "sql",
+        "html" | "htm" => "html",
+        "css" | "scss" | "sass" | "less" => "css",
+        "md" | "markdown" => "markdown",
+        "toml" => "toml",
+        "yaml" | "yml" => "yaml",
+        "json" => "json",
+        "xml" => "xml",
+        "proto" => "protobuf",
+        _ => return None,
+    };
+    Some(lang)
+}
+
+/// Count total, blank, and non-blank lines
+pub fn count_lines(content: &str) -> LineCount {
+    let mut count = LineCount::default();
+    for line in content.lines() {
+        count.total += 1;
+        if line.trim().is_empty() {
+            count.blank += 1;
+        }
+    }
+    count.code = count.total - count.blank;
+    count
+}
+
+/// Module a file belongs to for per-module rollups: its parent directory
+/// (or "." for files at the root)
+pub fn module_of(rel_path: &str) -> String {
+    match rel_path.rsplit_once('/') {
+        Some((dir, _)) => dir.to_string(),
+        None => ".".to_string(),
+    }
+}
+
+/// Year-month bucket ("2026-03") of an ISO 8601 timestamp
+pub fn month_of(timestamp: &str) -> Option<String> {
+    (timestamp.len() >= 7 && timestamp.as_bytes()[4] == b'-').then(|| timestamp[..7].to_string())
+}
+
+/// Top `limit` entries of a count map, highest first (ties by key)
+pub fn top_counts(counts: &BTreeMap<String, usize>, limit: usize) -> Vec<(String, usize)> {
+    let mut entries: Vec<(String, usize)> = counts.iter().map(|(k, v)| (k.clone(), *v)).collect();
+    entries.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    entries.truncate(limit);
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_languages() {
+        assert_eq!(language_for(Path::new("src/main.rs")), Some("rust"));
+        assert_eq!(language_for(Path::new("web/app.tsx")), Some("typescript"));
+        assert_eq!(language_for(Path::new("Dockerfile")), Some("dockerfile"));
+        assert_eq!(language_for(Path::new("LICENSE")), None);
+    }
+
+    #[test]
+    fn counts_blank_and_code_lines() {
+        let count = count_lines("fn a() {}\n\n  \nfn b() {}\n");
+        assert_eq!(
+            count,
+            LineCount {
+                total: 4,
+                blank: 2,
+                code: 2
+            }
+        );
+    }
+
+    #[test]
+    fn modules_and_months() {
+        assert_eq!(module_of("src/api/handler.rs"), "src/api");
+        assert_eq!(module_of("README.md"), ".");
+        assert_eq!(
+            month_of("2026-03-14T10:00:00+00:00").as_deref(),
+            Some("2026-03")
+        );
+        assert_eq!(month_of("soon"), None);
+    }
+
+    #[test]
+    fn top_counts_orders_by_count_then_name() {
+        let counts: BTreeMap<String, usize> = [("b", 3), ("a", 3), ("c", 5), ("d", 1)]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect();
+        let top = top_counts(&counts, 3);
+        assert_eq!(top, vec![("c".into(), 5), ("a".into(), 3), ("b".into(), 3)]);
+    }
+}
@@ -0,0 +1,398 @@
+// ABOUTME: Disk usage of the .agent directory per subsystem (caches, checkpoints, audit logs, ...) for `stats --storage`
+// ABOUTME: Manifest [storage] caps; a subsystem over its cap is pruned oldest-first after state-changing commands
+
+use serde::{Deserialize, Deserializer, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Audit-log command name of an automatic prune (skipped by replay)
+pub const AUTO_PRUNE_COMMAND: &str = "storage prune";
+
+/// Past this size an uncapped subsystem gets a retention recommendation
+pub const LARGE_BYTES: u64 = 100 * 1024 * 1024;
+
+/// A part of `.agent` that takes disk space
+#[derive(Debug, Clone, Copy)]
+pub struct Subsystem {
+    pub name: &'static str,
+    pub dir: &'static str,
+    /// Its files can be deleted without losing anything that matters: they
+    /// are rebuilt, or are records past their use
+    pub prunable: bool,
+    pub about: &'static str,
+    /// What to do when it grows
+    pub advice: &'static str,
+}
+
+pub const SUBSYSTEMS: &[Subsystem] = &[
+    Subsystem {
+        name: "cache",
+        dir: ".agent/cache",
+        prunable: true,
+        about: "blob and history caches, rebuilt on demand",
+        advice: "lower cache.disk_max_bytes, or turn off cache.disk / cache.history",
+    },
+    Subsystem {
+        name: "checkpoints",
+        dir: ".agent/checkpoints",
+        prunable: true,
+        about: "checkpoint records",
+        advice: "run `agentjj gc` (gc.keep_checkpoints, gc.checkpoint_max_age_days)",
+    },
+    Subsystem {
+        name: "audit",
+        dir: ".agent/audit",
+        prunable: true,
+        about: "per-session audit logs",
+        advice: "run `agentjj gc` (gc.audit_max_age_days), or export sessions you need with `replay export`",
+    },
+    Subsystem {
+        name: "trace",
+        dir: ".agent/trace",
+        prunable: true,
+        about: "Chrome traces from --trace-file",
+        advice: "delete traces you have looked at",
+    },
+    Subsystem {
+        name: "out",
+        dir: ".agent/out",
+        prunable: true,
+        about: "output spilled past --max-output-bytes",
+        advice: "delete spilled output that was read",
+    },
+    Subsystem {
+        name: "tests",
+        dir: ".agent/tests",
+        prunable: true,
+        about: "test run history for `test flaky`",
+        advice: "cap it; `test flaky` only needs recent runs",
+    },
+    Subsystem {
+        name: "metrics",
+        dir: ".agent/metrics",
+        prunable: true,
+        about: "local usage metrics",
+        advice: "cap it, or turn off metrics.enabled",
+    },
+    Subsystem {
+        name: "changes",
+        dir: ".agent/changes",
+        prunable: false,
+        about: "typed change metadata",
+        advice: "run `agentjj gc --aggressive` to drop metadata of abandoned changes",
+    },
+    Subsystem {
+        name: "stash",
+        dir: ".agent/stash",
+        prunable: false,
+        about: "stash records",
+        advice: "drop stashes you no longer need with `stash drop`",
+    },
+];
+
+/// A size in bytes, written as a number or with a unit (`"500MB"`, `"2 GiB"`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct ByteSize(pub u64);
+
+impl<'de> Deserialize<'de> for ByteSize {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Bytes(u64),
+            Text(String),
+        }
+        match Raw::deserialize(deserializer)? {
+            Raw::Bytes(bytes) => Ok(ByteSize(bytes)),
+            Raw::Text(text) => parse_size(&text).map(ByteSize).ok_or_else(|| {
+                serde::de::Error::custom(format!(
+                    "invalid size '{}' (use bytes or e.g. \"500MB\", \"2GB\")",
+                    text
+                ))
+            }),
+        }
+    }
+}
+
+/// Parse `1024`, `512KB`, `500 MB`, `2GiB`, ... (units are powers of 1024)
+pub fn parse_size(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        "t" | "tb" | "tib" => 1 << 40,
+        _ => return None,
+    };
+    Some((number * multiplier as f64) as u64)
+}
+
+/// Manifest `[storage]`: caps on prunable `.agent` subsystems
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StorageConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache: Option<ByteSize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checkpoints: Option<ByteSize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audit: Option<ByteSize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace: Option<ByteSize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub out: Option<ByteSize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tests: Option<ByteSize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<ByteSize>,
+}
+
+impl StorageConfig {
+    /// The cap on a subsystem, by name
+    pub fn cap(&self, name: &str) -> Option<u64> {
+        let cap = match name {
+            "cache" => self.cache,
+            "checkpoints" => self.checkpoints,
+            "audit" => self.audit,
+            "trace" => self.trace,
+            "out" => self.out,
+            "tests" => self.tests,
+            "metrics" => self.metrics,
+            _ => None,
+        };
+        cap.map(|c| c.0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        SUBSYSTEMS.iter().all(|s| self.cap(s.name).is_none())
+    }
+}
+
+/// One subsystem's disk usage
+#[derive(Debug, Clone, Serialize)]
+pub struct SubsystemUsage {
+    pub name: &'static str,
+    pub path: &'static str,
+    pub about: &'static str,
+    pub bytes: u64,
+    pub files: usize,
+    /// Modification time of its oldest file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oldest: Option<String>,
+    pub prunable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cap_bytes: Option<u64>,
+    pub over_cap: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recommendation: Option<String>,
+}
+
+/// `stats --storage`
+#[derive(Debug, Clone, Serialize)]
+pub struct StorageReport {
+    /// The whole `.agent` directory
+    pub total_bytes: u64,
+    /// Largest first
+    pub subsystems: Vec<SubsystemUsage>,
+    /// Everything else: the manifest, queries, notes, approvals, ...
+    pub other_bytes: u64,
+}
+
+/// Files under `dir` (`.gitignore` markers left out) with their size and
+/// modification time
+fn files(dir: &Path) -> Vec<(PathBuf, u64, SystemTime)> {
+    let mut out = Vec::new();
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return out;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(meta) = std::fs::symlink_metadata(&path) else {
+            continue;
+        };
+        if meta.is_dir() {
+            out.extend(files(&path));
+        } else if path.file_name().is_some_and(|n| n != ".gitignore") {
+            let modified = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            out.push((path, meta.len(), modified));
+        }
+    }
+    out
+}
+
+fn timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+    crate::repo::utc_timestamp(secs)
+}
+
+/// How much each subsystem of `.agent` takes, against its cap
+pub fn report(repo_root: &Path, caps: &StorageConfig) -> StorageReport {
+    let total_bytes: u64 = files(&repo_root.join(".agent"))
+        .iter()
+        .map(|(_, size, _)| size)
+        .sum();
+    let mut subsystems: Vec<SubsystemUsage> = SUBSYSTEMS
+        .iter()
+        .map(|s| {
+            let found = files(&repo_root.join(s.dir));
+            let bytes: u64 = found.iter().map(|(_, size, _)| size).sum();
+            let cap_bytes = caps.cap(s.name);
+            let over_cap = cap_bytes.is_some_and(|cap| bytes > cap);
+            let recommendation = if over_cap {
+                Some(format!(
+                    "over its {} byte cap: the next state-changing command prunes the oldest files",
+                    cap_bytes.unwrap_or_default()
+                ))
+            } else if bytes > LARGE_BYTES {
+                Some(match (s.prunable, cap_bytes) {
+                    (true, None) => format!(
+                        "{}, or set `{} = \"{}MB\"` under [storage] to prune it automatically",
+                        s.advice,
+                        s.name,
+                        LARGE_BYTES >> 20
+                    ),
+                    _ => s.advice.to_string(),
+                })
+            } else {
+                None
+            };
+            SubsystemUsage {
+                name: s.name,
+                path: s.dir,
+                about: s.about,
+                bytes,
+                files: found.len(),
+                oldest: found.iter().map(|(_, _, t)| *t).min().map(timestamp),
+                prunable: s.prunable,
+                cap_bytes,
+                over_cap,
+                recommendation,
+            }
+        })
+        .collect();
+    subsystems.sort_by_key(|s| std::cmp::Reverse(s.bytes));
+    let counted: u64 = subsystems.iter().map(|s| s.bytes).sum();
+    StorageReport {
+        total_bytes,
+        other_bytes: total_bytes.saturating_sub(counted),
+        subsystems,
+    }
+}
+
+/// What pruning one subsystem removed
+#[derive(Debug, Clone, Serialize)]
+pub struct Pruned {
+    pub subsystem: &'static str,
+    pub cap_bytes: u64,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// Prune every capped subsystem that is over its cap, oldest files first,
+/// down to three quarters of the cap (as the blob cache does), so the next
+/// few writes don't prune again
+pub fn enforce(repo_root: &Path, caps: &StorageConfig) -> Vec<Pruned> {
+    let mut pruned = Vec::new();
+    for s in SUBSYSTEMS.iter().filter(|s| s.prunable) {
+        let Some(cap) = caps.cap(s.name) else {
+            continue;
+        };
+        let mut found = files(&repo_root.join(s.dir));
+        let mut total: u64 = found.iter().map(|(_, size, _)| size).sum();
+        if total <= cap {
+            continue;
+        }
+        found.sort_by_key(|(_, _, modified)| *modified);
+        let mut removed = Pruned {
+            subsystem: s.name,
+            cap_bytes: cap,
+            files: 0,
+            bytes: 0,
+        };
+        for (path, size, _) in found {
+            if total <= cap / 4 * 3 {
+                break;
+            }
+            if std::fs::remove_file(&path).is_ok() {
+                total -= size;
+                removed.files += 1;
+                removed.bytes += size;
+            }
+        }
+        pruned.push(removed);
+    }
+    pruned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn parses_sizes_with_units() {
+        assert_eq!(parse_size("1024"), Some(1024));
+        assert_eq!(parse_size("512KB"), Some(512 * 1024));
+        assert_eq!(parse_size("1.5 MiB"), Some(1536 * 1024));
+        assert_eq!(parse_size("2gb"), Some(2 << 30));
+        assert_eq!(parse_size("lots"), None);
+        assert_eq!(parse_size("10 parsecs"), None);
+
+        let caps: StorageConfig = toml::from_str("cache = \"1KB\"\naudit = 300").unwrap();
+        assert_eq!(caps.cap("cache"), Some(1024));
+        assert_eq!(caps.cap("audit"), Some(300));
+        assert!(toml::from_str::<StorageConfig>("changes = \"1KB\"").is_err());
+        assert!(toml::from_str::<StorageConfig>("cache = \"big\"").is_err());
+    }
+
+    #[test]
+    fn reports_usage_and_prunes_oldest_first() {
+        let tmp = TempDir::new().unwrap();
+        let audit = tmp.path().join(".agent/audit");
+        std::fs::create_dir_all(&audit).unwrap();
+        std::fs::write(audit.join(".gitignore"), "*\n").unwrap();
+        for (i, name) in ["old", "mid", "new"].iter().enumerate() {
+            let path = audit.join(format!("{}.jsonl", name));
+            std::fs::write(&path, vec![b'x'; 400]).unwrap();
+            let time = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000 + i as u64);
+            std::fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(time)
+                .unwrap();
+        }
+        std::fs::write(tmp.path().join(".agent/manifest.toml"), "x").unwrap();
+
+        let caps = StorageConfig {
+            audit: Some(ByteSize(1000)),
+            ..Default::default()
+        };
+        let report = report(tmp.path(), &caps);
+        let usage = &report.subsystems[0];
+        assert_eq!((usage.name, usage.bytes, usage.files), ("audit", 1200, 3));
+        assert!(usage.over_cap);
+        assert!(usage.recommendation.is_some());
+        assert_eq!(usage.oldest.as_deref(), Some("1970-01-01T00:16:40Z"));
+        assert_eq!(report.other_bytes, 1);
+
+        let pruned = enforce(tmp.path(), &caps);
+        assert_eq!(pruned.len(), 1);
+        // Down to 750 bytes: the two oldest go
+        assert_eq!((pruned[0].files, pruned[0].bytes), (2, 800));
+        assert!(audit.join("new.jsonl").exists());
+        assert!(!audit.join("old.jsonl").exists());
+        assert!(audit.join(".gitignore").exists());
+        assert!(enforce(tmp.path(), &caps).is_empty());
+    }
+}
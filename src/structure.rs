@@ -0,0 +1,419 @@
+// ABOUTME: Line-based structure extraction for files without a tree-sitter grammar
+// ABOUTME: Dockerfile stages, YAML keys and GitHub Actions jobs/steps, Markdown sections/code blocks
+
+use crate::symbols::{Symbol, SymbolKind};
+
+fn symbol(
+    name: impl Into<String>,
+    kind: SymbolKind,
+    line: &str,
+    start: usize,
+    end: usize,
+) -> Symbol {
+    Symbol {
+        name: name.into(),
+        kind,
+        signature: Some(line.trim().to_string()),
+        docstring: None,
+        start_line: start + 1,
+        end_line: end + 1,
+        children: Vec::new(),
+    }
+}
+
+/// Dockerfile stages (named by `AS` alias, else their index like `--from=0`)
+/// and every instruction, with `\` continuations folded into one span
+pub fn dockerfile_symbols(source: &str) -> Vec<Symbol> {
+    let lines: Vec<&str> = source.lines().collect();
+    let mut symbols = Vec::new();
+    let mut stage: Option<Symbol> = None;
+    let mut stage_index = 0;
+
+    let mut i = 0;
+    while i < lines.len() {
+        let trimmed = lines[i].trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while lines[i].trim_end().ends_with('\\') && i + 1 < lines.len() {
+            i += 1;
+        }
+        let keyword = trimmed
+            .split_whitespace()
+            .next()
+            .unwrap_or("")
+            .to_uppercase();
+
+        if keyword == "FROM" {
+            symbols.extend(stage.take());
+            let words: Vec<&str> = trimmed.split_whitespace().collect();
+            let alias = words
+                .iter()
+                .position(|w| w.eq_ignore_ascii_case("as"))
+                .and_then(|at| words.get(at + 1));
+            let name = alias.map_or_else(|| stage_index.to_string(), |a| a.to_string());
+            stage = Some(symbol(name, SymbolKind::Stage, trimmed, start, i));
+            stage_index += 1;
+        } else if let Some(stage) = stage.as_mut() {
+            stage.end_line = i + 1;
+        }
+        symbols.push(symbol(keyword, SymbolKind::Instruction, trimmed, start, i));
+        i += 1;
+    }
+    symbols.extend(stage);
+    symbols.sort_by_key(|s| (s.start_line, s.kind != SymbolKind::Stage));
+    symbols
+}
+
+/// A YAML `key:` line, returning the unquoted key
+fn yaml_key(text: &str) -> Option<&str> {
+    if text.starts_with('#') || text.starts_with('-') {
+        return None;
+    }
+    let (key, rest) = if let Some(quoted) = text.strip_prefix('"').or(text.strip_prefix('\'')) {
+        let close = quoted.find(['"', '\''])?;
+        (&quoted[..close], quoted[close + 1..].trim_start())
+    } else {
+        let colon = text.find(':')?;
+        (text[..colon].trim_end(), &text[colon..])
+    };
+    let rest = rest.strip_prefix(':')?;
+    (rest.is_empty() || rest.starts_with(char::is_whitespace)).then_some(key)
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// Whether a line is content (not blank, not a comment)
+fn is_content(line: &str) -> bool {
+    let trimmed = line.trim();
+    !trimmed.is_empty() && !trimmed.starts_with('#')
+}
+
+/// Last content line of the block that starts at `start` and holds every
+/// following line indented deeper than `indent`
+fn block_end(lines: &[&str], start: usize, indent: usize) -> usize {
+    let mut end = start;
+    for (i, line) in lines.iter().enumerate().skip(start + 1) {
+        if !is_content(line) {
+            continue;
+        }
+        if indent_of(line) <= indent {
+            break;
+        }
+        end = i;
+    }
+    end
+}
+
+/// YAML top-level keys; for workflows (a top-level `jobs:` mapping), also
+/// each job and each of its `steps`
+pub fn yaml_symbols(source: &str) -> Vec<Symbol> {
+    let lines: Vec<&str> = source.lines().collect();
+    let mut symbols = Vec::new();
+
+    for (i, line) in lines.iter().enumerate() {
+        if line.starts_with(char::is_whitespace) || *line == "---" {
+            continue;
+        }
+        let Some(key) = yaml_key(line) else {
+            continue;
+        };
+        let end = block_end(&lines, i, 0);
+        symbols.push(symbol(key, SymbolKind::Key, line, i, end));
+        if key == "jobs" {
+            workflow_jobs(&lines, i, end, &mut symbols);
+        }
+    }
+    symbols
+}
+
+/// Jobs are the keys one level under `jobs:`; steps are the list items under
+/// each job's `steps:`
+fn workflow_jobs(lines: &[&str], jobs_line: usize, jobs_end: usize, symbols: &mut Vec<Symbol>) {
+    let Some(job_indent) = (jobs_line + 1..=jobs_end)
+        .find(|&i| is_content(lines[i]))
+        .map(|i| indent_of(lines[i]))
+    else {
+        return;
+    };
+
+    for i in jobs_line + 1..=jobs_end {
+        let line = lines[i];
+        if indent_of(line) != job_indent || !is_content(line) {
+            continue;
+        }
+        let Some(job) = yaml_key(line.trim_start()) else {
+            continue;
+        };
+        let job_end = block_end(lines, i, job_indent);
+        symbols.push(symbol(job, SymbolKind::Job, line, i, job_end));
+
+        let steps = (i + 1..=job_end).find(|&s| yaml_key(lines[s].trim_start()) == Some("steps"));
+        if let Some(steps_line) = steps {
+            let steps_indent = indent_of(lines[steps_line]);
+            let steps_end = block_end(lines, steps_line, steps_indent);
+            workflow_steps(lines, steps_line, steps_end, symbols);
+        }
+    }
+}
+
+fn workflow_steps(lines: &[&str], steps_line: usize, steps_end: usize, symbols: &mut Vec<Symbol>) {
+    let items: Vec<usize> = (steps_line + 1..=steps_end)
+        .filter(|&i| lines[i].trim_start().starts_with("- ") || lines[i].trim() == "-")
+        .collect();
+    let Some(item_indent) = items.first().map(|&i| indent_of(lines[i])) else {
+        return;
+    };
+    let items: Vec<usize> = items
+        .into_iter()
+        .filter(|&i| indent_of(lines[i]) == item_indent)
+        .collect();
+
+    for (n, &start) in items.iter().enumerate() {
+        let end = items.get(n + 1).map_or(steps_end, |&next| {
+            (start..next)
+                .rev()
+                .find(|&i| is_content(lines[i]))
+                .unwrap_or(start)
+        });
+
+        // A step is named by `name:`, else what it `uses:` or `run:`s
+        let field = |wanted: &str| {
+            // Only the step's own keys: the `- ` line and its siblings
+            (start..=end).find_map(|i| {
+                if i != start && indent_of(lines[i]) != item_indent + 2 {
+                    return None;
+                }
+                let text = lines[i].trim_start().trim_start_matches("- ");
+                let value = text.strip_prefix(wanted)?.strip_prefix(':')?.trim();
+                let value = value.trim_matches(['"', '\'']);
+                (!value.is_empty() && value != "|" && value != ">").then(|| value.to_string())
+            })
+        };
+        let name = field("name")
+            .or_else(|| field("uses"))
+            .or_else(|| field("run"))
+            .unwrap_or_else(|| format!("step-{}", n + 1));
+        symbols.push(symbol(name, SymbolKind::Step, lines[start], start, end));
+    }
+}
+
+/// A fence opening/closing line: the fence string and the info string
+fn code_fence(line: &str) -> Option<(&str, &str)> {
+    let trimmed = line.trim_start();
+    let ch = trimmed.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let len = trimmed.chars().take_while(|c| *c == ch).count();
+    (len >= 3).then(|| (&trimmed[..len], trimmed[len..].trim()))
+}
+
+/// An ATX heading (`## Title ##`): its level and text
+fn atx_heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    let rest = &line[level..];
+    if !(1..=6).contains(&level) || !(rest.is_empty() || rest.starts_with([' ', '\t'])) {
+        return None;
+    }
+    Some((level, rest.trim().trim_end_matches('#').trim_end()))
+}
+
+/// GitHub-style anchor of a heading: `Quick Start!` -> `quick-start`
+pub fn heading_slug(text: &str) -> String {
+    text.to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Markdown sections (one per heading, spanning until the next heading of
+/// the same or a higher level) and fenced code blocks (`code-1`, `code-2`, ...)
+pub fn markdown_symbols(source: &str) -> Vec<Symbol> {
+    let lines: Vec<&str> = source.lines().collect();
+    let mut symbols = Vec::new();
+    // (level, index into symbols) of sections still open
+    let mut open: Vec<(usize, usize)> = Vec::new();
+    let mut fence: Option<(String, usize, String)> = None;
+    let mut code_blocks = 0;
+
+    let close =
+        |symbols: &mut Vec<Symbol>, open: &mut Vec<(usize, usize)>, level: usize, end: usize| {
+            while let Some(&(open_level, index)) = open.last() {
+                if open_level < level {
+                    break;
+                }
+                symbols[index].end_line = (end + 1).max(symbols[index].start_line);
+                open.pop();
+            }
+        };
+
+    for (i, line) in lines.iter().enumerate() {
+        if let Some((marker, start, info)) = &fence {
+            if code_fence(line)
+                .is_some_and(|(m, rest)| m.starts_with(marker.as_str()) && rest.is_empty())
+            {
+                code_blocks += 1;
+                let name = format!("code-{}", code_blocks);
+                let mut block = symbol(name, SymbolKind::CodeBlock, lines[*start], *start, i);
+                block.docstring = (!info.is_empty()).then(|| info.clone());
+                symbols.push(block);
+                fence = None;
+            }
+            continue;
+        }
+        if let Some((marker, info)) = code_fence(line) {
+            fence = Some((marker.to_string(), i, info.to_string()));
+            continue;
+        }
+
+        // ATX `## Title`, or setext `Title` underlined with === / ---
+        let heading = atx_heading(line).or_else(|| {
+            let next = lines.get(i + 1)?.trim();
+            let level = match next.chars().next()? {
+                '=' if next.chars().all(|c| c == '=') => 1,
+                '-' if next.chars().all(|c| c == '-') && next.len() >= 2 => 2,
+                _ => return None,
+            };
+            let text = line.trim();
+            (!text.is_empty() && !line.starts_with([' ', '\t', '-', '*', '>']))
+                .then_some((level, text))
+        });
+        if let Some((level, text)) = heading {
+            close(&mut symbols, &mut open, level, last_content(&lines, i));
+            open.push((level, symbols.len()));
+            symbols.push(symbol(text, SymbolKind::Section, line, i, i));
+        }
+    }
+    close(
+        &mut symbols,
+        &mut open,
+        0,
+        last_content(&lines, lines.len()),
+    );
+    symbols.sort_by_key(|s| s.start_line);
+    symbols
+}
+
+/// Last non-blank line before `line`
+fn last_content(lines: &[&str], line: usize) -> usize {
+    (0..line)
+        .rev()
+        .find(|&i| !lines[i].trim().is_empty())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spans(symbols: &[Symbol]) -> Vec<(&str, SymbolKind, usize, usize)> {
+        symbols
+            .iter()
+            .map(|s| (s.name.as_str(), s.kind, s.start_line, s.end_line))
+            .collect()
+    }
+
+    #[test]
+    fn dockerfile_stages_and_instructions() {
+        let source = "\
+# syntax=docker/dockerfile:1
+FROM rust:1.80 AS builder
+RUN apt-get update && \\
+    apt-get install -y git
+
+COPY . .
+FROM debian:stable
+COPY --from=builder /app /app
+";
+        let symbols = dockerfile_symbols(source);
+        assert_eq!(
+            spans(&symbols),
+            vec![
+                ("builder", SymbolKind::Stage, 2, 6),
+                ("FROM", SymbolKind::Instruction, 2, 2),
+                ("RUN", SymbolKind::Instruction, 3, 4),
+                ("COPY", SymbolKind::Instruction, 6, 6),
+                ("1", SymbolKind::Stage, 7, 8),
+                ("FROM", SymbolKind::Instruction, 7, 7),
+                ("COPY", SymbolKind::Instruction, 8, 8),
+            ]
+        );
+    }
+
+    #[test]
+    fn yaml_keys_and_workflow_jobs() {
+        let source = "\
+name: CI
+on:
+  push:
+
+jobs:
+  test:
+    runs-on: ubuntu-latest
+    steps:
+      - uses: actions/checkout@v4
+      - name: Run tests
+        run: cargo test
+  lint:
+    steps:
+      - run: |
+          cargo clippy
+";
+        let symbols = yaml_symbols(source);
+        assert_eq!(
+            spans(&symbols),
+            vec![
+                ("name", SymbolKind::Key, 1, 1),
+                ("on", SymbolKind::Key, 2, 3),
+                ("jobs", SymbolKind::Key, 5, 15),
+                ("test", SymbolKind::Job, 6, 11),
+                ("actions/checkout@v4", SymbolKind::Step, 9, 9),
+                ("Run tests", SymbolKind::Step, 10, 11),
+                ("lint", SymbolKind::Job, 12, 15),
+                ("step-1", SymbolKind::Step, 14, 15),
+            ]
+        );
+    }
+
+    #[test]
+    fn markdown_sections_and_code_blocks() {
+        let source = "\
+# agentjj
+
+Intro.
+
+## Installation
+
+```bash
+# not a heading
+cargo install agentjj
+```
+
+## Usage ##
+
+Setext
+------
+
+Text.
+";
+        let symbols = markdown_symbols(source);
+        assert_eq!(
+            spans(&symbols),
+            vec![
+                ("agentjj", SymbolKind::Section, 1, 17),
+                ("Installation", SymbolKind::Section, 5, 10),
+                ("code-1", SymbolKind::CodeBlock, 7, 10),
+                ("Usage", SymbolKind::Section, 12, 12),
+                ("Setext", SymbolKind::Section, 14, 17),
+            ]
+        );
+        assert_eq!(symbols[2].docstring.as_deref(), Some("bash"));
+        assert_eq!(heading_slug("Quick Start!"), "quick-start");
+    }
+}
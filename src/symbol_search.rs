@@ -0,0 +1,182 @@
+// ABOUTME: `symbol --find`: symbols across the repository ranked against a name query
+// ABOUTME: Exact, prefix, substring, typo-tolerant, and subsequence matches, scored for ranking
+
+use serde::Serialize;
+
+use crate::symbols::{Symbol, SymbolKind};
+
+/// A symbol that matched the query
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SymbolMatch {
+    pub file: String,
+    pub name: String,
+    pub kind: SymbolKind,
+    /// The class, impl, or other symbol it is nested in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    pub line: usize,
+    pub end_line: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    /// 1 to 100; higher is a closer match
+    pub score: u32,
+}
+
+/// How well `name` matches `query`, or `None` when it doesn't. Case only
+/// matters to tell an exact match from one that differs in case.
+pub fn score(query: &str, name: &str) -> Option<u32> {
+    if query.is_empty() {
+        return None;
+    }
+    if name == query {
+        return Some(100);
+    }
+    let (q, n) = (query.to_lowercase(), name.to_lowercase());
+    // Longer names rank below shorter ones with the same kind of match
+    let extra = (n.len().saturating_sub(q.len()) as u32).min(20) / 2;
+    if n == q {
+        Some(95)
+    } else if n.starts_with(&q) {
+        Some(80 - extra)
+    } else if n.contains(&q) {
+        Some(65 - extra)
+    } else if let Some(distance) = typo_distance(&q, &n) {
+        Some(55 - 10 * distance)
+    } else if q.len() >= 2 {
+        subsequence_gaps(&q, &n).map(|gaps| 40u32.saturating_sub(2 * gaps).max(1))
+    } else {
+        None
+    }
+}
+
+/// Edit distance when it is small for the length: one edit for short
+/// names, two from eight characters up
+fn typo_distance(q: &str, n: &str) -> Option<u32> {
+    let allowed = if q.len() >= 8 { 2 } else { 1 };
+    if q.len() < 4 || q.len().abs_diff(n.len()) > allowed {
+        return None;
+    }
+    let (q, n): (Vec<char>, Vec<char>) = (q.chars().collect(), n.chars().collect());
+    let mut previous: Vec<usize> = (0..=n.len()).collect();
+    for (i, qc) in q.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, nc) in n.iter().enumerate() {
+            let substitution = previous[j] + usize::from(qc != nc);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    let distance = previous[n.len()];
+    (distance <= allowed).then_some(distance as u32)
+}
+
+/// Characters skipped in `n` to find `q` as a subsequence (`prq` in
+/// `process_request`), or `None` if it is not one
+fn subsequence_gaps(q: &str, n: &str) -> Option<u32> {
+    let mut gaps = 0;
+    let mut rest = n.chars();
+    for qc in q.chars() {
+        loop {
+            let nc = rest.next()?;
+            if nc == qc {
+                break;
+            }
+            gaps += 1;
+        }
+    }
+    Some(gaps)
+}
+
+/// Symbols in one file that match `query`, nested ones included. Imports
+/// are only matched when `kind` asks for them.
+pub fn search(
+    file: &str,
+    symbols: &[Symbol],
+    query: &str,
+    kind: Option<SymbolKind>,
+) -> Vec<SymbolMatch> {
+    let all = crate::triage::flatten(symbols);
+    // The innermost class-like symbol whose lines enclose another
+    let parent = |symbol: &Symbol| -> Option<String> {
+        all.iter()
+            .filter(|c| {
+                matches!(
+                    c.kind,
+                    SymbolKind::Class
+                        | SymbolKind::Struct
+                        | SymbolKind::Interface
+                        | SymbolKind::Enum
+                        | SymbolKind::Module
+                ) && !std::ptr::eq(**c, symbol)
+                    && c.start_line <= symbol.start_line
+                    && c.end_line >= symbol.end_line
+            })
+            .min_by_key(|c| c.end_line - c.start_line)
+            .map(|c| c.name.clone())
+    };
+    all.iter()
+        .filter(|symbol| match kind {
+            Some(kind) => symbol.kind == kind,
+            None => symbol.kind != SymbolKind::Import,
+        })
+        .filter_map(|symbol| {
+            Some(SymbolMatch {
+                score: score(query, &symbol.name)?,
+                file: file.to_string(),
+                name: symbol.name.clone(),
+                kind: symbol.kind,
+                parent: parent(symbol),
+                line: symbol.start_line,
+                end_line: symbol.end_line,
+                signature: symbol.signature.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Best matches first: by score, then shorter names, then file and line
+pub fn rank(matches: &mut Vec<SymbolMatch>, limit: usize) {
+    matches.sort_by(|a, b| {
+        b.score
+            .cmp(&a.score)
+            .then_with(|| a.name.len().cmp(&b.name.len()))
+            .then_with(|| a.file.cmp(&b.file))
+            .then_with(|| a.line.cmp(&b.line))
+    });
+    matches.truncate(limit);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbols::{extract_symbols, SupportedLanguage};
+
+    #[test]
+    fn ranks_exact_prefix_substring_typo_and_subsequence_matches() {
+        assert_eq!(score("process_request", "process_request"), Some(100));
+        assert_eq!(score("processrequest", "ProcessRequest"), Some(95));
+        let prefix = score("process", "process_request").unwrap();
+        let substring = score("request", "process_request").unwrap();
+        let typo = score("proces_request", "process_request").unwrap();
+        let fuzzy = score("prcreq", "process_request").unwrap();
+        assert!(prefix > substring && substring > typo && typo > fuzzy);
+        assert_eq!(score("xyz", "process_request"), None);
+
+        let source = "class Handler:\n    def process_request(self):\n        pass\n\ndef process(x):\n    return x\n\ndef unrelated():\n    pass\n";
+        let symbols = extract_symbols(source, SupportedLanguage::Python).unwrap();
+        let mut matches = search("app.py", &symbols, "process", None);
+        rank(&mut matches, 10);
+        let names: Vec<(&str, Option<&str>)> = matches
+            .iter()
+            .map(|m| (m.name.as_str(), m.parent.as_deref()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![("process", None), ("process_request", Some("Handler"))]
+        );
+        assert_eq!(matches[1].line, 2);
+
+        let classes = search("app.py", &symbols, "handler", Some(SymbolKind::Class));
+        assert_eq!(classes.len(), 1);
+    }
+}
@@ -2,10 +2,12 @@
 // ABOUTME: Provides function signatures, class definitions, and minimal context for agents
 
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::path::Path;
 use streaming_iterator::StreamingIterator;
 use tree_sitter::{Language, Parser, Query, QueryCursor};
 
+use crate::docs::DocComment;
 use crate::error::{Error, Result};
 
 /// A symbol extracted from source code
@@ -34,6 +36,33 @@ pub enum SymbolKind {
     Variable,
     Module,
     Import,
+    Typedef,
+    Macro,
+    /// Dockerfile build stage
+    Stage,
+    /// Dockerfile instruction
+    Instruction,
+    /// YAML top-level key
+    Key,
+    /// GitHub Actions job
+    Job,
+    /// GitHub Actions step
+    Step,
+    /// Markdown heading and everything under it
+    Section,
+    /// Fenced Markdown code block
+    #[serde(rename = "code_block")]
+    CodeBlock,
+    /// SQL table
+    Table,
+    /// SQL column (named `table.column`)
+    Column,
+    /// SQL index
+    Index,
+    /// SQL view
+    View,
+    /// SQL ALTER statement
+    Alter,
 }
 
 /// Supported languages for symbol extraction
@@ -43,9 +72,57 @@ pub enum SupportedLanguage {
     Rust,
     JavaScript,
     TypeScript,
+    C,
+    Cpp,
+    Ruby,
+    Php,
+    Shell,
+    Dockerfile,
+    Yaml,
+    Markdown,
+    Sql,
+    Notebook,
 }
 
 impl SupportedLanguage {
+    /// Every language symbols can be extracted from
+    pub const ALL: [SupportedLanguage; 14] = [
+        Self::Python,
+        Self::Rust,
+        Self::JavaScript,
+        Self::TypeScript,
+        Self::C,
+        Self::Cpp,
+        Self::Ruby,
+        Self::Php,
+        Self::Shell,
+        Self::Dockerfile,
+        Self::Yaml,
+        Self::Markdown,
+        Self::Sql,
+        Self::Notebook,
+    ];
+
+    /// Name reported to agents (orient, capabilities); `from_name` accepts it
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Python => "python",
+            Self::Rust => "rust",
+            Self::JavaScript => "javascript",
+            Self::TypeScript => "typescript",
+            Self::C => "c",
+            Self::Cpp => "cpp",
+            Self::Ruby => "ruby",
+            Self::Php => "php",
+            Self::Shell => "shell",
+            Self::Dockerfile => "dockerfile",
+            Self::Yaml => "yaml",
+            Self::Markdown => "markdown",
+            Self::Sql => "sql",
+            Self::Notebook => "ipynb",
+        }
+    }
+
     /// Detect language from file extension
     pub fn from_extension(ext: &str) -> Option<Self> {
         match ext.to_lowercase().as_str() {
@@ -53,25 +130,77 @@ impl SupportedLanguage {
             "rs" => Some(Self::Rust),
             "js" | "jsx" | "mjs" => Some(Self::JavaScript),
             "ts" | "tsx" => Some(Self::TypeScript),
+            "c" | "h" => Some(Self::C),
+            "cc" | "cpp" | "cxx" | "c++" | "hh" | "hpp" | "hxx" | "h++" => Some(Self::Cpp),
+            "rb" | "rake" => Some(Self::Ruby),
+            "php" => Some(Self::Php),
+            "sh" | "bash" | "zsh" => Some(Self::Shell),
+            "dockerfile" | "containerfile" => Some(Self::Dockerfile),
+            "yml" | "yaml" => Some(Self::Yaml),
+            "md" | "markdown" => Some(Self::Markdown),
+            "sql" => Some(Self::Sql),
+            "ipynb" => Some(Self::Notebook),
             _ => None,
         }
     }
 
+    /// Parse a language name as given on the command line; file
+    /// extensions work too
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "python" => Some(Self::Python),
+            "rust" => Some(Self::Rust),
+            "javascript" => Some(Self::JavaScript),
+            "typescript" => Some(Self::TypeScript),
+            "cpp" | "c++" => Some(Self::Cpp),
+            "ruby" => Some(Self::Ruby),
+            "shell" => Some(Self::Shell),
+            "markdown" => Some(Self::Markdown),
+            "notebook" | "jupyter" => Some(Self::Notebook),
+            other => Self::from_extension(other),
+        }
+    }
+
     /// Detect language from file path
     pub fn from_path(path: &Path) -> Option<Self> {
+        // Dockerfile, Dockerfile.dev, Containerfile
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        let stem = name.split('.').next().unwrap_or("").to_lowercase();
+        if stem == "dockerfile" || stem == "containerfile" {
+            return Some(Self::Dockerfile);
+        }
         path.extension()
             .and_then(|e| e.to_str())
             .and_then(Self::from_extension)
     }
 
-    /// Get the tree-sitter language
-    fn tree_sitter_language(&self) -> Language {
-        match self {
+    /// Whether a path is a C/C++ header (what it declares is its public API)
+    pub fn is_header(path: &Path) -> bool {
+        path.extension().and_then(|e| e.to_str()).is_some_and(|e| {
+            matches!(
+                e.to_lowercase().as_str(),
+                "h" | "hh" | "hpp" | "hxx" | "h++"
+            )
+        })
+    }
+
+    /// Get the tree-sitter language (None for line-parsed formats)
+    fn tree_sitter_language(&self) -> Option<Language> {
+        let language = match self {
             Self::Python => tree_sitter_python::LANGUAGE.into(),
             Self::Rust => tree_sitter_rust::LANGUAGE.into(),
             Self::JavaScript => tree_sitter_javascript::LANGUAGE.into(),
             Self::TypeScript => tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
-        }
+            Self::C => tree_sitter_c::LANGUAGE.into(),
+            Self::Cpp => tree_sitter_cpp::LANGUAGE.into(),
+            Self::Ruby => tree_sitter_ruby::LANGUAGE.into(),
+            Self::Php => tree_sitter_php::LANGUAGE_PHP.into(),
+            Self::Shell => tree_sitter_bash::LANGUAGE.into(),
+            Self::Dockerfile | Self::Yaml | Self::Markdown | Self::Sql | Self::Notebook => {
+                return None
+            }
+        };
+        Some(language)
     }
 
     /// Get the query for extracting symbols
@@ -80,6 +209,12 @@ impl SupportedLanguage {
             Self::Python => PYTHON_SYMBOL_QUERY,
             Self::Rust => RUST_SYMBOL_QUERY,
             Self::JavaScript | Self::TypeScript => JS_SYMBOL_QUERY,
+            Self::C => C_SYMBOL_QUERY,
+            Self::Cpp => CPP_SYMBOL_QUERY,
+            Self::Ruby => RUBY_SYMBOL_QUERY,
+            Self::Php => PHP_SYMBOL_QUERY,
+            Self::Shell => SHELL_SYMBOL_QUERY,
+            Self::Dockerfile | Self::Yaml | Self::Markdown | Self::Sql | Self::Notebook => "",
         }
     }
 }
@@ -162,11 +297,198 @@ const JS_SYMBOL_QUERY: &str = r#"
 ) @const.def
 "#;
 
+// Shared by C and C++: functions (definitions and prototypes, including
+// pointer-returning ones), structs, unions, enums, typedefs, macros, includes.
+// A macro so C++ can `concat!` onto it.
+macro_rules! c_common_query {
+    () => {
+        r#"
+(function_definition
+  declarator: [
+    (function_declarator declarator: (identifier) @function.name)
+    (pointer_declarator declarator: (function_declarator declarator: (identifier) @function.name))
+  ]
+) @function.def
+
+(declaration
+  declarator: [
+    (function_declarator declarator: (identifier) @function.name)
+    (pointer_declarator declarator: (function_declarator declarator: (identifier) @function.name))
+  ]
+) @function.def
+
+(struct_specifier
+  name: (type_identifier) @struct.name
+  body: (field_declaration_list)
+) @struct.def
+
+(union_specifier
+  name: (type_identifier) @struct.name
+  body: (field_declaration_list)
+) @struct.def
+
+(enum_specifier
+  name: (type_identifier) @enum.name
+  body: (enumerator_list)
+) @enum.def
+
+(type_definition
+  declarator: (type_identifier) @typedef.name
+) @typedef.def
+
+(preproc_def
+  name: (identifier) @macro.name
+) @macro.def
+
+(preproc_function_def
+  name: (identifier) @macro.name
+) @macro.def
+
+(preproc_include
+  path: (_) @include.path
+) @include.def
+"#
+    };
+}
+
+const C_SYMBOL_QUERY: &str = c_common_query!();
+
+// C++ adds classes, namespaces, `using` aliases, and member functions
+// (inline in the class body, declared there, or defined as `Class::name`)
+const CPP_SYMBOL_QUERY: &str = concat!(
+    c_common_query!(),
+    r#"
+(class_specifier
+  name: (type_identifier) @class.name
+  body: (field_declaration_list)
+) @class.def
+
+(namespace_definition
+  name: (namespace_identifier) @module.name
+) @module.def
+
+(alias_declaration
+  name: (type_identifier) @typedef.name
+) @typedef.def
+
+(function_definition
+  declarator: [
+    (function_declarator declarator: [(field_identifier) (qualified_identifier) (destructor_name) (operator_name)] @method.name)
+    (pointer_declarator declarator: (function_declarator declarator: [(field_identifier) (qualified_identifier)] @method.name))
+    (reference_declarator (function_declarator declarator: [(identifier) (field_identifier) (qualified_identifier)] @method.name))
+  ]
+) @method.def
+
+(field_declaration
+  declarator: (function_declarator declarator: (field_identifier) @method.name)
+) @method.def
+"#
+);
+
+const RUBY_SYMBOL_QUERY: &str = r#"
+(class
+  name: (_) @class.name
+) @class.def
+
+(module
+  name: (_) @module.name
+) @module.def
+
+(method
+  name: (_) @method.name
+) @method.def
+
+(singleton_method
+  name: (_) @method.name
+) @method.def
+
+(call
+  method: (identifier) @attr.macro
+  arguments: (argument_list (simple_symbol) @attr.name)
+  (#match? @attr.macro "^attr_(reader|writer|accessor)$")
+) @attr.def
+
+(call
+  method: (identifier) @require.macro
+  arguments: (argument_list (string (string_content) @include.path))
+  (#match? @require.macro "^require(_relative)?$")
+) @include.def
+"#;
+
+const PHP_SYMBOL_QUERY: &str = r#"
+(namespace_definition
+  name: (namespace_name) @module.name
+) @module.def
+
+(namespace_use_declaration
+  (namespace_use_clause
+    [(name) (qualified_name)] @include.path)
+) @include.def
+
+(class_declaration
+  name: (name) @class.name
+) @class.def
+
+(interface_declaration
+  name: (name) @trait.name
+) @trait.def
+
+(trait_declaration
+  name: (name) @trait.name
+) @trait.def
+
+(enum_declaration
+  name: (name) @enum.name
+) @enum.def
+
+(function_definition
+  name: (name) @function.name
+) @function.def
+
+(method_declaration
+  name: (name) @method.name
+) @method.def
+"#;
+
+const SHELL_SYMBOL_QUERY: &str = r#"
+(function_definition
+  name: (word) @function.name
+) @function.def
+
+(command
+  name: (command_name (word) @source.command)
+  argument: (_) @include.path
+  (#match? @source.command "^(source|\\.)$")
+) @include.def
+"#;
+
 /// Extract symbols from source code
 pub fn extract_symbols(source: &str, language: SupportedLanguage) -> Result<Vec<Symbol>> {
+    // Notebooks: symbols of the code cells, with lines in the rendered view
+    if language == SupportedLanguage::Notebook {
+        let notebook = crate::notebook::Notebook::parse(source)?;
+        return match notebook.code_language() {
+            Some(code) if code != SupportedLanguage::Notebook => {
+                extract_symbols(&notebook.render(), code)
+            }
+            _ => Ok(Vec::new()),
+        };
+    }
+    let ts_language = match language {
+        SupportedLanguage::Dockerfile => return Ok(crate::structure::dockerfile_symbols(source)),
+        SupportedLanguage::Yaml => return Ok(crate::structure::yaml_symbols(source)),
+        SupportedLanguage::Markdown => return Ok(crate::structure::markdown_symbols(source)),
+        SupportedLanguage::Sql => return Ok(crate::sql::sql_symbols(source)),
+        _ => language
+            .tree_sitter_language()
+            .ok_or_else(|| Error::Repository {
+                message: format!("no grammar for {:?}", language),
+            })?,
+    };
+
     let mut parser = Parser::new();
     parser
-        .set_language(&language.tree_sitter_language())
+        .set_language(&ts_language)
         .map_err(|e| Error::Repository {
             message: format!("Failed to set language: {}", e),
         })?;
@@ -178,10 +500,8 @@ pub fn extract_symbols(source: &str, language: SupportedLanguage) -> Result<Vec<
         })?;
 
     let query =
-        Query::new(&language.tree_sitter_language(), language.symbol_query()).map_err(|e| {
-            Error::Repository {
-                message: format!("Failed to compile query: {}", e),
-            }
+        Query::new(&ts_language, language.symbol_query()).map_err(|e| Error::Repository {
+            message: format!("Failed to compile query: {}", e),
         })?;
 
     let mut cursor = QueryCursor::new();
@@ -199,6 +519,7 @@ pub fn extract_symbols(source: &str, language: SupportedLanguage) -> Result<Vec<
         let mut kind = SymbolKind::Function;
         let mut signature = None;
         let mut docstring = None;
+        let mut def_node = None;
         let mut start_line = 0;
         let mut end_line = 0;
 
@@ -232,30 +553,53 @@ pub fn extract_symbols(source: &str, language: SupportedLanguage) -> Result<Vec<
                     name = Some(text.to_string());
                     kind = SymbolKind::Interface;
                 }
+                "typedef.name" => {
+                    name = Some(text.to_string());
+                    kind = SymbolKind::Typedef;
+                }
+                "macro.name" => {
+                    name = Some(text.to_string());
+                    kind = SymbolKind::Macro;
+                }
+                "module.name" => {
+                    name = Some(text.to_string());
+                    kind = SymbolKind::Module;
+                }
+                "include.path" => {
+                    // `<stdio.h>` or `"util.h"` -> the header path
+                    name = Some(text.trim_matches(['<', '>', '"']).to_string());
+                    kind = SymbolKind::Import;
+                }
+                "attr.name" => {
+                    // `attr_reader :name` defines a `name` method
+                    name = Some(text.trim_start_matches(':').to_string());
+                    kind = SymbolKind::Method;
+                }
                 "function.def" | "method.def" | "class.def" | "struct.def" | "enum.def"
-                | "trait.def" => {
+                | "trait.def" | "typedef.def" | "macro.def" | "module.def" | "include.def"
+                | "attr.def" => {
                     start_line = node.start_position().row + 1;
                     end_line = node.end_position().row + 1;
+                    def_node = Some(node);
                     // Extract first line as signature
                     let first_line = text.lines().next().unwrap_or(text);
                     signature = Some(first_line.to_string());
                 }
                 "function.docstring" | "class.docstring" => {
-                    // Clean up the docstring - remove quotes and leading/trailing whitespace
-                    let cleaned = text
-                        .trim_start_matches("\"\"\"")
-                        .trim_start_matches("'''")
-                        .trim_end_matches("\"\"\"")
-                        .trim_end_matches("'''")
-                        .trim();
+                    // Remove the quotes and the body's indentation
+                    let cleaned = crate::docs::normalize(text, language);
                     if !cleaned.is_empty() {
-                        docstring = Some(cleaned.to_string());
+                        docstring = Some(cleaned);
                     }
                 }
                 _ => {}
             }
         }
 
+        if docstring.is_none() {
+            docstring = def_node.and_then(|node| leading_doc_comment(node, language, source_bytes));
+        }
+
         if let Some(n) = name {
             symbols.push(Symbol {
                 name: n,
@@ -270,12 +614,70 @@ pub fn extract_symbols(source: &str, language: SupportedLanguage) -> Result<Vec<
     }
 
     // Deduplicate by name and line
-    symbols.sort_by(|a, b| a.start_line.cmp(&b.start_line));
+    symbols.sort_by_key(|a| a.start_line);
     symbols.dedup_by(|a, b| a.name == b.name && a.start_line == b.start_line);
 
     Ok(symbols)
 }
 
+/// Doc comment directly above a definition (`///`, `/** */`), normalized
+fn leading_doc_comment(
+    node: tree_sitter::Node,
+    language: SupportedLanguage,
+    source: &[u8],
+) -> Option<String> {
+    // `export function f` documents the export statement, not the function
+    let node = match node.parent() {
+        Some(parent) if parent.kind() == "export_statement" => parent,
+        _ => node,
+    };
+
+    let mut comments = Vec::new();
+    let mut next_row = node.start_position().row;
+    // Comments before the first statement of a body can belong to the
+    // enclosing node (Ruby's `module`), ahead of the body itself
+    let mut sibling = node.prev_sibling().or_else(|| {
+        node.parent()
+            .filter(|parent| parent.start_byte() == node.start_byte())
+            .and_then(|parent| parent.prev_sibling())
+    });
+    while let Some(prev) = sibling {
+        // Comments must touch the definition (or the block above it)
+        if prev.end_position().row + 1 < next_row {
+            break;
+        }
+        let text = prev.utf8_text(source).unwrap_or("").trim_end();
+        match prev.kind() {
+            "attribute_item" | "decorator" => {}
+            "line_comment" | "comment" if text.starts_with("///") && !text.starts_with("////") => {
+                comments.push(text)
+            }
+            // Ruby and shell document with `#` comment blocks
+            "comment"
+                if matches!(language, SupportedLanguage::Ruby | SupportedLanguage::Shell)
+                    && text.starts_with('#')
+                    && !text.starts_with("#!") =>
+            {
+                comments.push(text)
+            }
+            "block_comment" | "comment" if text.starts_with("/**") => {
+                comments.push(text);
+                break;
+            }
+            _ => break,
+        }
+        next_row = prev.start_position().row;
+        sibling = prev.prev_sibling();
+    }
+    if comments.is_empty() {
+        return None;
+    }
+    comments.reverse();
+
+    let doc = crate::docs::normalize(&comments.join("\n"), language);
+    (!doc.is_empty()).then_some(doc)
+}
+
 /// Find a specific symbol by name in a file
 pub fn find_symbol(
     source: &str,
@@ -292,17 +694,225 @@ pub fn get_symbol_context(
     language: SupportedLanguage,
     symbol_name: &str,
 ) -> Result<Option<SymbolContext>> {
-    let symbol = find_symbol(source, language, symbol_name)?;
-
-    Ok(symbol.map(|s| SymbolContext {
-        name: s.name,
-        kind: s.kind,
-        signature: s.signature,
-        docstring: s.docstring,
-        imports_needed: Vec::new(), // TODO: analyze imports
+    if language == SupportedLanguage::Notebook {
+        let notebook = crate::notebook::Notebook::parse(source)?;
+        return match notebook.code_language() {
+            Some(code) if code != SupportedLanguage::Notebook => {
+                get_symbol_context(&notebook.render(), code, symbol_name)
+            }
+            _ => Ok(None),
+        };
+    }
+
+    let mut symbols = extract_symbols(source, language)?;
+
+    // C/C++ definitions compile against the file's #includes; Ruby and PHP
+    // code relies on its `require`s and `use`s
+    let imports_needed = match language {
+        SupportedLanguage::C
+        | SupportedLanguage::Cpp
+        | SupportedLanguage::Ruby
+        | SupportedLanguage::Php => symbols
+            .iter()
+            .filter(|s| s.kind == SymbolKind::Import)
+            .filter_map(|s| s.signature.as_deref().map(|sig| sig.trim().to_string()))
+            .collect(),
+        _ => Vec::new(), // TODO: analyze imports
+    };
+
+    // Markdown sections can also be addressed by their anchor (`quick-start`)
+    let symbol = symbols
+        .iter()
+        .position(|s| s.name == symbol_name && s.kind != SymbolKind::Import)
+        .or_else(|| {
+            symbols.iter().position(|s| {
+                s.kind == SymbolKind::Section
+                    && crate::structure::heading_slug(&s.name) == symbol_name
+            })
+        })
+        .map(|i| symbols.swap_remove(i));
+
+    Ok(symbol.map(|s| {
+        // A section's context is the section itself
+        let body = (s.kind == SymbolKind::Section).then(|| {
+            source
+                .lines()
+                .skip(s.start_line - 1)
+                .take(s.end_line + 1 - s.start_line)
+                .collect::<Vec<_>>()
+                .join("\n")
+        });
+        let is_doc = matches!(s.kind, SymbolKind::Section | SymbolKind::CodeBlock);
+        SymbolContext {
+            name: s.name,
+            kind: s.kind,
+            signature: s.signature,
+            doc: s
+                .docstring
+                .as_deref()
+                .filter(|_| !is_doc)
+                .map(crate::docs::parse),
+            docstring: s.docstring,
+            body,
+            imports_needed,
+        }
     }))
 }
 
+/// Check if a symbol is public based on language conventions
+pub fn is_public_symbol(symbol: &Symbol, lang: SupportedLanguage) -> bool {
+    match lang {
+        SupportedLanguage::Rust => {
+            // Rust: check for "pub" keyword in signature
+            symbol
+                .signature
+                .as_ref()
+                .map(|sig: &String| sig.contains("pub"))
+                .unwrap_or(false)
+        }
+        SupportedLanguage::Python | SupportedLanguage::Notebook => {
+            // Python: underscore prefix means private (convention)
+            !symbol.name.starts_with('_')
+        }
+        SupportedLanguage::JavaScript | SupportedLanguage::TypeScript => {
+            // JS/TS: check for "export" keyword in signature
+            symbol
+                .signature
+                .as_ref()
+                .map(|sig: &String| sig.contains("export"))
+                .unwrap_or(true)
+        }
+        SupportedLanguage::C | SupportedLanguage::Cpp => {
+            // C/C++: `static` means internal linkage
+            !symbol
+                .signature
+                .as_ref()
+                .is_some_and(|sig| sig.split_whitespace().any(|w| w == "static"))
+        }
+        SupportedLanguage::Ruby => {
+            // Ruby: `initialize` is always private
+            symbol.name != "initialize"
+        }
+        SupportedLanguage::Shell => {
+            // Shell: underscore prefix marks internal helpers (convention)
+            !symbol.name.starts_with('_')
+        }
+        SupportedLanguage::Dockerfile
+        | SupportedLanguage::Yaml
+        | SupportedLanguage::Markdown
+        | SupportedLanguage::Sql => true,
+        SupportedLanguage::Php => {
+            // PHP: members are public unless marked private/protected
+            !symbol.signature.as_ref().is_some_and(|sig| {
+                sig.split_whitespace()
+                    .any(|w| w == "private" || w == "protected")
+            })
+        }
+    }
+}
+
+/// Header-aware visibility for a symbol defined in `path`. C/C++ symbols
+/// are public when declared in a header: either `path` is a header, or the
+/// name is in `header_names` (collected from the headers the file includes).
+/// Other languages fall back to `is_public_symbol`.
+pub fn is_public_symbol_in(
+    symbol: &Symbol,
+    lang: SupportedLanguage,
+    path: &Path,
+    header_names: &HashSet<String>,
+) -> bool {
+    match lang {
+        SupportedLanguage::C | SupportedLanguage::Cpp => {
+            is_public_symbol(symbol, lang)
+                && (SupportedLanguage::is_header(path) || header_names.contains(&symbol.name))
+        }
+        _ => is_public_symbol(symbol, lang),
+    }
+}
+
+/// Resolve an import symbol of a file in `from` to a file in the repo:
+///
+/// - C/C++ quoted `#include`s: next to the including file, then `<root>`
+///   and `<root>/include` (like `-I`). System includes (`<...>`) never resolve.
+/// - Ruby: `require_relative` from the file's directory; `require` from
+///   `<root>/lib` and `<root>`.
+/// - PHP `use` statements: composer.json PSR-4 prefixes, then `<root>/src`
+///   and `<root>` by namespace path.
+pub fn resolve_include(
+    include: &Symbol,
+    lang: SupportedLanguage,
+    from: &Path,
+    root: &Path,
+) -> Option<std::path::PathBuf> {
+    if include.kind != SymbolKind::Import {
+        return None;
+    }
+    let signature = include.signature.as_deref().unwrap_or("");
+    let dir = from.parent().unwrap_or(Path::new("")).to_path_buf();
+    let candidates: Vec<std::path::PathBuf> = match lang {
+        SupportedLanguage::C | SupportedLanguage::Cpp => {
+            if signature.contains('<') {
+                return None;
+            }
+            [dir, root.to_path_buf(), root.join("include")]
+                .into_iter()
+                .map(|base| base.join(&include.name))
+                .collect()
+        }
+        SupportedLanguage::Ruby => {
+            let file = if include.name.ends_with(".rb") {
+                include.name.clone()
+            } else {
+                format!("{}.rb", include.name)
+            };
+            let bases = if signature.starts_with("require_relative") {
+                vec![dir]
+            } else {
+                vec![root.join("lib"), root.to_path_buf()]
+            };
+            bases.into_iter().map(|base| base.join(&file)).collect()
+        }
+        SupportedLanguage::Php => {
+            let class = include.name.trim_start_matches('\\');
+            let mut candidates: Vec<std::path::PathBuf> = psr4_prefixes(root)
+                .into_iter()
+                .filter_map(|(prefix, base)| {
+                    let rest = class.strip_prefix(prefix.trim_end_matches('\\'))?;
+                    let rest = rest.strip_prefix('\\')?;
+                    Some(
+                        root.join(base)
+                            .join(format!("{}.php", rest.replace('\\', "/"))),
+                    )
+                })
+                .collect();
+            let path = format!("{}.php", class.replace('\\', "/"));
+            candidates.push(root.join("src").join(&path));
+            candidates.push(root.join(&path));
+            candidates
+        }
+        _ => return None,
+    };
+    candidates.into_iter().find(|candidate| candidate.is_file())
+}
+
+/// `autoload.psr-4` namespace prefixes and their directories from composer.json
+fn psr4_prefixes(root: &Path) -> Vec<(String, String)> {
+    let Ok(content) = std::fs::read_to_string(root.join("composer.json")) else {
+        return Vec::new();
+    };
+    let Ok(composer) = serde_json::from_str::<serde_json::Value>(&content) else {
+        return Vec::new();
+    };
+    composer["autoload"]["psr-4"]
+        .as_object()
+        .map(|map| {
+            map.iter()
+                .filter_map(|(prefix, dir)| Some((prefix.clone(), dir.as_str()?.to_string())))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
 /// Minimal context needed to use a symbol
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SymbolContext {
@@ -310,6 +920,12 @@ pub struct SymbolContext {
     pub kind: SymbolKind,
     pub signature: Option<String>,
     pub docstring: Option<String>,
+    /// The docstring split into summary, params, returns, and examples
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub doc: Option<DocComment>,
+    /// Full text of a Markdown section, heading included
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
     pub imports_needed: Vec<String>,
 }
 
@@ -489,4 +1105,327 @@ class NoDocClass:
         let no_doc = symbols.iter().find(|s| s.name == "NoDocClass").unwrap();
         assert!(no_doc.docstring.is_none());
     }
+
+    #[test]
+    fn extract_rust_and_jsdoc_comments() {
+        let rust = r#"
+/// Parse a config.
+///
+/// # Arguments
+///
+/// * `path` - Where to read from
+#[inline]
+pub fn parse(path: &str) {}
+
+// Not a doc comment
+fn plain() {}
+"#;
+        let symbols = extract_symbols(rust, SupportedLanguage::Rust).unwrap();
+        let parse = symbols.iter().find(|s| s.name == "parse").unwrap();
+        assert_eq!(
+            parse.docstring.as_deref(),
+            Some("Parse a config.\n\n# Arguments\n\n* `path` - Where to read from")
+        );
+        let plain = symbols.iter().find(|s| s.name == "plain").unwrap();
+        assert!(plain.docstring.is_none());
+
+        let js = r#"
+/**
+ * Fetch a user.
+ * @param {number} id - The id
+ */
+export function fetchUser(id) {}
+"#;
+        let ctx = get_symbol_context(js, SupportedLanguage::JavaScript, "fetchUser")
+            .unwrap()
+            .unwrap();
+        let doc = ctx.doc.unwrap();
+        assert_eq!(doc.summary, "Fetch a user.");
+        assert_eq!(doc.params[0].name, "id");
+        assert_eq!(doc.params[0].ty.as_deref(), Some("number"));
+    }
+
+    #[test]
+    fn extract_c_symbols() {
+        let source = r#"
+#include <stdio.h>
+#include "util.h"
+
+#define MAX_LEN 64
+#define MIN(a, b) ((a) < (b) ? (a) : (b))
+
+typedef struct Point {
+    int x;
+} Point;
+
+typedef unsigned long id_t;
+
+enum Color { RED, GREEN };
+
+/// Add two numbers.
+int add(int a, int b);
+
+static char *name_of(int id) {
+    return 0;
+}
+"#;
+        let symbols = extract_symbols(source, SupportedLanguage::C).unwrap();
+        let kind_of = |name: &str| symbols.iter().find(|s| s.name == name).map(|s| s.kind);
+
+        assert_eq!(kind_of("stdio.h"), Some(SymbolKind::Import));
+        assert_eq!(kind_of("util.h"), Some(SymbolKind::Import));
+        assert_eq!(kind_of("MAX_LEN"), Some(SymbolKind::Macro));
+        assert_eq!(kind_of("MIN"), Some(SymbolKind::Macro));
+        assert_eq!(kind_of("Point"), Some(SymbolKind::Struct));
+        assert_eq!(kind_of("id_t"), Some(SymbolKind::Typedef));
+        assert_eq!(kind_of("Color"), Some(SymbolKind::Enum));
+        assert_eq!(kind_of("add"), Some(SymbolKind::Function));
+        assert_eq!(kind_of("name_of"), Some(SymbolKind::Function));
+
+        let add = symbols.iter().find(|s| s.name == "add").unwrap();
+        assert_eq!(add.docstring.as_deref(), Some("Add two numbers."));
+        assert!(is_public_symbol(add, SupportedLanguage::C));
+        let name_of = symbols.iter().find(|s| s.name == "name_of").unwrap();
+        assert!(!is_public_symbol(name_of, SupportedLanguage::C));
+
+        // Defined in a .c file: public only if a header declares it
+        let headers: HashSet<String> = ["add".to_string()].into();
+        let c_file = Path::new("src/math.c");
+        assert!(is_public_symbol_in(
+            add,
+            SupportedLanguage::C,
+            c_file,
+            &headers
+        ));
+        assert!(!is_public_symbol_in(
+            add,
+            SupportedLanguage::C,
+            c_file,
+            &HashSet::new()
+        ));
+        assert!(is_public_symbol_in(
+            add,
+            SupportedLanguage::C,
+            Path::new("include/math.h"),
+            &HashSet::new()
+        ));
+
+        let ctx = get_symbol_context(source, SupportedLanguage::C, "add")
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            ctx.imports_needed,
+            vec!["#include <stdio.h>", "#include \"util.h\""]
+        );
+    }
+
+    #[test]
+    fn extract_cpp_symbols() {
+        let source = r#"
+namespace geo {
+
+class Shape {
+public:
+    virtual double area() const;
+    int sides() { return 0; }
+};
+
+using Id = int;
+
+}
+
+double geo::Shape::area() const { return 0.0; }
+
+template <typename T>
+T clamp(T v) { return v; }
+"#;
+        let symbols = extract_symbols(source, SupportedLanguage::Cpp).unwrap();
+        let kind_of = |name: &str| symbols.iter().find(|s| s.name == name).map(|s| s.kind);
+
+        assert_eq!(kind_of("geo"), Some(SymbolKind::Module));
+        assert_eq!(kind_of("Shape"), Some(SymbolKind::Class));
+        assert_eq!(kind_of("area"), Some(SymbolKind::Method));
+        assert_eq!(kind_of("sides"), Some(SymbolKind::Method));
+        assert_eq!(kind_of("Id"), Some(SymbolKind::Typedef));
+        assert_eq!(kind_of("geo::Shape::area"), Some(SymbolKind::Method));
+        assert_eq!(kind_of("clamp"), Some(SymbolKind::Function));
+        assert_eq!(
+            SupportedLanguage::from_path(Path::new("src/shape.hpp")),
+            Some(SupportedLanguage::Cpp)
+        );
+        assert!(SupportedLanguage::is_header(Path::new("src/shape.hpp")));
+        assert!(!SupportedLanguage::is_header(Path::new("src/shape.cpp")));
+    }
+
+    #[test]
+    fn extract_ruby_symbols() {
+        let source = r#"
+require "json"
+require_relative "support/base"
+
+module Billing
+  # Charges a customer.
+  #
+  # @param amount [Integer] cents to charge
+  class Invoice < Base
+    attr_reader :total, :currency
+
+    def initialize(total)
+      @total = total
+    end
+
+    def self.build(attrs)
+      new(attrs)
+    end
+
+    def paid?
+      true
+    end
+  end
+end
+"#;
+        let symbols = extract_symbols(source, SupportedLanguage::Ruby).unwrap();
+        let kind_of = |name: &str| symbols.iter().find(|s| s.name == name).map(|s| s.kind);
+
+        assert_eq!(kind_of("json"), Some(SymbolKind::Import));
+        assert_eq!(kind_of("support/base"), Some(SymbolKind::Import));
+        assert_eq!(kind_of("Billing"), Some(SymbolKind::Module));
+        assert_eq!(kind_of("Invoice"), Some(SymbolKind::Class));
+        assert_eq!(kind_of("total"), Some(SymbolKind::Method));
+        assert_eq!(kind_of("currency"), Some(SymbolKind::Method));
+        assert_eq!(kind_of("build"), Some(SymbolKind::Method));
+        assert_eq!(kind_of("paid?"), Some(SymbolKind::Method));
+
+        let init = symbols.iter().find(|s| s.name == "initialize").unwrap();
+        assert!(!is_public_symbol(init, SupportedLanguage::Ruby));
+
+        let ctx = get_symbol_context(source, SupportedLanguage::Ruby, "Invoice")
+            .unwrap()
+            .unwrap();
+        assert_eq!(ctx.doc.unwrap().summary, "Charges a customer.");
+        assert_eq!(
+            ctx.imports_needed,
+            vec!["require \"json\"", "require_relative \"support/base\""]
+        );
+    }
+
+    #[test]
+    fn extract_php_symbols() {
+        let source = r#"<?php
+namespace App\Billing;
+
+use App\Models\User;
+use Psr\Log\LoggerInterface;
+
+interface Payable {}
+
+trait Auditable {}
+
+/**
+ * An invoice.
+ */
+class Invoice implements Payable
+{
+    public function total(): int { return 0; }
+
+    private function recalc(): void {}
+}
+
+function helper() {}
+"#;
+        let symbols = extract_symbols(source, SupportedLanguage::Php).unwrap();
+        let kind_of = |name: &str| symbols.iter().find(|s| s.name == name).map(|s| s.kind);
+
+        assert_eq!(kind_of("App\\Billing"), Some(SymbolKind::Module));
+        assert_eq!(kind_of("App\\Models\\User"), Some(SymbolKind::Import));
+        assert_eq!(kind_of("Payable"), Some(SymbolKind::Interface));
+        assert_eq!(kind_of("Auditable"), Some(SymbolKind::Interface));
+        assert_eq!(kind_of("Invoice"), Some(SymbolKind::Class));
+        assert_eq!(kind_of("total"), Some(SymbolKind::Method));
+        assert_eq!(kind_of("helper"), Some(SymbolKind::Function));
+
+        let total = symbols.iter().find(|s| s.name == "total").unwrap();
+        let recalc = symbols.iter().find(|s| s.name == "recalc").unwrap();
+        assert!(is_public_symbol(total, SupportedLanguage::Php));
+        assert!(!is_public_symbol(recalc, SupportedLanguage::Php));
+
+        let ctx = get_symbol_context(source, SupportedLanguage::Php, "Invoice")
+            .unwrap()
+            .unwrap();
+        assert_eq!(ctx.docstring.as_deref(), Some("An invoice."));
+        assert_eq!(ctx.imports_needed[0], "use App\\Models\\User;");
+    }
+
+    #[test]
+    fn resolve_ruby_and_php_imports() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        std::fs::create_dir_all(root.join("lib/support")).unwrap();
+        std::fs::create_dir_all(root.join("app/Models")).unwrap();
+        std::fs::write(root.join("lib/support/base.rb"), "").unwrap();
+        std::fs::write(root.join("lib/util.rb"), "").unwrap();
+        std::fs::write(root.join("app/Models/User.php"), "").unwrap();
+        std::fs::write(
+            root.join("composer.json"),
+            r#"{"autoload": {"psr-4": {"App\\": "app/"}}}"#,
+        )
+        .unwrap();
+
+        let ruby = "require_relative \"support/base\"\nrequire \"util\"\nrequire \"json\"\n";
+        let from = root.join("lib/billing.rb");
+        let imports = extract_symbols(ruby, SupportedLanguage::Ruby).unwrap();
+        let resolved: Vec<_> = imports
+            .iter()
+            .map(|i| resolve_include(i, SupportedLanguage::Ruby, &from, root))
+            .collect();
+        assert_eq!(resolved[0], Some(root.join("lib/support/base.rb")));
+        assert_eq!(resolved[1], Some(root.join("lib/util.rb")));
+        assert_eq!(resolved[2], None);
+
+        let php = "<?php\nuse App\\Models\\User;\n";
+        let imports = extract_symbols(php, SupportedLanguage::Php).unwrap();
+        assert_eq!(
+            resolve_include(
+                &imports[0],
+                SupportedLanguage::Php,
+                &root.join("x.php"),
+                root
+            ),
+            Some(root.join("app/Models/User.php"))
+        );
+    }
+
+    #[test]
+    fn extract_shell_symbols() {
+        let source = r#"#!/bin/bash
+source ./lib/common.sh
+
+# Build the release image.
+build_image() {
+  docker build .
+}
+
+function _cleanup {
+  rm -rf tmp
+}
+"#;
+        let symbols = extract_symbols(source, SupportedLanguage::Shell).unwrap();
+        let kind_of = |name: &str| symbols.iter().find(|s| s.name == name).map(|s| s.kind);
+        assert_eq!(kind_of("./lib/common.sh"), Some(SymbolKind::Import));
+        assert_eq!(kind_of("build_image"), Some(SymbolKind::Function));
+
+        let build = symbols.iter().find(|s| s.name == "build_image").unwrap();
+        assert_eq!(build.docstring.as_deref(), Some("Build the release image."));
+        let cleanup = symbols.iter().find(|s| s.name == "_cleanup").unwrap();
+        assert!(!is_public_symbol(cleanup, SupportedLanguage::Shell));
+
+        assert_eq!(
+            SupportedLanguage::from_path(Path::new("docker/Dockerfile.dev")),
+            Some(SupportedLanguage::Dockerfile)
+        );
+        assert_eq!(
+            SupportedLanguage::from_path(Path::new(".github/workflows/ci.yml")),
+            Some(SupportedLanguage::Yaml)
+        );
+    }
 }
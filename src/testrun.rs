@@ -0,0 +1,590 @@
+// ABOUTME: Test orchestration for `test run`: result parsing, test-impact selection, stored runs
+// ABOUTME: Runs are kept per change in .agent/tests/<change_id>.json and read back by validate
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+
+/// Counts and failing test names parsed from a runner's output
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TestSummary {
+    /// Output format recognized (cargo, pytest, jest, go); None leaves the
+    /// counts at zero and only the exit code decides
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    pub passed: usize,
+    pub failed: usize,
+    pub ignored: usize,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failing: Vec<String>,
+}
+
+/// One test command as it ran
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestCommandResult {
+    /// Invariant name, or "test" for the detected test command
+    pub name: String,
+    pub command: String,
+    pub success: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    #[serde(default)]
+    pub timed_out: bool,
+    pub duration_ms: u64,
+    pub summary: TestSummary,
+    /// Last lines of output, kept when the command failed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub output_tail: Vec<String>,
+    /// Failing tests (or this command) that history shows to be flaky
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub known_flakes: Vec<String>,
+}
+
+/// A `test run`, stored against the change it tested
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestRun {
+    pub change_id: String,
+    /// Working-copy commit when the run started; a different commit later
+    /// means the results are stale
+    pub commit_id: String,
+    pub ran_at: String,
+    pub affected_only: bool,
+    /// Test files chosen by impact analysis (`--affected-only`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub selected: Option<Vec<String>>,
+    pub commands: Vec<TestCommandResult>,
+    pub success: bool,
+}
+
+impl TestRun {
+    /// Failing test names across commands, or the failing command names when
+    /// the output could not be parsed
+    pub fn failures(&self) -> Vec<String> {
+        self.commands
+            .iter()
+            .filter(|c| !c.success)
+            .flat_map(|c| {
+                if c.summary.failing.is_empty() {
+                    vec![c.name.clone()]
+                } else {
+                    c.summary.failing.clone()
+                }
+            })
+            .collect()
+    }
+}
+
+/// One test command outcome, kept in the history flakes are found in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Outcome {
+    /// Invariant name, or "test"
+    pub name: String,
+    pub command: String,
+    /// Working-copy tree the command ran against: same tree, same code
+    pub tree_id: String,
+    pub change_id: String,
+    pub at: String,
+    pub success: bool,
+    /// Whether the output was parsed, so tests not in `failing` passed
+    pub parsed: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failing: Vec<String>,
+}
+
+impl Outcome {
+    pub fn new(
+        name: &str,
+        command: &str,
+        tree_id: &str,
+        change_id: &str,
+        success: bool,
+        summary: &TestSummary,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            command: command.to_string(),
+            tree_id: tree_id.to_string(),
+            change_id: change_id.to_string(),
+            at: crate::repo::utc_now(),
+            success,
+            parsed: summary.format.is_some(),
+            failing: summary.failing.clone(),
+        }
+    }
+
+    /// Whether `test` passed in this run, if the run tells
+    fn passed(&self, test: &str) -> Option<bool> {
+        if self.failing.iter().any(|f| f == test) {
+            Some(false)
+        } else if self.success || self.parsed {
+            Some(true)
+        } else {
+            None
+        }
+    }
+}
+
+/// A test (or whole invariant) that both passed and failed on the same code
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Flake {
+    /// Test name, or the invariant name for a flaky command
+    pub test: String,
+    pub invariant: String,
+    pub passes: usize,
+    pub failures: usize,
+    /// Distinct trees it flip-flopped on
+    pub trees: usize,
+    pub last_failed: String,
+}
+
+/// Tests with inconsistent outcomes on an unchanged tree (same command, same
+/// tree ID), most failures first
+pub fn flaky(history: &[Outcome]) -> Vec<Flake> {
+    use std::collections::BTreeMap;
+
+    let mut groups: BTreeMap<(&str, &str, &str), Vec<&Outcome>> = BTreeMap::new();
+    for outcome in history {
+        groups
+            .entry((&outcome.name, &outcome.command, &outcome.tree_id))
+            .or_default()
+            .push(outcome);
+    }
+
+    let mut flakes: BTreeMap<(String, String), Flake> = BTreeMap::new();
+    for ((invariant, _, _), runs) in groups {
+        let mut tests: Vec<(&str, Vec<Option<bool>>)> =
+            vec![(invariant, runs.iter().map(|r| Some(r.success)).collect())];
+        let mut names: Vec<&str> = runs
+            .iter()
+            .flat_map(|r| r.failing.iter().map(String::as_str))
+            .collect();
+        names.sort();
+        names.dedup();
+        for name in names {
+            tests.push((name, runs.iter().map(|r| r.passed(name)).collect()));
+        }
+
+        for (test, results) in tests {
+            let passes = results.iter().filter(|r| **r == Some(true)).count();
+            let failures = results.iter().filter(|r| **r == Some(false)).count();
+            if passes == 0 || failures == 0 {
+                continue;
+            }
+            let last_failed = runs
+                .iter()
+                .zip(&results)
+                .filter(|(_, r)| **r == Some(false))
+                .map(|(run, _)| run.at.clone())
+                .max()
+                .unwrap_or_default();
+            let flake = flakes
+                .entry((invariant.to_string(), test.to_string()))
+                .or_insert_with(|| Flake {
+                    test: test.to_string(),
+                    invariant: invariant.to_string(),
+                    passes: 0,
+                    failures: 0,
+                    trees: 0,
+                    last_failed: String::new(),
+                });
+            flake.passes += passes;
+            flake.failures += failures;
+            flake.trees += 1;
+            flake.last_failed = flake.last_failed.clone().max(last_failed);
+        }
+    }
+
+    let mut flakes: Vec<Flake> = flakes.into_values().collect();
+    flakes.sort_by(|a, b| b.failures.cmp(&a.failures).then(a.test.cmp(&b.test)));
+    flakes
+}
+
+/// Which of an invariant's failures history marks as flaky: the failing
+/// tests that are known flakes, or the invariant itself when its output
+/// named no tests
+pub fn known_flakes(flakes: &[Flake], invariant: &str, failing: &[String]) -> Vec<String> {
+    let is_flaky = |test: &str| {
+        flakes
+            .iter()
+            .any(|f| f.invariant == invariant && f.test == test)
+    };
+    if failing.is_empty() {
+        return if is_flaky(invariant) {
+            vec![invariant.to_string()]
+        } else {
+            Vec::new()
+        };
+    }
+    failing.iter().filter(|t| is_flaky(t)).cloned().collect()
+}
+
+/// Lines of output kept for a failing command
+const OUTPUT_TAIL_LINES: usize = 20;
+
+/// The last lines of a command's output
+pub fn output_tail(stdout: &str, stderr: &str) -> Vec<String> {
+    let lines: Vec<&str> = stdout.lines().chain(stderr.lines()).collect();
+    lines[lines.len().saturating_sub(OUTPUT_TAIL_LINES)..]
+        .iter()
+        .map(|l| l.to_string())
+        .collect()
+}
+
+/// Parse pass/fail counts and failing test names from cargo, pytest, jest,
+/// or `go test` output
+pub fn parse_output(output: &str) -> TestSummary {
+    let lines: Vec<&str> = output.lines().map(str::trim_end).collect();
+    let mut summary = TestSummary::default();
+
+    // cargo: one "test result:" line per test binary
+    let cargo: Vec<&&str> = lines
+        .iter()
+        .filter(|l| l.starts_with("test result:"))
+        .collect();
+    if !cargo.is_empty() {
+        for line in cargo {
+            add_counts(&mut summary, line);
+        }
+        summary.failing = lines
+            .iter()
+            .filter_map(|l| l.strip_prefix("test ")?.strip_suffix(" ... FAILED"))
+            .map(str::to_string)
+            .collect();
+        summary.format = Some("cargo".into());
+        return summary;
+    }
+
+    // jest: "Tests:       1 failed, 5 passed, 6 total"
+    if let Some(line) = lines.iter().rev().find(|l| l.trim().starts_with("Tests:")) {
+        add_counts(&mut summary, line);
+        summary.failing = lines
+            .iter()
+            .filter_map(|l| l.trim().strip_prefix("● "))
+            .map(str::to_string)
+            .collect();
+        summary.failing.dedup();
+        summary.format = Some("jest".into());
+        return summary;
+    }
+
+    // pytest: "==== 1 failed, 5 passed, 1 skipped in 0.12s ===="
+    if let Some(line) = lines.iter().rev().find(|l| is_pytest_summary(l)) {
+        add_counts(&mut summary, line);
+        summary.failing = lines
+            .iter()
+            .filter_map(|l| l.strip_prefix("FAILED "))
+            .map(|l| l.split(" - ").next().unwrap_or(l).to_string())
+            .collect();
+        summary.format = Some("pytest".into());
+        return summary;
+    }
+
+    // go test -v: "--- FAIL: TestName (0.00s)"
+    for line in &lines {
+        let line = line.trim_start();
+        if line.starts_with("--- PASS: ") {
+            summary.passed += 1;
+        } else if let Some(rest) = line.strip_prefix("--- FAIL: ") {
+            summary.failed += 1;
+            summary
+                .failing
+                .extend(rest.split_whitespace().next().map(str::to_string));
+        } else if line.starts_with("--- SKIP: ") {
+            summary.ignored += 1;
+        }
+    }
+    if summary.passed + summary.failed + summary.ignored > 0
+        || lines.iter().any(|l| l.starts_with("ok  \t"))
+    {
+        summary.format = Some("go".into());
+    }
+    summary
+}
+
+fn is_pytest_summary(line: &str) -> bool {
+    let core = line.trim_matches(|c: char| c == '=' || c.is_whitespace());
+    let Some((counts, time)) = core.rsplit_once(" in ") else {
+        return false;
+    };
+    (counts.contains(" passed") || counts.contains(" failed") || counts.contains(" error"))
+        && time
+            .split_whitespace()
+            .next()
+            .is_some_and(|t| t.ends_with('s') && t[..t.len() - 1].parse::<f64>().is_ok())
+}
+
+/// Add every "<n> passed/failed/ignored/skipped" in `line` to the counts
+fn add_counts(summary: &mut TestSummary, line: &str) {
+    let tokens: Vec<&str> = line
+        .split(|c: char| c.is_whitespace() || matches!(c, ',' | ';' | ':' | '.'))
+        .filter(|t| !t.is_empty())
+        .collect();
+    for pair in tokens.windows(2) {
+        let Ok(n) = pair[0].parse::<usize>() else {
+            continue;
+        };
+        match pair[1] {
+            "passed" => summary.passed += n,
+            "failed" | "errors" | "error" => summary.failed += n,
+            "ignored" | "skipped" | "todo" => summary.ignored += n,
+            _ => {}
+        }
+    }
+}
+
+/// Whether a file holds tests: test directories and naming conventions, or
+/// Rust files with an inline `#[cfg(test)]` module
+pub fn is_test_file(path: &str, content: &str) -> bool {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    path.split('/')
+        .any(|c| matches!(c, "tests" | "test" | "__tests__" | "spec"))
+        || name.starts_with("test_")
+        || name.contains("_test.")
+        || name.contains(".test.")
+        || name.contains(".spec.")
+        || (name.ends_with(".rs") && content.contains("#[cfg(test)]"))
+}
+
+/// Test impact analysis: test files that changed, plus test files that
+/// mention a changed source file's module name (e.g. `billing` for
+/// `src/billing.py`). Text matching, like `affected`, so it over-selects
+/// rather than misses.
+pub fn affected_tests(changed: &[String], test_files: &[(String, String)]) -> Vec<String> {
+    let modules: Vec<&str> = changed
+        .iter()
+        .filter(|path| !test_files.iter().any(|(t, _)| t == *path))
+        .filter_map(|path| Path::new(path).file_stem()?.to_str())
+        .filter(|stem| !matches!(*stem, "mod" | "lib" | "main" | "index" | "__init__"))
+        .collect();
+
+    test_files
+        .iter()
+        .filter(|(path, content)| {
+            changed.contains(path) || modules.iter().any(|m| mentions_word(content, m))
+        })
+        .map(|(path, _)| path.clone())
+        .collect()
+}
+
+pub(crate) fn mentions_word(content: &str, word: &str) -> bool {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    content.match_indices(word).any(|(start, _)| {
+        let before = content[..start].chars().next_back();
+        let after = content[start + word.len()..].chars().next();
+        !before.is_some_and(is_ident) && !after.is_some_and(is_ident)
+    })
+}
+
+/// `command` narrowed to the selected test files, for runners that accept
+/// a selection; None when the runner is not recognized
+pub fn narrow_command(command: &str, tests: &[String]) -> Option<String> {
+    if command.contains("cargo test") {
+        let mut args = Vec::new();
+        for test in tests {
+            match test
+                .strip_prefix("tests/")
+                .and_then(|t| t.strip_suffix(".rs"))
+            {
+                Some(target) if !target.contains('/') => args.push(format!("--test {}", target)),
+                _ => {
+                    if !args.contains(&"--lib".to_string()) {
+                        args.push("--lib".to_string());
+                    }
+                }
+            }
+        }
+        return Some(format!("{} {}", command, args.join(" ")));
+    }
+    if command.contains("pytest") || command.contains("jest") || command.contains("vitest") {
+        return Some(format!("{} {}", command, tests.join(" ")));
+    }
+    if command.contains("go test") {
+        let mut packages: Vec<String> = tests
+            .iter()
+            .map(|t| match t.rsplit_once('/') {
+                Some((dir, _)) => format!("./{}", dir),
+                None => ".".to_string(),
+            })
+            .collect();
+        packages.dedup();
+        return Some(command.replace("./...", &packages.join(" ")));
+    }
+    None
+}
+
+/// Stored test runs, one per change
+pub struct TestStore {
+    dir: PathBuf,
+}
+
+impl TestStore {
+    pub const DEFAULT_DIR: &'static str = ".agent/tests";
+    const HISTORY: &'static str = "history.jsonl";
+
+    pub fn open(repo_root: impl AsRef<Path>) -> Self {
+        Self {
+            dir: repo_root.as_ref().join(Self::DEFAULT_DIR),
+        }
+    }
+
+    pub fn save(&self, run: &TestRun) -> Result<()> {
+        crate::agent_dir::write_json(&self.dir.join(format!("{}.json", run.change_id)), run)
+    }
+
+    /// The latest run recorded for a change
+    pub fn get(&self, change_id: &str) -> Option<TestRun> {
+        let content = std::fs::read_to_string(self.dir.join(format!("{}.json", change_id))).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    /// Append outcomes to the history (`history.jsonl`)
+    pub fn record(&self, outcomes: &[Outcome]) -> Result<()> {
+        use std::io::Write;
+
+        crate::agent_dir::ensure_private(&self.dir)?;
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(Self::HISTORY))?;
+        for outcome in outcomes {
+            let line = serde_json::to_string(outcome).map_err(|e| Error::Io {
+                message: e.to_string(),
+            })?;
+            writeln!(file, "{}", line)?;
+        }
+        Ok(())
+    }
+
+    /// Every recorded outcome, oldest first; unreadable lines are skipped
+    pub fn history(&self) -> Vec<Outcome> {
+        std::fs::read_to_string(self.dir.join(Self::HISTORY))
+            .map(|content| {
+                content
+                    .lines()
+                    .filter_map(|l| serde_json::from_str(l).ok())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_common_runner_output() {
+        let cargo = "running 3 tests\ntest a::ok ... ok\ntest a::bad ... FAILED\n\
+                     test result: FAILED. 2 passed; 1 failed; 1 ignored; 0 measured\n\
+                     test result: ok. 4 passed; 0 failed; 0 ignored; 0 measured\n";
+        let summary = parse_output(cargo);
+        assert_eq!(summary.format.as_deref(), Some("cargo"));
+        assert_eq!((summary.passed, summary.failed, summary.ignored), (6, 1, 1));
+        assert_eq!(summary.failing, vec!["a::bad"]);
+
+        let pytest = "FAILED tests/test_x.py::test_y - assert 1 == 2\n\
+                      ========= 1 failed, 5 passed, 2 skipped in 0.42s =========\n";
+        let summary = parse_output(pytest);
+        assert_eq!(summary.format.as_deref(), Some("pytest"));
+        assert_eq!((summary.passed, summary.failed, summary.ignored), (5, 1, 2));
+        assert_eq!(summary.failing, vec!["tests/test_x.py::test_y"]);
+
+        let jest = "  ● math › adds\n\nTests:       1 failed, 3 passed, 4 total\n";
+        let summary = parse_output(jest);
+        assert_eq!((summary.passed, summary.failed), (3, 1));
+        assert_eq!(summary.failing, vec!["math › adds"]);
+
+        let go = "=== RUN   TestA\n--- PASS: TestA (0.00s)\n--- FAIL: TestB (0.01s)\nFAIL\n";
+        let summary = parse_output(go);
+        assert_eq!(summary.format.as_deref(), Some("go"));
+        assert_eq!(summary.failing, vec!["TestB"]);
+
+        assert_eq!(parse_output("all good\n"), TestSummary::default());
+    }
+
+    #[test]
+    fn selects_and_narrows_affected_tests() {
+        let tests = vec![
+            (
+                "tests/test_billing.py".to_string(),
+                "from app import billing\n".to_string(),
+            ),
+            (
+                "tests/test_users.py".to_string(),
+                "from app import users_billing\n".to_string(),
+            ),
+            ("tests/test_misc.py".to_string(), "import os\n".to_string()),
+        ];
+        let changed = vec![
+            "app/billing.py".to_string(),
+            "tests/test_misc.py".to_string(),
+        ];
+        let selected = affected_tests(&changed, &tests);
+        assert_eq!(
+            selected,
+            vec!["tests/test_billing.py", "tests/test_misc.py"]
+        );
+
+        assert_eq!(
+            narrow_command("uv run pytest", &selected).as_deref(),
+            Some("uv run pytest tests/test_billing.py tests/test_misc.py")
+        );
+        assert_eq!(
+            narrow_command(
+                "cargo test",
+                &[
+                    "tests/cli.rs".into(),
+                    "src/repo.rs".into(),
+                    "src/a.rs".into()
+                ]
+            )
+            .as_deref(),
+            Some("cargo test --test cli --lib")
+        );
+        assert_eq!(
+            narrow_command("go test ./...", &["pkg/a/a_test.go".into()]).as_deref(),
+            Some("go test ./pkg/a")
+        );
+        assert_eq!(narrow_command("make check", &selected), None);
+        assert!(is_test_file("src/x.rs", "#[cfg(test)]\nmod tests {}"));
+        assert!(!is_test_file("src/x.rs", "fn main() {}"));
+    }
+
+    #[test]
+    fn finds_flakes_on_unchanged_trees() {
+        let summary = |failing: &[&str]| TestSummary {
+            format: Some("cargo".into()),
+            failing: failing.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        };
+        let run = |tree: &str, failing: &[&str]| {
+            Outcome::new(
+                "unit",
+                "cargo test",
+                tree,
+                "c1",
+                failing.is_empty(),
+                &summary(failing),
+            )
+        };
+        let history = vec![
+            run("t1", &["net::retry"]),
+            run("t1", &[]),
+            run("t1", &["net::retry", "db::pool"]),
+            // Fails consistently on its tree: a real failure, not a flake
+            run("t2", &["parse::bad"]),
+            run("t2", &["parse::bad"]),
+        ];
+
+        let flakes = flaky(&history);
+        let names: Vec<&str> = flakes.iter().map(|f| f.test.as_str()).collect();
+        assert_eq!(names, vec!["net::retry", "unit", "db::pool"]);
+        assert_eq!((flakes[0].passes, flakes[0].failures), (1, 2));
+
+        let failing = vec!["net::retry".to_string(), "parse::bad".to_string()];
+        assert_eq!(known_flakes(&flakes, "unit", &failing), vec!["net::retry"]);
+        assert_eq!(known_flakes(&flakes, "unit", &[]), vec!["unit"]);
+        assert!(known_flakes(&flakes, "lint", &[]).is_empty());
+    }
+}
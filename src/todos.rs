@@ -0,0 +1,118 @@
+// ABOUTME: TODO/FIXME/HACK comment scanning for `agentjj todos` and validate
+// ABOUTME: Finds markers in comments; blame and age are attached by the caller
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum TodoKind {
+    Todo,
+    Fixme,
+    Hack,
+}
+
+impl TodoKind {
+    const ALL: [(&'static str, TodoKind); 3] = [
+        ("TODO", TodoKind::Todo),
+        ("FIXME", TodoKind::Fixme),
+        ("HACK", TodoKind::Hack),
+    ];
+}
+
+/// A marker found in a comment
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TodoComment {
+    /// 1-based line number
+    pub line: usize,
+    pub kind: TodoKind,
+    /// What follows the marker, without `:` or an `(owner)` tag
+    pub text: String,
+}
+
+/// Comment openers across the languages agentjj reads
+const COMMENT_MARKERS: [&str; 5] = ["//", "#", "/*", "--", "<!--"];
+
+fn is_word_char(c: Option<char>) -> bool {
+    c.is_some_and(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// TODO/FIXME/HACK markers in comments. A marker counts when it is an
+/// uppercase word followed by `:`, `(`, `-`, a space, or the line end, after
+/// a comment opener on the same line or on a `*` continuation line of a
+/// block comment.
+pub fn scan(source: &str) -> Vec<TodoComment> {
+    let mut out = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let comment_start = COMMENT_MARKERS
+            .iter()
+            .filter_map(|marker| line.find(marker))
+            .min()
+            .or_else(|| line.trim_start().starts_with('*').then_some(0));
+        let Some(comment_start) = comment_start else {
+            continue;
+        };
+        let comment = &line[comment_start..];
+        let found = TodoKind::ALL.iter().find_map(|(word, kind)| {
+            comment.match_indices(word).find_map(|(at, _)| {
+                let before = comment[..at].chars().next_back();
+                // Not the tail of a slash-joined list such as `TODO/HACK`
+                let listed = comment[..at]
+                    .strip_suffix('/')
+                    .is_some_and(|rest| is_word_char(rest.chars().next_back()));
+                let after = comment[at + word.len()..].chars().next();
+                let ends_marker = after.is_none_or(|c| c.is_whitespace() || ":(-".contains(c));
+                (!is_word_char(before) && !listed && ends_marker).then_some((at, word, *kind))
+            })
+        });
+        if let Some((at, word, kind)) = found {
+            let mut text = comment[at + word.len()..].trim_start();
+            if text.starts_with('(') {
+                text = text.find(')').map_or(text, |end| &text[end + 1..]);
+            }
+            let text = text
+                .trim_start_matches([':', '-', ' '])
+                .trim_end_matches("*/")
+                .trim_end_matches("-->")
+                .trim();
+            out.push(TodoComment {
+                line: index + 1,
+                kind,
+                text: text.to_string(),
+            });
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_markers_in_comments_only() {
+        let source = r#"fn main() {
+    // TODO: handle errors
+    let s = "TODO in a string";
+    let todo_count = 0; # FIXME(alice) - off by one
+    /* HACK until upstream fixes it */
+    // TODOS are not markers, nor is NOTTODO or TODO/FIXME
+}
+"""
+ * FIXME docstring continuation
+"#;
+        let todos = scan(source);
+        let found: Vec<(usize, TodoKind, &str)> = todos
+            .iter()
+            .map(|t| (t.line, t.kind, t.text.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (2, TodoKind::Todo, "handle errors"),
+                (4, TodoKind::Fixme, "off by one"),
+                (5, TodoKind::Hack, "until upstream fixes it"),
+                (9, TodoKind::Fixme, "docstring continuation"),
+            ]
+        );
+    }
+}
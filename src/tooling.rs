@@ -0,0 +1,511 @@
+// ABOUTME: Detection of build systems, test commands, CI providers, and entry points
+// ABOUTME: Lets orient tell agents how to build and test a repo without exploratory shell calls
+
+use serde::Serialize;
+use std::path::Path;
+
+/// Everything orient knows about how a repository is built, tested, and run
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct Tooling {
+    /// Detected build systems, most specific first
+    pub build_systems: Vec<BuildSystem>,
+    /// CI providers with configuration present in the repo
+    pub ci_providers: Vec<String>,
+    /// Binaries, main modules, and scripts that start the program
+    pub entry_points: Vec<EntryPoint>,
+    /// Concrete commands for the primary build system
+    pub how_to: HowTo,
+}
+
+/// A build system detected from its manifest file
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildSystem {
+    /// Tool name (cargo, npm, yarn, pnpm, poetry, uv, pip, make, bazel)
+    pub name: String,
+    /// File that revealed it (e.g. Cargo.toml)
+    pub manifest: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub test: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lint: Option<String>,
+}
+
+/// A way into the program
+#[derive(Debug, Clone, Serialize)]
+pub struct EntryPoint {
+    /// bin, main, or script
+    pub kind: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+}
+
+/// Concrete commands an agent can run right away
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct HowTo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub test: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub run: Option<String>,
+}
+
+impl Tooling {
+    /// The test command of the primary build system, if any
+    pub fn test_command(&self) -> Option<&str> {
+        self.how_to.test.as_deref()
+    }
+}
+
+/// Detect build and test tooling at the repository root
+pub fn detect(root: &Path) -> Tooling {
+    let mut build_systems = Vec::new();
+    let mut entry_points = Vec::new();
+
+    detect_cargo(root, &mut build_systems, &mut entry_points);
+    detect_node(root, &mut build_systems, &mut entry_points);
+    detect_python(root, &mut build_systems, &mut entry_points);
+    detect_bazel(root, &mut build_systems);
+    detect_make(root, &mut build_systems);
+
+    let how_to = build_systems
+        .first()
+        .map(|primary| HowTo {
+            build: primary.build.clone(),
+            test: primary
+                .test
+                .clone()
+                .or_else(|| build_systems.iter().find_map(|b| b.test.clone())),
+            lint: primary.lint.clone(),
+            run: run_command(primary, &entry_points),
+        })
+        .unwrap_or_default();
+
+    Tooling {
+        build_systems,
+        ci_providers: detect_ci(root),
+        entry_points,
+        how_to,
+    }
+}
+
+fn read_toml(path: &Path) -> Option<toml::Value> {
+    std::fs::read_to_string(path).ok()?.parse().ok()
+}
+
+fn detect_cargo(root: &Path, systems: &mut Vec<BuildSystem>, entries: &mut Vec<EntryPoint>) {
+    let Some(manifest) = read_toml(&root.join("Cargo.toml")) else {
+        return;
+    };
+
+    let workspace = manifest.get("workspace").is_some();
+    let scope = if workspace { " --workspace" } else { "" };
+    systems.push(BuildSystem {
+        name: "cargo".to_string(),
+        manifest: "Cargo.toml".to_string(),
+        build: Some(format!("cargo build{}", scope)),
+        test: Some(format!("cargo test{}", scope)),
+        lint: Some(format!("cargo clippy{} --all-targets", scope)),
+    });
+
+    let package_name = manifest
+        .get("package")
+        .and_then(|p| p.get("name"))
+        .and_then(|n| n.as_str());
+
+    if let Some(bins) = manifest.get("bin").and_then(|b| b.as_array()) {
+        for bin in bins {
+            if let Some(name) = bin.get("name").and_then(|n| n.as_str()) {
+                entries.push(EntryPoint {
+                    kind: "bin".to_string(),
+                    name: name.to_string(),
+                    path: bin
+                        .get("path")
+                        .and_then(|p| p.as_str())
+                        .map(str::to_string)
+                        .or_else(|| Some(format!("src/bin/{}.rs", name))),
+                });
+            }
+        }
+    }
+
+    if let Some(name) = package_name {
+        if root.join("src/main.rs").exists() && !entries.iter().any(|e| e.name == name) {
+            entries.push(EntryPoint {
+                kind: "bin".to_string(),
+                name: name.to_string(),
+                path: Some("src/main.rs".to_string()),
+            });
+        }
+    }
+
+    if let Ok(dir) = std::fs::read_dir(root.join("src/bin")) {
+        let mut names: Vec<String> = dir
+            .flatten()
+            .filter_map(|e| {
+                let path = e.path();
+                (path.extension().and_then(|x| x.to_str()) == Some("rs"))
+                    .then(|| path.file_stem()?.to_str().map(str::to_string))
+                    .flatten()
+            })
+            .collect();
+        names.sort();
+        for name in names {
+            if !entries.iter().any(|e| e.name == name) {
+                entries.push(EntryPoint {
+                    kind: "bin".to_string(),
+                    path: Some(format!("src/bin/{}.rs", name)),
+                    name,
+                });
+            }
+        }
+    }
+}
+
+fn detect_node(root: &Path, systems: &mut Vec<BuildSystem>, entries: &mut Vec<EntryPoint>) {
+    let Some(package) = std::fs::read_to_string(root.join("package.json"))
+        .ok()
+        .and_then(|c| serde_json::from_str::<serde_json::Value>(&c).ok())
+    else {
+        return;
+    };
+
+    let manager = if root.join("pnpm-lock.yaml").exists() {
+        "pnpm"
+    } else if root.join("yarn.lock").exists() {
+        "yarn"
+    } else {
+        "npm"
+    };
+
+    let scripts = package.get("scripts").and_then(|s| s.as_object());
+    let has_script = |name: &str| scripts.map(|s| s.contains_key(name)).unwrap_or(false);
+    let run = |name: &str| match manager {
+        "npm" if name != "test" => format!("npm run {}", name),
+        _ => format!("{} {}", manager, name),
+    };
+
+    systems.push(BuildSystem {
+        name: manager.to_string(),
+        manifest: "package.json".to_string(),
+        build: has_script("build").then(|| run("build")),
+        test: has_script("test").then(|| run("test")),
+        lint: has_script("lint").then(|| run("lint")),
+    });
+
+    if let Some(main) = package.get("main").and_then(|m| m.as_str()) {
+        entries.push(EntryPoint {
+            kind: "main".to_string(),
+            name: package
+                .get("name")
+                .and_then(|n| n.as_str())
+                .unwrap_or(main)
+                .to_string(),
+            path: Some(main.to_string()),
+        });
+    }
+
+    match package.get("bin") {
+        Some(serde_json::Value::String(path)) => entries.push(EntryPoint {
+            kind: "bin".to_string(),
+            name: package
+                .get("name")
+                .and_then(|n| n.as_str())
+                .unwrap_or("bin")
+                .to_string(),
+            path: Some(path.clone()),
+        }),
+        Some(serde_json::Value::Object(bins)) => {
+            for (name, path) in bins {
+                entries.push(EntryPoint {
+                    kind: "bin".to_string(),
+                    name: name.clone(),
+                    path: path.as_str().map(str::to_string),
+                });
+            }
+        }
+        _ => {}
+    }
+}
+
+fn detect_python(root: &Path, systems: &mut Vec<BuildSystem>, entries: &mut Vec<EntryPoint>) {
+    let pyproject = read_toml(&root.join("pyproject.toml"));
+    let has_setup = root.join("setup.py").exists() || root.join("requirements.txt").exists();
+    if pyproject.is_none() && !has_setup {
+        return;
+    }
+
+    let poetry = pyproject
+        .as_ref()
+        .and_then(|p| p.get("tool"))
+        .and_then(|t| t.get("poetry"));
+
+    let (name, prefix, build) = if root.join("uv.lock").exists() {
+        ("uv", "uv run ", Some("uv build".to_string()))
+    } else if poetry.is_some() || root.join("poetry.lock").exists() {
+        ("poetry", "poetry run ", Some("poetry build".to_string()))
+    } else {
+        ("pip", "", None)
+    };
+
+    let uses_pytest = root.join("tests").is_dir()
+        || root.join("pytest.ini").exists()
+        || root.join("conftest.py").exists()
+        || pyproject
+            .as_ref()
+            .and_then(|p| p.get("tool"))
+            .and_then(|t| t.get("pytest"))
+            .is_some();
+
+    let uses_ruff = root.join("ruff.toml").exists()
+        || pyproject
+            .as_ref()
+            .and_then(|p| p.get("tool"))
+            .and_then(|t| t.get("ruff"))
+            .is_some();
+
+    systems.push(BuildSystem {
+        name: name.to_string(),
+        manifest: if pyproject.is_some() {
+            "pyproject.toml".to_string()
+        } else if root.join("setup.py").exists() {
+            "setup.py".to_string()
+        } else {
+            "requirements.txt".to_string()
+        },
+        build,
+        test: uses_pytest.then(|| format!("{}pytest", prefix)),
+        lint: uses_ruff.then(|| format!("{}ruff check .", prefix)),
+    });
+
+    let scripts = pyproject
+        .as_ref()
+        .and_then(|p| p.get("project"))
+        .and_then(|p| p.get("scripts"))
+        .or_else(|| poetry.and_then(|p| p.get("scripts")));
+    if let Some(table) = scripts.and_then(|s| s.as_table()) {
+        for (script, target) in table {
+            entries.push(EntryPoint {
+                kind: "script".to_string(),
+                name: script.clone(),
+                path: target.as_str().map(str::to_string),
+            });
+        }
+    }
+
+    for candidate in ["main.py", "app.py", "manage.py", "__main__.py"] {
+        if root.join(candidate).exists() {
+            entries.push(EntryPoint {
+                kind: "main".to_string(),
+                name: candidate.trim_end_matches(".py").to_string(),
+                path: Some(candidate.to_string()),
+            });
+        }
+    }
+}
+
+fn detect_bazel(root: &Path, systems: &mut Vec<BuildSystem>) {
+    let manifest = ["MODULE.bazel", "WORKSPACE.bazel", "WORKSPACE"]
+        .into_iter()
+        .find(|f| root.join(f).exists());
+    if let Some(manifest) = manifest {
+        systems.push(BuildSystem {
+            name: "bazel".to_string(),
+            manifest: manifest.to_string(),
+            build: Some("bazel build //...".to_string()),
+            test: Some("bazel test //...".to_string()),
+            lint: None,
+        });
+    }
+}
+
+fn detect_make(root: &Path, systems: &mut Vec<BuildSystem>) {
+    let Some((manifest, content)) = ["Makefile", "makefile", "GNUmakefile"]
+        .into_iter()
+        .find_map(|f| Some((f, std::fs::read_to_string(root.join(f)).ok()?)))
+    else {
+        return;
+    };
+
+    let has_target = |target: &str| {
+        content
+            .lines()
+            .any(|l| l.starts_with(&format!("{}:", target)))
+    };
+
+    systems.push(BuildSystem {
+        name: "make".to_string(),
+        manifest: manifest.to_string(),
+        build: Some(if has_target("build") {
+            "make build".to_string()
+        } else {
+            "make".to_string()
+        }),
+        test: has_target("test").then(|| "make test".to_string()),
+        lint: has_target("lint").then(|| "make lint".to_string()),
+    });
+}
+
+fn detect_ci(root: &Path) -> Vec<String> {
+    let mut providers = Vec::new();
+
+    let has_workflows = std::fs::read_dir(root.join(".github/workflows"))
+        .map(|dir| {
+            dir.flatten().any(|e| {
+                matches!(
+                    e.path().extension().and_then(|x| x.to_str()),
+                    Some("yml" | "yaml")
+                )
+            })
+        })
+        .unwrap_or(false);
+    if has_workflows {
+        providers.push("github-actions".to_string());
+    }
+
+    let markers = [
+        (".gitlab-ci.yml", "gitlab-ci"),
+        (".circleci/config.yml", "circleci"),
+        ("Jenkinsfile", "jenkins"),
+        (".travis.yml", "travis"),
+        ("azure-pipelines.yml", "azure-pipelines"),
+        (".buildkite", "buildkite"),
+    ];
+    for (path, name) in markers {
+        if root.join(path).exists() {
+            providers.push(name.to_string());
+        }
+    }
+
+    providers
+}
+
+fn run_command(primary: &BuildSystem, entries: &[EntryPoint]) -> Option<String> {
+    match primary.name.as_str() {
+        "cargo" => {
+            let bins: Vec<_> = entries.iter().filter(|e| e.kind == "bin").collect();
+            match bins.as_slice() {
+                [] => None,
+                [_] => Some("cargo run --".to_string()),
+                [first, ..] => Some(format!("cargo run --bin {} --", first.name)),
+            }
+        }
+        "npm" | "yarn" | "pnpm" => entries
+            .iter()
+            .find(|e| e.kind == "main")
+            .and_then(|e| e.path.as_ref())
+            .map(|p| format!("node {}", p)),
+        "uv" | "poetry" | "pip" => {
+            let prefix = match primary.name.as_str() {
+                "uv" => "uv run ",
+                "poetry" => "poetry run ",
+                _ => "",
+            };
+            entries
+                .iter()
+                .find(|e| e.kind == "script")
+                .map(|e| format!("{}{}", prefix, e.name))
+                .or_else(|| {
+                    entries
+                        .iter()
+                        .find(|e| e.kind == "main")
+                        .and_then(|e| e.path.as_ref())
+                        .map(|p| format!("{}python {}", prefix, p))
+                })
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn detects_cargo_project() {
+        let tmp = TempDir::new().unwrap();
+        std::fs::write(
+            tmp.path().join("Cargo.toml"),
+            "[package]\nname = \"demo\"\nversion = \"0.1.0\"\n",
+        )
+        .unwrap();
+        std::fs::create_dir_all(tmp.path().join("src")).unwrap();
+        std::fs::write(tmp.path().join("src/main.rs"), "fn main() {}\n").unwrap();
+
+        let tooling = detect(tmp.path());
+
+        assert_eq!(tooling.build_systems[0].name, "cargo");
+        assert_eq!(tooling.test_command(), Some("cargo test"));
+        assert_eq!(tooling.entry_points[0].name, "demo");
+        assert_eq!(tooling.how_to.run.as_deref(), Some("cargo run --"));
+    }
+
+    #[test]
+    fn detects_node_package_manager_from_lockfile() {
+        let tmp = TempDir::new().unwrap();
+        std::fs::write(
+            tmp.path().join("package.json"),
+            r#"{"name": "web", "main": "index.js", "scripts": {"test": "jest", "build": "tsc"}}"#,
+        )
+        .unwrap();
+        std::fs::write(tmp.path().join("pnpm-lock.yaml"), "").unwrap();
+
+        let tooling = detect(tmp.path());
+
+        assert_eq!(tooling.build_systems[0].name, "pnpm");
+        assert_eq!(tooling.how_to.test.as_deref(), Some("pnpm test"));
+        assert_eq!(tooling.how_to.build.as_deref(), Some("pnpm build"));
+        assert_eq!(tooling.how_to.run.as_deref(), Some("node index.js"));
+    }
+
+    #[test]
+    fn detects_uv_python_project_with_scripts() {
+        let tmp = TempDir::new().unwrap();
+        std::fs::write(
+            tmp.path().join("pyproject.toml"),
+            "[project]\nname = \"svc\"\n\n[project.scripts]\nsvc = \"svc.cli:main\"\n",
+        )
+        .unwrap();
+        std::fs::write(tmp.path().join("uv.lock"), "").unwrap();
+        std::fs::create_dir_all(tmp.path().join("tests")).unwrap();
+
+        let tooling = detect(tmp.path());
+
+        assert_eq!(tooling.build_systems[0].name, "uv");
+        assert_eq!(tooling.test_command(), Some("uv run pytest"));
+        assert_eq!(tooling.how_to.run.as_deref(), Some("uv run svc"));
+    }
+
+    #[test]
+    fn detects_make_targets_and_ci() {
+        let tmp = TempDir::new().unwrap();
+        std::fs::write(
+            tmp.path().join("Makefile"),
+            "build:\n\tcc main.c\n\ntest:\n\t./run-tests\n",
+        )
+        .unwrap();
+        std::fs::create_dir_all(tmp.path().join(".github/workflows")).unwrap();
+        std::fs::write(tmp.path().join(".github/workflows/ci.yml"), "on: push\n").unwrap();
+        std::fs::write(tmp.path().join(".gitlab-ci.yml"), "").unwrap();
+
+        let tooling = detect(tmp.path());
+
+        assert_eq!(tooling.build_systems[0].name, "make");
+        assert_eq!(tooling.how_to.test.as_deref(), Some("make test"));
+        assert_eq!(tooling.ci_providers, vec!["github-actions", "gitlab-ci"]);
+    }
+
+    #[test]
+    fn empty_repo_has_no_tooling() {
+        let tmp = TempDir::new().unwrap();
+        let tooling = detect(tmp.path());
+        assert!(tooling.build_systems.is_empty());
+        assert!(tooling.how_to.test.is_none());
+    }
+}
@@ -0,0 +1,266 @@
+// ABOUTME: `--trace`: timing spans (workspace load, snapshot, tree diff, invariants, subprocesses)
+// ABOUTME: Collected through `tracing` and reported as a `timings` section or a Chrome trace file
+
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::registry::LookupSpan;
+
+/// A span as recorded: offsets from the collector's start
+#[derive(Debug, Clone)]
+struct SpanRecord {
+    name: &'static str,
+    fields: Map<String, Value>,
+    depth: usize,
+    thread: u64,
+    start: Duration,
+    end: Option<Duration>,
+}
+
+/// Index of a span's record, kept in its registry extensions
+struct Slot(usize);
+
+struct FieldVisitor<'a>(&'a mut Map<String, Value>);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), Value::from(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0.insert(
+            field.name().to_string(),
+            Value::from(format!("{:?}", value)),
+        );
+    }
+}
+
+/// Small stable number for the current thread (Chrome's `tid`)
+fn thread_number() -> u64 {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    static NEXT: AtomicU64 = AtomicU64::new(1);
+    thread_local! {
+        static NUMBER: u64 = NEXT.fetch_add(1, Ordering::Relaxed);
+    }
+    NUMBER.with(|n| *n)
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_micros() as f64 / 1000.0
+}
+
+/// Records agentjj's own spans; other crates' spans (jj-lib) are ignored
+#[derive(Clone)]
+pub struct Collector {
+    start: Instant,
+    spans: Arc<Mutex<Vec<SpanRecord>>>,
+}
+
+impl Default for Collector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Collector {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            spans: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// A subscriber feeding this collector, for `tracing::subscriber::with_default`
+    pub fn subscriber(&self) -> impl Subscriber + Send + Sync {
+        tracing_subscriber::registry().with(self.clone())
+    }
+
+    fn records(&self) -> Vec<SpanRecord> {
+        self.spans.lock().map(|s| s.clone()).unwrap_or_default()
+    }
+
+    /// The `timings` section: every span in start order, with spans still
+    /// open measured up to now, and totals per span name
+    pub fn timings(&self) -> Value {
+        let now = self.start.elapsed();
+        let mut by_name: BTreeMap<&str, (u64, Duration)> = BTreeMap::new();
+        let spans: Vec<Value> = self
+            .records()
+            .into_iter()
+            .map(|span| {
+                let duration = span.end.unwrap_or(now).saturating_sub(span.start);
+                let total = by_name.entry(span.name).or_default();
+                *total = (total.0 + 1, total.1 + duration);
+                let mut value = json!({
+                    "name": span.name,
+                    "start_ms": millis(span.start),
+                    "duration_ms": millis(duration),
+                    "depth": span.depth,
+                });
+                if !span.fields.is_empty() {
+                    value["fields"] = Value::Object(span.fields);
+                }
+                if span.end.is_none() {
+                    value["open"] = Value::Bool(true);
+                }
+                value
+            })
+            .collect();
+        json!({
+            "total_ms": millis(now),
+            "spans": spans,
+            "by_name": by_name
+                .into_iter()
+                .map(|(name, (count, total))| {
+                    (name.to_string(), json!({ "count": count, "total_ms": millis(total) }))
+                })
+                .collect::<Map<_, _>>(),
+        })
+    }
+
+    /// The spans as a Chrome trace (chrome://tracing, Perfetto): one
+    /// complete event per span, microseconds from the start
+    pub fn chrome_trace(&self, process: &str) -> Value {
+        let now = self.start.elapsed();
+        let pid = std::process::id();
+        let mut events = vec![json!({
+            "ph": "M",
+            "name": "process_name",
+            "pid": pid,
+            "args": { "name": process },
+        })];
+        events.extend(self.records().into_iter().map(|span| {
+            let duration = span.end.unwrap_or(now).saturating_sub(span.start);
+            json!({
+                "ph": "X",
+                "name": span.name,
+                "cat": "agentjj",
+                "ts": span.start.as_micros() as u64,
+                "dur": duration.as_micros() as u64,
+                "pid": pid,
+                "tid": span.thread,
+                "args": span.fields,
+            })
+        }));
+        json!({ "traceEvents": events, "displayTimeUnit": "ms" })
+    }
+}
+
+impl<S> Layer<S> for Collector
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn enabled(&self, metadata: &tracing::Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        metadata.is_span() && metadata.target().starts_with("agentjj")
+    }
+
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut fields = Map::new();
+        attrs.record(&mut FieldVisitor(&mut fields));
+        let record = SpanRecord {
+            name: attrs.metadata().name(),
+            fields,
+            depth: span.scope().skip(1).count(),
+            thread: thread_number(),
+            start: self.start.elapsed(),
+            end: None,
+        };
+        let Ok(mut spans) = self.spans.lock() else {
+            return;
+        };
+        span.extensions_mut().insert(Slot(spans.len()));
+        spans.push(record);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let slot = span.extensions().get::<Slot>().map(|slot| slot.0);
+        if let (Some(slot), Ok(mut spans)) = (slot, self.spans.lock()) {
+            if let Some(record) = spans.get_mut(slot) {
+                values.record(&mut FieldVisitor(&mut record.fields));
+            }
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let slot = span.extensions().get::<Slot>().map(|slot| slot.0);
+        if let (Some(slot), Ok(mut spans)) = (slot, self.spans.lock()) {
+            if let Some(record) = spans.get_mut(slot) {
+                record.end = Some(self.start.elapsed());
+            }
+        }
+    }
+}
+
+static GLOBAL: OnceLock<Collector> = OnceLock::new();
+
+/// Start collecting spans for the rest of the process
+pub fn enable() {
+    let collector = GLOBAL.get_or_init(Collector::new);
+    let _ = tracing::subscriber::set_global_default(collector.subscriber());
+}
+
+/// The process-wide collector, when `--trace` enabled one
+pub fn collector() -> Option<&'static Collector> {
+    GLOBAL.get()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_nested_spans_with_fields() {
+        let collector = Collector::new();
+        tracing::subscriber::with_default(collector.subscriber(), || {
+            let _outer = tracing::info_span!("workspace_load").entered();
+            for cmd in ["true", "false"] {
+                let _inner = tracing::info_span!("subprocess", cmd).entered();
+            }
+            // Other crates' spans stay out
+            let _jj = tracing::info_span!(target: "jj_lib::store", "read_tree").entered();
+        });
+
+        let timings = collector.timings();
+        let spans = timings["spans"].as_array().unwrap();
+        assert_eq!(spans.len(), 3);
+        assert_eq!(spans[0]["name"], "workspace_load");
+        assert_eq!(spans[0]["depth"], 0);
+        assert_eq!(spans[1]["fields"]["cmd"], "true");
+        assert_eq!(spans[2]["depth"], 1);
+        assert!(spans.iter().all(|s| s.get("open").is_none()));
+        assert_eq!(timings["by_name"]["subprocess"]["count"], 2);
+
+        let trace = collector.chrome_trace("agentjj status");
+        let events = trace["traceEvents"].as_array().unwrap();
+        assert_eq!(events[0]["args"]["name"], "agentjj status");
+        assert_eq!(events[2]["ph"], "X");
+        assert_eq!(events[2]["args"]["cmd"], "true");
+    }
+}
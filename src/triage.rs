@@ -0,0 +1,511 @@
+// ABOUTME: Failure triage for `triage`: parses compiler and test logs into structured failures
+// ABOUTME: and ranks the current change's files by how strongly the failures point at them
+
+use serde::Serialize;
+use std::path::Path;
+
+use crate::symbols::{Symbol, SymbolKind};
+use crate::testrun::mentions_word;
+
+/// One failure found in a log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Failure {
+    /// Tool whose output format matched: rustc, cargo-test, pytest, jest
+    pub source: String,
+    /// "compile" for build errors, "test" for failing tests
+    pub kind: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    pub message: String,
+    /// Failing test, when the failure is a test
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symbol: Option<String>,
+}
+
+/// A symbol the change added or whose text it modified
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TouchedSymbol {
+    pub file: String,
+    pub name: String,
+    pub kind: SymbolKind,
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+/// A file suspected of causing the failures, with the evidence
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Culprit {
+    pub file: String,
+    pub score: u32,
+    /// Whether the current change touches the file
+    pub changed: bool,
+    pub reasons: Vec<String>,
+}
+
+/// Parse cargo/rustc, pytest, and jest output into failures. Lines from
+/// `gh run view --log` keep their job/step/timestamp prefixes, so those are
+/// stripped first, along with ANSI colors.
+pub fn parse_log(text: &str) -> Vec<Failure> {
+    let lines: Vec<String> = text.lines().map(clean_line).collect();
+    let mut failures = Vec::new();
+    let mut failed_tests = Vec::new();
+    let mut pytest_locations: Vec<(String, usize)> = Vec::new();
+
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+
+        // rustc: "error[E0425]: cannot find value `x`" then " --> src/lib.rs:3:5"
+        if let Some(message) = rustc_error(trimmed) {
+            let location = lines[i + 1..]
+                .iter()
+                .take(6)
+                .find_map(|l| parse_location(l.trim().strip_prefix("--> ")?));
+            failures.push(Failure {
+                source: "rustc".into(),
+                kind: "compile".into(),
+                file: location.as_ref().map(|(f, _)| f.clone()),
+                line: location.map(|(_, l)| l),
+                message: message.to_string(),
+                symbol: None,
+            });
+            continue;
+        }
+
+        // cargo test: "thread 'tests::it_adds' panicked at src/lib.rs:10:5:"
+        // followed by the message; older toolchains put the message first
+        if let Some(rest) = trimmed.strip_prefix("thread '") {
+            let Some((test, rest)) = rest.split_once("' panicked at ") else {
+                continue;
+            };
+            let (message, location) = match rest.strip_suffix(':') {
+                Some(location) => (
+                    lines.get(i + 1).map(|l| l.trim().to_string()),
+                    parse_location(location),
+                ),
+                None => match rest.rsplit_once("', ") {
+                    Some((message, location)) => (
+                        Some(message.trim_start_matches('\'').to_string()),
+                        parse_location(location),
+                    ),
+                    None => (None, parse_location(rest)),
+                },
+            };
+            failures.push(Failure {
+                source: "cargo-test".into(),
+                kind: "test".into(),
+                file: location.as_ref().map(|(f, _)| f.clone()),
+                line: location.map(|(_, l)| l),
+                message: message.unwrap_or_else(|| "panicked".into()),
+                symbol: Some(test.to_string()),
+            });
+            continue;
+        }
+        if let Some(test) = trimmed
+            .strip_prefix("test ")
+            .and_then(|l| l.strip_suffix(" ... FAILED"))
+        {
+            failed_tests.push(test.to_string());
+            continue;
+        }
+
+        // pytest: "FAILED tests/test_x.py::test_total - assert 3 == 4", with
+        // "tests/test_x.py:12: AssertionError" locations in the tracebacks
+        if let Some(rest) = trimmed
+            .strip_prefix("FAILED ")
+            .or_else(|| trimmed.strip_prefix("ERROR "))
+        {
+            let (id, message) = rest.split_once(" - ").unwrap_or((rest, ""));
+            if let Some((file, test)) = id.split_once("::") {
+                failures.push(Failure {
+                    source: "pytest".into(),
+                    kind: "test".into(),
+                    file: Some(file.to_string()),
+                    line: None,
+                    message: if message.is_empty() {
+                        "failed".into()
+                    } else {
+                        message.to_string()
+                    },
+                    symbol: Some(test.rsplit("::").next().unwrap_or(test).to_string()),
+                });
+            }
+            continue;
+        }
+        if let Some((location, _)) = trimmed.split_once(": ") {
+            if location.ends_with(|c: char| c.is_ascii_digit()) && location.contains(".py:") {
+                pytest_locations.extend(parse_location(location));
+            }
+        }
+
+        // jest: "● Suite › test name", then the message, then "at ... (file:line:col)"
+        if let Some(test) = trimmed.strip_prefix("● ") {
+            let block: Vec<&str> = lines[i + 1..]
+                .iter()
+                .map(|l| l.trim())
+                .take_while(|l| !l.starts_with("● ") && !l.starts_with("Test Suites:"))
+                .collect();
+            let message = block
+                .iter()
+                .find(|l| !l.is_empty())
+                .map(|l| l.to_string())
+                .unwrap_or_else(|| "failed".into());
+            let location = block
+                .iter()
+                .filter_map(|l| l.strip_prefix("at "))
+                .filter(|l| !l.contains("node_modules"))
+                .find_map(|l| {
+                    let inner = l
+                        .rsplit_once('(')
+                        .map(|(_, loc)| loc.trim_end_matches(')'))
+                        .unwrap_or(l);
+                    parse_location(inner)
+                });
+            failures.push(Failure {
+                source: "jest".into(),
+                kind: "test".into(),
+                file: location.as_ref().map(|(f, _)| f.clone()),
+                line: location.map(|(_, l)| l),
+                message,
+                symbol: Some(test.to_string()),
+            });
+        }
+    }
+
+    // pytest tracebacks end at the failing line in the test file
+    for failure in failures.iter_mut().filter(|f| f.source == "pytest") {
+        failure.line = pytest_locations
+            .iter()
+            .rev()
+            .find(|(file, _)| Some(file) == failure.file.as_ref())
+            .map(|(_, line)| *line);
+    }
+
+    // "test x ... FAILED" without a panic (e.g. #[should_panic] that didn't)
+    for test in failed_tests {
+        if !failures.iter().any(|f| f.symbol.as_ref() == Some(&test)) {
+            failures.push(Failure {
+                source: "cargo-test".into(),
+                kind: "test".into(),
+                file: None,
+                line: None,
+                message: "failed".into(),
+                symbol: Some(test),
+            });
+        }
+    }
+
+    // jest repeats each failure in its closing summary
+    let mut unique: Vec<Failure> = Vec::new();
+    for failure in failures {
+        if !unique.contains(&failure) {
+            unique.push(failure);
+        }
+    }
+    unique
+}
+
+fn rustc_error(line: &str) -> Option<&str> {
+    let message = match line.strip_prefix("error[") {
+        Some(rest) => rest.split_once("]: ")?.1,
+        None => line.strip_prefix("error: ")?,
+    };
+    // cargo's summary lines repeat the errors already reported
+    let summary = [
+        "could not compile",
+        "aborting due to",
+        "test failed",
+        "build failed",
+    ];
+    (!summary.iter().any(|s| message.starts_with(s))).then_some(message)
+}
+
+/// "path:line" or "path:line:col"
+fn parse_location(text: &str) -> Option<(String, usize)> {
+    let text = text.trim();
+    let mut parts = text.rsplitn(3, ':');
+    let last = parts.next()?;
+    let middle = parts.next()?;
+    let (path, line) = match (middle.parse::<usize>(), parts.next()) {
+        (Ok(line), Some(path)) if last.parse::<usize>().is_ok() => (path, line),
+        _ => (text.rsplit_once(':')?.0, last.parse().ok()?),
+    };
+    (!path.is_empty() && !path.contains(' ')).then(|| (path.to_string(), line))
+}
+
+/// Drop ANSI escapes and the "job\tstep\t<timestamp> " prefix of CI logs
+fn clean_line(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            // CSI sequence: ESC [ params final-byte
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    let line = out.rsplit('\t').next().unwrap_or(&out);
+    let line = match line.split_once(' ') {
+        Some((stamp, rest))
+            if stamp.len() >= 20
+                && stamp.ends_with('Z')
+                && stamp.as_bytes()[4] == b'-'
+                && stamp.as_bytes()[10] == b'T' =>
+        {
+            rest
+        }
+        _ => line,
+    };
+    line.to_string()
+}
+
+/// Symbols in `after` that are new or whose text differs from `before`,
+/// including methods nested in classes and impls
+pub fn touched_symbols(
+    file: &str,
+    before: Option<(&str, &[Symbol])>,
+    after: (&str, &[Symbol]),
+) -> Vec<TouchedSymbol> {
+    let text = |source: &str, symbol: &Symbol| -> String {
+        source
+            .lines()
+            .skip(symbol.start_line.saturating_sub(1))
+            .take((symbol.end_line + 1).saturating_sub(symbol.start_line.max(1)))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    let old: Vec<(&Symbol, String)> = before
+        .map(|(source, symbols)| {
+            flatten(symbols)
+                .into_iter()
+                .map(|s| (s, text(source, s)))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut touched: Vec<TouchedSymbol> = flatten(after.1)
+        .into_iter()
+        .filter(|s| !matches!(s.kind, SymbolKind::Import | SymbolKind::Instruction))
+        .filter(|s| {
+            let body = text(after.0, s);
+            !old.iter()
+                .any(|(o, o_body)| o.name == s.name && o.kind == s.kind && *o_body == body)
+        })
+        .map(|s| TouchedSymbol {
+            file: file.to_string(),
+            name: s.name.clone(),
+            kind: s.kind,
+            start_line: s.start_line,
+            end_line: s.end_line,
+        })
+        .collect();
+    touched.sort_by_key(|s| s.start_line);
+    touched
+}
+
+/// Every symbol, including those nested in classes and impls
+pub(crate) fn flatten(symbols: &[Symbol]) -> Vec<&Symbol> {
+    let mut out = Vec::new();
+    let mut stack: Vec<&Symbol> = symbols.iter().collect();
+    while let Some(symbol) = stack.pop() {
+        stack.extend(&symbol.children);
+        out.push(symbol);
+    }
+    out
+}
+
+/// Rank files by the evidence tying them to the failures. Files the change
+/// touches score for failures located in them (+3), failures inside a
+/// touched symbol (+2), failures naming a touched symbol (+2), and failing
+/// tests that mention the file's module (+1). Files the change does not
+/// touch only score for failures located in them (+1), so a broken
+/// dependency still shows up below the change's own files.
+pub fn culprits(
+    failures: &[Failure],
+    changed: &[String],
+    touched: &[TouchedSymbol],
+) -> Vec<Culprit> {
+    let in_file = |failure: &Failure, file: &str| {
+        failure
+            .file
+            .as_deref()
+            .is_some_and(|f| f == file || f.ends_with(&format!("/{}", file)))
+    };
+    let describe = |failure: &Failure| match (&failure.symbol, &failure.file, failure.line) {
+        (Some(symbol), _, _) => format!("`{}`", symbol),
+        (None, Some(file), Some(line)) => format!("{}:{}", file, line),
+        _ => format!("\"{}\"", failure.message),
+    };
+
+    let mut culprits: Vec<Culprit> = Vec::new();
+    for file in changed {
+        let stem = Path::new(file)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .filter(|s| !matches!(*s, "mod" | "lib" | "main" | "index" | "__init__"));
+        let symbols: Vec<&TouchedSymbol> = touched.iter().filter(|s| &s.file == file).collect();
+        let mut score = 0;
+        let mut reasons = Vec::new();
+        for failure in failures {
+            let text = format!(
+                "{} {}",
+                failure.symbol.as_deref().unwrap_or_default(),
+                failure.message
+            );
+            if in_file(failure, file) {
+                score += 3;
+                reasons.push(format!("{} fails in this file", describe(failure)));
+                if let Some(s) = failure.line.and_then(|line| {
+                    symbols
+                        .iter()
+                        .find(|s| (s.start_line..=s.end_line).contains(&line))
+                }) {
+                    score += 2;
+                    reasons.push(format!(
+                        "{} fails inside changed `{}`",
+                        describe(failure),
+                        s.name
+                    ));
+                }
+            }
+            if let Some(s) = symbols.iter().find(|s| mentions_word(&text, &s.name)) {
+                score += 2;
+                reasons.push(format!(
+                    "{} mentions changed `{}`",
+                    describe(failure),
+                    s.name
+                ));
+            }
+            if failure.kind == "test" && !in_file(failure, file) {
+                let test_text = format!("{} {}", failure.file.as_deref().unwrap_or_default(), text);
+                if let Some(stem) = stem.filter(|stem| mentions_word(&test_text, stem)) {
+                    score += 1;
+                    reasons.push(format!("{} mentions module `{}`", describe(failure), stem));
+                }
+            }
+        }
+        if score > 0 {
+            culprits.push(Culprit {
+                file: file.clone(),
+                score,
+                changed: true,
+                reasons,
+            });
+        }
+    }
+
+    for failure in failures {
+        let Some(file) = &failure.file else {
+            continue;
+        };
+        if changed.iter().any(|c| in_file(failure, c)) {
+            continue;
+        }
+        let reason = format!("{} fails in this file", describe(failure));
+        match culprits.iter_mut().find(|c| &c.file == file) {
+            Some(culprit) => {
+                culprit.score += 1;
+                culprit.reasons.push(reason);
+            }
+            None => culprits.push(Culprit {
+                file: file.clone(),
+                score: 1,
+                changed: false,
+                reasons: vec![reason],
+            }),
+        }
+    }
+
+    culprits.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.file.cmp(&b.file)));
+    culprits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbols::{extract_symbols, SupportedLanguage};
+
+    #[test]
+    fn parses_cargo_pytest_and_jest_failures() {
+        let log = "\
+build\tRun tests\t2026-01-01T00:00:00.1234567Z error[E0425]: cannot find value `totl` in this scope
+  --> src/billing.rs:12:5
+error: could not compile `shop` (lib) due to 1 previous error
+thread 'tests::charges_tax' panicked at src/billing.rs:40:9:
+assertion `left == right` failed
+test tests::charges_tax ... FAILED
+test tests::rounds ... FAILED
+FAILED tests/test_cart.py::test_total - assert 3 == 4
+tests/test_cart.py:17: AssertionError
+  ● cart › sums items
+
+    expect(received).toBe(expected)
+
+      at Object.<anonymous> (src/cart.test.js:5:17)
+";
+        let failures = parse_log(log);
+        assert_eq!(failures.len(), 5, "{:#?}", failures);
+
+        assert_eq!(failures[0].source, "rustc");
+        assert_eq!(failures[0].kind, "compile");
+        assert_eq!(failures[0].file.as_deref(), Some("src/billing.rs"));
+        assert_eq!(failures[0].line, Some(12));
+        assert_eq!(
+            failures[0].message,
+            "cannot find value `totl` in this scope"
+        );
+
+        assert_eq!(failures[1].symbol.as_deref(), Some("tests::charges_tax"));
+        assert_eq!(failures[1].line, Some(40));
+        assert_eq!(failures[1].message, "assertion `left == right` failed");
+
+        assert_eq!(failures[2].source, "pytest");
+        assert_eq!(failures[2].file.as_deref(), Some("tests/test_cart.py"));
+        assert_eq!(failures[2].line, Some(17));
+        assert_eq!(failures[2].symbol.as_deref(), Some("test_total"));
+
+        assert_eq!(failures[3].source, "jest");
+        assert_eq!(failures[3].file.as_deref(), Some("src/cart.test.js"));
+        assert_eq!(failures[3].line, Some(5));
+        assert_eq!(failures[3].message, "expect(received).toBe(expected)");
+
+        assert_eq!(failures[4].symbol.as_deref(), Some("tests::rounds"));
+        assert_eq!(failures[4].file, None);
+    }
+
+    #[test]
+    fn ranks_changed_files_by_evidence() {
+        let before = "fn total() -> u32 {\n    1\n}\n\nfn tax() -> u32 {\n    2\n}\n";
+        let after = "fn total() -> u32 {\n    3\n}\n\nfn tax() -> u32 {\n    2\n}\n";
+        let lang = SupportedLanguage::Rust;
+        let old = extract_symbols(before, lang).unwrap();
+        let new = extract_symbols(after, lang).unwrap();
+        let touched = touched_symbols("src/billing.rs", Some((before, &old)), (after, &new));
+        let names: Vec<&str> = touched.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["total"]);
+
+        let failures = parse_log(
+            "thread 'billing_totals' panicked at tests/billing.rs:4:5:\nassertion failed: total() == 1\n\
+             thread 'other' panicked at src/util.rs:9:1:\nboom\n",
+        );
+        let changed = vec!["src/billing.rs".to_string(), "README.md".to_string()];
+        let ranked = culprits(&failures, &changed, &touched);
+        assert_eq!(ranked[0].file, "src/billing.rs");
+        assert!(ranked[0].changed);
+        // mentions `total` (+2) and the billing module (+1)
+        assert_eq!(ranked[0].score, 3, "{:?}", ranked[0].reasons);
+        let unchanged: Vec<&str> = ranked
+            .iter()
+            .filter(|c| !c.changed)
+            .map(|c| c.file.as_str())
+            .collect();
+        assert_eq!(unchanged, vec!["src/util.rs", "tests/billing.rs"]);
+        assert!(!ranked.iter().any(|c| c.file == "README.md"));
+    }
+}
@@ -0,0 +1,647 @@
+// ABOUTME: `agentjj ui`: a live terminal dashboard (ratatui) over dashboard.rs, refreshed on a timer
+// ABOUTME: Keys run `agentjj checkpoint create` / `agentjj undo` as subprocesses (off in read-only mode)
+
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::Frame;
+
+use crate::change::InvariantStatus;
+use crate::dashboard::Dashboard;
+use crate::error::{Error, Result};
+use crate::repo::{short_id, Repo};
+
+/// Key help shown in the footer
+const KEYS: &str = "↑/↓ select  enter diff  c checkpoint  u undo  r refresh  q quit";
+
+/// Key help in read-only mode, without the state-changing keys
+const READ_ONLY_KEYS: &str = "↑/↓ select  enter diff  r refresh  q quit  (read-only)";
+
+/// A state-changing key waiting for `y`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pending {
+    Undo,
+}
+
+/// The diff of one change, shown full screen
+#[derive(Debug, Clone)]
+pub struct DiffView {
+    pub title: String,
+    pub lines: Vec<String>,
+    pub scroll: usize,
+}
+
+/// Dashboard state between frames
+pub struct App {
+    pub root: PathBuf,
+    pub limit: usize,
+    /// `--read-only` or the manifest's `default_mode`: checkpoint and undo
+    /// keys are refused
+    pub read_only: bool,
+    pub dashboard: Dashboard,
+    pub selected: usize,
+    pub diff: Option<DiffView>,
+    pub pending: Option<Pending>,
+    /// Result of the last action or refresh error, shown in the footer
+    pub message: Option<String>,
+    quit: bool,
+}
+
+impl App {
+    pub fn new(root: PathBuf, limit: usize, read_only: bool, dashboard: Dashboard) -> Self {
+        Self {
+            root,
+            limit,
+            read_only,
+            dashboard,
+            selected: 0,
+            diff: None,
+            pending: None,
+            message: None,
+            quit: false,
+        }
+    }
+
+    fn refresh(&mut self) {
+        let loaded =
+            Repo::open(&self.root).and_then(|mut repo| Dashboard::load(&mut repo, self.limit));
+        match loaded {
+            Ok(dashboard) => {
+                self.selected = self.selected.min(dashboard.changes.len().saturating_sub(1));
+                self.dashboard = dashboard;
+            }
+            Err(e) => self.message = Some(format!("✗ refresh failed: {}", e)),
+        }
+    }
+
+    fn open_diff(&mut self) {
+        let Some(change) = self.dashboard.changes.get(self.selected) else {
+            return;
+        };
+        let diff = Repo::open(&self.root)
+            .and_then(|mut repo| repo.tree_changes(&change.change_id))
+            .map(|changes| crate::unified::render(&changes));
+        match diff {
+            Ok(diff) => {
+                let mut lines: Vec<String> = diff.lines().map(str::to_string).collect();
+                if lines.is_empty() {
+                    lines.push("(no file changes)".to_string());
+                }
+                self.diff = Some(DiffView {
+                    title: format!(
+                        "{} {}",
+                        short_id(&change.change_id),
+                        summary(&change.description)
+                    ),
+                    lines,
+                    scroll: 0,
+                });
+            }
+            Err(e) => self.message = Some(format!("✗ diff failed: {}", e)),
+        }
+    }
+
+    /// Run `agentjj --json <args>` in the repository and report the outcome
+    fn run(&mut self, args: &[&str], done: impl FnOnce(&serde_json::Value) -> String) {
+        // Read-only mode lives in this process, so the child is told again
+        let read_only = self.read_only.then_some("--read-only");
+        let output = std::env::current_exe().and_then(|exe| {
+            std::process::Command::new(exe)
+                .args(read_only)
+                .arg("--json")
+                .args(args)
+                .current_dir(&self.root)
+                .output()
+        });
+        self.message = Some(match output {
+            Ok(output) => {
+                let value: serde_json::Value =
+                    serde_json::from_slice(&output.stdout).unwrap_or_default();
+                if output.status.success() {
+                    format!("✓ {}", done(&value))
+                } else {
+                    let message = value["message"]
+                        .as_str()
+                        .map(str::to_string)
+                        .unwrap_or_else(|| String::from_utf8_lossy(&output.stderr).to_string());
+                    format!("✗ agentjj {}: {}", args.join(" "), message.trim())
+                }
+            }
+            Err(e) => format!("✗ could not run agentjj: {}", e),
+        });
+        self.refresh();
+    }
+
+    fn checkpoint(&mut self) {
+        let now = crate::repo::utc_timestamp(
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs() as i64,
+        );
+        let name = format!("ui-{}", now.replace([':', '-'], "").trim_end_matches('Z'));
+        self.run(&["checkpoint", "create", &name], |value| {
+            format!(
+                "checkpoint '{}' created (restore: {})",
+                name,
+                value["restore_command"].as_str().unwrap_or_default()
+            )
+        });
+    }
+
+    fn undo(&mut self) {
+        let undone = self.dashboard.last_operation.clone();
+        self.run(&["undo"], |_| format!("undid '{}'", undone));
+    }
+
+    /// Apply one key press
+    pub fn key(&mut self, key: KeyEvent) {
+        if key.kind != KeyEventKind::Press {
+            return;
+        }
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            self.quit = true;
+            return;
+        }
+        if let Some(pending) = self.pending.take() {
+            match (pending, key.code) {
+                (Pending::Undo, KeyCode::Char('y')) => self.undo(),
+                _ => self.message = Some("cancelled".to_string()),
+            }
+            return;
+        }
+        if let Some(diff) = &mut self.diff {
+            let last = diff.lines.len().saturating_sub(1);
+            match key.code {
+                KeyCode::Down | KeyCode::Char('j') => diff.scroll = (diff.scroll + 1).min(last),
+                KeyCode::Up | KeyCode::Char('k') => diff.scroll = diff.scroll.saturating_sub(1),
+                KeyCode::PageDown | KeyCode::Char(' ') => {
+                    diff.scroll = (diff.scroll + 20).min(last)
+                }
+                KeyCode::PageUp => diff.scroll = diff.scroll.saturating_sub(20),
+                KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') | KeyCode::Char('d') => {
+                    self.diff = None
+                }
+                _ => {}
+            }
+            return;
+        }
+        let last = self.dashboard.changes.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+            KeyCode::Down | KeyCode::Char('j') => self.selected = (self.selected + 1).min(last),
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Enter | KeyCode::Char('d') => self.open_diff(),
+            KeyCode::Char('c') | KeyCode::Char('u') if self.read_only => {
+                self.message =
+                    Some("✗ read-only mode: checkpoint and undo are disabled".to_string());
+            }
+            KeyCode::Char('c') => self.checkpoint(),
+            KeyCode::Char('u') => {
+                self.pending = Some(Pending::Undo);
+                self.message = Some(format!(
+                    "Undo '{}'? y to confirm, any other key to cancel",
+                    self.dashboard.last_operation
+                ));
+            }
+            KeyCode::Char('r') => {
+                self.message = None;
+                self.refresh();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Run the dashboard until `q`, refreshing every `interval`
+pub fn run(root: PathBuf, limit: usize, interval: Duration, read_only: bool) -> Result<()> {
+    let dashboard = Dashboard::load(&mut Repo::open(&root)?, limit)?;
+    let mut app = App::new(root, limit, read_only, dashboard);
+    let mut terminal = ratatui::init();
+    let result = (|| -> std::io::Result<()> {
+        let mut refreshed = Instant::now();
+        while !app.quit {
+            terminal.draw(|frame| draw(frame, &app))?;
+            let wait = interval.saturating_sub(refreshed.elapsed());
+            if event::poll(wait)? {
+                if let Event::Key(key) = event::read()? {
+                    app.key(key);
+                }
+            }
+            if refreshed.elapsed() >= interval {
+                app.refresh();
+                refreshed = Instant::now();
+            }
+        }
+        Ok(())
+    })();
+    ratatui::restore();
+    result.map_err(|e| Error::Io {
+        message: format!("terminal error: {}", e),
+    })
+}
+
+fn summary(description: &str) -> &str {
+    match description.lines().next() {
+        Some(line) if !line.trim().is_empty() => line,
+        _ => "(no description set)",
+    }
+}
+
+fn block(title: &str) -> Block<'_> {
+    Block::default().borders(Borders::ALL).title(Span::styled(
+        title,
+        Style::default().add_modifier(Modifier::BOLD),
+    ))
+}
+
+/// Draw one frame
+pub fn draw(frame: &mut Frame, app: &App) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1),
+            Constraint::Min(8),
+            Constraint::Length(7),
+            Constraint::Length(1),
+        ])
+        .split(frame.area());
+
+    let dashboard = &app.dashboard;
+    frame.render_widget(
+        Paragraph::new(Line::from(vec![
+            Span::styled("agentjj ui ", Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(format!(
+                " @ {}  op {}  refreshed {}",
+                short_id(&dashboard.change_id),
+                short_id(&dashboard.operation_id),
+                dashboard.refreshed_at
+            )),
+        ])),
+        rows[0],
+    );
+
+    if let Some(diff) = &app.diff {
+        draw_diff(frame, diff, rows[1].union(rows[2]));
+    } else {
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(55), Constraint::Percentage(45)])
+            .split(rows[1]);
+        draw_changes(frame, app, columns[0]);
+        let right = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+            .split(columns[1]);
+        draw_change(frame, app, right[0]);
+        draw_status(frame, dashboard, right[1]);
+        draw_sessions(frame, dashboard, rows[2]);
+    }
+
+    let footer = match &app.message {
+        Some(message) => Line::from(Span::styled(
+            message.as_str(),
+            Style::default().fg(if app.pending.is_some() {
+                Color::Yellow
+            } else if message.starts_with('✗') {
+                Color::Red
+            } else {
+                Color::Green
+            }),
+        )),
+        None if app.diff.is_some() => Line::from("↑/↓ scroll  space page  esc back"),
+        None if app.read_only => Line::from(READ_ONLY_KEYS),
+        None => Line::from(KEYS),
+    };
+    frame.render_widget(Paragraph::new(footer), rows[3]);
+}
+
+fn draw_changes(frame: &mut Frame, app: &App, area: Rect) {
+    let items: Vec<ListItem> = app
+        .dashboard
+        .changes
+        .iter()
+        .map(|change| {
+            let (marker, color) = if change.working_copy {
+                ("@", Color::Green)
+            } else if change.immutable {
+                ("◆", Color::Blue)
+            } else {
+                ("○", Color::Reset)
+            };
+            let mut spans = vec![
+                Span::styled(format!("{} ", marker), Style::default().fg(color)),
+                Span::styled(
+                    format!("{} ", &change.change_id[..change.change_id.len().min(8)]),
+                    Style::default().fg(Color::Magenta),
+                ),
+            ];
+            if change.parents.len() > 1 {
+                spans.push(Span::styled("merge ", Style::default().fg(Color::Yellow)));
+            }
+            if let Some(typed) = &change.typed {
+                spans.push(Span::styled(
+                    format!("[{}] ", crate::change::enum_name(typed.change_type)),
+                    Style::default().fg(Color::Cyan),
+                ));
+            }
+            spans.push(Span::raw(summary(&change.description).to_string()));
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+    let mut state = ListState::default().with_selected(Some(app.selected));
+    frame.render_stateful_widget(
+        List::new(items)
+            .block(block("Changes"))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED)),
+        area,
+        &mut state,
+    );
+}
+
+fn draw_change(frame: &mut Frame, app: &App, area: Rect) {
+    let Some(change) = app.dashboard.changes.get(app.selected) else {
+        frame.render_widget(Paragraph::new("No changes").block(block("Change")), area);
+        return;
+    };
+    let label =
+        |name: &str| Span::styled(format!("{:<11}", name), Style::default().fg(Color::Gray));
+    let mut lines = vec![
+        Line::from(vec![label("change"), Span::raw(change.change_id.clone())]),
+        Line::from(vec![
+            label("commit"),
+            Span::raw(short_id(&change.commit_id).to_string()),
+        ]),
+    ];
+    if let Some(author) = &change.author {
+        let when = change.timestamp.as_deref().unwrap_or("");
+        lines.push(Line::from(vec![
+            label("author"),
+            Span::raw(format!("{} {}", author, when)),
+        ]));
+    }
+    if !change.parents.is_empty() {
+        lines.push(Line::from(vec![
+            label("parents"),
+            Span::raw(change.parents.join(", ")),
+        ]));
+    }
+    match &change.typed {
+        Some(typed) => {
+            lines.push(Line::from(vec![
+                label("type"),
+                Span::raw(crate::change::enum_name(typed.change_type)),
+                Span::styled(
+                    if typed.breaking { "  BREAKING" } else { "" },
+                    Style::default().fg(Color::Red),
+                ),
+            ]));
+            lines.push(Line::from(vec![
+                label("intent"),
+                Span::raw(typed.intent.clone()),
+            ]));
+            if !typed.categories.is_empty() {
+                let categories: Vec<String> =
+                    typed.categories.iter().map(|c| c.to_string()).collect();
+                lines.push(Line::from(vec![
+                    label("categories"),
+                    Span::raw(categories.join(", ")),
+                ]));
+            }
+            if !typed.depends_on.is_empty() {
+                lines.push(Line::from(vec![
+                    label("depends on"),
+                    Span::raw(typed.depends_on.join(", ")),
+                ]));
+            }
+            let invariants = &typed.invariants;
+            lines.push(Line::from(vec![
+                label("invariants"),
+                status_span(invariants.status),
+                Span::raw(if invariants.checked.is_empty() {
+                    String::new()
+                } else {
+                    format!(" ({})", invariants.checked.join(", "))
+                }),
+            ]));
+            let mut details: Vec<_> = invariants.details.iter().collect();
+            details.sort_by_key(|(name, _)| *name);
+            for (name, status) in details {
+                lines.push(Line::from(vec![
+                    Span::raw(format!("  {:<9} ", name)),
+                    status_span(*status),
+                ]));
+            }
+        }
+        None => lines.push(Line::from(Span::styled(
+            "no typed metadata",
+            Style::default().fg(Color::DarkGray),
+        ))),
+    }
+    frame.render_widget(
+        Paragraph::new(lines)
+            .block(block("Change"))
+            .wrap(Wrap { trim: false }),
+        area,
+    );
+}
+
+fn status_span(status: InvariantStatus) -> Span<'static> {
+    let color = match status {
+        InvariantStatus::Passed => Color::Green,
+        InvariantStatus::Failed => Color::Red,
+        InvariantStatus::Skipped => Color::Yellow,
+        InvariantStatus::Unknown => Color::DarkGray,
+    };
+    Span::styled(crate::change::enum_name(status), Style::default().fg(color))
+}
+
+fn draw_status(frame: &mut Frame, dashboard: &Dashboard, area: Rect) {
+    let lines: Vec<Line> = if dashboard.status.is_empty() {
+        vec![Line::from(Span::styled(
+            "clean",
+            Style::default().fg(Color::DarkGray),
+        ))]
+    } else {
+        dashboard
+            .status
+            .iter()
+            .map(|entry| {
+                let color = match entry.status {
+                    "added" => Color::Green,
+                    "deleted" | "conflicted" => Color::Red,
+                    "modified" => Color::Yellow,
+                    _ => Color::DarkGray,
+                };
+                Line::from(vec![
+                    Span::styled(format!("{:<11}", entry.status), Style::default().fg(color)),
+                    Span::raw(entry.path.clone()),
+                ])
+            })
+            .collect()
+    };
+    frame.render_widget(Paragraph::new(lines).block(block("Working copy")), area);
+}
+
+fn draw_sessions(frame: &mut Frame, dashboard: &Dashboard, area: Rect) {
+    let lines: Vec<Line> = if dashboard.sessions.is_empty() {
+        vec![Line::from(Span::styled(
+            "no recorded sessions (set AGENTJJ_SESSION per agent)",
+            Style::default().fg(Color::DarkGray),
+        ))]
+    } else {
+        dashboard
+            .sessions
+            .iter()
+            .map(|session| {
+                let (mark, color) = if session.active {
+                    ("●", Color::Green)
+                } else {
+                    ("○", Color::DarkGray)
+                };
+                let failed = if session.failed > 0 {
+                    format!(", {} failed", session.failed)
+                } else {
+                    String::new()
+                };
+                Line::from(vec![
+                    Span::styled(format!("{} ", mark), Style::default().fg(color)),
+                    Span::raw(format!(
+                        "{:<20} {:<20} last {} ({} command(s){})",
+                        session.session,
+                        session.last_active,
+                        session.last_command,
+                        session.commands,
+                        failed
+                    )),
+                ])
+            })
+            .collect()
+    };
+    frame.render_widget(Paragraph::new(lines).block(block("Sessions")), area);
+}
+
+fn draw_diff(frame: &mut Frame, diff: &DiffView, area: Rect) {
+    let lines: Vec<Line> = diff
+        .lines
+        .iter()
+        .skip(diff.scroll)
+        .map(|line| {
+            let color = if line.starts_with("diff --git") {
+                Color::Yellow
+            } else if line.starts_with("@@") {
+                Color::Cyan
+            } else if line.starts_with('+') && !line.starts_with("+++") {
+                Color::Green
+            } else if line.starts_with('-') && !line.starts_with("---") {
+                Color::Red
+            } else {
+                Color::Reset
+            };
+            Line::from(Span::styled(line.as_str(), Style::default().fg(color)))
+        })
+        .collect();
+    frame.render_widget(
+        Paragraph::new(lines).block(block(&format!("Diff {}", diff.title))),
+        area,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::change::{ChangeType, TypedChange};
+    use crate::dashboard::{DashboardChange, SessionInfo, StatusEntry};
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    fn screen(app: &App) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(100, 24)).unwrap();
+        terminal.draw(|frame| draw(frame, app)).unwrap();
+        let buffer = terminal.backend().buffer();
+        (0..buffer.area.height)
+            .map(|y| {
+                (0..buffer.area.width)
+                    .map(|x| buffer[(x, y)].symbol())
+                    .collect::<String>()
+                    + "\n"
+            })
+            .collect()
+    }
+
+    #[test]
+    fn draws_changes_metadata_status_and_sessions() {
+        let mut typed = TypedChange::new("aaaa1111", ChangeType::Behavioral, "Retry uploads");
+        typed.invariants.status = InvariantStatus::Failed;
+        typed.invariants.checked = vec!["test".into()];
+        typed
+            .invariants
+            .details
+            .insert("test".into(), InvariantStatus::Failed);
+        let change = |id: &str, description: &str, working_copy: bool| DashboardChange {
+            change_id: id.into(),
+            commit_id: "c0ffee".into(),
+            description: description.into(),
+            parents: vec![],
+            working_copy,
+            immutable: false,
+            author: None,
+            timestamp: None,
+            typed: None,
+        };
+        let dashboard = Dashboard {
+            change_id: "bbbb2222".into(),
+            operation_id: "0p0p".into(),
+            last_operation: "snapshot working copy".into(),
+            changes: vec![
+                change("bbbb2222", "", true),
+                DashboardChange {
+                    typed: Some(typed),
+                    ..change("aaaa1111", "Retry uploads", false)
+                },
+            ],
+            status: vec![StatusEntry {
+                path: "src/up.rs".into(),
+                status: "modified",
+            }],
+            sessions: vec![SessionInfo {
+                session: "agent-7".into(),
+                commands: 3,
+                failed: 1,
+                last_command: "commit".into(),
+                last_active: "2026-03-01T11:55:00Z".into(),
+                active: true,
+            }],
+            refreshed_at: "2026-03-01T12:00:00Z".into(),
+        };
+        let mut app = App::new(PathBuf::from("."), 20, false, dashboard.clone());
+        app.key(KeyEvent::from(KeyCode::Down));
+
+        let text = screen(&app);
+        assert!(text.contains("@ bbbb2222 (no description set)"), "{}", text);
+        assert!(text.contains("[behavioral] Retry uploads"), "{}", text);
+        assert!(text.contains("invariants failed (test)"), "{}", text);
+        assert!(text.contains("modified   src/up.rs"), "{}", text);
+        assert!(text.contains("agent-7"), "{}", text);
+
+        app.key(KeyEvent::from(KeyCode::Char('u')));
+        assert_eq!(app.pending, Some(Pending::Undo));
+        assert!(screen(&app).contains("Undo 'snapshot working copy'?"));
+        app.key(KeyEvent::from(KeyCode::Char('n')));
+        assert_eq!(app.pending, None);
+        assert_eq!(app.message.as_deref(), Some("cancelled"));
+
+        // Read-only: no state-changing keys
+        let mut app = App::new(PathBuf::from("."), 20, true, dashboard);
+        assert!(screen(&app).contains("(read-only)"));
+        app.key(KeyEvent::from(KeyCode::Char('u')));
+        assert_eq!(app.pending, None);
+        app.key(KeyEvent::from(KeyCode::Char('c')));
+        assert!(app.message.as_deref().unwrap().contains("read-only mode"));
+    }
+}
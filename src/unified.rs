@@ -0,0 +1,335 @@
+// ABOUTME: Git-format unified diffs rendered from jj tree changes, for repositories without git
+// ABOUTME: Output matches `git diff` closely enough for diff parsing, numstat, and patch review
+
+use jj_lib::diff::{ContentDiff, DiffHunkKind};
+
+/// Lines of unchanged context around each hunk, as git uses by default
+const CONTEXT: usize = 3;
+
+/// One side of a changed path
+#[derive(Debug, Clone, PartialEq)]
+pub struct Side {
+    pub content: Vec<u8>,
+    /// Git file mode: "100644", "100755", or "120000" for symlinks
+    pub mode: &'static str,
+}
+
+/// A path that differs between two trees; `None` where it is absent
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileChange {
+    pub path: String,
+    pub before: Option<Side>,
+    pub after: Option<Side>,
+}
+
+impl FileChange {
+    /// Lines added and removed; `None` for binary content, as `--numstat`
+    /// reports it
+    pub fn line_counts(&self) -> Option<(usize, usize)> {
+        let (before, after) = (content(&self.before), content(&self.after));
+        if is_binary(before) || is_binary(after) {
+            return None;
+        }
+        let (mut added, mut removed) = (0, 0);
+        for (op, _) in line_ops(before, after) {
+            match op {
+                Op::Add => added += 1,
+                Op::Remove => removed += 1,
+                Op::Keep => {}
+            }
+        }
+        Some((added, removed))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Keep,
+    Remove,
+    Add,
+}
+
+fn content(side: &Option<Side>) -> &[u8] {
+    side.as_ref().map_or(&[][..], |s| &s.content)
+}
+
+fn is_binary(content: &[u8]) -> bool {
+    content.contains(&0)
+}
+
+/// Every line of both sides, in order, tagged with what happened to it
+fn line_ops<'a>(before: &'a [u8], after: &'a [u8]) -> Vec<(Op, &'a [u8])> {
+    let mut ops = Vec::new();
+    for hunk in ContentDiff::by_line([before, after]).hunks() {
+        match hunk.kind {
+            DiffHunkKind::Matching => {
+                ops.extend(
+                    hunk.contents[0]
+                        .split_inclusive(|b| *b == b'\n')
+                        .map(|l| (Op::Keep, l)),
+                );
+            }
+            DiffHunkKind::Different => {
+                ops.extend(
+                    hunk.contents[0]
+                        .split_inclusive(|b| *b == b'\n')
+                        .map(|l| (Op::Remove, l)),
+                );
+                ops.extend(
+                    hunk.contents[1]
+                        .split_inclusive(|b| *b == b'\n')
+                        .map(|l| (Op::Add, l)),
+                );
+            }
+        }
+    }
+    ops
+}
+
+/// `@@` hunks with `CONTEXT` lines around each run of changes
+fn render_hunks(out: &mut String, ops: &[(Op, &[u8])]) {
+    let mut next = 0;
+    while let Some(first) = (next..ops.len()).find(|&i| ops[i].0 != Op::Keep) {
+        let start = first.saturating_sub(CONTEXT).max(next);
+        // Merge changes separated by at most two contexts' worth of lines
+        let mut end = first;
+        loop {
+            while end < ops.len() && ops[end].0 != Op::Keep {
+                end += 1;
+            }
+            match (end..ops.len()).find(|&i| ops[i].0 != Op::Keep) {
+                Some(change) if change - end <= 2 * CONTEXT => end = change,
+                _ => break,
+            }
+        }
+        let stop = (end + CONTEXT).min(ops.len());
+
+        let old_before = ops[..start].iter().filter(|(op, _)| *op != Op::Add).count();
+        let new_before = ops[..start]
+            .iter()
+            .filter(|(op, _)| *op != Op::Remove)
+            .count();
+        let hunk = &ops[start..stop];
+        let old_len = hunk.iter().filter(|(op, _)| *op != Op::Add).count();
+        let new_len = hunk.iter().filter(|(op, _)| *op != Op::Remove).count();
+        // An empty side is numbered from the line before it, as git does
+        let position = |before: usize, len: usize| if len == 0 { before } else { before + 1 };
+        out.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            position(old_before, old_len),
+            old_len,
+            position(new_before, new_len),
+            new_len
+        ));
+        for (op, line) in hunk {
+            out.push(match op {
+                Op::Keep => ' ',
+                Op::Remove => '-',
+                Op::Add => '+',
+            });
+            out.push_str(&String::from_utf8_lossy(line));
+            if !line.ends_with(b"\n") {
+                out.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+        next = stop;
+    }
+}
+
+/// Replacement of lines `start_line..=end_line` (1-based, numbered in
+/// `before`) by `content`; `old_content` is what those lines held
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineEdit {
+    pub start_line: usize,
+    pub end_line: usize,
+    pub old_content: String,
+    pub content: String,
+}
+
+/// The edits that turn `before` into `after`, in order and never
+/// overlapping. A pure insertion takes in the line before it (or after it,
+/// at the top of the file), since a range cannot be empty; `before` must
+/// have at least one line.
+pub fn line_edits(before: &str, after: &str) -> Vec<LineEdit> {
+    let ops = line_ops(before.as_bytes(), after.as_bytes());
+    let mut changed: Vec<bool> = ops.iter().map(|(op, _)| *op != Op::Keep).collect();
+    let mut i = 0;
+    while i < ops.len() {
+        if ops[i].0 == Op::Keep {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < ops.len() && ops[i].0 != Op::Keep {
+            i += 1;
+        }
+        if ops[start..i].iter().all(|(op, _)| *op == Op::Add) {
+            match start.checked_sub(1) {
+                Some(previous) => changed[previous] = true,
+                None if i < ops.len() => changed[i] = true,
+                None => {}
+            }
+        }
+    }
+
+    let text = |line: &[u8]| String::from_utf8_lossy(line).to_string();
+    let mut edits = Vec::new();
+    let mut old_line = 0;
+    let mut i = 0;
+    while i < ops.len() {
+        if !changed[i] {
+            old_line += 1;
+            i += 1;
+            continue;
+        }
+        let mut edit = LineEdit {
+            start_line: old_line + 1,
+            end_line: old_line,
+            old_content: String::new(),
+            content: String::new(),
+        };
+        while i < ops.len() && changed[i] {
+            let (op, line) = ops[i];
+            if op != Op::Add {
+                old_line += 1;
+                edit.end_line = old_line;
+                edit.old_content.push_str(&text(line));
+            }
+            if op != Op::Remove {
+                edit.content.push_str(&text(line));
+            }
+            i += 1;
+        }
+        if edit.end_line >= edit.start_line {
+            edits.push(edit);
+        }
+    }
+    edits
+}
+
+/// Render changes as `git diff` would print them
+pub fn render(changes: &[FileChange]) -> String {
+    let mut out = String::new();
+    for change in changes {
+        let path = &change.path;
+        out.push_str(&format!("diff --git a/{} b/{}\n", path, path));
+        match (&change.before, &change.after) {
+            (None, Some(after)) => out.push_str(&format!("new file mode {}\n", after.mode)),
+            (Some(before), None) => out.push_str(&format!("deleted file mode {}\n", before.mode)),
+            (Some(before), Some(after)) if before.mode != after.mode => {
+                out.push_str(&format!(
+                    "old mode {}\nnew mode {}\n",
+                    before.mode, after.mode
+                ));
+            }
+            _ => {}
+        }
+
+        let (before, after) = (content(&change.before), content(&change.after));
+        if before == after {
+            continue;
+        }
+        let old_name = match change.before {
+            Some(_) => format!("a/{}", path),
+            None => "/dev/null".to_string(),
+        };
+        let new_name = match change.after {
+            Some(_) => format!("b/{}", path),
+            None => "/dev/null".to_string(),
+        };
+        if is_binary(before) || is_binary(after) {
+            out.push_str(&format!(
+                "Binary files {} and {} differ\n",
+                old_name, new_name
+            ));
+            continue;
+        }
+        out.push_str(&format!("--- {}\n+++ {}\n", old_name, new_name));
+        render_hunks(&mut out, &line_ops(before, after));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(content: &str) -> Option<Side> {
+        Some(Side {
+            content: content.as_bytes().to_vec(),
+            mode: "100644",
+        })
+    }
+
+    #[test]
+    fn renders_like_git_diff() {
+        let before: String = (1..=20).map(|n| format!("line {}\n", n)).collect();
+        let after = before
+            .replace("line 2\n", "line two\n")
+            .replace("line 18\n", "line 18\nline 18b\n");
+        let changes = vec![
+            FileChange {
+                path: "src/a.txt".into(),
+                before: file(&before),
+                after: file(&after),
+            },
+            FileChange {
+                path: "new.txt".into(),
+                before: None,
+                after: file("hello"),
+            },
+        ];
+
+        let diff = render(&changes);
+        assert!(diff.starts_with(
+            "diff --git a/src/a.txt b/src/a.txt\n--- a/src/a.txt\n+++ b/src/a.txt\n\
+             @@ -1,5 +1,5 @@\n line 1\n-line 2\n+line two\n line 3\n"
+        ));
+        // Far-apart changes get their own hunk
+        assert!(diff.contains(
+            "@@ -16,5 +16,6 @@\n line 16\n line 17\n line 18\n+line 18b\n line 19\n line 20\n"
+        ));
+        assert!(diff.contains(
+            "diff --git a/new.txt b/new.txt\nnew file mode 100644\n--- /dev/null\n+++ b/new.txt\n\
+             @@ -0,0 +1,1 @@\n+hello\n\\ No newline at end of file\n"
+        ));
+
+        assert_eq!(changes[0].line_counts(), Some((2, 1)));
+        let binary = FileChange {
+            path: "logo.png".into(),
+            before: None,
+            after: Some(Side {
+                content: vec![0x89, 0, 1],
+                mode: "100644",
+            }),
+        };
+        assert_eq!(binary.line_counts(), None);
+        assert!(render(&[binary]).contains("Binary files /dev/null and b/logo.png differ"));
+    }
+
+    #[test]
+    fn line_edits_cover_insertions_with_a_neighbouring_line() {
+        let edits = line_edits("a\nb\nc\nd\n", "top\na\nb\nB\nc\n");
+        let spans: Vec<(usize, usize, &str, &str)> = edits
+            .iter()
+            .map(|e| {
+                (
+                    e.start_line,
+                    e.end_line,
+                    e.old_content.as_str(),
+                    e.content.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            spans,
+            vec![
+                // Both insertions take in a line; touching edits merge
+                (1, 2, "a\nb\n", "top\na\nb\nB\n"),
+                (4, 4, "d\n", ""),
+            ]
+        );
+        assert!(line_edits("same\n", "same\n").is_empty());
+        assert_eq!(line_edits("x", "x\ny")[0].content, "x\ny");
+    }
+}
@@ -7,12 +7,12 @@ use std::fs;
 use std::process::Command as StdCommand;
 use tempfile::TempDir;
 
-/// Helper to create a git repository in a temp directory
-/// agentjj auto-colocates with git repos, so we just need git init
+/// Helper to create a git repository in a temp directory with jj colocated
+/// (read-only commands never set jj up themselves)
 fn setup_jj_repo() -> TempDir {
     let tmp = TempDir::new().expect("Failed to create temp directory");
 
-    // Initialize git repo - agentjj will auto-colocate
+    // Initialize git repo, then colocate jj with it
     let output = StdCommand::new("git")
         .args(["init"])
         .current_dir(tmp.path())
@@ -38,6 +38,8 @@ fn setup_jj_repo() -> TempDir {
         .output()
         .expect("Failed to configure git name");
 
+    agentjj::repo::Repo::init_colocated_git(tmp.path()).expect("Failed to colocate jj");
+
     tmp
 }
 
@@ -500,6 +502,224 @@ mod checkpoint_and_recovery {
             "Second checkpoint should be the older one"
         );
     }
+
+    #[test]
+    fn checkpoint_status_reports_new_files() {
+        let tmp = setup_jj_repo();
+
+        agentjj()
+            .current_dir(tmp.path())
+            .args(["init"])
+            .assert()
+            .success();
+
+        agentjj()
+            .current_dir(tmp.path())
+            .args(["checkpoint", "create", "before-work"])
+            .assert()
+            .success();
+
+        fs::write(tmp.path().join("work.txt"), "valuable\n").expect("Failed to create file");
+
+        let output = agentjj()
+            .current_dir(tmp.path())
+            .args(["--json", "checkpoint", "status", "before-work"])
+            .assert()
+            .success();
+
+        let stdout = String::from_utf8_lossy(&output.get_output().stdout);
+        let json: serde_json::Value =
+            serde_json::from_str(&stdout).expect("Output should be valid JSON");
+
+        assert_eq!(json["operation_found"], true);
+        assert_eq!(json["restore_loses_work"], true);
+        let files = json["files_modified"]
+            .as_array()
+            .expect("files_modified should be an array");
+        assert!(files
+            .iter()
+            .any(|f| f["path"] == "work.txt" && f["status"] == "added"));
+        assert!(json["changes"]["created"].is_array());
+        assert!(json["operations_since"].is_array());
+    }
+
+    #[test]
+    fn checkpoint_status_lists_invariants_in_name_order() {
+        let tmp = setup_jj_repo();
+
+        agentjj()
+            .current_dir(tmp.path())
+            .args(["init"])
+            .assert()
+            .success();
+        fs::write(
+            tmp.path().join(".agent/manifest.toml"),
+            r#"
+[repo]
+name = "test-repo"
+
+[invariants]
+zeta = { cmd = "true", on = ["pre-commit"] }
+alpha = { cmd = "false", on = ["pre-commit"] }
+mid = { cmd = "true", on = ["pre-commit"] }
+"#,
+        )
+        .expect("Failed to write manifest");
+
+        agentjj()
+            .current_dir(tmp.path())
+            .args(["checkpoint", "create", "before-work"])
+            .assert()
+            .success();
+
+        let output = agentjj()
+            .current_dir(tmp.path())
+            .args(["--json", "checkpoint", "status", "before-work"])
+            .assert()
+            .success();
+        let stdout = String::from_utf8_lossy(&output.get_output().stdout);
+        assert!(
+            stdout.find("\"alpha\"") < stdout.find("\"mid\"")
+                && stdout.find("\"mid\"") < stdout.find("\"zeta\""),
+            "invariants should be sorted by name: {}",
+            stdout
+        );
+
+        let output = agentjj()
+            .current_dir(tmp.path())
+            .args(["checkpoint", "status", "before-work"])
+            .assert()
+            .success();
+        let stdout = String::from_utf8_lossy(&output.get_output().stdout);
+        let names: Vec<_> = stdout
+            .lines()
+            .skip_while(|l| l.trim() != "invariants:")
+            .skip(1)
+            .map_while(|l| l.trim().split_once(':').map(|(name, _)| name.to_string()))
+            .collect();
+        assert_eq!(names, ["alpha", "mid", "zeta"]);
+    }
+
+    #[test]
+    fn checkpoint_status_unknown_checkpoint_fails() {
+        let tmp = setup_jj_repo();
+
+        agentjj()
+            .current_dir(tmp.path())
+            .args(["checkpoint", "status", "does-not-exist"])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("not found"));
+    }
+
+    #[test]
+    fn undo_paths_restores_only_selected_files() {
+        let tmp = setup_jj_repo();
+        fs::write(tmp.path().join("broken.txt"), "original\n").unwrap();
+        fs::write(tmp.path().join("keep.txt"), "original\n").unwrap();
+
+        agentjj()
+            .current_dir(tmp.path())
+            .args(["checkpoint", "create", "partial"])
+            .assert()
+            .success();
+
+        fs::write(tmp.path().join("broken.txt"), "bad edit\n").unwrap();
+        fs::write(tmp.path().join("keep.txt"), "good edit\n").unwrap();
+
+        let output = agentjj()
+            .current_dir(tmp.path())
+            .args(["--json", "undo", "--to", "partial", "--paths", "broken.txt"])
+            .assert()
+            .success();
+
+        let stdout = String::from_utf8_lossy(&output.get_output().stdout);
+        let json: serde_json::Value =
+            serde_json::from_str(&stdout).expect("Output should be valid JSON");
+        assert_eq!(json["restored"], true);
+        assert_eq!(json["files"][0]["path"], "broken.txt");
+
+        assert_eq!(
+            fs::read_to_string(tmp.path().join("broken.txt")).unwrap(),
+            "original\n"
+        );
+        assert_eq!(
+            fs::read_to_string(tmp.path().join("keep.txt")).unwrap(),
+            "good edit\n"
+        );
+    }
+
+    #[test]
+    fn undo_paths_respects_manifest_permissions() {
+        let tmp = setup_jj_repo();
+        fs::create_dir_all(tmp.path().join(".agent")).unwrap();
+        fs::write(
+            tmp.path().join(".agent/manifest.toml"),
+            "[repo]\nname = \"perm-test\"\n\n[permissions]\ndeny_change = [\"locked.txt\"]\n",
+        )
+        .unwrap();
+        fs::write(tmp.path().join("locked.txt"), "original\n").unwrap();
+
+        agentjj()
+            .current_dir(tmp.path())
+            .args(["checkpoint", "create", "denied"])
+            .assert()
+            .success();
+
+        fs::write(tmp.path().join("locked.txt"), "changed\n").unwrap();
+
+        agentjj()
+            .current_dir(tmp.path())
+            .args(["undo", "--to", "denied", "--paths", "locked.txt"])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("permission denied"));
+
+        assert_eq!(
+            fs::read_to_string(tmp.path().join("locked.txt")).unwrap(),
+            "changed\n"
+        );
+    }
+
+    #[test]
+    fn gc_prunes_checkpoints_beyond_retention() {
+        let tmp = setup_jj_repo();
+
+        for (name, created_at) in [("oldest", "1"), ("middle", "2"), ("newest", "3")] {
+            agentjj()
+                .current_dir(tmp.path())
+                .args(["checkpoint", "create", name])
+                .assert()
+                .success();
+            // Force a distinct creation order regardless of clock resolution
+            let path = tmp.path().join(format!(".agent/checkpoints/{}.json", name));
+            let mut checkpoint: serde_json::Value =
+                serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+            checkpoint["created_at"] = format!("2026-01-0{}T00:00:00Z", created_at).into();
+            fs::write(&path, checkpoint.to_string()).unwrap();
+        }
+
+        // Dry run reports but keeps everything
+        let output = agentjj()
+            .current_dir(tmp.path())
+            .args(["--json", "--config", "gc.keep_checkpoints=1", "gc"])
+            .args(["--aggressive", "--dry-run"])
+            .assert()
+            .success();
+        let json: serde_json::Value = serde_json::from_slice(&output.get_output().stdout).unwrap();
+        assert_eq!(json["checkpoints"]["pruned"].as_array().unwrap().len(), 2);
+        assert!(tmp.path().join(".agent/checkpoints/oldest.json").exists());
+
+        agentjj()
+            .current_dir(tmp.path())
+            .args(["--config", "gc.keep_checkpoints=1", "gc", "--aggressive"])
+            .assert()
+            .success();
+
+        assert!(tmp.path().join(".agent/checkpoints/newest.json").exists());
+        assert!(!tmp.path().join(".agent/checkpoints/middle.json").exists());
+        assert!(!tmp.path().join(".agent/checkpoints/oldest.json").exists());
+    }
 }
 
 // =============================================================================
@@ -985,3 +1205,131 @@ mod additional_workflows {
             .stdout(predicate::str::contains("init"));
     }
 }
+
+// =============================================================================
+// Scenario: Audit log and replay
+// =============================================================================
+
+mod audit_and_replay {
+    use super::*;
+
+    #[test]
+    fn replay_exported_session_in_fresh_clone() {
+        let origin = setup_jj_repo();
+        fs::write(origin.path().join("base.txt"), "base\n").unwrap();
+        StdCommand::new("git")
+            .args(["add", "."])
+            .current_dir(origin.path())
+            .output()
+            .unwrap();
+        StdCommand::new("git")
+            .args(["commit", "-m", "base"])
+            .current_dir(origin.path())
+            .output()
+            .unwrap();
+
+        // Record a session: one commit, one failed command
+        fs::write(origin.path().join("feature.txt"), "feature\n").unwrap();
+        agentjj()
+            .current_dir(origin.path())
+            .env("AGENTJJ_SESSION", "agent-1")
+            .args(["commit", "-m", "add feature", "--no-invariants"])
+            .assert()
+            .success();
+        agentjj()
+            .current_dir(origin.path())
+            .env("AGENTJJ_SESSION", "agent-1")
+            .args(["commit", "-m", "bad", "--type", "nonsense"])
+            .assert()
+            .failure();
+
+        let script_path = origin.path().join("replay.json");
+        agentjj()
+            .current_dir(origin.path())
+            .args(["replay", "export", "--session", "agent-1", "--out"])
+            .arg(&script_path)
+            .assert()
+            .success();
+
+        let script: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&script_path).unwrap()).unwrap();
+        let steps = script["steps"].as_array().unwrap();
+        assert_eq!(steps.len(), 1, "Failed commands should not be exported");
+        assert_eq!(steps[0]["command"], "commit");
+
+        // Replay against a fresh clone that never saw feature.txt
+        let clone = TempDir::new().unwrap();
+        StdCommand::new("git")
+            .args(["clone", "-q"])
+            .arg(origin.path())
+            .arg(clone.path().join("repo"))
+            .output()
+            .unwrap();
+        let clone_root = clone.path().join("repo");
+        for (key, value) in [("user.email", "test@test.com"), ("user.name", "Test User")] {
+            StdCommand::new("git")
+                .args(["config", key, value])
+                .current_dir(&clone_root)
+                .output()
+                .unwrap();
+        }
+
+        let output = agentjj()
+            .current_dir(&clone_root)
+            .args(["--json", "replay", "run"])
+            .arg(&script_path)
+            .assert()
+            .success();
+
+        let json: serde_json::Value = serde_json::from_slice(&output.get_output().stdout).unwrap();
+        assert_eq!(json["completed"], true);
+        assert_eq!(json["replayed"], 1);
+        assert_eq!(
+            fs::read_to_string(clone_root.join("feature.txt")).unwrap(),
+            "feature\n"
+        );
+    }
+
+    #[test]
+    fn replay_export_unknown_session_fails() {
+        let tmp = setup_jj_repo();
+
+        agentjj()
+            .current_dir(tmp.path())
+            .args(["replay", "export", "--session", "never-ran"])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("no audit records"));
+    }
+
+    #[test]
+    fn replay_run_refuses_files_outside_the_repo() {
+        let tmp = setup_jj_repo();
+        let outside = TempDir::new().unwrap();
+        let target = outside.path().join("owned.txt");
+        let script = serde_json::json!({
+            "version": 1,
+            "session": "evil",
+            "steps": [{
+                "command": "commit",
+                "args": ["commit", "-m", "x"],
+                "inputs": {"files": {
+                    target.display().to_string(): {"content": "owned\n"},
+                    "../owned.txt": {"content": "owned\n"},
+                }},
+            }],
+        });
+        let script_path = outside.path().join("replay.json");
+        fs::write(&script_path, script.to_string()).unwrap();
+
+        agentjj()
+            .current_dir(tmp.path())
+            .args(["replay", "run"])
+            .arg(&script_path)
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("writes outside the repository"));
+        assert!(!target.exists());
+        assert!(!tmp.path().parent().unwrap().join("owned.txt").exists());
+    }
+}